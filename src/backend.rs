@@ -0,0 +1,209 @@
+//! # Database Backends
+//!
+//! This module puts a small trait in front of the connection layer so the REPL and the
+//! query subcommands can run SQL against more than one kind of database. Local files use
+//! `rusqlite` directly; with the `remote` feature, libsql/Turso databases are reached by
+//! URL over HTTP.
+//!
+//! ## Features:
+//! - **`Backend` trait**: Runs a statement and returns its columns and rows, wherever
+//!   the database lives.
+//! - **Local files**: `rusqlite::Connection` implements `Backend`.
+//! - **Target detection**: `is_remote_target` tells URLs from file paths, and
+//!   `open_backend` opens either kind.
+//! - **Schema introspection**: `Backend::table_names` and `Backend::columns` describe a
+//!   database with plain SQL, so they work on any backend.
+//!
+//! The trait and its types are part of the core that builds without the `native`
+//! feature, so an application compiled to WebAssembly can implement `Backend` over its
+//! own SQLite build and reuse the rest of the core on top of it.
+
+#[cfg(feature = "native")]
+use anyhow::Context;
+use anyhow::Result;
+#[cfg(feature = "native")]
+use rusqlite::Connection;
+
+/// A single SQL value: `rusqlite`'s value type when the `native` feature is on.
+#[cfg(feature = "native")]
+pub use rusqlite::types::Value;
+
+/// A single SQL value, with the same variants as `rusqlite::types::Value`.
+///
+/// The portable core defines its own so it doesn't depend on `rusqlite`, which links
+/// against a SQLite library that a WebAssembly build doesn't have.
+#[cfg(not(feature = "native"))]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+/// A single row of values flowing through a backend, reader, or writer.
+pub type Record = Vec<Value>;
+
+/// A column of a table, as shown by `.schema` and `.describe`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnDescription {
+    pub name: String,
+    /// The declared type, or an empty string if the column has none.
+    pub declared_type: String,
+    pub not_null: bool,
+    /// The default value as SQL text, such as `'active'` or `CURRENT_TIMESTAMP`.
+    pub default_value: Option<String>,
+    pub primary_key: bool,
+}
+
+/// The result of running a single statement through a `Backend`.
+#[derive(Debug, Clone, Default)]
+pub struct QueryResult {
+    /// The column names. Empty for statements that don't return rows.
+    pub columns: Vec<String>,
+    /// The returned rows, cut off at the requested maximum.
+    pub rows: Vec<Record>,
+    /// The number of rows changed by an `INSERT`, `UPDATE`, or `DELETE`.
+    pub rows_affected: usize,
+}
+
+/// A database that SQL statements can be run against.
+pub trait Backend {
+    /// A short description of the database for messages (a file path or URL).
+    fn describe(&self) -> String;
+
+    /// Runs a single SQL statement, returning at most `max_rows` rows.
+    fn run(&self, sql: &str, max_rows: Option<usize>) -> Result<QueryResult>;
+
+    /// Returns the names of all user tables, sorted by name.
+    fn table_names(&self) -> Result<Vec<String>> {
+        let result = self.run(
+            "SELECT name FROM sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
+            None,
+        )?;
+        Ok(result
+            .rows
+            .into_iter()
+            .filter_map(|row| match row.into_iter().next() {
+                Some(Value::Text(name)) => Some(name),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Returns the columns of a table in the main database, in table order. The list is
+    /// empty if there is no such table.
+    ///
+    /// The default reads `pragma_table_info` through `run`, for backends that have no
+    /// `rusqlite::Connection`; local connections use `db::visible_columns` instead.
+    fn columns(&self, table: &str) -> Result<Vec<ColumnDescription>> {
+        let sql = format!(
+            "SELECT name, type, \"notnull\", dflt_value, pk FROM pragma_table_info('{}') ORDER BY cid",
+            table.replace('\'', "''")
+        );
+        let text = |value: &Value| match value {
+            Value::Text(text) => Some(text.clone()),
+            _ => None,
+        };
+        let flag = |value: &Value| matches!(value, Value::Integer(n) if *n != 0);
+        Ok(self
+            .run(&sql, None)?
+            .rows
+            .iter()
+            .filter(|row| row.len() == 5)
+            .map(|row| ColumnDescription {
+                name: text(&row[0]).unwrap_or_default(),
+                declared_type: text(&row[1]).unwrap_or_default(),
+                not_null: flag(&row[2]),
+                default_value: text(&row[3]),
+                primary_key: flag(&row[4]),
+            })
+            .collect())
+    }
+}
+
+#[cfg(feature = "native")]
+impl Backend for Connection {
+    fn describe(&self) -> String {
+        self.path().unwrap_or(":memory:").to_string()
+    }
+
+    fn run(&self, sql: &str, max_rows: Option<usize>) -> Result<QueryResult> {
+        let mut stmt = self
+            .prepare(sql)
+            .context("Failed to prepare SQL statement")?;
+
+        if stmt.column_count() == 0 {
+            let rows_affected = stmt
+                .execute([])
+                .context("Failed to execute non-SELECT query")?;
+            return Ok(QueryResult {
+                rows_affected,
+                ..Default::default()
+            });
+        }
+
+        let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+        let mut rows = stmt.query([]).context("Failed to execute SELECT query")?;
+        let mut result = QueryResult {
+            columns,
+            ..Default::default()
+        };
+        while let Some(row) = rows.next()? {
+            let record = (0..result.columns.len())
+                .map(|i| row.get(i))
+                .collect::<rusqlite::Result<Record>>()?;
+            result.rows.push(record);
+            if max_rows.is_some_and(|limit| result.rows.len() >= limit) {
+                break;
+            }
+        }
+        Ok(result)
+    }
+
+    fn columns(&self, table: &str) -> Result<Vec<ColumnDescription>> {
+        Ok(crate::db::visible_columns(self, Some("main"), table)?
+            .into_iter()
+            .map(ColumnDescription::from)
+            .collect())
+    }
+}
+
+/// Returns true if a database target is a URL rather than a local file path.
+pub fn is_remote_target(target: &str) -> bool {
+    ["libsql://", "http://", "https://"]
+        .iter()
+        .any(|scheme| target.starts_with(scheme))
+}
+
+/// Opens a local database file or, with the `remote` feature, a remote database URL.
+///
+/// Remote databases read their auth token from the `authToken` URL parameter or the
+/// `LIBSQL_AUTH_TOKEN` / `TURSO_AUTH_TOKEN` environment variables.
+///
+/// # Arguments
+///
+/// * `target` - A database file path or a `libsql://`, `https://`, or `http://` URL.
+///
+/// # Returns
+///
+/// A `Result` containing the opened backend, or an `Err` if the database cannot be
+/// opened or remote support is not compiled in.
+#[cfg(feature = "native")]
+pub fn open_backend(target: &str) -> Result<Box<dyn Backend>> {
+    if is_remote_target(target) {
+        #[cfg(feature = "remote")]
+        return Ok(Box::new(crate::remote::RemoteBackend::connect(target)?));
+        #[cfg(not(feature = "remote"))]
+        anyhow::bail!(
+            "'{}' is a remote database URL, but vapor-cli was built without the `remote` feature",
+            target
+        );
+    }
+
+    let conn = crate::db::ConnectionOptions::current()
+        .open(target)
+        .with_context(|| format!("Failed to open database '{}'", target))?;
+    Ok(Box::new(conn))
+}
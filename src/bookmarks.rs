@@ -156,7 +156,14 @@ impl BookmarkManager {
         self.bookmarks.get(name)
     }
 
-        /// Lists all saved bookmarks in a formatted table.
+    /// Returns the names of all bookmarks, sorted alphabetically.
+    pub fn bookmark_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.bookmarks.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Lists all saved bookmarks in a formatted table.
     ///
     /// This function prints a user-friendly table of all bookmarks to the console, including
     /// their name, description, timestamps, and a preview of the query.
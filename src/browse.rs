@@ -0,0 +1,404 @@
+//! # Table Browser
+//!
+//! This module is a quick way to explore an unfamiliar database: pick a table from a list
+//! that narrows as you type, and see its first rows along with a summary of each column.
+//! It backs the REPL's `.browse` command.
+//!
+//! ## Features:
+//! - **Fuzzy filtering**: Table names match when the typed letters appear in order, so
+//!   `ordit` finds `order_items`. The best matches are shown after the cursor while typing,
+//!   and Tab completes them.
+//! - **Quick picks**: A table can be opened by its number in the last list shown, by its
+//!   name, or by any filter that leaves only one table.
+//! - **Column stats**: Each column's declared type, null count, distinct count, and
+//!   minimum and maximum values are shown under the rows. Large tables are summarized
+//!   from a sample of their first rows.
+
+use anyhow::Result;
+use colored::Colorize;
+use rusqlite::types::Value;
+use rusqlite::Connection;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Editor, Helper};
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex};
+
+use crate::db::{get_all_table_names, split_table_name, visible_columns};
+use crate::display::{execute_sql_with_last, print_records, LastQuery, QueryOptions};
+use crate::formats::{quote_identifier, Record};
+
+/// The number of rows shown for a picked table.
+const PAGE_ROWS: usize = 20;
+
+/// Tables with more rows than this are summarized from their first `STATS_SAMPLE_ROWS`.
+const STATS_SAMPLE_ROWS: usize = 100_000;
+
+/// The longest text shown as a column's minimum or maximum.
+const MAX_STAT_VALUE_LEN: usize = 40;
+
+/// A summary of the values in one column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnStats {
+    pub name: String,
+    /// The declared type, or an empty string if the column has none.
+    pub declared_type: String,
+    pub nulls: i64,
+    pub distinct: i64,
+    pub min: Value,
+    pub max: Value,
+}
+
+/// Scores how well a filter matches a name, or `None` if it doesn't match.
+///
+/// The filter's characters must appear in the name in order, ignoring case. Runs of
+/// consecutive characters and characters at the start of a word score higher.
+///
+/// # Arguments
+///
+/// * `filter` - What has been typed.
+/// * `name` - A table name.
+///
+/// # Returns
+///
+/// The score, higher for better matches, or `None` if the name doesn't match.
+pub fn fuzzy_score(filter: &str, name: &str) -> Option<i64> {
+    let name: Vec<char> = name.chars().flat_map(char::to_lowercase).collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+    for wanted in filter.chars().flat_map(char::to_lowercase) {
+        let found = position + name[position..].iter().position(|&c| c == wanted)?;
+        score += 1;
+        if previous.is_some_and(|previous| previous + 1 == found) {
+            score += 5;
+        }
+        if found == 0 || matches!(name[found - 1], '_' | '.' | ' ' | '-') {
+            score += 3;
+        }
+        previous = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
+
+/// Filters table names with `fuzzy_score`, best matches first.
+///
+/// # Arguments
+///
+/// * `tables` - The table names.
+/// * `filter` - What has been typed.
+///
+/// # Returns
+///
+/// The matching names, ordered by score, then by length, then by name.
+pub fn filter_tables(tables: &[String], filter: &str) -> Vec<String> {
+    let mut matches: Vec<(i64, &String)> = tables
+        .iter()
+        .filter_map(|name| Some((fuzzy_score(filter, name)?, name)))
+        .collect();
+    matches.sort_by(|a, b| {
+        b.0.cmp(&a.0)
+            .then(a.1.len().cmp(&b.1.len()))
+            .then(a.1.cmp(b.1))
+    });
+    matches.into_iter().map(|(_, name)| name.clone()).collect()
+}
+
+/// Summarizes each column of a table.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `table` - The table, qualified with an attached database's alias if needed.
+/// * `sample_rows` - Only the first this many rows are summarized, if given.
+///
+/// # Returns
+///
+/// A `Result` containing a summary per column, or an `Err` if the table doesn't exist.
+pub fn column_stats(
+    conn: &Connection,
+    table: &str,
+    sample_rows: Option<usize>,
+) -> Result<Vec<ColumnStats>> {
+    let (schema, table_name) = split_table_name(conn, table)?;
+    let columns: Vec<(String, String)> = visible_columns(conn, Some(&schema), &table_name)?
+        .into_iter()
+        .map(|column| (column.name, column.decl_type))
+        .collect();
+    if columns.is_empty() {
+        anyhow::bail!("Table '{}' does not exist", table);
+    }
+
+    let aggregates = columns
+        .iter()
+        .map(|(name, _)| {
+            let column = quote_identifier(name);
+            format!(
+                "COUNT(*) - COUNT({0}), COUNT(DISTINCT {0}), MIN({0}), MAX({0})",
+                column
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let limit = sample_rows.map_or(-1, |rows| rows as i64);
+    let sql = format!(
+        "SELECT {} FROM (SELECT * FROM {}.{} LIMIT {})",
+        aggregates,
+        quote_identifier(&schema),
+        quote_identifier(&table_name),
+        limit
+    );
+    conn.query_row(&sql, [], |row| {
+        columns
+            .iter()
+            .enumerate()
+            .map(|(i, (name, declared_type))| {
+                Ok(ColumnStats {
+                    name: name.clone(),
+                    declared_type: declared_type.clone(),
+                    nulls: row.get(i * 4)?,
+                    distinct: row.get(i * 4 + 1)?,
+                    min: row.get(i * 4 + 2)?,
+                    max: row.get(i * 4 + 3)?,
+                })
+            })
+            .collect()
+    })
+    .map_err(Into::into)
+}
+
+/// Runs the interactive table browser until the user leaves it.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `query_options` - The output settings used to show rows and column stats.
+/// * `last_select_query` - Set to the query of the last table shown, for `.export`.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` when the user leaves, or an `Err` on failure.
+pub fn browse_tables(
+    conn: &Connection,
+    query_options: &QueryOptions,
+    last_select_query: &Arc<Mutex<LastQuery>>,
+) -> Result<()> {
+    let tables = get_all_table_names(conn)?;
+    if tables.is_empty() {
+        println!("No tables to browse.");
+        return Ok(());
+    }
+    let mut rl: Editor<TablePicker, DefaultHistory> = Editor::new()?;
+    rl.set_helper(Some(TablePicker {
+        tables: tables.clone(),
+        listed: tables.clone(),
+    }));
+
+    print_choices(&tables);
+    println!("Type to filter, then enter a number or name to open a table. Press Enter on an empty line to leave.");
+    loop {
+        let line = match rl.readline("browse> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        let input = line.trim();
+        if input.is_empty() {
+            break;
+        }
+        let picker = rl.helper_mut().expect("the picker is set above");
+        let picked = match input.parse::<usize>() {
+            Ok(n) if (1..=picker.listed.len()).contains(&n) => Some(picker.listed[n - 1].clone()),
+            _ => tables
+                .iter()
+                .find(|name| name.eq_ignore_ascii_case(input))
+                .cloned(),
+        };
+        let table = match picked {
+            Some(table) => table,
+            None => {
+                let matches = filter_tables(&tables, input);
+                match matches.as_slice() {
+                    [] => {
+                        println!("No tables match '{}'.", input);
+                        continue;
+                    }
+                    [table] => table.clone(),
+                    _ => {
+                        print_choices(&matches);
+                        picker.listed = matches;
+                        continue;
+                    }
+                }
+            }
+        };
+        if let Err(e) = show_table(conn, &table, query_options, last_select_query) {
+            eprintln!("Error: {:#}", e);
+        }
+        println!("Pick another table, or press Enter on an empty line to leave.");
+    }
+    Ok(())
+}
+
+/// Prints table names numbered from 1.
+fn print_choices(tables: &[String]) {
+    let width = tables.len().to_string().len();
+    for (i, table) in tables.iter().enumerate() {
+        println!("  {:>width$}  {}", i + 1, table, width = width);
+    }
+}
+
+/// Shows a table's row count, first page of rows, and column stats.
+fn show_table(
+    conn: &Connection,
+    table: &str,
+    query_options: &QueryOptions,
+    last_select_query: &Arc<Mutex<LastQuery>>,
+) -> Result<()> {
+    let (schema, table_name) = split_table_name(conn, table)?;
+    let qualified = format!(
+        "{}.{}",
+        quote_identifier(&schema),
+        quote_identifier(&table_name)
+    );
+    let rows: i64 = conn.query_row(&format!("SELECT COUNT(*) FROM {}", qualified), [], |row| {
+        row.get(0)
+    })?;
+    println!("\n{} ({} rows)", table.bold(), rows);
+    let sql = format!("SELECT * FROM {} LIMIT {}", qualified, PAGE_ROWS);
+    execute_sql_with_last(conn, &sql, query_options, last_select_query)?;
+
+    let sampled = rows as usize > STATS_SAMPLE_ROWS;
+    let stats = column_stats(conn, table, sampled.then_some(STATS_SAMPLE_ROWS))?;
+    let columns = ["column", "type", "nulls", "distinct", "min", "max"].map(String::from);
+    let records: Vec<Record> = stats
+        .into_iter()
+        .map(|stat| {
+            vec![
+                Value::Text(stat.name),
+                Value::Text(stat.declared_type),
+                Value::Integer(stat.nulls),
+                Value::Integer(stat.distinct),
+                shorten(stat.min),
+                shorten(stat.max),
+            ]
+        })
+        .collect();
+    println!("\nColumns:");
+    print_records(&query_options.format, &columns, &records)?;
+    if sampled {
+        println!(
+            "Column stats are from the first {} rows.",
+            STATS_SAMPLE_ROWS
+        );
+    }
+    Ok(())
+}
+
+/// Cuts long text short so the stats stay readable.
+fn shorten(value: Value) -> Value {
+    match value {
+        Value::Text(text) if text.chars().count() > MAX_STAT_VALUE_LEN => {
+            let cut: String = text.chars().take(MAX_STAT_VALUE_LEN - 3).collect();
+            Value::Text(format!("{}...", cut))
+        }
+        value => value,
+    }
+}
+
+/// The `rustyline` helper for the browser prompt, which hints and completes table names.
+struct TablePicker {
+    tables: Vec<String>,
+    /// The tables in the last list printed, which numbers refer to.
+    listed: Vec<String>,
+}
+
+impl Completer for TablePicker {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        _pos: usize,
+        _ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok((0, filter_tables(&self.tables, line.trim())))
+    }
+}
+
+impl Hinter for TablePicker {
+    type Hint = String;
+
+    fn hint(&self, line: &str, pos: usize, _ctx: &rustyline::Context<'_>) -> Option<String> {
+        let input = line.trim();
+        if input.is_empty() || pos < line.len() {
+            return None;
+        }
+        if let Ok(n) = input.parse::<usize>() {
+            return self
+                .listed
+                .get(n.checked_sub(1)?)
+                .map(|table| format!("  -> {}", table));
+        }
+        let matches = filter_tables(&self.tables, input);
+        let hint = match matches.len() {
+            0 => "no matching tables".to_string(),
+            1..=3 => matches.join(", "),
+            n => format!("{}, ... ({} tables)", matches[..3].join(", "), n),
+        };
+        Some(format!("  -> {}", hint))
+    }
+}
+
+impl Highlighter for TablePicker {
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Cow::Owned(hint.dimmed().to_string())
+    }
+}
+
+impl Validator for TablePicker {}
+
+impl Helper for TablePicker {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_and_column_stats() {
+        let tables: Vec<String> = ["orders", "order_items", "customers", "archive.old_orders"]
+            .map(String::from)
+            .to_vec();
+        assert_eq!(filter_tables(&tables, "ordit"), ["order_items"]);
+        assert_eq!(
+            filter_tables(&tables, "ORD"),
+            ["orders", "order_items", "archive.old_orders"]
+        );
+        assert_eq!(filter_tables(&tables, "cst"), ["customers"]);
+        assert!(filter_tables(&tables, "xyz").is_empty());
+
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT, price REAL);
+             INSERT INTO items (name, price) VALUES ('pen', 1.5), ('ink', NULL), ('pen', 3.0);",
+        )
+        .unwrap();
+        let stats = column_stats(&conn, "items", None).unwrap();
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats[1].name, "name");
+        assert_eq!(stats[1].declared_type, "TEXT");
+        assert_eq!(stats[1].distinct, 2);
+        assert_eq!(stats[1].min, Value::Text("ink".to_string()));
+        assert_eq!(stats[2].nulls, 1);
+        assert_eq!(stats[2].max, Value::Real(3.0));
+
+        let sampled = column_stats(&conn, "items", Some(1)).unwrap();
+        assert_eq!(sampled[0].max, Value::Integer(1));
+        assert!(column_stats(&conn, "missing", None).is_err());
+    }
+}
@@ -0,0 +1,231 @@
+//! # Cancellation
+//!
+//! This module lets an application embedding vapor-cli stop a long-running operation,
+//! such as a query, export, import, or `populate`, from another thread. A
+//! `CancellationToken` is handed to `VaporDB::run_cancellable`, and calling `cancel` on a
+//! clone of it aborts the operation at the next row.
+//!
+//! ## Features:
+//! - **SQLite interrupts**: Cancelling interrupts the statements running on the
+//!   operation's connections through `rusqlite::InterruptHandle`, so even a single slow
+//!   query stops promptly.
+//! - **Row loops**: Imports and `populate` also check the token between rows, since
+//!   SQLite only interrupts statements that are running at the time.
+//! - **Partial progress**: A cancelled operation fails with a `Cancelled` error holding
+//!   the rows completed, such as the rows already written to an export file. Imports and
+//!   `populate` roll back the rows they hadn't committed.
+
+use anyhow::Result;
+use rusqlite::{Connection, InterruptHandle};
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// The error an operation fails with when it is cancelled.
+///
+/// Callers can tell cancellation from other failures with
+/// `error.downcast_ref::<Cancelled>()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cancelled {
+    /// The rows completed before the operation stopped.
+    pub completed: u64,
+}
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Operation cancelled after {} rows", self.completed)
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+#[derive(Default)]
+struct TokenState {
+    cancelled: AtomicBool,
+    completed: AtomicU64,
+    /// The connections to interrupt, most recently registered last.
+    interrupts: Mutex<Vec<InterruptHandle>>,
+}
+
+/// A handle for cancelling an operation from another thread.
+///
+/// Clones share the same state, so one clone can be moved to the thread that cancels
+/// while another runs the operation. A cancelled token stays cancelled; use a new token
+/// for each operation.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    state: Arc<TokenState>,
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<CancellationToken>> = const { RefCell::new(None) };
+}
+
+impl CancellationToken {
+    /// Creates a token that hasn't been cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the operation running with this token, interrupting its connections.
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::SeqCst);
+        for interrupt in self.state.interrupts.lock().unwrap().iter() {
+            interrupt.interrupt();
+        }
+    }
+
+    /// Whether `cancel` has been called.
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// The rows completed so far by the operation running with this token.
+    pub fn completed(&self) -> u64 {
+        self.state.completed.load(Ordering::Relaxed)
+    }
+
+    /// Runs an operation that can be cancelled with this token.
+    ///
+    /// # Arguments
+    ///
+    /// * `interrupt` - The interrupt handle of the connection the operation uses.
+    /// * `operation` - The operation to run on this thread.
+    ///
+    /// # Returns
+    ///
+    /// What the operation returns, or a `Cancelled` error if the token was cancelled
+    /// before or while it ran.
+    pub fn run<T>(
+        &self,
+        interrupt: InterruptHandle,
+        operation: impl FnOnce() -> Result<T>,
+    ) -> Result<T> {
+        self.state.completed.store(0, Ordering::Relaxed);
+        let previous = CURRENT.with(|current| current.replace(Some(self.clone())));
+        let result = {
+            let _interrupt = self.register(interrupt);
+            if self.is_cancelled() {
+                Err(Cancelled { completed: 0 }.into())
+            } else {
+                operation()
+            }
+        };
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+
+        match result {
+            // An interrupted statement fails with a SQLite error, so report it as cancelled
+            Err(e) if self.is_cancelled() && e.downcast_ref::<Cancelled>().is_none() => {
+                Err(Cancelled {
+                    completed: self.completed(),
+                }
+                .into())
+            }
+            result => result,
+        }
+    }
+
+    /// Records the rows completed by the running operation.
+    pub(crate) fn record_progress(&self, completed: u64) {
+        self.state.completed.store(completed, Ordering::Relaxed);
+    }
+
+    fn register(&self, interrupt: InterruptHandle) -> InterruptGuard {
+        self.state.interrupts.lock().unwrap().push(interrupt);
+        InterruptGuard {
+            token: self.clone(),
+        }
+    }
+}
+
+/// Unregisters a connection from a token when dropped.
+pub(crate) struct InterruptGuard {
+    token: CancellationToken,
+}
+
+impl Drop for InterruptGuard {
+    fn drop(&mut self) {
+        // Guards are dropped in the reverse order they were created
+        self.token.state.interrupts.lock().unwrap().pop();
+    }
+}
+
+/// The token of the cancellable operation running on this thread, if any.
+pub(crate) fn current_token() -> Option<CancellationToken> {
+    CURRENT.with(|current| current.borrow().clone())
+}
+
+/// Interrupts a connection the operation opened itself when the operation is cancelled.
+///
+/// # Arguments
+///
+/// * `conn` - The connection, which must stay open while the guard is held.
+///
+/// # Returns
+///
+/// A guard to hold while the connection is in use, or `None` if no cancellable
+/// operation is running on this thread.
+pub(crate) fn interrupt_on_cancel(conn: &Connection) -> Option<InterruptGuard> {
+    let token = current_token()?;
+    Some(token.register(conn.get_interrupt_handle()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::ProgressTracker;
+    use std::time::Duration;
+
+    #[test]
+    fn test_cancel_operations() {
+        let conn = Connection::open_in_memory().unwrap();
+        let endless = "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c) SELECT COUNT(*) FROM c";
+
+        // A running query is interrupted from another thread
+        let token = CancellationToken::new();
+        let canceller = token.clone();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            canceller.cancel();
+        });
+        let result = token.run(conn.get_interrupt_handle(), || {
+            Ok(conn.query_row(endless, [], |row| row.get::<_, i64>(0))?)
+        });
+        handle.join().unwrap();
+        assert!(result.unwrap_err().downcast_ref::<Cancelled>().is_some());
+        assert!(current_token().is_none());
+
+        // Row loops stop at the next row with the rows done so far
+        let token = CancellationToken::new();
+        let result: Result<()> = token.run(conn.get_interrupt_handle(), || {
+            let mut progress = ProgressTracker::new("cancel-test", None);
+            progress.update(5);
+            progress.check_cancelled()?;
+            token.cancel();
+            progress.update(6);
+            progress.check_cancelled()?;
+            unreachable!()
+        });
+        let error = result.unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<Cancelled>(),
+            Some(&Cancelled { completed: 6 })
+        );
+
+        // A token cancelled up front doesn't run the operation, and others are unaffected
+        let result = token.run(conn.get_interrupt_handle(), || Ok(1));
+        assert!(result.is_err());
+        let token = CancellationToken::new();
+        assert_eq!(
+            token
+                .run(conn.get_interrupt_handle(), || Ok(conn.query_row(
+                    "SELECT 2",
+                    [],
+                    |row| row.get::<_, i64>(0)
+                )?))
+                .unwrap(),
+            2
+        );
+    }
+}
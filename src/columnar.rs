@@ -0,0 +1,639 @@
+//! # Columnar Conversion Layer
+//!
+//! This module converts between SQLite rows and Apache Arrow record batches. It is the
+//! shared foundation for columnar interchange formats such as Arrow IPC (Feather), so
+//! result sets can be handed to Polars or pandas without a CSV round trip.
+//!
+//! ## Features:
+//! - **Rows to Batches**: `query_to_record_batches` streams a query's results as
+//!   fixed-size `RecordBatch`es with a schema derived from the declared column types.
+//! - **Batches to Rows**: `record_batch_rows` turns a batch back into SQLite values,
+//!   and `sqlite_type_for` picks a column type when creating tables from a schema.
+//! - **Arrow IPC**: `ArrowFormat` registers Arrow IPC files with the format registry,
+//!   so `.export`/`.import` and the `export`/`import` commands handle them like any
+//!   other format. `export_to_arrow` and `import_arrow_to_table` are shortcuts for it.
+//!
+//! This module requires the `arrow` feature.
+
+use anyhow::{Context, Result};
+use arrow_array::builder::{BinaryBuilder, Float64Builder, Int64Builder, StringBuilder};
+use arrow_array::cast::AsArray;
+use arrow_array::types::{
+    Float32Type, Float64Type, Int16Type, Int32Type, Int64Type, Int8Type, UInt16Type, UInt32Type,
+    UInt64Type, UInt8Type,
+};
+use arrow_array::{Array, ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use rusqlite::types::Value;
+use rusqlite::Connection;
+use std::io::{BufRead, BufReader, Read};
+use std::sync::Arc;
+
+use crate::compression::OutputWriter;
+use crate::formats::{
+    export_query, import_file_with_options, FileImportOptions, Format, Record, RecordReader,
+    RecordWriter,
+};
+
+/// The default number of rows per record batch.
+pub const DEFAULT_BATCH_SIZE: usize = 65_536;
+
+/// Maps a declared SQLite column type to an Arrow type using SQLite's affinity rules.
+fn arrow_type_for_declared(declared: &str) -> DataType {
+    let declared = declared.to_uppercase();
+    if declared.contains("INT") {
+        DataType::Int64
+    } else if ["CHAR", "CLOB", "TEXT"]
+        .iter()
+        .any(|t| declared.contains(t))
+    {
+        DataType::Utf8
+    } else if declared.contains("BLOB") {
+        DataType::Binary
+    } else if ["REAL", "FLOA", "DOUB", "NUMERIC", "DECIMAL"]
+        .iter()
+        .any(|t| declared.contains(t))
+    {
+        DataType::Float64
+    } else {
+        DataType::Utf8
+    }
+}
+
+/// Returns the SQLite column type used to store an Arrow type.
+pub fn sqlite_type_for(data_type: &DataType) -> &'static str {
+    match data_type {
+        DataType::Boolean
+        | DataType::Int8
+        | DataType::Int16
+        | DataType::Int32
+        | DataType::Int64
+        | DataType::UInt8
+        | DataType::UInt16
+        | DataType::UInt32
+        | DataType::UInt64 => "INTEGER",
+        DataType::Float16 | DataType::Float32 | DataType::Float64 => "REAL",
+        DataType::Binary | DataType::LargeBinary | DataType::FixedSizeBinary(_) => "BLOB",
+        _ => "TEXT",
+    }
+}
+
+/// An Arrow array builder for one result column.
+enum ColumnBuilder {
+    Int64(Int64Builder),
+    Float64(Float64Builder),
+    Utf8(StringBuilder),
+    Binary(BinaryBuilder),
+}
+
+impl ColumnBuilder {
+    fn new(data_type: &DataType) -> Self {
+        match data_type {
+            DataType::Int64 => ColumnBuilder::Int64(Int64Builder::new()),
+            DataType::Float64 => ColumnBuilder::Float64(Float64Builder::new()),
+            DataType::Binary => ColumnBuilder::Binary(BinaryBuilder::new()),
+            _ => ColumnBuilder::Utf8(StringBuilder::new()),
+        }
+    }
+
+    /// Appends a value, converting it to the column type since SQLite does not enforce types.
+    fn append(&mut self, value: Value) -> std::result::Result<(), String> {
+        match (self, value) {
+            (ColumnBuilder::Int64(b), Value::Null) => b.append_null(),
+            (ColumnBuilder::Float64(b), Value::Null) => b.append_null(),
+            (ColumnBuilder::Utf8(b), Value::Null) => b.append_null(),
+            (ColumnBuilder::Binary(b), Value::Null) => b.append_null(),
+            (ColumnBuilder::Int64(b), Value::Integer(i)) => b.append_value(i),
+            (ColumnBuilder::Int64(b), Value::Real(f)) if f.fract() == 0.0 => {
+                b.append_value(f as i64)
+            }
+            (ColumnBuilder::Int64(b), Value::Text(t)) => b.append_value(
+                t.trim()
+                    .parse()
+                    .map_err(|_| format!("'{}' is not an integer", t))?,
+            ),
+            (ColumnBuilder::Float64(b), Value::Integer(i)) => b.append_value(i as f64),
+            (ColumnBuilder::Float64(b), Value::Real(f)) => b.append_value(f),
+            (ColumnBuilder::Float64(b), Value::Text(t)) => b.append_value(
+                t.trim()
+                    .parse()
+                    .map_err(|_| format!("'{}' is not a number", t))?,
+            ),
+            (ColumnBuilder::Utf8(b), Value::Integer(i)) => b.append_value(i.to_string()),
+            (ColumnBuilder::Utf8(b), Value::Real(f)) => b.append_value(f.to_string()),
+            (ColumnBuilder::Utf8(b), Value::Text(t)) => b.append_value(t),
+            (ColumnBuilder::Utf8(b), Value::Blob(bytes)) => b.append_value(
+                String::from_utf8(bytes).map_err(|_| "blob is not valid UTF-8".to_string())?,
+            ),
+            (ColumnBuilder::Binary(b), Value::Blob(bytes)) => b.append_value(bytes),
+            (ColumnBuilder::Binary(b), Value::Text(t)) => b.append_value(t.as_bytes()),
+            (_, value) => {
+                return Err(format!(
+                    "{:?} value does not match the column type",
+                    value.data_type()
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            ColumnBuilder::Int64(b) => Arc::new(b.finish()),
+            ColumnBuilder::Float64(b) => Arc::new(b.finish()),
+            ColumnBuilder::Utf8(b) => Arc::new(b.finish()),
+            ColumnBuilder::Binary(b) => Arc::new(b.finish()),
+        }
+    }
+}
+
+/// Accumulates rows into Arrow record batches with a fixed schema.
+struct BatchBuilder {
+    schema: SchemaRef,
+    builders: Vec<ColumnBuilder>,
+    /// The rows appended so far, across all batches.
+    total: usize,
+    /// The rows in the batch being built.
+    in_batch: usize,
+}
+
+impl BatchBuilder {
+    /// Derives the schema from the declared column types, falling back to the type of
+    /// the first row's value (or `Utf8`) for columns without one.
+    fn new(names: &[String], declared: &[Option<DataType>], first: Option<&[Value]>) -> Self {
+        let fields: Vec<Field> = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let data_type = declared.get(i).cloned().flatten().unwrap_or_else(|| {
+                    match first.and_then(|r| r.get(i)) {
+                        Some(Value::Integer(_)) => DataType::Int64,
+                        Some(Value::Real(_)) => DataType::Float64,
+                        Some(Value::Blob(_)) => DataType::Binary,
+                        _ => DataType::Utf8,
+                    }
+                });
+                Field::new(name, data_type, true)
+            })
+            .collect();
+        let schema: SchemaRef = Arc::new(Schema::new(fields));
+        let builders = schema
+            .fields()
+            .iter()
+            .map(|field| ColumnBuilder::new(field.data_type()))
+            .collect();
+        Self {
+            schema,
+            builders,
+            total: 0,
+            in_batch: 0,
+        }
+    }
+
+    /// Appends a row, converting each value to its column's type.
+    fn append(&mut self, values: impl IntoIterator<Item = Value>) -> Result<()> {
+        for ((builder, value), field) in self
+            .builders
+            .iter_mut()
+            .zip(values)
+            .zip(self.schema.fields())
+        {
+            builder.append(value).map_err(|e| {
+                anyhow::anyhow!("Row {}, column '{}': {}", self.total + 1, field.name(), e)
+            })?;
+        }
+        self.total += 1;
+        self.in_batch += 1;
+        Ok(())
+    }
+
+    /// Finishes the batch being built and starts a new one.
+    fn finish_batch(&mut self) -> Result<RecordBatch> {
+        let columns = self
+            .builders
+            .iter_mut()
+            .map(ColumnBuilder::finish)
+            .collect();
+        self.in_batch = 0;
+        Ok(RecordBatch::try_new(self.schema.clone(), columns)?)
+    }
+}
+
+/// Runs a query and passes its results to `f` as Arrow record batches.
+///
+/// The schema is derived from the declared types of the result columns: integer columns
+/// become `Int64`, real columns `Float64`, blob columns `Binary`, and everything else
+/// `Utf8`. Columns without a declared type (such as expressions) take their type from the
+/// first row. All fields are nullable. At least one batch is always produced, so an empty
+/// result still carries its schema.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `query` - The SQL query to run.
+/// * `batch_size` - The maximum number of rows per batch.
+/// * `f` - A callback that receives each batch in order.
+///
+/// # Returns
+///
+/// A `Result` containing the total number of rows converted.
+pub fn query_to_record_batches<F>(
+    conn: &Connection,
+    query: &str,
+    batch_size: usize,
+    mut f: F,
+) -> Result<usize>
+where
+    F: FnMut(RecordBatch) -> Result<()>,
+{
+    let batch_size = batch_size.max(1);
+    let mut stmt = conn
+        .prepare(query)
+        .with_context(|| format!("Failed to prepare query: {}", query))?;
+    let declared: Vec<Option<DataType>> = stmt
+        .columns()
+        .iter()
+        .map(|c| c.decl_type().map(arrow_type_for_declared))
+        .collect();
+    let names: Vec<String> = stmt.column_names().iter().map(|s| s.to_string()).collect();
+    let column_count = names.len();
+
+    let mut rows = stmt
+        .query([])
+        .with_context(|| format!("Failed to execute query: {}", query))?;
+    let read_row = |row: &rusqlite::Row| -> Result<Vec<Value>> {
+        (0..column_count)
+            .map(|i| row.get::<_, Value>(i).map_err(Into::into))
+            .collect()
+    };
+    let mut pending = match rows.next()? {
+        Some(row) => Some(read_row(row)?),
+        None => None,
+    };
+
+    let mut batches = BatchBuilder::new(&names, &declared, pending.as_deref());
+    let mut emitted = false;
+
+    while let Some(values) = pending.take() {
+        batches.append(values)?;
+        if batches.in_batch == batch_size {
+            f(batches.finish_batch()?)?;
+            emitted = true;
+        }
+        if let Some(row) = rows.next()? {
+            pending = Some(read_row(row)?);
+        }
+    }
+
+    if batches.in_batch > 0 || !emitted {
+        f(batches.finish_batch()?)?;
+    }
+    Ok(batches.total)
+}
+
+/// Converts a record batch into rows of SQLite values.
+///
+/// Integer, floating-point, boolean, string, and binary columns map to the matching
+/// SQLite types. Any other Arrow type (dates, timestamps, decimals, ...) is stored as its
+/// text representation.
+///
+/// # Arguments
+///
+/// * `batch` - The record batch to convert.
+///
+/// # Returns
+///
+/// A `Result` containing one `Vec<Value>` per row.
+pub fn record_batch_rows(batch: &RecordBatch) -> Result<Vec<Vec<Value>>> {
+    let mut rows = vec![Vec::with_capacity(batch.num_columns()); batch.num_rows()];
+    for column in batch.columns() {
+        for (i, row) in rows.iter_mut().enumerate() {
+            row.push(array_value(column, i)?);
+        }
+    }
+    Ok(rows)
+}
+
+/// Reads a single value from an Arrow array as a SQLite value.
+fn array_value(array: &ArrayRef, i: usize) -> Result<Value> {
+    if array.is_null(i) {
+        return Ok(Value::Null);
+    }
+    Ok(match array.data_type() {
+        DataType::Boolean => Value::Integer(array.as_boolean().value(i) as i64),
+        DataType::Int8 => Value::Integer(array.as_primitive::<Int8Type>().value(i) as i64),
+        DataType::Int16 => Value::Integer(array.as_primitive::<Int16Type>().value(i) as i64),
+        DataType::Int32 => Value::Integer(array.as_primitive::<Int32Type>().value(i) as i64),
+        DataType::Int64 => Value::Integer(array.as_primitive::<Int64Type>().value(i)),
+        DataType::UInt8 => Value::Integer(array.as_primitive::<UInt8Type>().value(i) as i64),
+        DataType::UInt16 => Value::Integer(array.as_primitive::<UInt16Type>().value(i) as i64),
+        DataType::UInt32 => Value::Integer(array.as_primitive::<UInt32Type>().value(i) as i64),
+        DataType::UInt64 => {
+            let value = array.as_primitive::<UInt64Type>().value(i);
+            i64::try_from(value)
+                .map(Value::Integer)
+                .unwrap_or(Value::Real(value as f64))
+        }
+        DataType::Float32 => Value::Real(array.as_primitive::<Float32Type>().value(i) as f64),
+        DataType::Float64 => Value::Real(array.as_primitive::<Float64Type>().value(i)),
+        DataType::Utf8 => Value::Text(array.as_string::<i32>().value(i).to_string()),
+        DataType::LargeUtf8 => Value::Text(array.as_string::<i64>().value(i).to_string()),
+        DataType::Binary => Value::Blob(array.as_binary::<i32>().value(i).to_vec()),
+        DataType::LargeBinary => Value::Blob(array.as_binary::<i64>().value(i).to_vec()),
+        _ => Value::Text(
+            arrow_cast::display::array_value_to_string(array, i)
+                .context("Failed to convert Arrow value")?,
+        ),
+    })
+}
+
+/// Exports the results of a SQL query to an Arrow IPC (Feather v2) file.
+///
+/// This is `formats::export_query` with the `arrow` format.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `query` - The `SELECT` SQL query whose results will be exported.
+/// * `filename` - The path to the output file (e.g. `data.arrow` or `data.feather`).
+///
+/// # Returns
+///
+/// A `Result` containing the number of rows written.
+pub fn export_to_arrow(conn: &Connection, query: &str, filename: &str) -> Result<usize> {
+    export_query(conn, query, filename, Some("arrow"))
+}
+
+/// The Arrow IPC file format, also known as Feather v2.
+///
+/// Exports derive the schema like `query_to_record_batches` and write batches of
+/// `DEFAULT_BATCH_SIZE` rows. Imports read the record batches in order, so they also
+/// work on compressed files and standard input, and report SQLite column types derived
+/// from the Arrow schema for `--create-table`. This format requires the `arrow` feature.
+pub struct ArrowFormat;
+
+/// The leading magic bytes of an Arrow IPC file.
+const ARROW_FILE_MAGIC: &[u8] = b"ARROW1";
+
+struct ArrowRecordWriter {
+    output: Option<OutputWriter>,
+    names: Vec<String>,
+    declared: Vec<Option<DataType>>,
+    batches: Option<BatchBuilder>,
+    writer: Option<arrow_ipc::writer::FileWriter<OutputWriter>>,
+}
+
+impl ArrowRecordWriter {
+    /// Derives the schema from the declared types and the first row, and starts the file.
+    fn start(&mut self, first: Option<&[Value]>) -> Result<()> {
+        let batches = BatchBuilder::new(&self.names, &self.declared, first);
+        let output = self
+            .output
+            .take()
+            .context("The Arrow file was already started")?;
+        self.writer = Some(
+            arrow_ipc::writer::FileWriter::try_new(output, &batches.schema)
+                .context("Failed to start Arrow IPC file")?,
+        );
+        self.batches = Some(batches);
+        Ok(())
+    }
+
+    /// Writes the rows built so far as one record batch.
+    fn write_batch(&mut self) -> Result<()> {
+        if let (Some(batches), Some(writer)) = (self.batches.as_mut(), self.writer.as_mut()) {
+            writer.write(&batches.finish_batch()?)?;
+        }
+        Ok(())
+    }
+}
+
+impl RecordWriter for ArrowRecordWriter {
+    fn set_declared_types(&mut self, declared: &[Option<String>]) {
+        self.declared = declared
+            .iter()
+            .map(|d| d.as_deref().map(arrow_type_for_declared))
+            .collect();
+    }
+
+    fn write_header(&mut self, columns: &[String]) -> Result<()> {
+        self.names = columns.to_vec();
+        Ok(())
+    }
+
+    fn write_record(&mut self, record: &[Value]) -> Result<()> {
+        if self.batches.is_none() {
+            self.start(Some(record))?;
+        }
+        let full = match self.batches.as_mut() {
+            Some(batches) => {
+                batches.append(record.iter().cloned())?;
+                batches.in_batch == DEFAULT_BATCH_SIZE
+            }
+            None => false,
+        };
+        if full {
+            self.write_batch()?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        // Always write at least one batch, so an empty result still carries its schema
+        let empty = self.batches.is_none();
+        if empty {
+            self.start(None)?;
+        }
+        if empty || self.batches.as_ref().is_some_and(|b| b.in_batch > 0) {
+            self.write_batch()?;
+        }
+        self.writer
+            .take()
+            .context("The Arrow file was never started")?
+            .into_inner()
+            .context("Failed to finalize Arrow file")?
+            .finish()
+    }
+}
+
+/// Reads rows from an Arrow IPC file, one record batch at a time.
+struct ArrowRecordReader {
+    batches: arrow_ipc::reader::StreamReader<BufReader<Box<dyn Read>>>,
+    columns: Vec<String>,
+    types: Vec<&'static str>,
+    pending: std::vec::IntoIter<Record>,
+}
+
+impl RecordReader for ArrowRecordReader {
+    fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    fn column_types(&self) -> Option<Vec<&'static str>> {
+        Some(self.types.clone())
+    }
+
+    fn next_record(&mut self) -> Result<Option<Record>> {
+        loop {
+            if let Some(record) = self.pending.next() {
+                return Ok(Some(record));
+            }
+            match self.batches.next() {
+                Some(batch) => {
+                    let batch = batch.context("Failed to read Arrow record batch")?;
+                    self.pending = record_batch_rows(&batch)?.into_iter();
+                }
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+impl Format for ArrowFormat {
+    fn name(&self) -> &'static str {
+        "arrow"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["arrow", "feather", "ipc"]
+    }
+
+    fn reader(&self, input: Box<dyn Read>) -> Result<Box<dyn RecordReader>> {
+        // An IPC file is the IPC stream between its magic bytes and its footer, so it can
+        // be read front to back without seeking to the footer
+        let mut input = BufReader::new(input);
+        let mut magic = [0; 6];
+        input
+            .read_exact(&mut magic)
+            .ok()
+            .filter(|_| magic == ARROW_FILE_MAGIC)
+            .context("Not a valid Arrow IPC file")?;
+        // Skip the zero padding that aligns the stream after the magic bytes
+        loop {
+            let buffer = input.fill_buf()?;
+            let padding = buffer.iter().take_while(|&&b| b == 0).count();
+            let done = padding < buffer.len() || buffer.is_empty();
+            input.consume(padding);
+            if done {
+                break;
+            }
+        }
+        let batches = arrow_ipc::reader::StreamReader::try_new(input, None)
+            .context("Not a valid Arrow IPC file")?;
+        let schema = batches.schema();
+        Ok(Box::new(ArrowRecordReader {
+            columns: schema.fields().iter().map(|f| f.name().clone()).collect(),
+            types: schema
+                .fields()
+                .iter()
+                .map(|f| sqlite_type_for(f.data_type()))
+                .collect(),
+            batches,
+            pending: Vec::new().into_iter(),
+        }))
+    }
+
+    fn writer(&self, output: OutputWriter) -> Result<Box<dyn RecordWriter>> {
+        Ok(Box::new(ArrowRecordWriter {
+            output: Some(output),
+            names: Vec::new(),
+            declared: Vec::new(),
+            batches: None,
+            writer: None,
+        }))
+    }
+}
+
+/// Imports an Arrow IPC (Feather v2) file into a table.
+///
+/// This is `formats::import_file_with_options` with the `arrow` format. Columns are
+/// mapped to table columns by name. When `create_table` is set and the table does not
+/// exist, it is created with column types derived from the Arrow schema.
+///
+/// # Arguments
+///
+/// * `conn` - A mutable reference to the active `rusqlite::Connection`.
+/// * `path` - The path to the Arrow IPC file.
+/// * `table_name` - The table to insert rows into.
+/// * `create_table` - Whether to create a missing table from the file's schema.
+///
+/// # Returns
+///
+/// A `Result` containing the number of rows imported.
+pub fn import_arrow_to_table(
+    conn: &mut Connection,
+    path: &str,
+    table_name: &str,
+    create_table: bool,
+) -> Result<usize> {
+    import_file_with_options(
+        conn,
+        path,
+        table_name,
+        Some("arrow"),
+        &FileImportOptions { create_table },
+    )
+}
+
+/// Returns `true` if a path looks like an Arrow IPC or Feather file.
+pub fn is_arrow_path(path: &str) -> bool {
+    let path = path.to_lowercase();
+    [".arrow", ".feather", ".ipc"]
+        .iter()
+        .any(|extension| path.ends_with(extension))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arrow_roundtrip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut conn = Connection::open_in_memory()?;
+        conn.execute_batch(
+            "CREATE TABLE src (id INTEGER, score REAL, name TEXT, raw BLOB);
+             INSERT INTO src VALUES (1, 1.5, 'a', x'00ff'), (2, NULL, 'b', NULL), (3, 2.0, NULL, NULL);",
+        )?;
+
+        let mut batches = 0;
+        query_to_record_batches(&conn, "SELECT *, id + 1 AS next FROM src", 2, |batch| {
+            assert_eq!(batch.schema().field(4).data_type(), &DataType::Int64);
+            batches += 1;
+            Ok(())
+        })?;
+        assert_eq!(batches, 2);
+
+        let path = dir.path().join("src.feather");
+        let path = path.to_str().unwrap();
+        assert_eq!(
+            export_to_arrow(&conn, "SELECT * FROM src ORDER BY id", path)?,
+            3
+        );
+        assert_eq!(import_arrow_to_table(&mut conn, path, "copy", true)?, 3);
+
+        let (score, raw): (Option<f64>, Vec<u8>) =
+            conn.query_row("SELECT score, raw FROM copy WHERE id = 1", [], |r| {
+                Ok((r.get(0)?, r.get(1)?))
+            })?;
+        assert_eq!(score, Some(1.5));
+        assert_eq!(raw, vec![0x00, 0xff]);
+
+        // Through the registry, compressed, with the format named by an extension
+        let gz = dir.path().join("src.feather.gz");
+        let gz = gz.to_str().unwrap();
+        assert_eq!(export_query(&conn, "SELECT * FROM src", gz, None)?, 3);
+        let options = FileImportOptions { create_table: true };
+        assert_eq!(
+            import_file_with_options(&mut conn, gz, "copy2", Some("feather"), &options)?,
+            3
+        );
+        let declared: String = conn.query_row(
+            "SELECT type FROM pragma_table_info('copy2') WHERE name = 'score'",
+            [],
+            |r| r.get(0),
+        )?;
+        assert_eq!(declared, "REAL");
+        Ok(())
+    }
+}
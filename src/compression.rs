@@ -0,0 +1,357 @@
+//! # Transparent Compression
+//!
+//! This module lets exporters write compressed files and importers read them without
+//! buffering the whole file in memory or on disk. Data is streamed through a gzip or zstd
+//! encoder/decoder as it is processed, so memory usage stays flat regardless of file size.
+//!
+//! ## Features:
+//! - **Output**: Compression is chosen either explicitly or from the file name: paths
+//!   ending in `.gz` use gzip and paths ending in `.zst` use zstd.
+//! - **Input**: gzip, zstd, and zip inputs are detected from their magic bytes, so a
+//!   misnamed file is still read correctly. Zip archives must contain a single data file
+//!   (or a single `.csv` file), which is decompressed in place without extracting it.
+//! - **Standard input**: The input path `-` reads from stdin, so data can be piped in
+//!   from other tools. gzip and zstd streams are still detected; zip archives are not.
+
+use anyhow::{Context, Result};
+use flate2::read::{DeflateDecoder, MultiGzDecoder};
+use flate2::write::GzEncoder;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// The input path that reads from standard input instead of a file.
+pub const STDIN_PATH: &str = "-";
+
+/// Supported compression algorithms for exported files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Detects the compression to use from a file name's extension.
+    pub fn from_path(path: &str) -> Self {
+        let lower = path.to_lowercase();
+        if lower.ends_with(".gz") {
+            Compression::Gzip
+        } else if lower.ends_with(".zst") {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Parses a compression name such as `gzip`, `gz`, `zstd`, `zst`, or `none`.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "none" | "off" => Ok(Compression::None),
+            "gzip" | "gz" => Ok(Compression::Gzip),
+            "zstd" | "zst" => Ok(Compression::Zstd),
+            other => anyhow::bail!(
+                "Unknown compression '{}'. Available: none, gzip, zstd",
+                other
+            ),
+        }
+    }
+
+    /// Returns the conventional file extension (without the dot) for this compression.
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            Compression::None => None,
+            Compression::Gzip => Some("gz"),
+            Compression::Zstd => Some("zst"),
+        }
+    }
+}
+
+/// Returns the path with any compression extension removed (e.g. `data.csv.gz` -> `data.csv`).
+///
+/// This is used to find the underlying data format of a compressed file.
+pub fn strip_compression_extension(path: &str) -> &str {
+    let lower = path.to_lowercase();
+    for suffix in [".gz", ".zst"] {
+        if lower.ends_with(suffix) {
+            return &path[..path.len() - suffix.len()];
+        }
+    }
+    path
+}
+
+/// A file being written by an export, possibly through a gzip or zstd encoder.
+///
+/// Once all data has been written, call `finish` to write the end of the compressed
+/// stream and flush the file. Dropping the writer instead discards any error doing so,
+/// and a zstd stream that is dropped unfinished is left truncated.
+pub struct OutputWriter {
+    inner: OutputInner,
+}
+
+enum OutputInner {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl OutputWriter {
+    /// Wraps an already-open file that is written without compression.
+    pub fn uncompressed(file: File) -> Self {
+        Self {
+            inner: OutputInner::Plain(BufWriter::new(file)),
+        }
+    }
+
+    /// Finishes any compressed stream and flushes all data to the file.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` which is `Ok(())` once the output is complete, or an `Err` if the
+    /// remaining data or the end of the compressed stream could not be written.
+    pub fn finish(self) -> Result<()> {
+        match self.inner {
+            OutputInner::Plain(mut file) => file.flush(),
+            OutputInner::Gzip(encoder) => encoder.finish().and_then(|mut file| file.flush()),
+            OutputInner::Zstd(encoder) => encoder.finish().and_then(|mut file| file.flush()),
+        }
+        .context("Failed to finish writing the output file")
+    }
+}
+
+impl Write for OutputWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match &mut self.inner {
+            OutputInner::Plain(file) => file.write(buf),
+            OutputInner::Gzip(encoder) => encoder.write(buf),
+            OutputInner::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.inner {
+            OutputInner::Plain(file) => file.flush(),
+            OutputInner::Gzip(encoder) => encoder.flush(),
+            OutputInner::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Creates an output file, wrapping it in a streaming encoder when requested.
+///
+/// Callers must call `OutputWriter::finish` once all data has been written, so that
+/// errors while completing a compressed stream are reported.
+///
+/// # Arguments
+///
+/// * `path` - The output file path. The file is created or truncated.
+/// * `compression` - The compression to apply, or `None` to detect it from `path`.
+///
+/// # Returns
+///
+/// A `Result` containing a writer for the (possibly compressed) output.
+pub fn create_output(path: &str, compression: Option<Compression>) -> Result<OutputWriter> {
+    let compression = compression.unwrap_or_else(|| Compression::from_path(path));
+
+    if let Some(parent) = Path::new(path).parent() {
+        if parent != Path::new("") && !parent.exists() {
+            anyhow::bail!(
+                "Directory '{}' does not exist. Create it first or use a different path.",
+                parent.display()
+            );
+        }
+    }
+
+    let file = File::create(path).with_context(|| {
+        format!(
+            "Failed to create file '{}'. Check permissions and disk space.",
+            path
+        )
+    })?;
+    let file = BufWriter::new(file);
+
+    let inner = match compression {
+        Compression::None => OutputInner::Plain(file),
+        Compression::Gzip => {
+            OutputInner::Gzip(GzEncoder::new(file, flate2::Compression::default()))
+        }
+        Compression::Zstd => OutputInner::Zstd(
+            zstd::Encoder::new(file, 0).context("Failed to initialize zstd encoder")?,
+        ),
+    };
+    Ok(OutputWriter { inner })
+}
+
+/// Opens an input file, transparently decompressing gzip, zstd, or zip content.
+///
+/// The compression is detected from the file's leading magic bytes rather than its
+/// extension. Uncompressed files are returned as a plain buffered reader.
+///
+/// # Arguments
+///
+/// * `path` - The input file path, or `-` to read from standard input.
+///
+/// # Returns
+///
+/// A `Result` containing a boxed reader that yields the decompressed content.
+pub fn open_input(path: &str) -> Result<Box<dyn Read>> {
+    let mut reader: Box<dyn BufRead> = if path == STDIN_PATH {
+        if atty::is(atty::Stream::Stdin) {
+            eprintln!("Reading from standard input; press Ctrl+D when done.");
+        }
+        Box::new(BufReader::new(std::io::stdin()))
+    } else {
+        let file = File::open(path).with_context(|| format!("File not found: {}", path))?;
+        Box::new(BufReader::new(file))
+    };
+    let magic = reader
+        .fill_buf()
+        .with_context(|| format!("Failed to read '{}'", path))?
+        .to_vec();
+
+    if magic.starts_with(GZIP_MAGIC) {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+    } else if magic.starts_with(ZSTD_MAGIC) {
+        let decoder = zstd::Decoder::with_buffer(reader)
+            .with_context(|| format!("Failed to initialize zstd decoder for '{}'", path))?;
+        Ok(Box::new(BufReader::new(decoder)))
+    } else if magic.starts_with(ZIP_MAGIC) {
+        if path == STDIN_PATH {
+            anyhow::bail!("Zip archives cannot be read from standard input");
+        }
+        open_zip_entry(path)
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Streams the single data file contained in a zip archive.
+///
+/// The entry is decoded directly from the archive file, so nothing is extracted to disk.
+fn open_zip_entry(path: &str) -> Result<Box<dyn Read>> {
+    let file = File::open(path).with_context(|| format!("File not found: {}", path))?;
+    let mut archive = zip::ZipArchive::new(BufReader::new(file))
+        .with_context(|| format!("Failed to read zip archive '{}'", path))?;
+
+    let files: Vec<(usize, String)> = (0..archive.len())
+        .filter_map(|i| {
+            let entry = archive.by_index_raw(i).ok()?;
+            (!entry.is_dir()).then(|| (i, entry.name().to_string()))
+        })
+        .collect();
+    let index = match files.as_slice() {
+        [(index, _)] => *index,
+        _ => {
+            let csv_files: Vec<&(usize, String)> = files
+                .iter()
+                .filter(|(_, name)| name.to_lowercase().ends_with(".csv"))
+                .collect();
+            match csv_files.as_slice() {
+                [(index, _)] => *index,
+                _ => anyhow::bail!(
+                    "Zip archive '{}' must contain exactly one data file, found: {}",
+                    path,
+                    files
+                        .iter()
+                        .map(|(_, name)| name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            }
+        }
+    };
+
+    let entry = archive.by_index_raw(index)?;
+    let name = entry.name().to_string();
+    let method = entry.compression();
+    let data_start = entry.data_start();
+    let compressed_size = entry.compressed_size();
+    drop(entry);
+
+    let mut file = archive.into_inner();
+    file.seek(SeekFrom::Start(data_start))?;
+    let data = file.take(compressed_size);
+
+    match method {
+        zip::CompressionMethod::Stored => Ok(Box::new(data)),
+        zip::CompressionMethod::Deflated => Ok(Box::new(BufReader::new(DeflateDecoder::new(data)))),
+        other => anyhow::bail!(
+            "Unsupported compression method {:?} for '{}' in zip archive '{}'",
+            other,
+            name,
+            path
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_detection_and_stripping() {
+        assert_eq!(Compression::from_path("out.csv.GZ"), Compression::Gzip);
+        assert_eq!(Compression::from_path("out.csv.zst"), Compression::Zstd);
+        assert_eq!(Compression::from_path("out.csv"), Compression::None);
+        assert_eq!(strip_compression_extension("out.csv.gz"), "out.csv");
+        assert_eq!(strip_compression_extension("out.json"), "out.json");
+        assert!(Compression::parse("brotli").is_err());
+    }
+
+    #[test]
+    fn test_compressed_output_roundtrip() -> Result<()> {
+        let dir = tempdir()?;
+
+        let gz_path = dir.path().join("data.txt.gz");
+        {
+            let mut out = create_output(gz_path.to_str().unwrap(), None)?;
+            out.write_all(b"hello gzip")?;
+            out.finish()?;
+        }
+        let mut text = String::new();
+        flate2::read::GzDecoder::new(File::open(&gz_path)?).read_to_string(&mut text)?;
+        assert_eq!(text, "hello gzip");
+
+        let zst_path = dir.path().join("data.txt");
+        {
+            let mut out = create_output(zst_path.to_str().unwrap(), Some(Compression::Zstd))?;
+            out.write_all(b"hello zstd")?;
+            out.finish()?;
+        }
+        let decoded = zstd::decode_all(File::open(&zst_path)?)?;
+        assert_eq!(decoded, b"hello zstd");
+
+        // Inputs are detected by content, not by name
+        for path in [&gz_path, &zst_path] {
+            let mut text = String::new();
+            open_input(path.to_str().unwrap())?.read_to_string(&mut text)?;
+            assert!(text.starts_with("hello"));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_zip_input() -> Result<()> {
+        let dir = tempdir()?;
+        let zip_path = dir.path().join("drop.zip");
+        {
+            let mut zip = zip::ZipWriter::new(File::create(&zip_path)?);
+            zip.start_file("README.txt", zip::write::SimpleFileOptions::default())?;
+            zip.write_all(b"not data")?;
+            zip.start_file("data.csv", zip::write::SimpleFileOptions::default())?;
+            zip.write_all(b"id,name\n1,alpha\n")?;
+            zip.finish()?;
+        }
+
+        let mut text = String::new();
+        open_input(zip_path.to_str().unwrap())?.read_to_string(&mut text)?;
+        assert_eq!(text, "id,name\n1,alpha\n");
+        Ok(())
+    }
+}
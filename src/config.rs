@@ -45,6 +45,64 @@ pub fn get_bookmarks_path() -> Result<PathBuf> {
     Ok(get_vapor_dir()?.join("bookmarks.json"))
 }
 
+/// Returns the full path to the command usage counts.
+///
+/// This is typically `~/.vapor/usage.json`.
+///
+/// # Returns
+///
+/// A `Result` containing the `PathBuf` for the usage file.
+pub fn get_usage_path() -> Result<PathBuf> {
+    Ok(get_vapor_dir()?.join("usage.json"))
+}
+
+/// Returns the directory holding user-defined database templates.
+///
+/// This is typically `~/.vapor/templates`. A file named `NAME.db` there can be used as
+/// `init --template NAME`.
+///
+/// # Returns
+///
+/// A `Result` containing the `PathBuf` for the templates directory.
+pub fn get_templates_dir() -> Result<PathBuf> {
+    Ok(get_vapor_dir()?.join("templates"))
+}
+
+/// Returns the full path to the database profiles file.
+///
+/// This is typically `~/.vapor/profiles.json`.
+///
+/// # Returns
+///
+/// A `Result` containing the `PathBuf` for the profiles file.
+pub fn get_profiles_path() -> Result<PathBuf> {
+    Ok(get_vapor_dir()?.join("profiles.json"))
+}
+
+/// Returns the full path to the session hooks file.
+///
+/// This is typically `~/.vapor/hooks.json`. It lists SQL run at the start of a session,
+/// before each query, and at exit.
+///
+/// # Returns
+///
+/// A `Result` containing the `PathBuf` for the hooks file.
+pub fn get_hooks_path() -> Result<PathBuf> {
+    Ok(get_vapor_dir()?.join("hooks.json"))
+}
+
+/// Returns the full path to the REPL startup file.
+///
+/// This is typically `~/.vapor/vaporrc`. Its dot-commands and SQL run at the start of
+/// every interactive REPL session.
+///
+/// # Returns
+///
+/// A `Result` containing the `PathBuf` for the startup file.
+pub fn get_rc_path() -> Result<PathBuf> {
+    Ok(get_vapor_dir()?.join("vaporrc"))
+}
+
 /// Returns the full path to the shell history file.
 ///
 /// This is typically `~/.vapor/shell_history`.
@@ -56,9 +114,10 @@ pub fn get_shell_history_path() -> Result<PathBuf> {
     Ok(get_vapor_dir()?.join("shell_history"))
 }
 
-/// Returns the full path to the REPL history file.
+/// Returns the full path to the REPL history file used before each database had its own.
 ///
-/// This is typically `~/.vapor/repl_history`.
+/// This is typically `~/.vapor/repl_history`. It is no longer written; a database without
+/// a history file of its own starts from a copy of it.
 ///
 /// # Returns
 ///
@@ -67,6 +126,138 @@ pub fn get_repl_history_path() -> Result<PathBuf> {
     Ok(get_vapor_dir()?.join("repl_history"))
 }
 
+/// Returns the path to the REPL history file for one database.
+///
+/// Each database gets its own file in `~/.vapor/history/`, named after a hash of its
+/// absolute path (or URL), so working on several databases doesn't mix their histories.
+/// The directory is created if needed.
+///
+/// # Arguments
+///
+/// * `db_path` - The database file path or remote URL.
+///
+/// # Returns
+///
+/// A `Result` containing the `PathBuf` for the database's history file.
+pub fn get_database_history_path(db_path: &str) -> Result<PathBuf> {
+    Ok(get_history_dir()?.join(database_file_name(db_path)))
+}
+
+/// Returns the path to the REPL history file for a remote database.
+///
+/// Remote databases keep their history apart from local files: `~/.vapor/history/`
+/// holds it under `remote-` plus a hash of the URL. The URL's query string is left out
+/// of the hash, since it may carry an auth token that changes.
+///
+/// # Arguments
+///
+/// * `url` - The remote database URL.
+///
+/// # Returns
+///
+/// A `Result` containing the `PathBuf` for the remote database's history file.
+pub fn get_remote_history_path(url: &str) -> Result<PathBuf> {
+    let url = url.split('?').next().unwrap_or(url);
+    Ok(get_history_dir()?.join(format!("remote-{}", database_file_name(url))))
+}
+
+/// Returns `~/.vapor/history/`, creating it if needed.
+fn get_history_dir() -> Result<PathBuf> {
+    let history_dir = get_vapor_dir()?.join("history");
+    fs::create_dir_all(&history_dir).with_context(|| {
+        format!(
+            "Failed to create history directory at {}",
+            history_dir.display()
+        )
+    })?;
+    Ok(history_dir)
+}
+
+/// Returns the path to the session notes file for one database.
+///
+/// Notes added with `.note` are kept next to the database's history, in
+/// `~/.vapor/history/`, with the same name plus `.notes`. The directory is created if
+/// needed.
+///
+/// # Arguments
+///
+/// * `db_path` - The database file path or remote URL.
+///
+/// # Returns
+///
+/// A `Result` containing the `PathBuf` for the database's notes file.
+pub fn get_session_notes_path(db_path: &str) -> Result<PathBuf> {
+    let history = get_database_history_path(db_path)?;
+    Ok(history.with_extension("notes"))
+}
+
+/// A file name for a database's per-database files: a hash of its absolute path (or URL).
+fn database_file_name(db_path: &str) -> String {
+    let key = fs::canonicalize(db_path)
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| db_path.to_string());
+    // FNV-1a, so file names stay the same across Rust releases (unlike `DefaultHasher`)
+    let hash = key.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+/// The number of REPL history entries kept when `VAPOR_HISTORY_SIZE` isn't set.
+pub const DEFAULT_HISTORY_SIZE: usize = 1000;
+
+/// Returns the maximum number of entries kept in the REPL history.
+///
+/// This is read from the `VAPOR_HISTORY_SIZE` environment variable, falling back to
+/// `DEFAULT_HISTORY_SIZE` if it is unset or not a positive number.
+///
+/// # Returns
+///
+/// The maximum history size.
+pub fn get_repl_history_size() -> usize {
+    std::env::var("VAPOR_HISTORY_SIZE")
+        .ok()
+        .and_then(|size| size.trim().parse().ok())
+        .filter(|&size| size > 0)
+        .unwrap_or(DEFAULT_HISTORY_SIZE)
+}
+
+/// Returns whether sessions run `PRAGMA optimize` and a WAL checkpoint when they end.
+///
+/// This is on unless the `VAPOR_OPTIMIZE_ON_EXIT` environment variable is `0`, `off`,
+/// `false`, or `no`.
+///
+/// # Returns
+///
+/// `true` if the exit tidy-up should run.
+pub fn get_optimize_on_exit() -> bool {
+    env_switch("VAPOR_OPTIMIZE_ON_EXIT")
+}
+
+/// Returns whether new REPL and `Session` connections enforce foreign keys.
+///
+/// This is on unless the `VAPOR_FOREIGN_KEYS` environment variable is `0`, `off`,
+/// `false`, or `no`.
+///
+/// # Returns
+///
+/// `true` if connections should turn `PRAGMA foreign_keys` on when they open, `false` if
+/// they should turn it off.
+pub fn get_foreign_keys() -> bool {
+    env_switch("VAPOR_FOREIGN_KEYS")
+}
+
+/// Reads a setting that is on unless its environment variable turns it off.
+fn env_switch(name: &str) -> bool {
+    match std::env::var(name) {
+        Ok(value) => !matches!(
+            value.trim().to_lowercase().as_str(),
+            "0" | "off" | "false" | "no"
+        ),
+        Err(_) => true,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -94,4 +285,66 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_database_history_path() -> Result<()> {
+        let dir = tempdir()?;
+        let a = dir.path().join("a.db");
+        let b = dir.path().join("b.db");
+        fs::write(&a, "")?;
+        fs::write(&b, "")?;
+        let a_path = a.to_str().unwrap();
+        // Compared by file name, since another test may point HOME elsewhere meanwhile
+        let name = |path: PathBuf| path.file_name().unwrap().to_owned();
+
+        // Each database has its own file in the history directory, found again from any
+        // path to the same file
+        let a_history = get_database_history_path(a_path)?;
+        assert!(a_history.parent().unwrap().ends_with(".vapor/history"));
+        assert_eq!(
+            a_history.file_name().unwrap(),
+            database_file_name(a_path).as_str()
+        );
+        assert_ne!(
+            name(a_history.clone()),
+            name(get_database_history_path(b.to_str().unwrap())?)
+        );
+        let dotted = dir.path().join(".").join("a.db");
+        assert_eq!(
+            name(a_history.clone()),
+            name(get_database_history_path(dotted.to_str().unwrap())?)
+        );
+        assert_eq!(
+            name(get_session_notes_path(a_path)?),
+            name(a_history.with_extension("notes"))
+        );
+
+        // Remote histories are kept apart, whatever the URL's auth token
+        let remote = name(get_remote_history_path(
+            "https://db.example.com/app?token=1",
+        )?);
+        assert_eq!(
+            remote,
+            name(get_remote_history_path(
+                "https://db.example.com/app?token=2"
+            )?)
+        );
+        assert_ne!(
+            remote,
+            name(get_database_history_path("https://db.example.com/app")?)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_repl_history_size() {
+        std::env::set_var("VAPOR_HISTORY_SIZE", "250");
+        assert_eq!(get_repl_history_size(), 250);
+        std::env::set_var("VAPOR_HISTORY_SIZE", "0");
+        assert_eq!(get_repl_history_size(), DEFAULT_HISTORY_SIZE);
+        std::env::set_var("VAPOR_HISTORY_SIZE", "lots");
+        assert_eq!(get_repl_history_size(), DEFAULT_HISTORY_SIZE);
+        std::env::remove_var("VAPOR_HISTORY_SIZE");
+        assert_eq!(get_repl_history_size(), DEFAULT_HISTORY_SIZE);
+    }
 }
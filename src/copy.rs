@@ -0,0 +1,330 @@
+//! # Cross-Database Copying
+//!
+//! This module copies rows between tables of attached databases, such as from `main` into
+//! an archive attached with `.attach`. It backs the REPL's `.copy` command, replacing a
+//! hand-written `INSERT ... SELECT` that has to line up the columns of both tables.
+//!
+//! ## Features:
+//! - **Column matching**: Columns are matched by name, ignoring case. Source columns the
+//!   target lacks are left out, and target columns the source lacks get their defaults.
+//! - **Table creation**: With `create`, a missing target table is created with the source
+//!   table's columns, types, defaults, `NOT NULL` constraints, and primary key.
+//! - **Batches with progress**: Rows are copied in batches of `batch_size`, and progress
+//!   is reported about once a second for large tables. Views and `WITHOUT ROWID` tables
+//!   have no rowid to batch on and are copied in a single statement.
+//! - **All or nothing**: The copy runs in a savepoint, so a failing row leaves the target
+//!   unchanged, even inside an open transaction.
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::time::{Duration, Instant};
+
+use crate::db::{
+    column_definitions, create_table_sql, split_table_name, table_columns, ColumnInfo,
+    CreateTableOptions,
+};
+use crate::formats::quote_identifier;
+use crate::progress::ProgressTracker;
+
+/// Options for `copy_table`.
+#[derive(Debug, Clone)]
+pub struct CopyOptions {
+    /// Create the target table if it doesn't exist.
+    pub create: bool,
+    /// The number of rows copied per statement.
+    pub batch_size: usize,
+}
+
+impl Default for CopyOptions {
+    fn default() -> Self {
+        Self {
+            create: false,
+            batch_size: 10_000,
+        }
+    }
+}
+
+/// What `copy_table` did.
+#[derive(Debug, Clone, Default)]
+pub struct CopyReport {
+    pub rows: u64,
+    /// The columns copied.
+    pub columns: Vec<String>,
+    /// Source columns that the target table doesn't have.
+    pub skipped_columns: Vec<String>,
+    /// Target columns that the source table doesn't have, left to their defaults.
+    pub defaulted_columns: Vec<String>,
+    /// Whether the target table was created.
+    pub created: bool,
+}
+
+/// Copies the rows of one table into another, possibly in another attached database.
+///
+/// # Arguments
+///
+/// * `conn` - A mutable reference to the active `rusqlite::Connection`.
+/// * `source` - The table to copy from, such as `users` or `main.users`.
+/// * `target` - The table to copy into, such as `archive.users`.
+/// * `options` - Whether to create the target, and the batch size.
+///
+/// # Returns
+///
+/// A `Result` containing what was copied, or an `Err` if a table is missing, the tables
+/// share no columns, or a row can't be inserted. Nothing is copied on error.
+pub fn copy_table(
+    conn: &mut Connection,
+    source: &str,
+    target: &str,
+    options: &CopyOptions,
+) -> Result<CopyReport> {
+    let (source_schema, source_table) = split_table_name(conn, source)?;
+    let (target_schema, target_table) = split_table_name(conn, target)?;
+    if source_schema.eq_ignore_ascii_case(&target_schema)
+        && source_table.eq_ignore_ascii_case(&target_table)
+    {
+        anyhow::bail!("'{}' and '{}' are the same table", source, target);
+    }
+    let source_columns = stored_columns(conn, &source_schema, &source_table)?;
+    if source_columns.is_empty() {
+        anyhow::bail!("Table '{}' does not exist", source);
+    }
+    let source_name = format!(
+        "{}.{}",
+        quote_identifier(&source_schema),
+        quote_identifier(&source_table)
+    );
+    let target_name = format!(
+        "{}.{}",
+        quote_identifier(&target_schema),
+        quote_identifier(&target_table)
+    );
+
+    let savepoint = conn.savepoint().context("Failed to start a savepoint")?;
+    let mut report = CopyReport::default();
+    let mut target_columns = stored_columns(&savepoint, &target_schema, &target_table)?;
+    if target_columns.is_empty() {
+        if let Some((alias, _)) = target_table.split_once('.') {
+            anyhow::bail!("No database is attached as '{}'", alias);
+        }
+        if !options.create {
+            anyhow::bail!(
+                "Table '{}' does not exist. Use --create to create it from '{}'",
+                target,
+                source
+            );
+        }
+        let create = create_table_sql(
+            &target_name,
+            &column_definitions(&source_columns),
+            &CreateTableOptions::default(),
+        )?;
+        savepoint
+            .execute_batch(&create)
+            .with_context(|| format!("Failed to create table '{}'", target))?;
+        target_columns = stored_columns(&savepoint, &target_schema, &target_table)?;
+        report.created = true;
+    }
+
+    for column in &source_columns {
+        match target_columns
+            .iter()
+            .find(|t| t.name.eq_ignore_ascii_case(&column.name))
+        {
+            Some(_) => report.columns.push(column.name.clone()),
+            None => report.skipped_columns.push(column.name.clone()),
+        }
+    }
+    report.defaulted_columns = target_columns
+        .iter()
+        .filter(|t| {
+            !source_columns
+                .iter()
+                .any(|s| s.name.eq_ignore_ascii_case(&t.name))
+        })
+        .map(|t| t.name.clone())
+        .collect();
+    if report.columns.is_empty() {
+        anyhow::bail!("'{}' and '{}' have no columns in common", source, target);
+    }
+
+    let column_list = report
+        .columns
+        .iter()
+        .map(|c| quote_identifier(c))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let insert_sql = format!(
+        "INSERT INTO {} ({cols}) SELECT {cols} FROM {} WHERE rowid > ?1 AND rowid <= ?2 ORDER BY rowid",
+        target_name,
+        source_name,
+        cols = column_list
+    );
+    let boundary_sql = format!(
+        "SELECT rowid FROM {} WHERE rowid > ?1 ORDER BY rowid LIMIT 1 OFFSET ?2",
+        source_name
+    );
+    let total: i64 = savepoint
+        .query_row(
+            &format!("SELECT COUNT(*) FROM {}", source_name),
+            [],
+            |row| row.get(0),
+        )
+        .with_context(|| format!("Failed to count the rows of '{}'", source))?;
+
+    let mut progress = ProgressTracker::new("copy", Some(total as u64));
+    // Views and WITHOUT ROWID tables have no rowid to batch on, so they are copied in one
+    // statement
+    if savepoint
+        .prepare(&format!("SELECT rowid FROM {} LIMIT 0", source_name))
+        .is_err()
+    {
+        let copied = savepoint
+            .execute(
+                &format!(
+                    "INSERT INTO {} ({cols}) SELECT {cols} FROM {}",
+                    target_name,
+                    source_name,
+                    cols = column_list
+                ),
+                [],
+            )
+            .with_context(|| format!("Failed to copy rows into '{}'", target))?;
+        report.rows = copied as u64;
+        progress.update(report.rows);
+        savepoint.commit().context("Failed to commit the copy")?;
+        progress.finish();
+        return Ok(report);
+    }
+    let start = Instant::now();
+    let mut last_report = start;
+    let batch_size = options.batch_size.max(1) as i64;
+    let mut last_rowid = i64::MIN;
+    loop {
+        // Copy up to the rowid `batch_size` rows on, or everything left after the last batch
+        let boundary: Option<i64> = savepoint
+            .query_row(&boundary_sql, params![last_rowid, batch_size - 1], |row| {
+                row.get(0)
+            })
+            .optional()
+            .with_context(|| format!("Failed to read '{}'", source))?;
+        let copied = savepoint
+            .execute(
+                &insert_sql,
+                params![last_rowid, boundary.unwrap_or(i64::MAX)],
+            )
+            .with_context(|| format!("Failed to copy rows into '{}'", target))?;
+        report.rows += copied as u64;
+        progress.update(report.rows);
+        let Some(boundary) = boundary else {
+            break;
+        };
+        last_rowid = boundary;
+        if last_report.elapsed() < Duration::from_secs(1) {
+            continue;
+        }
+        last_report = Instant::now();
+        let rate = report.rows as f64 / start.elapsed().as_secs_f64().max(f64::EPSILON);
+        println!(
+            "Progress: {}/{} rows ({:.1}%) - {:.0} rows/sec",
+            report.rows,
+            total,
+            report.rows as f64 / total.max(1) as f64 * 100.0,
+            rate
+        );
+    }
+    savepoint.commit().context("Failed to commit the copy")?;
+    progress.finish();
+    Ok(report)
+}
+
+/// The columns of a table that rows can be copied into and out of: all but generated ones.
+fn stored_columns(conn: &Connection, schema: &str, table: &str) -> Result<Vec<ColumnInfo>> {
+    let mut columns = table_columns(conn, Some(schema), table)?;
+    columns.retain(|column| !column.generated);
+    Ok(columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_table() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "ATTACH ':memory:' AS aux;
+             CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT NOT NULL, email TEXT);
+             WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 25)
+             INSERT INTO users SELECT i, 'user' || i, 'u' || i || '@example.com' FROM n;
+             CREATE TABLE aux.people (ID INTEGER PRIMARY KEY, Name TEXT, active INTEGER DEFAULT 1);",
+        )
+        .unwrap();
+
+        let options = CopyOptions {
+            create: false,
+            batch_size: 10,
+        };
+        assert!(copy_table(&mut conn, "users", "aux.users", &options).is_err());
+        assert!(copy_table(&mut conn, "users", "main.users", &options).is_err());
+
+        let report = copy_table(&mut conn, "main.users", "aux.people", &options).unwrap();
+        assert_eq!(report.rows, 25);
+        assert_eq!(report.columns, ["id", "name"]);
+        assert_eq!(report.skipped_columns, ["email"]);
+        assert_eq!(report.defaulted_columns, ["active"]);
+        let active: i64 = conn
+            .query_row("SELECT SUM(active) FROM aux.people", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(active, 25);
+
+        let options = CopyOptions {
+            create: true,
+            ..options
+        };
+        let report = copy_table(&mut conn, "users", "aux.users", &options).unwrap();
+        assert!(report.created);
+        assert_eq!(report.rows, 25);
+        let sql: String = conn
+            .query_row(
+                "SELECT sql FROM aux.sqlite_master WHERE name = 'users'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(sql.contains("\"name\" TEXT NOT NULL"));
+        assert!(sql.contains("PRIMARY KEY (\"id\")"));
+
+        // A duplicate key rolls the whole copy back
+        assert!(copy_table(&mut conn, "users", "aux.users", &options).is_err());
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM aux.users", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 25);
+    }
+
+    #[test]
+    fn test_copy_without_rowid_and_view() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "ATTACH ':memory:' AS aux;
+             CREATE TABLE kv (k TEXT PRIMARY KEY, v INTEGER) WITHOUT ROWID;
+             INSERT INTO kv VALUES ('a', 1), ('b', 2), ('c', 3);
+             CREATE VIEW big AS SELECT k, v FROM kv WHERE v > 1;",
+        )
+        .unwrap();
+        let options = CopyOptions {
+            create: true,
+            batch_size: 2,
+        };
+
+        let report = copy_table(&mut conn, "kv", "aux.kv", &options).unwrap();
+        assert!(report.created);
+        assert_eq!(report.rows, 3);
+        let report = copy_table(&mut conn, "big", "aux.big", &options).unwrap();
+        assert_eq!(report.rows, 2);
+        let keys: String = conn
+            .query_row("SELECT group_concat(k) FROM aux.big", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(keys, "b,c");
+    }
+}
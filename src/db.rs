@@ -7,11 +7,28 @@
 
 use anyhow::{Context, Result};
 use prettytable::{row, Table};
-use rusqlite::{params, Connection};
+use rusqlite::backup::{Backup, StepResult};
+use rusqlite::{params, Connection, OpenFlags};
 use std::fs;
+use std::io::Write;
 use std::path::Path;
+use std::sync::OnceLock;
 use std::time::Duration;
 
+use crate::backend::{is_remote_target, open_backend, Backend, ColumnDescription};
+use crate::formats::quote_identifier;
+use crate::progress::ProgressTracker;
+
+/// The pages copied per step of a backup. Locks are only held during a step, so other
+/// connections can use the database between steps.
+const BACKUP_PAGES_PER_STEP: i32 = 256;
+
+/// How long a backup waits before retrying a step that met a lock.
+const BACKUP_RETRY_PAUSE: Duration = Duration::from_millis(50);
+
+/// How long a backup keeps retrying while the database stays locked.
+const BACKUP_LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+
 /// Initializes a new SQLite database file.
 ///
 /// This function creates a new database file at the specified path. It includes logic to:
@@ -29,11 +46,7 @@ use std::time::Duration;
 ///
 /// A `Result` which is `Ok(())` on successful creation, or an `Err` with context if it fails.
 pub fn init_database(name: &str) -> Result<()> {
-    let db_path = if name.ends_with(".db") {
-        name.to_string()
-    } else {
-        format!("{}.db", name)
-    };
+    let db_path = database_file_name(name);
 
     // Check if the database already exists
     if Path::new(&db_path).exists() {
@@ -57,7 +70,12 @@ pub fn init_database(name: &str) -> Result<()> {
     }
 
     // Create a new SQLite database with retry logic
-    let _conn = create_connection_with_retry(&db_path, 3)?;
+    let options = ConnectionOptions {
+        read_only: false,
+        create_if_missing: true,
+        ..ConnectionOptions::current()
+    };
+    let _conn = create_connection_with_retry(&db_path, 3, &options)?;
 
     // Verify the database was created successfully
     verify_database_integrity(&db_path)?;
@@ -68,6 +86,77 @@ pub fn init_database(name: &str) -> Result<()> {
     Ok(())
 }
 
+/// Returns the schema version stamped in a database (`PRAGMA user_version`).
+///
+/// # Arguments
+///
+/// * `backend` - The database to read, local or remote.
+///
+/// # Returns
+///
+/// A `Result` containing the schema version, which is 0 for a database that was never
+/// stamped.
+pub fn schema_version(backend: &dyn Backend) -> Result<i32> {
+    let result = backend.run("PRAGMA user_version", None)?;
+    match result.rows.first().and_then(|row| row.first()) {
+        Some(rusqlite::types::Value::Integer(version)) => Ok(*version as i32),
+        _ => anyhow::bail!(
+            "Could not read the schema version of '{}'",
+            backend.describe()
+        ),
+    }
+}
+
+/// Stamps a schema version into a database (`PRAGMA user_version = N`).
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `version` - The version to stamp.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` once the version has been written.
+pub fn set_schema_version(conn: &Connection, version: i32) -> Result<()> {
+    conn.execute_batch(&format!("PRAGMA user_version = {}", version))
+        .context("Failed to set the schema version")
+}
+
+/// Fails unless a database is stamped with the required schema version.
+///
+/// This guards scripts against running on the wrong database, such as a production copy
+/// that hasn't been migrated yet.
+///
+/// # Arguments
+///
+/// * `backend` - The database to check, local or remote.
+/// * `required` - The schema version the caller expects.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` if the versions match, or an `Err` naming both versions.
+pub fn check_schema_version(backend: &dyn Backend, required: i32) -> Result<()> {
+    let actual = schema_version(backend)?;
+    if actual != required {
+        anyhow::bail!(
+            "Schema version mismatch: '{}' is at version {}, but version {} is required",
+            backend.describe(),
+            actual,
+            required
+        );
+    }
+    Ok(())
+}
+
+/// Returns the file name for a new database, appending `.db` if it is missing.
+pub(crate) fn database_file_name(name: &str) -> String {
+    if name.ends_with(".db") {
+        name.to_string()
+    } else {
+        format!("{}.db", name)
+    }
+}
+
 /// Connects to an existing SQLite database.
 ///
 /// This function establishes a connection to a given database file. It performs several checks:
@@ -102,7 +191,7 @@ pub fn connect_database(path: &str) -> Result<()> {
     }
 
     // Try to connect to the database with retry logic
-    let _conn = create_connection_with_retry(path, 3)?;
+    let _conn = create_connection_with_retry(path, 3, &ConnectionOptions::current())?;
 
     // Verify database integrity
     verify_database_integrity(path)?;
@@ -113,6 +202,279 @@ pub fn connect_database(path: &str) -> Result<()> {
     Ok(())
 }
 
+/// Options for `create_table_with_options`.
+#[derive(Debug, Clone, Default)]
+pub struct CreateTableOptions {
+    /// Create a `STRICT` table, whose columns only accept values of their declared type.
+    pub strict: bool,
+    /// Columns computed from the other columns, added after them.
+    pub generated: Vec<GeneratedColumn>,
+    /// Table-level `CHECK` constraint expressions.
+    pub checks: Vec<String>,
+}
+
+/// A generated column, whose value is computed from an expression over the other columns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeneratedColumn {
+    pub name: String,
+    /// The declared type, if one was given.
+    pub declared_type: Option<String>,
+    pub expression: String,
+    /// Whether the value is stored in the table (`STORED`) or computed when read (`VIRTUAL`).
+    pub stored: bool,
+}
+
+impl GeneratedColumn {
+    /// Parses a generated column given as `NAME [TYPE] = EXPR [STORED|VIRTUAL]`.
+    ///
+    /// # Arguments
+    ///
+    /// * `spec` - The column, e.g. `total REAL = price * quantity STORED`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `GeneratedColumn`, which is `VIRTUAL` unless `STORED` is
+    /// given, or an `Err` if the name or expression is missing or malformed.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let Some((column, expression)) = spec.split_once('=') else {
+            anyhow::bail!(
+                "Invalid generated column '{}'. Use NAME [TYPE] = EXPR [STORED|VIRTUAL]",
+                spec
+            );
+        };
+        let mut column = column.split_whitespace();
+        let Some(name) = column.next() else {
+            anyhow::bail!("Generated column '{}' needs a name", spec);
+        };
+        let declared_type = column.collect::<Vec<_>>().join(" ");
+
+        let mut expression = expression.trim();
+        let mut stored = false;
+        if let Some((rest, last)) = expression.rsplit_once(char::is_whitespace) {
+            if last.eq_ignore_ascii_case("STORED") || last.eq_ignore_ascii_case("VIRTUAL") {
+                stored = last.eq_ignore_ascii_case("STORED");
+                expression = rest.trim_end();
+            }
+        }
+        validate_expression(expression)
+            .with_context(|| format!("Invalid expression for generated column '{}'", name))?;
+
+        Ok(GeneratedColumn {
+            name: name.to_string(),
+            declared_type: (!declared_type.is_empty()).then_some(declared_type),
+            expression: expression.to_string(),
+            stored,
+        })
+    }
+}
+
+/// Builds the `CREATE TABLE` statement for a table.
+///
+/// # Arguments
+///
+/// * `table_name` - The name of the table to create.
+/// * `columns` - A string defining the table's columns (e.g., "id INTEGER PRIMARY KEY, name TEXT").
+/// * `options` - Additional table options, such as `STRICT`, generated columns, and
+///   `CHECK` constraints.
+///
+/// # Returns
+///
+/// A `Result` containing the statement, without a trailing semicolon, or an `Err` if a
+/// `CHECK` expression is malformed.
+pub fn create_table_sql(
+    table_name: &str,
+    columns: &str,
+    options: &CreateTableOptions,
+) -> Result<String> {
+    let mut definitions = vec![columns.trim().to_string()];
+    for column in &options.generated {
+        // Every column of a STRICT table needs a type
+        let declared_type = match (&column.declared_type, options.strict) {
+            (Some(declared_type), _) => format!(" {}", declared_type),
+            (None, true) => " ANY".to_string(),
+            (None, false) => String::new(),
+        };
+        definitions.push(format!(
+            "{}{} GENERATED ALWAYS AS ({}) {}",
+            column.name,
+            declared_type,
+            column.expression,
+            if column.stored { "STORED" } else { "VIRTUAL" }
+        ));
+    }
+    for check in &options.checks {
+        validate_expression(check)
+            .with_context(|| format!("Invalid CHECK constraint '{}'", check))?;
+        definitions.push(format!("CHECK ({})", check.trim()));
+    }
+
+    let mut sql = format!("CREATE TABLE {} ({})", table_name, definitions.join(", "));
+    if options.strict {
+        sql.push_str(" STRICT");
+    }
+    Ok(sql)
+}
+
+/// A column as `PRAGMA table_xinfo` describes it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnInfo {
+    pub name: String,
+    /// The declared type, which may be empty.
+    pub decl_type: String,
+    pub not_null: bool,
+    /// The default value as written in the schema, e.g. `'active'` or `CURRENT_TIMESTAMP`.
+    pub default: Option<String>,
+    /// The column's position in the primary key, or 0 if it isn't part of it.
+    pub primary_key: i64,
+    /// Whether it is a generated column.
+    pub generated: bool,
+    /// Whether it is a hidden column of a virtual table, such as an FTS5 table's `rank`.
+    pub hidden: bool,
+}
+
+impl ColumnInfo {
+    /// The column as it would be written in `CREATE TABLE` or `ADD COLUMN`.
+    pub fn definition(&self) -> String {
+        let mut definition = quote_identifier(&self.name);
+        if !self.decl_type.is_empty() {
+            definition.push(' ');
+            definition.push_str(&self.decl_type);
+        }
+        if self.not_null {
+            definition.push_str(" NOT NULL");
+        }
+        if let Some(default) = &self.default {
+            definition.push_str(" DEFAULT ");
+            definition.push_str(default);
+        }
+        definition
+    }
+
+    /// Whether `ALTER TABLE ... ADD COLUMN` can add the column, which rules out primary
+    /// key and generated columns, and `NOT NULL` columns without a default.
+    pub(crate) fn can_add(&self) -> bool {
+        self.primary_key == 0 && !self.generated && (!self.not_null || self.default.is_some())
+    }
+}
+
+impl From<ColumnInfo> for ColumnDescription {
+    fn from(column: ColumnInfo) -> Self {
+        ColumnDescription {
+            name: column.name,
+            declared_type: column.decl_type,
+            not_null: column.not_null,
+            default_value: column.default,
+            primary_key: column.primary_key > 0,
+        }
+    }
+}
+
+/// Returns the columns of a table, generated and hidden ones included, in table order.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `schema` - The database the table is in, such as `main` or an attached alias, or
+///   `None` to look in `temp`, `main`, and then the attached databases, as SQL does.
+/// * `table` - The table name, unquoted.
+///
+/// # Returns
+///
+/// A `Result` containing the columns, which is empty if there is no such table.
+pub(crate) fn table_columns(
+    conn: &Connection,
+    schema: Option<&str>,
+    table: &str,
+) -> Result<Vec<ColumnInfo>> {
+    let mut stmt = conn.prepare(
+        "SELECT name, type, \"notnull\", dflt_value, pk, hidden FROM pragma_table_xinfo(?1, ?2) ORDER BY cid",
+    )?;
+    let columns = stmt
+        .query_map(params![table, schema], |row| {
+            Ok(ColumnInfo {
+                name: row.get(0)?,
+                decl_type: row.get(1)?,
+                not_null: row.get(2)?,
+                default: row.get(3)?,
+                primary_key: row.get(4)?,
+                // 1 marks hidden virtual table columns; 2 and 3 generated ones
+                generated: row.get::<_, i64>(5)? >= 2,
+                hidden: row.get::<_, i64>(5)? == 1,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .with_context(|| format!("Failed to read the columns of table '{}'", table))?;
+    Ok(columns)
+}
+
+/// Returns the columns `PRAGMA table_info` lists: those of [`table_columns`] without
+/// generated and hidden ones, which can't be inserted into.
+pub(crate) fn visible_columns(
+    conn: &Connection,
+    schema: Option<&str>,
+    table: &str,
+) -> Result<Vec<ColumnInfo>> {
+    let mut columns = table_columns(conn, schema, table)?;
+    columns.retain(|column| !column.generated && !column.hidden);
+    Ok(columns)
+}
+
+/// Joins columns into the column definitions of a `CREATE TABLE` statement, for
+/// [`create_table_sql`]. Columns in the primary key get a `PRIMARY KEY` clause, and
+/// generated columns are left out.
+pub(crate) fn column_definitions(columns: &[ColumnInfo]) -> String {
+    let columns: Vec<&ColumnInfo> = columns.iter().filter(|c| !c.generated).collect();
+    let mut definitions: Vec<String> = columns.iter().map(|c| c.definition()).collect();
+    let mut key: Vec<&ColumnInfo> = columns
+        .iter()
+        .copied()
+        .filter(|c| c.primary_key > 0)
+        .collect();
+    key.sort_by_key(|c| c.primary_key);
+    if !key.is_empty() {
+        definitions.push(format!(
+            "PRIMARY KEY ({})",
+            key.iter()
+                .map(|c| quote_identifier(&c.name))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    definitions.join(", ")
+}
+
+/// Checks that an expression can be placed inside parentheses in a column definition:
+/// it isn't empty, its parentheses balance, and it has no `;` outside string literals.
+fn validate_expression(expression: &str) -> Result<()> {
+    if expression.trim().is_empty() {
+        anyhow::bail!("The expression is empty");
+    }
+    let mut depth = 0usize;
+    let mut quote = None;
+    for c in expression.chars() {
+        match (quote, c) {
+            (Some(open), _) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => {
+                depth = depth
+                    .checked_sub(1)
+                    .context("The expression has an unmatched ')'")?;
+            }
+            (None, ';') => anyhow::bail!("The expression can't contain ';'"),
+            _ => {}
+        }
+    }
+    if quote.is_some() {
+        anyhow::bail!("The expression has an unclosed quote");
+    }
+    if depth != 0 {
+        anyhow::bail!("The expression has an unclosed '('");
+    }
+    Ok(())
+}
+
 /// Creates a new table in the specified database.
 ///
 /// This function adds a new table to the database with the given name and column definitions.
@@ -132,6 +494,27 @@ pub fn connect_database(path: &str) -> Result<()> {
 ///
 /// A `Result` which is `Ok(())` on successful table creation, or an `Err` with context if it fails.
 pub fn create_table(db_path: &str, table_name: &str, columns: &str) -> Result<()> {
+    create_table_with_options(db_path, table_name, columns, &CreateTableOptions::default())
+}
+
+/// Creates a new table in the specified database, as `create_table` does, with options.
+///
+/// # Arguments
+///
+/// * `db_path` - The path to the database file.
+/// * `table_name` - The name of the table to create.
+/// * `columns` - A string defining the table's columns (e.g., "id INTEGER PRIMARY KEY, name TEXT").
+/// * `options` - Additional table options, such as `STRICT`.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` on successful table creation, or an `Err` with context if it fails.
+pub fn create_table_with_options(
+    db_path: &str,
+    table_name: &str,
+    columns: &str,
+    options: &CreateTableOptions,
+) -> Result<()> {
     // Validate database exists and is accessible
     if !Path::new(db_path).exists() {
         anyhow::bail!(
@@ -142,7 +525,7 @@ pub fn create_table(db_path: &str, table_name: &str, columns: &str) -> Result<()
     }
 
     // Connect to the database with retry logic
-    let conn = create_connection_with_retry(db_path, 3)?;
+    let conn = create_connection_with_retry(db_path, 3, &ConnectionOptions::current())?;
 
     // Check if table already exists
     let table_exists = check_table_exists(&conn, table_name)?;
@@ -158,7 +541,7 @@ pub fn create_table(db_path: &str, table_name: &str, columns: &str) -> Result<()
     validate_column_syntax(columns)?;
 
     // Create the table with proper error handling
-    let create_table_sql = format!("CREATE TABLE {} ({})", table_name, columns);
+    let create_table_sql = create_table_sql(table_name, columns, options)?;
 
     conn.execute(&create_table_sql, params![])
         .with_context(|| {
@@ -193,12 +576,18 @@ pub fn create_table(db_path: &str, table_name: &str, columns: &str) -> Result<()
 ///
 /// # Arguments
 ///
-/// * `db_path` - The path to the database file.
+/// * `db_path` - The path to the database file, or a remote database URL (`remote` feature).
 ///
 /// # Returns
 ///
 /// A `Result` containing a `Vec<String>` of table names on success, or an `Err` with context if it fails.
 pub fn list_tables(db_path: &str) -> Result<Vec<String>> {
+    if is_remote_target(db_path) {
+        let tables = open_backend(db_path)?.table_names()?;
+        print_table_list(db_path, &tables);
+        return Ok(tables);
+    }
+
     // Validate database exists and is accessible
     if !Path::new(db_path).exists() {
         anyhow::bail!(
@@ -209,7 +598,7 @@ pub fn list_tables(db_path: &str) -> Result<Vec<String>> {
     }
 
     // Connect to the database with retry logic
-    let conn = create_connection_with_retry(db_path, 3)?;
+    let conn = create_connection_with_retry(db_path, 3, &ConnectionOptions::current())?;
 
     // Query for all tables with error handling
     let mut stmt = conn
@@ -220,42 +609,915 @@ pub fn list_tables(db_path: &str) -> Result<Vec<String>> {
         .query_map(params![], |row| row.get::<_, String>(0))
         .context("Failed to execute query for listing tables")?;
 
-    // Create a pretty table for display
+    let tables = table_names
+        .collect::<rusqlite::Result<Vec<String>>>()
+        .with_context(|| "Failed to read table name from database result")?;
+    print_table_list(db_path, &tables);
+
+    // Connection will be automatically dropped when it goes out of scope
+    Ok(tables)
+}
+
+/// Prints a list of table names as a table, or a hint if there are none.
+pub(crate) fn print_table_list(db_path: &str, tables: &[String]) {
+    if tables.is_empty() {
+        println!("No tables found in database: {}", db_path);
+        println!("Use 'create-table' command to create your first table.");
+        return;
+    }
+
     let mut table = Table::new();
     table.add_row(row!["Table Name"]);
+    for name in tables {
+        table.add_row(row![name]);
+    }
+    println!("Tables in database '{}':", db_path);
+    table.printstd();
+    println!("Total: {} table(s)", tables.len());
+}
 
-    let mut has_tables = false;
-    let mut table_count = 0;
-    let mut tables = Vec::new();
+/// Returns the names of all user-created tables on an open connection.
+///
+/// Unlike `list_tables`, this does not print anything, which makes it suitable for
+/// internal callers that need to iterate over tables (e.g. whole-database exports).
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+///
+/// # Returns
+///
+/// A `Result` containing the table names sorted alphabetically.
+pub fn get_table_names(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT name FROM sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
+        )
+        .context("Failed to prepare statement for listing tables")?;
+
+    let names = stmt
+        .query_map(params![], |row| row.get::<_, String>(0))
+        .context("Failed to query table names")?
+        .collect::<rusqlite::Result<Vec<String>>>()
+        .context("Failed to read table names")?;
+
+    Ok(names)
+}
 
-    for table_name_result in table_names {
-        let name =
-            table_name_result.with_context(|| "Failed to read table name from database result")?;
-        table.add_row(row![&name]);
-        tables.push(name);
-        has_tables = true;
-        table_count += 1;
+/// Returns the databases attached to a connection with `ATTACH`.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+///
+/// # Returns
+///
+/// A `Result` containing `(alias, file)` pairs in the order they were attached. The
+/// `main` and `temp` databases are left out, and in-memory databases have an empty file.
+pub fn attached_databases(conn: &Connection) -> Result<Vec<(String, String)>> {
+    let mut stmt = conn
+        .prepare("SELECT name, file FROM pragma_database_list WHERE name NOT IN ('main', 'temp')")
+        .context("Failed to prepare statement for listing attached databases")?;
+
+    let databases = stmt
+        .query_map(params![], |row| Ok((row.get(0)?, row.get(1)?)))
+        .context("Failed to query attached databases")?
+        .collect::<rusqlite::Result<Vec<(String, String)>>>()
+        .context("Failed to read attached databases")?;
+
+    Ok(databases)
+}
+
+/// Returns the names of the tables in the main database and every attached database.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+///
+/// # Returns
+///
+/// A `Result` containing the main database's tables sorted alphabetically, followed by
+/// each attached database's tables qualified with its alias (`alias.table`).
+pub fn get_all_table_names(conn: &Connection) -> Result<Vec<String>> {
+    let mut names = get_table_names(conn)?;
+    for (alias, _) in attached_databases(conn)? {
+        let sql = format!(
+            "SELECT name FROM {}.sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
+            quote_identifier(&alias)
+        );
+        let mut stmt = conn
+            .prepare(&sql)
+            .with_context(|| format!("Failed to list tables in attached database '{}'", alias))?;
+        let tables = stmt
+            .query_map(params![], |row| row.get::<_, String>(0))
+            .with_context(|| format!("Failed to query tables in attached database '{}'", alias))?;
+        for table in tables {
+            names.push(format!("{}.{}", alias, table?));
+        }
     }
+    Ok(names)
+}
 
-    if has_tables {
-        println!("Tables in database '{}':", db_path);
-        table.printstd();
-        println!("Total: {} table(s)", table_count);
-    } else {
-        println!("No tables found in database: {}", db_path);
-        println!("Use 'create-table' command to create your first table.");
+/// Splits a table name that may be qualified with an attached database's alias.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `name` - A table name, such as `users` or `archive.users`.
+///
+/// # Returns
+///
+/// A `Result` containing the schema (`main` unless the prefix names an attached
+/// database) and the table name. Names with dots that don't start with an alias are
+/// taken as table names in `main`.
+pub fn split_table_name(conn: &Connection, name: &str) -> Result<(String, String)> {
+    if let Some((schema, table)) = name.split_once('.') {
+        let is_schema = schema == "main"
+            || schema == "temp"
+            || attached_databases(conn)?
+                .iter()
+                .any(|(alias, _)| alias.eq_ignore_ascii_case(schema));
+        if is_schema {
+            return Ok((schema.to_string(), table.to_string()));
+        }
     }
+    Ok(("main".to_string(), name.to_string()))
+}
 
-    // Connection will be automatically dropped when it goes out of scope
-    Ok(tables)
+/// Tidies up a database at the end of a session.
+///
+/// This runs `PRAGMA optimize`, so the next connection plans queries with up-to-date
+/// statistics, and in WAL mode a passive checkpoint, which copies what it can from the
+/// WAL file into the database without waiting on other connections.
+///
+/// # Arguments
+///
+/// * `backend` - The database to tidy up.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` on success, or an `Err` if either step fails.
+pub fn optimize_on_exit(backend: &dyn Backend) -> Result<()> {
+    backend
+        .run("PRAGMA optimize", Some(0))
+        .context("Failed to run PRAGMA optimize")?;
+    let result = backend
+        .run("PRAGMA journal_mode", None)
+        .context("Failed to read the journal mode")?;
+    let wal = matches!(
+        result.rows.first().and_then(|row| row.first()),
+        Some(rusqlite::types::Value::Text(mode)) if mode.eq_ignore_ascii_case("wal")
+    );
+    if wal {
+        backend
+            .run("PRAGMA wal_checkpoint(PASSIVE)", None)
+            .context("Failed to checkpoint the WAL file")?;
+    }
+    Ok(())
+}
+
+/// Pragmas offered when completing `.pragma NAME`.
+pub const KNOWN_PRAGMAS: &[&str] = &[
+    "analysis_limit",
+    "application_id",
+    "auto_vacuum",
+    "automatic_index",
+    "busy_timeout",
+    "cache_size",
+    "cache_spill",
+    "case_sensitive_like",
+    "cell_size_check",
+    "checkpoint_fullfsync",
+    "collation_list",
+    "compile_options",
+    "data_version",
+    "database_list",
+    "defer_foreign_keys",
+    "encoding",
+    "foreign_key_check",
+    "foreign_key_list",
+    "foreign_keys",
+    "freelist_count",
+    "fullfsync",
+    "function_list",
+    "hard_heap_limit",
+    "ignore_check_constraints",
+    "incremental_vacuum",
+    "index_info",
+    "index_list",
+    "index_xinfo",
+    "integrity_check",
+    "journal_mode",
+    "journal_size_limit",
+    "legacy_alter_table",
+    "locking_mode",
+    "max_page_count",
+    "mmap_size",
+    "module_list",
+    "optimize",
+    "page_count",
+    "page_size",
+    "pragma_list",
+    "query_only",
+    "quick_check",
+    "read_uncommitted",
+    "recursive_triggers",
+    "reverse_unordered_selects",
+    "secure_delete",
+    "shrink_memory",
+    "soft_heap_limit",
+    "synchronous",
+    "table_info",
+    "table_list",
+    "table_xinfo",
+    "temp_store",
+    "threads",
+    "trusted_schema",
+    "user_version",
+    "wal_autocheckpoint",
+    "wal_checkpoint",
+];
+
+/// The pragmas `.pragma list` shows, which most affect safety and speed.
+pub const IMPORTANT_PRAGMAS: &[&str] = &[
+    "journal_mode",
+    "synchronous",
+    "foreign_keys",
+    "cache_size",
+    "busy_timeout",
+];
+
+/// Builds a `PRAGMA` statement from a name and an optional value or argument.
+///
+/// # Arguments
+///
+/// * `name` - The pragma, optionally qualified with a schema (`main.journal_mode`).
+/// * `value` - The value to set, or the argument of a pragma such as `table_info`. It
+///   must be a number, a name, or a quoted string.
+///
+/// # Returns
+///
+/// A `Result` containing the statement, or an `Err` if the name or value isn't one of
+/// those forms.
+pub fn pragma_statement(name: &str, value: Option<&str>) -> Result<String> {
+    let name_pattern = regex::Regex::new(r"^([A-Za-z_]\w*\.)?[A-Za-z_]\w*$")?;
+    if !name_pattern.is_match(name) {
+        anyhow::bail!("Invalid pragma name '{}'", name);
+    }
+    let Some(value) = value else {
+        return Ok(format!("PRAGMA {}", name));
+    };
+    let value_pattern =
+        regex::Regex::new(r#"^([-+]?\d+(\.\d+)?|[A-Za-z_][\w.]*|'([^']|'')*'|"([^"]|"")*")$"#)?;
+    if !value_pattern.is_match(value) {
+        anyhow::bail!(
+            "Invalid pragma value '{}'. Use a number, a name, or a quoted string.",
+            value
+        );
+    }
+    Ok(format!("PRAGMA {} = {}", name, value))
+}
+
+/// Reads the current values of the [`IMPORTANT_PRAGMAS`], described for display.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+///
+/// # Returns
+///
+/// A `Result` containing `(pragma, value)` pairs. Numeric settings are followed by what
+/// they mean, such as `2 (FULL)` for `synchronous`.
+pub fn pragma_settings(conn: &Connection) -> Result<Vec<(&'static str, String)>> {
+    let mut settings = Vec::new();
+    for &name in IMPORTANT_PRAGMAS {
+        let value: rusqlite::types::Value = conn
+            .query_row(&format!("PRAGMA {}", name), [], |row| row.get(0))
+            .with_context(|| format!("Failed to read PRAGMA {}", name))?;
+        let described = match (name, value) {
+            ("synchronous", rusqlite::types::Value::Integer(level)) => {
+                let meaning = ["OFF", "NORMAL", "FULL", "EXTRA"]
+                    .get(level as usize)
+                    .copied()
+                    .unwrap_or("unknown");
+                format!("{} ({})", level, meaning)
+            }
+            ("foreign_keys", rusqlite::types::Value::Integer(on)) => {
+                format!("{} ({})", on, if on != 0 { "on" } else { "off" })
+            }
+            ("cache_size", rusqlite::types::Value::Integer(size)) if size < 0 => {
+                format!("{} ({} KiB)", size, -size)
+            }
+            ("cache_size", rusqlite::types::Value::Integer(size)) => {
+                format!("{} (pages)", size)
+            }
+            ("busy_timeout", rusqlite::types::Value::Integer(ms)) => format!("{} ms", ms),
+            (_, value) => crate::render::display_value(&value),
+        };
+        settings.push((name, described));
+    }
+    Ok(settings)
+}
+
+/// Prints the values from [`pragma_settings`] as a table.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` once the table is printed, or an `Err` if a pragma
+/// can't be read.
+pub fn print_pragma_settings(conn: &Connection) -> Result<()> {
+    let mut table = Table::new();
+    table.add_row(row!["Pragma", "Value"]);
+    for (name, value) in pragma_settings(conn)? {
+        table.add_row(row![name, value]);
+    }
+    table.printstd();
+    Ok(())
+}
+
+/// A maintenance operation run by `run_maintenance`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Maintenance {
+    /// Rebuilds the database file without its free pages, or with `into` writes a
+    /// compacted copy to that file and leaves the database as it is.
+    Vacuum { into: Option<String> },
+    /// Gathers the statistics the query planner uses to choose indexes.
+    Analyze,
+    /// Rebuilds every index.
+    Reindex,
+}
+
+impl Maintenance {
+    /// Returns the SQL that runs the operation.
+    fn sql(&self) -> String {
+        match self {
+            Maintenance::Vacuum { into: None } => "VACUUM".to_string(),
+            Maintenance::Vacuum { into: Some(file) } => {
+                format!("VACUUM INTO '{}'", file.replace('\'', "''"))
+            }
+            Maintenance::Analyze => "ANALYZE".to_string(),
+            Maintenance::Reindex => "REINDEX".to_string(),
+        }
+    }
+}
+
+/// What a maintenance operation did, as returned by `run_maintenance`.
+#[derive(Debug, Clone)]
+pub struct MaintenanceReport {
+    /// The SQL that was run.
+    pub sql: String,
+    pub elapsed: Duration,
+    /// The size of the database before the operation, in bytes.
+    pub size_before: i64,
+    /// The size of the database afterwards, or of the copy written by `VACUUM INTO`.
+    pub size_after: i64,
+}
+
+impl MaintenanceReport {
+    /// Prints the operation with its timing and the size before and after.
+    pub fn print(&self) {
+        let change = self.size_after - self.size_before;
+        let change = if change < 0 {
+            format!(", {} smaller", crate::display::format_size(-change))
+        } else if change > 0 {
+            format!(", {} larger", crate::display::format_size(change))
+        } else {
+            String::new()
+        };
+        println!(
+            "{}: {} -> {}{} in {:.3}s",
+            self.sql,
+            crate::display::format_size(self.size_before),
+            crate::display::format_size(self.size_after),
+            change,
+            self.elapsed.as_secs_f64()
+        );
+    }
+}
+
+/// Runs `VACUUM`, `ANALYZE`, or `REINDEX` on the main database, timing it and measuring
+/// the database's size before and after.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `operation` - The operation to run.
+///
+/// # Returns
+///
+/// A `Result` containing the `MaintenanceReport`, or an `Err` if the operation fails or
+/// is a `VACUUM` while a transaction is open, which SQLite doesn't allow.
+pub fn run_maintenance(conn: &Connection, operation: &Maintenance) -> Result<MaintenanceReport> {
+    if matches!(operation, Maintenance::Vacuum { .. }) && !conn.is_autocommit() {
+        anyhow::bail!("Commit or roll back the open transaction before running VACUUM");
+    }
+    let size_before = database_size(conn)?;
+    let sql = operation.sql();
+    let started = std::time::Instant::now();
+    conn.execute_batch(&sql)
+        .with_context(|| format!("Failed to run {}", sql))?;
+    let elapsed = started.elapsed();
+    let size_after = match operation {
+        Maintenance::Vacuum { into: Some(file) } => fs::metadata(file)
+            .with_context(|| format!("Failed to read the size of '{}'", file))?
+            .len() as i64,
+        _ => database_size(conn)?,
+    };
+    Ok(MaintenanceReport {
+        sql,
+        elapsed,
+        size_before,
+        size_after,
+    })
+}
+
+/// Returns the size of the main database in bytes, from its page count and page size.
+fn database_size(conn: &Connection) -> Result<i64> {
+    conn.query_row(
+        "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+        [],
+        |row| row.get(0),
+    )
+    .context("Failed to read the database size")
+}
+
+/// The journal modes SQLite accepts, lower-cased as `PRAGMA journal_mode` reports them.
+pub const JOURNAL_MODES: &[&str] = &["delete", "truncate", "persist", "memory", "wal", "off"];
+
+/// Switches the main database to a journal mode, such as `wal` for concurrent readers
+/// or `delete` to go back to SQLite's default.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `mode` - One of `JOURNAL_MODES`, in any case.
+///
+/// # Returns
+///
+/// A `Result` containing the journal mode now in effect, or an `Err` if the mode is
+/// unknown, a transaction is open, or SQLite kept the old mode (an in-memory database
+/// can't use WAL, for instance).
+pub fn set_journal_mode(conn: &Connection, mode: &str) -> Result<String> {
+    let mode = mode.to_lowercase();
+    if !JOURNAL_MODES.contains(&mode.as_str()) {
+        anyhow::bail!(
+            "Unknown journal mode '{}'. Use one of: {}",
+            mode,
+            JOURNAL_MODES.join(", ")
+        );
+    }
+    if !conn.is_autocommit() {
+        anyhow::bail!("Commit or roll back the open transaction before changing the journal mode");
+    }
+    let current: String = conn
+        .query_row(&format!("PRAGMA journal_mode = {}", mode), [], |row| {
+            row.get(0)
+        })
+        .with_context(|| format!("Failed to switch to journal mode '{}'", mode))?;
+    if current.to_lowercase() != mode {
+        anyhow::bail!(
+            "The database stayed in journal mode '{}'; it can't use '{}'",
+            current,
+            mode
+        );
+    }
+    Ok(current)
+}
+
+/// Turns foreign key enforcement on or off for a connection.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `on` - Whether inserts, updates, and deletes must keep foreign keys intact.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` once the setting has changed, or an `Err` if a
+/// transaction is open, where SQLite ignores the change.
+pub fn set_foreign_keys(conn: &Connection, on: bool) -> Result<()> {
+    if !conn.is_autocommit() {
+        anyhow::bail!(
+            "Commit or roll back the open transaction before changing foreign key enforcement"
+        );
+    }
+    conn.pragma_update(None, "foreign_keys", on)
+        .context("Failed to change foreign key enforcement")
+}
+
+/// Returns the size of the main database's write-ahead log in bytes, or `None` if it is
+/// an in-memory database or has no log file.
+pub fn wal_size(conn: &Connection) -> Option<u64> {
+    let path = conn.path().filter(|path| !path.is_empty())?;
+    fs::metadata(format!("{}-wal", path))
+        .ok()
+        .map(|metadata| metadata.len())
+}
+
+/// How much work a checkpoint does, as in `PRAGMA wal_checkpoint(MODE)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CheckpointMode {
+    /// Copies as much of the log as it can without waiting for readers or writers.
+    #[default]
+    Passive,
+    /// Waits for writers and then copies the whole log.
+    Full,
+    /// Like `Full`, and also truncates the log file to zero bytes.
+    Truncate,
+}
+
+impl CheckpointMode {
+    /// Parses a checkpoint mode name: `passive`, `full`, or `truncate`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The mode name, in any case.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the mode, or an `Err` naming the valid modes.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "passive" => Ok(CheckpointMode::Passive),
+            "full" => Ok(CheckpointMode::Full),
+            "truncate" => Ok(CheckpointMode::Truncate),
+            _ => anyhow::bail!(
+                "Invalid checkpoint mode '{}'. Use PASSIVE, FULL, or TRUNCATE.",
+                name
+            ),
+        }
+    }
+
+    /// The mode's name, as SQLite spells it.
+    pub fn name(&self) -> &'static str {
+        match self {
+            CheckpointMode::Passive => "PASSIVE",
+            CheckpointMode::Full => "FULL",
+            CheckpointMode::Truncate => "TRUNCATE",
+        }
+    }
+}
+
+/// What a checkpoint did, as returned by `checkpoint`.
+#[derive(Debug, Clone)]
+pub struct CheckpointReport {
+    pub mode: CheckpointMode,
+    /// Whether the checkpoint couldn't finish because another connection was busy.
+    pub busy: bool,
+    /// The frames in the log.
+    pub log_frames: i64,
+    /// The frames copied into the database, which is all of them once the log is done.
+    pub checkpointed_frames: i64,
+    /// The size of the log file before and after, in bytes.
+    pub wal_size_before: u64,
+    pub wal_size_after: u64,
+}
+
+impl CheckpointReport {
+    /// Prints the frames checkpointed and the size of the log before and after.
+    pub fn print(&self) {
+        println!(
+            "Checkpoint ({}): {} of {} frame(s) copied into the database{}",
+            self.mode.name(),
+            self.checkpointed_frames,
+            self.log_frames,
+            if self.busy {
+                "; stopped early because another connection was busy"
+            } else {
+                ""
+            }
+        );
+        println!(
+            "WAL file: {} -> {}",
+            crate::display::format_size(self.wal_size_before as i64),
+            crate::display::format_size(self.wal_size_after as i64)
+        );
+    }
+}
+
+/// Copies the write-ahead log of the main database into the database file.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `mode` - How much work the checkpoint does.
+///
+/// # Returns
+///
+/// A `Result` containing the `CheckpointReport`, or an `Err` if the database isn't in
+/// WAL mode or the checkpoint fails.
+pub fn checkpoint(conn: &Connection, mode: CheckpointMode) -> Result<CheckpointReport> {
+    let journal_mode: String = conn
+        .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+        .context("Failed to read the journal mode")?;
+    if !journal_mode.eq_ignore_ascii_case("wal") {
+        anyhow::bail!(
+            "The database is in journal mode '{}'; checkpoints only apply to WAL mode (.wal on)",
+            journal_mode
+        );
+    }
+    let wal_size_before = wal_size(conn).unwrap_or(0);
+    let (busy, log_frames, checkpointed_frames) = conn
+        .query_row(
+            &format!("PRAGMA wal_checkpoint({})", mode.name()),
+            [],
+            |row| Ok((row.get::<_, i64>(0)? != 0, row.get(1)?, row.get(2)?)),
+        )
+        .context("Failed to checkpoint the write-ahead log")?;
+    Ok(CheckpointReport {
+        mode,
+        busy,
+        log_frames,
+        checkpointed_frames,
+        wal_size_before,
+        wal_size_after: wal_size(conn).unwrap_or(0),
+    })
+}
+
+/// Copies a database to a file with SQLite's online backup API.
+///
+/// Unlike copying the file, this is safe while other connections are using the database.
+/// The backup is written next to `dest` and renamed into place once it is complete, so a
+/// failed backup never leaves a partial file.
+///
+/// # Arguments
+///
+/// * `src` - The path to the database to back up.
+/// * `dest` - The path of the backup. An existing file is replaced.
+/// * `progress` - Called after each step with the pages copied so far and the total.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` once the backup is in place, or an `Err` if the source
+/// can't be opened or a step fails.
+pub fn backup_database(src: &str, dest: &str, progress: impl FnMut(u64, u64)) -> Result<()> {
+    if !Path::new(src).exists() {
+        anyhow::bail!("Database '{}' does not exist", src);
+    }
+    let conn = Connection::open_with_flags(src, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("Failed to open database '{}'", src))?;
+    backup_connection(&conn, dest, progress)
+}
+
+/// Copies the main database of an open connection to a file with SQLite's online backup
+/// API, as `backup_database` does for a path.
+///
+/// # Arguments
+///
+/// * `conn` - The connection whose main database is backed up.
+/// * `dest` - The path of the backup. An existing file is replaced.
+/// * `progress` - Called after each step with the pages copied so far and the total.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` once the backup is in place, or an `Err` if `dest` is the
+/// database itself, the connection has a transaction open, or a step fails.
+pub fn backup_connection(
+    conn: &Connection,
+    dest: &str,
+    mut progress: impl FnMut(u64, u64),
+) -> Result<()> {
+    let same_file = match (conn.path().map(fs::canonicalize), fs::canonicalize(dest)) {
+        (Some(Ok(source)), Ok(dest)) => source == dest,
+        _ => false,
+    };
+    if same_file {
+        anyhow::bail!("Cannot back up a database onto itself");
+    }
+    // The backup would wait on the connection's own locks
+    if !conn.is_autocommit() {
+        anyhow::bail!("Commit or roll back the open transaction before backing up");
+    }
+
+    let temp = format!("{}.backup-tmp", dest);
+    let _ = fs::remove_file(&temp);
+    let result = (|| -> Result<()> {
+        let mut target = Connection::open(&temp)
+            .with_context(|| format!("Failed to create backup file '{}'", dest))?;
+        let backup = Backup::new(conn, &mut target).context("Failed to start the backup")?;
+        let mut tracker = ProgressTracker::new("backup", None);
+        let mut locked_since = None;
+        loop {
+            let step = backup
+                .step(BACKUP_PAGES_PER_STEP)
+                .context("Backup step failed")?;
+            let pages = backup.progress();
+            let total = pages.pagecount.max(0) as u64;
+            let copied = total.saturating_sub(pages.remaining.max(0) as u64);
+            progress(copied, total);
+            tracker.update(copied);
+            tracker.check_cancelled()?;
+            match step {
+                StepResult::Done => break,
+                StepResult::More => locked_since = None,
+                // Busy or locked: another connection is writing, so try again shortly
+                _ => {
+                    let since = *locked_since.get_or_insert_with(std::time::Instant::now);
+                    if since.elapsed() >= BACKUP_LOCK_TIMEOUT {
+                        anyhow::bail!(
+                            "The database stayed locked for {} seconds",
+                            BACKUP_LOCK_TIMEOUT.as_secs()
+                        );
+                    }
+                    std::thread::sleep(BACKUP_RETRY_PAUSE);
+                }
+            }
+        }
+        tracker.finish();
+        Ok(())
+    })();
+    match result {
+        Ok(()) => fs::rename(&temp, dest)
+            .with_context(|| format!("Failed to move the backup into place at '{}'", dest)),
+        Err(e) => {
+            let _ = fs::remove_file(&temp);
+            Err(e)
+        }
+    }
+}
+
+/// Draws a backup's progress as a bar on stderr, for use as the `progress` callback of
+/// `backup_database`. Nothing is drawn when stderr isn't a terminal.
+///
+/// # Arguments
+///
+/// * `copied` - The pages copied so far.
+/// * `total` - The pages in the database.
+pub fn print_backup_progress(copied: u64, total: u64) {
+    const WIDTH: u64 = 30;
+    if !atty::is(atty::Stream::Stderr) {
+        return;
+    }
+    let filled = (copied * WIDTH).checked_div(total).unwrap_or(WIDTH);
+    let percent = (copied * 100).checked_div(total).unwrap_or(100);
+    eprint!(
+        "\rBacking up [{}{}] {:>3}% ({}/{} pages)",
+        "#".repeat(filled as usize),
+        "-".repeat((WIDTH - filled) as usize),
+        percent,
+        copied,
+        total
+    );
+    if copied >= total {
+        eprintln!();
+    }
+    let _ = std::io::stderr().flush();
+}
+
+/// Copies a database into a new file for `restore` and `clone`, checking both ends.
+///
+/// The source must be a SQLite database that passes an integrity check. The copy is made
+/// with `backup_database` and checked again once it is in place.
+///
+/// # Arguments
+///
+/// * `src` - The path to the database to copy.
+/// * `dest` - The path of the copy.
+/// * `force` - Whether an existing `dest` may be replaced.
+/// * `progress` - Called after each step with the pages copied so far and the total.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` once the copy is in place and passes its integrity check,
+/// or an `Err` if the source isn't a healthy database, `dest` exists and `force` is not
+/// set, or the copy fails.
+pub fn copy_database(
+    src: &str,
+    dest: &str,
+    force: bool,
+    progress: impl FnMut(u64, u64),
+) -> Result<()> {
+    let src_path = Path::new(src);
+    if !src_path.is_file() {
+        anyhow::bail!("Database '{}' does not exist", src);
+    }
+    if !crate::replication::is_sqlite_file(src_path) {
+        anyhow::bail!("'{}' is not a SQLite database", src);
+    }
+    let conn = Connection::open_with_flags(src, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("Failed to open database '{}'", src))?;
+    let integrity: String = conn
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .with_context(|| format!("Failed to check the integrity of '{}'", src))?;
+    if integrity != "ok" {
+        anyhow::bail!("'{}' failed its integrity check: {}", src, integrity);
+    }
+
+    if Path::new(dest).exists() && !force {
+        anyhow::bail!("'{}' already exists; use --force to overwrite it", dest);
+    }
+    backup_connection(&conn, dest, progress)?;
+    // A journal left behind by the replaced database would be replayed onto the copy
+    for suffix in ["-wal", "-shm", "-journal"] {
+        let _ = fs::remove_file(format!("{}{}", dest, suffix));
+    }
+    verify_database_integrity(dest).with_context(|| format!("The copy at '{}' is not usable", dest))
+}
+
+/// How connections to a database file are opened: the flags passed to SQLite and the
+/// settings applied once the connection is open. Every connection it opens also gets the
+/// built-in SQL functions from the `functions` module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionOptions {
+    /// How long a statement waits for another connection's lock before failing with
+    /// `SQLITE_BUSY`. `None` fails at once, as SQLite does by default.
+    pub busy_timeout: Option<Duration>,
+    /// Open the database read-only, so any write fails.
+    pub read_only: bool,
+    /// Create the database file if it doesn't exist.
+    pub create_if_missing: bool,
+    /// Accept `file:` URIs such as `file:data.db?mode=ro` as database names.
+    pub uri_filenames: bool,
+    /// Report extended result codes, such as `SQLITE_CONSTRAINT_FOREIGNKEY` rather than
+    /// `SQLITE_CONSTRAINT`, in errors.
+    pub extended_result_codes: bool,
+}
+
+impl Default for ConnectionOptions {
+    fn default() -> Self {
+        Self {
+            busy_timeout: None,
+            read_only: false,
+            create_if_missing: true,
+            uri_filenames: true,
+            extended_result_codes: true,
+        }
+    }
+}
+
+static CONNECTION_OPTIONS: OnceLock<ConnectionOptions> = OnceLock::new();
+
+impl ConnectionOptions {
+    /// Makes these the options `current` returns, for the connections opened by commands
+    /// and REPL sessions. Only the first call has an effect, since connections may
+    /// already have been opened with the earlier options.
+    pub fn set_current(options: ConnectionOptions) {
+        let _ = CONNECTION_OPTIONS.set(options);
+    }
+
+    /// The options set with `set_current`, or the defaults.
+    pub fn current() -> ConnectionOptions {
+        CONNECTION_OPTIONS.get().cloned().unwrap_or_default()
+    }
+
+    /// The flags these options pass to `sqlite3_open_v2`.
+    pub fn flags(&self) -> OpenFlags {
+        let mut flags = OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        if self.read_only {
+            flags |= OpenFlags::SQLITE_OPEN_READ_ONLY;
+        } else {
+            flags |= OpenFlags::SQLITE_OPEN_READ_WRITE;
+            if self.create_if_missing {
+                flags |= OpenFlags::SQLITE_OPEN_CREATE;
+            }
+        }
+        if self.uri_filenames {
+            flags |= OpenFlags::SQLITE_OPEN_URI;
+        }
+        if self.extended_result_codes {
+            flags |= OpenFlags::SQLITE_OPEN_EXRESCODE;
+        }
+        flags
+    }
+
+    /// Opens a connection to a database file with these options.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The database file, or a `file:` URI if `uri_filenames` is set.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the connection, or an `Err` if it can't be opened, for
+    /// example because the file doesn't exist and `create_if_missing` is off.
+    pub fn open(&self, path: &str) -> Result<Connection> {
+        Ok(self.open_connection(path)?)
+    }
+
+    pub(crate) fn open_connection(&self, path: &str) -> rusqlite::Result<Connection> {
+        let conn = Connection::open_with_flags(path, self.flags())?;
+        if let Some(timeout) = self.busy_timeout {
+            conn.busy_timeout(timeout)?;
+        }
+        crate::functions::register(&conn)?;
+        Ok(conn)
+    }
 }
 
 /// Create a database connection with retry logic for handling temporary issues
-fn create_connection_with_retry(db_path: &str, max_retries: u32) -> Result<Connection> {
+fn create_connection_with_retry(
+    db_path: &str,
+    max_retries: u32,
+    options: &ConnectionOptions,
+) -> Result<Connection> {
     let mut last_error = None;
 
     for attempt in 1..=max_retries {
-        match Connection::open(db_path) {
+        match options.open_connection(db_path) {
             Ok(conn) => {
                 if attempt > 1 {
                     println!("Connection succeeded on attempt {}", attempt);
@@ -280,7 +1542,7 @@ fn create_connection_with_retry(db_path: &str, max_retries: u32) -> Result<Conne
 }
 
 /// Verify database integrity
-fn verify_database_integrity(db_path: &str) -> Result<()> {
+pub(crate) fn verify_database_integrity(db_path: &str) -> Result<()> {
     let conn = Connection::open(db_path)
         .with_context(|| format!("Cannot open database '{}' for integrity check", db_path))?;
 
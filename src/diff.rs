@@ -0,0 +1,1046 @@
+//! # Database Diffs
+//!
+//! This module compares the schemas of two SQLite databases, for example a development
+//! database and production, or the rows of two tables, and reports what differs in a form
+//! that can be read at a glance or turned into SQL.
+//!
+//! ## Features:
+//! - **Schema objects**: Tables, indexes, triggers, and views that exist on only one side,
+//!   or whose definitions differ.
+//! - **Columns**: For tables on both sides, the columns added, removed, or changed (type,
+//!   `NOT NULL`, default, or primary key).
+//! - **Reconciling SQL**: `SchemaDiff::reconcile_statements` generates the `CREATE`,
+//!   `DROP`, and `ALTER TABLE` statements that turn the left database into the right one.
+//!   Changes SQLite can't make with `ALTER TABLE`, such as a column's type, are listed as
+//!   comments, since they need the table to be rebuilt.
+//! - **Table data**: `diff_table_data` matches the rows of two tables in the same or
+//!   attached databases by primary key, and counts the rows only one side has and the rows
+//!   whose values differ. `DataDiff::sync_statements` generates the `INSERT`, `DELETE`,
+//!   and `UPDATE` statements that make the left table's rows match the right's.
+
+use anyhow::{Context, Result};
+use rusqlite::types::Value;
+use rusqlite::{Connection, OpenFlags};
+use std::collections::BTreeMap;
+
+use crate::db::{split_table_name, table_columns, ColumnInfo};
+use crate::formats::quote_identifier;
+
+/// One difference between the left and the right database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaChange {
+    /// An object that exists only in the right database.
+    Added {
+        kind: String,
+        name: String,
+        sql: String,
+    },
+    /// An object that exists only in the left database.
+    Removed {
+        kind: String,
+        name: String,
+        sql: String,
+    },
+    /// An object whose definition differs, other than in a table's columns.
+    Changed {
+        kind: String,
+        name: String,
+        left_sql: String,
+        right_sql: String,
+    },
+    /// A column that exists only in the right database's table.
+    ColumnAdded { table: String, column: ColumnInfo },
+    /// A column that exists only in the left database's table.
+    ColumnRemoved { table: String, column: ColumnInfo },
+    /// A column whose type, constraints, or default differ.
+    ColumnChanged {
+        table: String,
+        left: ColumnInfo,
+        right: ColumnInfo,
+    },
+}
+
+/// The differences between the schemas of two databases, in the order they were found:
+/// by object type (tables, then indexes, triggers, and views), then by name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SchemaDiff {
+    pub changes: Vec<SchemaChange>,
+}
+
+/// The schema objects of one database: `(type, name)` to `(table, sql)`.
+type SchemaObjects = BTreeMap<(String, String), (String, String)>;
+
+/// The order objects are compared and listed in.
+const OBJECT_KINDS: &[&str] = &["table", "index", "trigger", "view"];
+
+fn schema_objects(conn: &Connection) -> Result<SchemaObjects> {
+    let mut stmt = conn.prepare(
+        "SELECT type, name, tbl_name, sql FROM sqlite_master \
+         WHERE sql IS NOT NULL AND name NOT LIKE 'sqlite_%'",
+    )?;
+    let objects = stmt
+        .query_map([], |row| {
+            Ok(((row.get(0)?, row.get(1)?), (row.get(2)?, row.get(3)?)))
+        })?
+        .collect::<rusqlite::Result<SchemaObjects>>()?;
+    Ok(objects)
+}
+
+/// Compares the schemas of the main databases of two connections.
+///
+/// # Arguments
+///
+/// * `left` - The database to compare from, e.g. the one to be updated.
+/// * `right` - The database to compare to.
+///
+/// # Returns
+///
+/// A `Result` containing the `SchemaDiff`, which is empty if the schemas match.
+pub fn diff_schemas(left: &Connection, right: &Connection) -> Result<SchemaDiff> {
+    let left_objects = schema_objects(left).context("Failed to read the left schema")?;
+    let right_objects = schema_objects(right).context("Failed to read the right schema")?;
+    let mut changes = Vec::new();
+
+    for kind in OBJECT_KINDS {
+        let mut names: Vec<&String> = left_objects
+            .keys()
+            .chain(right_objects.keys())
+            .filter(|(object_kind, _)| object_kind == kind)
+            .map(|(_, name)| name)
+            .collect();
+        names.sort();
+        names.dedup();
+
+        for name in names {
+            let key = (kind.to_string(), name.clone());
+            match (left_objects.get(&key), right_objects.get(&key)) {
+                (None, Some((_, sql))) => changes.push(SchemaChange::Added {
+                    kind: kind.to_string(),
+                    name: name.clone(),
+                    sql: sql.clone(),
+                }),
+                (Some((_, sql)), None) => changes.push(SchemaChange::Removed {
+                    kind: kind.to_string(),
+                    name: name.clone(),
+                    sql: sql.clone(),
+                }),
+                (Some((_, left_sql)), Some((_, right_sql))) if *kind == "table" => {
+                    let column_changes = diff_columns(
+                        name,
+                        &table_columns(left, Some("main"), name)?,
+                        &table_columns(right, Some("main"), name)?,
+                    );
+                    // Constraints and options that aren't part of a column still differ
+                    if column_changes.is_empty()
+                        && normalize_sql(left_sql) != normalize_sql(right_sql)
+                    {
+                        changes.push(SchemaChange::Changed {
+                            kind: kind.to_string(),
+                            name: name.clone(),
+                            left_sql: left_sql.clone(),
+                            right_sql: right_sql.clone(),
+                        });
+                    }
+                    changes.extend(column_changes);
+                }
+                (Some((_, left_sql)), Some((_, right_sql))) => {
+                    if normalize_sql(left_sql) != normalize_sql(right_sql) {
+                        changes.push(SchemaChange::Changed {
+                            kind: kind.to_string(),
+                            name: name.clone(),
+                            left_sql: left_sql.clone(),
+                            right_sql: right_sql.clone(),
+                        });
+                    }
+                }
+                (None, None) => {}
+            }
+        }
+    }
+    Ok(SchemaDiff { changes })
+}
+
+fn diff_columns(table: &str, left: &[ColumnInfo], right: &[ColumnInfo]) -> Vec<SchemaChange> {
+    let find = |columns: &[ColumnInfo], name: &str| {
+        columns
+            .iter()
+            .find(|column| column.name.eq_ignore_ascii_case(name))
+            .cloned()
+    };
+    let mut changes = Vec::new();
+    for column in left {
+        match find(right, &column.name) {
+            None => changes.push(SchemaChange::ColumnRemoved {
+                table: table.to_string(),
+                column: column.clone(),
+            }),
+            Some(other) if !same_column(column, &other) => {
+                changes.push(SchemaChange::ColumnChanged {
+                    table: table.to_string(),
+                    left: column.clone(),
+                    right: other,
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    for column in right {
+        if find(left, &column.name).is_none() {
+            changes.push(SchemaChange::ColumnAdded {
+                table: table.to_string(),
+                column: column.clone(),
+            });
+        }
+    }
+    changes
+}
+
+fn same_column(left: &ColumnInfo, right: &ColumnInfo) -> bool {
+    left.decl_type.eq_ignore_ascii_case(&right.decl_type)
+        && left.not_null == right.not_null
+        && left.default == right.default
+        && left.primary_key == right.primary_key
+        && left.generated == right.generated
+}
+
+/// Collapses whitespace, so reformatted but identical definitions compare equal.
+fn normalize_sql(sql: &str) -> String {
+    sql.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+impl SchemaDiff {
+    /// Returns true if the schemas match.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Describes each difference on its own line: `+` for what only the right database
+    /// has, `-` for what only the left has, and `~` for what differs.
+    pub fn lines(&self) -> Vec<String> {
+        self.changes
+            .iter()
+            .map(|change| match change {
+                SchemaChange::Added { kind, name, .. } => format!("+ {} {}", kind, name),
+                SchemaChange::Removed { kind, name, .. } => format!("- {} {}", kind, name),
+                SchemaChange::Changed {
+                    kind,
+                    name,
+                    left_sql,
+                    right_sql,
+                } => format!(
+                    "~ {} {}\n    left:  {}\n    right: {}",
+                    kind,
+                    name,
+                    normalize_sql(left_sql),
+                    normalize_sql(right_sql)
+                ),
+                SchemaChange::ColumnAdded { table, column } => {
+                    format!("+ column {}.{}", table, column.definition())
+                }
+                SchemaChange::ColumnRemoved { table, column } => {
+                    format!("- column {}.{}", table, column.definition())
+                }
+                SchemaChange::ColumnChanged { table, left, right } => format!(
+                    "~ column {}.{}: {} -> {}",
+                    table,
+                    left.name,
+                    left.definition(),
+                    right.definition()
+                ),
+            })
+            .collect()
+    }
+
+    /// Prints the differences with a header naming both databases.
+    ///
+    /// # Arguments
+    ///
+    /// * `left` - The name to show for the left database.
+    /// * `right` - The name to show for the right database.
+    pub fn print(&self, left: &str, right: &str) {
+        if self.is_empty() {
+            println!("The schemas of '{}' and '{}' are identical.", left, right);
+            return;
+        }
+        println!("--- {}", left);
+        println!("+++ {}", right);
+        for line in self.lines() {
+            println!("{}", line);
+        }
+        println!("{} difference(s).", self.changes.len());
+    }
+
+    /// Generates the statements that turn the left schema into the right one.
+    ///
+    /// Objects are dropped before tables change and created after, so indexes, triggers,
+    /// and views never refer to a missing table or column. Changes `ALTER TABLE` can't
+    /// make are included as `--` comments explaining that the table must be rebuilt.
+    ///
+    /// # Returns
+    ///
+    /// The statements, without trailing semicolons, in the order they should run.
+    pub fn reconcile_statements(&self) -> Vec<String> {
+        // Drops of dependent objects, then table changes, then creates of dependent objects
+        let mut drops = Vec::new();
+        let mut tables = Vec::new();
+        let mut creates = Vec::new();
+        for change in &self.changes {
+            match change {
+                SchemaChange::Added { kind, sql, .. } if kind == "table" => {
+                    tables.push(sql.clone())
+                }
+                SchemaChange::Added { sql, .. } => creates.push(sql.clone()),
+                SchemaChange::Removed { kind, name, .. } if kind == "table" => {
+                    tables.push(format!("DROP TABLE {}", quote_identifier(name)))
+                }
+                SchemaChange::Removed { kind, name, .. } => drops.push(format!(
+                    "DROP {} {}",
+                    kind.to_uppercase(),
+                    quote_identifier(name)
+                )),
+                SchemaChange::Changed { kind, name, .. } if kind == "table" => tables.push(
+                    format!(
+                        "-- table {} has different constraints or options; rebuild it to match",
+                        name
+                    ),
+                ),
+                SchemaChange::Changed {
+                    kind,
+                    name,
+                    right_sql,
+                    ..
+                } => {
+                    drops.push(format!(
+                        "DROP {} {}",
+                        kind.to_uppercase(),
+                        quote_identifier(name)
+                    ));
+                    creates.push(right_sql.clone());
+                }
+                SchemaChange::ColumnAdded { table, column } if column.can_add() => {
+                    tables.push(format!(
+                        "ALTER TABLE {} ADD COLUMN {}",
+                        quote_identifier(table),
+                        column.definition()
+                    ))
+                }
+                SchemaChange::ColumnAdded { table, column } => tables.push(format!(
+                    "-- column {}.{} can't be added with ALTER TABLE; rebuild the table to add it",
+                    table, column.name
+                )),
+                SchemaChange::ColumnRemoved { table, column } if column.primary_key == 0 => {
+                    tables.push(format!(
+                        "ALTER TABLE {} DROP COLUMN {}",
+                        quote_identifier(table),
+                        quote_identifier(&column.name)
+                    ))
+                }
+                SchemaChange::ColumnRemoved { table, column } => tables.push(format!(
+                    "-- column {}.{} is part of the primary key; rebuild the table to remove it",
+                    table, column.name
+                )),
+                SchemaChange::ColumnChanged { table, left, right } => tables.push(format!(
+                    "-- column {}.{} changed ({} -> {}); SQLite can't alter columns, rebuild the table to match",
+                    table,
+                    left.name,
+                    left.definition(),
+                    right.definition()
+                )),
+            }
+        }
+        drops.into_iter().chain(tables).chain(creates).collect()
+    }
+
+    /// Prints the statements from `reconcile_statements` as a script.
+    pub fn print_reconcile_statements(&self) {
+        for statement in self.reconcile_statements() {
+            if statement.starts_with("--") {
+                println!("{}", statement);
+            } else {
+                println!("{};", statement);
+            }
+        }
+    }
+}
+
+/// Opens two database files read-only and compares their schemas.
+///
+/// # Arguments
+///
+/// * `left` - The path to the database to compare from.
+/// * `right` - The path to the database to compare to.
+///
+/// # Returns
+///
+/// A `Result` containing the `SchemaDiff`, or an `Err` if either file can't be opened.
+pub fn diff_schema_files(left: &str, right: &str) -> Result<SchemaDiff> {
+    let left_conn = open_read_only(left)?;
+    let right_conn = open_read_only(right)?;
+    diff_schemas(&left_conn, &right_conn)
+}
+
+/// Opens an existing database file read-only.
+pub(crate) fn open_read_only(path: &str) -> Result<Connection> {
+    if !std::path::Path::new(path).exists() {
+        anyhow::bail!("Database '{}' not found", path);
+    }
+    Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("Failed to open database '{}'", path))
+}
+
+/// The number of rows of each kind `data-diff` and `.diff data` list by default.
+pub const DEFAULT_DIFF_ROWS: usize = 10;
+
+/// Options for `diff_table_data`.
+#[derive(Debug, Clone, Default)]
+pub struct DataDiffOptions {
+    /// The most rows of each kind (only left, only right, changed) to collect. Every row
+    /// is counted either way; `None` collects them all, as `sync_statements` needs.
+    pub limit: Option<usize>,
+}
+
+/// One row that differs between two tables.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RowChange {
+    /// A row only the right table has: its primary key and its other values.
+    Inserted { key: Vec<Value>, values: Vec<Value> },
+    /// A row only the left table has.
+    Deleted { key: Vec<Value>, values: Vec<Value> },
+    /// A row both tables have, with the columns whose values differ as
+    /// `(column, left value, right value)`.
+    Changed {
+        key: Vec<Value>,
+        columns: Vec<(String, Value, Value)>,
+    },
+}
+
+/// The differences between the rows of two tables, matched by the left table's primary key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataDiff {
+    /// The left table as generated SQL names it, e.g. `"users"` or `"archive"."users"`.
+    pub left_table: String,
+    /// The primary key columns rows are matched by.
+    pub key_columns: Vec<String>,
+    /// The other columns both tables have, which are compared.
+    pub value_columns: Vec<String>,
+    /// Columns only one of the tables has, which are left out.
+    pub skipped_columns: Vec<String>,
+    /// The number of rows only the right table has.
+    pub inserted: usize,
+    /// The number of rows only the left table has.
+    pub deleted: usize,
+    /// The number of rows with differing values.
+    pub changed: usize,
+    /// The rows collected, up to the limit for each kind: deleted rows, then inserted
+    /// rows, then changed rows, each ordered by primary key.
+    pub rows: Vec<RowChange>,
+}
+
+/// Compares the rows of two tables by primary key.
+///
+/// Both tables must be reachable from `conn`, as tables of the main database or of
+/// databases attached to it (`alias.table`).
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `left` - The table to compare from, e.g. the one to be updated.
+/// * `right` - The table to compare to.
+/// * `options` - How many rows of each kind to collect.
+///
+/// # Returns
+///
+/// A `Result` containing the `DataDiff`, or an `Err` if a table is missing, the left
+/// table has no primary key, or the right table lacks one of its columns.
+pub fn diff_table_data(
+    conn: &Connection,
+    left: &str,
+    right: &str,
+    options: &DataDiffOptions,
+) -> Result<DataDiff> {
+    let (left_schema, left_name) = split_table_name(conn, left)?;
+    let (right_schema, right_name) = split_table_name(conn, right)?;
+    if left_schema.eq_ignore_ascii_case(&right_schema)
+        && left_name.eq_ignore_ascii_case(&right_name)
+    {
+        anyhow::bail!("'{}' and '{}' are the same table", left, right);
+    }
+    let left_columns = table_columns(conn, Some(&left_schema), &left_name)?;
+    if left_columns.is_empty() {
+        anyhow::bail!("Table '{}' does not exist", left);
+    }
+    let right_columns = table_columns(conn, Some(&right_schema), &right_name)?;
+    if right_columns.is_empty() {
+        anyhow::bail!("Table '{}' does not exist", right);
+    }
+
+    let in_right = |name: &str| {
+        right_columns
+            .iter()
+            .any(|column| !column.generated && column.name.eq_ignore_ascii_case(name))
+    };
+    let mut keys: Vec<&ColumnInfo> = left_columns
+        .iter()
+        .filter(|column| column.primary_key > 0)
+        .collect();
+    keys.sort_by_key(|column| column.primary_key);
+    if keys.is_empty() {
+        anyhow::bail!("Table '{}' has no primary key to match rows by", left);
+    }
+    if let Some(missing) = keys.iter().find(|column| !in_right(&column.name)) {
+        anyhow::bail!(
+            "Table '{}' has no column '{}' to match the primary key of '{}'",
+            right,
+            missing.name,
+            left
+        );
+    }
+    let key_columns: Vec<String> = keys.iter().map(|column| column.name.clone()).collect();
+    let value_columns: Vec<String> = left_columns
+        .iter()
+        .filter(|column| column.primary_key == 0 && !column.generated && in_right(&column.name))
+        .map(|column| column.name.clone())
+        .collect();
+    let skipped_columns: Vec<String> = left_columns
+        .iter()
+        .chain(&right_columns)
+        .filter(|column| !column.generated)
+        .map(|column| &column.name)
+        .filter(|name| {
+            !key_columns
+                .iter()
+                .chain(&value_columns)
+                .any(|compared| compared.eq_ignore_ascii_case(name))
+        })
+        .cloned()
+        .collect();
+
+    let left_sql = qualified_name(&left_schema, &left_name);
+    let right_sql = qualified_name(&right_schema, &right_name);
+    let select = |alias: &str, columns: &[String]| {
+        columns
+            .iter()
+            .map(|column| format!("{}.{}", alias, quote_identifier(column)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let key_match = key_columns
+        .iter()
+        .map(|column| format!("l.{0} = r.{0}", quote_identifier(column)))
+        .collect::<Vec<_>>()
+        .join(" AND ");
+    let key_count = key_columns.len();
+    let mut diff = DataDiff {
+        left_table: left_sql.clone(),
+        key_columns: key_columns.clone(),
+        value_columns: value_columns.clone(),
+        skipped_columns,
+        inserted: 0,
+        deleted: 0,
+        changed: 0,
+        rows: Vec::new(),
+    };
+    let context = || format!("Failed to compare '{}' with '{}'", left, right);
+
+    let row_columns = format!(
+        "{}{}",
+        select("l", &key_columns),
+        select_rest("l", &value_columns)
+    );
+    diff.deleted = scan(
+        conn,
+        &format!(
+            "SELECT {} FROM {} AS l WHERE NOT EXISTS (SELECT 1 FROM {} AS r WHERE {}) ORDER BY {}",
+            row_columns,
+            left_sql,
+            right_sql,
+            key_match,
+            select("l", &key_columns)
+        ),
+        options.limit,
+        |mut values| {
+            let key = values.drain(..key_count).collect();
+            diff.rows.push(RowChange::Deleted { key, values });
+        },
+    )
+    .with_context(context)?;
+
+    let row_columns = format!(
+        "{}{}",
+        select("r", &key_columns),
+        select_rest("r", &value_columns)
+    );
+    diff.inserted = scan(
+        conn,
+        &format!(
+            "SELECT {} FROM {} AS r WHERE NOT EXISTS (SELECT 1 FROM {} AS l WHERE {}) ORDER BY {}",
+            row_columns,
+            right_sql,
+            left_sql,
+            key_match,
+            select("r", &key_columns)
+        ),
+        options.limit,
+        |mut values| {
+            let key = values.drain(..key_count).collect();
+            diff.rows.push(RowChange::Inserted { key, values });
+        },
+    )
+    .with_context(context)?;
+
+    if !value_columns.is_empty() {
+        // SQLite decides what differs, so 1 and 1.0 compare equal as they do in queries
+        let differs: Vec<String> = value_columns
+            .iter()
+            .map(|column| format!("l.{0} IS NOT r.{0}", quote_identifier(column)))
+            .collect();
+        let width = value_columns.len();
+        diff.changed = scan(
+            conn,
+            &format!(
+                "SELECT {}{}{}, {} FROM {} AS l JOIN {} AS r ON {} WHERE {} ORDER BY {}",
+                select("l", &key_columns),
+                select_rest("l", &value_columns),
+                select_rest("r", &value_columns),
+                differs.join(", "),
+                left_sql,
+                right_sql,
+                key_match,
+                differs.join(" OR "),
+                select("l", &key_columns)
+            ),
+            options.limit,
+            |mut values| {
+                let key: Vec<Value> = values.drain(..key_count).collect();
+                let columns = (0..width)
+                    .filter(|&i| values[2 * width + i] == Value::Integer(1))
+                    .map(|i| {
+                        (
+                            value_columns[i].clone(),
+                            values[i].clone(),
+                            values[width + i].clone(),
+                        )
+                    })
+                    .collect();
+                diff.rows.push(RowChange::Changed { key, columns });
+            },
+        )
+        .with_context(context)?;
+    }
+    Ok(diff)
+}
+
+/// Selects more columns after others, so an empty list adds nothing.
+fn select_rest(alias: &str, columns: &[String]) -> String {
+    columns
+        .iter()
+        .map(|column| format!(", {}.{}", alias, quote_identifier(column)))
+        .collect()
+}
+
+/// Names a table for generated SQL, leaving out the schema for the main database.
+fn qualified_name(schema: &str, table: &str) -> String {
+    if schema.eq_ignore_ascii_case("main") {
+        quote_identifier(table)
+    } else {
+        format!("{}.{}", quote_identifier(schema), quote_identifier(table))
+    }
+}
+
+/// Runs a query and counts its rows, passing the values of the first `limit` of them to
+/// `keep`.
+fn scan(
+    conn: &Connection,
+    sql: &str,
+    limit: Option<usize>,
+    mut keep: impl FnMut(Vec<Value>),
+) -> Result<usize> {
+    let mut stmt = conn.prepare(sql)?;
+    let width = stmt.column_count();
+    let mut rows = stmt.query([])?;
+    let mut count = 0;
+    while let Some(row) = rows.next()? {
+        if count < limit.unwrap_or(usize::MAX) {
+            keep(
+                (0..width)
+                    .map(|i| row.get(i))
+                    .collect::<rusqlite::Result<_>>()?,
+            );
+        }
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Writes a value as a SQL literal.
+fn sql_literal(value: &Value) -> String {
+    match value {
+        Value::Null => "NULL".to_string(),
+        Value::Integer(i) => i.to_string(),
+        Value::Real(f) if f.is_infinite() => if *f > 0.0 { "1e999" } else { "-1e999" }.to_string(),
+        // Debug keeps the decimal point, so the value stays a REAL
+        Value::Real(f) => format!("{:?}", f),
+        Value::Text(text) => format!("'{}'", text.replace('\'', "''")),
+        Value::Blob(bytes) => {
+            let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+            format!("X'{}'", hex)
+        }
+    }
+}
+
+impl DataDiff {
+    /// Returns true if the tables have the same rows.
+    pub fn is_empty(&self) -> bool {
+        self.inserted == 0 && self.deleted == 0 && self.changed == 0
+    }
+
+    /// Returns true if every differing row was collected, not just the first few.
+    pub fn is_complete(&self) -> bool {
+        self.rows.len() == self.inserted + self.deleted + self.changed
+    }
+
+    /// The condition that selects a row of the left table by its primary key.
+    fn key_condition(&self, key: &[Value]) -> String {
+        self.key_columns
+            .iter()
+            .zip(key)
+            .map(|(column, value)| match value {
+                Value::Null => format!("{} IS NULL", quote_identifier(column)),
+                _ => format!("{} = {}", quote_identifier(column), sql_literal(value)),
+            })
+            .collect::<Vec<_>>()
+            .join(" AND ")
+    }
+
+    /// Writes a primary key as `id=1`, or `(a=1, b='x')` for a composite key.
+    fn key_label(&self, key: &[Value]) -> String {
+        let pairs: Vec<String> = self
+            .key_columns
+            .iter()
+            .zip(key)
+            .map(|(column, value)| format!("{}={}", column, sql_literal(value)))
+            .collect();
+        match pairs.as_slice() {
+            [pair] => pair.clone(),
+            _ => format!("({})", pairs.join(", ")),
+        }
+    }
+
+    /// Describes each collected row on its own line: `+` for rows only the right table
+    /// has, `-` for rows only the left has, and `~` for rows whose values differ.
+    pub fn lines(&self) -> Vec<String> {
+        let values = |values: &[Value]| {
+            self.value_columns
+                .iter()
+                .zip(values)
+                .map(|(column, value)| format!("{}={}", column, sql_literal(value)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        self.rows
+            .iter()
+            .map(|row| match row {
+                RowChange::Deleted { key, values: row } => {
+                    format!("- {} {}", self.key_label(key), values(row))
+                }
+                RowChange::Inserted { key, values: row } => {
+                    format!("+ {} {}", self.key_label(key), values(row))
+                }
+                RowChange::Changed { key, columns } => format!(
+                    "~ {} {}",
+                    self.key_label(key),
+                    columns
+                        .iter()
+                        .map(|(column, left, right)| format!(
+                            "{}: {} -> {}",
+                            column,
+                            sql_literal(left),
+                            sql_literal(right)
+                        ))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            })
+            .map(|line| line.trim_end().to_string())
+            .collect()
+    }
+
+    /// Prints the collected rows and a summary with a header naming both tables.
+    ///
+    /// # Arguments
+    ///
+    /// * `left` - The name to show for the left table.
+    /// * `right` - The name to show for the right table.
+    pub fn print(&self, left: &str, right: &str) {
+        if !self.skipped_columns.is_empty() {
+            println!(
+                "Not compared (not in both tables): {}",
+                self.skipped_columns.join(", ")
+            );
+        }
+        if self.is_empty() {
+            println!("The rows of '{}' and '{}' are identical.", left, right);
+            return;
+        }
+        println!("--- {}", left);
+        println!("+++ {}", right);
+        for line in self.lines() {
+            println!("{}", line);
+        }
+        if !self.is_complete() {
+            println!(
+                "... {} more row(s) not shown.",
+                self.inserted + self.deleted + self.changed - self.rows.len()
+            );
+        }
+        println!(
+            "{} row(s) only in '{}', {} only in '{}', {} changed.",
+            self.deleted, left, self.inserted, right, self.changed
+        );
+    }
+
+    /// Generates the statements that make the left table's rows match the right's.
+    ///
+    /// Deletes come first, then updates, then inserts, so a row whose unique value moved
+    /// to another key doesn't conflict with the one it replaces. Only collected rows are
+    /// included; diff with no limit to sync every row.
+    ///
+    /// # Returns
+    ///
+    /// The statements, without trailing semicolons, in the order they should run.
+    pub fn sync_statements(&self) -> Vec<String> {
+        let mut deletes = Vec::new();
+        let mut updates = Vec::new();
+        let mut inserts = Vec::new();
+        for row in &self.rows {
+            match row {
+                RowChange::Deleted { key, .. } => deletes.push(format!(
+                    "DELETE FROM {} WHERE {}",
+                    self.left_table,
+                    self.key_condition(key)
+                )),
+                RowChange::Changed { key, columns } => updates.push(format!(
+                    "UPDATE {} SET {} WHERE {}",
+                    self.left_table,
+                    columns
+                        .iter()
+                        .map(|(column, _, right)| format!(
+                            "{} = {}",
+                            quote_identifier(column),
+                            sql_literal(right)
+                        ))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    self.key_condition(key)
+                )),
+                RowChange::Inserted { key, values } => inserts.push(format!(
+                    "INSERT INTO {} ({}) VALUES ({})",
+                    self.left_table,
+                    self.key_columns
+                        .iter()
+                        .chain(&self.value_columns)
+                        .map(|column| quote_identifier(column))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    key.iter()
+                        .chain(values)
+                        .map(sql_literal)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )),
+            }
+        }
+        deletes.into_iter().chain(updates).chain(inserts).collect()
+    }
+
+    /// Prints the statements from `sync_statements` as a script.
+    pub fn print_sync_statements(&self) {
+        for statement in self.sync_statements() {
+            println!("{};", statement);
+        }
+    }
+}
+
+/// Compares the rows of tables in two database files by primary key.
+///
+/// The left database is opened read-only, and the right one attached to it if it is a
+/// different file.
+///
+/// # Arguments
+///
+/// * `left` - The path to the database with the table to compare from.
+/// * `left_table` - The table to compare from.
+/// * `right` - The path to the database with the table to compare to.
+/// * `right_table` - The table to compare to.
+/// * `options` - How many rows of each kind to collect.
+///
+/// # Returns
+///
+/// A `Result` containing the `DataDiff`, or an `Err` if a file can't be opened or the
+/// tables can't be compared.
+pub fn diff_table_files(
+    left: &str,
+    left_table: &str,
+    right: &str,
+    right_table: &str,
+    options: &DataDiffOptions,
+) -> Result<DataDiff> {
+    let conn = open_read_only(left)?;
+    let same_file = match (std::fs::canonicalize(left), std::fs::canonicalize(right)) {
+        (Ok(left), Ok(right)) => left == right,
+        _ => false,
+    };
+    if same_file {
+        return diff_table_data(
+            &conn,
+            &format!("main.{}", left_table),
+            &format!("main.{}", right_table),
+            options,
+        );
+    }
+    if !std::path::Path::new(right).exists() {
+        anyhow::bail!("Database '{}' not found", right);
+    }
+    conn.execute("ATTACH DATABASE ?1 AS vapor_right", [right])
+        .with_context(|| format!("Failed to attach database '{}'", right))?;
+    diff_table_data(
+        &conn,
+        &format!("main.{}", left_table),
+        &format!("vapor_right.{}", right_table),
+        options,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_schemas() {
+        let left = Connection::open_in_memory().unwrap();
+        left.execute_batch(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, nickname TEXT, age INTEGER);
+             CREATE TABLE legacy (id INTEGER PRIMARY KEY);
+             CREATE INDEX idx_users_name ON users (name);
+             CREATE VIEW adults AS SELECT * FROM users WHERE age >= 18;",
+        )
+        .unwrap();
+        let right = Connection::open_in_memory().unwrap();
+        right
+            .execute_batch(
+                "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT,
+                     age TEXT, email TEXT NOT NULL DEFAULT '');
+                 CREATE TABLE orders (id INTEGER PRIMARY KEY, user_id INTEGER);
+                 CREATE INDEX idx_users_name ON users (name, email);
+                 CREATE VIEW adults AS SELECT * FROM users WHERE age >= 18;",
+            )
+            .unwrap();
+
+        let diff = diff_schemas(&left, &right).unwrap();
+        assert_eq!(
+            diff.lines(),
+            vec![
+                "- table legacy",
+                "+ table orders",
+                "- column users.\"nickname\" TEXT",
+                "~ column users.age: \"age\" INTEGER -> \"age\" TEXT",
+                "+ column users.\"email\" TEXT NOT NULL DEFAULT ''",
+                "~ index idx_users_name\n    left:  CREATE INDEX idx_users_name ON users (name)\n    right: CREATE INDEX idx_users_name ON users (name, email)",
+            ]
+        );
+
+        let statements = diff.reconcile_statements();
+        assert_eq!(statements[0], "DROP INDEX \"idx_users_name\"");
+        assert!(statements.contains(&"ALTER TABLE \"users\" DROP COLUMN \"nickname\"".to_string()));
+        assert!(statements
+            .iter()
+            .any(|s| s.starts_with("-- column users.age changed")));
+        assert_eq!(
+            statements.last().unwrap(),
+            "CREATE INDEX idx_users_name ON users (name, email)"
+        );
+
+        // Applying the statements leaves only the change that needs a rebuild
+        left.execute_batch(
+            &statements
+                .iter()
+                .filter(|s| !s.starts_with("--"))
+                .map(|s| format!("{};", s))
+                .collect::<String>(),
+        )
+        .unwrap();
+        let remaining = diff_schemas(&left, &right).unwrap();
+        assert_eq!(remaining.changes.len(), 1);
+        assert!(matches!(
+            remaining.changes[0],
+            SchemaChange::ColumnChanged { .. }
+        ));
+        assert!(diff_schemas(&right, &right).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_diff_table_data() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, score REAL, note TEXT);
+             INSERT INTO users VALUES (1, 'ann', 1.0, 'x'), (2, 'bob', 2.5, NULL),
+                 (3, 'cy', NULL, NULL), (4, 'dee', 4, NULL);
+             CREATE TABLE users_copy (id INTEGER PRIMARY KEY, name TEXT, score REAL, email TEXT);
+             INSERT INTO users_copy VALUES (1, 'ann', 1, 'a@x'), (2, 'bo''b', 2.5, NULL),
+                 (4, 'dee', NULL, NULL), (5, 'eve', 5.5, NULL), (6, 'fay', NULL, NULL);
+             CREATE TABLE keyless (name TEXT);",
+        )
+        .unwrap();
+
+        let diff =
+            diff_table_data(&conn, "users", "users_copy", &DataDiffOptions::default()).unwrap();
+        assert_eq!((diff.deleted, diff.inserted, diff.changed), (1, 2, 2));
+        assert_eq!(diff.skipped_columns, vec!["note", "email"]);
+        assert!(diff.is_complete());
+        assert_eq!(
+            diff.lines(),
+            vec![
+                "- id=3 name='cy', score=NULL",
+                "+ id=5 name='eve', score=5.5",
+                "+ id=6 name='fay', score=NULL",
+                "~ id=2 name: 'bob' -> 'bo''b'",
+                "~ id=4 score: 4.0 -> NULL",
+            ]
+        );
+
+        let limited = diff_table_data(
+            &conn,
+            "users",
+            "users_copy",
+            &DataDiffOptions { limit: Some(1) },
+        )
+        .unwrap();
+        assert_eq!(limited.rows.len(), 3);
+        assert_eq!(limited.inserted, 2);
+        assert!(!limited.is_complete());
+
+        // Applying the statements makes the compared columns match
+        let statements = diff.sync_statements();
+        assert_eq!(statements[0], "DELETE FROM \"users\" WHERE \"id\" = 3");
+        conn.execute_batch(
+            &statements
+                .iter()
+                .map(|s| format!("{};", s))
+                .collect::<String>(),
+        )
+        .unwrap();
+        let synced =
+            diff_table_data(&conn, "users", "users_copy", &DataDiffOptions::default()).unwrap();
+        assert!(synced.is_empty());
+
+        assert!(
+            diff_table_data(&conn, "keyless", "users", &DataDiffOptions::default())
+                .unwrap_err()
+                .to_string()
+                .contains("no primary key")
+        );
+        assert!(
+            diff_table_data(&conn, "users", "main.users", &DataDiffOptions::default()).is_err()
+        );
+    }
+}
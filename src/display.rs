@@ -6,10 +6,18 @@
 //!
 //! ## Core Components:
 //! - `execute_sql`: The main function that runs a SQL query and manages the display of its results.
-//! - `OutputFormat`: An enum to specify the desired output format (`Table`, `Json`, `Csv`).
+//!   `execute_sql_on` does the same for any `Backend`, including remote databases.
+//! - `OutputFormat`: An enum to specify the desired output format (`Table`, `Json`, `Csv`,
+//!   `Plain`). The CLI uses `Plain` when stdout is not a terminal.
 //! - `QueryOptions`: A struct to control display settings like row limits and timing information.
+//! - Output Redirection: `set_output_file` sends query results to a file instead of stdout,
+//!   for every following query or only the next one (`.output` and `.once` in the REPL).
+//!   `set_output_pipe` streams the next query's results into a shell command (`.pipe`).
+//! - Large Values: Text over `LARGE_VALUE_SIZE` bytes is cut short in tables, and
+//!   `show_cell` pages the full value from the last result (`.cell` in the REPL).
 //! - Schema Display: Functions like `show_table_schema` and `show_all_schemas` for inspecting the DB structure.
-//! - Database Info: `show_database_info` provides a summary of the database file and its contents.
+//! - Database Info: `database_info` collects a summary of the database file and its contents as a
+//!   serializable `DatabaseInfo`, and `show_database_info` prints it.
 //!
 //! The module also includes experimental, currently unused features for result caching (`QueryCache`)
 //! and progressive data loading (`ProgressiveLoader`).
@@ -17,21 +25,26 @@
 use anyhow::{Context, Result};
 use prettytable::{row, Table};
 use rusqlite::{params, Connection};
-use serde_json::{json, Value};
+use serde::Serialize;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::process::Child;
+use std::sync::{Mutex, OnceLock};
 use std::time::{Duration, Instant};
 
-/// Specifies the output format for query results.
-#[derive(Debug, Clone)]
-pub enum OutputFormat {
-    Table,
-    Json,
-    Csv,
-}
+use crate::backend::{Backend, ColumnDescription, QueryResult};
+use crate::db::{attached_databases, get_all_table_names, split_table_name, visible_columns};
+use crate::formats::{quote_identifier, Record};
+pub use crate::render::OutputFormat;
+use crate::render::{
+    build_table, display_value, format_csv, format_json, format_plain, schema_table,
+};
+use crate::shell::spawn_piped;
+use crate::statement::{classify, StatementKind};
 
-#[allow(dead_code)]
 /// Custom error types for display-related operations.
 ///
 /// Note: This is currently not used extensively but is defined for future error handling enhancements.
@@ -54,10 +67,15 @@ impl fmt::Display for DisplayError {
 impl Error for DisplayError {}
 
 /// Defines options for controlling how a query is executed and displayed.
+///
+/// The default format is `Table`. The CLI starts from [`QueryOptions::for_stdout`]
+/// instead, which picks `Plain` when stdout is piped.
 pub struct QueryOptions {
     pub format: OutputFormat,
     pub max_rows: Option<usize>,
     pub show_timing: bool,
+    /// Print only the results, without row counts or timing, for scripts.
+    pub quiet: bool,
 }
 
 impl Default for QueryOptions {
@@ -66,11 +84,238 @@ impl Default for QueryOptions {
             format: OutputFormat::Table,
             max_rows: Some(1000),
             show_timing: true,
+            quiet: false,
         }
     }
 }
 
-#[allow(dead_code)]
+impl QueryOptions {
+    /// The default options, with `Plain` output when stdout is not a terminal: box-drawing
+    /// tables are for people, while pipes get columns that are easy to split.
+    pub fn for_stdout() -> Self {
+        let format = if atty::is(atty::Stream::Stdout) {
+            OutputFormat::Table
+        } else {
+            OutputFormat::Plain
+        };
+        Self {
+            format,
+            ..Self::default()
+        }
+    }
+}
+
+/// A file or command that query results are written to instead of stdout.
+struct OutputFile {
+    /// The file path, or `| command` for a pipe.
+    path: String,
+    writer: Box<dyn Write + Send>,
+    /// The command being piped into, if any.
+    child: Option<Child>,
+    /// Go back to stdout after the next query.
+    once: bool,
+}
+
+impl Drop for OutputFile {
+    fn drop(&mut self) {
+        let _ = self.writer.flush();
+        if let Some(mut child) = self.child.take() {
+            // Closing the command's stdin lets it finish, and waiting for it keeps its
+            // output ahead of the next prompt
+            self.writer = Box::new(std::io::sink());
+            match child.wait() {
+                Ok(status) if !status.success() => eprintln!("'{}' {}", &self.path[2..], status),
+                Ok(_) => {}
+                Err(e) => eprintln!("Failed to wait for '{}': {}", &self.path[2..], e),
+            }
+        }
+    }
+}
+
+fn output_file() -> &'static Mutex<Option<OutputFile>> {
+    static OUTPUT: OnceLock<Mutex<Option<OutputFile>>> = OnceLock::new();
+    OUTPUT.get_or_init(|| Mutex::new(None))
+}
+
+/// Sends the results of following queries to a file instead of stdout.
+///
+/// Results are written in the current output format. Row counts, timing, and errors are
+/// still printed to the console.
+///
+/// # Arguments
+///
+/// * `path` - The file to write to. It is created, or truncated if it exists.
+/// * `once` - Go back to stdout after the next query, whether or not it succeeds.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` once the file is open, or an `Err` if it can't be created.
+pub fn set_output_file(path: &str, once: bool) -> Result<()> {
+    let file =
+        File::create(path).with_context(|| format!("Failed to open output file '{}'", path))?;
+    *output_file().lock().unwrap() = Some(OutputFile {
+        path: path.to_string(),
+        writer: Box::new(BufWriter::new(file)),
+        child: None,
+        once,
+    });
+    Ok(())
+}
+
+/// Streams the results of the next query into a shell command's standard input.
+///
+/// Results are written in the current output format, and the command's own output goes
+/// to the console. Row counts, timing, and errors are still printed to the console.
+///
+/// # Arguments
+///
+/// * `command` - The command line, run by the system shell, e.g. `grep error`.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` once the command has started, or an `Err` if it can't be.
+pub fn set_output_pipe(command: &str) -> Result<()> {
+    let mut child = spawn_piped(command)?;
+    let stdin = child
+        .stdin
+        .take()
+        .context("Failed to open the command's standard input")?;
+    *output_file().lock().unwrap() = Some(OutputFile {
+        path: format!("| {}", command),
+        writer: Box::new(BufWriter::new(stdin)),
+        child: Some(child),
+        once: true,
+    });
+    Ok(())
+}
+
+/// Sends query results back to stdout, closing the output file or pipe if there is one.
+pub fn reset_output() {
+    output_file().lock().unwrap().take();
+}
+
+/// Returns the file query results are currently written to, or `None` for stdout.
+pub fn output_path() -> Option<String> {
+    output_file()
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|output| output.path.clone())
+}
+
+/// Text values longer than this many bytes are cut short in table output.
+pub const LARGE_VALUE_SIZE: usize = 1024;
+
+/// How many characters of a large value are shown before the size marker.
+const LARGE_VALUE_PREVIEW: usize = 60;
+
+/// The columns and rows of the last query, kept so `.cell` can show values in full.
+struct LastResult {
+    columns: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+fn last_result() -> &'static Mutex<Option<LastResult>> {
+    static LAST: OnceLock<Mutex<Option<LastResult>>> = OnceLock::new();
+    LAST.get_or_init(|| Mutex::new(None))
+}
+
+/// Shortens a value over `LARGE_VALUE_SIZE` bytes to its start and a marker with its size.
+fn truncate_large_value(value: &str) -> std::borrow::Cow<'_, str> {
+    if value.len() <= LARGE_VALUE_SIZE {
+        return value.into();
+    }
+    let first_line = value.lines().next().unwrap_or("");
+    let preview: String = first_line.chars().take(LARGE_VALUE_PREVIEW).collect();
+    format!(
+        "{}[... {:.1} KB, use .cell to view]",
+        preview,
+        value.len() as f64 / 1024.0
+    )
+    .into()
+}
+
+/// Shows a value from the last query's results in full, through a pager when stdout is
+/// a terminal.
+///
+/// # Arguments
+///
+/// * `row` - The row, counting from 1. Defaults to the row of the first large value.
+/// * `column` - The column name or number, counting from 1. Defaults to the column of
+///   the first large value in the row.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` once the value has been shown, or an `Err` if there is
+/// no such value.
+pub fn show_cell(row: Option<usize>, column: Option<&str>) -> Result<()> {
+    let value = {
+        let last = last_result().lock().unwrap();
+        let last = last
+            .as_ref()
+            .context("No query results to show a value from")?;
+        let is_large = |value: &String| value.len() > LARGE_VALUE_SIZE;
+        let row_index = match row {
+            Some(0) => anyhow::bail!("Rows are numbered from 1"),
+            Some(row) => row - 1,
+            None => last
+                .rows
+                .iter()
+                .position(|values| values.iter().any(is_large))
+                .context("The last result has no large values; use .cell ROW COLUMN")?,
+        };
+        let values = last.rows.get(row_index).with_context(|| {
+            format!(
+                "Row {} is out of range; the last result has {} row(s)",
+                row_index + 1,
+                last.rows.len()
+            )
+        })?;
+        let column_index = match column {
+            Some(column) => match column.parse::<usize>() {
+                Ok(0) => anyhow::bail!("Columns are numbered from 1"),
+                Ok(number) if number <= last.columns.len() => number - 1,
+                Ok(number) => anyhow::bail!(
+                    "Column {} is out of range; the last result has {} column(s)",
+                    number,
+                    last.columns.len()
+                ),
+                Err(_) => last
+                    .columns
+                    .iter()
+                    .position(|name| name.eq_ignore_ascii_case(column))
+                    .with_context(|| format!("The last result has no column '{}'", column))?,
+            },
+            None => values.iter().position(is_large).unwrap_or(0),
+        };
+        values[column_index].clone()
+    };
+    page_text(&value)
+}
+
+/// Prints text through `$PAGER` (or `less`) when stdout is a terminal, and directly
+/// otherwise or if the pager can't be started.
+fn page_text(text: &str) -> Result<()> {
+    if atty::is(atty::Stream::Stdout) {
+        let pager = std::env::var("PAGER")
+            .ok()
+            .filter(|pager| !pager.trim().is_empty())
+            .unwrap_or_else(|| (if cfg!(windows) { "more" } else { "less" }).to_string());
+        if let Ok(mut child) = spawn_piped(&pager) {
+            if let Some(mut stdin) = child.stdin.take() {
+                // Quitting the pager early closes its input, which isn't an error
+                let _ = writeln!(stdin, "{}", text);
+            }
+            child
+                .wait()
+                .with_context(|| format!("Failed to wait for '{}'", pager))?;
+            return Ok(());
+        }
+    }
+    println!("{}", text);
+    Ok(())
+}
+
 /// A cache for storing and retrieving query results to improve performance for repeated queries.
 ///
 /// Note: This feature is experimental and not currently integrated into the REPL or CLI.
@@ -122,7 +367,6 @@ impl QueryCache {
     }
 }
 
-#[allow(dead_code)]
 /// A helper for loading and displaying large result sets in batches to avoid high memory usage.
 ///
 /// Note: This feature is experimental and not currently integrated into the REPL or CLI.
@@ -175,8 +419,9 @@ impl ProgressiveLoader {
 
 /// Executes a SQL statement and displays the results according to the provided options.
 ///
-/// This function handles both `SELECT` queries, which produce result sets, and other
-/// statements (e.g., `INSERT`, `UPDATE`, `CREATE`), which report the number of affected rows.
+/// Any statement that has result columns is displayed as a result set: `SELECT`, but also
+/// `WITH ... SELECT`, `VALUES`, `PRAGMA`, `EXPLAIN`, and `RETURNING` clauses. Other
+/// statements (e.g., `INSERT`, `UPDATE`, `CREATE`) report the number of affected rows.
 ///
 /// # Arguments
 ///
@@ -187,75 +432,129 @@ impl ProgressiveLoader {
 /// # Returns
 ///
 /// A `Result` which is `Ok(())` on success, or an `Err` if the query fails to prepare or execute.
-pub fn execute_sql(conn: &Connection, sql: &str, options: &QueryOptions, last_select_query: &std::sync::Arc<std::sync::Mutex<String>>) -> Result<()> {
-    let start_time = Instant::now();
+pub fn execute_sql(
+    conn: &Connection,
+    sql: &str,
+    options: &QueryOptions,
+    last_select_query: &std::sync::Arc<std::sync::Mutex<String>>,
+) -> Result<()> {
+    execute_sql_on(conn, sql, options, last_select_query)
+}
 
-    // Execute the query
-    let mut stmt = conn
-        .prepare(sql)
-        .context("Failed to prepare SQL statement")?;
+/// Executes a SQL statement on any `Backend` and displays the results.
+///
+/// This is the backend-independent form of `execute_sql`, used for remote databases.
+///
+/// # Arguments
+///
+/// * `backend` - The database to run the statement against.
+/// * `sql` - The SQL string to execute.
+/// * `options` - A `QueryOptions` struct specifying the format, row limit, and other settings.
+/// * `last_select_query` - Receives the statement if it returns rows, for `.export`.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` on success, or an `Err` if the statement fails.
+pub fn execute_sql_on(
+    backend: &dyn Backend,
+    sql: &str,
+    options: &QueryOptions,
+    last_select_query: &std::sync::Arc<std::sync::Mutex<String>>,
+) -> Result<()> {
+    let last = std::sync::Arc::new(std::sync::Mutex::new(LastQuery {
+        sql: last_select_query.lock().unwrap().clone(),
+        returned: None,
+    }));
+    let result = execute_sql_with_last(backend, sql, options, &last);
+    *last_select_query.lock().unwrap() = last.lock().unwrap().sql.clone();
+    result
+}
 
-    // Check if it's a SELECT query
-    let is_select = sql.trim().to_uppercase().starts_with("SELECT");
+/// Executes a SQL statement on any `Backend` and displays the results, like
+/// `execute_sql_on`, keeping the rows returned by a write with `RETURNING` as well.
+///
+/// # Arguments
+///
+/// * `backend` - The database to run the statement against.
+/// * `sql` - The SQL string to execute.
+/// * `options` - A `QueryOptions` struct specifying the format, row limit, and other settings.
+/// * `last` - Receives the statement if it returns rows, or the rows returned by a write,
+///   for `.export`.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` on success, or an `Err` if the statement fails.
+pub fn execute_sql_with_last(
+    backend: &dyn Backend,
+    sql: &str,
+    options: &QueryOptions,
+    last: &std::sync::Arc<std::sync::Mutex<LastQuery>>,
+) -> Result<()> {
+    execute_sql_counted(backend, sql, options, last).map(|_| ())
+}
 
-    if is_select {
-        let mut last_query_guard = last_select_query.lock().unwrap();
-        last_query_guard.clear();
-        last_query_guard.push_str(sql);
-    }
+/// Executes and displays a statement like `execute_sql_on`, returning the number of
+/// rows it returned (up to the row limit) or changed.
+pub(crate) fn execute_sql_counted(
+    backend: &dyn Backend,
+    sql: &str,
+    options: &QueryOptions,
+    last_select_query: &std::sync::Arc<std::sync::Mutex<LastQuery>>,
+) -> Result<usize> {
+    let start_time = Instant::now();
 
-    if is_select {
-        // Get column names before executing the query
-        let column_names: Vec<String> = stmt
-            .column_names()
+    // Execute the query. Rows returned by a write are all kept for `.export`, since it
+    // can't run the statement again; the row limit only applies to what is shown.
+    let returns_written_rows = classify(sql) == StatementKind::Dml;
+    let fetch_limit = if returns_written_rows {
+        None
+    } else {
+        options.max_rows
+    };
+    let result = backend.run(sql, fetch_limit);
+    // `.once` applies to this query even if it fails
+    let once = {
+        let mut output = output_file().lock().unwrap();
+        match output.as_ref() {
+            Some(file) if file.once => output.take(),
+            _ => None,
+        }
+    };
+    let result = result?;
+    remember_query(last_select_query, sql, &result);
+
+    let count = if !result.columns.is_empty() {
+        let shown = options
+            .max_rows
+            .map_or(result.rows.len(), |limit| limit.min(result.rows.len()));
+        let all_rows: Vec<Vec<String>> = result.rows[..shown]
             .iter()
-            .map(|name| name.to_string())
+            .map(|row| row.iter().map(display_value).collect())
             .collect();
-
-        let mut rows = stmt.query([]).context("Failed to execute SELECT query")?;
-
-        // Collect all rows
-        let mut all_rows = Vec::new();
-        let mut row_count = 0;
-
-        while let Some(row) = rows.next()? {
-            let mut row_values = Vec::new();
-            for i in 0..column_names.len() {
-                let value = match row.get_ref(i)? {
-                    rusqlite::types::ValueRef::Null => "NULL".to_string(),
-                    rusqlite::types::ValueRef::Integer(val) => val.to_string(),
-                    rusqlite::types::ValueRef::Real(val) => val.to_string(),
-                    rusqlite::types::ValueRef::Text(val) => {
-                        String::from_utf8_lossy(val).to_string()
-                    }
-                    rusqlite::types::ValueRef::Blob(val) => {
-                        format!("<binary data: {} bytes>", val.len())
-                    }
-                };
-                row_values.push(value);
-            }
-            all_rows.push(row_values);
-            row_count += 1;
-
-            if let Some(limit) = options.max_rows {
-                if row_count >= limit {
-                    break;
-                }
-            }
-        }
+        let row_count = all_rows.len();
 
         // Display results based on format
         if !all_rows.is_empty() {
-            match options.format {
-                OutputFormat::Table => display_as_table(&column_names, &all_rows),
-                OutputFormat::Json => display_as_json(&column_names, &all_rows)?,
-                OutputFormat::Csv => display_as_csv(&column_names, &all_rows),
+            let mut once = once;
+            let mut output = output_file().lock().unwrap();
+            match once.as_mut().or(output.as_mut()) {
+                Some(file) => write_results(
+                    &mut file.writer,
+                    &options.format,
+                    &result.columns,
+                    &all_rows,
+                )
+                .or_else(ignore_broken_pipe)
+                .with_context(|| format!("Failed to write results to '{}'", file.path))?,
+                None => print_rows(&options.format, &result.columns, &all_rows)?,
             }
         }
 
-        println!("{} row(s) returned", row_count);
+        if !options.quiet {
+            println!("{} row(s) returned", row_count);
+        }
 
-        if let Some(limit) = options.max_rows {
+        if let Some(limit) = options.max_rows.filter(|_| !options.quiet) {
             if row_count >= limit {
                 println!(
                     "(Limited to {} rows. Use '.limit 0' to show all rows)",
@@ -263,112 +562,160 @@ pub fn execute_sql(conn: &Connection, sql: &str, options: &QueryOptions, last_se
                 );
             }
         }
+        *last_result().lock().unwrap() = Some(LastResult {
+            columns: result.columns,
+            rows: all_rows,
+        });
+        row_count
     } else {
         // For non-SELECT queries
-        let affected = stmt
-            .execute([])
-            .context("Failed to execute non-SELECT query")?;
-
-        println!("{} row(s) affected", affected);
-    }
+        if !options.quiet {
+            println!("{} row(s) affected", result.rows_affected);
+        }
+        result.rows_affected
+    };
 
-    if options.show_timing {
+    if options.show_timing && !options.quiet {
         println!(
             "Query executed in {:.3}ms",
             start_time.elapsed().as_secs_f64() * 1000.0
         );
     }
 
-    Ok(())
+    Ok(count)
 }
 
-/// Formats and prints query results as a bordered table to the console.
-fn display_as_table(column_names: &[String], rows: &[Vec<String>]) {
-    let mut table = Table::new();
-    table.set_format(*prettytable::format::consts::FORMAT_BOX_CHARS);
+/// The last statement that returned rows in a session, for `.export` and bookmarks.
+#[derive(Debug, Clone, Default)]
+pub struct LastQuery {
+    /// The last query that returned rows, to run again. It is empty after a write with a
+    /// `RETURNING` clause.
+    pub sql: String,
+    /// The rows returned by the last `INSERT`, `UPDATE`, or `DELETE ... RETURNING`, if that
+    /// was the last statement to return rows.
+    pub returned: Option<QueryResult>,
+}
+
+/// Remembers the last statement that returned rows, for `.export`.
+///
+/// Anything with result columns counts: `WITH ... SELECT`, `VALUES`, `PRAGMA`, and
+/// `EXPLAIN` are kept as SQL to run again. Writes with a `RETURNING` clause would repeat
+/// the write if run again, so their rows are kept instead and the SQL is cleared.
+pub(crate) fn remember_query(
+    last_select_query: &std::sync::Mutex<LastQuery>,
+    sql: &str,
+    result: &QueryResult,
+) {
+    if result.columns.is_empty() {
+        return;
+    }
+    let mut last = last_select_query.lock().unwrap();
+    if classify(sql) == StatementKind::Dml {
+        last.sql.clear();
+        last.returned = Some(result.clone());
+    } else {
+        last.sql = sql.to_string();
+        last.returned = None;
+    }
+}
+
+/// Prints rows to the console in the given format.
+///
+/// # Arguments
+///
+/// * `format` - The output format.
+/// * `column_names` - The column names.
+/// * `rows` - The rows to print.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` on success, or an `Err` if the rows can't be formatted.
+pub fn print_records(
+    format: &OutputFormat,
+    column_names: &[String],
+    rows: &[Record],
+) -> Result<()> {
+    let rows: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| row.iter().map(display_value).collect())
+        .collect();
+    print_rows(format, column_names, &rows)
+}
 
-    // Add header row
-    let mut header_row = prettytable::Row::empty();
-    for col_name in column_names {
-        header_row.add_cell(prettytable::Cell::new(col_name).style_spec("b"));
+fn print_rows(format: &OutputFormat, column_names: &[String], rows: &[Vec<String>]) -> Result<()> {
+    match format {
+        OutputFormat::Table => display_as_table(column_names, rows),
+        OutputFormat::Json => display_as_json(column_names, rows)?,
+        OutputFormat::Csv => display_as_csv(column_names, rows),
+        OutputFormat::Plain => print!("{}", format_plain(column_names, rows)),
     }
-    table.add_row(header_row);
+    Ok(())
+}
 
-    // Add data rows
-    for row_values in rows {
-        let mut data_row = prettytable::Row::empty();
-        for value in row_values {
-            data_row.add_cell(prettytable::Cell::new(value));
+/// Writes query results to an output file in the given format.
+fn write_results(
+    out: &mut dyn Write,
+    format: &OutputFormat,
+    column_names: &[String],
+    rows: &[Vec<String>],
+) -> Result<()> {
+    match format {
+        OutputFormat::Table => {
+            build_table(column_names, rows).print(out)?;
         }
-        table.add_row(data_row);
+        OutputFormat::Json => writeln!(out, "{}", format_json(column_names, rows)?)?,
+        OutputFormat::Csv => write!(out, "{}", format_csv(column_names, rows))?,
+        OutputFormat::Plain => write!(out, "{}", format_plain(column_names, rows))?,
     }
+    out.flush()?;
+    Ok(())
+}
 
-    table.printstd();
+/// Treats a command that stopped reading early, like `head`, as having taken the output.
+fn ignore_broken_pipe(e: anyhow::Error) -> Result<()> {
+    match e.downcast_ref::<std::io::Error>() {
+        Some(io) if io.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        _ => Err(e),
+    }
 }
 
-/// Formats and prints query results as a JSON object to the console.
+/// Formats and prints query results as a bordered table to the console.
 ///
-/// The JSON output includes the column names, the number of rows, and the data itself.
-/// It attempts to infer numeric types from the string values.
-fn display_as_json(column_names: &[String], rows: &[Vec<String>]) -> Result<()> {
-    let mut json_rows = Vec::new();
-
-    for row_values in rows {
-        let mut json_row = serde_json::Map::new();
-        for (i, value) in row_values.iter().enumerate() {
-            let json_value = if value == "NULL" {
-                Value::Null
-            } else if let Ok(int_val) = value.parse::<i64>() {
-                Value::Number(serde_json::Number::from(int_val))
-            } else if let Ok(float_val) = value.parse::<f64>() {
-                Value::Number(
-                    serde_json::Number::from_f64(float_val)
-                        .unwrap_or_else(|| serde_json::Number::from(0)),
-                )
-            } else {
-                Value::String(value.clone())
-            };
-            json_row.insert(column_names[i].clone(), json_value);
-        }
-        json_rows.push(Value::Object(json_row));
+/// Large values are cut short so they don't flood the terminal; files get them in full.
+fn display_as_table(column_names: &[String], rows: &[Vec<String>]) {
+    if !rows
+        .iter()
+        .flatten()
+        .any(|value| value.len() > LARGE_VALUE_SIZE)
+    {
+        build_table(column_names, rows).printstd();
+        return;
     }
+    let shortened: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|value| truncate_large_value(value).into_owned())
+                .collect()
+        })
+        .collect();
+    build_table(column_names, &shortened).printstd();
+}
 
-    let output = json!({
-        "data": json_rows,
-        "columns": column_names,
-        "row_count": rows.len()
-    });
-
-    println!("{}", serde_json::to_string_pretty(&output)?);
+/// Formats and prints query results as a JSON object to the console.
+fn display_as_json(column_names: &[String], rows: &[Vec<String>]) -> Result<()> {
+    println!("{}", format_json(column_names, rows)?);
     Ok(())
 }
 
 /// Formats and prints query results as CSV data to the console.
-///
-/// This function handles basic CSV escaping for values containing commas or quotes.
 fn display_as_csv(column_names: &[String], rows: &[Vec<String>]) {
-    // Print header
-    println!("{}", column_names.join(","));
-
-    // Print rows
-    for row_values in rows {
-        let escaped_values: Vec<String> = row_values
-            .iter()
-            .map(|v| {
-                if v.contains(',') || v.contains('"') || v.contains('\n') {
-                    format!("\"{}\"", v.replace('"', "\"\""))
-                } else {
-                    v.clone()
-                }
-            })
-            .collect();
-        println!("{}", escaped_values.join(","));
-    }
+    print!("{}", format_csv(column_names, rows));
 }
 
 /// Displays the schema for a specific table, including column names, types, and constraints.
 ///
-/// It uses `PRAGMA table_info` to retrieve the schema information from SQLite.
+/// It lists the columns `PRAGMA table_info` would, leaving out generated ones.
 ///
 /// # Arguments
 ///
@@ -379,13 +726,19 @@ fn display_as_csv(column_names: &[String], rows: &[Vec<String>]) {
 ///
 /// A `Result` which is `Ok(())` on success, or an `Err` on failure.
 pub fn show_table_schema(conn: &Connection, table_name: &str) -> Result<()> {
+    // Tables in attached databases are qualified with the database's alias
+    let (schema, table) = split_table_name(conn, table_name)?;
+
     // Check if the table exists
     let mut check_stmt = conn
-        .prepare("SELECT name FROM sqlite_master WHERE type='table' AND name = ?")
+        .prepare(&format!(
+            "SELECT name FROM {}.sqlite_master WHERE type='table' AND name = ?",
+            quote_identifier(&schema)
+        ))
         .context("Failed to prepare statement for checking table existence")?;
 
     let exists: bool = check_stmt
-        .exists(params![table_name])
+        .exists(params![table])
         .context(format!("Failed to check if table '{}' exists", table_name))?;
 
     if !exists {
@@ -394,71 +747,266 @@ pub fn show_table_schema(conn: &Connection, table_name: &str) -> Result<()> {
     }
 
     // Get the table schema
-    let pragma_sql = format!("PRAGMA table_info({})", table_name);
-    let mut stmt = conn.prepare(&pragma_sql).context(format!(
-        "Failed to prepare statement for table schema: {}",
-        table_name
-    ))?;
-
-    let columns = stmt
-        .query_map(params![], |row| {
-            Ok((
-                row.get::<_, i32>(0)?,            // cid
-                row.get::<_, String>(1)?,         // name
-                row.get::<_, String>(2)?,         // type
-                row.get::<_, bool>(3)?,           // notnull
-                row.get::<_, Option<String>>(4)?, // dflt_value
-                row.get::<_, i32>(5)?,            // pk
-            ))
-        })
-        .context(format!("Failed to query schema for table: {}", table_name))?;
+    let columns: Vec<ColumnDescription> = visible_columns(conn, Some(&schema), &table)
+        .context(format!("Failed to query schema for table: {}", table_name))?
+        .into_iter()
+        .map(ColumnDescription::from)
+        .collect();
 
-    // Create a pretty table for display
-    let mut table = Table::new();
-    table.set_format(*prettytable::format::consts::FORMAT_BOX_CHARS);
-    table.add_row(row![
-        "ID",
-        "Name",
-        "Type",
-        "Not Null",
-        "Default Value",
-        "Primary Key"
-    ]);
+    if columns.is_empty() {
+        println!("No columns found for table: {}", table_name);
+    } else {
+        println!("Schema for table '{}':", table_name);
+        schema_table(&columns).printstd();
+    }
 
-    let mut has_columns = false;
-    for column_result in columns {
-        has_columns = true;
-        let (cid, name, type_name, not_null, default_value, pk) = column_result.context(
-            format!("Failed to read column info for table: {}", table_name),
-        )?;
+    Ok(())
+}
 
-        let not_null_str = if not_null { "YES" } else { "NO" };
-        let pk_str = if pk > 0 { "YES" } else { "NO" };
-        let default_str = default_value.unwrap_or_else(|| "NULL".to_string());
+/// Displays a table's indexes, foreign keys, triggers, and CHECK constraints.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `table_name` - The table, qualified with an attached database's alias if needed.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` on success, or an `Err` on failure.
+pub fn show_table_constraints(conn: &Connection, table_name: &str) -> Result<()> {
+    let (schema, table) = split_table_name(conn, table_name)?;
+    let create_sql: Option<String> = conn
+        .query_row(
+            &format!(
+                "SELECT sql FROM {}.sqlite_master WHERE type = 'table' AND name = ?1",
+                quote_identifier(&schema)
+            ),
+            [&table],
+            |row| row.get(0),
+        )
+        .ok();
+    let Some(create_sql) = create_sql else {
+        return Ok(());
+    };
+
+    let sections = [
+        ("Indexes", describe_indexes(conn, &schema, &table)?),
+        (
+            "Foreign keys",
+            describe_foreign_keys(conn, &schema, &table)?,
+        ),
+        ("Triggers", describe_triggers(conn, &schema, &table)?),
+        ("Check constraints", check_constraints(&create_sql)),
+    ];
+    for (title, lines) in sections {
+        if lines.is_empty() {
+            println!("{}: none", title);
+        } else {
+            println!("{}:", title);
+            for line in lines {
+                println!("  {}", line);
+            }
+        }
+    }
+    Ok(())
+}
 
-        table.add_row(row![
-            cid,
-            name,
-            type_name,
-            not_null_str,
-            default_str,
-            pk_str
-        ]);
+/// Describes each index of a table as `name  UNIQUE  (columns)`.
+fn describe_indexes(conn: &Connection, schema: &str, table: &str) -> Result<Vec<String>> {
+    let schema = quote_identifier(schema);
+    let mut stmt = conn.prepare(&format!(
+        "PRAGMA {}.index_list({})",
+        schema,
+        quote_identifier(table)
+    ))?;
+    let indexes = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(1)?, // name
+                row.get::<_, bool>(2)?,   // unique
+                row.get::<_, String>(3)?, // origin: c (CREATE INDEX), u (UNIQUE), or pk
+                row.get::<_, bool>(4)?,   // partial
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut lines = Vec::new();
+    for (name, unique, origin, partial) in indexes.into_iter().rev() {
+        let mut stmt = conn.prepare(&format!(
+            "PRAGMA {}.index_info({})",
+            schema,
+            quote_identifier(&name)
+        ))?;
+        let columns = stmt
+            .query_map([], |row| row.get::<_, Option<String>>(2))?
+            .map(|column| column.map(|c| c.unwrap_or_else(|| "<expression>".to_string())))
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let mut line = name;
+        if unique {
+            line.push_str("  UNIQUE");
+        }
+        line.push_str(&format!("  ({})", columns.join(", ")));
+        match origin.as_str() {
+            "u" => line.push_str("  from a UNIQUE constraint"),
+            "pk" => line.push_str("  from the PRIMARY KEY"),
+            _ => {}
+        }
+        if partial {
+            line.push_str("  partial");
+        }
+        lines.push(line);
     }
+    Ok(lines)
+}
 
-    if has_columns {
-        println!("Schema for table '{}':", table_name);
-        table.printstd();
-    } else {
-        println!("No columns found for table: {}", table_name);
+/// Describes each foreign key of a table as `(columns) -> parent(columns)` with its actions.
+fn describe_foreign_keys(conn: &Connection, schema: &str, table: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!(
+        "PRAGMA {}.foreign_key_list({})",
+        quote_identifier(schema),
+        quote_identifier(table)
+    ))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,            // id
+                row.get::<_, String>(2)?,         // parent table
+                row.get::<_, String>(3)?,         // from
+                row.get::<_, Option<String>>(4)?, // to, or NULL for the parent's primary key
+                row.get::<_, String>(5)?,         // on_update
+                row.get::<_, String>(6)?,         // on_delete
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    // Each row is one column of a key; a key's columns share its id
+    let mut lines = Vec::new();
+    let mut i = 0;
+    while i < rows.len() {
+        let id = rows[i].0;
+        let key: Vec<_> = rows[i..].iter().take_while(|row| row.0 == id).collect();
+        let (_, parent, _, _, on_update, on_delete) = key[0];
+        let from: Vec<&str> = key.iter().map(|row| row.2.as_str()).collect();
+        let to: Vec<&str> = key.iter().filter_map(|row| row.3.as_deref()).collect();
+        let target = if to.is_empty() {
+            parent.clone()
+        } else {
+            format!("{}({})", parent, to.join(", "))
+        };
+        lines.push(format!(
+            "({}) -> {}  ON DELETE {}  ON UPDATE {}",
+            from.join(", "),
+            target,
+            on_delete,
+            on_update
+        ));
+        i += key.len();
     }
+    Ok(lines)
+}
 
-    Ok(())
+/// Describes each trigger on a table as `name: BEFORE|AFTER|INSTEAD OF event ON table`.
+fn describe_triggers(conn: &Connection, schema: &str, table: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT name, sql FROM {}.sqlite_master WHERE type = 'trigger' AND tbl_name = ?1 ORDER BY name",
+        quote_identifier(schema)
+    ))?;
+    let triggers = stmt
+        .query_map([table], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(triggers
+        .into_iter()
+        .map(|(name, sql)| {
+            // Show the part between the trigger's name and its body
+            let header = sql.split_whitespace().collect::<Vec<_>>().join(" ");
+            let upper = header.to_uppercase();
+            let end = upper.find(" BEGIN").unwrap_or(header.len());
+            let start = [" BEFORE ", " AFTER ", " INSTEAD OF "]
+                .iter()
+                .filter_map(|timing| upper.find(timing))
+                .min()
+                // Triggers without a timing run before the statement
+                .or_else(|| {
+                    [" INSERT ", " UPDATE ", " DELETE "]
+                        .iter()
+                        .filter_map(|event| upper.find(event))
+                        .min()
+                })
+                .unwrap_or(0);
+            format!("{}: {}", name, header[start..end].trim())
+        })
+        .collect())
+}
+
+/// Finds the CHECK constraints in a `CREATE TABLE` statement.
+fn check_constraints(sql: &str) -> Vec<String> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut checks = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            // Skip quoted strings and identifiers
+            quote @ ('\'' | '"' | '`' | '[') => {
+                let close = if quote == '[' { ']' } else { quote };
+                i += 1;
+                while i < chars.len() && chars[i] != close {
+                    i += 1;
+                }
+                i += 1;
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                if !word.eq_ignore_ascii_case("CHECK") {
+                    continue;
+                }
+                let mut j = i;
+                while j < chars.len() && chars[j].is_whitespace() {
+                    j += 1;
+                }
+                if chars.get(j) != Some(&'(') {
+                    continue;
+                }
+                let open = j;
+                let mut depth = 0;
+                while j < chars.len() {
+                    match chars[j] {
+                        '(' => depth += 1,
+                        ')' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                break;
+                            }
+                        }
+                        quote @ ('\'' | '"' | '`') => {
+                            j += 1;
+                            while j < chars.len() && chars[j] != quote {
+                                j += 1;
+                            }
+                        }
+                        _ => {}
+                    }
+                    j += 1;
+                }
+                let expression: String = chars[open..(j + 1).min(chars.len())].iter().collect();
+                checks.push(format!("CHECK {}", expression));
+                i = j + 1;
+            }
+            _ => i += 1,
+        }
+    }
+    checks
 }
 
 /// Iterates through all user-defined tables in the database and displays the schema for each one.
 ///
 /// It queries the `sqlite_master` table to find all tables and then calls `show_table_schema` for each.
+/// Tables in attached databases are included, qualified with the database's alias.
 ///
 /// # Arguments
 ///
@@ -468,35 +1016,157 @@ pub fn show_table_schema(conn: &Connection, table_name: &str) -> Result<()> {
 ///
 /// A `Result` which is `Ok(())` on success, or an `Err` on failure.
 pub fn show_all_schemas(conn: &Connection) -> Result<()> {
-    // Get all table names
-    let mut stmt = conn
-        .prepare("SELECT name FROM sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%'")
-        .context("Failed to prepare statement for listing tables")?;
-
-    let table_names = stmt
-        .query_map(params![], |row| row.get::<_, String>(0))
-        .context("Failed to query tables")?;
-
-    let mut has_tables = false;
-    for (i, table_name_result) in table_names.enumerate() {
-        has_tables = true;
-        let table_name = table_name_result.context("Failed to read table name")?;
+    let table_names = get_all_table_names(conn)?;
+    for (i, table_name) in table_names.iter().enumerate() {
         if i > 0 {
             println!();
         }
-        show_table_schema(conn, &table_name)?;
+        show_table_schema(conn, table_name)?;
     }
 
-    if !has_tables {
+    if table_names.is_empty() {
         println!("No tables found in the database.");
     }
 
     Ok(())
 }
 
+/// A summary of a database file and its contents, as shown by `.info`.
+///
+/// It serializes to JSON, so monitoring scripts can read it without parsing console text.
+/// Row counts are estimates unless it was collected with `exact` set; see `TableStats::estimated`.
+#[derive(Debug, Clone, Serialize)]
+pub struct DatabaseInfo {
+    pub path: String,
+    /// The size of the database file, or `None` if it has no file (e.g. `:memory:`).
+    pub size_bytes: Option<u64>,
+    pub sqlite_version: String,
+    pub page_size: i64,
+    pub page_count: i64,
+    /// Pages that are allocated but unused; `VACUUM` gives them back.
+    pub freelist_count: i64,
+    /// The version stored in `PRAGMA user_version`.
+    pub schema_version: i64,
+    pub journal_mode: String,
+    pub attached: Vec<AttachedDatabase>,
+    pub tables: Vec<TableStats>,
+    /// The sum of the tables' row counts, estimated if any of them is.
+    pub total_rows: i64,
+}
+
+/// A database attached to the connection with `ATTACH`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AttachedDatabase {
+    pub alias: String,
+    /// The attached file, or `:memory:` for an in-memory database.
+    pub file: String,
+}
+
+impl DatabaseInfo {
+    /// Prints the summary in the `.info` layout.
+    pub fn print(&self) {
+        println!("Database Information:");
+        println!("  Path: {}", self.path);
+        if let Some(size) = self.size_bytes {
+            println!("  Size: {:.2} MB", size as f64 / (1024.0 * 1024.0));
+        }
+        println!("  SQLite Version: {}", self.sqlite_version);
+        println!("  Page Size: {} bytes", self.page_size);
+        println!("  Page Count: {}", self.page_count);
+        println!("  Free Pages: {}", self.freelist_count);
+        println!("  Journal Mode: {}", self.journal_mode);
+        println!("  Schema Version: {}", self.schema_version);
+
+        if !self.attached.is_empty() {
+            println!("\nAttached Databases:");
+            for database in &self.attached {
+                println!("  {}: {}", database.alias, database.file);
+            }
+        }
+
+        println!("\nTable Statistics:");
+        let approximate = |estimated: bool| if estimated { "~" } else { "" };
+        for table in &self.tables {
+            println!(
+                "  {}: {}{} rows, {} index(es)",
+                table.name,
+                approximate(table.estimated),
+                table.rows,
+                table.indexes
+            );
+        }
+        let estimated = self.tables.iter().any(|table| table.estimated);
+        println!(
+            "  Total Rows: {}{}",
+            approximate(estimated),
+            self.total_rows
+        );
+        if estimated {
+            println!("  (~ marks estimates; use .info --exact to count every row)");
+        }
+    }
+
+    /// Serializes the summary as pretty-printed JSON.
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize database information")
+    }
+}
+
+/// Collects general information and statistics about the connected database.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `db_path` - The file path of the database, used to read its size.
+/// * `exact` - Count every table's rows with `COUNT(*)` instead of estimating them from
+///   `sqlite_stat1` or the largest rowid, which is much slower on large tables.
+///
+/// # Returns
+///
+/// A `Result` containing the `DatabaseInfo`.
+pub fn database_info(conn: &Connection, db_path: &str, exact: bool) -> Result<DatabaseInfo> {
+    let pragma = |name: &str| -> Result<i64> {
+        conn.query_row(&format!("PRAGMA {}", name), [], |row| row.get(0))
+            .with_context(|| format!("Failed to read PRAGMA {}", name))
+    };
+    let sqlite_version: String = conn.query_row("SELECT sqlite_version()", [], |row| row.get(0))?;
+    let journal_mode: String = conn.query_row("PRAGMA journal_mode", [], |row| row.get(0))?;
+    let attached = attached_databases(conn)?
+        .into_iter()
+        .map(|(alias, file)| AttachedDatabase {
+            alias,
+            file: if file.is_empty() {
+                ":memory:".to_string()
+            } else {
+                file
+            },
+        })
+        .collect();
+    let tables = collect_table_stats(conn, exact)?;
+    let total_rows = tables.iter().map(|table| table.rows).sum();
+
+    Ok(DatabaseInfo {
+        path: db_path.to_string(),
+        size_bytes: std::fs::metadata(db_path)
+            .ok()
+            .map(|metadata| metadata.len()),
+        sqlite_version,
+        page_size: pragma("page_size")?,
+        page_count: pragma("page_count")?,
+        freelist_count: pragma("freelist_count")?,
+        schema_version: pragma("user_version")?,
+        journal_mode,
+        attached,
+        tables,
+        total_rows,
+    })
+}
+
 /// Displays general information and statistics about the connected database.
 ///
-/// This includes the database file path, size, SQLite version, and row counts for each table.
+/// This includes the database file path, size, SQLite version, page statistics, journal mode,
+/// attached databases, and row and index counts for each table. Row counts are estimated, so
+/// this stays fast on very large tables; use `database_info` with `exact` for precise counts.
 ///
 /// # Arguments
 ///
@@ -507,43 +1177,440 @@ pub fn show_all_schemas(conn: &Connection) -> Result<()> {
 ///
 /// A `Result` which is `Ok(())` on success, or an `Err` on failure.
 pub fn show_database_info(conn: &Connection, db_path: &str) -> Result<()> {
-    println!("Database Information:");
-    println!("  Path: {}", db_path);
+    database_info(conn, db_path, false)?.print();
+    Ok(())
+}
+
+/// The row count, size, and index count of a table, as shown by `.counts`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TableStats {
+    /// The table, qualified with its database's alias if it is attached.
+    pub name: String,
+    pub rows: i64,
+    /// The bytes used by the table and its indexes, or `None` if SQLite was built
+    /// without the `dbstat` virtual table.
+    pub size_bytes: Option<i64>,
+    pub indexes: i64,
+    /// Whether `rows` is an estimate rather than an exact `COUNT(*)`.
+    pub estimated: bool,
+}
+
+/// Collects the row count, on-disk size, and index count of every table.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+///
+/// # Returns
+///
+/// A `Result` containing one entry per table, in `.tables` order.
+pub fn table_stats(conn: &Connection) -> Result<Vec<TableStats>> {
+    collect_table_stats(conn, true)
+}
+
+/// Estimates a table's row count without scanning it.
+///
+/// The estimate comes from `sqlite_stat1` when `ANALYZE` has been run, and otherwise from the
+/// largest rowid, which SQLite reads from the end of the table's b-tree. Deleted rows make
+/// both estimates high. Returns `None` for a `WITHOUT ROWID` table with no statistics.
+fn estimated_row_count(conn: &Connection, schema: &str, table: &str) -> Option<i64> {
+    let schema_name = quote_identifier(schema);
+    // Every row for a table starts with its row count; partial indexes count fewer rows
+    let analyzed = conn
+        .query_row(
+            &format!(
+                "SELECT MAX(CAST(stat AS INTEGER)) FROM {}.sqlite_stat1 WHERE tbl = ?1",
+                schema_name
+            ),
+            [table],
+            |row| row.get::<_, Option<i64>>(0),
+        )
+        .ok()
+        .flatten();
+    analyzed.or_else(|| {
+        conn.query_row(
+            &format!(
+                "SELECT COALESCE(MAX(rowid), 0) FROM {}.{}",
+                schema_name,
+                quote_identifier(table)
+            ),
+            [],
+            |row| row.get(0),
+        )
+        .ok()
+    })
+}
+
+/// Collects per-table statistics, counting rows exactly or estimating them.
+///
+/// Estimating skips the `dbstat` scan too, so `size_bytes` is `None` unless `exact` is set.
+fn collect_table_stats(conn: &Connection, exact: bool) -> Result<Vec<TableStats>> {
+    let mut stats = Vec::new();
+    for name in get_all_table_names(conn)? {
+        let (schema, table) = split_table_name(conn, &name)?;
+        let schema_name = quote_identifier(&schema);
+        let estimate = if exact {
+            None
+        } else {
+            estimated_row_count(conn, &schema, &table)
+        };
+        let rows: i64 = match estimate {
+            Some(rows) => rows,
+            None => conn.query_row(
+                &format!(
+                    "SELECT COUNT(*) FROM {}.{}",
+                    schema_name,
+                    quote_identifier(&table)
+                ),
+                [],
+                |row| row.get(0),
+            )?,
+        };
+        let indexes: i64 = conn.query_row(
+            &format!(
+                "SELECT COUNT(*) FROM {}.sqlite_master WHERE type = 'index' AND tbl_name = ?1",
+                schema_name
+            ),
+            [&table],
+            |row| row.get(0),
+        )?;
+        // dbstat is optional in SQLite builds, so sizes are left out when it is missing
+        let size_bytes = exact
+            .then(|| conn.query_row(
+                &format!(
+                    "SELECT SUM(pgsize) FROM dbstat(?1, 1) WHERE name IN (SELECT name FROM {}.sqlite_master WHERE tbl_name = ?2)",
+                    schema_name
+                ),
+                [&schema, &table],
+                |row| row.get::<_, Option<i64>>(0),
+            ))
+            .and_then(|size| size.ok())
+            .map(Option::unwrap_or_default);
+        stats.push(TableStats {
+            name,
+            rows,
+            size_bytes,
+            indexes,
+            estimated: estimate.is_some(),
+        });
+    }
+    Ok(stats)
+}
+
+/// Displays each table's row count, on-disk size, and number of indexes in one table.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` on success, or an `Err` on failure.
+pub fn show_table_counts(conn: &Connection) -> Result<()> {
+    let stats = table_stats(conn)?;
+    if stats.is_empty() {
+        println!("No tables found.");
+        return Ok(());
+    }
+    let mut table = Table::new();
+    table.set_format(*prettytable::format::consts::FORMAT_BOX_CHARS);
+    table.add_row(row![b => "Table", "Rows", "Size", "Indexes"]);
+    for stat in &stats {
+        table.add_row(row![
+            stat.name,
+            r -> stat.rows,
+            r -> stat.size_bytes.map_or("-".to_string(), format_size),
+            r -> stat.indexes
+        ]);
+    }
+    let total_size = stats
+        .iter()
+        .map(|stat| stat.size_bytes)
+        .sum::<Option<i64>>();
+    table.add_row(row![
+        b -> "Total",
+        rb -> stats.iter().map(|stat| stat.rows).sum::<i64>(),
+        rb -> total_size.map_or("-".to_string(), format_size),
+        rb -> stats.iter().map(|stat| stat.indexes).sum::<i64>()
+    ]);
+    table.printstd();
+    if total_size.is_none() {
+        println!("Sizes are not available: this SQLite build has no dbstat table.");
+    }
+    Ok(())
+}
 
-    // Get database file size
-    if let Ok(metadata) = std::fs::metadata(db_path) {
-        let size_mb = metadata.len() as f64 / (1024.0 * 1024.0);
-        println!("  Size: {:.2} MB", size_mb);
+/// Formats a byte count with a binary unit, such as `12.3 KB`.
+pub(crate) fn format_size(bytes: i64) -> String {
+    const UNITS: [&str; 3] = ["KB", "MB", "GB"];
+    if bytes < 1024 {
+        return format!("{} bytes", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
     }
+    format!("{:.1} {}", size, UNITS[unit])
+}
 
-    // Get SQLite version
-    let version: String = conn.query_row("SELECT sqlite_version()", [], |row| row.get(0))?;
-    println!("  SQLite Version: {}", version);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_stats() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT UNIQUE);
+             CREATE INDEX users_email ON users (email);
+             INSERT INTO users (email) VALUES ('a@example.com'), ('b@example.com');
+             CREATE TABLE empty (x);",
+        )
+        .unwrap();
+
+        let stats = table_stats(&conn).unwrap();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(
+            (stats[0].name.as_str(), stats[0].rows, stats[0].indexes),
+            ("empty", 0, 0)
+        );
+        assert_eq!(
+            (stats[1].name.as_str(), stats[1].rows, stats[1].indexes),
+            ("users", 2, 2)
+        );
+        assert!(stats[1].size_bytes.unwrap() > 0);
+        assert_eq!(format_size(512), "512 bytes");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MB");
+    }
 
-    // Get page size and page count
-    let page_size: i64 = conn.query_row("PRAGMA page_size", [], |row| row.get(0))?;
-    let page_count: i64 = conn.query_row("PRAGMA page_count", [], |row| row.get(0))?;
-    println!("  Page Size: {} bytes", page_size);
-    println!("  Page Count: {}", page_count);
+    #[test]
+    fn test_estimated_row_counts() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE events (id INTEGER PRIMARY KEY, kind TEXT);
+             CREATE INDEX events_kind ON events (kind);
+             WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 100)
+             INSERT INTO events SELECT i, 'k' || (i % 3) FROM n;
+             DELETE FROM events WHERE id % 4 = 1;",
+        )
+        .unwrap();
+        let rows = |exact: bool| {
+            let stats = collect_table_stats(&conn, exact).unwrap();
+            assert_eq!(stats[0].estimated, !exact);
+            stats[0].rows
+        };
+        assert_eq!(rows(true), 75);
+
+        // Without sqlite_stat1 the estimate is the largest rowid, high after deletes
+        assert_eq!(rows(false), 100);
+
+        // With sqlite_stat1 the estimate is the analyzed count, until rows change again
+        conn.execute_batch("ANALYZE").unwrap();
+        assert_eq!(rows(false), rows(true));
+        conn.execute("DELETE FROM events WHERE id > 90", [])
+            .unwrap();
+        assert_eq!((rows(false), rows(true)), (75, 67));
+
+        // A table missing from sqlite_stat1 falls back to the largest rowid
+        conn.execute_batch(
+            "DROP TABLE sqlite_stat1; ANALYZE sqlite_schema; CREATE TABLE later (x);",
+        )
+        .unwrap();
+        let analyzed: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_stat1 WHERE tbl = 'events'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(analyzed, 0);
+        let stats = collect_table_stats(&conn, false).unwrap();
+        assert_eq!((stats[0].rows, stats[1].rows), (90, 0));
+
+        // A WITHOUT ROWID table with no statistics is counted exactly
+        conn.execute_batch(
+            "DELETE FROM events;
+             CREATE TABLE codes (code TEXT PRIMARY KEY) WITHOUT ROWID;
+             INSERT INTO codes VALUES ('a'), ('b');",
+        )
+        .unwrap();
+        let stats = collect_table_stats(&conn, false).unwrap();
+        assert_eq!(stats[0].name, "codes");
+        assert_eq!((stats[0].rows, stats[0].estimated), (2, false));
+    }
 
-    // Get table statistics
-    let mut stmt = conn.prepare(
-        "SELECT name FROM sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%'",
-    )?;
-    let table_names = stmt.query_map([], |row| row.get::<_, String>(0))?;
+    #[test]
+    fn test_describe_constraints() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE orgs (id INTEGER PRIMARY KEY, code TEXT, region TEXT, UNIQUE (code, region));
+             CREATE TABLE users (
+                 id INTEGER PRIMARY KEY,
+                 org_id INTEGER REFERENCES orgs(id) ON DELETE CASCADE,
+                 email TEXT NOT NULL UNIQUE,
+                 age INTEGER CHECK (age >= 0 AND age < 150),
+                 note TEXT DEFAULT 'check (this)',
+                 CONSTRAINT adult CHECK (coalesce(age, 18) >= (18))
+             );
+             CREATE INDEX users_org ON users (org_id, lower(email)) WHERE org_id IS NOT NULL;
+             CREATE TRIGGER users_touch AFTER UPDATE OF email ON users BEGIN SELECT 1; END;",
+        )
+        .unwrap();
+
+        let indexes = describe_indexes(&conn, "main", "users").unwrap();
+        assert_eq!(
+            indexes,
+            [
+                "sqlite_autoindex_users_1  UNIQUE  (email)  from a UNIQUE constraint",
+                "users_org  (org_id, <expression>)  partial"
+            ]
+        );
+        assert_eq!(
+            describe_indexes(&conn, "main", "orgs").unwrap(),
+            ["sqlite_autoindex_orgs_1  UNIQUE  (code, region)  from a UNIQUE constraint"]
+        );
+        assert_eq!(
+            describe_foreign_keys(&conn, "main", "users").unwrap(),
+            ["(org_id) -> orgs(id)  ON DELETE CASCADE  ON UPDATE NO ACTION"]
+        );
+        assert_eq!(
+            describe_triggers(&conn, "main", "users").unwrap(),
+            ["users_touch: AFTER UPDATE OF email ON users"]
+        );
 
-    println!("\nTable Statistics:");
-    let mut total_rows = 0;
+        let sql: String = conn
+            .query_row(
+                "SELECT sql FROM sqlite_master WHERE name = 'users'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(
+            check_constraints(&sql),
+            [
+                "CHECK (age >= 0 AND age < 150)",
+                "CHECK (coalesce(age, 18) >= (18))"
+            ]
+        );
+    }
 
-    for table_name_result in table_names {
-        let table_name = table_name_result?;
-        let count_sql = format!("SELECT COUNT(*) FROM {}", table_name);
-        let row_count: i64 = conn.query_row(&count_sql, [], |row| row.get(0))?;
-        println!("  {}: {} rows", table_name, row_count);
-        total_rows += row_count;
+    #[test]
+    fn test_remember_row_returning_statements() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE t (x INTEGER); INSERT INTO t VALUES (1), (2);")
+            .unwrap();
+        let options = QueryOptions::default();
+        let last = std::sync::Arc::new(std::sync::Mutex::new(String::new()));
+
+        // Each statement kind that returns rows is shown and kept for `.export`
+        for sql in [
+            "SELECT x FROM t",
+            "VALUES (1, 'a'), (2, 'b')",
+            "PRAGMA table_info(t)",
+            "EXPLAIN SELECT x FROM t",
+            "EXPLAIN QUERY PLAN SELECT x FROM t",
+            "WITH doubled AS (SELECT x * 2 AS y FROM t) SELECT y FROM doubled",
+        ] {
+            execute_sql(&conn, sql, &options, &last).unwrap();
+            assert_eq!(*last.lock().unwrap(), sql);
+        }
+
+        // Statements without rows leave the last query alone
+        let kept = "SELECT x FROM t";
+        execute_sql(&conn, kept, &options, &last).unwrap();
+        for sql in [
+            "INSERT INTO t VALUES (3)",
+            "UPDATE t SET x = x + 1",
+            "CREATE TABLE u (y)",
+            "PRAGMA user_version = 3",
+        ] {
+            execute_sql(&conn, sql, &options, &last).unwrap();
+            assert_eq!(*last.lock().unwrap(), kept);
+        }
+
+        // remember_query goes by the result: a statement that returned no columns is
+        // not kept, and a write's returned rows are kept in place of its SQL
+        let last = std::sync::Mutex::new(LastQuery::default());
+        remember_query(&last, "DELETE FROM t", &QueryResult::default());
+        assert!(last.lock().unwrap().sql.is_empty());
+        assert!(last.lock().unwrap().returned.is_none());
+
+        let rows = conn.run("VALUES (1)", None).unwrap();
+        remember_query(&last, "VALUES (1)", &rows);
+        assert_eq!(last.lock().unwrap().sql, "VALUES (1)");
+
+        let returned = conn
+            .run("INSERT INTO t VALUES (4) RETURNING x", None)
+            .unwrap();
+        remember_query(&last, "INSERT INTO t VALUES (4) RETURNING x", &returned);
+        let last = last.lock().unwrap();
+        assert!(last.sql.is_empty());
+        assert_eq!(last.returned.as_ref().unwrap().rows.len(), 1);
     }
 
-    println!("  Total Rows: {}", total_rows);
+    #[test]
+    fn test_write_results_to_file() {
+        let columns = vec!["city".to_string(), "n".to_string()];
+        let rows = vec![vec!["東京, JP".to_string(), "1".to_string()]];
+
+        let mut out = Vec::new();
+        write_results(&mut out, &OutputFormat::Csv, &columns, &rows).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "city,n\n\"東京, JP\",1\n");
+
+        // Tables written to a file have no terminal styling
+        let mut out = Vec::new();
+        write_results(&mut out, &OutputFormat::Table, &columns, &rows).unwrap();
+        let table = String::from_utf8(out).unwrap();
+        assert!(table.contains("東京, JP") && !table.contains('\x1b'));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.txt");
+        let path = path.to_str().unwrap();
+        set_output_file(path, false).unwrap();
+        assert_eq!(output_path().as_deref(), Some(path));
+        reset_output();
+        assert_eq!(output_path(), None);
+    }
 
-    Ok(())
+    #[test]
+    fn test_truncate_large_value() {
+        assert_eq!(truncate_large_value("short"), "short");
+        let value = format!("{{\"data\": \"{}\"}}", "x".repeat(12_600));
+        assert_eq!(
+            truncate_large_value(&value),
+            format!(
+                "{{\"data\": \"{}[... 12.3 KB, use .cell to view]",
+                "x".repeat(LARGE_VALUE_PREVIEW - 10)
+            )
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_results_to_pipe() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("piped.txt");
+        let command = format!("grep Oslo > '{}'", path.display());
+        let mut child = spawn_piped(&command).unwrap();
+        let mut pipe = OutputFile {
+            path: format!("| {}", command),
+            writer: Box::new(child.stdin.take().unwrap()),
+            child: Some(child),
+            once: true,
+        };
+        let columns = vec!["city".to_string()];
+        let rows = vec![vec!["Bergen".to_string()], vec!["Oslo".to_string()]];
+        write_results(&mut pipe.writer, &OutputFormat::Plain, &columns, &rows).unwrap();
+
+        // Dropping the pipe waits for the command to finish
+        drop(pipe);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "Oslo\n");
+
+        // A command that stops reading early isn't an error
+        let error = anyhow::Error::from(std::io::Error::from(std::io::ErrorKind::BrokenPipe));
+        assert!(ignore_broken_pipe(error).is_ok());
+    }
 }
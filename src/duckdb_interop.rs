@@ -0,0 +1,451 @@
+//! # DuckDB Interoperability
+//!
+//! This module copies tables between SQLite and DuckDB database files, so datasets can be
+//! moved to DuckDB for analytics and back again without ad-hoc scripts.
+//!
+//! ## Features:
+//! - **SQLite to DuckDB**: `copy_sqlite_to_duckdb` recreates every user table (column
+//!   types, `NOT NULL` constraints, and primary keys) in a DuckDB file and bulk-loads the
+//!   rows with DuckDB's appender.
+//! - **DuckDB to SQLite**: `copy_duckdb_to_sqlite` does the reverse, mapping DuckDB's
+//!   richer types onto SQLite storage classes.
+//! - **All or Nothing**: Each direction runs in a single transaction on the target, and
+//!   refuses to overwrite tables that already exist there.
+//!
+//! This module requires the `duckdb` feature.
+
+use anyhow::{Context, Result};
+use duckdb::types::Value as DuckValue;
+use rusqlite::types::Value;
+use rusqlite::Connection;
+
+use crate::db::{get_table_names, visible_columns};
+use crate::formats::quote_identifier;
+
+/// A column type shared by both engines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ColumnType {
+    Integer,
+    Real,
+    Text,
+    Blob,
+}
+
+impl ColumnType {
+    /// Picks a type from a declared SQLite column type using SQLite's affinity rules.
+    fn from_sqlite(declared: &str) -> Self {
+        let declared = declared.to_uppercase();
+        if declared.contains("INT") {
+            ColumnType::Integer
+        } else if declared.contains("BLOB") {
+            ColumnType::Blob
+        } else if ["REAL", "FLOA", "DOUB", "NUMERIC", "DECIMAL"]
+            .iter()
+            .any(|t| declared.contains(t))
+        {
+            ColumnType::Real
+        } else {
+            ColumnType::Text
+        }
+    }
+
+    /// Picks a type from a DuckDB `data_type` name.
+    fn from_duckdb(data_type: &str) -> Self {
+        let data_type = data_type.to_uppercase();
+        if data_type.contains("INT") || data_type == "BOOLEAN" {
+            ColumnType::Integer
+        } else if ["FLOAT", "DOUBLE", "REAL", "DECIMAL"]
+            .iter()
+            .any(|t| data_type.starts_with(t))
+        {
+            ColumnType::Real
+        } else if data_type == "BLOB" {
+            ColumnType::Blob
+        } else {
+            ColumnType::Text
+        }
+    }
+
+    fn duckdb_name(self) -> &'static str {
+        match self {
+            ColumnType::Integer => "BIGINT",
+            ColumnType::Real => "DOUBLE",
+            ColumnType::Text => "VARCHAR",
+            ColumnType::Blob => "BLOB",
+        }
+    }
+
+    fn sqlite_name(self) -> &'static str {
+        match self {
+            ColumnType::Integer => "INTEGER",
+            ColumnType::Real => "REAL",
+            ColumnType::Text => "TEXT",
+            ColumnType::Blob => "BLOB",
+        }
+    }
+}
+
+/// A column definition read from the source database.
+struct ColumnDef {
+    name: String,
+    column_type: ColumnType,
+    not_null: bool,
+}
+
+/// A table definition read from the source database.
+struct TableDef {
+    name: String,
+    columns: Vec<ColumnDef>,
+    primary_key: Vec<String>,
+}
+
+impl TableDef {
+    /// Renders a `CREATE TABLE` statement using the given type names.
+    fn create_sql(&self, type_name: fn(ColumnType) -> &'static str) -> String {
+        let mut definitions: Vec<String> = self
+            .columns
+            .iter()
+            .map(|column| {
+                format!(
+                    "{} {}{}",
+                    quote_identifier(&column.name),
+                    type_name(column.column_type),
+                    if column.not_null { " NOT NULL" } else { "" }
+                )
+            })
+            .collect();
+        if !self.primary_key.is_empty() {
+            let key: Vec<String> = self
+                .primary_key
+                .iter()
+                .map(|c| quote_identifier(c))
+                .collect();
+            definitions.push(format!("PRIMARY KEY ({})", key.join(", ")));
+        }
+        format!(
+            "CREATE TABLE {} ({})",
+            quote_identifier(&self.name),
+            definitions.join(", ")
+        )
+    }
+}
+
+/// Copies every user table from a SQLite database into a DuckDB database file.
+///
+/// The DuckDB file is created if it does not exist. Tables are recreated with equivalent
+/// column types, `NOT NULL` constraints, and primary keys, and all rows are loaded in one
+/// transaction. Values that don't fit their column's declared type (SQLite allows this)
+/// are converted where possible and reported otherwise.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the source SQLite `Connection`.
+/// * `output` - The path of the DuckDB database file to write.
+///
+/// # Returns
+///
+/// A `Result` containing the name and row count of each copied table, or an `Err` if a
+/// table already exists in the DuckDB file or a value cannot be converted.
+pub fn copy_sqlite_to_duckdb(conn: &Connection, output: &str) -> Result<Vec<(String, usize)>> {
+    let tables = get_table_names(conn)?
+        .iter()
+        .map(|name| sqlite_table_def(conn, name))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut duck = duckdb::Connection::open(output)
+        .with_context(|| format!("Failed to open DuckDB database '{}'", output))?;
+    let existing = duckdb_table_names(&duck)?;
+    if let Some(table) = tables.iter().find(|t| existing.contains(&t.name)) {
+        anyhow::bail!(
+            "Table '{}' already exists in DuckDB database '{}'",
+            table.name,
+            output
+        );
+    }
+
+    let tx = duck.transaction()?;
+    let mut copied = Vec::new();
+    for table in &tables {
+        tx.execute_batch(&table.create_sql(ColumnType::duckdb_name))
+            .with_context(|| format!("Failed to create table '{}' in DuckDB", table.name))?;
+
+        let columns: Vec<String> = table
+            .columns
+            .iter()
+            .map(|c| quote_identifier(&c.name))
+            .collect();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM {}",
+            columns.join(", "),
+            quote_identifier(&table.name)
+        ))?;
+        let mut rows = stmt.query([])?;
+        let mut appender = tx.appender(&table.name)?;
+        let mut count = 0;
+        while let Some(row) = rows.next()? {
+            let values = table
+                .columns
+                .iter()
+                .enumerate()
+                .map(|(i, column)| {
+                    to_duckdb_value(row.get(i)?, column.column_type).with_context(|| {
+                        format!("Invalid value in column '{}.{}'", table.name, column.name)
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            appender.append_row(duckdb::appender_params_from_iter(values))?;
+            count += 1;
+        }
+        appender
+            .flush()
+            .with_context(|| format!("Failed to load rows into DuckDB table '{}'", table.name))?;
+        copied.push((table.name.clone(), count));
+    }
+    tx.commit().context("Failed to commit DuckDB transaction")?;
+    Ok(copied)
+}
+
+/// Copies every table in a DuckDB database file into a SQLite database.
+///
+/// Integer and boolean columns become `INTEGER`, floating point and decimal columns become
+/// `REAL`, `BLOB` stays `BLOB`, and everything else (dates, timestamps, nested types) is
+/// stored as DuckDB's text representation.
+///
+/// # Arguments
+///
+/// * `input` - The path of the DuckDB database file to read.
+/// * `conn` - A mutable reference to the target SQLite `Connection`.
+///
+/// # Returns
+///
+/// A `Result` containing the name and row count of each copied table, or an `Err` if a
+/// table already exists in the SQLite database or the copy fails.
+pub fn copy_duckdb_to_sqlite(input: &str, conn: &mut Connection) -> Result<Vec<(String, usize)>> {
+    if !std::path::Path::new(input).exists() {
+        anyhow::bail!("DuckDB database '{}' not found", input);
+    }
+    let duck = duckdb::Connection::open(input)
+        .with_context(|| format!("Failed to open DuckDB database '{}'", input))?;
+    let tables = duckdb_table_names(&duck)?
+        .iter()
+        .map(|name| duckdb_table_def(&duck, name))
+        .collect::<Result<Vec<_>>>()?;
+
+    let existing = get_table_names(conn)?;
+    if let Some(table) = tables.iter().find(|t| existing.contains(&t.name)) {
+        anyhow::bail!(
+            "Table '{}' already exists in the SQLite database",
+            table.name
+        );
+    }
+
+    let tx = conn.transaction()?;
+    let mut copied = Vec::new();
+    for table in &tables {
+        tx.execute_batch(&table.create_sql(ColumnType::sqlite_name))
+            .with_context(|| format!("Failed to create table '{}'", table.name))?;
+
+        // Casting in DuckDB leaves only values that map directly onto SQLite storage classes
+        let select: Vec<String> = table
+            .columns
+            .iter()
+            .map(|column| {
+                let name = quote_identifier(&column.name);
+                match column.column_type {
+                    ColumnType::Blob => name,
+                    column_type => format!("CAST({} AS {})", name, column_type.duckdb_name()),
+                }
+            })
+            .collect();
+        let mut source = duck.prepare(&format!(
+            "SELECT {} FROM {}",
+            select.join(", "),
+            quote_identifier(&table.name)
+        ))?;
+        let mut insert = tx.prepare(&format!(
+            "INSERT INTO {} VALUES ({})",
+            quote_identifier(&table.name),
+            vec!["?"; table.columns.len()].join(", ")
+        ))?;
+
+        let mut rows = source.query([])?;
+        let mut count = 0;
+        while let Some(row) = rows.next()? {
+            let values = (0..table.columns.len())
+                .map(|i| Ok(from_duckdb_value(row.get(i)?)))
+                .collect::<Result<Vec<_>>>()?;
+            insert
+                .execute(rusqlite::params_from_iter(values))
+                .with_context(|| format!("Failed to insert into table '{}'", table.name))?;
+            count += 1;
+        }
+        copied.push((table.name.clone(), count));
+    }
+    tx.commit()?;
+    Ok(copied)
+}
+
+/// Reads a table's columns and primary key from SQLite.
+fn sqlite_table_def(conn: &Connection, table: &str) -> Result<TableDef> {
+    let mut primary_key = Vec::new();
+    let mut columns = Vec::new();
+    for column in visible_columns(conn, None, table)? {
+        if column.primary_key > 0 {
+            primary_key.push((column.primary_key, column.name.clone()));
+        }
+        columns.push(ColumnDef {
+            column_type: ColumnType::from_sqlite(&column.decl_type),
+            not_null: column.not_null,
+            name: column.name,
+        });
+    }
+    primary_key.sort();
+    Ok(TableDef {
+        name: table.to_string(),
+        columns,
+        primary_key: primary_key.into_iter().map(|(_, name)| name).collect(),
+    })
+}
+
+/// Lists the base tables in DuckDB's `main` schema.
+fn duckdb_table_names(duck: &duckdb::Connection) -> Result<Vec<String>> {
+    let mut stmt = duck.prepare(
+        "SELECT table_name FROM information_schema.tables \
+         WHERE table_schema = 'main' AND table_type = 'BASE TABLE' ORDER BY table_name",
+    )?;
+    let names = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<duckdb::Result<Vec<_>>>()
+        .context("Failed to list DuckDB tables")?;
+    Ok(names)
+}
+
+/// Reads a table's columns and primary key from DuckDB.
+fn duckdb_table_def(duck: &duckdb::Connection, table: &str) -> Result<TableDef> {
+    let mut stmt = duck.prepare(
+        "SELECT column_name, data_type, is_nullable FROM information_schema.columns \
+         WHERE table_schema = 'main' AND table_name = ? ORDER BY ordinal_position",
+    )?;
+    let columns = stmt
+        .query_map([table], |row| {
+            Ok(ColumnDef {
+                name: row.get(0)?,
+                column_type: ColumnType::from_duckdb(&row.get::<_, String>(1)?),
+                not_null: row.get::<_, String>(2)? == "NO",
+            })
+        })?
+        .collect::<duckdb::Result<Vec<_>>>()
+        .with_context(|| format!("Failed to read columns of DuckDB table '{}'", table))?;
+
+    let mut stmt = duck.prepare(
+        "SELECT constraint_column_names FROM duckdb_constraints() \
+         WHERE schema_name = 'main' AND table_name = ? AND constraint_type = 'PRIMARY KEY'",
+    )?;
+    let mut primary_key = Vec::new();
+    let mut rows = stmt.query([table])?;
+    if let Some(row) = rows.next()? {
+        if let DuckValue::List(names) = row.get::<_, DuckValue>(0)? {
+            for name in names {
+                if let DuckValue::Text(name) = name {
+                    primary_key.push(name);
+                }
+            }
+        }
+    }
+
+    Ok(TableDef {
+        name: table.to_string(),
+        columns,
+        primary_key,
+    })
+}
+
+/// Converts a SQLite value into a DuckDB value for a column of the given type.
+fn to_duckdb_value(value: Value, column_type: ColumnType) -> Result<DuckValue> {
+    Ok(match (value, column_type) {
+        (Value::Null, _) => DuckValue::Null,
+        (Value::Integer(i), ColumnType::Integer) => DuckValue::BigInt(i),
+        (Value::Integer(i), ColumnType::Real) => DuckValue::Double(i as f64),
+        (Value::Real(f), ColumnType::Real) => DuckValue::Double(f),
+        (Value::Real(f), ColumnType::Integer) if f.fract() == 0.0 => DuckValue::BigInt(f as i64),
+        (Value::Text(s), ColumnType::Integer) => DuckValue::BigInt(
+            s.trim()
+                .parse()
+                .with_context(|| format!("'{}' is not an integer", s))?,
+        ),
+        (Value::Text(s), ColumnType::Real) => DuckValue::Double(
+            s.trim()
+                .parse()
+                .with_context(|| format!("'{}' is not a number", s))?,
+        ),
+        (Value::Integer(i), ColumnType::Text) => DuckValue::Text(i.to_string()),
+        (Value::Real(f), ColumnType::Text) => DuckValue::Text(f.to_string()),
+        (Value::Text(s), ColumnType::Text) => DuckValue::Text(s),
+        (Value::Text(s), ColumnType::Blob) => DuckValue::Blob(s.into_bytes()),
+        (Value::Blob(b), ColumnType::Blob) => DuckValue::Blob(b),
+        (Value::Blob(b), ColumnType::Text) => {
+            DuckValue::Text(String::from_utf8(b).context("Binary value is not valid UTF-8 text")?)
+        }
+        (value, column_type) => anyhow::bail!(
+            "Cannot store {:?} in a {} column",
+            value.data_type(),
+            column_type.duckdb_name()
+        ),
+    })
+}
+
+/// Converts a DuckDB value (already cast to a SQLite-compatible type) into a SQLite value.
+fn from_duckdb_value(value: DuckValue) -> Value {
+    match value {
+        DuckValue::Null => Value::Null,
+        DuckValue::Boolean(b) => Value::Integer(b as i64),
+        DuckValue::BigInt(i) => Value::Integer(i),
+        DuckValue::Double(f) => Value::Real(f),
+        DuckValue::Blob(b) => Value::Blob(b),
+        DuckValue::Text(s) => Value::Text(s),
+        other => Value::Text(format!("{:?}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duckdb_roundtrip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let duck_path = temp_dir.path().join("data.duckdb");
+        let duck_path = duck_path.to_str().unwrap();
+
+        let source = Connection::open_in_memory().unwrap();
+        source
+            .execute_batch(
+                "CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT NOT NULL, price REAL, data BLOB);
+                 INSERT INTO items VALUES (1, 'apple', 1.5, x'00ff'), (2, 'pear', NULL, NULL);",
+            )
+            .unwrap();
+        let copied = copy_sqlite_to_duckdb(&source, duck_path).unwrap();
+        assert_eq!(copied, vec![("items".to_string(), 2)]);
+
+        let mut target = Connection::open_in_memory().unwrap();
+        let copied = copy_duckdb_to_sqlite(duck_path, &mut target).unwrap();
+        assert_eq!(copied, vec![("items".to_string(), 2)]);
+
+        let rows: Vec<(i64, String, Option<f64>)> = target
+            .prepare("SELECT id, name, price FROM items ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(rows[0], (1, "apple".to_string(), Some(1.5)));
+        assert_eq!(rows[1], (2, "pear".to_string(), None));
+        let data: Vec<u8> = target
+            .query_row("SELECT data FROM items WHERE id = 1", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(data, vec![0, 255]);
+
+        // Copying again refuses to overwrite the existing tables
+        assert!(copy_sqlite_to_duckdb(&source, duck_path).is_err());
+    }
+}
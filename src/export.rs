@@ -1,30 +1,273 @@
 //! # Data Import and Export
 //!
 //! This module provides functionality for importing data into and exporting data from the
-//! SQLite database. It currently focuses on the CSV format, which is a common and
-//! versatile format for data interchange.
+//! SQLite database. It focuses on the CSV format, which is a common and versatile
+//! format for data interchange, and also supports Excel spreadsheets for export.
 //!
 //! ## Key Functions:
 //! - `import_csv_to_table`: Imports data from a CSV file into a specified database table.
+//! - `import_csv_to_table_with_options`: Imports a CSV file with a custom dialect
+//!   (delimiter, quoting and escapes, line endings, header, `NULL` marker, skipped lines,
+//!   and encoding), which `sniff::sniff_csv_file` can detect from the file. Legacy
+//!   encodings (Latin-1, Windows-1252, Shift_JIS) are transcoded to UTF-8, and a leading
+//!   UTF-8 byte order mark is skipped.
 //! - `export_to_csv`: Exports the results of a SQL query to a CSV file.
+//! - `export_to_xlsx`: Exports the results of a SQL query to an Excel (`.xlsx`) workbook.
+//! - `export_tables_to_xlsx`: Exports whole tables to a workbook, one sheet per table.
+//! - `export_database_to_csv_dir`: Exports every table to a directory of CSV files with
+//!   a `manifest.json` describing them.
+//! - `export_to_avro`: Exports query results to an Avro container file (`avro` feature).
+//!
+//! CSV exports are transparently compressed when the file name ends in `.gz` or `.zst`,
+//! or when a compression is requested through `ExportOptions`. Large uncompressed CSV
+//! exports write a `<file>.checkpoint` every 10,000 rows, so an interrupted export can
+//! be resumed with `ExportOptions::resume` instead of starting over. CSV imports accept gzip,
+//! zstd, and single-file zip inputs, detected from the file contents. Imports committed in
+//! chunks write a `<file>.import-checkpoint` after each chunk, so one that fails can be
+//! resumed after its last committed chunk.
 //!
 //! The module includes robust error handling, input validation, and progress indicators
 //! for long-running operations to ensure a reliable user experience.
 
 use anyhow::{Context, Result};
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
 use rusqlite::Connection;
+use rust_xlsxwriter::{ExcelDateTime, Format, Workbook, Worksheet};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
 use std::path::Path;
+use std::time::Instant;
+
+use crate::compression::{create_output, open_input, Compression, OutputWriter, STDIN_PATH};
+use crate::db::{get_table_names, visible_columns};
+use crate::formats::quote_identifier;
+use crate::import::{set_column_values, ColumnSetting, ConflictPolicy, ImportReport};
+use crate::progress::ProgressTracker;
+
+/// Options controlling how query results are written to an export file.
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    /// The compression to apply. `None` detects it from the file extension.
+    pub compression: Option<Compression>,
+    /// Continue an interrupted CSV export from its checkpoint file instead of starting
+    /// over. Only uncompressed CSV output can be resumed.
+    pub resume: bool,
+    /// Start CSV output with a UTF-8 byte order mark, which Excel needs to detect that
+    /// a CSV file is UTF-8.
+    pub bom: bool,
+    /// Don't print a summary when the export is done, for exports running in the
+    /// background or callers that report the row count themselves.
+    pub quiet: bool,
+}
+
+/// The UTF-8 byte order mark.
+pub const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// The maximum number of rows (including the header) an Excel worksheet can hold.
+pub(crate) const XLSX_MAX_ROWS: usize = 1_048_576;
+
+/// Text encodings supported for CSV imports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextEncoding {
+    #[default]
+    Utf8,
+    /// ISO-8859-1, where every byte maps directly to a Unicode code point.
+    Latin1,
+    /// Windows-1252, the Western European default on Windows. It differs from Latin-1
+    /// in 0x80-0x9F, which hold characters such as `€` and curly quotes.
+    Windows1252,
+    /// Shift_JIS, as commonly written by Japanese versions of Excel.
+    ShiftJis,
+}
+
+impl TextEncoding {
+    /// Parses an encoding name such as `utf-8`, `latin-1`, `windows-1252` (or `cp1252`),
+    /// or `shift-jis` (or `sjis`).
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().replace('_', "-").as_str() {
+            "utf-8" | "utf8" => Ok(TextEncoding::Utf8),
+            "latin-1" | "latin1" | "iso-8859-1" => Ok(TextEncoding::Latin1),
+            "windows-1252" | "cp1252" => Ok(TextEncoding::Windows1252),
+            "shift-jis" | "shiftjis" | "sjis" | "cp932" => Ok(TextEncoding::ShiftJis),
+            other => anyhow::bail!(
+                "Unknown encoding '{}'. Available: utf-8, latin-1, windows-1252, shift-jis",
+                other
+            ),
+        }
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<String> {
+        let (encoding, name) = match self {
+            TextEncoding::Utf8 => {
+                return Ok(std::str::from_utf8(bytes)
+                    .context("Input is not valid UTF-8. Try the latin-1, windows-1252, or shift-jis encoding.")?
+                    .to_string())
+            }
+            TextEncoding::Latin1 => return Ok(bytes.iter().map(|&b| b as char).collect()),
+            TextEncoding::Windows1252 => (encoding_rs::WINDOWS_1252, "Windows-1252"),
+            TextEncoding::ShiftJis => (encoding_rs::SHIFT_JIS, "Shift_JIS"),
+        };
+        let (text, had_errors) = encoding.decode_without_bom_handling(bytes);
+        if had_errors {
+            anyhow::bail!("Input is not valid {}", name);
+        }
+        Ok(text.into_owned())
+    }
+}
+
+/// Skips a UTF-8 byte order mark at the start of `reader`, if there is one.
+///
+/// # Returns
+///
+/// `Ok(true)` if a byte order mark was skipped.
+pub(crate) fn skip_utf8_bom(reader: &mut impl BufRead) -> std::io::Result<bool> {
+    let found = reader.fill_buf()?.starts_with(UTF8_BOM);
+    if found {
+        reader.consume(UTF8_BOM.len());
+    }
+    Ok(found)
+}
+
+/// Writes a UTF-8 byte order mark to a new CSV export if `options` asks for one.
+pub(crate) fn write_bom(output: &mut dyn Write, options: &ExportOptions) -> Result<()> {
+    if options.bom {
+        output
+            .write_all(UTF8_BOM)
+            .context("Failed to write the byte order mark")?;
+    }
+    Ok(())
+}
+
+/// The line endings that end a CSV record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    /// `\r\n` or `\n`, or a lone `\r`.
+    #[default]
+    Crlf,
+    /// Only `\n`, so a `\r` before it is kept as part of the last field.
+    Lf,
+    /// Only `\r`, as written by classic Mac OS applications.
+    Cr,
+}
+
+impl LineEnding {
+    /// Parses a line ending name: `crlf` (or `auto`), `lf`, or `cr`.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "crlf" | "auto" => Ok(LineEnding::Crlf),
+            "lf" => Ok(LineEnding::Lf),
+            "cr" => Ok(LineEnding::Cr),
+            other => anyhow::bail!("Unknown line ending '{}'. Available: crlf, lf, cr", other),
+        }
+    }
+
+    fn terminator(&self) -> csv::Terminator {
+        match self {
+            LineEnding::Crlf => csv::Terminator::CRLF,
+            LineEnding::Lf => csv::Terminator::Any(b'\n'),
+            LineEnding::Cr => csv::Terminator::Any(b'\r'),
+        }
+    }
+
+    /// The byte that ends a line, for skipping leading lines.
+    fn line_end(&self) -> u8 {
+        match self {
+            LineEnding::Cr => b'\r',
+            _ => b'\n',
+        }
+    }
+}
+
+/// Options describing the dialect of a CSV file being imported.
+#[derive(Debug, Clone)]
+pub struct CsvImportOptions {
+    /// The field delimiter.
+    pub delimiter: u8,
+    /// The quote character.
+    pub quote: u8,
+    /// The character that escapes a quote inside a quoted field, such as `\`. Without
+    /// one, quotes are escaped by doubling them (`""`).
+    pub escape: Option<u8>,
+    /// The line endings that end a record.
+    pub line_ending: LineEnding,
+    /// Whether the first (non-skipped) line holds column names. Without a header,
+    /// fields are mapped to the table's columns by position.
+    pub has_header: bool,
+    /// A field value to import as `NULL`, such as `NA` or `\N`.
+    pub null_string: Option<String>,
+    /// The number of leading lines to skip before the header or data.
+    pub skip_lines: usize,
+    /// The text encoding of the file.
+    pub encoding: TextEncoding,
+    /// Commit every `batch_size` rows instead of running one transaction for the whole
+    /// file. Rows committed before a failure stay in the table, and a checkpoint lets
+    /// `resume` continue after them.
+    pub batch_size: Option<usize>,
+    /// Continue a chunked import that failed, after the rows it committed, using the
+    /// `<file>.import-checkpoint` it left behind.
+    pub resume: bool,
+    /// What to do with rows that collide with an existing row.
+    pub on_conflict: ConflictPolicy,
+    /// Values given to columns for every row. The columns can't also be in the file.
+    pub set_columns: Vec<ColumnSetting>,
+    /// Skip rows that fail to parse or insert instead of aborting the import.
+    pub continue_on_error: bool,
+    /// A CSV file that receives skipped rows along with an `_error` column.
+    pub reject_file: Option<String>,
+}
+
+impl Default for CsvImportOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            quote: b'"',
+            escape: None,
+            line_ending: LineEnding::Crlf,
+            has_header: true,
+            null_string: None,
+            skip_lines: 0,
+            encoding: TextEncoding::Utf8,
+            batch_size: None,
+            resume: false,
+            on_conflict: ConflictPolicy::Fail,
+            set_columns: Vec::new(),
+            continue_on_error: false,
+            reject_file: None,
+        }
+    }
+}
+
+/// Parses a single-byte CSV delimiter or quote character.
+///
+/// Accepts a literal ASCII character or one of the names `tab` (or `\t`), `comma`,
+/// `semicolon`, `pipe`, `space`, and `backslash`.
+pub fn parse_csv_char(value: &str) -> Result<u8> {
+    match value {
+        "tab" | "\\t" | "\t" => Ok(b'\t'),
+        "comma" => Ok(b','),
+        "semicolon" => Ok(b';'),
+        "pipe" => Ok(b'|'),
+        "space" => Ok(b' '),
+        "backslash" => Ok(b'\\'),
+        _ if value.len() == 1 && value.is_ascii() => Ok(value.as_bytes()[0]),
+        _ => anyhow::bail!(
+            "Invalid CSV character '{}'. Use a single ASCII character or tab, comma, semicolon, pipe, space, backslash",
+            value
+        ),
+    }
+}
 
 /// Imports data from a CSV file into a specified database table.
 ///
 /// This function reads a CSV file, using the header row to map columns to the
 /// corresponding columns in the target table. The entire import process is wrapped
-/// in a single database transaction to ensure atomicity.
+/// in a single database transaction to ensure atomicity. Compressed files (gzip, zstd,
+/// or a zip archive holding one CSV) are decompressed on the fly.
 ///
 /// # Arguments
 ///
 /// * `conn` - A mutable reference to the active `rusqlite::Connection`.
-/// * `file_path` - The path to the CSV file to be imported.
+/// * `file_path` - The path to the CSV file to be imported, or `-` for standard input.
 /// * `table_name` - The name of the database table to insert data into.
 ///
 /// # Returns
@@ -32,39 +275,277 @@ use std::path::Path;
 /// A `Result` which is `Ok(())` on successful import, or an `Err` if the file cannot
 /// be read, the CSV is malformed, or the database insertion fails.
 pub fn import_csv_to_table(conn: &mut Connection, file_path: &str, table_name: &str) -> Result<()> {
-    let file = Path::new(file_path);
-    if !file.exists() {
+    import_csv_to_table_with_options(conn, file_path, table_name, &CsvImportOptions::default())?;
+    Ok(())
+}
+
+/// Imports data from a CSV file into a table using a custom CSV dialect.
+///
+/// This behaves like `import_csv_to_table`, but allows a different delimiter and quote
+/// character, files without a header row, a marker string for `NULL` values, skipping
+/// leading lines (such as report titles), and Latin-1 encoded input. Large files can be
+/// committed in chunks with progress output, and bad rows can be skipped and collected
+/// in a reject file instead of aborting the import.
+///
+/// # Arguments
+///
+/// * `conn` - A mutable reference to the active `rusqlite::Connection`.
+/// * `file_path` - The path to the CSV file to be imported, or `-` for standard input.
+/// * `table_name` - The name of the database table to insert data into.
+/// * `options` - A `CsvImportOptions` struct describing the CSV dialect.
+///
+/// # Returns
+///
+/// A `Result` containing an `ImportReport` with the imported and skipped row counts, or
+/// an `Err` if the file cannot be read or a row fails without `continue_on_error`.
+pub fn import_csv_to_table_with_options(
+    conn: &mut Connection,
+    file_path: &str,
+    table_name: &str,
+    options: &CsvImportOptions,
+) -> Result<ImportReport> {
+    if file_path != STDIN_PATH && !Path::new(file_path).exists() {
         anyhow::bail!("File not found: {}", file_path);
     }
+    let checkpoint = ImportCheckpoint::for_import(file_path, table_name, options.resume)?;
 
-    let mut rdr = csv::Reader::from_path(file_path)?;
-    let headers = rdr.headers()?.clone();
+    let mut input = BufReader::new(open_input(file_path)?);
+    skip_utf8_bom(&mut input)?;
+    let mut skipped = Vec::new();
+    for _ in 0..options.skip_lines {
+        skipped.clear();
+        if input.read_until(options.line_ending.line_end(), &mut skipped)? == 0 {
+            break;
+        }
+    }
 
-    let tx = conn.transaction()?;
+    // Rows with the wrong number of fields are rejected individually when continuing on errors
+    let mut rdr = csv::ReaderBuilder::new()
+        .delimiter(options.delimiter)
+        .quote(options.quote)
+        .escape(options.escape)
+        .double_quote(options.escape.is_none())
+        .terminator(options.line_ending.terminator())
+        .has_headers(options.has_header)
+        .flexible(options.continue_on_error)
+        .from_reader(input);
+    // The columns rows can be inserted into, needed without a header row or with --set
+    let columns: Vec<String> = if !options.has_header || !options.set_columns.is_empty() {
+        let columns = visible_columns(conn, None, table_name)?;
+        if columns.is_empty() {
+            anyhow::bail!("Table '{}' does not exist", table_name);
+        }
+        columns.into_iter().map(|column| column.name).collect()
+    } else {
+        Vec::new()
+    };
+    let headers: Vec<String> = if options.has_header {
+        rdr.byte_headers()?
+            .iter()
+            .map(|h| options.encoding.decode(h))
+            .collect::<Result<_>>()
+            .context("Failed to decode CSV header")?
+    } else {
+        // Fields map onto the table's columns in order, leaving out those set with --set
+        columns
+            .iter()
+            .filter(|column| {
+                !options
+                    .set_columns
+                    .iter()
+                    .any(|setting| setting.column.eq_ignore_ascii_case(column))
+            })
+            .cloned()
+            .collect()
+    };
 
-    {
-        let sql = format!(
-            "INSERT INTO {} ({}) VALUES ({})",
-            table_name,
-            headers
+    let (set_names, set_values) = if options.set_columns.is_empty() {
+        (Vec::new(), Vec::new())
+    } else {
+        if let Some(setting) = options.set_columns.iter().find(|setting| {
+            options.has_header
+                && headers
+                    .iter()
+                    .any(|header| header.eq_ignore_ascii_case(&setting.column))
+        }) {
+            anyhow::bail!(
+                "Column '{}' is both in '{}' and set with --set",
+                setting.column,
+                file_path
+            );
+        }
+        set_column_values(&options.set_columns, &columns, table_name, file_path)?
+    };
+    let sql = |count: usize| {
+        format!(
+            "{} INTO {} ({}) VALUES ({})",
+            options.on_conflict.insert_verb(),
+            quote_identifier(table_name),
+            headers[..count]
                 .iter()
-                .map(|h| format!("\"{}\"", h))
+                .map(|h| quote_identifier(h))
+                .chain(set_names.iter().cloned())
                 .collect::<Vec<_>>()
                 .join(","),
-            headers.iter().map(|_| "?").collect::<Vec<_>>().join(",")
-        );
+            vec!["?".to_string(); count]
+                .into_iter()
+                .chain(set_values.iter().cloned())
+                .collect::<Vec<_>>()
+                .join(",")
+        )
+    };
 
-        let mut stmt = tx.prepare(&sql)?;
+    let mut rejects = match &options.reject_file {
+        Some(path) => {
+            let mut writer = csv::WriterBuilder::new()
+                .flexible(true)
+                .from_path(path)
+                .with_context(|| format!("Failed to create reject file '{}'", path))?;
+            let mut header = headers.clone();
+            header.push("_error".to_string());
+            writer.write_record(&header)?;
+            Some(writer)
+        }
+        None => None,
+    };
 
-        for result in rdr.records() {
-            let record = result?;
-            let params: Vec<&str> = record.iter().collect();
-            stmt.execute(rusqlite::params_from_iter(params))?;
+    let rows_before = options.on_conflict.rows_before(conn, table_name)?;
+    let mut report = ImportReport::default();
+    let mut progress = ProgressTracker::new("import", None);
+    let start_time = Instant::now();
+    let mut records = rdr.byte_records();
+    let mut row = 0;
+    if let Some(checkpoint) = &checkpoint {
+        while row < checkpoint.records && records.next().is_some() {
+            row += 1;
         }
-    } // stmt is dropped here
+    }
+    let mut hint = ResumeHint {
+        committed: row,
+        armed: options.batch_size.is_some(),
+    };
+    let mut finished = false;
 
-    tx.commit()?;
-    Ok(())
+    while !finished {
+        let tx = conn.transaction()?;
+        let mut in_chunk = 0;
+
+        loop {
+            let Some(result) = records.next() else {
+                finished = true;
+                break;
+            };
+            row += 1;
+
+            let (record, error) = match result {
+                Ok(record) => match insert_csv_record(&tx, &record, &headers, options, &sql) {
+                    Ok(0) => {
+                        report.ignored += 1;
+                        continue;
+                    }
+                    Ok(_) => {
+                        report.imported += 1;
+                        in_chunk += 1;
+                        progress.update(report.imported as u64);
+                        progress.check_cancelled()?;
+                        if options.batch_size == Some(in_chunk) {
+                            break;
+                        }
+                        continue;
+                    }
+                    Err(e) => (Some(record), e),
+                },
+                Err(e) => (None, e.into()),
+            };
+
+            if !options.continue_on_error {
+                return Err(error.context(format!("Failed to import row {}", row)));
+            }
+            report.skip(format!("row {}: {:#}", row, error));
+            if let Some(writer) = rejects.as_mut() {
+                let mut fields: Vec<String> = record
+                    .iter()
+                    .flat_map(|r| r.iter())
+                    .map(|field| String::from_utf8_lossy(field).into_owned())
+                    .collect();
+                fields.push(format!("{:#}", error));
+                writer.write_record(&fields)?;
+            }
+        }
+
+        tx.commit()?;
+        hint.committed = row;
+        if options.batch_size.is_some() && !finished {
+            ImportCheckpoint::save(file_path, table_name, row)?;
+            let rate = report.imported as f64 / start_time.elapsed().as_secs_f64();
+            println!(
+                "Progress: {} rows committed - {:.0} rows/sec",
+                report.imported, rate
+            );
+        }
+    }
+    hint.disarm();
+    ImportCheckpoint::remove(file_path);
+    progress.finish();
+    report.count_replaced(conn, table_name, options.on_conflict, rows_before)?;
+
+    if let Some(mut writer) = rejects {
+        writer.flush()?;
+    }
+    let elapsed = start_time.elapsed().as_secs_f64();
+    if options.batch_size.is_some() || report.skipped > 0 || report.ignored > 0 {
+        println!(
+            "Imported {} rows in {:.2}s ({:.0} rows/sec)",
+            report.imported,
+            elapsed,
+            if elapsed > 0.0 {
+                report.imported as f64 / elapsed
+            } else {
+                0.0
+            }
+        );
+    }
+    report.print_conflicts();
+    if report.skipped > 0 {
+        println!("Skipped {} bad rows:", report.skipped);
+        for error in &report.errors {
+            println!("  {}", error);
+        }
+        if let Some(path) = &options.reject_file {
+            println!("Rejected rows were written to '{}'", path);
+        }
+    }
+    Ok(report)
+}
+
+/// Decodes a CSV record and inserts it with the given transaction, returning the number of
+/// rows inserted (0 when an ignored conflict skipped it).
+fn insert_csv_record(
+    tx: &rusqlite::Transaction,
+    record: &csv::ByteRecord,
+    headers: &[String],
+    options: &CsvImportOptions,
+    sql: &dyn Fn(usize) -> String,
+) -> Result<usize> {
+    if record.len() > headers.len() {
+        anyhow::bail!(
+            "{} fields but only {} columns are available",
+            record.len(),
+            headers.len()
+        );
+    }
+    let params = record
+        .iter()
+        .map(|field| {
+            let value = options.encoding.decode(field)?;
+            Ok(match &options.null_string {
+                Some(null) if *null == value => None,
+                _ => Some(value),
+            })
+        })
+        .collect::<Result<Vec<Option<String>>>>()?;
+    let mut stmt = tx.prepare_cached(&sql(params.len()))?;
+    Ok(stmt.execute(rusqlite::params_from_iter(params))?)
 }
 
 /// Exports the results of a SQL query to a CSV file.
@@ -84,16 +565,63 @@ pub fn import_csv_to_table(conn: &mut Connection, file_path: &str, table_name: &
 /// A `Result` which is `Ok(())` on successful export, or an `Err` if the query is invalid,
 /// the file cannot be written, or other errors occur during the process.
 pub fn export_to_csv(conn: &Connection, query: &str, filename: &str) -> Result<()> {
+    export_to_csv_with_options(conn, query, filename, &ExportOptions::default())
+}
+
+/// Exports the results of a SQL query to a CSV file with custom export options.
+///
+/// This behaves like `export_to_csv`, but allows the output to be compressed with gzip
+/// or zstd regardless of the file name. Compressed output is streamed, so memory usage
+/// stays flat for large exports.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `query` - The `SELECT` SQL query whose results will be exported.
+/// * `filename` - The path to the output file. The file will be overwritten if it exists.
+/// * `options` - An `ExportOptions` struct specifying compression and other settings.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` on successful export, or an `Err` on failure.
+pub fn export_to_csv_with_options(
+    conn: &Connection,
+    query: &str,
+    filename: &str,
+    options: &ExportOptions,
+) -> Result<()> {
     // Validate inputs
     validate_export_inputs(query, filename)?;
 
+    let row_count = write_query_to_csv(conn, query, filename, options, process_row)?;
+    println!("Successfully exported {} rows to '{}'", row_count, filename);
+    Ok(())
+}
+
+/// Runs a query and writes its results to a CSV file, returning the row count.
+///
+/// `format_row` turns each row into CSV fields. This is the one CSV export and resume
+/// path: the export registry's `csv` format calls it with its own row formatting.
+pub(crate) fn write_query_to_csv(
+    conn: &Connection,
+    query: &str,
+    filename: &str,
+    options: &ExportOptions,
+    format_row: fn(&rusqlite::Row, &[String]) -> Result<Vec<String>>,
+) -> Result<usize> {
+    let checkpoint = resume_checkpoint(query, filename, options)?;
+    let resumed_rows = checkpoint.as_ref().map_or(0, |c| c.rows);
+    let resumed_skipped = checkpoint.as_ref().map_or(0, |c| c.skipped);
+
     // Prepare the statement with error handling
-    let mut stmt = conn.prepare(query).with_context(|| {
-        format!(
-            "Failed to prepare export query. Check SQL syntax: {}",
-            query
-        )
-    })?;
+    let mut stmt = conn
+        .prepare(&offset_query(query, resumed_rows + resumed_skipped))
+        .with_context(|| {
+            format!(
+                "Failed to prepare export query. Check SQL syntax: {}",
+                query
+            )
+        })?;
 
     let column_names: Vec<String> = stmt.column_names().iter().map(|&s| s.to_string()).collect();
 
@@ -103,31 +631,34 @@ pub fn export_to_csv(conn: &Connection, query: &str, filename: &str) -> Result<(
         );
     }
 
-    // Create the CSV writer with error handling
-    let mut wtr = csv::Writer::from_path(filename).with_context(|| {
-        format!(
-            "Failed to create CSV file '{}'. Check permissions and disk space.",
-            filename
-        )
-    })?;
+    // Create the CSV writer with error handling, compressing if requested
+    let mut output = open_export_output(filename, options, checkpoint.as_ref())?;
+    if resumed_rows == 0 {
+        write_bom(&mut output, options)?;
+    }
+    let mut wtr = csv::Writer::from_writer(output);
 
-    // Write header row
-    wtr.write_record(&column_names)
-        .with_context(|| format!("Failed to write CSV header to '{}'", filename))?;
+    // Write header row, unless it was written before the export was interrupted
+    if resumed_rows == 0 {
+        wtr.write_record(&column_names)
+            .with_context(|| format!("Failed to write CSV header to '{}'", filename))?;
+    }
 
     // Execute query and write rows with progress tracking
     let mut rows = stmt
         .query([])
         .with_context(|| format!("Failed to execute export query: {}", query))?;
 
-    let mut row_count = 0;
+    let checkpointing = can_checkpoint(filename, options);
+    let mut row_count = resumed_rows;
     let mut error_count = 0;
+    let mut progress = ProgressTracker::new("export", None);
 
     while let Some(row) = rows
         .next()
         .with_context(|| format!("Failed to fetch row {} from query results", row_count + 1))?
     {
-        match process_row(&row, &column_names) {
+        match format_row(row, &column_names) {
             Ok(record) => {
                 if let Err(e) = wtr.write_record(&record) {
                     error_count += 1;
@@ -138,10 +669,22 @@ pub fn export_to_csv(conn: &Connection, query: &str, filename: &str) -> Result<(
                     }
                 } else {
                     row_count += 1;
+                    progress.update(row_count as u64);
 
-                    // Progress indicator for large exports
-                    if row_count % 10000 == 0 {
-                        println!("Exported {} rows...", row_count);
+                    // Progress indicator and checkpoint for large exports
+                    if row_count % CHECKPOINT_INTERVAL == 0 {
+                        if !options.quiet {
+                            println!("Exported {} rows...", row_count);
+                        }
+                        if checkpointing {
+                            wtr.flush()?;
+                            save_checkpoint(
+                                query,
+                                filename,
+                                row_count,
+                                resumed_skipped + error_count,
+                            )?;
+                        }
                     }
                 }
             }
@@ -159,22 +702,1013 @@ pub fn export_to_csv(conn: &Connection, query: &str, filename: &str) -> Result<(
         }
     }
 
-    // Ensure all data is written to disk
-    wtr.flush()
+    // Ensure all data is written to disk and any compressed stream is finished
+    wtr.into_inner()
+        .map_err(|e| e.into_error())
+        .context("Failed to flush data")
+        .and_then(OutputWriter::finish)
         .with_context(|| format!("Failed to flush data to CSV file '{}'", filename))?;
+    progress.finish();
+    remove_checkpoint(filename);
 
     // Verify the file was created successfully
-    verify_export_file(filename, row_count)?;
+    verify_export_file(filename, row_count, options.quiet)?;
 
     if error_count > 0 {
         println!("Export completed with {} warning(s)", error_count);
     }
 
+    Ok(row_count)
+}
+
+/// How often, in rows, an uncompressed CSV export records a checkpoint.
+const CHECKPOINT_INTERVAL: usize = 10_000;
+
+/// The progress of an interrupted CSV export, saved next to the output file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportCheckpoint {
+    /// The exported query. A checkpoint is only used to resume the same query.
+    pub query: String,
+    /// The number of rows written when the checkpoint was taken.
+    pub rows: usize,
+    /// The number of query rows skipped as errors before the checkpoint. The export
+    /// resumes after `rows + skipped` rows of the query.
+    #[serde(default)]
+    pub skipped: usize,
+    /// The size of the output file, in bytes, when the checkpoint was taken.
+    pub bytes: u64,
+}
+
+impl ExportCheckpoint {
+    /// Returns the checkpoint file path for an export file (`<output>.checkpoint`).
+    pub fn path_for(output: &str) -> String {
+        format!("{}.checkpoint", output)
+    }
+
+    /// Loads the checkpoint for an export file, if there is one.
+    pub fn load(output: &str) -> Result<Option<Self>> {
+        let path = Self::path_for(output);
+        match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                Ok(Some(serde_json::from_str(&text).with_context(|| {
+                    format!("Checkpoint file '{}' is corrupted", path)
+                })?))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read '{}'", path)),
+        }
+    }
+}
+
+/// Returns true if an export to `filename` can be checkpointed and resumed.
+fn can_checkpoint(filename: &str, options: &ExportOptions) -> bool {
+    options
+        .compression
+        .unwrap_or_else(|| Compression::from_path(filename))
+        == Compression::None
+}
+
+/// Finds the checkpoint to resume an export from, when resuming was requested.
+///
+/// Returns `None` when the export should start from the beginning.
+fn resume_checkpoint(
+    query: &str,
+    filename: &str,
+    options: &ExportOptions,
+) -> Result<Option<ExportCheckpoint>> {
+    if !options.resume {
+        return Ok(None);
+    }
+    if !can_checkpoint(filename, options) {
+        anyhow::bail!("Compressed exports cannot be resumed");
+    }
+
+    match ExportCheckpoint::load(filename)? {
+        Some(checkpoint) if Path::new(filename).exists() => {
+            if checkpoint.query != query {
+                anyhow::bail!(
+                    "The checkpoint for '{}' belongs to a different query: {}",
+                    filename,
+                    checkpoint.query
+                );
+            }
+            Ok(Some(checkpoint))
+        }
+        _ => {
+            println!(
+                "No checkpoint found for '{}'; starting from the beginning",
+                filename
+            );
+            Ok(None)
+        }
+    }
+}
+
+/// Opens the output of an export, continuing after a checkpoint when one is given.
+///
+/// When resuming, the file is truncated to its size at the checkpoint, dropping any
+/// partly written rows.
+pub(crate) fn open_export_output(
+    filename: &str,
+    options: &ExportOptions,
+    checkpoint: Option<&ExportCheckpoint>,
+) -> Result<OutputWriter> {
+    let Some(checkpoint) = checkpoint else {
+        remove_checkpoint(filename);
+        return create_output(filename, options.compression)
+            .with_context(|| format!("Failed to create export file '{}'", filename));
+    };
+
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .open(filename)
+        .with_context(|| format!("Failed to open '{}' to resume the export", filename))?;
+    file.set_len(checkpoint.bytes)?;
+    file.seek(SeekFrom::End(0))?;
+    println!(
+        "Resuming export to '{}' after {} rows",
+        filename, checkpoint.rows
+    );
+    Ok(OutputWriter::uncompressed(file))
+}
+
+/// Wraps a query so it skips the rows written before an export was interrupted.
+///
+/// Resuming relies on the query returning rows in the same order each time, so queries
+/// without an `ORDER BY` should only be resumed against an unchanged database.
+fn offset_query(query: &str, skip: usize) -> String {
+    if skip == 0 {
+        return query.to_string();
+    }
+    format!(
+        "SELECT * FROM ({}) LIMIT -1 OFFSET {}",
+        query.trim().trim_end_matches(';'),
+        skip
+    )
+}
+
+/// Records that `rows` rows of an export have been flushed to `filename`, after
+/// `skipped` rows of the query were skipped as errors.
+fn save_checkpoint(query: &str, filename: &str, rows: usize, skipped: usize) -> Result<()> {
+    let checkpoint = ExportCheckpoint {
+        query: query.to_string(),
+        rows,
+        skipped,
+        bytes: std::fs::metadata(filename)?.len(),
+    };
+    let path = ExportCheckpoint::path_for(filename);
+    let temp = format!("{}.tmp", path);
+    std::fs::write(&temp, serde_json::to_string(&checkpoint)?)
+        .with_context(|| format!("Failed to write checkpoint '{}'", path))?;
+    std::fs::rename(&temp, &path)
+        .with_context(|| format!("Failed to write checkpoint '{}'", path))?;
+    Ok(())
+}
+
+/// Removes the checkpoint of a finished (or restarted) export, if there is one.
+fn remove_checkpoint(filename: &str) {
+    let _ = std::fs::remove_file(ExportCheckpoint::path_for(filename));
+}
+
+/// How far a chunked import got, saved next to the input file after each committed chunk.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportCheckpoint {
+    /// The table being imported into. A checkpoint is only used to resume into it.
+    pub table: String,
+    /// The size of the input file, in bytes, to tell when it has changed.
+    pub file_size: u64,
+    /// The records (rows or documents) read from the input when the last chunk was
+    /// committed, including skipped ones.
+    pub records: usize,
+}
+
+impl ImportCheckpoint {
+    /// Returns the checkpoint file path for an input file (`<input>.import-checkpoint`).
+    pub fn path_for(input: &str) -> String {
+        format!("{}.import-checkpoint", input)
+    }
+
+    /// Finds the checkpoint to resume an import from.
+    ///
+    /// Without `resume`, any old checkpoint is removed and the import starts from the
+    /// beginning. With it, the checkpoint must belong to the same table and an unchanged
+    /// input file.
+    pub(crate) fn for_import(input: &str, table: &str, resume: bool) -> Result<Option<Self>> {
+        if !resume {
+            Self::remove(input);
+            return Ok(None);
+        }
+        if input == STDIN_PATH {
+            anyhow::bail!("Imports from standard input cannot be resumed");
+        }
+        let path = Self::path_for(input);
+        let checkpoint: Self = match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text)
+                .with_context(|| format!("Checkpoint file '{}' is corrupted", path))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                println!(
+                    "No checkpoint found for '{}'; starting from the beginning",
+                    input
+                );
+                return Ok(None);
+            }
+            Err(e) => return Err(e).with_context(|| format!("Failed to read '{}'", path)),
+        };
+        if checkpoint.table != table {
+            anyhow::bail!(
+                "The checkpoint for '{}' belongs to an import into '{}'",
+                input,
+                checkpoint.table
+            );
+        }
+        if std::fs::metadata(input)?.len() != checkpoint.file_size {
+            anyhow::bail!(
+                "'{}' has changed since the checkpoint was taken; import it again without --resume",
+                input
+            );
+        }
+        println!(
+            "Resuming import of '{}' after {} records",
+            input, checkpoint.records
+        );
+        Ok(Some(checkpoint))
+    }
+
+    /// Records that the first `records` records of `input` are committed to `table`.
+    /// Standard input is never checkpointed.
+    pub(crate) fn save(input: &str, table: &str, records: usize) -> Result<()> {
+        if input == STDIN_PATH {
+            return Ok(());
+        }
+        let checkpoint = Self {
+            table: table.to_string(),
+            file_size: std::fs::metadata(input)?.len(),
+            records,
+        };
+        let path = Self::path_for(input);
+        let temp = format!("{}.tmp", path);
+        std::fs::write(&temp, serde_json::to_string(&checkpoint)?)
+            .with_context(|| format!("Failed to write checkpoint '{}'", path))?;
+        std::fs::rename(&temp, &path)
+            .with_context(|| format!("Failed to write checkpoint '{}'", path))?;
+        Ok(())
+    }
+
+    /// Removes the checkpoint of a finished (or restarted) import, if there is one.
+    pub(crate) fn remove(input: &str) {
+        let _ = std::fs::remove_file(Self::path_for(input));
+    }
+}
+
+/// Explains how to continue a chunked import if it is dropped before being disarmed, as
+/// when the import returns an error.
+pub(crate) struct ResumeHint {
+    /// The records read when the last chunk was committed.
+    pub committed: usize,
+    /// Whether the import is chunked and hasn't finished.
+    pub armed: bool,
+}
+
+impl ResumeHint {
+    /// Marks the import as finished.
+    pub fn disarm(&mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for ResumeHint {
+    fn drop(&mut self) {
+        if self.armed && self.committed > 0 {
+            eprintln!(
+                "The first {} records were committed. Run the import again with --resume to continue after them.",
+                self.committed
+            );
+        }
+    }
+}
+
+/// The manifest written alongside a directory export, describing each exported table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportManifest {
+    /// When the export was taken, in RFC 3339 format.
+    pub exported_at: String,
+    /// The exported tables, in export order.
+    pub tables: Vec<ManifestTable>,
+}
+
+/// A single table entry in an `ExportManifest`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestTable {
+    /// The table name in the database.
+    pub name: String,
+    /// The CSV file name, relative to the export directory.
+    pub file: String,
+    /// The number of exported rows.
+    pub rows: usize,
+    /// The column names and declared types, in table order.
+    pub columns: Vec<ManifestColumn>,
+}
+
+/// A column entry in a `ManifestTable`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestColumn {
+    /// The column name.
+    pub name: String,
+    /// The declared SQLite type, which may be empty.
+    #[serde(rename = "type")]
+    pub declared_type: String,
+}
+
+/// The file name of the manifest written by `export_database_to_csv_dir`.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Exports every user table to its own CSV file in a directory, plus a manifest.
+///
+/// The directory is created if it does not exist. Each table is written to
+/// `<table>.csv` (with characters that are unsafe in file names replaced by underscores),
+/// and `manifest.json` records the file, row count, and column types of every table so
+/// the export can be understood or reloaded without the original database.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `dir` - The directory to write the CSV files and manifest into.
+///
+/// # Returns
+///
+/// A `Result` containing the written `ExportManifest`, or an `Err` if any table cannot
+/// be exported.
+pub fn export_database_to_csv_dir(conn: &Connection, dir: &str) -> Result<ExportManifest> {
+    let tables = get_table_names(conn)?;
+    if tables.is_empty() {
+        anyhow::bail!("No tables to export");
+    }
+
+    let dir_path = Path::new(dir);
+    std::fs::create_dir_all(dir_path)
+        .with_context(|| format!("Failed to create export directory '{}'", dir))?;
+
+    let mut manifest = ExportManifest {
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        tables: Vec::with_capacity(tables.len()),
+    };
+    let mut used_names: Vec<String> = vec![MANIFEST_FILE.to_string()];
+
+    for table in &tables {
+        let file = unique_csv_file_name(table, &used_names);
+        used_names.push(file.to_lowercase());
+
+        let path = dir_path.join(&file);
+        let query = format!("SELECT * FROM \"{}\"", table.replace('"', "\"\""));
+        let rows = write_query_to_csv(
+            conn,
+            &query,
+            &path.to_string_lossy(),
+            &ExportOptions::default(),
+            process_row,
+        )
+        .with_context(|| format!("Failed to export table '{}'", table))?;
+        println!("  {}: {} rows -> {}", table, rows, file);
+
+        let columns = visible_columns(conn, Some("main"), table)?
+            .into_iter()
+            .map(|column| ManifestColumn {
+                name: column.name,
+                declared_type: column.decl_type,
+            })
+            .collect();
+
+        manifest.tables.push(ManifestTable {
+            name: table.clone(),
+            file,
+            rows,
+            columns,
+        });
+    }
+
+    let manifest_path = dir_path.join(MANIFEST_FILE);
+    std::fs::write(&manifest_path, serde_json::to_string_pretty(&manifest)?)
+        .with_context(|| format!("Failed to write manifest '{}'", manifest_path.display()))?;
+
+    println!(
+        "Successfully exported {} table(s) ({} rows) to '{}'",
+        manifest.tables.len(),
+        manifest.tables.iter().map(|t| t.rows).sum::<usize>(),
+        dir
+    );
+    Ok(manifest)
+}
+
+/// Derives a CSV file name from a table name that is safe on common file systems and
+/// unique (case-insensitively) among the names already used.
+fn unique_csv_file_name(table: &str, used_lowercase: &[String]) -> String {
+    let mut base: String = table
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if base.is_empty() {
+        base = "table".to_string();
+    }
+
+    let candidate = format!("{}.csv", base);
+    if !used_lowercase.contains(&candidate.to_lowercase()) {
+        return candidate;
+    }
+    for n in 2.. {
+        let candidate = format!("{}_{}.csv", base, n);
+        if !used_lowercase.contains(&candidate.to_lowercase()) {
+            return candidate;
+        }
+    }
+    unreachable!("file name search is unbounded")
+}
+
+/// Exports the results of a SQL query to an Excel (`.xlsx`) workbook.
+///
+/// The workbook contains a single `Results` sheet with a bold header row. Integer and
+/// real values are written as numeric cells, and text values that look like dates
+/// (`YYYY-MM-DD`) or timestamps (`YYYY-MM-DD HH:MM:SS`) are written as date cells so
+/// they sort and filter correctly in spreadsheet applications.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `query` - The `SELECT` SQL query whose results will be exported.
+/// * `filename` - The path to the output `.xlsx` file. The file will be overwritten if it exists.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` on successful export, or an `Err` if the query is invalid,
+/// the result set is too large for a worksheet, or the file cannot be written.
+pub fn export_to_xlsx(conn: &Connection, query: &str, filename: &str) -> Result<()> {
+    validate_export_inputs(query, filename)?;
+
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+    worksheet
+        .set_name("Results")
+        .context("Failed to name worksheet")?;
+
+    let row_count = write_query_to_worksheet(conn, query, worksheet)?;
+
+    workbook
+        .save(filename)
+        .with_context(|| format!("Failed to write Excel file '{}'", filename))?;
+
+    verify_export_file(filename, row_count, false)?;
     println!("Successfully exported {} rows to '{}'", row_count, filename);
 
     Ok(())
 }
 
+/// Exports whole tables to an Excel (`.xlsx`) workbook with one sheet per table.
+///
+/// Sheet names are derived from the table names, with characters that Excel does not
+/// allow replaced by underscores and long names truncated to 31 characters.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `filename` - The path to the output `.xlsx` file.
+/// * `tables` - The tables to export. If empty, every user table in the database is exported.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` on success, or an `Err` if any table cannot be exported.
+pub fn export_tables_to_xlsx(conn: &Connection, filename: &str, tables: &[String]) -> Result<()> {
+    let tables = if tables.is_empty() {
+        get_table_names(conn)?
+    } else {
+        tables.to_vec()
+    };
+
+    if tables.is_empty() {
+        anyhow::bail!("No tables to export");
+    }
+
+    let mut workbook = Workbook::new();
+    let mut used_names: Vec<String> = Vec::new();
+    let mut total_rows = 0;
+
+    for table in &tables {
+        let sheet_name = unique_sheet_name(table, &used_names);
+        used_names.push(sheet_name.to_lowercase());
+
+        let worksheet = workbook.add_worksheet();
+        worksheet
+            .set_name(&sheet_name)
+            .with_context(|| format!("Invalid worksheet name '{}'", sheet_name))?;
+
+        let query = format!("SELECT * FROM \"{}\"", table.replace('"', "\"\""));
+        let rows = write_query_to_worksheet(conn, &query, worksheet)
+            .with_context(|| format!("Failed to export table '{}'", table))?;
+        println!("  {}: {} rows", table, rows);
+        total_rows += rows;
+    }
+
+    workbook
+        .save(filename)
+        .with_context(|| format!("Failed to write Excel file '{}'", filename))?;
+
+    verify_export_file(filename, total_rows, false)?;
+    println!(
+        "Successfully exported {} table(s) ({} rows) to '{}'",
+        tables.len(),
+        total_rows,
+        filename
+    );
+
+    Ok(())
+}
+
+/// Runs a query and writes its header and rows into a worksheet, returning the row count.
+fn write_query_to_worksheet(
+    conn: &Connection,
+    query: &str,
+    worksheet: &mut Worksheet,
+) -> Result<usize> {
+    let mut stmt = conn.prepare(query).with_context(|| {
+        format!(
+            "Failed to prepare export query. Check SQL syntax: {}",
+            query
+        )
+    })?;
+
+    let column_names: Vec<String> = stmt.column_names().iter().map(|&s| s.to_string()).collect();
+    if column_names.is_empty() {
+        anyhow::bail!(
+            "Query returned no columns. Make sure your query includes SELECT statements."
+        );
+    }
+
+    let formats = XlsxFormats::new();
+    write_xlsx_header(worksheet, &column_names, &formats)?;
+
+    let mut rows = stmt
+        .query([])
+        .with_context(|| format!("Failed to execute export query: {}", query))?;
+
+    let mut row_count = 0;
+    let mut progress = ProgressTracker::new("export", None);
+
+    while let Some(row) = rows.next()? {
+        if row_count + 1 >= XLSX_MAX_ROWS {
+            anyhow::bail!(
+                "Result set exceeds the Excel limit of {} rows per sheet. Use CSV export instead.",
+                XLSX_MAX_ROWS - 1
+            );
+        }
+        let excel_row = (row_count + 1) as u32;
+
+        for col in 0..column_names.len() {
+            write_xlsx_value(
+                worksheet,
+                excel_row,
+                col as u16,
+                row.get_ref(col)?,
+                &formats,
+            )?;
+        }
+
+        row_count += 1;
+        progress.update(row_count as u64);
+    }
+
+    progress.finish();
+    worksheet.autofit();
+    Ok(row_count)
+}
+
+/// Cell formats shared by every worksheet written by the Excel exporters.
+pub(crate) struct XlsxFormats {
+    header: Format,
+    date: Format,
+    datetime: Format,
+}
+
+impl XlsxFormats {
+    pub(crate) fn new() -> Self {
+        Self {
+            header: Format::new().set_bold(),
+            date: Format::new().set_num_format("yyyy-mm-dd"),
+            datetime: Format::new().set_num_format("yyyy-mm-dd hh:mm:ss"),
+        }
+    }
+}
+
+/// Writes a bold header row and freezes it so it stays visible while scrolling.
+pub(crate) fn write_xlsx_header(
+    worksheet: &mut Worksheet,
+    column_names: &[String],
+    formats: &XlsxFormats,
+) -> Result<()> {
+    for (col, name) in column_names.iter().enumerate() {
+        worksheet.write_string_with_format(0, col as u16, name, &formats.header)?;
+    }
+    worksheet.set_freeze_panes(1, 0)?;
+    Ok(())
+}
+
+/// Writes a single SQLite value into a worksheet cell using the matching Excel cell type.
+pub(crate) fn write_xlsx_value(
+    worksheet: &mut Worksheet,
+    row: u32,
+    col: u16,
+    value: rusqlite::types::ValueRef<'_>,
+    formats: &XlsxFormats,
+) -> Result<()> {
+    match value {
+        rusqlite::types::ValueRef::Null => {}
+        rusqlite::types::ValueRef::Integer(i) => {
+            worksheet.write_number(row, col, i as f64)?;
+        }
+        rusqlite::types::ValueRef::Real(f) => {
+            worksheet.write_number(row, col, f)?;
+        }
+        rusqlite::types::ValueRef::Text(t) => {
+            let text = String::from_utf8_lossy(t);
+            match parse_excel_datetime(&text) {
+                Some((datetime, true)) => {
+                    worksheet.write_datetime_with_format(row, col, &datetime, &formats.datetime)?;
+                }
+                Some((datetime, false)) => {
+                    worksheet.write_datetime_with_format(row, col, &datetime, &formats.date)?;
+                }
+                None => {
+                    worksheet.write_string(row, col, text.as_ref())?;
+                }
+            }
+        }
+        rusqlite::types::ValueRef::Blob(b) => {
+            worksheet.write_string(row, col, format!("[BLOB {} bytes]", b.len()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Parses a text value as a date or timestamp for Excel.
+///
+/// Returns the parsed value and whether it carries a time component.
+fn parse_excel_datetime(text: &str) -> Option<(ExcelDateTime, bool)> {
+    let text = text.trim();
+    if text.len() < 10 {
+        return None;
+    }
+
+    for pattern in [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M:%S%.f",
+    ] {
+        if let Ok(dt) = NaiveDateTime::parse_from_str(text, pattern) {
+            let datetime =
+                ExcelDateTime::from_ymd(dt.year() as u16, dt.month() as u8, dt.day() as u8)
+                    .and_then(|d| {
+                        d.and_hms(dt.hour() as u16, dt.minute() as u8, dt.second() as f64)
+                    })
+                    .ok()?;
+            return Some((datetime, true));
+        }
+    }
+
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        let datetime =
+            ExcelDateTime::from_ymd(date.year() as u16, date.month() as u8, date.day() as u8)
+                .ok()?;
+        return Some((datetime, false));
+    }
+
+    None
+}
+
+/// Builds a valid, unique Excel worksheet name from a table name.
+fn unique_sheet_name(table: &str, used_lowercase: &[String]) -> String {
+    let mut base: String = table
+        .chars()
+        .map(|c| if "[]:*?/\\".contains(c) { '_' } else { c })
+        .collect();
+    base = base.trim_matches('\'').to_string();
+    if base.is_empty() {
+        base = "Sheet".to_string();
+    }
+    let base: String = base.chars().take(31).collect();
+
+    if !used_lowercase.contains(&base.to_lowercase()) {
+        return base;
+    }
+
+    for n in 2.. {
+        let suffix = format!("_{}", n);
+        let truncated: String = base.chars().take(31 - suffix.len()).collect();
+        let candidate = format!("{}{}", truncated, suffix);
+        if !used_lowercase.contains(&candidate.to_lowercase()) {
+            return candidate;
+        }
+    }
+    unreachable!("sheet name search is unbounded")
+}
+
+/// Exports the results of a SQL query to an Avro object container file.
+///
+/// This is `formats::export_query` with the `avro` format; see `AvroFormat` for how
+/// the schema is derived. This function requires the `avro` feature.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `query` - The `SELECT` SQL query whose results will be exported.
+/// * `filename` - The path to the output `.avro` file. The file will be overwritten if it exists.
+///
+/// # Returns
+///
+/// A `Result` containing the number of rows written, or an `Err` if the query is invalid
+/// or a value cannot be represented in the derived schema.
+#[cfg(feature = "avro")]
+pub fn export_to_avro(conn: &Connection, query: &str, filename: &str) -> Result<usize> {
+    validate_export_inputs(query, filename)?;
+    let row_count = crate::formats::export_query(conn, query, filename, Some("avro"))?;
+    verify_export_file(filename, row_count, false)?;
+    Ok(row_count)
+}
+
+/// An Avro object container file.
+///
+/// The Avro schema is derived from the declared types of the result columns: integer
+/// columns become `long`, real columns `double`, blob columns `bytes`, and everything
+/// else `string`. Columns without a declared type (such as expressions, or rows that
+/// were already fetched) take their type from the first row. Every field is a union with
+/// `null`, and column names are turned into valid Avro names. This format requires the
+/// `avro` feature.
+#[cfg(feature = "avro")]
+pub struct AvroFormat;
+
+/// The number of rows written to each Avro data block.
+#[cfg(feature = "avro")]
+const AVRO_BLOCK_ROWS: usize = 1_000;
+
+#[cfg(feature = "avro")]
+struct AvroRecordWriter {
+    output: OutputWriter,
+    names: Vec<String>,
+    declared: Vec<Option<AvroType>>,
+    /// The schema and column types, derived once the first row (or the end of an empty
+    /// result) is reached.
+    schema: Option<(apache_avro::Schema, Vec<AvroType>)>,
+    marker: [u8; 16],
+    block: Vec<apache_avro::types::Value>,
+    row_count: usize,
+}
+
+#[cfg(feature = "avro")]
+impl AvroRecordWriter {
+    /// Derives the schema from the declared types and the first row, and writes the
+    /// file header.
+    fn start(&mut self, first: Option<&[rusqlite::types::Value]>) -> Result<()> {
+        use rusqlite::types::Value;
+
+        let types: Vec<AvroType> = (0..self.names.len())
+            .map(|i| {
+                self.declared.get(i).copied().flatten().unwrap_or_else(|| {
+                    match first.and_then(|r| r.get(i)) {
+                        Some(Value::Integer(_)) => AvroType::Long,
+                        Some(Value::Real(_)) => AvroType::Double,
+                        Some(Value::Blob(_)) => AvroType::Bytes,
+                        _ => AvroType::String,
+                    }
+                })
+            })
+            .collect();
+        let schema_json = serde_json::json!({
+            "type": "record",
+            "name": "Row",
+            "namespace": "vapor",
+            "fields": self.names.iter().zip(&types).map(|(name, ty)| serde_json::json!({
+                "name": name,
+                "type": ["null", ty.name()],
+                "default": null,
+            })).collect::<Vec<_>>(),
+        });
+        let schema =
+            apache_avro::Schema::parse(&schema_json).context("Failed to build Avro schema")?;
+
+        apache_avro::Writer::builder()
+            .schema(&schema)
+            .writer(&mut self.output)
+            .marker(self.marker)
+            .build()
+            .into_inner()
+            .context("Failed to write Avro header")?;
+        self.schema = Some((schema, types));
+        Ok(())
+    }
+
+    /// Writes the buffered rows as one data block.
+    fn write_block(&mut self) -> Result<()> {
+        let Some((schema, _)) = &self.schema else {
+            return Ok(());
+        };
+        if self.block.is_empty() {
+            return Ok(());
+        }
+        let mut writer = apache_avro::Writer::append_to(schema, &mut self.output, self.marker);
+        writer.extend(self.block.drain(..))?;
+        writer.into_inner()?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "avro")]
+impl crate::formats::RecordWriter for AvroRecordWriter {
+    fn set_declared_types(&mut self, declared: &[Option<String>]) {
+        self.declared = declared
+            .iter()
+            .map(|d| d.as_deref().map(AvroType::from_declared_type))
+            .collect();
+    }
+
+    fn write_header(&mut self, columns: &[String]) -> Result<()> {
+        self.names = avro_field_names(columns.iter().map(String::as_str));
+        Ok(())
+    }
+
+    fn write_record(&mut self, record: &[rusqlite::types::Value]) -> Result<()> {
+        use apache_avro::types::Value as AvroValue;
+
+        if self.schema.is_none() {
+            self.start(Some(record))?;
+        }
+        let Some((_, types)) = &self.schema else {
+            unreachable!("the Avro schema is derived before the first row");
+        };
+        let fields = self
+            .names
+            .iter()
+            .zip(types)
+            .zip(record)
+            .map(|((name, ty), value)| {
+                let value = match ty.convert(value.clone()) {
+                    Ok(AvroValue::Null) => AvroValue::Union(0, Box::new(AvroValue::Null)),
+                    Ok(value) => AvroValue::Union(1, Box::new(value)),
+                    Err(e) => {
+                        anyhow::bail!("Row {}, column '{}': {}", self.row_count + 1, name, e)
+                    }
+                };
+                Ok((name.clone(), value))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.block.push(AvroValue::Record(fields));
+        self.row_count += 1;
+        if self.block.len() >= AVRO_BLOCK_ROWS {
+            self.write_block()?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        if self.schema.is_none() {
+            self.start(None)?;
+        }
+        self.write_block()?;
+        self.output.finish()
+    }
+}
+
+#[cfg(feature = "avro")]
+impl crate::formats::Format for AvroFormat {
+    fn name(&self) -> &'static str {
+        "avro"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["avro"]
+    }
+
+    fn writer(&self, output: OutputWriter) -> Result<Box<dyn crate::formats::RecordWriter>> {
+        Ok(Box::new(AvroRecordWriter {
+            output,
+            names: Vec::new(),
+            declared: Vec::new(),
+            schema: None,
+            marker: *uuid::Uuid::new_v4().as_bytes(),
+            block: Vec::new(),
+            row_count: 0,
+        }))
+    }
+}
+
+/// The Avro primitive type used for a result column.
+#[cfg(feature = "avro")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AvroType {
+    Long,
+    Double,
+    Bytes,
+    String,
+}
+
+#[cfg(feature = "avro")]
+impl AvroType {
+    /// Maps a declared SQLite column type to an Avro type using SQLite's affinity rules.
+    fn from_declared_type(declared: &str) -> Self {
+        let declared = declared.to_uppercase();
+        if declared.contains("INT") {
+            AvroType::Long
+        } else if ["CHAR", "CLOB", "TEXT"]
+            .iter()
+            .any(|t| declared.contains(t))
+        {
+            AvroType::String
+        } else if declared.contains("BLOB") {
+            AvroType::Bytes
+        } else if ["REAL", "FLOA", "DOUB", "NUMERIC", "DECIMAL"]
+            .iter()
+            .any(|t| declared.contains(t))
+        {
+            AvroType::Double
+        } else {
+            AvroType::String
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            AvroType::Long => "long",
+            AvroType::Double => "double",
+            AvroType::Bytes => "bytes",
+            AvroType::String => "string",
+        }
+    }
+
+    /// Converts a SQLite value to this type, since SQLite does not enforce column types.
+    fn convert(
+        &self,
+        value: rusqlite::types::Value,
+    ) -> std::result::Result<apache_avro::types::Value, String> {
+        use apache_avro::types::Value as AvroValue;
+        use rusqlite::types::Value;
+
+        match (self, value) {
+            (_, Value::Null) => Ok(AvroValue::Null),
+            (AvroType::Long, Value::Integer(i)) => Ok(AvroValue::Long(i)),
+            (AvroType::Long, Value::Real(f)) if f.fract() == 0.0 => Ok(AvroValue::Long(f as i64)),
+            (AvroType::Long, Value::Text(t)) => t
+                .trim()
+                .parse()
+                .map(AvroValue::Long)
+                .map_err(|_| format!("'{}' is not an integer", t)),
+            (AvroType::Double, Value::Integer(i)) => Ok(AvroValue::Double(i as f64)),
+            (AvroType::Double, Value::Real(f)) => Ok(AvroValue::Double(f)),
+            (AvroType::Double, Value::Text(t)) => t
+                .trim()
+                .parse()
+                .map(AvroValue::Double)
+                .map_err(|_| format!("'{}' is not a number", t)),
+            (AvroType::Bytes, Value::Blob(b)) => Ok(AvroValue::Bytes(b)),
+            (AvroType::Bytes, Value::Text(t)) => Ok(AvroValue::Bytes(t.into_bytes())),
+            (AvroType::String, Value::Integer(i)) => Ok(AvroValue::String(i.to_string())),
+            (AvroType::String, Value::Real(f)) => Ok(AvroValue::String(f.to_string())),
+            (AvroType::String, Value::Text(t)) => Ok(AvroValue::String(t)),
+            (AvroType::String, Value::Blob(b)) => Ok(AvroValue::String(
+                String::from_utf8(b).map_err(|_| "blob is not valid UTF-8".to_string())?,
+            )),
+            (ty, value) => Err(format!(
+                "{:?} value cannot be stored as {}",
+                value.data_type(),
+                ty.name()
+            )),
+        }
+    }
+}
+
+/// Turns column names into unique, valid Avro field names.
+#[cfg(feature = "avro")]
+fn avro_field_names<'a>(columns: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for column in columns {
+        let mut name: String = column
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            name.insert(0, '_');
+        }
+        let base = name.clone();
+        let mut n = 2;
+        while names.contains(&name) {
+            name = format!("{}_{}", base, n);
+            n += 1;
+        }
+        names.push(name);
+    }
+    names
+}
+
 /// Helper function to validate the inputs for the `export_to_csv` function.
 ///
 /// Performs checks for:
@@ -255,12 +1789,9 @@ fn validate_export_inputs(query: &str, filename: &str) -> Result<()> {
 fn process_row(row: &rusqlite::Row, column_names: &[String]) -> Result<Vec<String>> {
     let mut record = Vec::with_capacity(column_names.len());
 
-    for i in 0..column_names.len() {
+    for (i, column_name) in column_names.iter().enumerate() {
         let val: rusqlite::types::Value = row.get(i).with_context(|| {
-            format!(
-                "Failed to get value from column {} ('{}')",
-                i, column_names[i]
-            )
+            format!("Failed to get value from column {} ('{}')", i, column_name)
         })?;
 
         let value_str = match val {
@@ -303,7 +1834,8 @@ fn process_row(row: &rusqlite::Row, column_names: &[String]) -> Result<Vec<Strin
 /// Helper function to verify that the export file was created and appears valid.
 ///
 /// Checks if the file exists and if its size is non-zero when rows were expected to be written.
-fn verify_export_file(filename: &str, expected_rows: usize) -> Result<()> {
+/// The file size is reported unless `quiet` is set.
+fn verify_export_file(filename: &str, expected_rows: usize, quiet: bool) -> Result<()> {
     let path = Path::new(filename);
 
     if !path.exists() {
@@ -323,7 +1855,7 @@ fn verify_export_file(filename: &str, expected_rows: usize) -> Result<()> {
 
     // Basic file size sanity check
     let file_size = metadata.len();
-    if file_size > 0 {
+    if file_size > 0 && !quiet {
         println!("Export file size: {} bytes", file_size);
     }
 
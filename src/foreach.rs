@@ -0,0 +1,332 @@
+//! # Broadcast Execution
+//!
+//! This module runs the same SQL against many SQLite files, such as one database per
+//! tenant or shard, and collects what happened to each one. It backs the `foreach` and
+//! `federate` commands.
+//!
+//! ## Features:
+//! - **Glob patterns**: Database files are picked with shell-style patterns like
+//!   `data/*.db`, expanded in sorted order.
+//! - **All or nothing per file**: The SQL runs in a transaction on each database, so a
+//!   failing statement leaves that file unchanged and the rest are still updated.
+//! - **Parallel runs**: Several databases can be worked on at once with worker threads.
+//! - **Summary**: Each file's status, rows changed, and time are reported in a table.
+//! - **Federated queries**: A query is run against each file in turn and the results are
+//!   combined into one, with a column naming the file each row came from.
+
+use anyhow::{Context, Result};
+use prettytable::{row, Table};
+use rusqlite::types::Value;
+use rusqlite::{Connection, OpenFlags};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::backend::Backend;
+use crate::formats::Record;
+
+/// Options for `run_foreach`.
+#[derive(Debug, Clone)]
+pub struct ForeachOptions {
+    /// The number of databases to work on at once.
+    pub parallel: usize,
+}
+
+impl Default for ForeachOptions {
+    fn default() -> Self {
+        Self { parallel: 1 }
+    }
+}
+
+/// What happened when the SQL ran against one database.
+#[derive(Debug, Clone)]
+pub struct ForeachResult {
+    pub path: String,
+    /// The number of rows changed, or the error that rolled the database back.
+    pub outcome: Result<u64, String>,
+    pub elapsed: Duration,
+}
+
+/// Expands glob patterns into a sorted list of database files.
+///
+/// # Arguments
+///
+/// * `patterns` - Shell-style patterns such as `data/*.db`.
+///
+/// # Returns
+///
+/// A `Result` containing the matching files, or an `Err` if a pattern is invalid or
+/// nothing matches.
+pub fn expand_globs(patterns: &[String]) -> Result<Vec<String>> {
+    let mut paths = Vec::new();
+    for pattern in patterns {
+        let entries =
+            glob::glob(pattern).with_context(|| format!("Invalid glob pattern '{}'", pattern))?;
+        for entry in entries {
+            let path = entry.with_context(|| format!("Failed to read a match of '{}'", pattern))?;
+            if path.is_file() {
+                paths.push(path.to_string_lossy().into_owned());
+            }
+        }
+    }
+    paths.sort();
+    paths.dedup();
+    if paths.is_empty() {
+        anyhow::bail!("No database files match {}", patterns.join(", "));
+    }
+    Ok(paths)
+}
+
+/// Runs SQL against each database, in a transaction per database.
+///
+/// # Arguments
+///
+/// * `paths` - The database files. They must already exist.
+/// * `sql` - One or more SQL statements.
+/// * `options` - How many databases to work on at once.
+///
+/// # Returns
+///
+/// One result per database, in the order of `paths`.
+pub fn run_foreach(paths: &[String], sql: &str, options: &ForeachOptions) -> Vec<ForeachResult> {
+    let results: Mutex<Vec<Option<ForeachResult>>> = Mutex::new(vec![None; paths.len()]);
+    let next = AtomicUsize::new(0);
+    let workers = options.parallel.clamp(1, paths.len().max(1));
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                let Some(path) = paths.get(i) else {
+                    break;
+                };
+                let start = Instant::now();
+                let outcome = run_on_database(path, sql).map_err(|e| format!("{:#}", e));
+                results.lock().unwrap()[i] = Some(ForeachResult {
+                    path: path.clone(),
+                    outcome,
+                    elapsed: start.elapsed(),
+                });
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// Runs SQL against one database in a transaction, returning the number of rows changed.
+fn run_on_database(path: &str, sql: &str) -> Result<u64> {
+    let mut conn = Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .with_context(|| format!("Failed to open database '{}'", path))?;
+    let tx = conn
+        .transaction()
+        .context("Failed to start a transaction")?;
+    tx.execute_batch(sql)?;
+    let changed: i64 = tx.query_row("SELECT total_changes()", [], |row| row.get(0))?;
+    tx.commit().context("Failed to commit")?;
+    Ok(changed as u64)
+}
+
+/// The combined results of a query run against several databases.
+#[derive(Debug, Clone, Default)]
+pub struct FederatedResult {
+    /// The source column followed by the query's columns.
+    pub columns: Vec<String>,
+    pub rows: Vec<Record>,
+    /// The databases the query failed on, and why.
+    pub errors: Vec<(String, String)>,
+}
+
+/// Runs a query against each database in turn and combines the results.
+///
+/// # Arguments
+///
+/// * `paths` - The database files, opened read-only.
+/// * `sql` - A query returning the same columns from every database.
+/// * `source_column` - The name of the column added in front, holding each row's file.
+///
+/// # Returns
+///
+/// A `Result` containing the combined rows and the databases the query failed on, or an
+/// `Err` if there are no databases or it failed on all of them.
+pub fn federate_query(paths: &[String], sql: &str, source_column: &str) -> Result<FederatedResult> {
+    if paths.is_empty() {
+        anyhow::bail!("No databases to query");
+    }
+    let mut result = FederatedResult::default();
+    for path in paths {
+        match query_database(path, sql) {
+            Ok(shard) => {
+                if result.columns.is_empty() {
+                    result.columns.push(source_column.to_string());
+                    result.columns.extend(shard.columns);
+                } else if result.columns[1..] != shard.columns[..] {
+                    result.errors.push((
+                        path.clone(),
+                        format!(
+                            "returned columns ({}) instead of ({})",
+                            shard.columns.join(", "),
+                            result.columns[1..].join(", ")
+                        ),
+                    ));
+                    continue;
+                }
+                result.rows.extend(shard.rows.into_iter().map(|row| {
+                    let mut record = Vec::with_capacity(row.len() + 1);
+                    record.push(Value::Text(path.clone()));
+                    record.extend(row);
+                    record
+                }));
+            }
+            Err(e) => result.errors.push((path.clone(), format!("{:#}", e))),
+        }
+    }
+    if result.columns.is_empty() {
+        let (path, error) = &result.errors[0];
+        anyhow::bail!(
+            "The query failed on every database (first: '{}': {})",
+            path,
+            error
+        );
+    }
+    Ok(result)
+}
+
+fn query_database(path: &str, sql: &str) -> Result<crate::backend::QueryResult> {
+    let conn = Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .with_context(|| format!("Failed to open database '{}'", path))?;
+    let result = conn.run(sql, None)?;
+    if result.columns.is_empty() {
+        anyhow::bail!("The statement doesn't return rows; use 'foreach' to change databases");
+    }
+    Ok(result)
+}
+
+/// Prints a table of per-database results followed by a one-line summary.
+///
+/// # Arguments
+///
+/// * `results` - The results from `run_foreach`.
+pub fn print_foreach_summary(results: &[ForeachResult]) {
+    let mut table = Table::new();
+    table.set_format(*prettytable::format::consts::FORMAT_BOX_CHARS);
+    table.add_row(row![b => "Database", "Status", "Rows Changed", "Time"]);
+    for result in results {
+        let (status, rows) = match &result.outcome {
+            Ok(rows) => ("ok".to_string(), rows.to_string()),
+            Err(e) => (format!("failed: {}", e), "-".to_string()),
+        };
+        table.add_row(row![
+            result.path,
+            status,
+            r -> rows,
+            format!("{:.3}s", result.elapsed.as_secs_f64())
+        ]);
+    }
+    table.printstd();
+
+    let failed = results.iter().filter(|r| r.outcome.is_err()).count();
+    println!(
+        "{} database(s): {} succeeded, {} failed",
+        results.len(),
+        results.len() - failed,
+        failed
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_foreach() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, table) in [("a.db", "config"), ("b.db", "config"), ("c.db", "other")] {
+            let conn = Connection::open(dir.path().join(name)).unwrap();
+            conn.execute_batch(&format!(
+                "CREATE TABLE {} (k TEXT, v TEXT); INSERT INTO {} VALUES ('a', '1'), ('b', '2');",
+                table, table
+            ))
+            .unwrap();
+        }
+        std::fs::write(dir.path().join("notes.txt"), "not a database").unwrap();
+
+        let pattern = format!("{}/*.db", dir.path().display());
+        let paths = expand_globs(&[pattern]).unwrap();
+        assert_eq!(paths.len(), 3);
+        assert!(expand_globs(&[format!("{}/*.sqlite", dir.path().display())]).is_err());
+
+        let sql = "UPDATE config SET v = 'x'; INSERT INTO config VALUES ('c', '3');";
+        let options = ForeachOptions { parallel: 2 };
+        let results = run_foreach(&paths, sql, &options);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].outcome, Ok(3));
+        assert_eq!(results[1].outcome, Ok(3));
+        assert!(results[2].outcome.is_err());
+        assert!(results[2].path.ends_with("c.db"));
+
+        let conn = Connection::open(&paths[0]).unwrap();
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM config WHERE v = 'x'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn test_federate_query() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut paths = Vec::new();
+        for (name, setup) in [
+            (
+                "a.db",
+                "CREATE TABLE config (k, v); INSERT INTO config VALUES ('a', 1), ('b', 2);",
+            ),
+            (
+                "b.db",
+                "CREATE TABLE config (k, v); INSERT INTO config VALUES ('c', 3);",
+            ),
+            ("c.db", "CREATE TABLE other (k);"),
+            ("d.db", "CREATE TABLE config (k, extra, v);"),
+        ] {
+            let path = dir.path().join(name);
+            Connection::open(&path)
+                .unwrap()
+                .execute_batch(setup)
+                .unwrap();
+            paths.push(path.to_string_lossy().to_string());
+        }
+        paths.push(dir.path().join("missing.db").to_string_lossy().to_string());
+
+        let federated =
+            federate_query(&paths, "SELECT * FROM config ORDER BY k", "source").unwrap();
+        assert_eq!(federated.columns, ["source", "k", "v"]);
+        assert_eq!(federated.rows.len(), 3);
+        assert_eq!(federated.rows[0][0], Value::Text(paths[0].clone()));
+        assert_eq!(federated.rows[2][0], Value::Text(paths[1].clone()));
+        // The missing table, the different columns, and the missing file are each reported
+        let failed: Vec<&str> = federated
+            .errors
+            .iter()
+            .map(|(path, _)| path.as_str())
+            .collect();
+        assert_eq!(failed, [&paths[2], &paths[3], &paths[4]]);
+        assert!(federated.errors[1].1.contains("returned columns"));
+
+        assert!(federate_query(&paths[2..3], "SELECT * FROM config", "source").is_err());
+        assert!(federate_query(&paths[4..], "SELECT 1", "source").is_err());
+        assert!(federate_query(&[], "SELECT 1", "source").is_err());
+    }
+}
@@ -0,0 +1,1018 @@
+//! # Pluggable Import/Export Formats
+//!
+//! This module defines a small plugin system for file formats. Each format implements the
+//! `Format` trait, which can produce a `RecordReader` (for imports) and/or a `RecordWriter`
+//! (for exports) over plain byte streams. Formats are registered in a `FormatRegistry`,
+//! and the generic `export_query` and `import_file` drivers look them up by name or by
+//! file extension.
+//!
+//! ## Built-in Formats:
+//! - `csv`: Comma-separated values (read and write).
+//! - `json`: A JSON array of objects, one per row (write).
+//! - `xlsx`: Excel workbook with a single sheet (write).
+//! - `xml`: Rows as `<row>` elements (write; see `import::import_xml_to_table` for reading).
+//! - `avro`: An Avro object container file (write; `avro` feature).
+//! - `arrow`: An Arrow IPC (Feather v2) file (read and write; `arrow` feature).
+//!
+//! ## Extending:
+//! Third-party crates can add formats without modifying `export.rs` by implementing
+//! `Format` and calling `register_format`:
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use vapor_cli::formats::{register_format, Format};
+//!
+//! struct Tsv;
+//! impl Format for Tsv {
+//!     fn name(&self) -> &'static str { "tsv" }
+//!     fn extensions(&self) -> &'static [&'static str] { &["tsv"] }
+//! }
+//!
+//! register_format(Arc::new(Tsv));
+//! ```
+
+use anyhow::{Context, Result};
+use rusqlite::types::Value;
+use rusqlite::Connection;
+use rust_xlsxwriter::Workbook;
+use std::io::{BufReader, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::backend::{Backend, QueryResult};
+use crate::compression::{create_output, open_input, strip_compression_extension, OutputWriter};
+use crate::export::{
+    open_export_output, skip_utf8_bom, write_bom, write_query_to_csv, write_xlsx_header,
+    write_xlsx_value, ExportOptions, XlsxFormats, XLSX_MAX_ROWS,
+};
+use crate::progress::ProgressTracker;
+
+pub use crate::backend::Record;
+
+/// A stream of records read from an input file.
+pub trait RecordReader {
+    /// Returns the column names of the records produced by this reader.
+    fn columns(&self) -> &[String];
+
+    /// Returns the SQLite column type of each column, when the input declares them.
+    /// Used to create a missing table on import; `None` creates `TEXT` columns.
+    fn column_types(&self) -> Option<Vec<&'static str>> {
+        None
+    }
+
+    /// Returns the next record, or `None` once the input is exhausted.
+    fn next_record(&mut self) -> Result<Option<Record>>;
+}
+
+/// A sink that writes records to an output stream.
+pub trait RecordWriter {
+    /// Receives the declared SQLite type of each column, when the export source knows
+    /// them. Called before `write_header`; formats with typed schemas use the types to
+    /// pick column types, and other formats can ignore them.
+    fn set_declared_types(&mut self, _declared: &[Option<String>]) {}
+
+    /// Writes the column header. Called exactly once before any records.
+    fn write_header(&mut self, columns: &[String]) -> Result<()>;
+
+    /// Writes a single record.
+    fn write_record(&mut self, record: &[Value]) -> Result<()>;
+
+    /// Flushes any buffered data and finalizes the output, including calling
+    /// `OutputWriter::finish`.
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+/// A file format that can read and/or write record streams.
+///
+/// Both `reader` and `writer` have default implementations that report the operation
+/// as unsupported, so a format only needs to implement the directions it supports.
+pub trait Format: Send + Sync {
+    /// A short, unique, lowercase name for the format (e.g. `csv`).
+    fn name(&self) -> &'static str;
+
+    /// File extensions (without the leading dot) handled by this format.
+    fn extensions(&self) -> &'static [&'static str];
+
+    /// Creates a reader over the given input stream.
+    fn reader(&self, _input: Box<dyn Read>) -> Result<Box<dyn RecordReader>> {
+        anyhow::bail!("Format '{}' does not support importing", self.name())
+    }
+
+    /// Creates a writer over the given output file.
+    fn writer(&self, _output: OutputWriter) -> Result<Box<dyn RecordWriter>> {
+        anyhow::bail!("Format '{}' does not support exporting", self.name())
+    }
+}
+
+/// A collection of available formats, searchable by name or file extension.
+#[derive(Clone, Default)]
+pub struct FormatRegistry {
+    formats: Vec<Arc<dyn Format>>,
+}
+
+impl FormatRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a registry containing all built-in formats.
+    pub fn with_builtin_formats() -> Self {
+        let mut registry = Self::new();
+        registry.register(Arc::new(CsvFormat));
+        registry.register(Arc::new(JsonFormat));
+        registry.register(Arc::new(XlsxFormat));
+        registry.register(Arc::new(XmlFormat));
+        #[cfg(feature = "avro")]
+        registry.register(Arc::new(crate::export::AvroFormat));
+        #[cfg(feature = "arrow")]
+        registry.register(Arc::new(crate::columnar::ArrowFormat));
+        registry
+    }
+
+    /// Adds a format to the registry, replacing any existing format with the same name.
+    pub fn register(&mut self, format: Arc<dyn Format>) {
+        self.formats.retain(|f| f.name() != format.name());
+        self.formats.push(format);
+    }
+
+    /// Looks up a format by its name (case-insensitive), or failing that by one of its
+    /// extensions, so `feather` finds the `arrow` format.
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Format>> {
+        let name = name.to_lowercase();
+        self.formats
+            .iter()
+            .find(|f| f.name() == name)
+            .or_else(|| {
+                self.formats
+                    .iter()
+                    .find(|f| f.extensions().contains(&name.as_str()))
+            })
+            .cloned()
+    }
+
+    /// Looks up a format by the extension of a file path (case-insensitive).
+    ///
+    /// Compression extensions are ignored, so `data.csv.gz` resolves to `csv`.
+    pub fn for_path(&self, path: &str) -> Option<Arc<dyn Format>> {
+        let extension = Path::new(strip_compression_extension(path))
+            .extension()?
+            .to_string_lossy()
+            .to_lowercase();
+        self.formats
+            .iter()
+            .find(|f| f.extensions().contains(&extension.as_str()))
+            .cloned()
+    }
+
+    /// Returns the names of all registered formats.
+    pub fn names(&self) -> Vec<&'static str> {
+        self.formats.iter().map(|f| f.name()).collect()
+    }
+
+    /// Resolves a format from an explicit name, falling back to the file extension.
+    pub fn resolve(&self, path: &str, format: Option<&str>) -> Result<Arc<dyn Format>> {
+        match format {
+            Some(name) => self.get(name).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown format '{}'. Available formats: {}",
+                    name,
+                    self.names().join(", ")
+                )
+            }),
+            None => self.for_path(path).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Cannot determine format from file name '{}'. Available formats: {}",
+                    path,
+                    self.names().join(", ")
+                )
+            }),
+        }
+    }
+}
+
+fn global_registry() -> &'static RwLock<FormatRegistry> {
+    static REGISTRY: OnceLock<RwLock<FormatRegistry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(FormatRegistry::with_builtin_formats()))
+}
+
+/// Registers a format in the process-wide registry used by the CLI and REPL.
+pub fn register_format(format: Arc<dyn Format>) {
+    global_registry().write().unwrap().register(format);
+}
+
+/// Returns a snapshot of the process-wide format registry.
+pub fn registry() -> FormatRegistry {
+    global_registry().read().unwrap().clone()
+}
+
+/// Exports the results of a query to a file using a registered format.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `query` - The SQL query whose results will be exported.
+/// * `path` - The output file path. It will be overwritten if it exists.
+/// * `format` - An explicit format name, or `None` to pick one from the file extension.
+///
+/// # Returns
+///
+/// A `Result` containing the number of rows written.
+pub fn export_query(
+    conn: &Connection,
+    query: &str,
+    path: &str,
+    format: Option<&str>,
+) -> Result<usize> {
+    export_query_with_options(conn, query, path, format, &ExportOptions::default())
+}
+
+/// Exports the results of a query to a file using a registered format and export options.
+///
+/// Output is compressed when requested in `options` or when the path ends in `.gz`/`.zst`.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `query` - The SQL query whose results will be exported.
+/// * `path` - The output file path. It will be overwritten if it exists.
+/// * `format` - An explicit format name, or `None` to pick one from the file extension.
+/// * `options` - An `ExportOptions` struct specifying compression and other settings.
+///
+/// # Returns
+///
+/// A `Result` containing the number of rows written.
+pub fn export_query_with_options(
+    conn: &Connection,
+    query: &str,
+    path: &str,
+    format: Option<&str>,
+    options: &ExportOptions,
+) -> Result<usize> {
+    let format = registry().resolve(path, format)?;
+    if format.name() == "csv" {
+        // CSV exports share the checkpoint and resume path with `export_to_csv`
+        let row_count = write_query_to_csv(conn, query, path, options, csv_text_row)?;
+        if !options.quiet {
+            println!(
+                "Successfully exported {} rows to '{}' ({})",
+                row_count,
+                path,
+                format.name()
+            );
+        }
+        return Ok(row_count);
+    }
+    if options.resume {
+        anyhow::bail!("Only CSV exports can be resumed");
+    }
+    if options.bom {
+        anyhow::bail!("A byte order mark can only be written to CSV exports");
+    }
+
+    let mut stmt = conn.prepare(query).with_context(|| {
+        format!(
+            "Failed to prepare export query. Check SQL syntax: {}",
+            query
+        )
+    })?;
+    let column_names: Vec<String> = stmt.column_names().iter().map(|&s| s.to_string()).collect();
+    if column_names.is_empty() {
+        anyhow::bail!(
+            "Query returned no columns. Make sure your query includes SELECT statements."
+        );
+    }
+    let declared_types: Vec<Option<String>> = stmt
+        .columns()
+        .iter()
+        .map(|c| c.decl_type().map(str::to_string))
+        .collect();
+
+    let output = open_export_output(path, options, None)?;
+    let mut writer = format.writer(output)?;
+    writer.set_declared_types(&declared_types);
+    writer.write_header(&column_names)?;
+
+    let mut rows = stmt
+        .query([])
+        .with_context(|| format!("Failed to execute export query: {}", query))?;
+    let mut row_count = 0;
+    let mut progress = ProgressTracker::new("export", None);
+    let mut record = Vec::with_capacity(column_names.len());
+
+    while let Some(row) = rows.next()? {
+        record.clear();
+        for i in 0..column_names.len() {
+            record.push(row.get::<_, Value>(i)?);
+        }
+        writer
+            .write_record(&record)
+            .with_context(|| format!("Failed to write row {}", row_count + 1))?;
+        row_count += 1;
+        progress.update(row_count as u64);
+    }
+
+    writer
+        .finish()
+        .with_context(|| format!("Failed to finalize '{}'", path))?;
+    progress.finish();
+
+    if !options.quiet {
+        println!(
+            "Successfully exported {} rows to '{}' ({})",
+            row_count,
+            path,
+            format.name()
+        );
+    }
+    Ok(row_count)
+}
+
+/// Exports the results of a query run on any `Backend`, such as a remote database.
+///
+/// Unlike `export_query_with_options`, the whole result set is fetched before writing,
+/// since remote backends return complete results.
+///
+/// # Arguments
+///
+/// * `backend` - The database to run the query against.
+/// * `query` - The SQL query whose results will be exported.
+/// * `path` - The output file path. It will be overwritten if it exists.
+/// * `format` - An explicit format name, or `None` to pick one from the file extension.
+/// * `options` - An `ExportOptions` struct specifying compression and other settings.
+///
+/// # Returns
+///
+/// A `Result` containing the number of rows written.
+pub fn export_backend_query(
+    backend: &dyn Backend,
+    query: &str,
+    path: &str,
+    format: Option<&str>,
+    options: &ExportOptions,
+) -> Result<usize> {
+    let format = registry().resolve(path, format)?;
+    if options.resume {
+        anyhow::bail!("Exports from remote databases cannot be resumed");
+    }
+    if options.bom && format.name() != "csv" {
+        anyhow::bail!("A byte order mark can only be written to CSV exports");
+    }
+    let result = backend
+        .run(query, None)
+        .with_context(|| format!("Failed to execute export query: {}", query))?;
+    if result.columns.is_empty() {
+        anyhow::bail!(
+            "Query returned no columns. Make sure your query includes SELECT statements."
+        );
+    }
+    write_result(&result, path, format, options)
+}
+
+/// Exports rows that were already fetched, such as those returned by an `INSERT ...
+/// RETURNING`, to a file using a registered format.
+///
+/// # Arguments
+///
+/// * `result` - The columns and rows to write.
+/// * `path` - The output file path. It will be overwritten if it exists.
+/// * `format` - An explicit format name, or `None` to pick one from the file extension.
+/// * `options` - An `ExportOptions` struct specifying compression and other settings.
+///
+/// # Returns
+///
+/// A `Result` containing the number of rows written.
+pub fn export_result(
+    result: &QueryResult,
+    path: &str,
+    format: Option<&str>,
+    options: &ExportOptions,
+) -> Result<usize> {
+    let format = registry().resolve(path, format)?;
+    if options.resume {
+        anyhow::bail!("Rows returned by a write cannot be resumed; export them again instead");
+    }
+    if options.bom && format.name() != "csv" {
+        anyhow::bail!("A byte order mark can only be written to CSV exports");
+    }
+    write_result(result, path, format, options)
+}
+
+/// Writes a complete result set to `path` in `format`.
+fn write_result(
+    result: &QueryResult,
+    path: &str,
+    format: Arc<dyn Format>,
+    options: &ExportOptions,
+) -> Result<usize> {
+    let mut output = create_output(path, options.compression)?;
+    write_bom(&mut output, options)?;
+    let mut writer = format.writer(output)?;
+    writer.write_header(&result.columns)?;
+    for (i, record) in result.rows.iter().enumerate() {
+        writer
+            .write_record(record)
+            .with_context(|| format!("Failed to write row {}", i + 1))?;
+    }
+    writer
+        .finish()
+        .with_context(|| format!("Failed to finalize '{}'", path))?;
+
+    if !options.quiet {
+        println!(
+            "Successfully exported {} rows to '{}' ({})",
+            result.rows.len(),
+            path,
+            format.name()
+        );
+    }
+    Ok(result.rows.len())
+}
+
+/// Options controlling how a file is imported with a registered format.
+#[derive(Debug, Clone, Default)]
+pub struct FileImportOptions {
+    /// Create the table if it does not exist, with the column types the input declares
+    /// (or `TEXT` columns when it declares none).
+    pub create_table: bool,
+}
+
+/// Imports a file into an existing table using a registered format.
+///
+/// Column names from the input are mapped to table columns by name. All rows are
+/// inserted within a single transaction.
+///
+/// # Arguments
+///
+/// * `conn` - A mutable reference to the active `rusqlite::Connection`.
+/// * `path` - The input file path.
+/// * `table_name` - The table to insert rows into.
+/// * `format` - An explicit format name, or `None` to pick one from the file extension.
+///
+/// # Returns
+///
+/// A `Result` containing the number of rows imported.
+pub fn import_file(
+    conn: &mut Connection,
+    path: &str,
+    table_name: &str,
+    format: Option<&str>,
+) -> Result<usize> {
+    import_file_with_options(
+        conn,
+        path,
+        table_name,
+        format,
+        &FileImportOptions::default(),
+    )
+}
+
+/// Imports a file into a table using a registered format and import options.
+///
+/// # Arguments
+///
+/// * `conn` - A mutable reference to the active `rusqlite::Connection`.
+/// * `path` - The input file path.
+/// * `table_name` - The table to insert rows into.
+/// * `format` - An explicit format name, or `None` to pick one from the file extension.
+/// * `options` - A `FileImportOptions` struct, e.g. to create a missing table.
+///
+/// # Returns
+///
+/// A `Result` containing the number of rows imported.
+pub fn import_file_with_options(
+    conn: &mut Connection,
+    path: &str,
+    table_name: &str,
+    format: Option<&str>,
+    options: &FileImportOptions,
+) -> Result<usize> {
+    let format = registry().resolve(path, format)?;
+    let mut reader = format.reader(open_input(path)?)?;
+    if reader.columns().is_empty() {
+        anyhow::bail!("Input file '{}' has no columns", path);
+    }
+
+    if options.create_table {
+        let types = reader.column_types();
+        let definitions = reader
+            .columns()
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                let column_type = types.as_ref().and_then(|t| t.get(i)).unwrap_or(&"TEXT");
+                format!("{} {}", quote_identifier(column), column_type)
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        conn.execute(
+            &format!(
+                "CREATE TABLE IF NOT EXISTS {} ({})",
+                quote_identifier(table_name),
+                definitions
+            ),
+            [],
+        )
+        .with_context(|| format!("Failed to create table '{}'", table_name))?;
+    }
+
+    let imported = insert_records(conn, reader.as_mut(), table_name)?;
+
+    println!(
+        "Successfully imported {} rows from '{}' into '{}' ({})",
+        imported,
+        path,
+        table_name,
+        format.name()
+    );
+    Ok(imported)
+}
+
+/// Inserts every record from a reader into a table within a single transaction.
+///
+/// Reader columns are mapped to table columns by name.
+pub(crate) fn insert_records(
+    conn: &mut Connection,
+    reader: &mut dyn RecordReader,
+    table_name: &str,
+) -> Result<usize> {
+    let columns = reader.columns().to_vec();
+    let sql = format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        quote_identifier(table_name),
+        columns
+            .iter()
+            .map(|c| quote_identifier(c))
+            .collect::<Vec<_>>()
+            .join(","),
+        columns.iter().map(|_| "?").collect::<Vec<_>>().join(",")
+    );
+
+    let tx = conn.transaction()?;
+    let mut imported = 0;
+    {
+        let mut stmt = tx
+            .prepare(&sql)
+            .with_context(|| format!("Failed to prepare insert into '{}'", table_name))?;
+        let mut progress = ProgressTracker::new("import", None);
+
+        while let Some(record) = reader.next_record()? {
+            stmt.execute(rusqlite::params_from_iter(record.iter()))
+                .with_context(|| format!("Failed to insert record {}", imported + 1))?;
+            imported += 1;
+            progress.update(imported as u64);
+            progress.check_cancelled()?;
+        }
+        progress.finish();
+    }
+    tx.commit()?;
+    Ok(imported)
+}
+
+/// Quotes an identifier for use in generated SQL.
+pub(crate) fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Builds a `SELECT` statement that exports some or all columns of a table.
+///
+/// # Arguments
+///
+/// * `table` - The table to select from.
+/// * `columns` - The columns to select. If empty, all columns are selected.
+/// * `filter` - An optional SQL condition for a `WHERE` clause (e.g. `age > 30`).
+///
+/// # Returns
+///
+/// The SQL query, with the table and column names quoted.
+pub fn table_select_query(table: &str, columns: &[String], filter: Option<&str>) -> String {
+    let columns = if columns.is_empty() {
+        "*".to_string()
+    } else {
+        columns
+            .iter()
+            .map(|c| quote_identifier(c.trim()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let mut query = format!("SELECT {} FROM {}", columns, quote_identifier(table));
+    if let Some(filter) = filter.filter(|f| !f.trim().is_empty()) {
+        query.push_str(&format!(" WHERE {}", filter));
+    }
+    query
+}
+
+/// Converts a SQLite value to the plain-text form used by text-based formats.
+pub fn value_to_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::Integer(i) => i.to_string(),
+        Value::Real(f) => f.to_string(),
+        Value::Text(t) => t.clone(),
+        Value::Blob(b) => format!("[BLOB {} bytes]", b.len()),
+    }
+}
+
+/// Formats a row as CSV fields the way the `csv` format writes values.
+fn csv_text_row(row: &rusqlite::Row, column_names: &[String]) -> Result<Vec<String>> {
+    (0..column_names.len())
+        .map(|i| Ok(value_to_text(&row.get::<_, Value>(i)?)))
+        .collect()
+}
+
+/// Converts a SQLite value to a JSON value.
+pub fn value_to_json(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Null => serde_json::Value::Null,
+        Value::Integer(i) => serde_json::Value::from(*i),
+        Value::Real(f) => serde_json::Number::from_f64(*f)
+            .map(serde_json::Value::Number)
+            .unwrap_or(serde_json::Value::Null),
+        Value::Text(t) => serde_json::Value::String(t.clone()),
+        Value::Blob(b) => serde_json::Value::String(format!("[BLOB {} bytes]", b.len())),
+    }
+}
+
+/// Comma-separated values.
+pub struct CsvFormat;
+
+struct CsvRecordReader {
+    reader: csv::Reader<BufReader<Box<dyn Read>>>,
+    columns: Vec<String>,
+    record: csv::StringRecord,
+}
+
+impl RecordReader for CsvRecordReader {
+    fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    fn next_record(&mut self) -> Result<Option<Record>> {
+        if !self.reader.read_record(&mut self.record)? {
+            return Ok(None);
+        }
+        Ok(Some(
+            self.record
+                .iter()
+                .map(|field| Value::Text(field.to_string()))
+                .collect(),
+        ))
+    }
+}
+
+struct CsvRecordWriter {
+    writer: csv::Writer<OutputWriter>,
+}
+
+impl RecordWriter for CsvRecordWriter {
+    fn write_header(&mut self, columns: &[String]) -> Result<()> {
+        self.writer.write_record(columns)?;
+        Ok(())
+    }
+
+    fn write_record(&mut self, record: &[Value]) -> Result<()> {
+        self.writer.write_record(record.iter().map(value_to_text))?;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        self.writer
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .finish()
+    }
+}
+
+impl Format for CsvFormat {
+    fn name(&self) -> &'static str {
+        "csv"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["csv"]
+    }
+
+    fn reader(&self, input: Box<dyn Read>) -> Result<Box<dyn RecordReader>> {
+        let mut input = BufReader::new(input);
+        skip_utf8_bom(&mut input)?;
+        let mut reader = csv::Reader::from_reader(input);
+        let columns = reader.headers()?.iter().map(|h| h.to_string()).collect();
+        Ok(Box::new(CsvRecordReader {
+            reader,
+            columns,
+            record: csv::StringRecord::new(),
+        }))
+    }
+
+    fn writer(&self, output: OutputWriter) -> Result<Box<dyn RecordWriter>> {
+        Ok(Box::new(CsvRecordWriter {
+            writer: csv::Writer::from_writer(output),
+        }))
+    }
+}
+
+/// A JSON array of objects keyed by column name.
+pub struct JsonFormat;
+
+struct JsonRecordWriter {
+    output: OutputWriter,
+    columns: Vec<String>,
+    first: bool,
+}
+
+impl RecordWriter for JsonRecordWriter {
+    fn write_header(&mut self, columns: &[String]) -> Result<()> {
+        self.columns = columns.to_vec();
+        self.output.write_all(b"[")?;
+        Ok(())
+    }
+
+    fn write_record(&mut self, record: &[Value]) -> Result<()> {
+        let object: serde_json::Map<String, serde_json::Value> = self
+            .columns
+            .iter()
+            .cloned()
+            .zip(record.iter().map(value_to_json))
+            .collect();
+        if !self.first {
+            self.output.write_all(b",")?;
+        }
+        self.first = false;
+        self.output.write_all(b"\n  ")?;
+        serde_json::to_writer(&mut self.output, &object)?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.output.write_all(b"\n]\n")?;
+        self.output.finish()
+    }
+}
+
+impl Format for JsonFormat {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["json"]
+    }
+
+    fn writer(&self, output: OutputWriter) -> Result<Box<dyn RecordWriter>> {
+        Ok(Box::new(JsonRecordWriter {
+            output,
+            columns: Vec::new(),
+            first: true,
+        }))
+    }
+}
+
+/// Rows as XML elements: `<rows><row><column>value</column></row></rows>`.
+///
+/// Columns whose names are not valid element names (such as `first name`) are written
+/// as `<field name="first name">value</field>`, so importing the file back recreates the
+/// same columns. `NULL` values are omitted so that importing the file yields `NULL` again.
+pub struct XmlFormat;
+
+struct XmlRecordWriter {
+    output: OutputWriter,
+    /// The opening and closing tag of each column's element.
+    elements: Vec<(String, String)>,
+}
+
+impl RecordWriter for XmlRecordWriter {
+    fn write_header(&mut self, columns: &[String]) -> Result<()> {
+        self.elements = columns.iter().map(|c| xml_column_tags(c)).collect();
+        self.output
+            .write_all(b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<rows>\n")?;
+        Ok(())
+    }
+
+    fn write_record(&mut self, record: &[Value]) -> Result<()> {
+        self.output.write_all(b"  <row>")?;
+        for ((open, close), value) in self.elements.iter().zip(record) {
+            if matches!(value, Value::Null) {
+                continue;
+            }
+            write!(
+                self.output,
+                "{}{}{}",
+                open,
+                escape_xml(&value_to_text(value)),
+                close
+            )?;
+        }
+        self.output.write_all(b"</row>\n")?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.output.write_all(b"</rows>\n")?;
+        self.output.finish()
+    }
+}
+
+impl Format for XmlFormat {
+    fn name(&self) -> &'static str {
+        "xml"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["xml"]
+    }
+
+    fn writer(&self, output: OutputWriter) -> Result<Box<dyn RecordWriter>> {
+        Ok(Box::new(XmlRecordWriter {
+            output,
+            elements: Vec::new(),
+        }))
+    }
+}
+
+/// The element used for columns whose names are not valid element names.
+pub(crate) const XML_FIELD_ELEMENT: &str = "field";
+
+/// Returns the opening and closing tags that hold a column's value.
+///
+/// A column whose name is a valid element name is written as its own element. Any other
+/// column is written as a `field` element with the column name in its `name` attribute.
+fn xml_column_tags(column: &str) -> (String, String) {
+    if is_xml_element_name(column) {
+        (format!("<{}>", column), format!("</{}>", column))
+    } else {
+        (
+            format!("<{} name=\"{}\">", XML_FIELD_ELEMENT, escape_xml(column)),
+            format!("</{}>", XML_FIELD_ELEMENT),
+        )
+    }
+}
+
+/// Returns `true` if a column name can be used as an XML element name unchanged.
+fn is_xml_element_name(column: &str) -> bool {
+    column.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && column
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+/// Escapes the characters that are not allowed in XML text.
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// An Excel workbook with a single `Results` sheet.
+pub struct XlsxFormat;
+
+struct XlsxRecordWriter {
+    output: OutputWriter,
+    workbook: Workbook,
+    formats: XlsxFormats,
+    row: u32,
+}
+
+impl RecordWriter for XlsxRecordWriter {
+    fn write_header(&mut self, columns: &[String]) -> Result<()> {
+        let worksheet = self.workbook.worksheet_from_index(0)?;
+        write_xlsx_header(worksheet, columns, &self.formats)
+    }
+
+    fn write_record(&mut self, record: &[Value]) -> Result<()> {
+        if self.row as usize + 1 >= XLSX_MAX_ROWS {
+            anyhow::bail!(
+                "Result set exceeds the Excel limit of {} rows per sheet. Use CSV export instead.",
+                XLSX_MAX_ROWS - 1
+            );
+        }
+        self.row += 1;
+        let worksheet = self.workbook.worksheet_from_index(0)?;
+        for (col, value) in record.iter().enumerate() {
+            write_xlsx_value(worksheet, self.row, col as u16, value.into(), &self.formats)?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.workbook.worksheet_from_index(0)?.autofit();
+        let buffer = self.workbook.save_to_buffer()?;
+        self.output.write_all(&buffer)?;
+        self.output.finish()
+    }
+}
+
+impl Format for XlsxFormat {
+    fn name(&self) -> &'static str {
+        "xlsx"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["xlsx"]
+    }
+
+    fn writer(&self, output: OutputWriter) -> Result<Box<dyn RecordWriter>> {
+        let mut workbook = Workbook::new();
+        workbook.add_worksheet().set_name("Results")?;
+        Ok(Box::new(XlsxRecordWriter {
+            output,
+            workbook,
+            formats: XlsxFormats::new(),
+            row: 0,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    struct Tsv;
+
+    impl Format for Tsv {
+        fn name(&self) -> &'static str {
+            "tsv"
+        }
+
+        fn extensions(&self) -> &'static [&'static str] {
+            &["tsv"]
+        }
+    }
+
+    #[test]
+    fn test_registry_lookup() {
+        let mut registry = FormatRegistry::with_builtin_formats();
+        assert_eq!(registry.for_path("out.CSV").unwrap().name(), "csv");
+        assert_eq!(registry.get("JSON").unwrap().name(), "json");
+        assert!(registry.for_path("out.tsv").is_none());
+
+        registry.register(Arc::new(Tsv));
+        assert_eq!(registry.for_path("out.tsv").unwrap().name(), "tsv");
+        assert!(registry.resolve("x", Some("nope")).is_err());
+    }
+
+    #[test]
+    fn test_table_select_query() {
+        assert_eq!(
+            table_select_query("users", &[], None),
+            "SELECT * FROM \"users\""
+        );
+        assert_eq!(
+            table_select_query(
+                "users",
+                &["id".to_string(), " name".to_string()],
+                Some("age > 30")
+            ),
+            "SELECT \"id\", \"name\" FROM \"users\" WHERE age > 30"
+        );
+    }
+
+    #[test]
+    fn test_export_and_import_roundtrip() -> Result<()> {
+        let dir = tempdir()?;
+        let mut conn = Connection::open_in_memory()?;
+        conn.execute_batch(
+            "CREATE TABLE src (id INTEGER, name TEXT);
+             INSERT INTO src VALUES (1, 'a'), (2, 'b, with comma');
+             CREATE TABLE dst (id INTEGER, name TEXT);",
+        )?;
+
+        let csv_path = dir.path().join("out.csv");
+        let csv_path = csv_path.to_str().unwrap();
+        assert_eq!(export_query(&conn, "SELECT * FROM src", csv_path, None)?, 2);
+        assert_eq!(import_file(&mut conn, csv_path, "dst", None)?, 2);
+        let name: String = conn.query_row("SELECT name FROM dst WHERE id = 2", [], |r| r.get(0))?;
+        assert_eq!(name, "b, with comma");
+
+        // A created table whose name needs quoting is quoted the same way for the inserts
+        let options = FileImportOptions { create_table: true };
+        assert_eq!(
+            import_file_with_options(&mut conn, csv_path, "new rows", None, &options)?,
+            2
+        );
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM \"new rows\"", [], |r| r.get(0))?;
+        assert_eq!(count, 2);
+
+        let json_path = dir.path().join("out.json");
+        export_query(
+            &conn,
+            "SELECT * FROM src",
+            json_path.to_str().unwrap(),
+            None,
+        )?;
+        let parsed: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&json_path)?)?;
+        assert_eq!(parsed[0]["id"], 1);
+        assert_eq!(parsed[1]["name"], "b, with comma");
+        Ok(())
+    }
+}
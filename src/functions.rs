@@ -0,0 +1,203 @@
+//! # Built-in SQL Functions
+//!
+//! This module registers SQL functions that SQLite leaves out or that other databases have,
+//! so common queries don't fail with "no such function". They are registered on every
+//! connection vapor-cli opens through `ConnectionOptions`, and on `Session` connections.
+//!
+//! ## Features:
+//! - **`REGEXP`**: `text REGEXP pattern` (or `regexp(pattern, text)`) matches with Rust's
+//!   `regex` syntax. Each statement compiles its pattern once.
+//! - **`UUID()`**: A random version 4 UUID, e.g. for filling key columns.
+//! - **`MD5(x)` and `SHA256(x)`**: Lowercase hex digests of text or blobs.
+//! - **`DATE_FORMAT(value, format)`**: Formats a date, a datetime, or a Unix timestamp in
+//!   seconds with `strftime`-style specifiers, such as `'%d %b %Y'`.
+//!
+//! Every function returns NULL when its value argument is NULL, like SQLite's own.
+
+use std::fmt::Write;
+
+use anyhow::{Context as _, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use md5::Md5;
+use regex::Regex;
+use rusqlite::functions::{Context, FunctionFlags};
+use rusqlite::types::{Null, ToSqlOutput, ValueRef};
+use rusqlite::{Connection, Error};
+use sha2::{Digest, Sha256};
+
+/// Registers the built-in functions on a connection.
+///
+/// # Arguments
+///
+/// * `conn` - The connection to register the functions on.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` on success, or an `Err` if SQLite refused a function.
+pub fn register_functions(conn: &Connection) -> Result<()> {
+    register(conn).context("Failed to register the built-in SQL functions")
+}
+
+pub(crate) fn register(conn: &Connection) -> rusqlite::Result<()> {
+    conn.create_scalar_function("regexp", 2, deterministic(), regexp)?;
+    conn.create_scalar_function("uuid", 0, FunctionFlags::SQLITE_UTF8, |_| {
+        Ok(uuid::Uuid::new_v4().to_string())
+    })?;
+    conn.create_scalar_function("md5", 1, deterministic(), hex_digest::<Md5>)?;
+    conn.create_scalar_function("sha256", 1, deterministic(), hex_digest::<Sha256>)?;
+    conn.create_scalar_function("date_format", 2, deterministic(), date_format)?;
+    Ok(())
+}
+
+fn deterministic() -> FunctionFlags {
+    FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC
+}
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+fn user_error(message: String) -> Error {
+    Error::UserFunctionError(message.into())
+}
+
+fn regexp(ctx: &Context<'_>) -> rusqlite::Result<Option<bool>> {
+    // Numbers are matched as the text SQLite would show for them
+    let text = match ctx.get_raw(1) {
+        ValueRef::Null => return Ok(None),
+        ValueRef::Text(bytes) | ValueRef::Blob(bytes) => String::from_utf8_lossy(bytes),
+        ValueRef::Integer(i) => i.to_string().into(),
+        ValueRef::Real(f) => f.to_string().into(),
+    };
+    // SQLite keeps the compiled pattern for as long as the argument stays the same
+    let pattern = ctx.get_or_create_aux(0, |value| -> Result<Regex, BoxError> {
+        let pattern = value.as_str()?;
+        Regex::new(pattern)
+            .map_err(|e| format!("Invalid REGEXP pattern '{}': {}", pattern, e).into())
+    })?;
+    Ok(Some(pattern.is_match(&text)))
+}
+
+fn hex_digest<D: Digest>(ctx: &Context<'_>) -> rusqlite::Result<ToSqlOutput<'static>> {
+    let digest = match ctx.get_raw(0) {
+        ValueRef::Null => return Ok(ToSqlOutput::from(Null)),
+        ValueRef::Text(bytes) | ValueRef::Blob(bytes) => D::digest(bytes),
+        ValueRef::Integer(i) => D::digest(i.to_string()),
+        ValueRef::Real(f) => D::digest(f.to_string()),
+    };
+    let mut hex = String::with_capacity(digest.len() * 2);
+    for byte in digest {
+        let _ = write!(hex, "{:02x}", byte);
+    }
+    Ok(ToSqlOutput::from(hex))
+}
+
+/// Reads a datetime in the forms SQLite's date functions accept, or Unix seconds.
+fn parse_datetime(value: ValueRef<'_>) -> Option<NaiveDateTime> {
+    let text = match value {
+        ValueRef::Integer(seconds) => {
+            return DateTime::from_timestamp(seconds, 0).map(|dt| dt.naive_utc())
+        }
+        ValueRef::Real(seconds) => {
+            return DateTime::from_timestamp_millis((seconds * 1000.0) as i64)
+                .map(|dt| dt.naive_utc())
+        }
+        ValueRef::Text(text) => std::str::from_utf8(text).ok()?.trim(),
+        ValueRef::Null | ValueRef::Blob(_) => return None,
+    };
+    if let Ok(dt) = DateTime::parse_from_rfc3339(text) {
+        return Some(dt.naive_utc());
+    }
+    [
+        "%Y-%m-%d %H:%M:%S%.f",
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+    .or_else(|| {
+        NaiveDate::parse_from_str(text, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+    })
+}
+
+fn date_format(ctx: &Context<'_>) -> rusqlite::Result<Option<String>> {
+    let format: String = ctx.get(1)?;
+    let Some(datetime) = parse_datetime(ctx.get_raw(0)) else {
+        return Ok(None);
+    };
+    let mut formatted = String::new();
+    // chrono reports an unknown specifier as a formatting error rather than at parse time
+    write!(formatted, "{}", datetime.format(&format))
+        .map_err(|_| user_error(format!("Invalid DATE_FORMAT format '{}'", format)))?;
+    Ok(Some(formatted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(conn: &Connection, sql: &str) -> Option<String> {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_builtin_functions() {
+        let conn = Connection::open_in_memory().unwrap();
+        register_functions(&conn).unwrap();
+
+        let matched: i64 = conn
+            .query_row("SELECT 'order-123' REGEXP '^order-\\d+$'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(matched, 1);
+        let matched: i64 = conn
+            .query_row("SELECT 42 REGEXP '^4'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(matched, 1);
+        assert_eq!(query(&conn, "SELECT NULL REGEXP 'a'"), None);
+        let error = conn
+            .query_row("SELECT 'a' REGEXP '('", [], |row| row.get::<_, i64>(0))
+            .unwrap_err();
+        assert!(error.to_string().contains("Invalid REGEXP pattern"));
+
+        let uuid = query(&conn, "SELECT uuid()").unwrap();
+        assert_eq!(uuid.len(), 36);
+        assert_ne!(query(&conn, "SELECT uuid()").unwrap(), uuid);
+
+        assert_eq!(
+            query(&conn, "SELECT md5('abc')").unwrap(),
+            "900150983cd24fb0d6963f7d28e17f72"
+        );
+        assert_eq!(
+            query(&conn, "SELECT sha256('abc')").unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(query(&conn, "SELECT md5(NULL)"), None);
+
+        assert_eq!(
+            query(
+                &conn,
+                "SELECT date_format('2024-03-05 14:30:00', '%d %b %Y %H:%M')"
+            )
+            .unwrap(),
+            "05 Mar 2024 14:30"
+        );
+        assert_eq!(
+            query(&conn, "SELECT date_format('2024-03-05', '%A')").unwrap(),
+            "Tuesday"
+        );
+        assert_eq!(
+            query(&conn, "SELECT date_format(0, '%Y-%m-%d')").unwrap(),
+            "1970-01-01"
+        );
+        assert_eq!(query(&conn, "SELECT date_format('soon', '%Y')"), None);
+        assert!(conn
+            .query_row("SELECT date_format('2024-03-05', '%Q')", [], |row| row
+                .get::<_, String>(
+                0
+            ))
+            .is_err());
+    }
+}
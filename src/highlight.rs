@@ -0,0 +1,431 @@
+//! # SQL Syntax Highlighting
+//!
+//! This module colors SQL in the REPL prompt as it is typed. It plugs into `rustyline`
+//! as the editor's helper and uses the same `colored` styles as the rest of the output.
+//! The helper also tells the editor when a statement is incomplete, so Enter starts a
+//! new line instead of running it, and completes dot-commands and bookmark names.
+//!
+//! ## Features:
+//! - **Keywords**: Common SQL keywords are shown in bold blue, in any letter case.
+//! - **Literals**: String literals are green and numbers are yellow.
+//! - **Comments**: `--` and `/* */` comments are dimmed.
+//! - **Toggle**: Highlighting can be switched off at runtime for terminals that don't
+//!   handle ANSI colors, and is off by default when `NO_COLOR` is set or `TERM=dumb`.
+//! - **Multi-line input**: Uses `statement::is_complete_input` to keep reading lines
+//!   until the statement is complete.
+//! - **Completion**: Tab completes dot-commands and bookmark names, most used first, and
+//!   the aliases of attached databases.
+
+use colored::Colorize;
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::Helper;
+use std::borrow::Cow;
+
+use crate::bookmarks::BookmarkManager;
+use crate::db::KNOWN_PRAGMAS;
+use crate::repl::DOT_COMMANDS;
+use crate::statement::is_complete_input;
+use crate::usage::UsageStats;
+
+const KEYWORDS: &[&str] = &[
+    "ABORT",
+    "ADD",
+    "ALL",
+    "ALTER",
+    "ANALYZE",
+    "AND",
+    "AS",
+    "ASC",
+    "ATTACH",
+    "AUTOINCREMENT",
+    "BEGIN",
+    "BETWEEN",
+    "BY",
+    "CASCADE",
+    "CASE",
+    "CAST",
+    "CHECK",
+    "COLLATE",
+    "COLUMN",
+    "COMMIT",
+    "CONFLICT",
+    "CONSTRAINT",
+    "CREATE",
+    "CROSS",
+    "CURRENT_DATE",
+    "CURRENT_TIME",
+    "CURRENT_TIMESTAMP",
+    "DEFAULT",
+    "DELETE",
+    "DESC",
+    "DETACH",
+    "DISTINCT",
+    "DROP",
+    "ELSE",
+    "END",
+    "ESCAPE",
+    "EXCEPT",
+    "EXISTS",
+    "EXPLAIN",
+    "FOREIGN",
+    "FROM",
+    "FULL",
+    "GLOB",
+    "GROUP",
+    "HAVING",
+    "IF",
+    "IGNORE",
+    "IN",
+    "INDEX",
+    "INNER",
+    "INSERT",
+    "INTERSECT",
+    "INTO",
+    "IS",
+    "JOIN",
+    "KEY",
+    "LEFT",
+    "LIKE",
+    "LIMIT",
+    "NOT",
+    "NULL",
+    "OFFSET",
+    "ON",
+    "OR",
+    "ORDER",
+    "OUTER",
+    "OVER",
+    "PARTITION",
+    "PRAGMA",
+    "PRIMARY",
+    "RECURSIVE",
+    "REFERENCES",
+    "REINDEX",
+    "RELEASE",
+    "RENAME",
+    "REPLACE",
+    "RETURNING",
+    "RIGHT",
+    "ROLLBACK",
+    "SAVEPOINT",
+    "SELECT",
+    "SET",
+    "TABLE",
+    "TEMP",
+    "TEMPORARY",
+    "THEN",
+    "TRANSACTION",
+    "TRIGGER",
+    "UNION",
+    "UNIQUE",
+    "UPDATE",
+    "USING",
+    "VACUUM",
+    "VALUES",
+    "VIEW",
+    "VIRTUAL",
+    "WHEN",
+    "WHERE",
+    "WINDOW",
+    "WITH",
+    "WITHOUT",
+];
+
+/// The `rustyline` helper for the REPL editor, which highlights SQL as it is typed.
+#[derive(Debug, Clone)]
+pub struct SqlHelper {
+    /// Whether the input line is colored.
+    pub enabled: bool,
+    /// The aliases of attached databases, for completion.
+    pub databases: Vec<String>,
+}
+
+impl Default for SqlHelper {
+    fn default() -> Self {
+        let dumb_terminal = std::env::var("TERM").is_ok_and(|term| term == "dumb");
+        Self {
+            enabled: !dumb_terminal && colored::control::SHOULD_COLORIZE.should_colorize(),
+            databases: Vec::new(),
+        }
+    }
+}
+
+impl Highlighter for SqlHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        if self.enabled {
+            Cow::Owned(highlight_sql(line))
+        } else {
+            Cow::Borrowed(line)
+        }
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize) -> bool {
+        // Redraw on every keystroke so a word is colored as soon as it is complete
+        self.enabled
+    }
+}
+
+impl Completer for SqlHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let before = &line[..pos];
+        let parts: Vec<&str> = before.split_whitespace().collect();
+        let typing_new_word = before.ends_with(char::is_whitespace);
+        let usage = UsageStats::load();
+
+        // `.bookmark run|show|delete NAME`
+        let bookmark_prefix = match (parts.as_slice(), typing_new_word) {
+            ([".bookmark", "run" | "show" | "delete"], true) => Some(""),
+            ([".bookmark", "run" | "show" | "delete", name], false) => Some(*name),
+            _ => None,
+        };
+        if let Some(prefix) = bookmark_prefix {
+            let mut names: Vec<String> = BookmarkManager::new()
+                .map(|manager| manager.bookmark_names())
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|name| name.starts_with(prefix))
+                .collect();
+            usage.rank_bookmarks(&mut names);
+            return Ok((pos - prefix.len(), names));
+        }
+
+        // `.pragma NAME`
+        let pragma_prefix = match (parts.as_slice(), typing_new_word) {
+            ([".pragma"], true) => Some(""),
+            ([".pragma", name], false) => Some(*name),
+            _ => None,
+        };
+        if let Some(prefix) = pragma_prefix {
+            let names = std::iter::once("list")
+                .chain(KNOWN_PRAGMAS.iter().copied())
+                .filter(|name| name.starts_with(prefix))
+                .map(str::to_string)
+                .collect();
+            return Ok((pos - prefix.len(), names));
+        }
+
+        // A dot-command being typed at the start of the line
+        if let ([command], false) = (parts.as_slice(), typing_new_word) {
+            if command.starts_with('.') {
+                let mut commands: Vec<&str> = DOT_COMMANDS
+                    .iter()
+                    .copied()
+                    .filter(|name| name.starts_with(command))
+                    .collect();
+                usage.rank_commands(&mut commands, |name| name);
+                let start = pos - command.len();
+                return Ok((start, commands.into_iter().map(str::to_string).collect()));
+            }
+        }
+
+        // `.detach ALIAS`, or an alias starting a qualified name in SQL
+        let word = if typing_new_word {
+            ""
+        } else {
+            parts.last().copied().unwrap_or("")
+        };
+        let word = word.rsplit(['(', ',']).next().unwrap_or(word);
+        let candidates: Vec<String> = match (parts.as_slice(), typing_new_word) {
+            ([".detach"], true) | ([".detach", _], false) => self
+                .databases
+                .iter()
+                .filter(|alias| alias.starts_with(word))
+                .cloned()
+                .collect(),
+            ([first, ..], _) if !first.starts_with('.') && !word.is_empty() => self
+                .databases
+                .iter()
+                .filter(|alias| alias.to_lowercase().starts_with(&word.to_lowercase()))
+                .map(|alias| format!("{}.", alias))
+                .collect(),
+            _ => Vec::new(),
+        };
+        Ok((pos - word.len(), candidates))
+    }
+}
+
+impl Hinter for SqlHelper {
+    type Hint = String;
+}
+
+impl Validator for SqlHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        if is_complete_input(ctx.input()) {
+            Ok(ValidationResult::Valid(None))
+        } else {
+            Ok(ValidationResult::Incomplete)
+        }
+    }
+}
+
+impl Helper for SqlHelper {}
+
+/// Adds ANSI colors to a line of SQL.
+///
+/// Dot-commands are left as they are. An unterminated string or block comment is colored
+/// to the end of the line.
+///
+/// # Arguments
+///
+/// * `line` - The SQL to highlight.
+///
+/// # Returns
+///
+/// The line with ANSI color codes around keywords, literals, and comments.
+pub fn highlight_sql(line: &str) -> String {
+    if line.trim_start().starts_with('.') {
+        return line.to_string();
+    }
+
+    let mut out = String::with_capacity(line.len() * 2);
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        let len = if rest.starts_with("--") {
+            let len = rest.find('\n').unwrap_or(rest.len());
+            out.push_str(&rest[..len].dimmed().to_string());
+            len
+        } else if let Some(body) = rest.strip_prefix("/*") {
+            let len = body.find("*/").map_or(rest.len(), |end| end + 4);
+            out.push_str(&rest[..len].dimmed().to_string());
+            len
+        } else if c == '\'' {
+            let len = quoted_len(rest);
+            out.push_str(&rest[..len].green().to_string());
+            len
+        } else if c == '"' || c == '`' || c == '[' {
+            // Quoted identifiers are never keywords; copy them through untouched
+            let close = if c == '[' { ']' } else { c };
+            let len = rest[1..].find(close).map_or(rest.len(), |end| end + 2);
+            out.push_str(&rest[..len]);
+            len
+        } else if c.is_ascii_digit() {
+            let len = rest
+                .find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '.'))
+                .unwrap_or(rest.len());
+            out.push_str(&rest[..len].yellow().to_string());
+            len
+        } else if c.is_alphabetic() || c == '_' {
+            let len = rest
+                .find(|ch: char| !(ch.is_alphanumeric() || ch == '_' || ch == '$'))
+                .unwrap_or(rest.len());
+            let word = &rest[..len];
+            if is_keyword(word) {
+                out.push_str(&word.blue().bold().to_string());
+            } else {
+                out.push_str(word);
+            }
+            len
+        } else {
+            out.push(c);
+            c.len_utf8()
+        };
+        rest = &rest[len..];
+    }
+    out
+}
+
+/// Returns the length of a single-quoted string at the start of `text`, where `''` is an
+/// escaped quote.
+fn quoted_len(text: &str) -> usize {
+    let bytes = text.as_bytes();
+    let mut i = 1;
+    while i < bytes.len() {
+        if bytes[i] == b'\'' {
+            if bytes.get(i + 1) == Some(&b'\'') {
+                i += 2;
+                continue;
+            }
+            return i + 1;
+        }
+        i += 1;
+    }
+    text.len()
+}
+
+fn is_keyword(word: &str) -> bool {
+    KEYWORDS
+        .iter()
+        .any(|keyword| keyword.eq_ignore_ascii_case(word))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_complete_attached_databases() {
+        let helper = SqlHelper {
+            enabled: false,
+            databases: vec!["archive".to_string(), "audit".to_string()],
+        };
+        let history = rustyline::history::DefaultHistory::new();
+        let ctx = rustyline::Context::new(&history);
+        let complete = |line: &str| helper.complete(line, line.len(), &ctx).unwrap();
+
+        assert_eq!(
+            complete(".detach "),
+            (8, vec!["archive".to_string(), "audit".to_string()])
+        );
+        assert_eq!(complete(".detach ar"), (8, vec!["archive".to_string()]));
+        assert_eq!(
+            complete("SELECT * FROM AU"),
+            (14, vec!["audit.".to_string()])
+        );
+        assert_eq!(
+            complete("SELECT count(arc"),
+            (13, vec!["archive.".to_string()])
+        );
+        assert!(complete("SELECT * FROM ").1.is_empty());
+    }
+
+    #[test]
+    fn test_complete_pragmas() {
+        let helper = SqlHelper::default();
+        let history = rustyline::history::DefaultHistory::new();
+        let ctx = rustyline::Context::new(&history);
+        let complete = |line: &str| helper.complete(line, line.len(), &ctx).unwrap();
+
+        assert_eq!(
+            complete(".pragma journal_"),
+            (
+                8,
+                vec!["journal_mode".to_string(), "journal_size_limit".to_string()]
+            )
+        );
+        assert_eq!(complete(".pragma li").1, vec!["list".to_string()]);
+        assert!(complete(".pragma journal_mode ").1.is_empty());
+    }
+
+    #[test]
+    fn test_highlight_sql() {
+        colored::control::set_override(true);
+
+        let line = "select name, 'it''s' FROM t WHERE n > 42 -- done";
+        let highlighted = highlight_sql(line);
+        assert!(highlighted.contains(&"select".blue().bold().to_string()));
+        assert!(highlighted.contains(&"'it''s'".green().to_string()));
+        assert!(highlighted.contains(&"42".yellow().to_string()));
+        assert!(highlighted.contains(&"-- done".dimmed().to_string()));
+        assert!(highlighted.contains(" name, "));
+
+        // Highlighting only adds escape codes
+        let stripped = regex::Regex::new("\x1b\\[[0-9;]*m")
+            .unwrap()
+            .replace_all(&highlighted, "");
+        assert_eq!(stripped, line);
+
+        assert_eq!(highlight_sql(".tables"), ".tables");
+        assert_eq!(highlight_sql("\"select\""), "\"select\"");
+        colored::control::unset_override();
+    }
+}
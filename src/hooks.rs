@@ -0,0 +1,148 @@
+//! # Session Hooks
+//!
+//! This module runs SQL that users want in every session without having to remember it,
+//! such as `PRAGMA foreign_keys=ON` when a session starts or `PRAGMA optimize` when it
+//! ends. Hooks are kept in `~/.vapor/hooks.json`:
+//!
+//! ```json
+//! {
+//!   "on_start": ["PRAGMA foreign_keys=ON"],
+//!   "before_query": [],
+//!   "on_exit": ["PRAGMA optimize"]
+//! }
+//! ```
+//!
+//! ## Features:
+//! - **Three stages**: `on_start` runs when a REPL session (or `repl --command`) opens
+//!   the database, `before_query` before each SQL statement typed in the REPL, and
+//!   `on_exit` when the session ends.
+//! - **Quiet**: Hook statements print nothing. One that fails is reported as a warning
+//!   and doesn't stop the session or the statement it precedes.
+//! - **Best effort loading**: A missing file counts as no hooks, and `.show settings`
+//!   lists the hooks in effect.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::sync::{Mutex, OnceLock};
+
+use crate::backend::Backend;
+use crate::config;
+use crate::statement::split_statements;
+
+/// SQL run automatically at points in a session.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Hooks {
+    /// Statements run when a session opens the database.
+    #[serde(default)]
+    pub on_start: Vec<String>,
+    /// Statements run before each SQL statement typed in the REPL.
+    #[serde(default)]
+    pub before_query: Vec<String>,
+    /// Statements run when a session ends.
+    #[serde(default)]
+    pub on_exit: Vec<String>,
+}
+
+/// The hooks of the current session, loaded when it starts.
+pub fn session_hooks() -> &'static Mutex<Hooks> {
+    static HOOKS: OnceLock<Mutex<Hooks>> = OnceLock::new();
+    HOOKS.get_or_init(|| Mutex::new(Hooks::default()))
+}
+
+impl Hooks {
+    /// Loads the hooks, or returns none if there is no hooks file.
+    ///
+    /// A file that exists but can't be parsed is reported as a warning, since silently
+    /// skipping a `PRAGMA foreign_keys=ON` would be worse than the noise.
+    pub fn load() -> Self {
+        let Some(json) = config::get_hooks_path()
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+        else {
+            return Self::default();
+        };
+        Self::parse(&json).unwrap_or_else(|e| {
+            eprintln!("Warning: {:#}", e);
+            Self::default()
+        })
+    }
+
+    /// Parses hooks from the JSON of a hooks file.
+    ///
+    /// # Arguments
+    ///
+    /// * `json` - An object with optional `on_start`, `before_query`, and `on_exit`
+    ///   arrays of SQL.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the hooks, or an `Err` if the JSON is invalid.
+    pub fn parse(json: &str) -> Result<Self> {
+        serde_json::from_str(json).context("Invalid hooks file ~/.vapor/hooks.json")
+    }
+
+    /// Whether no hooks are set.
+    pub fn is_empty(&self) -> bool {
+        self.on_start.is_empty() && self.before_query.is_empty() && self.on_exit.is_empty()
+    }
+
+    /// The hooks of each stage, with the stage's name.
+    pub fn stages(&self) -> [(&'static str, &[String]); 3] {
+        [
+            ("on_start", &self.on_start),
+            ("before_query", &self.before_query),
+            ("on_exit", &self.on_exit),
+        ]
+    }
+}
+
+/// Runs hook statements, reporting any that fail.
+///
+/// # Arguments
+///
+/// * `backend` - The database to run them against.
+/// * `stage` - The stage name for warnings, such as `on_start`.
+/// * `hooks` - The hook entries. Each can hold several statements.
+///
+/// # Returns
+///
+/// The number of statements that failed.
+pub fn run_hooks(backend: &dyn Backend, stage: &str, hooks: &[String]) -> usize {
+    let mut failed = 0;
+    for hook in hooks {
+        for (_, statement) in split_statements(hook) {
+            if let Err(e) = backend.run(statement, Some(0)) {
+                eprintln!("Warning: {} hook '{}' failed: {:#}", stage, statement, e);
+                failed += 1;
+            }
+        }
+    }
+    failed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+
+    #[test]
+    fn test_run_hooks() {
+        let hooks = Hooks::parse(
+            r#"{"on_start": ["PRAGMA foreign_keys=ON; CREATE TEMP TABLE seen (x)"],
+                "on_exit": ["INSERT INTO missing VALUES (1)"]}"#,
+        )
+        .unwrap();
+        assert!(hooks.before_query.is_empty() && !hooks.is_empty());
+
+        let conn = Connection::open_in_memory().unwrap();
+        assert_eq!(run_hooks(&conn, "on_start", &hooks.on_start), 0);
+        let foreign_keys: i64 = conn
+            .query_row("PRAGMA foreign_keys", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(foreign_keys, 1);
+        assert_eq!(run_hooks(&conn, "on_exit", &hooks.on_exit), 1);
+
+        assert!(Hooks::parse(r#"{"on_start": "PRAGMA foreign_keys=ON"}"#).is_err());
+    }
+}
@@ -0,0 +1,1605 @@
+//! # Text Import Parsers
+//!
+//! This module provides importers for line-oriented text files that are not delimited,
+//! so that server logs and mainframe-style exports can be loaded into tables for SQL
+//! analysis. Each parser is a `RecordReader`, so rows flow through the same insert
+//! pipeline as the pluggable formats in `formats`.
+//!
+//! ## Features:
+//! - **Fixed-Width**: Columns are cut from each line by character position, as
+//!   described by a column spec such as `id:1-5,name:6-25,amount:10`.
+//! - **Regex Lines**: Each line is matched against a regular expression with named
+//!   capture groups, e.g. `(?P<ts>\S+) (?P<level>\w+) (?P<msg>.*)`. Lines that do not
+//!   match are skipped and reported.
+//! - **JSON**: A JSON array of objects or newline-delimited JSON (NDJSON). Keys are mapped
+//!   to columns and values are coerced to each column's declared type. Invalid records
+//!   are skipped and reported instead of aborting the import.
+//!
+//! - **XML**: Simple row-oriented XML. Record elements are chosen with an XPath-like
+//!   selector (`/export/customers/customer`, `//customer`, `/*/*`); their attributes and
+//!   child elements become fields, which then go through the same path as JSON records.
+//!
+//! - **Import reports**: `ImportAudit` records what an import did (counts, duration,
+//!   error samples, and the command that ran it) as JSON or Markdown next to the input,
+//!   as an audit trail.
+//!
+//! For the text parsers, missing target tables are created with one `TEXT` column per
+//! parsed field; JSON imports can infer a typed schema instead. Inputs may be gzip, zstd,
+//! or zip compressed.
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use rusqlite::types::Value;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::time::Instant;
+
+use crate::compression::{open_input, strip_compression_extension, STDIN_PATH};
+use crate::db::table_columns;
+use crate::export::{skip_utf8_bom, ImportCheckpoint, ResumeHint};
+use crate::formats::{insert_records, quote_identifier, Record, RecordReader, XML_FIELD_ELEMENT};
+use crate::progress::ProgressTracker;
+
+/// The maximum number of skipped-record messages kept in an `ImportReport`.
+const MAX_REPORTED_ERRORS: usize = 10;
+
+/// A column cut from a fixed-width line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixedWidthColumn {
+    /// The target column name.
+    pub name: String,
+    /// The zero-based character offset where the column starts.
+    pub start: usize,
+    /// The number of characters in the column.
+    pub width: usize,
+}
+
+/// Parses a fixed-width column spec.
+///
+/// The spec is a list of `name:START-END` or `name:WIDTH` entries separated by commas or
+/// newlines. Positions are 1-based and inclusive, as in most record layouts; a bare width
+/// continues from the end of the previous column. Lines starting with `#` are ignored,
+/// so a spec can also be kept in a file and read with `std::fs::read_to_string`.
+///
+/// # Arguments
+///
+/// * `spec` - The column spec, e.g. `id:1-5,name:6-25,amount:10`.
+///
+/// # Returns
+///
+/// A `Result` containing the parsed columns in spec order.
+pub fn parse_fixed_width_spec(spec: &str) -> Result<Vec<FixedWidthColumn>> {
+    let mut columns = Vec::new();
+    let mut next_start = 0;
+
+    for entry in spec
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .flat_map(|line| line.split(','))
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        let (name, range) = entry.split_once(':').with_context(|| {
+            format!(
+                "Invalid column spec '{}'. Use name:START-END or name:WIDTH",
+                entry
+            )
+        })?;
+        let name = name.trim();
+        if name.is_empty() {
+            anyhow::bail!("Column spec '{}' is missing a column name", entry);
+        }
+
+        let parse = |n: &str| {
+            n.trim()
+                .parse::<usize>()
+                .with_context(|| format!("Invalid position '{}' in column spec '{}'", n, entry))
+        };
+        let (start, width) = match range.split_once('-') {
+            Some((from, to)) => {
+                let (from, to) = (parse(from)?, parse(to)?);
+                if from == 0 || to < from {
+                    anyhow::bail!(
+                        "Invalid range in column spec '{}'. Positions are 1-based and END must not precede START",
+                        entry
+                    );
+                }
+                (from - 1, to - from + 1)
+            }
+            None => (next_start, parse(range)?),
+        };
+        if width == 0 {
+            anyhow::bail!("Column '{}' must be at least one character wide", name);
+        }
+
+        next_start = start + width;
+        columns.push(FixedWidthColumn {
+            name: name.to_string(),
+            start,
+            width,
+        });
+    }
+
+    if columns.is_empty() {
+        anyhow::bail!("Fixed-width spec does not define any columns");
+    }
+    Ok(columns)
+}
+
+/// Reads records from fixed-width lines.
+///
+/// Values are trimmed of surrounding whitespace, and blank fields become `NULL`.
+pub struct FixedWidthReader {
+    lines: std::io::Lines<BufReader<Box<dyn Read>>>,
+    columns: Vec<FixedWidthColumn>,
+    names: Vec<String>,
+}
+
+impl FixedWidthReader {
+    /// Creates a reader over the given input using the given column layout.
+    pub fn new(input: Box<dyn Read>, columns: Vec<FixedWidthColumn>) -> Self {
+        let names = columns.iter().map(|c| c.name.clone()).collect();
+        Self {
+            lines: BufReader::new(input).lines(),
+            columns,
+            names,
+        }
+    }
+}
+
+impl RecordReader for FixedWidthReader {
+    fn columns(&self) -> &[String] {
+        &self.names
+    }
+
+    fn next_record(&mut self) -> Result<Option<Record>> {
+        for line in self.lines.by_ref() {
+            let line = line.context("Failed to read fixed-width input")?;
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let chars: Vec<char> = line.chars().collect();
+            let record = self
+                .columns
+                .iter()
+                .map(|column| {
+                    let end = (column.start + column.width).min(chars.len());
+                    let field: String = chars
+                        .get(column.start..end)
+                        .unwrap_or_default()
+                        .iter()
+                        .collect();
+                    match field.trim() {
+                        "" => Value::Null,
+                        value => Value::Text(value.to_string()),
+                    }
+                })
+                .collect();
+            return Ok(Some(record));
+        }
+        Ok(None)
+    }
+}
+
+/// Reads records from lines matched by a regular expression with named capture groups.
+///
+/// Each named group becomes a column. Lines that do not match are skipped and counted;
+/// optional groups that do not participate in a match become `NULL`.
+pub struct RegexLineReader {
+    lines: std::io::Lines<BufReader<Box<dyn Read>>>,
+    pattern: Regex,
+    names: Vec<String>,
+    skipped: usize,
+}
+
+impl RegexLineReader {
+    /// Creates a reader over the given input.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input stream.
+    /// * `pattern` - A regular expression with at least one named capture group.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the reader, or an `Err` if the pattern is invalid or has no
+    /// named groups.
+    pub fn new(input: Box<dyn Read>, pattern: &str) -> Result<Self> {
+        let pattern =
+            Regex::new(pattern).with_context(|| format!("Invalid pattern '{}'", pattern))?;
+        let names: Vec<String> = pattern
+            .capture_names()
+            .flatten()
+            .map(|name| name.to_string())
+            .collect();
+        if names.is_empty() {
+            anyhow::bail!("Pattern must contain named groups, e.g. '(?P<level>\\w+) (?P<msg>.*)'");
+        }
+        Ok(Self {
+            lines: BufReader::new(input).lines(),
+            pattern,
+            names,
+            skipped: 0,
+        })
+    }
+
+    /// Returns the number of lines skipped so far because they did not match.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+}
+
+impl RecordReader for RegexLineReader {
+    fn columns(&self) -> &[String] {
+        &self.names
+    }
+
+    fn next_record(&mut self) -> Result<Option<Record>> {
+        for line in self.lines.by_ref() {
+            let line = line.context("Failed to read log input")?;
+            let line = line.trim_end_matches('\r');
+            let Some(captures) = self.pattern.captures(line) else {
+                if !line.trim().is_empty() {
+                    self.skipped += 1;
+                }
+                continue;
+            };
+
+            let record = self
+                .names
+                .iter()
+                .map(|name| match captures.name(name) {
+                    Some(m) => Value::Text(m.as_str().to_string()),
+                    None => Value::Null,
+                })
+                .collect();
+            return Ok(Some(record));
+        }
+        Ok(None)
+    }
+}
+
+/// Imports a fixed-width file into a table.
+///
+/// # Arguments
+///
+/// * `conn` - A mutable reference to the active `rusqlite::Connection`.
+/// * `path` - The input file path.
+/// * `table_name` - The table to insert rows into. It is created if it does not exist.
+/// * `columns` - The column layout, usually from `parse_fixed_width_spec`.
+///
+/// # Returns
+///
+/// A `Result` containing the number of rows imported.
+pub fn import_fixed_width(
+    conn: &mut Connection,
+    path: &str,
+    table_name: &str,
+    columns: &[FixedWidthColumn],
+) -> Result<usize> {
+    let mut reader = FixedWidthReader::new(open_input(path)?, columns.to_vec());
+    ensure_text_table(conn, table_name, reader.columns())?;
+    let imported = insert_records(conn, &mut reader, table_name)?;
+
+    println!(
+        "Successfully imported {} rows from '{}' into '{}' (fixed-width)",
+        imported, path, table_name
+    );
+    Ok(imported)
+}
+
+/// Imports a line-oriented text file, such as a server log, into a table using a regex.
+///
+/// # Arguments
+///
+/// * `conn` - A mutable reference to the active `rusqlite::Connection`.
+/// * `path` - The input file path.
+/// * `table_name` - The table to insert rows into. It is created if it does not exist.
+/// * `pattern` - A regular expression whose named groups become columns.
+///
+/// # Returns
+///
+/// A `Result` containing the number of rows imported.
+pub fn import_log(
+    conn: &mut Connection,
+    path: &str,
+    table_name: &str,
+    pattern: &str,
+) -> Result<usize> {
+    let mut reader = RegexLineReader::new(open_input(path)?, pattern)?;
+    ensure_text_table(conn, table_name, reader.columns())?;
+    let imported = insert_records(conn, &mut reader, table_name)?;
+
+    println!(
+        "Successfully imported {} rows from '{}' into '{}' (pattern)",
+        imported, path, table_name
+    );
+    if reader.skipped() > 0 {
+        println!(
+            "Skipped {} lines that did not match the pattern",
+            reader.skipped()
+        );
+    }
+    Ok(imported)
+}
+
+/// Creates a table with one `TEXT` column per name unless it already exists.
+fn ensure_text_table(conn: &Connection, table_name: &str, columns: &[String]) -> Result<()> {
+    let sql = format!(
+        "CREATE TABLE IF NOT EXISTS {} ({})",
+        quote_identifier(table_name),
+        columns
+            .iter()
+            .map(|c| format!("{} TEXT", quote_identifier(c)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    conn.execute(&sql, [])
+        .with_context(|| format!("Failed to create table '{}'", table_name))?;
+    Ok(())
+}
+
+/// Options for importing JSON documents into a table.
+#[derive(Debug, Clone, Default)]
+pub struct JsonImportOptions {
+    /// Create the table from a schema inferred from the input if it does not exist.
+    pub create_table: bool,
+    /// Commit every `batch_size` documents instead of running one transaction for the
+    /// whole file. Documents committed before a failure stay in the table, and a
+    /// checkpoint lets `resume` continue after them.
+    pub batch_size: Option<usize>,
+    /// Continue a chunked import that failed, after the documents it committed, using the
+    /// `<file>.import-checkpoint` it left behind.
+    pub resume: bool,
+    /// What to do with documents that collide with an existing row.
+    pub on_conflict: ConflictPolicy,
+    /// Values given to columns for every document. They take precedence over document
+    /// keys for the same column.
+    pub set_columns: Vec<ColumnSetting>,
+}
+
+/// A value given to a column for every imported record, as with `--set column=value`, so
+/// columns missing from the input (such as provenance columns) are filled during the
+/// import.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnSetting {
+    /// The column to fill.
+    pub column: String,
+    /// The value. A function call such as `now()` or `lower('X')` is a SQL expression;
+    /// anything else is text. `{{filename}}` and `{{path}}` are replaced by the input's
+    /// file name and path in either.
+    pub value: String,
+}
+
+impl ColumnSetting {
+    /// Parses a `column=value` setting.
+    ///
+    /// # Arguments
+    ///
+    /// * `spec` - The setting, such as `source_file={{filename}}` or `imported_at=now()`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the setting, or an `Err` if there is no column name.
+    pub fn parse(spec: &str) -> Result<Self> {
+        match spec.split_once('=') {
+            Some((column, value)) if !column.trim().is_empty() => Ok(Self {
+                column: column.trim().to_string(),
+                value: value.to_string(),
+            }),
+            _ => anyhow::bail!("Invalid setting '{}'. Use COLUMN=VALUE.", spec),
+        }
+    }
+
+    /// Renders the value as a SQL expression for an import of `file_path`. `now()` is
+    /// `datetime('now')`, since SQLite has no `now` function.
+    pub(crate) fn sql_value(&self, file_path: &str) -> String {
+        let function_call = Regex::new(r"^[A-Za-z_][A-Za-z0-9_]*\s*\(.*\)$").unwrap();
+        let value = self.value.trim();
+        if value.eq_ignore_ascii_case("now()") {
+            "datetime('now')".to_string()
+        } else if function_call.is_match(value) {
+            expand_templates(value, file_path, &|text| text.replace('\'', "''"))
+        } else {
+            let text = expand_templates(&self.value, file_path, &|text| text.to_string());
+            format!("'{}'", text.replace('\'', "''"))
+        }
+    }
+}
+
+/// Replaces `{{filename}}` and `{{path}}` in a value, escaping the substituted text.
+fn expand_templates(value: &str, file_path: &str, escape: &dyn Fn(&str) -> String) -> String {
+    let filename = Path::new(file_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| file_path.to_string());
+    value
+        .replace("{{filename}}", &escape(&filename))
+        .replace("{{path}}", &escape(file_path))
+}
+
+/// Checks that every set column exists in a table, and returns the quoted column names and
+/// SQL values to add to each insert.
+pub(crate) fn set_column_values(
+    settings: &[ColumnSetting],
+    table_columns: &[String],
+    table_name: &str,
+    file_path: &str,
+) -> Result<(Vec<String>, Vec<String>)> {
+    let mut names = Vec::with_capacity(settings.len());
+    let mut values = Vec::with_capacity(settings.len());
+    for setting in settings {
+        let Some(column) = table_columns
+            .iter()
+            .find(|column| column.eq_ignore_ascii_case(&setting.column))
+        else {
+            anyhow::bail!(
+                "Column '{}' does not exist in '{}'",
+                setting.column,
+                table_name
+            );
+        };
+        names.push(quote_identifier(column));
+        values.push(setting.sql_value(file_path));
+    }
+    Ok((names, values))
+}
+
+/// How an import handles a record that collides with an existing row on a primary key or
+/// unique constraint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    /// The record fails like any other constraint violation.
+    #[default]
+    Fail,
+    /// The existing row is kept and the record is skipped (`INSERT OR IGNORE`).
+    Ignore,
+    /// The existing row is deleted and the record inserted (`INSERT OR REPLACE`).
+    Replace,
+}
+
+impl ConflictPolicy {
+    /// Parses a policy name: `fail`, `ignore`, or `replace`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The policy name, in any case.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the policy, or an `Err` naming the valid policies.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "fail" => Ok(Self::Fail),
+            "ignore" => Ok(Self::Ignore),
+            "replace" => Ok(Self::Replace),
+            _ => anyhow::bail!(
+                "Invalid conflict policy '{}'. Use fail, ignore, or replace.",
+                name
+            ),
+        }
+    }
+
+    /// The statement that inserts a record under this policy.
+    pub(crate) fn insert_verb(self) -> &'static str {
+        match self {
+            Self::Fail => "INSERT",
+            Self::Ignore => "INSERT OR IGNORE",
+            Self::Replace => "INSERT OR REPLACE",
+        }
+    }
+
+    /// Counts the rows of a table before a `Replace` import, so the rows it replaced can
+    /// be worked out afterwards. Other policies don't need the count and skip the scan.
+    pub(crate) fn rows_before(self, conn: &Connection, table_name: &str) -> Result<u64> {
+        match self {
+            Self::Replace => count_rows(conn, table_name),
+            _ => Ok(0),
+        }
+    }
+}
+
+/// Counts the rows of a table.
+fn count_rows(conn: &Connection, table_name: &str) -> Result<u64> {
+    conn.query_row(
+        &format!("SELECT COUNT(*) FROM {}", quote_identifier(table_name)),
+        [],
+        |row| row.get(0),
+    )
+    .with_context(|| format!("Failed to count the rows of '{}'", table_name))
+}
+
+/// A summary of an import that skips invalid records rather than failing.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ImportReport {
+    /// The number of records inserted.
+    pub imported: usize,
+    /// The number of records skipped because they were invalid.
+    pub skipped: usize,
+    /// The number of records skipped because they conflicted with an existing row.
+    pub ignored: usize,
+    /// The number of existing rows replaced by imported records.
+    pub replaced: u64,
+    /// Messages describing the first few skipped records.
+    pub errors: Vec<String>,
+}
+
+impl ImportReport {
+    pub(crate) fn skip(&mut self, message: String) {
+        self.skipped += 1;
+        if self.errors.len() < MAX_REPORTED_ERRORS {
+            self.errors.push(message);
+        }
+    }
+
+    /// Works out the rows replaced by a `Replace` import from the table's row count
+    /// before it. Every imported record that didn't add a row replaced one.
+    pub(crate) fn count_replaced(
+        &mut self,
+        conn: &Connection,
+        table_name: &str,
+        policy: ConflictPolicy,
+        rows_before: u64,
+    ) -> Result<()> {
+        if policy == ConflictPolicy::Replace {
+            let added = count_rows(conn, table_name)?.saturating_sub(rows_before);
+            self.replaced = (self.imported as u64).saturating_sub(added);
+        }
+        Ok(())
+    }
+
+    /// Prints the records ignored or rows replaced because of conflicts, if any.
+    pub(crate) fn print_conflicts(&self) {
+        if self.ignored > 0 {
+            println!(
+                "Ignored {} records that conflicted with existing rows",
+                self.ignored
+            );
+        }
+        if self.replaced > 0 {
+            println!("Replaced {} existing rows", self.replaced);
+        }
+    }
+}
+
+/// The format of an import report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Json,
+    Markdown,
+}
+
+impl ReportFormat {
+    /// Parses `json` or `markdown` (also `md`).
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The format name, in any case.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the format, or an `Err` naming the valid formats.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "markdown" | "md" => Ok(Self::Markdown),
+            _ => anyhow::bail!("Invalid report format '{}'. Use json or markdown.", name),
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Markdown => "md",
+        }
+    }
+}
+
+/// A record of one import for an audit trail, written next to the input file.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportAudit {
+    /// The input file.
+    pub source: String,
+    /// The table imported into.
+    pub table: String,
+    /// The database the table is in.
+    pub database: String,
+    /// When the import started, in RFC 3339 format.
+    pub started_at: String,
+    /// How long the import took, in seconds.
+    pub duration_secs: f64,
+    /// The error that stopped the import, if it failed.
+    pub error: Option<String>,
+    /// The counts and skipped-record messages of the import. They are zero when it failed.
+    pub report: ImportReport,
+    /// The command line that ran the import, which records the settings used.
+    pub command: Vec<String>,
+}
+
+impl ImportAudit {
+    /// Returns the report path for an input file (`<input>.import-report.json` or `.md`).
+    /// Imports from standard input are reported in `stdin.import-report.*`.
+    pub fn path_for(input: &str, format: ReportFormat) -> String {
+        let input = if input == STDIN_PATH { "stdin" } else { input };
+        format!("{}.import-report.{}", input, format.extension())
+    }
+
+    /// Writes the report next to the input file.
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - Whether to write JSON or Markdown.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the path of the report, or an `Err` if it can't be written.
+    pub fn write(&self, format: ReportFormat) -> Result<String> {
+        let path = Self::path_for(&self.source, format);
+        let contents = match format {
+            ReportFormat::Json => serde_json::to_string_pretty(self)?,
+            ReportFormat::Markdown => self.to_markdown(),
+        };
+        std::fs::write(&path, contents)
+            .with_context(|| format!("Failed to write import report '{}'", path))?;
+        Ok(path)
+    }
+
+    fn to_markdown(&self) -> String {
+        let report = &self.report;
+        let status = match &self.error {
+            Some(error) => format!("Failed: {}", error.replace('|', "\\|")),
+            None => "Succeeded".to_string(),
+        };
+        let mut markdown = format!(
+            "# Import report\n\n\
+             | | |\n|---|---|\n\
+             | Source | `{}` |\n| Table | `{}` |\n| Database | `{}` |\n\
+             | Started | {} |\n| Duration | {:.2}s |\n| Status | {} |\n\
+             | Rows read | {} |\n| Inserted | {} |\n| Skipped (invalid) | {} |\n\
+             | Ignored (conflicts) | {} |\n| Replaced | {} |\n",
+            self.source,
+            self.table,
+            self.database,
+            self.started_at,
+            self.duration_secs,
+            status,
+            report.imported + report.skipped + report.ignored,
+            report.imported,
+            report.skipped,
+            report.ignored,
+            report.replaced
+        );
+        markdown.push_str(&format!(
+            "\n## Settings\n\n```sh\n{}\n```\n",
+            self.command.join(" ")
+        ));
+        if !report.errors.is_empty() {
+            markdown.push_str("\n## Errors\n\n");
+            for error in &report.errors {
+                markdown.push_str(&format!("- {}\n", error));
+            }
+            if report.skipped > report.errors.len() {
+                markdown.push_str(&format!(
+                    "- ... and {} more\n",
+                    report.skipped - report.errors.len()
+                ));
+            }
+        }
+        markdown
+    }
+}
+
+/// Returns `true` if a path looks like a JSON or NDJSON file, ignoring compression.
+pub fn is_json_path(path: &str) -> bool {
+    let path = strip_compression_extension(path).to_lowercase();
+    [".json", ".jsonl", ".ndjson"]
+        .iter()
+        .any(|extension| path.ends_with(extension))
+}
+
+/// Imports a JSON array of objects or newline-delimited JSON into a table.
+///
+/// Object keys are mapped to table columns by name (case-insensitive), and values are
+/// coerced to each column's declared type. Keys without a matching column are ignored.
+/// Records that are not objects, fail to parse, cannot be coerced, or violate a
+/// constraint are skipped and reported; all other records are inserted in a single
+/// transaction, or in chunks of `batch_size` records when it is set. Records that collide
+/// with an existing row are handled according to `on_conflict`.
+///
+/// # Arguments
+///
+/// * `conn` - A mutable reference to the active `rusqlite::Connection`.
+/// * `file_path` - The path to the JSON or NDJSON file.
+/// * `table_name` - The table to insert records into.
+/// * `options` - A `JsonImportOptions` struct controlling table creation and chunking.
+///
+/// # Returns
+///
+/// A `Result` containing an `ImportReport` with the imported and skipped counts.
+pub fn import_json_to_table(
+    conn: &mut Connection,
+    file_path: &str,
+    table_name: &str,
+    options: &JsonImportOptions,
+) -> Result<ImportReport> {
+    import_documents(conn, file_path, table_name, options, &|f| {
+        for_each_json_document(file_path, f)
+    })
+}
+
+/// A callback receiving one labeled document, or a parse error, from a document source.
+type DocumentCallback<'a> =
+    dyn FnMut(String, std::result::Result<serde_json::Value, String>) -> Result<()> + 'a;
+
+/// A function that feeds every document of an input to a callback. It may be called more
+/// than once, e.g. to infer a schema before inserting.
+type DocumentSource<'a> = dyn Fn(&mut DocumentCallback) -> Result<()> + 'a;
+
+/// Inserts object documents into a table, skipping and reporting invalid ones.
+///
+/// This is shared by the JSON and XML importers, which both produce JSON objects.
+fn import_documents(
+    conn: &mut Connection,
+    file_path: &str,
+    table_name: &str,
+    options: &JsonImportOptions,
+    source: &DocumentSource,
+) -> Result<ImportReport> {
+    let mut columns = column_affinities(conn, table_name)?;
+    if columns.is_empty() {
+        if !options.create_table {
+            anyhow::bail!(
+                "Table '{}' does not exist. Create it first or enable table creation.",
+                table_name
+            );
+        }
+        create_inferred_table(conn, file_path, table_name, source)?;
+        // Set columns the documents don't have are added as text
+        for setting in &options.set_columns {
+            let exists = column_affinities(conn, table_name)?
+                .iter()
+                .any(|(name, _)| name.eq_ignore_ascii_case(&setting.column));
+            if !exists {
+                conn.execute(
+                    &format!(
+                        "ALTER TABLE {} ADD COLUMN {} TEXT",
+                        quote_identifier(table_name),
+                        quote_identifier(&setting.column)
+                    ),
+                    [],
+                )?;
+            }
+        }
+        columns = column_affinities(conn, table_name)?;
+    }
+    let column_names: Vec<String> = columns.iter().map(|(name, _)| name.clone()).collect();
+    let (set_names, set_values) =
+        set_column_values(&options.set_columns, &column_names, table_name, file_path)?;
+
+    let batch_size = options.batch_size;
+    let checkpoint = match batch_size {
+        Some(_) => ImportCheckpoint::for_import(file_path, table_name, options.resume)?,
+        None => None,
+    };
+    let skip = checkpoint.map_or(0, |checkpoint| checkpoint.records);
+    let mut hint = ResumeHint {
+        committed: skip,
+        armed: batch_size.is_some(),
+    };
+
+    let conn: &Connection = conn;
+    let rows_before = options.on_conflict.rows_before(conn, table_name)?;
+    let mut report = ImportReport::default();
+    let mut ignored_keys = BTreeSet::new();
+    let mut read = 0;
+    let start_time = Instant::now();
+    let mut progress = ProgressTracker::new("import", None);
+    conn.execute_batch("BEGIN")?;
+    let result = source(&mut |label, document| {
+        read += 1;
+        if read <= skip {
+            return Ok(());
+        }
+        'document: {
+            let object = match document {
+                Ok(serde_json::Value::Object(object)) => object,
+                Ok(_) => {
+                    report.skip(format!("{}: not a JSON object", label));
+                    break 'document;
+                }
+                Err(e) => {
+                    report.skip(format!("{}: {}", label, e));
+                    break 'document;
+                }
+            };
+
+            let mut names = Vec::with_capacity(object.len());
+            let mut values = Vec::with_capacity(object.len());
+            for (key, value) in &object {
+                let Some((name, affinity)) = columns
+                    .iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case(key))
+                else {
+                    ignored_keys.insert(key.clone());
+                    continue;
+                };
+                if options
+                    .set_columns
+                    .iter()
+                    .any(|setting| setting.column.eq_ignore_ascii_case(name))
+                {
+                    continue;
+                }
+                match coerce_json(value, *affinity) {
+                    Ok(value) => {
+                        names.push(quote_identifier(name));
+                        values.push(value);
+                    }
+                    Err(e) => {
+                        report.skip(format!("{}: column '{}': {}", label, name, e));
+                        break 'document;
+                    }
+                }
+            }
+            if names.is_empty() {
+                report.skip(format!(
+                    "{}: no keys match columns of '{}'",
+                    label, table_name
+                ));
+                break 'document;
+            }
+
+            let placeholders = vec!["?".to_string(); names.len()];
+            let sql = format!(
+                "{} INTO {} ({}) VALUES ({})",
+                options.on_conflict.insert_verb(),
+                quote_identifier(table_name),
+                [names, set_names.clone()].concat().join(","),
+                [placeholders, set_values.clone()].concat().join(",")
+            );
+            let mut stmt = conn
+                .prepare_cached(&sql)
+                .with_context(|| format!("Failed to prepare insert into '{}'", table_name))?;
+            match stmt.execute(rusqlite::params_from_iter(values.iter())) {
+                Ok(0) => report.ignored += 1,
+                Ok(_) => {
+                    report.imported += 1;
+                    progress.update(report.imported as u64);
+                    progress.check_cancelled()?;
+                }
+                Err(e) => report.skip(format!("{}: {}", label, e)),
+            }
+        }
+
+        if let Some(size) = batch_size {
+            if (read - skip) % size == 0 {
+                conn.execute_batch("COMMIT")?;
+                hint.committed = read;
+                ImportCheckpoint::save(file_path, table_name, read)?;
+                let rate = report.imported as f64 / start_time.elapsed().as_secs_f64();
+                println!(
+                    "Progress: {} rows committed - {:.0} rows/sec",
+                    report.imported, rate
+                );
+                conn.execute_batch("BEGIN")?;
+            }
+        }
+        Ok(())
+    });
+    if let Err(e) = result {
+        let _ = conn.execute_batch("ROLLBACK");
+        return Err(e);
+    }
+    conn.execute_batch("COMMIT")?;
+    hint.disarm();
+    if batch_size.is_some() {
+        ImportCheckpoint::remove(file_path);
+    }
+    progress.finish();
+    report.count_replaced(conn, table_name, options.on_conflict, rows_before)?;
+
+    println!(
+        "Successfully imported {} records from '{}' into '{}'",
+        report.imported, file_path, table_name
+    );
+    report.print_conflicts();
+    if !ignored_keys.is_empty() {
+        println!(
+            "Ignored keys without a matching column: {}",
+            ignored_keys.into_iter().collect::<Vec<_>>().join(", ")
+        );
+    }
+    if report.skipped > 0 {
+        println!("Skipped {} invalid records:", report.skipped);
+        for error in &report.errors {
+            println!("  {}", error);
+        }
+        if report.skipped > report.errors.len() {
+            println!("  ... and {} more", report.skipped - report.errors.len());
+        }
+    }
+    Ok(report)
+}
+
+/// Options for importing row-oriented XML into a table.
+#[derive(Debug, Clone)]
+pub struct XmlImportOptions {
+    /// An XPath-like selector for record elements.
+    ///
+    /// Supported forms are absolute paths (`/export/rows/row`), descendant paths
+    /// (`//row` or `//rows/row`), and `*` as a wildcard segment. The default, `/*/*`,
+    /// selects every child of the root element.
+    pub record_path: String,
+    /// Create the table from the inferred schema if it does not exist.
+    pub create_table: bool,
+    /// What to do with records that collide with an existing row.
+    pub on_conflict: ConflictPolicy,
+    /// Values given to columns for every record, taking precedence over the record's own.
+    pub set_columns: Vec<ColumnSetting>,
+}
+
+impl Default for XmlImportOptions {
+    fn default() -> Self {
+        Self {
+            record_path: "/*/*".to_string(),
+            create_table: false,
+            on_conflict: ConflictPolicy::Fail,
+            set_columns: Vec::new(),
+        }
+    }
+}
+
+/// Returns `true` if a path looks like an XML file, ignoring compression.
+pub fn is_xml_path(path: &str) -> bool {
+    strip_compression_extension(path)
+        .to_lowercase()
+        .ends_with(".xml")
+}
+
+/// Imports row-oriented XML into a table.
+///
+/// Each element matched by `options.record_path` becomes one record. The record's
+/// attributes and the text of its direct child elements become fields, mapped to
+/// columns by name; a repeated child element keeps its last value. A `<field name="...">`
+/// child, as written by the XML exporter for column names that are not valid element
+/// names, is mapped to the column named by its `name` attribute. Self-closing child
+/// elements (`<notes/>`) are imported as `NULL`. Values are coerced to the column types
+/// just like JSON strings, and invalid records are skipped and reported.
+///
+/// # Arguments
+///
+/// * `conn` - A mutable reference to the active `rusqlite::Connection`.
+/// * `file_path` - The path to the XML file.
+/// * `table_name` - The table to insert records into.
+/// * `options` - An `XmlImportOptions` struct with the record selector.
+///
+/// # Returns
+///
+/// A `Result` containing an `ImportReport` with the imported and skipped counts.
+pub fn import_xml_to_table(
+    conn: &mut Connection,
+    file_path: &str,
+    table_name: &str,
+    options: &XmlImportOptions,
+) -> Result<ImportReport> {
+    let selector = XmlSelector::parse(&options.record_path)?;
+    let document_options = JsonImportOptions {
+        create_table: options.create_table,
+        on_conflict: options.on_conflict,
+        set_columns: options.set_columns.clone(),
+        ..Default::default()
+    };
+    import_documents(conn, file_path, table_name, &document_options, &|f| {
+        for_each_xml_record(file_path, &selector, f)
+    })
+}
+
+/// A parsed record selector for XML imports.
+struct XmlSelector {
+    /// Match at any depth (`//`) rather than from the root.
+    anywhere: bool,
+    segments: Vec<String>,
+}
+
+impl XmlSelector {
+    fn parse(path: &str) -> Result<Self> {
+        let path = path.trim();
+        let (anywhere, rest) = match path.strip_prefix("//") {
+            Some(rest) => (true, rest),
+            None => (false, path.trim_start_matches('/')),
+        };
+        let segments: Vec<String> = rest.split('/').map(|s| s.trim().to_string()).collect();
+        if segments.iter().any(|s| s.is_empty()) {
+            anyhow::bail!(
+                "Invalid record path '{}'. Use a form like /root/row, //row, or /*/*",
+                path
+            );
+        }
+        Ok(Self { anywhere, segments })
+    }
+
+    /// Returns `true` if the element path (root first) is selected.
+    fn matches(&self, stack: &[String]) -> bool {
+        if stack.len() < self.segments.len()
+            || (!self.anywhere && stack.len() != self.segments.len())
+        {
+            return false;
+        }
+        stack[stack.len() - self.segments.len()..]
+            .iter()
+            .zip(&self.segments)
+            .all(|(name, segment)| segment == "*" || segment == name)
+    }
+}
+
+/// A record being collected while streaming through an XML document.
+struct XmlRecord {
+    depth: usize,
+    fields: serde_json::Map<String, serde_json::Value>,
+    field: Option<(String, String)>,
+}
+
+/// Streams the records selected by `selector` from an XML file as JSON objects.
+fn for_each_xml_record(
+    file_path: &str,
+    selector: &XmlSelector,
+    f: &mut DocumentCallback,
+) -> Result<()> {
+    use quick_xml::events::Event;
+
+    let mut reader = quick_xml::Reader::from_reader(BufReader::new(open_input(file_path)?));
+    let mut buf = Vec::new();
+    let mut stack: Vec<String> = Vec::new();
+    let mut record: Option<XmlRecord> = None;
+    let mut count = 0;
+
+    let xml_error = |e: &dyn std::fmt::Display, position: u64| {
+        anyhow::anyhow!("Invalid XML in '{}' at byte {}: {}", file_path, position, e)
+    };
+
+    loop {
+        let event = reader
+            .read_event_into(&mut buf)
+            .map_err(|e| xml_error(&e, reader.buffer_position()))?;
+        let is_empty = matches!(event, Event::Empty(_));
+        match event {
+            Event::Start(e) | Event::Empty(e) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                stack.push(name.clone());
+
+                match record.as_mut() {
+                    None if selector.matches(&stack) => {
+                        let mut fields = serde_json::Map::new();
+                        for attribute in e.attributes() {
+                            let attribute =
+                                attribute.map_err(|e| xml_error(&e, reader.buffer_position()))?;
+                            let key = String::from_utf8_lossy(attribute.key.local_name().as_ref())
+                                .into_owned();
+                            let value = attribute
+                                .unescape_value()
+                                .map_err(|e| xml_error(&e, reader.buffer_position()))?;
+                            fields.insert(key, serde_json::Value::String(value.into_owned()));
+                        }
+                        record = Some(XmlRecord {
+                            depth: stack.len(),
+                            fields,
+                            field: None,
+                        });
+                    }
+                    Some(current) if stack.len() == current.depth + 1 => {
+                        // `<field name="first name">` holds a column whose name is not
+                        // a valid element name
+                        let mut name = name;
+                        if name == XML_FIELD_ELEMENT {
+                            if let Some(attribute) = e
+                                .try_get_attribute("name")
+                                .map_err(|e| xml_error(&e, reader.buffer_position()))?
+                            {
+                                name = attribute
+                                    .unescape_value()
+                                    .map_err(|e| xml_error(&e, reader.buffer_position()))?
+                                    .into_owned();
+                            }
+                        }
+                        if is_empty {
+                            current.fields.insert(name, serde_json::Value::Null);
+                        } else {
+                            current.field = Some((name, String::new()));
+                        }
+                    }
+                    _ => {}
+                }
+
+                if is_empty {
+                    close_xml_element(&mut stack, &mut record, &mut count, f)?;
+                }
+            }
+            Event::Text(text) => {
+                if let Some((_, value)) = record.as_mut().and_then(|r| r.field.as_mut()) {
+                    let text = text
+                        .unescape()
+                        .map_err(|e| xml_error(&e, reader.buffer_position()))?;
+                    value.push_str(&text);
+                }
+            }
+            Event::CData(data) => {
+                if let Some((_, value)) = record.as_mut().and_then(|r| r.field.as_mut()) {
+                    value.push_str(&String::from_utf8_lossy(&data.into_inner()));
+                }
+            }
+            Event::End(_) => close_xml_element(&mut stack, &mut record, &mut count, f)?,
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(())
+}
+
+/// Handles the end of the innermost open element, emitting a record when it closes.
+fn close_xml_element(
+    stack: &mut Vec<String>,
+    record: &mut Option<XmlRecord>,
+    count: &mut usize,
+    f: &mut DocumentCallback,
+) -> Result<()> {
+    if let Some(current) = record.as_mut() {
+        if stack.len() == current.depth + 1 {
+            if let Some((name, value)) = current.field.take() {
+                current
+                    .fields
+                    .insert(name, serde_json::Value::String(value));
+            }
+        } else if stack.len() == current.depth {
+            let fields = std::mem::take(&mut current.fields);
+            *record = None;
+            *count += 1;
+            f(
+                format!("record {}", count),
+                Ok(serde_json::Value::Object(fields)),
+            )?;
+        }
+    }
+    stack.pop();
+    Ok(())
+}
+
+/// SQLite type affinity of a declared column type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Affinity {
+    Integer,
+    Real,
+    Text,
+    Numeric,
+    Blob,
+}
+
+impl Affinity {
+    /// Determines the affinity using SQLite's rules for declared column types.
+    pub(crate) fn from_declared_type(declared: &str) -> Self {
+        let declared = declared.to_uppercase();
+        if declared.contains("INT") {
+            Affinity::Integer
+        } else if ["CHAR", "CLOB", "TEXT"]
+            .iter()
+            .any(|t| declared.contains(t))
+        {
+            Affinity::Text
+        } else if declared.is_empty() || declared.contains("BLOB") {
+            Affinity::Blob
+        } else if ["REAL", "FLOA", "DOUB"]
+            .iter()
+            .any(|t| declared.contains(t))
+        {
+            Affinity::Real
+        } else {
+            Affinity::Numeric
+        }
+    }
+}
+
+/// Returns the stored (not generated) columns of a table with their affinities, or an
+/// empty list if it does not exist.
+fn column_affinities(conn: &Connection, table_name: &str) -> Result<Vec<(String, Affinity)>> {
+    Ok(table_columns(conn, None, table_name)?
+        .into_iter()
+        .filter(|column| !column.generated)
+        .map(|column| {
+            let affinity = Affinity::from_declared_type(&column.decl_type);
+            (column.name, affinity)
+        })
+        .collect())
+}
+
+/// Coerces a JSON value to a SQLite value suitable for a column with the given affinity.
+fn coerce_json(value: &serde_json::Value, affinity: Affinity) -> Result<Value, String> {
+    use serde_json::Value as Json;
+
+    match (value, affinity) {
+        (Json::Null, _) => Ok(Value::Null),
+        (Json::Bool(b), Affinity::Text) => Ok(Value::Text(b.to_string())),
+        (Json::Bool(b), _) => Ok(Value::Integer(*b as i64)),
+        (Json::Number(n), Affinity::Text) => Ok(Value::Text(n.to_string())),
+        (Json::Number(n), Affinity::Real) => n
+            .as_f64()
+            .map(Value::Real)
+            .ok_or_else(|| format!("{} is not a valid real number", n)),
+        (Json::Number(n), Affinity::Integer) => match (n.as_i64(), n.as_f64()) {
+            (Some(i), _) => Ok(Value::Integer(i)),
+            (None, Some(f)) if f.fract() == 0.0 && f.abs() < i64::MAX as f64 => {
+                Ok(Value::Integer(f as i64))
+            }
+            _ => Err(format!("{} is not an integer", n)),
+        },
+        (Json::Number(n), _) => Ok(n
+            .as_i64()
+            .map(Value::Integer)
+            .or_else(|| n.as_f64().map(Value::Real))
+            .unwrap_or_else(|| Value::Text(n.to_string()))),
+        (Json::String(s), Affinity::Integer) => s
+            .trim()
+            .parse::<i64>()
+            .map(Value::Integer)
+            .map_err(|_| format!("'{}' is not an integer", s)),
+        (Json::String(s), Affinity::Real) => s
+            .trim()
+            .parse::<f64>()
+            .map(Value::Real)
+            .map_err(|_| format!("'{}' is not a number", s)),
+        (Json::String(s), Affinity::Numeric) => Ok(s
+            .trim()
+            .parse::<i64>()
+            .map(Value::Integer)
+            .or_else(|_| s.trim().parse::<f64>().map(Value::Real))
+            .unwrap_or_else(|_| Value::Text(s.clone()))),
+        (Json::String(s), _) => Ok(Value::Text(s.clone())),
+        (Json::Array(_) | Json::Object(_), Affinity::Integer | Affinity::Real) => {
+            Err("nested arrays and objects cannot be stored in a numeric column".to_string())
+        }
+        // Nested values are kept as JSON text, which SQLite's JSON functions can query
+        (Json::Array(_) | Json::Object(_), _) => Ok(Value::Text(value.to_string())),
+    }
+}
+
+/// Calls `f` for every document in a JSON array or NDJSON file.
+///
+/// The first argument to `f` labels the document for error messages (e.g. `line 3`).
+/// Lines that fail to parse are passed as `Err` so the caller can skip them.
+fn for_each_json_document<F>(file_path: &str, mut f: F) -> Result<()>
+where
+    F: FnMut(String, std::result::Result<serde_json::Value, String>) -> Result<()>,
+{
+    let mut reader = BufReader::new(open_input(file_path)?);
+    skip_utf8_bom(&mut reader).with_context(|| format!("Failed to read '{}'", file_path))?;
+
+    // Skip leading whitespace to find out whether this is an array or NDJSON
+    let is_array = loop {
+        let buf = reader
+            .fill_buf()
+            .with_context(|| format!("Failed to read '{}'", file_path))?;
+        match buf.iter().position(|b| !b.is_ascii_whitespace()) {
+            Some(i) => {
+                let first = buf[i];
+                reader.consume(i);
+                break first == b'[';
+            }
+            None if buf.is_empty() => return Ok(()),
+            None => {
+                let len = buf.len();
+                reader.consume(len);
+            }
+        }
+    };
+
+    if is_array {
+        let documents: Vec<serde_json::Value> = serde_json::from_reader(reader)
+            .with_context(|| format!("Failed to parse JSON array in '{}'", file_path))?;
+        for (i, document) in documents.into_iter().enumerate() {
+            f(format!("record {}", i + 1), Ok(document))?;
+        }
+    } else {
+        for (i, line) in reader.lines().enumerate() {
+            let line = line.with_context(|| format!("Failed to read '{}'", file_path))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let document = serde_json::from_str(&line).map_err(|e| e.to_string());
+            f(format!("line {}", i + 1), document)?;
+        }
+    }
+    Ok(())
+}
+
+/// Creates a table whose columns and types are inferred from the documents of a source.
+///
+/// Columns appear in the order their keys are first seen. A column is `INTEGER` when
+/// every non-null value is an integer or boolean, `REAL` when every value is numeric,
+/// and `TEXT` otherwise.
+fn create_inferred_table(
+    conn: &Connection,
+    file_path: &str,
+    table_name: &str,
+    source: &DocumentSource,
+) -> Result<()> {
+    let mut columns: Vec<(String, Option<Affinity>)> = Vec::new();
+    source(&mut |_, document| {
+        let Ok(serde_json::Value::Object(object)) = document else {
+            return Ok(());
+        };
+        for (key, value) in object {
+            let inferred = match &value {
+                serde_json::Value::Null => None,
+                serde_json::Value::Bool(_) => Some(Affinity::Integer),
+                serde_json::Value::Number(n) if n.is_i64() || n.is_u64() => Some(Affinity::Integer),
+                serde_json::Value::Number(_) => Some(Affinity::Real),
+                _ => Some(Affinity::Text),
+            };
+            match columns
+                .iter_mut()
+                .find(|(name, _)| name.eq_ignore_ascii_case(&key))
+            {
+                Some((_, current)) => {
+                    *current = match (*current, inferred) {
+                        (current, None) => current,
+                        (None, inferred) => inferred,
+                        (Some(a), Some(b)) if a == b => Some(a),
+                        (Some(Affinity::Integer), Some(Affinity::Real))
+                        | (Some(Affinity::Real), Some(Affinity::Integer)) => Some(Affinity::Real),
+                        _ => Some(Affinity::Text),
+                    };
+                }
+                None => columns.push((key, inferred)),
+            }
+        }
+        Ok(())
+    })?;
+
+    if columns.is_empty() {
+        anyhow::bail!(
+            "Cannot infer a schema for '{}': no records found in '{}'",
+            table_name,
+            file_path
+        );
+    }
+
+    let definitions = columns
+        .iter()
+        .map(|(name, affinity)| {
+            let declared = match affinity {
+                Some(Affinity::Integer) => "INTEGER",
+                Some(Affinity::Real) => "REAL",
+                _ => "TEXT",
+            };
+            format!("{} {}", quote_identifier(name), declared)
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    conn.execute(
+        &format!(
+            "CREATE TABLE {} ({})",
+            quote_identifier(table_name),
+            definitions
+        ),
+        [],
+    )
+    .with_context(|| format!("Failed to create table '{}'", table_name))?;
+    println!("Created table '{}' ({})", table_name, definitions);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_fixed_width_spec_and_reader() -> Result<()> {
+        let columns = parse_fixed_width_spec("# layout\nid:1-3, name:4-9\namount:4")?;
+        assert_eq!(columns[2].start, 9);
+        assert_eq!(columns[2].width, 4);
+        assert!(parse_fixed_width_spec("id:5-1").is_err());
+
+        let input = "001alice   12\n002bob       \n";
+        let mut reader = FixedWidthReader::new(Box::new(Cursor::new(input)), columns);
+        let first = reader.next_record()?.unwrap();
+        assert_eq!(first[1], Value::Text("alice".into()));
+        assert_eq!(first[2], Value::Text("12".into()));
+        let second = reader.next_record()?.unwrap();
+        assert_eq!(second[2], Value::Null);
+        assert!(reader.next_record()?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_audit_report() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let input = dir.path().join("events.csv");
+        let mut report = ImportReport {
+            imported: 8,
+            ignored: 1,
+            ..Default::default()
+        };
+        report.skip("row 4: bad value".to_string());
+        let audit = ImportAudit {
+            source: input.to_str().unwrap().to_string(),
+            table: "events".to_string(),
+            database: "app.db".to_string(),
+            started_at: "2024-05-01T12:00:00+00:00".to_string(),
+            duration_secs: 1.5,
+            error: None,
+            report,
+            command: vec!["vapor-cli".to_string(), "import".to_string()],
+        };
+
+        let path = audit.write(ReportFormat::parse("JSON")?)?;
+        assert_eq!(path, format!("{}.import-report.json", input.display()));
+        let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+        assert_eq!(json["report"]["imported"], 8);
+        assert_eq!(json["report"]["errors"][0], "row 4: bad value");
+        assert_eq!(json["command"][1], "import");
+
+        let markdown = std::fs::read_to_string(audit.write(ReportFormat::Markdown)?)?;
+        assert!(markdown.contains("| Rows read | 10 |"));
+        assert!(markdown.contains("- row 4: bad value"));
+        assert!(ReportFormat::parse("html").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_log_skips_unmatched_lines() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("app.log");
+        std::fs::write(
+            &path,
+            "2024-01-01T00:00:00 INFO started\ngarbage\n2024-01-01T00:00:05 WARN disk almost full\n",
+        )?;
+
+        let mut conn = Connection::open_in_memory()?;
+        let imported = import_log(
+            &mut conn,
+            path.to_str().unwrap(),
+            "logs",
+            r"(?P<ts>\S+) (?P<level>\w+) (?P<msg>.*)",
+        )?;
+        assert_eq!(imported, 2);
+
+        let msg: String =
+            conn.query_row("SELECT msg FROM logs WHERE level = 'WARN'", [], |row| {
+                row.get(0)
+            })?;
+        assert_eq!(msg, "disk almost full");
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_json_array_and_ndjson() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut conn = Connection::open_in_memory()?;
+
+        let array_path = dir.path().join("users.json");
+        std::fs::write(
+            &array_path,
+            r#"[{"id": 1, "name": "ann", "score": 9.5, "tags": ["a"]}, {"id": 2, "name": "bob"}, 7]"#,
+        )?;
+        let report = import_json_to_table(
+            &mut conn,
+            array_path.to_str().unwrap(),
+            "users",
+            &JsonImportOptions {
+                create_table: true,
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(report.imported, 2);
+        assert_eq!(report.skipped, 1);
+        let tags: String =
+            conn.query_row("SELECT tags FROM users WHERE id = 1", [], |r| r.get(0))?;
+        assert_eq!(tags, r#"["a"]"#);
+
+        let ndjson_path = dir.path().join("more.ndjson");
+        std::fs::write(
+            &ndjson_path,
+            "{\"id\": 3, \"name\": \"cy\", \"extra\": true}\nnot json\n{\"id\": \"x\"}\n",
+        )?;
+        let report = import_json_to_table(
+            &mut conn,
+            ndjson_path.to_str().unwrap(),
+            "users",
+            &JsonImportOptions::default(),
+        )?;
+        assert_eq!(report.imported, 1);
+        assert_eq!(report.skipped, 2);
+        assert!(report.errors[1].contains("not an integer"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_xml_export_and_import_roundtrip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut conn = Connection::open_in_memory()?;
+        conn.execute_batch(
+            "CREATE TABLE partners (id INTEGER, name TEXT, notes TEXT);
+             INSERT INTO partners VALUES (1, 'Smith & Sons', NULL), (2, '<Acme>', 'net 30');",
+        )?;
+
+        let path = dir.path().join("partners.xml");
+        crate::formats::export_query(
+            &conn,
+            "SELECT * FROM partners ORDER BY id",
+            path.to_str().unwrap(),
+            None,
+        )?;
+        conn.execute("DELETE FROM partners", [])?;
+
+        let report = import_xml_to_table(
+            &mut conn,
+            path.to_str().unwrap(),
+            "partners",
+            &XmlImportOptions::default(),
+        )?;
+        assert_eq!(report.imported, 2);
+        let (name, notes): (String, Option<String>) =
+            conn.query_row("SELECT name, notes FROM partners WHERE id = 1", [], |r| {
+                Ok((r.get(0)?, r.get(1)?))
+            })?;
+        assert_eq!(name, "Smith & Sons");
+        assert_eq!(notes, None);
+
+        // Attributes and a descendant selector
+        let nested = dir.path().join("nested.xml");
+        std::fs::write(
+            &nested,
+            r#"<export><meta/><customers><customer id="7"><name>Zed</name></customer></customers></export>"#,
+        )?;
+        let options = XmlImportOptions {
+            record_path: "//customer".to_string(),
+            create_table: true,
+            ..Default::default()
+        };
+        let report =
+            import_xml_to_table(&mut conn, nested.to_str().unwrap(), "customers", &options)?;
+        assert_eq!(report.imported, 1);
+        let name: String =
+            conn.query_row("SELECT name FROM customers WHERE id = '7'", [], |r| {
+                r.get(0)
+            })?;
+        assert_eq!(name, "Zed");
+        Ok(())
+    }
+
+    #[test]
+    fn test_xml_roundtrip_keeps_column_names() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut conn = Connection::open_in_memory()?;
+        conn.execute_batch(
+            r#"CREATE TABLE people ("first name" TEXT, "2fa" INTEGER, field TEXT);
+               INSERT INTO people VALUES ('Ada', 1, 'math');"#,
+        )?;
+
+        let path = dir.path().join("people.xml");
+        crate::formats::export_query(&conn, "SELECT * FROM people", path.to_str().unwrap(), None)?;
+        let xml = std::fs::read_to_string(&path)?;
+        assert!(xml.contains(r#"<field name="first name">Ada</field>"#));
+        assert!(xml.contains("<field>math</field>"));
+
+        let options = XmlImportOptions {
+            create_table: true,
+            ..Default::default()
+        };
+        import_xml_to_table(&mut conn, path.to_str().unwrap(), "copy", &options)?;
+        let (first, twofa, field): (String, String, String) =
+            conn.query_row(r#"SELECT "first name", "2fa", field FROM copy"#, [], |r| {
+                Ok((r.get(0)?, r.get(1)?, r.get(2)?))
+            })?;
+        assert_eq!(
+            (first.as_str(), twofa.as_str(), field.as_str()),
+            ("Ada", "1", "math")
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,637 @@
+//! # Integrity Checks and Recovery
+//!
+//! This module checks a database for corruption and salvages what it can from a damaged
+//! one, in the spirit of the `sqlite3` shell's `.recover`.
+//!
+//! ## Features:
+//! - **Checks**: `check_integrity` runs `PRAGMA integrity_check`, or the faster
+//!   `quick_check` that skips verifying indexes against their tables.
+//! - **Recovery**: `recover_database` copies the schema and every readable row into a
+//!   fresh database. Rows are read in rowid order, and when a read fails the scan skips
+//!   ahead past the damaged pages and carries on, so one bad page only costs its rows.
+//! - **Foreign keys**: `foreign_key_violations` runs `PRAGMA foreign_key_check` and
+//!   groups the orphaned rows by table and foreign key, so they can be shown in full.
+//! - **Reporting**: Each table's recovered rows and read errors are counted, and schema
+//!   objects that could not be recreated are listed.
+
+use anyhow::{Context, Result};
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Connection, ErrorCode, OpenFlags};
+use std::fs;
+use std::path::Path;
+
+use crate::db::visible_columns;
+use crate::display::{print_records, OutputFormat};
+use crate::formats::quote_identifier;
+
+/// The problems `PRAGMA integrity_check` reports before stopping, by default.
+pub const DEFAULT_MAX_ERRORS: usize = 100;
+
+/// The result of `check_integrity`.
+#[derive(Debug, Clone)]
+pub struct IntegrityReport {
+    /// Whether this was a `quick_check`.
+    pub quick: bool,
+    /// The problems found; empty if the database is intact.
+    pub problems: Vec<String>,
+}
+
+impl IntegrityReport {
+    /// Returns `true` if no problems were found.
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Prints the result, numbering the problems.
+    pub fn print(&self) {
+        let check = if self.quick {
+            "Quick check"
+        } else {
+            "Integrity check"
+        };
+        if self.is_ok() {
+            println!("{}: ok", check);
+            return;
+        }
+        println!("{}: {} problem(s) found", check, self.problems.len());
+        for (i, problem) in self.problems.iter().enumerate() {
+            println!("  {:>3}. {}", i + 1, problem);
+        }
+    }
+}
+
+/// Runs `PRAGMA integrity_check` or `PRAGMA quick_check` on the main database.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `quick` - Run `quick_check`, which doesn't check indexes against their tables.
+/// * `max_errors` - The number of problems after which the check stops.
+///
+/// # Returns
+///
+/// A `Result` containing the `IntegrityReport`, or an `Err` if the check can't run at
+/// all, for example because the file is not a database.
+pub fn check_integrity(
+    conn: &Connection,
+    quick: bool,
+    max_errors: usize,
+) -> Result<IntegrityReport> {
+    let pragma = if quick {
+        "quick_check"
+    } else {
+        "integrity_check"
+    };
+    let lines = conn
+        .prepare(&format!("PRAGMA {}({})", pragma, max_errors.max(1)))
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()
+        });
+    let problems = match lines {
+        Ok(lines) if lines.len() == 1 && lines[0] == "ok" => Vec::new(),
+        Ok(lines) => lines,
+        // Damage can stop the check itself, which is a finding rather than a failure
+        Err(e) if is_corruption(&e) => vec![e.to_string()],
+        Err(e) => return Err(e).with_context(|| format!("Failed to run PRAGMA {}", pragma)),
+    };
+    Ok(IntegrityReport { quick, problems })
+}
+
+/// The rows of a table that break one of its foreign keys, as found by
+/// `foreign_key_violations`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignKeyViolations {
+    /// The table holding the orphaned rows.
+    pub table: String,
+    /// The table the foreign key refers to.
+    pub parent: String,
+    /// The foreign key's columns in `table`.
+    pub columns: Vec<String>,
+    /// The columns they refer to in `parent`, or empty for its primary key.
+    pub parent_columns: Vec<String>,
+    /// The rowids of the orphaned rows; `None` for rows of a `WITHOUT ROWID` table.
+    pub rowids: Vec<Option<i64>>,
+}
+
+impl ForeignKeyViolations {
+    /// Describes the foreign key, e.g. `orders (customer_id) -> customers (id)`.
+    pub fn describe(&self) -> String {
+        let parent_columns = if self.parent_columns.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", self.parent_columns.join(", "))
+        };
+        format!(
+            "{} ({}) -> {}{}",
+            self.table,
+            self.columns.join(", "),
+            self.parent,
+            parent_columns
+        )
+    }
+}
+
+/// Finds rows whose foreign keys refer to rows that don't exist, with
+/// `PRAGMA foreign_key_check`. This works whether or not `foreign_keys` is on.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `table` - Check only this table, or `None` for every table.
+///
+/// # Returns
+///
+/// A `Result` containing one `ForeignKeyViolations` per table and foreign key with
+/// orphaned rows, or an `Err` if the check fails, for example for an unknown table.
+pub fn foreign_key_violations(
+    conn: &Connection,
+    table: Option<&str>,
+) -> Result<Vec<ForeignKeyViolations>> {
+    let sql = match table {
+        Some(table) => format!("PRAGMA foreign_key_check('{}')", table.replace('\'', "''")),
+        None => "PRAGMA foreign_key_check".to_string(),
+    };
+    let rows = conn
+        .prepare(&sql)
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<i64>>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, i64>(3)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()
+        })
+        .map_err(|e| anyhow::anyhow!("Failed to run PRAGMA foreign_key_check: {}", e))?;
+
+    let mut groups: Vec<(i64, ForeignKeyViolations)> = Vec::new();
+    for (table, rowid, parent, key) in rows {
+        if let Some((_, group)) = groups
+            .iter_mut()
+            .find(|(k, group)| *k == key && group.table == table)
+        {
+            group.rowids.push(rowid);
+            continue;
+        }
+        let mut stmt = conn.prepare(
+            "SELECT \"from\", \"to\" FROM pragma_foreign_key_list(?1) WHERE id = ?2 ORDER BY seq",
+        )?;
+        let pairs = stmt
+            .query_map(rusqlite::params![table, key], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let columns = pairs.iter().map(|(from, _)| from.clone()).collect();
+        let parent_columns = pairs.into_iter().filter_map(|(_, to)| to).collect();
+        groups.push((
+            key,
+            ForeignKeyViolations {
+                table,
+                parent,
+                columns,
+                parent_columns,
+                rowids: vec![rowid],
+            },
+        ));
+    }
+    Ok(groups.into_iter().map(|(_, group)| group).collect())
+}
+
+/// Prints each group of foreign key violations with the orphaned rows in full, up to
+/// `max_rows` of them per group.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `violations` - The violations found by `foreign_key_violations`.
+/// * `format` - The output format for the rows.
+/// * `max_rows` - The most rows shown for each group.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` once everything is printed, or an `Err` if the rows can't
+/// be read.
+pub fn print_foreign_key_violations(
+    conn: &Connection,
+    violations: &[ForeignKeyViolations],
+    format: &OutputFormat,
+    max_rows: usize,
+) -> Result<()> {
+    for group in violations {
+        println!(
+            "{}: {} row(s) refer to missing rows",
+            group.describe(),
+            group.rowids.len()
+        );
+        let rowids: Vec<i64> = group
+            .rowids
+            .iter()
+            .flatten()
+            .take(max_rows)
+            .copied()
+            .collect();
+        if rowids.is_empty() {
+            continue;
+        }
+        let sql = format!(
+            "SELECT * FROM {} WHERE rowid IN ({}) ORDER BY rowid",
+            quote_identifier(&group.table),
+            placeholders(rowids.len())
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+        let rows = stmt
+            .query_map(params_from_iter(&rowids), |row| {
+                (0..columns.len())
+                    .map(|i| row.get::<_, Value>(i))
+                    .collect::<rusqlite::Result<Vec<Value>>>()
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        print_records(format, &columns, &rows)?;
+        if group.rowids.len() > rowids.len() {
+            println!("... and {} more", group.rowids.len() - rowids.len());
+        }
+    }
+    Ok(())
+}
+
+/// What was salvaged from one table by `recover_database`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableRecovery {
+    pub table: String,
+    /// The rows copied into the new database.
+    pub rows: u64,
+    /// The reads that failed, each of which skipped a damaged stretch of the table.
+    pub read_errors: u64,
+}
+
+/// The result of `recover_database`.
+#[derive(Debug, Clone, Default)]
+pub struct RecoveryReport {
+    pub tables: Vec<TableRecovery>,
+    /// Schema objects that could not be read or recreated, with the reason.
+    pub skipped: Vec<(String, String)>,
+}
+
+impl RecoveryReport {
+    /// Prints the rows recovered from each table and anything that was skipped.
+    pub fn print(&self) {
+        for table in &self.tables {
+            if table.read_errors > 0 {
+                println!(
+                    "  {}: {} row(s) recovered, {} damaged stretch(es) skipped",
+                    table.table, table.rows, table.read_errors
+                );
+            } else {
+                println!("  {}: {} row(s) recovered", table.table, table.rows);
+            }
+        }
+        for (name, reason) in &self.skipped {
+            println!("  {}: skipped ({})", name, reason);
+        }
+    }
+
+    /// Returns `true` if every table was read without errors and nothing was skipped.
+    pub fn is_complete(&self) -> bool {
+        self.skipped.is_empty() && self.tables.iter().all(|table| table.read_errors == 0)
+    }
+}
+
+/// Salvages the schema and every readable row of a damaged database into a new file.
+///
+/// Tables are created first and filled in rowid order, keeping each row's rowid; indexes,
+/// views, and triggers are created once the rows are in. Virtual tables are skipped.
+///
+/// # Arguments
+///
+/// * `src` - The path to the damaged database. It is only read.
+/// * `dest` - The path of the database to create.
+/// * `force` - Whether an existing `dest` may be replaced.
+///
+/// # Returns
+///
+/// A `Result` containing the `RecoveryReport`, or an `Err` if `dest` exists and `force`
+/// is not set, or the source's schema can't be read at all.
+pub fn recover_database(src: &str, dest: &str, force: bool) -> Result<RecoveryReport> {
+    if !Path::new(src).is_file() {
+        anyhow::bail!("Database '{}' does not exist", src);
+    }
+    if Path::new(dest).exists() {
+        if !force {
+            anyhow::bail!("'{}' already exists; use --force to overwrite it", dest);
+        }
+        for suffix in ["", "-wal", "-shm", "-journal"] {
+            let _ = fs::remove_file(format!("{}{}", dest, suffix));
+        }
+    }
+
+    let source = Connection::open_with_flags(src, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("Failed to open database '{}'", src))?;
+    let mut stmt = source
+        .prepare(
+            "SELECT m.type, m.name, m.sql, IFNULL(l.type, '') FROM sqlite_master m \
+             LEFT JOIN pragma_table_list l ON l.schema = 'main' AND l.name = m.name \
+             WHERE m.sql IS NOT NULL AND m.name NOT LIKE 'sqlite_%' ORDER BY m.rowid",
+        )
+        .context("Failed to read the schema; it is too damaged to recover from")?;
+    let objects = stmt
+        .query_map([], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })?
+        .collect::<rusqlite::Result<Vec<(String, String, String, String)>>>()
+        .context("Failed to read the schema; it is too damaged to recover from")?;
+
+    let target = Connection::open(dest).with_context(|| format!("Failed to create '{}'", dest))?;
+    target.execute_batch("BEGIN")?;
+    let mut report = RecoveryReport::default();
+    let mut tables = Vec::new();
+    for (kind, name, sql, table_kind) in &objects {
+        if kind != "table" {
+            continue;
+        }
+        if table_kind != "table" {
+            // Virtual tables need their module, and their shadow tables come with them
+            report
+                .skipped
+                .push((name.clone(), format!("{} table", table_kind)));
+            continue;
+        }
+        match target.execute_batch(sql) {
+            Ok(()) => tables.push(name),
+            Err(e) => report.skipped.push((name.clone(), e.to_string())),
+        }
+    }
+    for table in tables {
+        report.tables.push(recover_table(&source, &target, table)?);
+    }
+    for (kind, name, sql, _) in &objects {
+        if kind != "table" {
+            if let Err(e) = target.execute_batch(sql) {
+                report.skipped.push((name.clone(), e.to_string()));
+            }
+        }
+    }
+    target
+        .execute_batch("COMMIT")
+        .with_context(|| format!("Failed to write '{}'", dest))?;
+    Ok(report)
+}
+
+/// Copies the readable rows of one table, skipping past the stretches that fail to read.
+fn recover_table(source: &Connection, target: &Connection, table: &str) -> Result<TableRecovery> {
+    let quoted = quote_identifier(table);
+    let columns = visible_columns(target, Some("main"), table)?
+        .iter()
+        .map(|column| quote_identifier(&column.name))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut recovery = TableRecovery {
+        table: table.to_string(),
+        rows: 0,
+        read_errors: 0,
+    };
+    let without_rowid = source
+        .prepare(&format!("SELECT rowid FROM {} LIMIT 0", quoted))
+        .is_err();
+
+    if without_rowid {
+        // Without a rowid to seek by, the scan can only stop at the first damaged page
+        let mut insert = target.prepare(&format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            quoted,
+            columns,
+            placeholders(columns.split(", ").count())
+        ))?;
+        let mut select = source.prepare(&format!("SELECT {} FROM {}", columns, quoted))?;
+        let width = select.column_count();
+        let mut rows = select.query([])?;
+        loop {
+            match rows.next() {
+                Ok(Some(row)) => {
+                    let values = (0..width)
+                        .map(|i| row.get::<_, Value>(i))
+                        .collect::<rusqlite::Result<Vec<_>>>();
+                    match values {
+                        Ok(values) => {
+                            if insert.execute(params_from_iter(values)).is_ok() {
+                                recovery.rows += 1;
+                            }
+                        }
+                        Err(_) => recovery.read_errors += 1,
+                    }
+                }
+                Ok(None) => break,
+                Err(_) => {
+                    recovery.read_errors += 1;
+                    break;
+                }
+            }
+        }
+        return Ok(recovery);
+    }
+
+    let mut insert = target.prepare(&format!(
+        "INSERT INTO {} (rowid, {}) VALUES ({})",
+        quoted,
+        columns,
+        placeholders(columns.split(", ").count() + 1)
+    ))?;
+    let select_sql = format!(
+        "SELECT rowid, {} FROM {} WHERE rowid >= ?1 ORDER BY rowid",
+        columns, quoted
+    );
+    let mut next = Some(i64::MIN);
+    while let Some(start) = next {
+        let failed_at = {
+            let mut select = source.prepare(&select_sql)?;
+            let width = select.column_count();
+            let mut rows = select.query([start])?;
+            let mut failed_at = None;
+            loop {
+                match rows.next() {
+                    Ok(Some(row)) => {
+                        let values = (0..width)
+                            .map(|i| row.get::<_, Value>(i))
+                            .collect::<rusqlite::Result<Vec<_>>>();
+                        let rowid = match values.as_deref() {
+                            Ok([Value::Integer(rowid), ..]) => *rowid,
+                            _ => {
+                                failed_at = next;
+                                break;
+                            }
+                        };
+                        if insert.execute(params_from_iter(values?)).is_ok() {
+                            recovery.rows += 1;
+                        }
+                        next = rowid.checked_add(1);
+                    }
+                    Ok(None) => {
+                        next = None;
+                        break;
+                    }
+                    Err(_) => {
+                        failed_at = next;
+                        break;
+                    }
+                }
+            }
+            failed_at
+        };
+        if let Some(failed) = failed_at {
+            recovery.read_errors += 1;
+            next = next_readable_rowid(source, &select_sql, failed);
+        }
+    }
+    Ok(recovery)
+}
+
+/// Finds the lowest rowid after `failed` from which a scan can read a row again: first by
+/// doubling the jump until a read succeeds, then by narrowing back towards `failed`.
+fn next_readable_rowid(source: &Connection, select_sql: &str, failed: i64) -> Option<i64> {
+    let readable = |start: i64| {
+        source
+            .prepare(select_sql)
+            .and_then(|mut select| select.query([start])?.next().map(|_| ()))
+            .is_ok()
+    };
+    let mut low = failed;
+    let mut jump: i64 = 1;
+    let high = loop {
+        let candidate = failed.checked_add(jump)?;
+        if readable(candidate) {
+            break candidate;
+        }
+        low = candidate;
+        jump = jump.checked_mul(2)?;
+    };
+    let mut high = high;
+    while high - low > 1 {
+        let middle = low + (high - low) / 2;
+        if readable(middle) {
+            high = middle;
+        } else {
+            low = middle;
+        }
+    }
+    Some(high)
+}
+
+/// Returns `true` if an error reports a damaged database file.
+fn is_corruption(error: &rusqlite::Error) -> bool {
+    matches!(
+        error.sqlite_error_code(),
+        Some(ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase)
+    )
+}
+
+/// Returns `count` comma-separated `?` placeholders.
+fn placeholders(count: usize) -> String {
+    vec!["?"; count].join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Seek, SeekFrom, Write};
+
+    #[test]
+    fn test_foreign_key_violations() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "PRAGMA foreign_keys = OFF;
+             CREATE TABLE teams (id INTEGER PRIMARY KEY, code TEXT UNIQUE);
+             CREATE TABLE users (id INTEGER PRIMARY KEY, team_id INTEGER REFERENCES teams (id),
+                                 team_code TEXT REFERENCES teams (code));
+             INSERT INTO teams VALUES (1, 'a');
+             INSERT INTO users VALUES (1, 1, 'a'), (2, 7, 'a'), (3, 8, 'zz');",
+        )
+        .unwrap();
+
+        let mut violations = foreign_key_violations(&conn, None).unwrap();
+        violations.sort_by_key(|group| group.columns.clone());
+        assert_eq!(violations.len(), 2);
+        assert_eq!(
+            violations[0].describe(),
+            "users (team_code) -> teams (code)"
+        );
+        assert_eq!(violations[0].rowids, [Some(3)]);
+        assert_eq!(violations[1].describe(), "users (team_id) -> teams (id)");
+        assert_eq!(violations[1].rowids, [Some(2), Some(3)]);
+        assert!(foreign_key_violations(&conn, Some("teams"))
+            .unwrap()
+            .is_empty());
+        assert!(foreign_key_violations(&conn, Some("missing")).is_err());
+    }
+
+    #[test]
+    fn test_check_and_recover_damaged_database() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let src = temp_dir.path().join("damaged.db");
+        let dest = temp_dir.path().join("recovered.db");
+        let (src, dest) = (src.to_str().unwrap(), dest.to_str().unwrap());
+        let conn = Connection::open(src).unwrap();
+        conn.execute_batch(
+            "PRAGMA page_size = 1024;
+             CREATE TABLE t (id INTEGER PRIMARY KEY, body TEXT);
+             CREATE INDEX t_body ON t (body);
+             CREATE VIEW v AS SELECT COUNT(*) AS n FROM t;
+             WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 2000)
+             INSERT INTO t SELECT i, printf('%0100d', i) FROM n;",
+        )
+        .unwrap();
+        assert!(check_integrity(&conn, false, DEFAULT_MAX_ERRORS)
+            .unwrap()
+            .is_ok());
+        let root: i64 = conn
+            .query_row(
+                "SELECT rootpage FROM sqlite_master WHERE name = 't'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        // A leaf page of the table, well past its root
+        let leaf: i64 = conn
+            .query_row(
+                "SELECT pageno FROM dbstat WHERE name = 't' AND pagetype = 'leaf' LIMIT 1 OFFSET 20",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_ne!(leaf, root);
+        drop(conn);
+
+        let mut file = fs::OpenOptions::new().write(true).open(src).unwrap();
+        file.seek(SeekFrom::Start((leaf as u64 - 1) * 1024))
+            .unwrap();
+        file.write_all(&[0xFF; 1024]).unwrap();
+        drop(file);
+
+        let conn = Connection::open(src).unwrap();
+        let report = check_integrity(&conn, true, DEFAULT_MAX_ERRORS).unwrap();
+        assert!(!report.is_ok());
+        drop(conn);
+
+        let recovery = recover_database(src, dest, false).unwrap();
+        let table = &recovery.tables[0];
+        assert_eq!(table.table, "t");
+        assert!(table.read_errors > 0);
+        assert!(table.rows > 1500 && table.rows < 2000);
+
+        let recovered = Connection::open(dest).unwrap();
+        assert!(check_integrity(&recovered, false, DEFAULT_MAX_ERRORS)
+            .unwrap()
+            .is_ok());
+        let (last, count): (i64, i64) = recovered
+            .query_row("SELECT MAX(id), (SELECT n FROM v) FROM t", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!(last, 2000);
+        assert_eq!(count as u64, table.rows);
+
+        assert!(recover_database(src, dest, false).is_err());
+    }
+}
@@ -0,0 +1,301 @@
+//! # Background Jobs
+//!
+//! This module runs long operations, such as exporting a large query result, on worker
+//! threads so the REPL stays usable while they run. Each job gets a number that `.jobs`
+//! and `.job status N` use to report on it, and the REPL announces jobs as they finish.
+//!
+//! ## Features:
+//! - **Dedicated connections**: Export jobs read through their own read-only connection,
+//!   so they never see or disturb the REPL's open transaction.
+//! - **Live progress**: Rows written so far are picked up from the `ProgressTracker` the
+//!   operation already uses, along with the elapsed time and rate.
+//! - **Completion reports**: Each finished job is reported once, with its result or error.
+
+use anyhow::{Context, Result};
+use rusqlite::{Connection, OpenFlags};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::export::ExportOptions;
+use crate::formats::export_query_with_options;
+use crate::functions::register_functions;
+use crate::progress::observe_progress;
+
+/// How long a job's connection waits for a lock held by another connection.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The state of a background job.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobState {
+    Running,
+    /// Finished, with a summary of what it did.
+    Done(String),
+    Failed(String),
+}
+
+impl fmt::Display for JobState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobState::Running => write!(f, "running"),
+            JobState::Done(summary) => write!(f, "done: {}", summary),
+            JobState::Failed(message) => write!(f, "failed: {}", message),
+        }
+    }
+}
+
+/// A snapshot of a background job.
+#[derive(Debug, Clone)]
+pub struct JobInfo {
+    pub id: usize,
+    pub description: String,
+    pub state: JobState,
+    /// Units of work done so far, such as rows exported.
+    pub progress: u64,
+    pub elapsed: Duration,
+}
+
+impl JobInfo {
+    /// The average number of units of work done per second.
+    pub fn rate(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.progress as f64 / secs
+        } else {
+            0.0
+        }
+    }
+}
+
+struct Job {
+    id: usize,
+    description: String,
+    state: Arc<Mutex<JobState>>,
+    progress: Arc<AtomicU64>,
+    started: Instant,
+    finished: Arc<Mutex<Option<Instant>>>,
+    handle: Option<JoinHandle<()>>,
+    reported: bool,
+}
+
+impl Job {
+    fn info(&self) -> JobInfo {
+        let elapsed = match *self.finished.lock().unwrap() {
+            Some(finished) => finished - self.started,
+            None => self.started.elapsed(),
+        };
+        JobInfo {
+            id: self.id,
+            description: self.description.clone(),
+            state: self.state.lock().unwrap().clone(),
+            progress: self.progress.load(Ordering::Relaxed),
+            elapsed,
+        }
+    }
+}
+
+/// The background jobs started in this session.
+#[derive(Default)]
+pub struct JobManager {
+    jobs: Vec<Job>,
+}
+
+/// The REPL's background jobs.
+pub fn job_manager() -> &'static Mutex<JobManager> {
+    static JOBS: OnceLock<Mutex<JobManager>> = OnceLock::new();
+    JOBS.get_or_init(|| Mutex::new(JobManager::default()))
+}
+
+impl JobManager {
+    /// Runs work on a new worker thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `description` - What the job does, for listings.
+    /// * `work` - The work to do. It returns a summary of the result. Progress reported
+    ///   through a `ProgressTracker` on the worker thread is picked up automatically.
+    ///
+    /// # Returns
+    ///
+    /// The job's number.
+    pub fn spawn<F>(&mut self, description: &str, work: F) -> usize
+    where
+        F: FnOnce() -> Result<String> + Send + 'static,
+    {
+        let id = self.jobs.len() + 1;
+        let state = Arc::new(Mutex::new(JobState::Running));
+        let progress = Arc::new(AtomicU64::new(0));
+        let finished = Arc::new(Mutex::new(None));
+
+        let handle = {
+            let state = Arc::clone(&state);
+            let progress = Arc::clone(&progress);
+            let finished = Arc::clone(&finished);
+            std::thread::spawn(move || {
+                observe_progress(Some(progress));
+                let result = match work() {
+                    Ok(summary) => JobState::Done(summary),
+                    Err(e) => JobState::Failed(format!("{:#}", e)),
+                };
+                *state.lock().unwrap() = result;
+                *finished.lock().unwrap() = Some(Instant::now());
+            })
+        };
+
+        self.jobs.push(Job {
+            id,
+            description: description.to_string(),
+            state,
+            progress,
+            started: Instant::now(),
+            finished,
+            handle: Some(handle),
+            reported: false,
+        });
+        id
+    }
+
+    /// Exports the results of a query on a worker thread with its own read-only
+    /// connection.
+    ///
+    /// # Arguments
+    ///
+    /// * `db_path` - The database file.
+    /// * `query` - The query whose results are exported.
+    /// * `path` - The output file.
+    /// * `format` - An explicit format name, or `None` to pick one from the file extension.
+    /// * `options` - How to write the file.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the job's number, or an `Err` if the database can't be opened.
+    pub fn spawn_export(
+        &mut self,
+        db_path: &str,
+        query: &str,
+        path: &str,
+        format: Option<&str>,
+        options: &ExportOptions,
+    ) -> Result<usize> {
+        let conn = Connection::open_with_flags(
+            db_path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .with_context(|| format!("Failed to open database '{}' for the export job", db_path))?;
+        conn.busy_timeout(BUSY_TIMEOUT)
+            .context("Failed to set the export job's busy timeout")?;
+        register_functions(&conn)?;
+
+        let query = query.to_string();
+        let path = path.to_string();
+        let format = format.map(str::to_string);
+        let options = ExportOptions {
+            quiet: true,
+            ..options.clone()
+        };
+        let description = format!("export to '{}'", path);
+        Ok(self.spawn(&description, move || {
+            let rows =
+                export_query_with_options(&conn, &query, &path, format.as_deref(), &options)?;
+            Ok(format!("{} rows written to '{}'", rows, path))
+        }))
+    }
+
+    /// Snapshots of every job, oldest first.
+    pub fn list(&self) -> Vec<JobInfo> {
+        self.jobs.iter().map(Job::info).collect()
+    }
+
+    /// A snapshot of one job.
+    pub fn get(&self, id: usize) -> Option<JobInfo> {
+        self.jobs.iter().find(|job| job.id == id).map(Job::info)
+    }
+
+    /// The number of jobs still running.
+    pub fn running(&self) -> usize {
+        self.list()
+            .iter()
+            .filter(|job| job.state == JobState::Running)
+            .count()
+    }
+
+    /// Returns the jobs that have finished since the last call, so the REPL reports
+    /// each one once.
+    pub fn take_finished(&mut self) -> Vec<JobInfo> {
+        let mut finished = Vec::new();
+        for job in self.jobs.iter_mut().filter(|job| !job.reported) {
+            let info = job.info();
+            if info.state != JobState::Running {
+                job.reported = true;
+                finished.push(info);
+            }
+        }
+        finished
+    }
+
+    /// Waits for every running job to finish.
+    pub fn wait_all(&mut self) {
+        for job in &mut self.jobs {
+            if let Some(handle) = job.handle.take() {
+                let _ = handle.join();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_background_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("jobs.db");
+        let db_path = db_path.to_str().unwrap();
+        let conn = Connection::open(db_path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE t (x INTEGER);
+             WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c LIMIT 500)
+             INSERT INTO t SELECT x FROM c;",
+        )
+        .unwrap();
+        let out = dir.path().join("t.csv");
+        let out = out.to_str().unwrap();
+
+        let mut jobs = JobManager::default();
+        let id = jobs
+            .spawn_export(
+                db_path,
+                "SELECT x FROM t",
+                out,
+                None,
+                &ExportOptions::default(),
+            )
+            .unwrap();
+        let failing = jobs
+            .spawn_export(
+                db_path,
+                "SELECT * FROM missing",
+                &format!("{}.missing", out),
+                None,
+                &ExportOptions::default(),
+            )
+            .unwrap();
+        jobs.wait_all();
+
+        let job = jobs.get(id).unwrap();
+        assert_eq!(job.progress, 500);
+        assert!(matches!(&job.state, JobState::Done(summary) if summary.starts_with("500 rows")));
+        assert!(matches!(
+            jobs.get(failing).unwrap().state,
+            JobState::Failed(_)
+        ));
+        assert_eq!(std::fs::read_to_string(out).unwrap().lines().count(), 501);
+
+        assert_eq!(jobs.running(), 0);
+        assert_eq!(jobs.take_finished().len(), 2);
+        assert!(jobs.take_finished().is_empty());
+    }
+}
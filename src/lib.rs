@@ -9,50 +9,258 @@
 //! - **Interactive REPL**: An interactive Read-Eval-Print Loop (REPL) for executing SQL queries directly.
 //! - **Shell Mode**: A shell mode with database context for more advanced operations.
 //! - **Data Population**: A `populate` command to insert large amounts of data for testing purposes.
-//! 
+//!
 //! ## Modules
-//! 
+//!
 //! The crate is organized into several modules, each responsible for a specific part of the functionality:
-//! 
+//!
 //! - `db`: Core database operations like connecting, creating tables, and listing tables.
+//! - `backend`: The `Backend` trait that lets the REPL target local files or remote URLs.
 //! - `repl`: Implements the interactive REPL mode.
 //! - `shell`: Implements the shell mode.
 //! - `populate`: Provides functionality for populating the database with test data.
 //! - `bookmarks`: Manages SQL query bookmarks.
+//! - `browse`: An interactive table picker with fuzzy filtering and column stats.
+//! - `cancel`: Cancellation tokens for stopping long-running library operations.
+//! - `columnar`: Arrow record batch conversion and Arrow IPC files (`arrow` feature).
+//! - `compression`: Transparent gzip/zstd compression for exported files.
 //! - `config`: Handles application configuration.
+//! - `copy`: Copies rows between tables of attached databases, matching columns by name.
+//! - `diff`: Compares the schemas of two databases, or the rows of two tables, and generates SQL to reconcile them.
 //! - `display`: Manages the display of query results.
+//! - `duckdb_interop`: Copies tables between SQLite and DuckDB files (`duckdb` feature).
 //! - `export`: Handles data exporting.
+//! - `foreach`: Runs SQL against many database files matched by glob patterns.
+//! - `functions`: Built-in SQL functions (`REGEXP`, `UUID`, `MD5`, `SHA256`, `DATE_FORMAT`).
+//! - `formats`: Pluggable import/export formats and the format registry.
+//! - `highlight`: SQL syntax highlighting for the REPL prompt.
+//! - `hooks`: Runs SQL from `~/.vapor/hooks.json` at session start, before queries, and at exit.
+//! - `import`: Fixed-width, regex-based line, JSON, and XML importers.
+//! - `integrity`: Integrity checks, and recovery of readable data from damaged databases.
+//! - `jobs`: Runs long operations such as exports on worker threads and tracks them.
+//! - `notes`: Attaches notes to rows in a sidecar table.
+//! - `profiles`: Short names for database files, for `.use` in the REPL.
+//! - `progress`: Emits machine-readable progress events for long-running operations.
+//! - `queue`: Runs batches of queued SQL statements on a background thread.
+//! - `remote`: libsql/Turso databases over HTTP (`remote` feature).
+//! - `render`: Renders query results and schemas as text in the output formats.
+//! - `replica`: Compares a database with a replica of it and reports divergence.
+//! - `replication`: Ships WAL frames to a backup directory for point-in-time restore.
+//! - `script`: Runs SQL script files statement by statement, for `.read` and `--init`.
+//! - `scripting`: Runs Rhai automation scripts for `.script run` (`scripting` feature).
+//! - `search`: Searches the text columns of every table for a value, for `.find`.
+//! - `session`: A `Session` that offers the REPL's dot-commands as methods for embedding.
+//! - `sniff`: Detects the dialect of a CSV file (delimiter, quotes, line endings, header).
+//! - `softdelete`: Sets up soft delete (a `deleted_at` column, views, and a trigger) on tables.
+//! - `statement`: Decides whether REPL input is a complete SQL statement, classifies statements, and splits scripts.
+//! - `strict`: Checks stored types against declared ones and rebuilds tables as `STRICT`.
+//! - `templates`: Creates new databases from template databases or built-in presets.
+//! - `timestamps`: Adds `created_at`/`updated_at` columns and triggers to tables.
+//! - `tracking`: Tracks when each table last changed, by polling or with triggers.
+//! - `transcript`: Records REPL commands and their output to a session log for `.log`.
 //! - `transactions`: Manages database transactions.
-
+//! - `tuning`: Cache, mmap, and temp store settings for bulk commands.
+//! - `usage`: Counts dot-command and bookmark use to rank help and completion.
+//! - `variables`: Session variables substituted into REPL commands as `${NAME}`.
+//!
+//! ## The Portable Core
+//!
+//! Everything that needs a terminal, the filesystem, or the bundled SQLite is behind the
+//! default `native` feature. Built with `--no-default-features`, the crate has only
+//! `backend`, `render`, and `statement`. It doesn't depend on `rusqlite` (rows hold
+//! `backend::Value`) and compiles for `wasm32-unknown-unknown`, so a browser-based viewer
+//! can run its own SQLite behind `Backend` and format results and schemas exactly as the
+//! CLI does.
+
+pub mod backend;
+#[cfg(feature = "native")]
 pub mod bookmarks;
+#[cfg(feature = "native")]
+pub mod browse;
+#[cfg(feature = "native")]
+pub mod cancel;
+#[cfg(feature = "arrow")]
+pub mod columnar;
+#[cfg(feature = "native")]
+pub mod compression;
+#[cfg(feature = "native")]
 pub mod config;
+#[cfg(feature = "native")]
+pub mod copy;
+#[cfg(feature = "native")]
 pub mod db;
+#[cfg(feature = "native")]
+pub mod diff;
+#[cfg(feature = "native")]
 pub mod display;
-use std::sync::{Arc, Mutex};
+#[cfg(feature = "duckdb")]
+pub mod duckdb_interop;
+#[cfg(feature = "native")]
 pub mod export;
+#[cfg(feature = "native")]
+pub mod foreach;
+#[cfg(feature = "native")]
+pub mod formats;
+#[cfg(feature = "native")]
+pub mod functions;
+#[cfg(feature = "native")]
+pub mod highlight;
+#[cfg(feature = "native")]
+pub mod hooks;
+#[cfg(feature = "native")]
+pub mod import;
+#[cfg(feature = "native")]
+pub mod integrity;
+#[cfg(feature = "native")]
+pub mod jobs;
+#[cfg(feature = "native")]
+pub mod notes;
+#[cfg(feature = "native")]
 pub mod populate;
+#[cfg(feature = "native")]
+pub mod profiles;
+#[cfg(feature = "native")]
+pub mod progress;
+#[cfg(feature = "native")]
+pub mod queue;
+#[cfg(feature = "remote")]
+pub mod remote;
+pub mod render;
+#[cfg(feature = "native")]
 pub mod repl;
+#[cfg(feature = "native")]
+pub mod replica;
+#[cfg(feature = "native")]
+pub mod replication;
+#[cfg(feature = "native")]
+pub mod script;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+#[cfg(feature = "native")]
+pub mod search;
+#[cfg(feature = "native")]
+pub mod session;
+#[cfg(feature = "native")]
 pub mod shell;
+#[cfg(feature = "native")]
+pub mod sniff;
+#[cfg(feature = "native")]
+pub mod softdelete;
+pub mod statement;
+#[cfg(feature = "native")]
+pub mod strict;
+#[cfg(feature = "native")]
+pub mod templates;
+#[cfg(feature = "native")]
+pub mod timestamps;
+#[cfg(feature = "native")]
+pub mod tracking;
+#[cfg(feature = "native")]
 pub mod transactions;
-pub use crate::repl::repl_mode;
+#[cfg(feature = "native")]
+pub mod transcript;
+#[cfg(feature = "native")]
+pub mod tuning;
+#[cfg(feature = "native")]
+pub mod usage;
+#[cfg(feature = "native")]
+pub mod variables;
+#[cfg(feature = "native")]
+pub use crate::repl::{
+    command_mode, repl_mode, repl_mode_with_init, repl_mode_with_options, ReplOptions,
+};
+#[cfg(feature = "native")]
 pub use crate::shell::shell_mode;
-pub use db::{connect_database, create_table, init_database, list_tables};
+pub use anyhow::Result;
+#[cfg(feature = "native")]
+pub use backend::open_backend;
+pub use backend::{Backend, ColumnDescription, QueryResult, Record, Value};
+#[cfg(feature = "native")]
+pub use bookmarks::{Bookmark, BookmarkManager};
+#[cfg(feature = "native")]
+pub use cancel::{CancellationToken, Cancelled};
+#[cfg(feature = "arrow")]
+pub use columnar::{export_to_arrow, import_arrow_to_table};
+#[cfg(feature = "native")]
+pub use compression::Compression;
+#[cfg(feature = "native")]
+pub use db::{
+    check_schema_version, checkpoint, connect_database, create_table, create_table_with_options,
+    init_database, list_tables, run_maintenance, schema_version, set_foreign_keys,
+    set_journal_mode, set_schema_version, CheckpointMode, CheckpointReport, ColumnInfo,
+    ConnectionOptions, CreateTableOptions, GeneratedColumn, Maintenance, MaintenanceReport,
+};
+#[cfg(feature = "native")]
+pub use diff::{
+    diff_schema_files, diff_schemas, diff_table_data, diff_table_files, DataDiff, DataDiffOptions,
+    RowChange, SchemaChange, SchemaDiff,
+};
+#[cfg(feature = "native")]
 pub use display::{
-    execute_sql, show_all_schemas, show_database_info, show_table_schema, OutputFormat,
-    QueryOptions,
+    database_info, execute_sql, execute_sql_with_last, show_all_schemas, show_database_info,
+    show_table_constraints, show_table_counts, show_table_schema, table_stats, AttachedDatabase,
+    DatabaseInfo, LastQuery, QueryOptions, TableStats,
 };
-pub use export::export_to_csv;
-pub use shell::Shell;
-pub use bookmarks::{Bookmark, BookmarkManager};
-pub use transactions::{TransactionManager, TransactionState};
-pub use populate::{populate_database, ColumnConfig, DataDistribution, DataType, PopulationConfig};
-pub use anyhow::Result;
+#[cfg(feature = "duckdb")]
+pub use duckdb_interop::{copy_duckdb_to_sqlite, copy_sqlite_to_duckdb};
+#[cfg(feature = "avro")]
+pub use export::export_to_avro;
+#[cfg(feature = "native")]
+pub use export::{
+    export_database_to_csv_dir, export_tables_to_xlsx, export_to_csv, export_to_csv_with_options,
+    export_to_xlsx, import_csv_to_table, import_csv_to_table_with_options, CsvImportOptions,
+    ExportCheckpoint, ExportManifest, ExportOptions, ImportCheckpoint, LineEnding, TextEncoding,
+    UTF8_BOM,
+};
+#[cfg(feature = "native")]
+pub use formats::{
+    export_query, export_query_with_options, export_result, import_file, import_file_with_options,
+    register_format, FileImportOptions, Format, FormatRegistry,
+};
+#[cfg(feature = "native")]
+pub use functions::register_functions;
+#[cfg(feature = "native")]
+pub use hooks::{run_hooks, Hooks};
+#[cfg(feature = "native")]
+pub use import::{
+    import_fixed_width, import_json_to_table, import_log, import_xml_to_table,
+    parse_fixed_width_spec, ColumnSetting, ConflictPolicy, FixedWidthColumn, ImportAudit,
+    ImportReport, JsonImportOptions, ReportFormat, XmlImportOptions,
+};
+#[cfg(feature = "native")]
+pub use integrity::{
+    check_integrity, foreign_key_violations, recover_database, ForeignKeyViolations,
+    IntegrityReport, RecoveryReport,
+};
+#[cfg(feature = "native")]
+pub use populate::{
+    populate_database, populate_database_with_tuning, ColumnConfig, DataDistribution, DataType,
+    PopulationConfig,
+};
+#[cfg(feature = "native")]
+pub use progress::{set_progress_sink, ProgressEvent, ProgressTracker};
+pub use render::{render_columns, render_result, render_rows, OutputFormat};
+#[cfg(feature = "native")]
 pub use rusqlite::Connection;
+#[cfg(feature = "scripting")]
+pub use scripting::{check_script_file, run_script_file};
+#[cfg(feature = "native")]
+pub use session::{Session, TableDescription};
+#[cfg(feature = "native")]
+pub use shell::Shell;
+#[cfg(feature = "native")]
+pub use transactions::{ErrorPolicy, TransactionManager, TransactionState, TransactionStats};
+#[cfg(feature = "native")]
+pub use tuning::{TempStore, Tuning};
+
+#[cfg(feature = "native")]
+use std::sync::{Arc, Mutex, PoisonError};
 
 /// A high-level API for interacting with SQLite databases through vapor-cli
 ///
 /// This struct provides a simplified interface to vapor-cli's functionality,
 /// making it easy to use as a library in other Rust projects.
+#[cfg(feature = "native")]
 pub struct VaporDB {
     pub connection: Connection,
     pub db_path: String,
@@ -60,11 +268,21 @@ pub struct VaporDB {
     pub transaction_manager: TransactionManager,
 }
 
+#[cfg(feature = "native")]
 impl VaporDB {
     /// Create a new VaporDB instance with an existing database
     pub fn open<P: AsRef<std::path::Path>>(db_path: P) -> Result<Self> {
+        Self::open_with_options(db_path, &ConnectionOptions::current())
+    }
+
+    /// Create a new VaporDB instance, opening the database with the given options, such
+    /// as a busy timeout or read-only access
+    pub fn open_with_options<P: AsRef<std::path::Path>>(
+        db_path: P,
+        options: &ConnectionOptions,
+    ) -> Result<Self> {
         let db_path_str = db_path.as_ref().to_string_lossy().to_string();
-        let connection = Connection::open(&db_path_str)?;
+        let connection = options.open(&db_path_str)?;
 
         let bookmark_manager = BookmarkManager::new().ok();
         let transaction_manager = TransactionManager::new();
@@ -88,12 +306,14 @@ impl VaporDB {
     pub fn execute(&self, sql: &str) -> Result<()> {
         let options = QueryOptions::default();
         let dummy_last_query = Arc::new(Mutex::new(String::new()));
+        self.transaction_manager.record_statement();
         execute_sql(&self.connection, sql, &options, &dummy_last_query)
     }
 
     /// Execute a SQL query with custom options
     pub fn execute_with_options(&self, sql: &str, options: &QueryOptions) -> Result<()> {
         let dummy_last_query = Arc::new(Mutex::new(String::new()));
+        self.transaction_manager.record_statement();
         execute_sql(&self.connection, sql, options, &dummy_last_query)
     }
 
@@ -117,6 +337,12 @@ impl VaporDB {
         show_database_info(&self.connection, &self.db_path)
     }
 
+    /// Get database information (size, page statistics, journal mode, and per-table counts);
+    /// row counts are estimated unless `exact` is set
+    pub fn database_info(&self, exact: bool) -> Result<DatabaseInfo> {
+        database_info(&self.connection, &self.db_path, exact)
+    }
+
     /// Export a table to CSV
     pub fn export_to_csv(&self, table_name: &str, file_path: &str) -> Result<()> {
         let query = format!("SELECT * FROM {}", table_name);
@@ -128,6 +354,44 @@ impl VaporDB {
         export_to_csv(&self.connection, query, file_path)
     }
 
+    /// Export a table to an Excel workbook
+    pub fn export_to_xlsx(&self, table_name: &str, file_path: &str) -> Result<()> {
+        export_tables_to_xlsx(&self.connection, file_path, &[table_name.to_string()])
+    }
+
+    /// Export query results to an Excel workbook
+    pub fn export_query_to_xlsx(&self, query: &str, file_path: &str) -> Result<()> {
+        export_to_xlsx(&self.connection, query, file_path)
+    }
+
+    /// Import a file into a table, picking the format from the file extension
+    pub fn import_file(&mut self, file_path: &str, table_name: &str) -> Result<usize> {
+        import_file(&mut self.connection, file_path, table_name, None)
+    }
+
+    /// Run a long operation, such as a query, export, import, or populate, so that it
+    /// can be stopped from another thread with `token.cancel()`
+    ///
+    /// A cancelled operation fails with a `Cancelled` error holding the rows completed,
+    /// for example the rows already written to an export file.
+    ///
+    /// ```no_run
+    /// # use vapor_cli::{CancellationToken, VaporDB};
+    /// let mut db = VaporDB::open("my_database.db")?;
+    /// let token = CancellationToken::new();
+    /// let cancel = token.clone(); // move this to the thread that cancels
+    /// db.run_cancellable(&token, |db| db.export_query_to_csv("SELECT * FROM logs", "logs.csv"))?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn run_cancellable<T>(
+        &mut self,
+        token: &CancellationToken,
+        operation: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        let interrupt = self.connection.get_interrupt_handle();
+        token.run(interrupt, || operation(self))
+    }
+
     /// Start the interactive REPL
     pub fn start_repl(&self) -> Result<()> {
         repl_mode(&self.db_path)
@@ -144,20 +408,76 @@ impl VaporDB {
     }
 
     /// Begin a transaction
+    ///
+    /// Transactions nest: beginning one while another is active creates a savepoint, so
+    /// functions that each manage their own transaction can call one another.
+    ///
+    /// ```no_run
+    /// # use vapor_cli::VaporDB;
+    /// let db = VaporDB::open("my_database.db")?;
+    /// db.begin_transaction()?;
+    /// db.execute("INSERT INTO orders (id) VALUES (1)")?;
+    /// db.begin_transaction()?; // a savepoint
+    /// db.execute("INSERT INTO audit (order_id) VALUES (1)")?;
+    /// db.rollback_transaction()?; // undoes only the audit row
+    /// db.commit_transaction()?; // commits the order
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
     pub fn begin_transaction(&self) -> Result<()> {
-        self.transaction_manager.begin_transaction(&self.connection)
+        self.transaction_manager.begin_nested(&self.connection)
     }
 
-    /// Commit the current transaction
+    /// Commit the current transaction, or release the innermost nested one
     pub fn commit_transaction(&self) -> Result<()> {
-        self.transaction_manager
-            .commit_transaction(&self.connection)
+        self.transaction_manager.commit_nested(&self.connection)
     }
 
-    /// Rollback the current transaction
+    /// Rollback the current transaction, or only the innermost nested one
     pub fn rollback_transaction(&self) -> Result<()> {
-        self.transaction_manager
-            .rollback_transaction(&self.connection)
+        self.transaction_manager.rollback_nested(&self.connection)
+    }
+
+    /// Run code in a transaction that is committed if it returns `Ok` and rolled back if
+    /// it returns `Err` or panics
+    ///
+    /// Inside another transaction this uses a savepoint, so only the code's own changes
+    /// are undone. A panic is rolled back and then resumed.
+    ///
+    /// ```no_run
+    /// # use vapor_cli::VaporDB;
+    /// let mut db = VaporDB::open("my_database.db")?;
+    /// db.with_transaction(|db| {
+    ///     db.execute("INSERT INTO orders (id) VALUES (1)")?;
+    ///     db.execute("INSERT INTO audit (order_id) VALUES (1)")
+    /// })?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn with_transaction<T>(
+        &mut self,
+        operation: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        self.begin_transaction()?;
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| operation(self)));
+        match result {
+            Ok(Ok(value)) => {
+                if let Err(e) = self.commit_transaction() {
+                    // A failed COMMIT leaves the transaction open
+                    let _ = self.rollback_transaction();
+                    return Err(e);
+                }
+                Ok(value)
+            }
+            Ok(Err(e)) => {
+                if let Err(rollback_error) = self.rollback_transaction() {
+                    eprintln!("Warning: Could not roll back: {:#}", rollback_error);
+                }
+                Err(e)
+            }
+            Err(panic) => {
+                let _ = self.rollback_transaction();
+                std::panic::resume_unwind(panic)
+            }
+        }
     }
 
     /// Check if a transaction is active
@@ -165,13 +485,106 @@ impl VaporDB {
         self.transaction_manager.is_active()
     }
 
+    /// How deeply transactions are nested: 0 outside a transaction, 1 inside one, and
+    /// one more for each nested `begin_transaction`
+    pub fn transaction_depth(&self) -> usize {
+        self.transaction_manager.depth()
+    }
+
+    /// How long the open transaction has run, the statements executed in it, and the rows
+    /// they changed, or `None` outside a transaction
+    pub fn transaction_stats(&self) -> Option<TransactionStats> {
+        self.transaction_manager.stats(&self.connection)
+    }
+
     /// Get access to the bookmark manager
     pub fn bookmark_manager(&mut self) -> Option<&mut BookmarkManager> {
         self.bookmark_manager.as_mut()
     }
+
+    /// Turn this database into a handle that can be cloned and shared across threads,
+    /// such as between the handlers of a web server
+    ///
+    /// ```no_run
+    /// # use vapor_cli::VaporDB;
+    /// let db = VaporDB::open("my_database.db")?.into_shared();
+    /// let worker = db.clone();
+    /// std::thread::spawn(move || worker.with(|db| db.execute("DELETE FROM sessions")));
+    /// let tables = db.with(|db| db.list_tables())?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn into_shared(self) -> SharedVaporDB {
+        SharedVaporDB {
+            inner: Arc::new(Mutex::new(self)),
+        }
+    }
+}
+
+/// A `VaporDB` that can be shared across threads, created with `VaporDB::into_shared`
+///
+/// Clones refer to the same database. Each call to `with` has the database to itself
+/// until it returns, so a transaction begun and committed within one call can't be
+/// interleaved with statements from other threads. SQLite runs one statement at a time
+/// per connection, so calls from different threads run one after another.
+///
+/// The calls block, so async code should make them from a blocking task, such as with
+/// `tokio::task::spawn_blocking`.
+#[cfg(feature = "native")]
+#[derive(Clone)]
+pub struct SharedVaporDB {
+    inner: Arc<Mutex<VaporDB>>,
+}
+
+#[cfg(feature = "native")]
+impl SharedVaporDB {
+    /// Run code with exclusive access to the database
+    ///
+    /// If the code panics with a transaction open, the transaction is rolled back so the
+    /// next caller doesn't run inside it.
+    pub fn with<T>(&self, operation: impl FnOnce(&mut VaporDB) -> T) -> T {
+        // A panic in an earlier call leaves the database usable, so ignore the poisoning
+        let mut db = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        let guard = RollbackOnPanic(&mut db);
+        operation(&mut *guard.0)
+    }
+
+    /// The path of the database file
+    pub fn db_path(&self) -> String {
+        self.with(|db| db.db_path.clone())
+    }
+
+    /// Get the `VaporDB` back if this is the last handle to it, or the handle otherwise
+    pub fn try_into_inner(self) -> std::result::Result<VaporDB, Self> {
+        match Arc::try_unwrap(self.inner) {
+            Ok(mutex) => Ok(mutex.into_inner().unwrap_or_else(PoisonError::into_inner)),
+            Err(inner) => Err(Self { inner }),
+        }
+    }
+}
+
+/// Rolls back an open transaction if dropped while the thread is panicking
+#[cfg(feature = "native")]
+struct RollbackOnPanic<'a>(&'a mut VaporDB);
+
+#[cfg(feature = "native")]
+impl Drop for RollbackOnPanic<'_> {
+    fn drop(&mut self) {
+        if !std::thread::panicking() {
+            return;
+        }
+        if self.0.is_transaction_active() {
+            // Roll back the whole transaction, not just the innermost savepoint
+            let _ = self
+                .0
+                .transaction_manager
+                .rollback_transaction(&self.0.connection);
+        } else if !self.0.connection.is_autocommit() {
+            let _ = self.0.connection.execute_batch("ROLLBACK");
+        }
+    }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "native"))]
 mod tests {
     use super::*;
     use tempfile::NamedTempFile;
@@ -227,6 +640,64 @@ mod tests {
         .unwrap();
     }
 
+    #[test]
+    fn test_returning_rows_are_kept_for_export() {
+        let temp_db = NamedTempFile::new().unwrap();
+        let db_path = temp_db.path().to_str().unwrap();
+        let conn = rusqlite::Connection::open(db_path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE test_table (id INTEGER PRIMARY KEY, name TEXT);
+             INSERT INTO test_table (name) VALUES ('test');",
+        )
+        .unwrap();
+
+        // Rows returned by a write are kept in full instead of as a query to run again
+        let last = Arc::new(Mutex::new(LastQuery {
+            sql: "SELECT 1".to_string(),
+            returned: None,
+        }));
+        let limited = QueryOptions {
+            max_rows: Some(1),
+            ..QueryOptions::default()
+        };
+        execute_sql_with_last(
+            &conn,
+            "INSERT INTO test_table (name) VALUES ('a'), ('b') RETURNING id, name",
+            &limited,
+            &last,
+        )
+        .unwrap();
+        let kept = last.lock().unwrap().clone();
+        assert!(kept.sql.is_empty());
+        assert_eq!(kept.returned.unwrap().rows.len(), 2);
+
+        // The String form of the last query is cleared the same way
+        let last_query = Arc::new(Mutex::new("SELECT 1".to_string()));
+        execute_sql(
+            &conn,
+            "INSERT INTO test_table (name) VALUES ('c') RETURNING id",
+            &limited,
+            &last_query,
+        )
+        .unwrap();
+        assert!(last_query.lock().unwrap().is_empty());
+
+        // A session exports the rows its last write returned
+        let session = Session::open(db_path).unwrap();
+        session
+            .query("UPDATE test_table SET name = upper(name) WHERE id IN (2, 3) RETURNING name")
+            .unwrap();
+        assert_eq!(session.last_query(), None);
+        let temp_dir = tempfile::tempdir().unwrap();
+        let out = temp_dir.path().join("returned.csv");
+        let out = out.to_str().unwrap();
+        let written = session
+            .export_last_result(out, None, &ExportOptions::default())
+            .unwrap();
+        assert_eq!(written, 2);
+        assert_eq!(std::fs::read_to_string(out).unwrap(), "name\nA\nB\n");
+    }
+
     #[test]
     fn test_show_table_schema() {
         let temp_db = NamedTempFile::new().unwrap();
@@ -271,8 +742,156 @@ mod tests {
         )
         .unwrap();
 
+        conn.execute("CREATE INDEX idx_test_table_name ON test_table (name)", [])
+            .unwrap();
+        conn.execute("INSERT INTO test_table (name) VALUES ('a'), ('b')", [])
+            .unwrap();
+
         // Test showing database info
         show_database_info(&conn, db_path).unwrap();
+
+        let info = database_info(&conn, db_path, true).unwrap();
+        assert_eq!(info.path, db_path);
+        assert!(info.size_bytes.unwrap() > 0);
+        assert!(info.page_count > 0);
+        assert_eq!(info.journal_mode, "delete");
+        assert_eq!(info.tables.len(), 1);
+        assert_eq!(info.tables[0].name, "test_table");
+        assert_eq!(info.tables[0].rows, 2);
+        assert_eq!(info.tables[0].indexes, 1);
+        assert!(!info.tables[0].estimated);
+        assert_eq!(info.total_rows, 2);
+
+        // Estimates come from the largest rowid, then from sqlite_stat1 after ANALYZE
+        conn.execute("DELETE FROM test_table WHERE name = 'a'", [])
+            .unwrap();
+        let estimated = &database_info(&conn, db_path, false).unwrap().tables[0];
+        assert!(estimated.estimated);
+        assert_eq!(estimated.rows, 2);
+        assert_eq!(estimated.size_bytes, None);
+        conn.execute_batch("ANALYZE").unwrap();
+        let estimated = &database_info(&conn, db_path, false).unwrap().tables[0];
+        assert_eq!(estimated.rows, 1);
+
+        let json: serde_json::Value = serde_json::from_str(&info.to_json().unwrap()).unwrap();
+        assert_eq!(json["tables"][0]["name"], "test_table");
+        assert_eq!(json["total_rows"], 2);
+    }
+
+    #[test]
+    fn test_vapor_db_database_info() {
+        let temp_db = NamedTempFile::new().unwrap();
+        let vapor_db = VaporDB::create(temp_db.path()).unwrap();
+        let conn = &vapor_db.connection;
+        conn.execute_batch(
+            "PRAGMA user_version = 7;
+             CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT);
+             CREATE INDEX items_name ON items (name);
+             INSERT INTO items (name) VALUES ('a'), ('b'), ('c');
+             CREATE TABLE notes (body TEXT);
+             INSERT INTO notes VALUES ('x');
+             ATTACH ':memory:' AS scratch;
+             CREATE TABLE scratch.temp_rows (v);
+             INSERT INTO scratch.temp_rows VALUES (1), (2);",
+        )
+        .unwrap();
+        let pragma = |name: &str| -> i64 {
+            conn.query_row(&format!("PRAGMA {}", name), [], |row| row.get(0))
+                .unwrap()
+        };
+
+        let info = vapor_db.database_info(true).unwrap();
+        assert_eq!(info.path, vapor_db.db_path);
+        assert_eq!(
+            info.size_bytes,
+            Some(std::fs::metadata(temp_db.path()).unwrap().len())
+        );
+        assert_eq!(info.sqlite_version, rusqlite::version());
+        assert_eq!(info.page_size, pragma("page_size"));
+        assert_eq!(info.page_count, pragma("page_count"));
+        assert_eq!(info.freelist_count, 0);
+        assert_eq!(info.schema_version, 7);
+        let journal_mode: String = conn
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(info.journal_mode, journal_mode);
+        assert_eq!(
+            info.attached,
+            vec![AttachedDatabase {
+                alias: "scratch".to_string(),
+                file: ":memory:".to_string(),
+            }]
+        );
+        let tables: Vec<(&str, i64, i64, bool)> = info
+            .tables
+            .iter()
+            .map(|t| (t.name.as_str(), t.rows, t.indexes, t.estimated))
+            .collect();
+        assert_eq!(
+            tables,
+            vec![
+                ("items", 3, 1, false),
+                ("notes", 1, 0, false),
+                ("scratch.temp_rows", 2, 0, false),
+            ]
+        );
+        assert_eq!(info.total_rows, 6);
+
+        // The JSON form has one key per field, with tables and attached databases as arrays
+        let json: serde_json::Value = serde_json::from_str(&info.to_json().unwrap()).unwrap();
+        let mut keys: Vec<&str> = json
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        keys.sort_unstable();
+        assert_eq!(
+            keys,
+            [
+                "attached",
+                "freelist_count",
+                "journal_mode",
+                "page_count",
+                "page_size",
+                "path",
+                "schema_version",
+                "size_bytes",
+                "sqlite_version",
+                "tables",
+                "total_rows",
+            ]
+        );
+        assert_eq!(json["path"], info.path.as_str());
+        assert_eq!(json["schema_version"], 7);
+        assert_eq!(json["total_rows"], 6);
+        assert_eq!(
+            json["attached"],
+            serde_json::json!([{ "alias": "scratch", "file": ":memory:" }])
+        );
+        let mut table_keys: Vec<&str> = json["tables"][0]
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        table_keys.sort_unstable();
+        assert_eq!(
+            table_keys,
+            ["estimated", "indexes", "name", "rows", "size_bytes"]
+        );
+        assert_eq!(json["tables"][0]["name"], "items");
+        assert_eq!(json["tables"][0]["rows"], 3);
+        assert_eq!(json["tables"][0]["estimated"], false);
+
+        // Without a file there is no size, which serializes as null
+        let memory = rusqlite::Connection::open_in_memory().unwrap();
+        let info = database_info(&memory, ":memory:", false).unwrap();
+        assert_eq!(info.size_bytes, None);
+        assert!(info.tables.is_empty());
+        let json: serde_json::Value = serde_json::from_str(&info.to_json().unwrap()).unwrap();
+        assert!(json["size_bytes"].is_null());
+        assert_eq!(json["tables"], serde_json::json!([]));
     }
 
     #[test]
@@ -331,6 +950,269 @@ mod tests {
         assert!(!vapor_db.is_transaction_active());
     }
 
+    #[test]
+    fn test_nested_transactions() {
+        let temp_db = NamedTempFile::new().unwrap();
+        let vapor_db = VaporDB::create(temp_db.path()).unwrap();
+        vapor_db.execute("CREATE TABLE t (x INTEGER)").unwrap();
+        let values = |db: &VaporDB| -> Vec<i64> {
+            db.connection
+                .prepare("SELECT x FROM t ORDER BY x")
+                .unwrap()
+                .query_map([], |row| row.get(0))
+                .unwrap()
+                .collect::<rusqlite::Result<_>>()
+                .unwrap()
+        };
+
+        vapor_db.begin_transaction().unwrap();
+        vapor_db.execute("INSERT INTO t VALUES (1)").unwrap();
+        vapor_db.begin_transaction().unwrap();
+        vapor_db.execute("INSERT INTO t VALUES (2)").unwrap();
+        vapor_db.begin_transaction().unwrap();
+        assert_eq!(vapor_db.transaction_depth(), 3);
+        vapor_db.execute("INSERT INTO t VALUES (3)").unwrap();
+        vapor_db.rollback_transaction().unwrap();
+        vapor_db.commit_transaction().unwrap();
+        assert_eq!(vapor_db.transaction_depth(), 1);
+        assert!(!vapor_db.connection.is_autocommit());
+        vapor_db.commit_transaction().unwrap();
+        assert_eq!(vapor_db.transaction_depth(), 0);
+        assert_eq!(values(&vapor_db), [1, 2]);
+
+        // Rolling back the outer transaction undoes committed inner ones too
+        vapor_db.begin_transaction().unwrap();
+        vapor_db.begin_transaction().unwrap();
+        vapor_db.execute("INSERT INTO t VALUES (4)").unwrap();
+        vapor_db.commit_transaction().unwrap();
+        vapor_db.rollback_transaction().unwrap();
+        assert!(!vapor_db.is_transaction_active());
+        assert_eq!(values(&vapor_db), [1, 2]);
+    }
+
+    #[test]
+    fn test_with_transaction() {
+        let temp_db = NamedTempFile::new().unwrap();
+        let mut vapor_db = VaporDB::create(temp_db.path()).unwrap();
+        vapor_db.execute("CREATE TABLE t (x INTEGER)").unwrap();
+        let count = |db: &VaporDB| -> i64 {
+            db.connection
+                .query_row("SELECT COUNT(*) FROM t", [], |row| row.get(0))
+                .unwrap()
+        };
+
+        let value = vapor_db
+            .with_transaction(|db| {
+                db.execute("INSERT INTO t VALUES (1)")?;
+                // An inner failure undoes only its own changes
+                let inner = db.with_transaction(|db| {
+                    db.execute("INSERT INTO t VALUES (2)")?;
+                    db.execute("INSERT INTO missing VALUES (3)")
+                });
+                assert!(inner.is_err());
+                Ok(42)
+            })
+            .unwrap();
+        assert_eq!(value, 42);
+        assert_eq!(count(&vapor_db), 1);
+        assert_eq!(vapor_db.transaction_depth(), 0);
+
+        let failed = vapor_db.with_transaction(|db| -> Result<()> {
+            db.execute("INSERT INTO t VALUES (4)")?;
+            anyhow::bail!("validation failed")
+        });
+        assert_eq!(failed.unwrap_err().to_string(), "validation failed");
+        assert_eq!(count(&vapor_db), 1);
+
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            vapor_db.with_transaction(|db| -> Result<()> {
+                db.execute("INSERT INTO t VALUES (5)")?;
+                panic!("bug in the caller");
+            })
+        }));
+        assert!(panicked.is_err());
+        assert!(!vapor_db.is_transaction_active());
+        assert!(vapor_db.connection.is_autocommit());
+        assert_eq!(count(&vapor_db), 1);
+    }
+
+    #[test]
+    fn test_transaction_error_policy() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE t (x INTEGER PRIMARY KEY)")
+            .unwrap();
+        let manager = TransactionManager::new();
+        assert_eq!(manager.error_policy(), ErrorPolicy::Continue);
+
+        // By default a failed statement leaves the transaction open
+        assert!(manager.handle_sql_command(&conn, "BEGIN;").unwrap());
+        conn.execute_batch("INSERT INTO t VALUES (1)").unwrap();
+        assert!(conn.execute_batch("INSERT INTO t VALUES (1)").is_err());
+        manager.statement_failed(&conn).unwrap();
+        assert!(manager.is_active() && !manager.is_aborted());
+
+        manager.set_error_policy(ErrorPolicy::parse("ABORT").unwrap());
+        manager.statement_failed(&conn).unwrap();
+        assert!(manager.is_aborted() && manager.is_active());
+        // An aborted transaction is rolled back even when committed
+        manager.handle_sql_command(&conn, "commit;").unwrap();
+        assert!(!manager.is_active() && conn.is_autocommit());
+
+        manager.set_error_policy(ErrorPolicy::Rollback);
+        manager.begin_transaction(&conn).unwrap();
+        conn.execute_batch("INSERT INTO t VALUES (2)").unwrap();
+        manager.statement_failed(&conn).unwrap();
+        assert!(!manager.is_active() && conn.is_autocommit());
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM t", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 0);
+
+        assert!(ErrorPolicy::parse("ignore").is_err());
+    }
+
+    #[test]
+    fn test_connection_options() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("options.db");
+        let path = path.to_str().unwrap();
+
+        let missing = ConnectionOptions {
+            create_if_missing: false,
+            ..ConnectionOptions::default()
+        };
+        assert!(missing.open(path).is_err());
+        let conn = ConnectionOptions::default().open(path).unwrap();
+        conn.execute_batch("CREATE TABLE t (x INTEGER)").unwrap();
+
+        let options = ConnectionOptions {
+            busy_timeout: Some(std::time::Duration::from_millis(1500)),
+            read_only: true,
+            ..ConnectionOptions::default()
+        };
+        let vapor_db = VaporDB::open_with_options(path, &options).unwrap();
+        let timeout: i64 = vapor_db
+            .connection
+            .query_row("PRAGMA busy_timeout", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(timeout, 1500);
+        assert!(vapor_db
+            .connection
+            .execute_batch("INSERT INTO t VALUES (1)")
+            .is_err());
+        assert!(vapor_db
+            .connection
+            .query_row("SELECT COUNT(*) FROM t", [], |_| Ok(()))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_transaction_stats() {
+        let temp_db = NamedTempFile::new().unwrap();
+        let vapor_db = VaporDB::create(temp_db.path()).unwrap();
+        vapor_db.execute("CREATE TABLE t (x INTEGER)").unwrap();
+        assert!(vapor_db.transaction_stats().is_none());
+
+        vapor_db.begin_transaction().unwrap();
+        vapor_db
+            .execute("INSERT INTO t VALUES (1), (2), (3)")
+            .unwrap();
+        vapor_db.execute("DELETE FROM t WHERE x = 2").unwrap();
+        vapor_db
+            .execute_with_options("UPDATE t SET x = 4 WHERE x = 3", &QueryOptions::default())
+            .unwrap();
+        let stats = vapor_db.transaction_stats().unwrap();
+        assert_eq!(stats.statements, 3);
+        assert_eq!(stats.rows_changed, 5);
+        assert!(stats.to_string().ends_with("3 statements, 5 rows changed"));
+
+        vapor_db.commit_transaction().unwrap();
+        assert!(vapor_db.transaction_stats().is_none());
+    }
+
+    #[test]
+    fn test_optimize_on_exit() {
+        let temp_db = NamedTempFile::new().unwrap();
+        let conn = Connection::open(temp_db.path()).unwrap();
+        conn.query_row("PRAGMA journal_mode=WAL", [], |_| Ok(()))
+            .unwrap();
+        conn.execute_batch("CREATE TABLE t (x INTEGER); INSERT INTO t VALUES (1), (2);")
+            .unwrap();
+
+        crate::db::optimize_on_exit(&conn).unwrap();
+        // Every frame in the WAL file has been copied into the database
+        let (log, checkpointed): (i64, i64) = conn
+            .query_row("PRAGMA wal_checkpoint(PASSIVE)", [], |row| {
+                Ok((row.get(1)?, row.get(2)?))
+            })
+            .unwrap();
+        assert!(log > 0);
+        assert_eq!(log, checkpointed);
+
+        // Databases that aren't in WAL mode are only optimized
+        let memory = Connection::open_in_memory().unwrap();
+        crate::db::optimize_on_exit(&memory).unwrap();
+    }
+
+    #[test]
+    fn test_backup_database() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let src = temp_dir.path().join("src.db");
+        let dest = temp_dir.path().join("backup.db");
+        let (src, dest) = (src.to_str().unwrap(), dest.to_str().unwrap());
+        let conn = Connection::open(src).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE t (x TEXT);
+             WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 5000)
+             INSERT INTO t SELECT printf('%0500d', i) FROM n;",
+        )
+        .unwrap();
+
+        let mut steps = Vec::new();
+        crate::db::backup_database(src, dest, |copied, total| steps.push((copied, total))).unwrap();
+        let &(copied, total) = steps.last().unwrap();
+        assert!(steps.len() > 1);
+        assert_eq!(copied, total);
+        let count: i64 = Connection::open(dest)
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM t", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 5000);
+
+        assert!(crate::db::backup_connection(&conn, src, |_, _| {}).is_err());
+        conn.execute_batch("BEGIN").unwrap();
+        assert!(crate::db::backup_connection(&conn, dest, |_, _| {}).is_err());
+    }
+
+    #[test]
+    fn test_copy_database() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let src = temp_dir.path().join("src.db");
+        let dest = temp_dir.path().join("clone.db");
+        let (src, dest) = (src.to_str().unwrap(), dest.to_str().unwrap());
+        Connection::open(src)
+            .unwrap()
+            .execute_batch("CREATE TABLE t (x INTEGER); INSERT INTO t VALUES (1), (2);")
+            .unwrap();
+
+        crate::db::copy_database(src, dest, false, |_, _| {}).unwrap();
+        let count: i64 = Connection::open(dest)
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM t", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 2);
+
+        let err = crate::db::copy_database(src, dest, false, |_, _| {}).unwrap_err();
+        assert!(err.to_string().contains("--force"));
+        crate::db::copy_database(src, dest, true, |_, _| {}).unwrap();
+
+        let not_db = temp_dir.path().join("notes.txt");
+        std::fs::write(&not_db, "not a database").unwrap();
+        let not_db = not_db.to_str().unwrap();
+        assert!(crate::db::copy_database(not_db, dest, true, |_, _| {}).is_err());
+        assert!(crate::db::copy_database(src, src, true, |_, _| {}).is_err());
+    }
+
     #[test]
     fn test_bookmark_manager() {
         let temp_db = NamedTempFile::new().unwrap();
@@ -397,6 +1279,151 @@ mod tests {
         .unwrap();
     }
 
+    #[test]
+    fn test_create_table_generated_and_check() {
+        let temp_db = NamedTempFile::new().unwrap();
+        let db_path = temp_db.path().to_str().unwrap();
+        init_database(db_path).unwrap();
+
+        let options = CreateTableOptions {
+            strict: true,
+            generated: vec![
+                GeneratedColumn::parse("total REAL = price * quantity STORED").unwrap(),
+                GeneratedColumn::parse("label = name || ' (' || quantity || ')'").unwrap(),
+            ],
+            checks: vec!["price >= 0".to_string(), "name <> ';'".to_string()],
+        };
+        let sql = crate::db::create_table_sql(
+            "items",
+            "name TEXT, price REAL, quantity INTEGER",
+            &options,
+        )
+        .unwrap();
+        assert!(sql.contains("total REAL GENERATED ALWAYS AS (price * quantity) STORED"));
+        assert!(
+            sql.contains("label ANY GENERATED ALWAYS AS (name || ' (' || quantity || ')') VIRTUAL")
+        );
+        create_table_with_options(
+            db_path,
+            "items",
+            "name TEXT, price REAL, quantity INTEGER",
+            &options,
+        )
+        .unwrap();
+
+        let conn = rusqlite::Connection::open(db_path).unwrap();
+        conn.execute(
+            "INSERT INTO items (name, price, quantity) VALUES ('pen', 1.5, 4)",
+            [],
+        )
+        .unwrap();
+        let (total, label): (f64, String) = conn
+            .query_row("SELECT total, label FROM items", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!(total, 6.0);
+        assert_eq!(label, "pen (4)");
+        assert!(conn
+            .execute(
+                "INSERT INTO items (name, price, quantity) VALUES ('bad', -1, 1)",
+                []
+            )
+            .is_err());
+
+        assert!(GeneratedColumn::parse("total").is_err());
+        assert!(GeneratedColumn::parse("x = 1); DROP TABLE items; --").is_err());
+        let bad_check = CreateTableOptions {
+            checks: vec!["price > 0) OR (1".to_string()],
+            ..Default::default()
+        };
+        assert!(crate::db::create_table_sql("t", "price REAL", &bad_check).is_err());
+    }
+
+    #[test]
+    fn test_run_maintenance() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("maintain.db");
+        let copy_path = temp_dir.path().join("compacted.db");
+        let conn = rusqlite::Connection::open(&db_path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE t (x TEXT);
+             CREATE INDEX t_x ON t (x);
+             WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 2000)
+             INSERT INTO t SELECT printf('%0200d', i) FROM n;
+             DELETE FROM t WHERE rowid > 100;",
+        )
+        .unwrap();
+
+        let copy = copy_path.to_str().unwrap().to_string();
+        let report = run_maintenance(&conn, &Maintenance::Vacuum { into: Some(copy) }).unwrap();
+        assert!(report.sql.starts_with("VACUUM INTO"));
+        assert!(report.size_after < report.size_before);
+        let vacuum = run_maintenance(&conn, &Maintenance::Vacuum { into: None }).unwrap();
+        assert_eq!(vacuum.size_after, report.size_after);
+        run_maintenance(&conn, &Maintenance::Reindex).unwrap();
+        run_maintenance(&conn, &Maintenance::Analyze).unwrap();
+        let analyzed: i64 = conn
+            .query_row("SELECT COUNT(*) FROM sqlite_stat1", [], |row| row.get(0))
+            .unwrap();
+        assert!(analyzed > 0);
+
+        conn.execute_batch("BEGIN").unwrap();
+        assert!(run_maintenance(&conn, &Maintenance::Vacuum { into: None }).is_err());
+        conn.execute_batch("ROLLBACK").unwrap();
+    }
+
+    #[test]
+    fn test_wal_mode_and_checkpoint() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let conn = rusqlite::Connection::open(temp_dir.path().join("wal.db")).unwrap();
+        conn.execute_batch("CREATE TABLE t (x INTEGER)").unwrap();
+        assert!(checkpoint(&conn, CheckpointMode::Passive).is_err());
+        assert!(set_journal_mode(&conn, "fast").is_err());
+
+        assert_eq!(set_journal_mode(&conn, "WAL").unwrap(), "wal");
+        conn.execute_batch("INSERT INTO t VALUES (1), (2)").unwrap();
+        assert!(crate::db::wal_size(&conn).unwrap() > 0);
+        let report = checkpoint(&conn, CheckpointMode::parse("full").unwrap()).unwrap();
+        assert!(!report.busy);
+        assert!(report.log_frames > 0);
+        assert_eq!(report.checkpointed_frames, report.log_frames);
+        let report = checkpoint(&conn, CheckpointMode::Truncate).unwrap();
+        assert_eq!(report.wal_size_after, 0);
+
+        conn.execute_batch("BEGIN").unwrap();
+        assert!(set_journal_mode(&conn, "delete").is_err());
+        conn.execute_batch("ROLLBACK").unwrap();
+        assert_eq!(set_journal_mode(&conn, "delete").unwrap(), "delete");
+        let memory = rusqlite::Connection::open_in_memory().unwrap();
+        assert!(set_journal_mode(&memory, "wal").is_err());
+    }
+
+    #[test]
+    fn test_pragma_helpers() {
+        use crate::db::{pragma_settings, pragma_statement};
+
+        assert_eq!(
+            pragma_statement("main.journal_mode", None).unwrap(),
+            "PRAGMA main.journal_mode"
+        );
+        assert_eq!(
+            pragma_statement("cache_size", Some("-4000")).unwrap(),
+            "PRAGMA cache_size = -4000"
+        );
+        assert!(pragma_statement("table_info", Some("'it''s'")).is_ok());
+        assert!(pragma_statement("x; DROP TABLE t", None).is_err());
+        assert!(pragma_statement("user_version", Some("1; DROP TABLE t")).is_err());
+
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(&pragma_statement("foreign_keys", Some("ON")).unwrap())
+            .unwrap();
+        let settings = pragma_settings(&conn).unwrap();
+        let names: Vec<&str> = settings.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, crate::db::IMPORTANT_PRAGMAS);
+        assert!(settings.contains(&("foreign_keys", "1 (on)".to_string())));
+    }
+
     #[test]
     fn test_output_formats() {
         let temp_db = NamedTempFile::new().unwrap();
@@ -434,7 +1461,32 @@ mod tests {
             ..Default::default()
         };
         let dummy_last_query = Arc::new(Mutex::new(String::new()));
-        execute_sql(&conn, "SELECT * FROM test_output", &json_options, &dummy_last_query).unwrap();
+        execute_sql(
+            &conn,
+            "SELECT * FROM test_output",
+            &json_options,
+            &dummy_last_query,
+        )
+        .unwrap();
+
+        let plain_options = QueryOptions {
+            format: OutputFormat::Plain,
+            ..Default::default()
+        };
+        let dummy_last_query = Arc::new(Mutex::new(String::new()));
+        execute_sql(
+            &conn,
+            "SELECT * FROM test_output",
+            &plain_options,
+            &dummy_last_query,
+        )
+        .unwrap();
+
+        // The library's default doesn't depend on where stdout goes
+        assert!(matches!(
+            QueryOptions::default().format,
+            OutputFormat::Table
+        ));
     }
 
     #[test]
@@ -508,46 +1560,940 @@ mod tests {
     }
 
     #[test]
-    fn test_vapor_db_with_options() {
+    fn test_xlsx_export() {
         let temp_db = NamedTempFile::new().unwrap();
         let db_path = temp_db.path();
 
         let vapor_db = VaporDB::create(db_path).unwrap();
         vapor_db
-            .execute("CREATE TABLE options_test (id INTEGER PRIMARY KEY, data TEXT)")
+            .execute("CREATE TABLE xlsx_test (id INTEGER PRIMARY KEY, name TEXT, joined DATE)")
             .unwrap();
         vapor_db
-            .execute("INSERT INTO options_test (data) VALUES ('test1'), ('test2'), ('test3')")
+            .execute(
+                "INSERT INTO xlsx_test (name, joined) VALUES ('a', '2024-01-31'), ('b', 'n/a')",
+            )
+            .unwrap();
+        vapor_db
+            .execute("CREATE TABLE xlsx_other (value REAL)")
             .unwrap();
 
-        // Test execute with custom options
-        let options = QueryOptions {
-            format: OutputFormat::Json,
-            show_timing: true,
-            max_rows: Some(2),
-        };
+        let temp_dir = tempfile::tempdir().unwrap();
+        let query_path = temp_dir.path().join("query.xlsx");
         vapor_db
-            .execute_with_options("SELECT * FROM options_test", &options)
+            .export_query_to_xlsx("SELECT * FROM xlsx_test", query_path.to_str().unwrap())
             .unwrap();
+        // An .xlsx file is a zip archive
+        let bytes = std::fs::read(&query_path).unwrap();
+        assert_eq!(&bytes[..2], b"PK");
+
+        let all_path = temp_dir.path().join("all.xlsx");
+        export_tables_to_xlsx(&vapor_db.connection, all_path.to_str().unwrap(), &[]).unwrap();
+        assert!(all_path.exists());
     }
 
+    #[cfg(feature = "avro")]
     #[test]
-    fn test_transaction_rollback() {
+    fn test_avro_export() {
         let temp_db = NamedTempFile::new().unwrap();
-        let db_path = temp_db.path();
-
-        let vapor_db = VaporDB::create(db_path).unwrap();
+        let vapor_db = VaporDB::create(temp_db.path()).unwrap();
         vapor_db
-            .execute("CREATE TABLE rollback_test (id INTEGER PRIMARY KEY, name TEXT)")
+            .execute("CREATE TABLE avro_test (id INTEGER, price REAL, name TEXT)")
             .unwrap();
         vapor_db
-            .execute("INSERT INTO rollback_test (name) VALUES ('initial')")
+            .execute("INSERT INTO avro_test VALUES (1, 9.5, 'a'), (2, NULL, 'b')")
             .unwrap();
 
-        // Begin transaction and insert data
-        vapor_db.begin_transaction().unwrap();
-        vapor_db
-            .execute("INSERT INTO rollback_test (name) VALUES ('transactional')")
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("out.avro");
+        let rows = export_to_avro(
+            &vapor_db.connection,
+            "SELECT id, price, name, id * 2 AS doubled FROM avro_test ORDER BY id",
+            path.to_str().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(rows, 2);
+
+        let reader = apache_avro::Reader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let schema = serde_json::to_string(reader.writer_schema()).unwrap();
+        assert!(schema.contains(r#""name":"doubled","type":["null","long"]"#));
+        assert_eq!(reader.count(), 2);
+
+        // The registered format picks Avro from the extension, and keeps declared types
+        // for an empty result
+        let empty = temp_dir.path().join("empty.avro.gz");
+        let rows = export_query(
+            &vapor_db.connection,
+            "SELECT id, price FROM avro_test WHERE id > 10",
+            empty.to_str().unwrap(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(rows, 0);
+        let decoded = flate2::read::GzDecoder::new(std::fs::File::open(&empty).unwrap());
+        let reader = apache_avro::Reader::new(decoded).unwrap();
+        let schema = serde_json::to_string(reader.writer_schema()).unwrap();
+        assert!(schema.contains(r#""name":"price","type":["null","double"]"#));
+        assert_eq!(reader.count(), 0);
+
+        // Large results are written as several data blocks
+        let many = temp_dir.path().join("many.avro");
+        export_query(
+            &vapor_db.connection,
+            "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 2500) SELECT i FROM n",
+            many.to_str().unwrap(),
+            None,
+        )
+        .unwrap();
+        let reader = apache_avro::Reader::new(std::fs::File::open(&many).unwrap()).unwrap();
+        assert_eq!(reader.filter(|r| r.is_ok()).count(), 2500);
+    }
+
+    #[test]
+    fn test_csv_import_dialect() {
+        let temp_db = NamedTempFile::new().unwrap();
+        let mut vapor_db = VaporDB::create(temp_db.path()).unwrap();
+        vapor_db
+            .execute("CREATE TABLE dialect_test (id INTEGER, city TEXT, note TEXT)")
+            .unwrap();
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("latin1.csv");
+        // A title line, then semicolon-separated Latin-1 data without a header
+        let mut bytes = b"Report generated 2024-01-01\n1;M".to_vec();
+        bytes.push(0xFC); // 'ü' in Latin-1
+        bytes.extend_from_slice(b"nchen;NA\n2;'Oslo; Norway';ok\n");
+        std::fs::write(&path, bytes).unwrap();
+
+        let options = CsvImportOptions {
+            delimiter: b';',
+            quote: b'\'',
+            has_header: false,
+            null_string: Some("NA".to_string()),
+            skip_lines: 1,
+            encoding: TextEncoding::Latin1,
+            ..Default::default()
+        };
+        import_csv_to_table_with_options(
+            &mut vapor_db.connection,
+            path.to_str().unwrap(),
+            "dialect_test",
+            &options,
+        )
+        .unwrap();
+
+        let rows: Vec<(String, Option<String>)> = vapor_db
+            .connection
+            .prepare("SELECT city, note FROM dialect_test ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(rows[0], ("München".to_string(), None));
+        assert_eq!(
+            rows[1],
+            ("Oslo; Norway".to_string(), Some("ok".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_command_mode() {
+        let temp_db = NamedTempFile::new().unwrap();
+        let db_path = temp_db.path().to_str().unwrap();
+        let conn = Connection::open(db_path).unwrap();
+        conn.execute_batch("CREATE TABLE t (x INTEGER)").unwrap();
+
+        command_mode(
+            db_path,
+            "INSERT INTO t VALUES (1); INSERT INTO t VALUES (2); SELECT * FROM t",
+            None,
+            OutputFormat::Csv,
+        )
+        .unwrap();
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM t", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 2);
+
+        // Statements after a failing one don't run
+        let result = command_mode(
+            db_path,
+            "INSERT INTO missing VALUES (1); INSERT INTO t VALUES (3)",
+            None,
+            OutputFormat::Plain,
+        );
+        assert!(result.is_err());
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM t", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 2);
+        assert!(OutputFormat::parse("xml").is_err());
+    }
+
+    #[test]
+    fn test_csv_import_sniffed_dialect() {
+        let temp_db = NamedTempFile::new().unwrap();
+        let mut vapor_db = VaporDB::create(temp_db.path()).unwrap();
+        vapor_db
+            .execute("CREATE TABLE sniffed (id INTEGER, quote TEXT)")
+            .unwrap();
+
+        // A title line, then a tab-separated table with CR line endings and backslash escapes
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("export.tsv");
+        let path = path.to_str().unwrap();
+        std::fs::write(
+            path,
+            "Exported from Legacy\rid\tquote\r1\t\"say \\\"hi\\\"\"\r2\tplain\r",
+        )
+        .unwrap();
+
+        let dialect = crate::sniff::sniff_csv_file(path).unwrap();
+        assert_eq!(dialect.line_ending, LineEnding::Cr);
+        let mut options = CsvImportOptions::default();
+        dialect.apply_to(&mut options);
+        import_csv_to_table_with_options(&mut vapor_db.connection, path, "sniffed", &options)
+            .unwrap();
+
+        let quotes: Vec<String> = vapor_db
+            .connection
+            .prepare("SELECT quote FROM sniffed ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(quotes, vec!["say \"hi\"", "plain"]);
+    }
+
+    #[test]
+    fn test_unicode_round_trip_and_bom() {
+        let temp_db = NamedTempFile::new().unwrap();
+        let mut vapor_db = VaporDB::create(temp_db.path()).unwrap();
+        let texts = [
+            "東京タワー",
+            "naïve café",
+            "😀 emoji 👍🏽",
+            "한국어, \"quoted\"",
+        ];
+        vapor_db
+            .execute("CREATE TABLE src (id INTEGER PRIMARY KEY, text TEXT)")
+            .unwrap();
+        for text in texts {
+            vapor_db
+                .connection
+                .execute("INSERT INTO src (text) VALUES (?)", [text])
+                .unwrap();
+        }
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let query = "SELECT text FROM src ORDER BY id";
+        let read_back = |conn: &Connection, table: &str| -> Vec<String> {
+            conn.prepare(&format!("SELECT text FROM {} ORDER BY rowid", table))
+                .unwrap()
+                .query_map([], |row| row.get(0))
+                .unwrap()
+                .collect::<rusqlite::Result<_>>()
+                .unwrap()
+        };
+
+        // CSV with a byte order mark, read back by both CSV importers
+        let csv_path = temp_dir.path().join("bom.csv");
+        let csv_path = csv_path.to_str().unwrap();
+        let options = ExportOptions {
+            bom: true,
+            ..Default::default()
+        };
+        export_query_with_options(&vapor_db.connection, query, csv_path, None, &options).unwrap();
+        assert!(std::fs::read(csv_path)
+            .unwrap()
+            .starts_with(b"\xEF\xBB\xBFtext\n"));
+        vapor_db
+            .execute("CREATE TABLE from_csv (text TEXT)")
+            .unwrap();
+        import_csv_to_table_with_options(
+            &mut vapor_db.connection,
+            csv_path,
+            "from_csv",
+            &CsvImportOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(read_back(&vapor_db.connection, "from_csv"), texts);
+        vapor_db
+            .execute("CREATE TABLE from_format (text TEXT)")
+            .unwrap();
+        import_file(&mut vapor_db.connection, csv_path, "from_format", None).unwrap();
+        assert_eq!(read_back(&vapor_db.connection, "from_format"), texts);
+
+        // JSON, with a byte order mark added by another tool
+        let json_path = temp_dir.path().join("out.json");
+        let json_path = json_path.to_str().unwrap();
+        export_query_with_options(
+            &vapor_db.connection,
+            query,
+            json_path,
+            None,
+            &Default::default(),
+        )
+        .unwrap();
+        let mut json = UTF8_BOM.to_vec();
+        json.extend(std::fs::read(json_path).unwrap());
+        std::fs::write(json_path, json).unwrap();
+        import_json_to_table(
+            &mut vapor_db.connection,
+            json_path,
+            "from_json",
+            &JsonImportOptions {
+                create_table: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(read_back(&vapor_db.connection, "from_json"), texts);
+        assert!(
+            export_query_with_options(&vapor_db.connection, query, json_path, None, &options)
+                .is_err()
+        );
+
+        // Legacy encodings are transcoded
+        let legacy = [
+            (TextEncoding::ShiftJis, b"\x93\x8c\x8b\x9e".to_vec(), "東京"),
+            (
+                TextEncoding::Windows1252,
+                b"\x80 \x93hi\x94".to_vec(),
+                "€ “hi”",
+            ),
+        ];
+        for (encoding, bytes, expected) in legacy {
+            let path = temp_dir.path().join("legacy.csv");
+            let mut contents = b"text\n".to_vec();
+            contents.extend(bytes);
+            std::fs::write(&path, contents).unwrap();
+            vapor_db.execute("DELETE FROM from_csv").unwrap();
+            let options = CsvImportOptions {
+                encoding,
+                ..Default::default()
+            };
+            import_csv_to_table_with_options(
+                &mut vapor_db.connection,
+                path.to_str().unwrap(),
+                "from_csv",
+                &options,
+            )
+            .unwrap();
+            assert_eq!(read_back(&vapor_db.connection, "from_csv"), vec![expected]);
+        }
+        assert_eq!(TextEncoding::parse("SJIS").unwrap(), TextEncoding::ShiftJis);
+        assert_eq!(
+            TextEncoding::parse("cp1252").unwrap(),
+            TextEncoding::Windows1252
+        );
+    }
+
+    #[test]
+    fn test_export_database_to_csv_dir() {
+        let temp_db = NamedTempFile::new().unwrap();
+        let vapor_db = VaporDB::create(temp_db.path()).unwrap();
+        vapor_db
+            .execute("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)")
+            .unwrap();
+        vapor_db
+            .execute("INSERT INTO users (name) VALUES ('alice'), ('bob')")
+            .unwrap();
+        vapor_db
+            .execute("CREATE TABLE \"order items\" (sku TEXT)")
+            .unwrap();
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path().join("backup");
+        let manifest =
+            export_database_to_csv_dir(&vapor_db.connection, dir.to_str().unwrap()).unwrap();
+
+        assert_eq!(manifest.tables.len(), 2);
+        assert_eq!(manifest.tables[0].file, "order_items.csv");
+        assert_eq!(manifest.tables[1].rows, 2);
+        assert_eq!(manifest.tables[1].columns[0].declared_type, "INTEGER");
+
+        let users = std::fs::read_to_string(dir.join("users.csv")).unwrap();
+        assert_eq!(users, "id,name\n1,alice\n2,bob\n");
+        let written: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.join("manifest.json")).unwrap())
+                .unwrap();
+        assert_eq!(written["tables"][0]["name"], "order items");
+    }
+
+    #[test]
+    fn test_csv_import_batches_and_rejects() {
+        let temp_db = NamedTempFile::new().unwrap();
+        let mut vapor_db = VaporDB::create(temp_db.path()).unwrap();
+        vapor_db
+            .execute("CREATE TABLE batch_test (id INTEGER PRIMARY KEY, name TEXT)")
+            .unwrap();
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("batch.csv");
+        let reject_path = temp_dir.path().join("rejects.csv");
+        // Row 3 repeats a primary key and row 4 has an extra field
+        std::fs::write(&path, "id,name\n1,a\n2,b\n2,dup\n3,c,extra\n4,d\n5,e\n").unwrap();
+
+        let options = CsvImportOptions {
+            batch_size: Some(2),
+            continue_on_error: true,
+            reject_file: Some(reject_path.to_str().unwrap().to_string()),
+            ..Default::default()
+        };
+        let report = import_csv_to_table_with_options(
+            &mut vapor_db.connection,
+            path.to_str().unwrap(),
+            "batch_test",
+            &options,
+        )
+        .unwrap();
+        assert_eq!(report.imported, 4);
+        assert_eq!(report.skipped, 2);
+
+        let count: i64 = vapor_db
+            .connection
+            .query_row("SELECT COUNT(*) FROM batch_test", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 4);
+
+        let rejects = std::fs::read_to_string(&reject_path).unwrap();
+        let lines: Vec<&str> = rejects.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "id,name,_error");
+        assert!(lines[1].starts_with("2,dup,"));
+        assert!(lines[2].starts_with("3,c,extra,"));
+
+        // Without continue_on_error the first bad row aborts the import
+        vapor_db.execute("DELETE FROM batch_test").unwrap();
+        let result = import_csv_to_table_with_options(
+            &mut vapor_db.connection,
+            path.to_str().unwrap(),
+            "batch_test",
+            &CsvImportOptions::default(),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_chunked_import_resume() {
+        let temp_db = NamedTempFile::new().unwrap();
+        let mut vapor_db = VaporDB::create(temp_db.path()).unwrap();
+        vapor_db
+            .execute("CREATE TABLE resume_test (id INTEGER PRIMARY KEY, name TEXT)")
+            .unwrap();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let csv_path = temp_dir.path().join("rows.csv");
+        let csv = csv_path.to_str().unwrap();
+        let json_path = temp_dir.path().join("rows.jsonl");
+        let json = json_path.to_str().unwrap();
+        let mut csv_text = String::from("id,name\n");
+        let mut json_text = String::new();
+        for id in 1..=10 {
+            csv_text.push_str(&format!("{},row{}\n", id, id));
+            json_text.push_str(&format!(
+                "{{\"id\": {}, \"name\": \"row{}\"}}\n",
+                id + 10,
+                id
+            ));
+        }
+        std::fs::write(&csv_path, csv_text).unwrap();
+        std::fs::write(&json_path, json_text).unwrap();
+        let count = |db: &VaporDB| -> i64 {
+            db.connection
+                .query_row("SELECT COUNT(*) FROM resume_test", [], |row| row.get(0))
+                .unwrap()
+        };
+
+        // Row 5 collides with an existing key after the first chunk of 3 is committed
+        vapor_db
+            .execute("INSERT INTO resume_test VALUES (5, 'existing')")
+            .unwrap();
+        let mut options = CsvImportOptions {
+            batch_size: Some(3),
+            ..Default::default()
+        };
+        let result = import_csv_to_table_with_options(
+            &mut vapor_db.connection,
+            csv,
+            "resume_test",
+            &options,
+        );
+        assert!(result.is_err());
+        assert_eq!(count(&vapor_db), 4);
+        let checkpoint: ImportCheckpoint = serde_json::from_str(
+            &std::fs::read_to_string(ImportCheckpoint::path_for(csv)).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(checkpoint.records, 3);
+
+        // Resuming into another table is refused
+        options.resume = true;
+        assert!(
+            import_csv_to_table_with_options(&mut vapor_db.connection, csv, "other", &options)
+                .is_err()
+        );
+
+        vapor_db
+            .execute("DELETE FROM resume_test WHERE id = 5")
+            .unwrap();
+        let report = import_csv_to_table_with_options(
+            &mut vapor_db.connection,
+            csv,
+            "resume_test",
+            &options,
+        )
+        .unwrap();
+        assert_eq!(report.imported, 7);
+        assert_eq!(count(&vapor_db), 10);
+        assert!(!std::path::Path::new(&ImportCheckpoint::path_for(csv)).exists());
+
+        // JSON imports skip bad documents rather than failing, so a failure after the
+        // first chunk of 4 is simulated with the checkpoint it would leave
+        ImportCheckpoint::save(json, "resume_test", 4).unwrap();
+        let json_options = JsonImportOptions {
+            batch_size: Some(4),
+            resume: true,
+            ..Default::default()
+        };
+        let report =
+            import_json_to_table(&mut vapor_db.connection, json, "resume_test", &json_options)
+                .unwrap();
+        assert_eq!(report.imported, 6);
+        assert_eq!(count(&vapor_db), 16);
+        assert!(!std::path::Path::new(&ImportCheckpoint::path_for(json)).exists());
+    }
+
+    #[test]
+    fn test_import_conflict_policy() {
+        let temp_db = NamedTempFile::new().unwrap();
+        let mut vapor_db = VaporDB::create(temp_db.path()).unwrap();
+        vapor_db
+            .execute("CREATE TABLE conflict_test (id INTEGER PRIMARY KEY, name TEXT)")
+            .unwrap();
+        vapor_db
+            .execute("INSERT INTO conflict_test VALUES (1, 'old'), (2, 'old')")
+            .unwrap();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let csv_path = temp_dir.path().join("rows.csv");
+        let csv = csv_path.to_str().unwrap();
+        std::fs::write(&csv_path, "id,name\n1,new\n2,new\n3,new\n").unwrap();
+        let names = |db: &VaporDB| -> String {
+            db.connection
+                .query_row(
+                    "SELECT group_concat(name, ',') FROM (SELECT name FROM conflict_test ORDER BY id)",
+                    [],
+                    |row| row.get(0),
+                )
+                .unwrap()
+        };
+
+        assert!(import_csv_to_table(&mut vapor_db.connection, csv, "conflict_test").is_err());
+
+        let mut options = CsvImportOptions {
+            on_conflict: ConflictPolicy::parse("IGNORE").unwrap(),
+            ..Default::default()
+        };
+        let report = import_csv_to_table_with_options(
+            &mut vapor_db.connection,
+            csv,
+            "conflict_test",
+            &options,
+        )
+        .unwrap();
+        assert_eq!(
+            (report.imported, report.ignored, report.replaced),
+            (1, 2, 0)
+        );
+        assert_eq!(names(&vapor_db), "old,old,new");
+
+        options.on_conflict = ConflictPolicy::Replace;
+        let report = import_csv_to_table_with_options(
+            &mut vapor_db.connection,
+            csv,
+            "conflict_test",
+            &options,
+        )
+        .unwrap();
+        assert_eq!(
+            (report.imported, report.ignored, report.replaced),
+            (3, 0, 3)
+        );
+        assert_eq!(names(&vapor_db), "new,new,new");
+
+        let json_path = temp_dir.path().join("rows.jsonl");
+        std::fs::write(
+            &json_path,
+            "{\"id\": 3, \"name\": \"json\"}\n{\"id\": 4, \"name\": \"json\"}\n",
+        )
+        .unwrap();
+        let json_options = JsonImportOptions {
+            on_conflict: ConflictPolicy::Ignore,
+            ..Default::default()
+        };
+        let report = import_json_to_table(
+            &mut vapor_db.connection,
+            json_path.to_str().unwrap(),
+            "conflict_test",
+            &json_options,
+        )
+        .unwrap();
+        assert_eq!((report.imported, report.ignored, report.skipped), (1, 1, 0));
+        assert_eq!(names(&vapor_db), "new,new,new,json");
+        assert!(ConflictPolicy::parse("merge").is_err());
+    }
+
+    #[test]
+    fn test_import_set_columns() {
+        let temp_db = NamedTempFile::new().unwrap();
+        let mut vapor_db = VaporDB::create(temp_db.path()).unwrap();
+        vapor_db
+            .execute("CREATE TABLE set_test (id INTEGER, source TEXT, loud TEXT, imported_at TEXT)")
+            .unwrap();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let csv_path = temp_dir.path().join("o'neil.csv");
+        let csv = csv_path.to_str().unwrap();
+        std::fs::write(&csv_path, "id\n1\n2\n").unwrap();
+
+        let setting = |spec: &str| ColumnSetting::parse(spec).unwrap();
+        let mut options = CsvImportOptions {
+            set_columns: vec![
+                setting("source={{filename}}"),
+                setting("LOUD=upper('{{filename}}')"),
+                setting("imported_at=now()"),
+            ],
+            ..Default::default()
+        };
+        import_csv_to_table_with_options(&mut vapor_db.connection, csv, "set_test", &options)
+            .unwrap();
+        let (source, loud, missing_times): (String, String, i64) = vapor_db
+            .connection
+            .query_row(
+                "SELECT min(source), min(loud), sum(imported_at IS NULL) FROM set_test",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(source, "o'neil.csv");
+        assert_eq!(loud, "O'NEIL.CSV");
+        assert_eq!(missing_times, 0);
+
+        // Set columns must exist and can't also come from the CSV header
+        options.set_columns = vec![setting("nope=1")];
+        assert!(import_csv_to_table_with_options(
+            &mut vapor_db.connection,
+            csv,
+            "set_test",
+            &options
+        )
+        .is_err());
+        options.set_columns = vec![setting("id=7")];
+        assert!(import_csv_to_table_with_options(
+            &mut vapor_db.connection,
+            csv,
+            "set_test",
+            &options
+        )
+        .is_err());
+        assert!(ColumnSetting::parse("=value").is_err());
+
+        // JSON values are overridden, and a created table gains the set columns
+        let json_path = temp_dir.path().join("rows.jsonl");
+        std::fs::write(&json_path, "{\"id\": 1, \"batch\": \"a\"}\n").unwrap();
+        let json_options = JsonImportOptions {
+            create_table: true,
+            set_columns: vec![setting("batch=b"), setting("source={{path}}")],
+            ..Default::default()
+        };
+        let json = json_path.to_str().unwrap();
+        import_json_to_table(&mut vapor_db.connection, json, "set_json", &json_options).unwrap();
+        let (batch, source): (String, String) = vapor_db
+            .connection
+            .query_row("SELECT batch, source FROM set_json", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!(batch, "b");
+        assert_eq!(source, json);
+    }
+
+    #[test]
+    fn test_import_csv_quotes_identifiers() {
+        let temp_db = NamedTempFile::new().unwrap();
+        let mut vapor_db = VaporDB::create(temp_db.path()).unwrap();
+        vapor_db
+            .execute(r#"CREATE TABLE "order lines" ("say ""hi""" TEXT)"#)
+            .unwrap();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let csv_path = temp_dir.path().join("quoted.csv");
+        std::fs::write(&csv_path, "\"say \"\"hi\"\"\"\nhello\n").unwrap();
+
+        import_csv_to_table(
+            &mut vapor_db.connection,
+            csv_path.to_str().unwrap(),
+            "order lines",
+        )
+        .unwrap();
+        let value: String = vapor_db
+            .connection
+            .query_row(r#"SELECT "say ""hi""" FROM "order lines""#, [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(value, "hello");
+    }
+
+    #[test]
+    fn test_import_no_header_set_columns() {
+        let temp_db = NamedTempFile::new().unwrap();
+        let mut vapor_db = VaporDB::create(temp_db.path()).unwrap();
+        vapor_db
+            .execute("CREATE TABLE headless (id INTEGER, source TEXT, name TEXT)")
+            .unwrap();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let csv_path = temp_dir.path().join("rows.csv");
+        std::fs::write(&csv_path, "1,alice\n2,bob\n").unwrap();
+
+        // Fields fill the columns that aren't set, in table order
+        let options = CsvImportOptions {
+            has_header: false,
+            set_columns: vec![ColumnSetting::parse("source={{filename}}").unwrap()],
+            ..Default::default()
+        };
+        let report = import_csv_to_table_with_options(
+            &mut vapor_db.connection,
+            csv_path.to_str().unwrap(),
+            "headless",
+            &options,
+        )
+        .unwrap();
+        assert_eq!(report.imported, 2);
+        let rows: Vec<(i64, String, String)> = vapor_db
+            .connection
+            .prepare("SELECT id, source, name FROM headless ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            [
+                (1, "rows.csv".to_string(), "alice".to_string()),
+                (2, "rows.csv".to_string(), "bob".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compressed_csv_export() {
+        use std::io::Read;
+
+        let temp_db = NamedTempFile::new().unwrap();
+        let vapor_db = VaporDB::create(temp_db.path()).unwrap();
+        vapor_db
+            .execute("CREATE TABLE gz_test (id INTEGER PRIMARY KEY, name TEXT)")
+            .unwrap();
+        vapor_db
+            .execute("INSERT INTO gz_test (name) VALUES ('alpha'), ('beta')")
+            .unwrap();
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let gz_path = temp_dir.path().join("out.csv.gz");
+        export_to_csv(
+            &vapor_db.connection,
+            "SELECT name FROM gz_test ORDER BY id",
+            gz_path.to_str().unwrap(),
+        )
+        .unwrap();
+
+        let mut text = String::new();
+        flate2::read::GzDecoder::new(std::fs::File::open(&gz_path).unwrap())
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(text, "name\nalpha\nbeta\n");
+
+        let zst_path = temp_dir.path().join("out.csv");
+        let options = ExportOptions {
+            compression: Some(Compression::Zstd),
+            ..Default::default()
+        };
+        export_query_with_options(
+            &vapor_db.connection,
+            "SELECT name FROM gz_test ORDER BY id",
+            zst_path.to_str().unwrap(),
+            None,
+            &options,
+        )
+        .unwrap();
+        let decoded = zstd::decode_all(std::fs::File::open(&zst_path).unwrap()).unwrap();
+        assert_eq!(decoded, b"name\nalpha\nbeta\n");
+    }
+
+    #[test]
+    fn test_resume_interrupted_csv_export() {
+        use crate::export::ExportCheckpoint;
+
+        let temp_db = NamedTempFile::new().unwrap();
+        let vapor_db = VaporDB::create(temp_db.path()).unwrap();
+        vapor_db
+            .connection
+            .execute_batch(
+                "CREATE TABLE big (id INTEGER PRIMARY KEY);
+                 WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 25000)
+                 INSERT INTO big SELECT i FROM n;",
+            )
+            .unwrap();
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let out = temp_dir.path().join("big.csv");
+        let out = out.to_str().unwrap();
+        let query = "SELECT id FROM big ORDER BY id";
+        export_query_with_options(&vapor_db.connection, query, out, None, &Default::default())
+            .unwrap();
+        let complete = std::fs::read_to_string(out).unwrap();
+        assert!(ExportCheckpoint::load(out).unwrap().is_none());
+
+        // Simulate an export killed after its first checkpoint, mid-way through a row
+        let bytes = complete
+            .lines()
+            .take(10_001)
+            .map(|l| l.len() + 1)
+            .sum::<usize>();
+        std::fs::write(out, format!("{}100", &complete[..bytes])).unwrap();
+        let checkpoint = ExportCheckpoint {
+            query: query.to_string(),
+            rows: 10_000,
+            skipped: 0,
+            bytes: bytes as u64,
+        };
+        std::fs::write(
+            ExportCheckpoint::path_for(out),
+            serde_json::to_string(&checkpoint).unwrap(),
+        )
+        .unwrap();
+
+        let options = ExportOptions {
+            resume: true,
+            ..Default::default()
+        };
+        let rows =
+            export_query_with_options(&vapor_db.connection, query, out, None, &options).unwrap();
+        assert_eq!(rows, 25_000);
+        assert_eq!(std::fs::read_to_string(out).unwrap(), complete);
+        assert!(ExportCheckpoint::load(out).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_resume_csv_export_after_skipped_row() {
+        use crate::export::ExportCheckpoint;
+
+        let temp_db = NamedTempFile::new().unwrap();
+        let vapor_db = VaporDB::create(temp_db.path()).unwrap();
+        vapor_db
+            .connection
+            .execute_batch(
+                "CREATE TABLE big (id INTEGER PRIMARY KEY);
+                 WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 25000)
+                 INSERT INTO big SELECT i FROM n;",
+            )
+            .unwrap();
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let out = temp_dir.path().join("big.csv");
+        let out = out.to_str().unwrap();
+        let query = "SELECT id FROM big ORDER BY id";
+
+        // Simulate an export killed after its first checkpoint, which had skipped row 5000
+        // as an error: 10,000 rows written from the first 10,001 rows of the query
+        let written: String = std::iter::once("id".to_string())
+            .chain((1..=10_001).filter(|&i| i != 5000).map(|i| i.to_string()))
+            .map(|line| line + "\n")
+            .collect();
+        let interrupt = || {
+            std::fs::write(out, &written).unwrap();
+            let checkpoint = ExportCheckpoint {
+                query: query.to_string(),
+                rows: 10_000,
+                skipped: 1,
+                bytes: written.len() as u64,
+            };
+            std::fs::write(
+                ExportCheckpoint::path_for(out),
+                serde_json::to_string(&checkpoint).unwrap(),
+            )
+            .unwrap();
+        };
+
+        let options = ExportOptions {
+            resume: true,
+            ..Default::default()
+        };
+        let rest: String = (10_002..=25_000).map(|i| format!("{}\n", i)).collect();
+        interrupt();
+        export_to_csv_with_options(&vapor_db.connection, query, out, &options).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(out).unwrap(),
+            format!("{}{}", written, rest)
+        );
+        assert!(ExportCheckpoint::load(out).unwrap().is_none());
+
+        // The registry's csv format resumes through the same path
+        interrupt();
+        let rows =
+            export_query_with_options(&vapor_db.connection, query, out, Some("csv"), &options)
+                .unwrap();
+        assert_eq!(rows, 24_999);
+        assert_eq!(
+            std::fs::read_to_string(out).unwrap(),
+            format!("{}{}", written, rest)
+        );
+        assert!(ExportCheckpoint::load(out).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_vapor_db_with_options() {
+        let temp_db = NamedTempFile::new().unwrap();
+        let db_path = temp_db.path();
+
+        let vapor_db = VaporDB::create(db_path).unwrap();
+        vapor_db
+            .execute("CREATE TABLE options_test (id INTEGER PRIMARY KEY, data TEXT)")
+            .unwrap();
+        vapor_db
+            .execute("INSERT INTO options_test (data) VALUES ('test1'), ('test2'), ('test3')")
+            .unwrap();
+
+        // Test execute with custom options
+        let options = QueryOptions {
+            format: OutputFormat::Json,
+            show_timing: true,
+            max_rows: Some(2),
+            quiet: false,
+        };
+        vapor_db
+            .execute_with_options("SELECT * FROM options_test", &options)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_transaction_rollback() {
+        let temp_db = NamedTempFile::new().unwrap();
+        let db_path = temp_db.path();
+
+        let vapor_db = VaporDB::create(db_path).unwrap();
+        vapor_db
+            .execute("CREATE TABLE rollback_test (id INTEGER PRIMARY KEY, name TEXT)")
+            .unwrap();
+        vapor_db
+            .execute("INSERT INTO rollback_test (name) VALUES ('initial')")
+            .unwrap();
+
+        // Begin transaction and insert data
+        vapor_db.begin_transaction().unwrap();
+        vapor_db
+            .execute("INSERT INTO rollback_test (name) VALUES ('transactional')")
             .unwrap();
 
         // Rollback the transaction
@@ -572,6 +2518,35 @@ mod tests {
         vapor_db.show_database_info().unwrap();
     }
 
+    #[test]
+    fn test_attached_databases() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE users (id INTEGER);
+             ATTACH DATABASE ':memory:' AS archive;
+             CREATE TABLE archive.\"old users\" (id INTEGER, name TEXT);
+             INSERT INTO archive.\"old users\" VALUES (1, 'Ann');",
+        )
+        .unwrap();
+
+        let attached = db::attached_databases(&conn).unwrap();
+        assert_eq!(attached, vec![("archive".to_string(), String::new())]);
+        assert_eq!(
+            db::get_all_table_names(&conn).unwrap(),
+            vec!["users", "archive.old users"]
+        );
+        assert_eq!(
+            db::split_table_name(&conn, "archive.old users").unwrap(),
+            ("archive".to_string(), "old users".to_string())
+        );
+        assert_eq!(
+            db::split_table_name(&conn, "v1.2").unwrap(),
+            ("main".to_string(), "v1.2".to_string())
+        );
+        show_table_schema(&conn, "archive.old users").unwrap();
+        show_all_schemas(&conn).unwrap();
+    }
+
     #[test]
     fn test_bookmark_operations() {
         let temp_db = NamedTempFile::new().unwrap();
@@ -705,6 +2680,21 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_schema_version_check() {
+        let temp_db = NamedTempFile::new().unwrap();
+        let vapor_db = VaporDB::create(temp_db.path()).unwrap();
+        let conn = &vapor_db.connection;
+
+        assert_eq!(schema_version(conn).unwrap(), 0);
+        set_schema_version(conn, 3).unwrap();
+        assert_eq!(schema_version(conn).unwrap(), 3);
+
+        assert!(check_schema_version(conn, 3).is_ok());
+        let err = check_schema_version(conn, 4).unwrap_err();
+        assert!(err.to_string().contains("version 3, but version 4"));
+    }
+
     #[test]
     fn test_integration_workflow() {
         let temp_db = NamedTempFile::new().unwrap();
@@ -763,4 +2753,55 @@ mod tests {
             std::env::remove_var("HOME");
         }
     }
+
+    #[test]
+    fn test_shared_vapor_db() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<SharedVaporDB>();
+
+        let dir = tempfile::tempdir().unwrap();
+        let db = VaporDB::create(dir.path().join("shared.db"))
+            .unwrap()
+            .into_shared();
+        db.with(|db| db.connection.execute_batch("CREATE TABLE hits (n INTEGER)"))
+            .unwrap();
+
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let db = db.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..25 {
+                        db.with(|db| db.connection.execute("INSERT INTO hits VALUES (1)", []))
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        // A panic inside a transaction rolls it back and leaves the handle usable
+        let panicking = db.clone();
+        let result = std::thread::spawn(move || {
+            panicking.with(|db| {
+                db.begin_transaction().unwrap();
+                db.connection.execute("DELETE FROM hits", []).unwrap();
+                panic!("handler failed");
+            })
+        })
+        .join();
+        assert!(result.is_err());
+        let count: i64 = db
+            .with(|db| {
+                assert!(!db.is_transaction_active());
+                db.connection
+                    .query_row("SELECT COUNT(*) FROM hits", [], |row| row.get(0))
+            })
+            .unwrap();
+        assert_eq!(count, 100);
+
+        let db = db.try_into_inner().ok().unwrap();
+        assert!(db.db_path.ends_with("shared.db"));
+    }
 }
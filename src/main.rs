@@ -10,24 +10,73 @@
 //! gracefully, providing context and suggestions to the user.
 
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+use std::path::{Path, PathBuf};
 use std::process;
 
 use vapor_cli::{
-    db::{connect_database, create_table, init_database, list_tables},
-    populate::populate_database,
-    repl::repl_mode,
+    backend::{is_remote_target, open_backend, Backend},
+    compression::Compression,
+    db::{
+        backup_database, check_schema_version, connect_database, copy_database,
+        create_table_with_options, init_database, list_tables, print_backup_progress,
+        run_maintenance, ConnectionOptions, CreateTableOptions, GeneratedColumn, Maintenance,
+    },
+    diff::{diff_schema_files, diff_table_files, DataDiffOptions, DEFAULT_DIFF_ROWS},
+    display::{print_records, OutputFormat, QueryOptions},
+    export::{
+        export_database_to_csv_dir, import_csv_to_table_with_options, parse_csv_char,
+        CsvImportOptions, ExportOptions, LineEnding, TextEncoding,
+    },
+    foreach::{expand_globs, federate_query, print_foreach_summary, run_foreach, ForeachOptions},
+    formats::{
+        export_backend_query, export_query_with_options, import_file_with_options, registry,
+        table_select_query, FileImportOptions,
+    },
+    import::{
+        import_fixed_width, import_json_to_table, import_log, import_xml_to_table, is_json_path,
+        is_xml_path, parse_fixed_width_spec, ColumnSetting, ConflictPolicy, ImportAudit,
+        ImportReport, JsonImportOptions, ReportFormat, XmlImportOptions,
+    },
+    integrity::{check_integrity, recover_database, DEFAULT_MAX_ERRORS},
+    notes::notes_select_query,
+    populate::populate_database_with_tuning,
+    progress::set_progress_sink,
+    repl::{command_mode, repl_mode, repl_mode_with_options, ReplOptions},
+    replica::{verify_replica_files, VerifyOptions, DEFAULT_SAMPLE_ROWS},
+    replication::{parse_timestamp, plan_restore, replicate, restore_database, ReplicateOptions},
     shell::{shell_mode, ShellAction},
+    sniff::sniff_csv_file,
+    templates::init_database_from_template,
+    tuning::{parse_size, TempStore, Tuning},
 };
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 #[command(propagate_version = true)]
 struct Cli {
+    /// Emit JSON progress events for long-running operations to a file descriptor or file
+    #[arg(long, global = true, value_name = "FD|FILE")]
+    progress_json: Option<String>,
+
+    /// Refuse to run unless the database's schema version (user_version) is N
+    #[arg(long, global = true, value_name = "N")]
+    require_schema_version: Option<i32>,
+
+    /// Wait up to MS milliseconds for other connections' locks instead of failing at once
+    #[arg(long, global = true, value_name = "MS")]
+    busy_timeout: Option<u64>,
+
+    /// Open databases read-only, so statements that write fail
+    #[arg(long, global = true)]
+    read_only: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+// Parsed once at startup, so the size of the `Import` variant doesn't matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Commands {
     /// Initialize a new SQLite database
@@ -35,6 +84,13 @@ enum Commands {
         /// Name of the database file
         #[arg(short, long)]
         name: String,
+        /// Copy the schema of a template: a database file, a template saved in
+        /// ~/.vapor/templates, or a preset (users, blog, inventory)
+        #[arg(short, long)]
+        template: Option<String>,
+        /// Also copy the template's rows (or a preset's sample data)
+        #[arg(long, requires = "template")]
+        with_data: bool,
     },
     /// Connect to an existing SQLite database
     Connect {
@@ -54,24 +110,346 @@ enum Commands {
         /// Example: "id INTEGER PRIMARY KEY, name TEXT, age INTEGER"
         #[arg(short, long)]
         columns: String,
+        /// Create a STRICT table: column types must be INT, INTEGER, REAL, TEXT, BLOB, or
+        /// ANY, and values of the wrong type are rejected
+        #[arg(long)]
+        strict: bool,
+        /// Add a generated column, as "NAME [TYPE] = EXPR [STORED|VIRTUAL]" (repeatable)
+        /// Example: "total REAL = price * quantity STORED"
+        #[arg(long = "generated", value_name = "COLUMN")]
+        generated: Vec<String>,
+        /// Add a CHECK constraint on the table (repeatable), e.g. "price >= 0"
+        #[arg(long = "check", value_name = "EXPR")]
+        checks: Vec<String>,
     },
     /// List all tables in the connected database
     ListTables {
-        /// Path to the database file
+        /// Path to the database file, or a libsql:// / https:// URL (`remote` feature)
         #[arg(short, long)]
         db_path: String,
     },
     /// Start an interactive SQL REPL (Read-Eval-Print Loop)
     Repl {
-        /// Path to the database file
+        /// Path to the database file, or a libsql:// / https:// URL (`remote` feature)
         #[arg(short, long)]
         db_path: String,
+        /// SQL script to run before the session starts
+        #[arg(long)]
+        init: Option<String>,
+        /// Don't run the startup file (~/.vapor/vaporrc)
+        #[arg(long)]
+        no_rc: bool,
+        /// Run this SQL and exit instead of starting an interactive session
+        #[arg(short = 'c', long)]
+        command: Option<String>,
+        /// Output format for --command: table, json, csv, or plain (default: table in a terminal, plain otherwise)
+        #[arg(long, requires = "command")]
+        format: Option<String>,
     },
     /// Populate the database with a large amount of data for testing
     Populate {
         /// Path to the database file
         #[arg(short, long)]
         db_path: String,
+        #[command(flatten)]
+        tuning: TuningArgs,
+    },
+    /// Export a table, or selected columns and rows of it, to a file
+    Export {
+        /// Path to the database file, or a libsql:// / https:// URL (`remote` feature)
+        #[arg(short, long)]
+        db_path: String,
+        /// Name of the table to export
+        #[arg(short, long)]
+        table: String,
+        /// Comma-separated list of columns to export (default: all columns)
+        #[arg(short, long, value_delimiter = ',')]
+        columns: Vec<String>,
+        /// SQL condition selecting the rows to export, e.g. "age > 30"
+        #[arg(short = 'w', long = "where", value_name = "CONDITION")]
+        filter: Option<String>,
+        /// Output format: csv, json, xlsx, xml, or avro/arrow when built with those
+        /// features (default: from the file extension, falling back to CSV)
+        #[arg(short, long)]
+        format: Option<String>,
+        /// Path to the output file
+        #[arg(short, long)]
+        out: String,
+        /// Compress the output (gzip or zstd)
+        #[arg(long)]
+        compress: Option<String>,
+        /// Continue an interrupted CSV export from its checkpoint instead of starting over
+        #[arg(long)]
+        resume: bool,
+        /// Add a _notes column with the notes attached to each row (see .note in the REPL)
+        #[arg(long)]
+        with_notes: bool,
+        /// Start a CSV export with a UTF-8 byte order mark, so Excel detects the encoding
+        #[arg(long)]
+        bom: bool,
+        #[command(flatten)]
+        tuning: TuningArgs,
+    },
+    /// Export every table to a directory of CSV files with a manifest
+    ExportAll {
+        /// Path to the database file
+        #[arg(short, long)]
+        db_path: String,
+        /// Directory to write the CSV files into (created if missing)
+        #[arg(short = 'o', long)]
+        dir: String,
+        #[command(flatten)]
+        tuning: TuningArgs,
+    },
+    /// Compare a database with a replica and report any divergence
+    VerifyReplica {
+        /// Path to the primary database file
+        #[arg(short, long)]
+        primary: String,
+        /// Path to the replica database file
+        #[arg(short, long)]
+        replica: String,
+        /// Rows per table to include in the checksum (0 checksums every row)
+        #[arg(long, default_value_t = DEFAULT_SAMPLE_ROWS)]
+        sample: usize,
+    },
+    /// Compare the schemas of two databases: tables, columns, indexes, triggers, and views
+    SchemaDiff {
+        /// Path to the database to compare from
+        #[arg(short, long)]
+        left: String,
+        /// Path to the database to compare to
+        #[arg(short, long)]
+        right: String,
+        /// Print the statements that make LEFT's schema match RIGHT's instead of the diff
+        #[arg(long)]
+        sql: bool,
+    },
+    /// Compare the rows of two tables by primary key, in one database or two
+    DataDiff {
+        /// Path to the database with the table to compare from
+        #[arg(short, long)]
+        left: String,
+        /// Path to the database with the table to compare to (defaults to LEFT)
+        #[arg(short, long)]
+        right: Option<String>,
+        /// The table to compare
+        #[arg(short, long)]
+        table: String,
+        /// The table in RIGHT to compare with, if its name differs
+        #[arg(long, value_name = "TABLE")]
+        right_table: Option<String>,
+        /// Rows of each kind (only left, only right, changed) to list (0 lists every row)
+        #[arg(long, default_value_t = DEFAULT_DIFF_ROWS)]
+        limit: usize,
+        /// Print the DELETE, UPDATE, and INSERT statements that make LEFT's rows match RIGHT's instead of the diff
+        #[arg(long)]
+        sql: bool,
+    },
+    /// Continuously copy a database's WAL to a directory for point-in-time restore (experimental)
+    Replicate {
+        /// Path to the database file
+        #[arg(short, long)]
+        db_path: String,
+        /// Directory to replicate into (created if missing)
+        #[arg(short, long)]
+        target: String,
+        /// Seconds between checks for new WAL frames
+        #[arg(long, default_value_t = 1)]
+        interval: u64,
+        /// Copy once and exit instead of running until interrupted
+        #[arg(long)]
+        once: bool,
+    },
+    /// Back up a database to a file with SQLite's online backup API, safe while it is in use
+    Backup {
+        /// Path to the database file
+        #[arg(short, long)]
+        db_path: String,
+        /// Path of the backup file (replaced if it exists)
+        #[arg(short, long)]
+        out: String,
+    },
+    /// Check a database for corruption with PRAGMA integrity_check
+    Check {
+        /// Path to the database file
+        #[arg(short, long)]
+        db_path: String,
+        /// Run the faster quick_check, which doesn't check indexes against their tables
+        #[arg(long)]
+        quick: bool,
+        /// Stop after this many problems
+        #[arg(long, default_value_t = DEFAULT_MAX_ERRORS)]
+        max_errors: usize,
+    },
+    /// Salvage the readable schema and rows of a damaged database into a new file
+    Repair {
+        /// Path to the damaged database file
+        #[arg(short, long)]
+        db_path: String,
+        /// Path of the database to write the recovered data to
+        #[arg(short, long)]
+        out: String,
+        /// Replace the output file if it already exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Run VACUUM, ANALYZE, and REINDEX on a database, reporting timing and size
+    Maintain {
+        /// Path to the database file
+        #[arg(short, long)]
+        db_path: String,
+        /// Rebuild the database file without its free pages
+        #[arg(long)]
+        vacuum: bool,
+        /// Write a compacted copy of the database to FILE instead of rebuilding it in place
+        #[arg(long, value_name = "FILE", conflicts_with = "vacuum")]
+        vacuum_into: Option<String>,
+        /// Gather the statistics the query planner uses
+        #[arg(long)]
+        analyze: bool,
+        /// Rebuild every index
+        #[arg(long)]
+        reindex: bool,
+    },
+    /// Restore a database from a backup file, or a replication or backup directory
+    Restore {
+        /// Backup file, or replication or backup directory, to restore from
+        #[arg(short, long)]
+        from: String,
+        /// Path of the database file to create
+        #[arg(short, long)]
+        db_path: String,
+        /// Replace the database file if it already exists
+        #[arg(long)]
+        force: bool,
+        /// Point in time to restore to, e.g. 2024-05-01T12:00 (default: latest)
+        #[arg(short, long, visible_alias = "at")]
+        timestamp: Option<String>,
+        /// List the files that would be restored without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Copy a database to a new file and verify the copy's integrity
+    Clone {
+        /// Path to the database to copy
+        #[arg(short, long)]
+        src: String,
+        /// Path of the copy
+        #[arg(short, long)]
+        dest: String,
+        /// Replace the copy if it already exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Import a data file into a table
+    Import {
+        /// Path to the database file
+        #[arg(short, long)]
+        db_path: String,
+        /// Path to the file to import (may be .gz, .zst, or .zip compressed), or - for stdin
+        #[arg(short, long)]
+        file: String,
+        /// Name of the target table
+        #[arg(short, long)]
+        table: String,
+        /// Input format: csv, json, ndjson, xml, or arrow when built with that feature
+        /// (default: from the file extension, falling back to CSV)
+        #[arg(long)]
+        format: Option<String>,
+        /// Fixed-width column spec (e.g. "id:1-5,name:6-25") or a file containing one
+        #[arg(long, conflicts_with_all = ["pattern", "format"])]
+        spec: Option<String>,
+        /// Regex with named groups for line-based files such as logs
+        /// Example: '(?P<ts>\S+) (?P<level>\w+) (?P<msg>.*)'
+        #[arg(long, conflicts_with = "format")]
+        pattern: Option<String>,
+        /// XPath-like selector for record elements in XML input (e.g. "//customer")
+        #[arg(long, default_value = "/*/*")]
+        record_path: String,
+        /// Create the table from the inferred schema if it does not exist (JSON, XML, Arrow, and other registered formats)
+        #[arg(long)]
+        create_table: bool,
+        /// Detect the CSV dialect from the file; the dialect flags below override what is detected
+        #[arg(long)]
+        sniff: bool,
+        /// CSV field delimiter: a single character or tab, comma, semicolon, pipe, space [default: ,]
+        #[arg(long)]
+        delimiter: Option<String>,
+        /// CSV quote character [default: "]
+        #[arg(long)]
+        quote: Option<String>,
+        /// Character that escapes quotes inside quoted CSV fields, e.g. backslash (default: doubled quotes)
+        #[arg(long)]
+        escape: Option<String>,
+        /// CSV line endings: crlf (CRLF, LF, or CR), lf (LF only), or cr (CR only) [default: crlf]
+        #[arg(long)]
+        line_ending: Option<String>,
+        /// The CSV file has no header row; fields map to table columns by position
+        #[arg(long)]
+        no_header: bool,
+        /// CSV field value to import as NULL (e.g. "NA" or "\N")
+        #[arg(long, value_name = "STRING")]
+        null: Option<String>,
+        /// Number of leading lines to skip before the CSV header or data [default: 0]
+        #[arg(long, value_name = "N")]
+        skip: Option<usize>,
+        /// CSV text encoding (utf-8, latin-1, windows-1252, or shift-jis), transcoded to UTF-8 [default: utf-8]
+        #[arg(long)]
+        encoding: Option<String>,
+        /// Commit CSV rows and JSON documents in chunks of N and report progress
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+        batch_size: Option<u64>,
+        /// Continue a failed chunked import after its last committed chunk
+        #[arg(long, requires = "batch_size")]
+        resume: bool,
+        /// What to do with CSV, JSON, or XML rows that collide with an existing row (fail, ignore, or replace) [default: fail]
+        #[arg(long, value_name = "POLICY")]
+        on_conflict: Option<String>,
+        /// Fill a column for every CSV, JSON, or XML row (repeatable); {{filename}} and {{path}} name the input, and function calls such as now() are SQL
+        #[arg(long = "set", value_name = "COLUMN=VALUE")]
+        set_columns: Vec<String>,
+        /// Skip CSV rows that fail to parse or insert instead of aborting
+        #[arg(long)]
+        continue_on_error: bool,
+        /// Write skipped CSV rows to this file (implies --continue-on-error)
+        #[arg(long, value_name = "FILE")]
+        reject: Option<String>,
+        /// Write a report of the import (json or markdown) next to the data file
+        #[arg(long, value_name = "FORMAT")]
+        report: Option<String>,
+        #[command(flatten)]
+        tuning: TuningArgs,
+    },
+    /// Run a statement or script against every database file matching a glob
+    Foreach {
+        /// Glob pattern for the database files, e.g. 'data/*.db' (may be repeated)
+        #[arg(short, long, required = true)]
+        glob: Vec<String>,
+        /// SQL to run against each database
+        #[arg(short, long, conflicts_with = "file", required_unless_present = "file")]
+        sql: Option<String>,
+        /// SQL script file to run against each database
+        #[arg(short, long)]
+        file: Option<String>,
+        /// Number of databases to work on at once
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+        parallel: u64,
+    },
+    /// Run a query against every database file matching a glob and combine the results
+    Federate {
+        /// Glob pattern for the database files, e.g. 'shards/*.db' (may be repeated)
+        #[arg(short, long, required = true)]
+        glob: Vec<String>,
+        /// Query to run against each database; every file must return the same columns
+        #[arg(short, long)]
+        sql: String,
+        /// Output format: table, json, csv, or plain
+        #[arg(long, default_value = "table")]
+        format: String,
+        /// Name of the added column that holds each row's database file
+        #[arg(long, default_value = "source")]
+        source_column: String,
     },
     /// Start shell mode with database context
     Shell {
@@ -79,6 +457,62 @@ enum Commands {
         #[arg(short, long)]
         db_path: String,
     },
+    /// Copy all tables from a SQLite database into a DuckDB database file
+    #[cfg(feature = "duckdb")]
+    ToDuckdb {
+        /// Path to the SQLite database file
+        #[arg(short, long)]
+        db_path: String,
+        /// Path to the DuckDB database file (created if missing)
+        #[arg(short, long)]
+        output: String,
+    },
+    /// Copy all tables from a DuckDB database file into a SQLite database
+    #[cfg(feature = "duckdb")]
+    FromDuckdb {
+        /// Path to the DuckDB database file
+        #[arg(short, long)]
+        input: String,
+        /// Path to the SQLite database file (created if missing)
+        #[arg(short, long)]
+        db_path: String,
+    },
+}
+
+/// SQLite settings for the bulk commands (populate, import, export, export-all).
+#[derive(Args)]
+struct TuningArgs {
+    /// Settings preset: bulk (more memory, faster), low-memory, or default (SQLite's own)
+    #[arg(long, value_name = "PRESET")]
+    tune: Option<String>,
+    /// Page cache size, e.g. 64MB (overrides the preset)
+    #[arg(long, value_name = "SIZE")]
+    cache_size: Option<String>,
+    /// Memory-mapped I/O size, e.g. 256MB, or 0 to turn it off (overrides the preset)
+    #[arg(long, value_name = "SIZE")]
+    mmap_size: Option<String>,
+    /// Where temporary tables and indexes go: memory, file, or default (overrides the preset)
+    #[arg(long, value_name = "STORE")]
+    temp_store: Option<String>,
+}
+
+impl TuningArgs {
+    fn tuning(&self) -> Result<Tuning> {
+        let mut tuning = match &self.tune {
+            Some(preset) => Tuning::preset(preset)?,
+            None => Tuning::default(),
+        };
+        if let Some(size) = &self.cache_size {
+            tuning.cache_size = Some(parse_size(size)?);
+        }
+        if let Some(size) = &self.mmap_size {
+            tuning.mmap_size = Some(parse_size(size)?);
+        }
+        if let Some(store) = &self.temp_store {
+            tuning.temp_store = Some(TempStore::parse(store)?);
+        }
+        Ok(tuning)
+    }
 }
 
 fn main() {
@@ -100,14 +534,62 @@ fn main() {
     }
 }
 
+/// Returns the existing database a command works on, for `--require-schema-version`.
+fn command_database(command: &Commands) -> Option<&str> {
+    match command {
+        Commands::Connect { path } => Some(path),
+        Commands::CreateTable { db_path, .. }
+        | Commands::ListTables { db_path }
+        | Commands::Repl { db_path, .. }
+        | Commands::Populate { db_path, .. }
+        | Commands::Export { db_path, .. }
+        | Commands::ExportAll { db_path, .. }
+        | Commands::Replicate { db_path, .. }
+        | Commands::Backup { db_path, .. }
+        | Commands::Maintain { db_path, .. }
+        | Commands::Check { db_path, .. }
+        | Commands::Import { db_path, .. }
+        | Commands::Shell { db_path } => Some(db_path),
+        _ => None,
+    }
+}
+
 fn run() -> Result<()> {
     let cli = Cli::parse();
 
+    if let Some(target) = &cli.progress_json {
+        set_progress_sink(target)
+            .with_context(|| format!("Failed to set up progress output '{}'", target))?;
+    }
+
+    ConnectionOptions::set_current(ConnectionOptions {
+        busy_timeout: cli.busy_timeout.map(std::time::Duration::from_millis),
+        read_only: cli.read_only,
+        ..ConnectionOptions::default()
+    });
+
+    if let Some(required) = cli.require_schema_version {
+        if let Some(db_path) = command_database(&cli.command) {
+            if is_remote_target(db_path) || Path::new(db_path).exists() {
+                check_schema_version(open_backend(db_path)?.as_ref(), required)?;
+            }
+        }
+    }
+
     match &cli.command {
-        Commands::Init { name } => {
+        Commands::Init {
+            name,
+            template,
+            with_data,
+        } => {
             validate_database_name(name)?;
-            init_database(name)
-                .with_context(|| format!("Failed to initialize database '{}'", name))?;
+            match template {
+                Some(template) => {
+                    init_database_from_template(name, template, *with_data)?;
+                }
+                None => init_database(name)
+                    .with_context(|| format!("Failed to initialize database '{}'", name))?,
+            }
         }
         Commands::Connect { path } => {
             validate_database_path(path)?;
@@ -118,11 +600,22 @@ fn run() -> Result<()> {
             db_path,
             name,
             columns,
+            strict,
+            generated,
+            checks,
         } => {
             validate_database_path(db_path)?;
             validate_table_name(name)?;
             validate_column_definition(columns)?;
-            create_table(db_path, name, columns).with_context(|| {
+            let options = CreateTableOptions {
+                strict: *strict,
+                generated: generated
+                    .iter()
+                    .map(|spec| GeneratedColumn::parse(spec))
+                    .collect::<Result<_>>()?,
+                checks: checks.clone(),
+            };
+            create_table_with_options(db_path, name, columns, &options).with_context(|| {
                 format!(
                     "Failed to create table '{}' in database '{}'",
                     name, db_path
@@ -134,16 +627,571 @@ fn run() -> Result<()> {
             list_tables(db_path)
                 .with_context(|| format!("Failed to list tables in database '{}'", db_path))?;
         }
-        Commands::Repl { db_path } => {
+        Commands::Repl {
+            db_path,
+            init,
+            command: Some(sql),
+            format,
+            ..
+        } => {
+            validate_database_path(db_path)?;
+            let format = match format {
+                Some(name) => OutputFormat::parse(name)?,
+                None => QueryOptions::for_stdout().format,
+            };
+            command_mode(db_path, sql, init.as_deref().map(Path::new), format)
+                .with_context(|| format!("Command failed for database '{}'", db_path))?;
+        }
+        Commands::Repl {
+            db_path,
+            init,
+            no_rc,
+            ..
+        } => {
             validate_database_path(db_path)?;
-            repl_mode(db_path)
+            let options = ReplOptions {
+                init: init.as_ref().map(PathBuf::from),
+                rc_file: !no_rc,
+            };
+            repl_mode_with_options(db_path, &options)
                 .with_context(|| format!("REPL session failed for database '{}'", db_path))?;
         }
-        Commands::Populate { db_path } => {
+        Commands::Populate { db_path, tuning } => {
             validate_database_path(db_path)?;
-            populate_database(db_path, None)
+            populate_database_with_tuning(db_path, None, &tuning.tuning()?)
                 .with_context(|| format!("Failed to populate database '{}'", db_path))?;
         }
+        #[cfg(feature = "duckdb")]
+        Commands::ToDuckdb { db_path, output } => {
+            validate_database_path(db_path)?;
+            if !std::path::Path::new(db_path).exists() {
+                anyhow::bail!("Database '{}' not found", db_path);
+            }
+            let conn = ConnectionOptions::current()
+                .open(db_path)
+                .with_context(|| format!("Failed to open database '{}'", db_path))?;
+            let copied = vapor_cli::duckdb_interop::copy_sqlite_to_duckdb(&conn, output)
+                .with_context(|| format!("Failed to copy '{}' to DuckDB '{}'", db_path, output))?;
+            print_copied_tables(&copied);
+        }
+        #[cfg(feature = "duckdb")]
+        Commands::FromDuckdb { input, db_path } => {
+            validate_database_path(db_path)?;
+            let mut conn = ConnectionOptions::current()
+                .open(db_path)
+                .with_context(|| format!("Failed to open database '{}'", db_path))?;
+            let copied = vapor_cli::duckdb_interop::copy_duckdb_to_sqlite(input, &mut conn)
+                .with_context(|| format!("Failed to copy DuckDB '{}' to '{}'", input, db_path))?;
+            print_copied_tables(&copied);
+        }
+        Commands::Export {
+            db_path,
+            table,
+            columns,
+            filter,
+            format,
+            out,
+            compress,
+            resume,
+            with_notes,
+            bom,
+            tuning,
+        } => {
+            validate_database_path(db_path)?;
+            let table_query = |backend: &dyn Backend| -> Result<String> {
+                if *with_notes {
+                    notes_select_query(backend, table, columns, filter.as_deref())
+                } else {
+                    Ok(table_select_query(table, columns, filter.as_deref()))
+                }
+            };
+            let options = ExportOptions {
+                compression: compress.as_deref().map(Compression::parse).transpose()?,
+                resume: *resume,
+                bom: *bom,
+                ..Default::default()
+            };
+            // Unknown extensions fall back to CSV, matching `.export` in the REPL
+            let format = match format.as_deref() {
+                Some(name) => Some(name),
+                None if registry().for_path(out).is_none() => Some("csv"),
+                None => None,
+            };
+
+            if is_remote_target(db_path) {
+                if !tuning.tuning()?.is_default() {
+                    eprintln!("Warning: Tuning flags apply to local databases only; ignoring them");
+                }
+                let backend = open_backend(db_path)?;
+                let query = table_query(backend.as_ref())?;
+                export_backend_query(backend.as_ref(), &query, out, format, &options)
+                    .with_context(|| format!("Failed to export '{}' to '{}'", table, out))?;
+                return Ok(());
+            }
+            if !std::path::Path::new(db_path).exists() {
+                anyhow::bail!("Database '{}' not found", db_path);
+            }
+            let conn = ConnectionOptions::current()
+                .open(db_path)
+                .with_context(|| format!("Failed to open database '{}'", db_path))?;
+            tuning.tuning()?.apply(&conn)?;
+            let query = table_query(&conn)?;
+            export_query_with_options(&conn, &query, out, format, &options)
+                .with_context(|| format!("Failed to export '{}' to '{}'", table, out))?;
+        }
+        Commands::ExportAll {
+            db_path,
+            dir,
+            tuning,
+        } => {
+            validate_database_path(db_path)?;
+            if !std::path::Path::new(db_path).exists() {
+                anyhow::bail!("Database '{}' not found", db_path);
+            }
+            let conn = ConnectionOptions::current()
+                .open(db_path)
+                .with_context(|| format!("Failed to open database '{}'", db_path))?;
+            tuning.tuning()?.apply(&conn)?;
+            export_database_to_csv_dir(&conn, dir)
+                .with_context(|| format!("Failed to export '{}' to '{}'", db_path, dir))?;
+        }
+        Commands::VerifyReplica {
+            primary,
+            replica,
+            sample,
+        } => {
+            let options = VerifyOptions {
+                sample_rows: (*sample > 0).then_some(*sample),
+            };
+            let report = verify_replica_files(primary, replica, &options)
+                .with_context(|| format!("Failed to compare '{}' with '{}'", primary, replica))?;
+            report.print();
+            if !report.is_consistent() {
+                anyhow::bail!("Replica '{}' has diverged from '{}'", replica, primary);
+            }
+        }
+        Commands::SchemaDiff { left, right, sql } => {
+            let diff = diff_schema_files(left, right)
+                .with_context(|| format!("Failed to compare '{}' with '{}'", left, right))?;
+            if *sql {
+                diff.print_reconcile_statements();
+            } else {
+                diff.print(left, right);
+            }
+        }
+        Commands::DataDiff {
+            left,
+            right,
+            table,
+            right_table,
+            limit,
+            sql,
+        } => {
+            let right = right.as_deref().unwrap_or(left);
+            let right_table = right_table.as_deref().unwrap_or(table);
+            let options = DataDiffOptions {
+                // The statements need every row, not just the ones listed
+                limit: (*limit > 0 && !*sql).then_some(*limit),
+            };
+            let diff = diff_table_files(left, table, right, right_table, &options)
+                .with_context(|| format!("Failed to compare '{}' with '{}'", left, right))?;
+            if *sql {
+                diff.print_sync_statements();
+            } else {
+                diff.print(
+                    &format!("{}:{}", left, table),
+                    &format!("{}:{}", right, right_table),
+                );
+            }
+        }
+        Commands::Replicate {
+            db_path,
+            target,
+            interval,
+            once,
+        } => {
+            let options = ReplicateOptions {
+                interval: std::time::Duration::from_secs(*interval),
+                once: *once,
+            };
+            replicate(db_path, target, &options)
+                .with_context(|| format!("Failed to replicate '{}' to '{}'", db_path, target))?;
+        }
+        Commands::Backup { db_path, out } => {
+            validate_database_path(db_path)?;
+            backup_database(db_path, out, print_backup_progress)
+                .with_context(|| format!("Failed to back up '{}' to '{}'", db_path, out))?;
+            println!("Backed up '{}' to '{}'", db_path, out);
+        }
+        Commands::Check {
+            db_path,
+            quick,
+            max_errors,
+        } => {
+            validate_database_path(db_path)?;
+            if !Path::new(db_path).exists() {
+                anyhow::bail!("Database '{}' not found", db_path);
+            }
+            let conn = ConnectionOptions::current()
+                .open(db_path)
+                .with_context(|| format!("Failed to open database '{}'", db_path))?;
+            let report = check_integrity(&conn, *quick, *max_errors)?;
+            report.print();
+            if !report.is_ok() {
+                anyhow::bail!(
+                    "'{}' is damaged; 'vapor-cli repair --db-path {} --out FILE' salvages what can still be read",
+                    db_path,
+                    db_path
+                );
+            }
+        }
+        Commands::Repair {
+            db_path,
+            out,
+            force,
+        } => {
+            validate_database_path(db_path)?;
+            validate_database_path(out)?;
+            if !Path::new(db_path).exists() {
+                anyhow::bail!("Database '{}' not found", db_path);
+            }
+            let conn = ConnectionOptions::current()
+                .open(db_path)
+                .with_context(|| format!("Failed to open database '{}'", db_path))?;
+            if check_integrity(&conn, true, 1)?.is_ok() {
+                println!("No corruption found in '{}'; nothing to repair.", db_path);
+                return Ok(());
+            }
+            drop(conn);
+            let report = recover_database(db_path, out, *force)
+                .with_context(|| format!("Failed to recover '{}'", db_path))?;
+            println!("Recovered '{}' into '{}':", db_path, out);
+            report.print();
+            if !report.is_complete() {
+                println!("Some data could not be read; check the recovered database before relying on it.");
+            }
+        }
+        Commands::Maintain {
+            db_path,
+            vacuum,
+            vacuum_into,
+            analyze,
+            reindex,
+        } => {
+            validate_database_path(db_path)?;
+            let all = !(*vacuum || vacuum_into.is_some() || *analyze || *reindex);
+            let mut operations = Vec::new();
+            if *reindex || all {
+                operations.push(Maintenance::Reindex);
+            }
+            if *vacuum || vacuum_into.is_some() || all {
+                operations.push(Maintenance::Vacuum {
+                    into: vacuum_into.clone(),
+                });
+            }
+            if *analyze || all {
+                operations.push(Maintenance::Analyze);
+            }
+
+            if !Path::new(db_path).exists() {
+                anyhow::bail!("Database '{}' not found", db_path);
+            }
+            let conn = ConnectionOptions::current()
+                .open(db_path)
+                .with_context(|| format!("Failed to open database '{}'", db_path))?;
+            for operation in &operations {
+                run_maintenance(&conn, operation)?.print();
+            }
+        }
+        Commands::Restore {
+            from,
+            db_path,
+            force,
+            timestamp,
+            dry_run,
+        } => {
+            validate_database_path(db_path)?;
+            if Path::new(from).is_file() {
+                if timestamp.is_some() {
+                    anyhow::bail!(
+                        "--timestamp needs a replication or backup directory to restore from"
+                    );
+                }
+                if !*force && Path::new(db_path).exists() {
+                    anyhow::bail!("'{}' already exists; use --force to overwrite it", db_path);
+                }
+                if *dry_run {
+                    println!("Would restore '{}' from '{}'", db_path, from);
+                    return Ok(());
+                }
+                copy_database(from, db_path, *force, print_backup_progress)
+                    .with_context(|| format!("Failed to restore '{}' from '{}'", db_path, from))?;
+                println!("Restored '{}' from '{}'", db_path, from);
+                return Ok(());
+            }
+
+            let at = timestamp.as_deref().map(parse_timestamp).transpose()?;
+            let plan = plan_restore(from, at)?;
+            if !*force && Path::new(db_path).exists() {
+                anyhow::bail!("'{}' already exists; use --force to overwrite it", db_path);
+            }
+            if *dry_run {
+                println!("Would restore '{}' from:", db_path);
+                plan.print();
+                return Ok(());
+            }
+            // Restore next to the database so an existing file is only replaced on success
+            let temp = format!("{}.restore-tmp", db_path);
+            let _ = std::fs::remove_file(&temp);
+            restore_database(&plan, &temp)?;
+            std::fs::rename(&temp, db_path).with_context(|| {
+                format!("Failed to move the restored database to '{}'", db_path)
+            })?;
+            for suffix in ["-wal", "-shm", "-journal"] {
+                let _ = std::fs::remove_file(format!("{}{}", db_path, suffix));
+            }
+            println!(
+                "Restored '{}' from the snapshot taken {}{}",
+                db_path,
+                plan.snapshot_time.to_rfc3339(),
+                plan.restored_to
+                    .map(|t| format!(", replayed up to {}", t.to_rfc3339()))
+                    .unwrap_or_default()
+            );
+        }
+        Commands::Clone { src, dest, force } => {
+            validate_database_path(dest)?;
+            copy_database(src, dest, *force, print_backup_progress)
+                .with_context(|| format!("Failed to clone '{}' to '{}'", src, dest))?;
+            println!("Cloned '{}' to '{}' and verified its integrity", src, dest);
+        }
+        Commands::Import {
+            db_path,
+            file,
+            table,
+            format,
+            spec,
+            pattern,
+            record_path,
+            create_table,
+            sniff,
+            delimiter,
+            quote,
+            escape,
+            line_ending,
+            no_header,
+            null,
+            skip,
+            encoding,
+            batch_size,
+            resume,
+            on_conflict,
+            set_columns,
+            continue_on_error,
+            reject,
+            report,
+            tuning,
+        } => {
+            validate_database_path(db_path)?;
+            let report_format = report.as_deref().map(ReportFormat::parse).transpose()?;
+            if !std::path::Path::new(db_path).exists() {
+                anyhow::bail!("Database '{}' not found", db_path);
+            }
+            let mut conn = ConnectionOptions::current()
+                .open(db_path)
+                .with_context(|| format!("Failed to open database '{}'", db_path))?;
+            tuning.tuning()?.apply(&conn)?;
+            let conflict_policy = match on_conflict {
+                Some(policy) => ConflictPolicy::parse(policy)?,
+                None => ConflictPolicy::Fail,
+            };
+            let set_columns = set_columns
+                .iter()
+                .map(|setting| ColumnSetting::parse(setting))
+                .collect::<Result<Vec<_>>>()?;
+            let require_default_inserts = || -> Result<()> {
+                if on_conflict.is_some() || !set_columns.is_empty() {
+                    anyhow::bail!(
+                        "--on-conflict and --set are only supported for CSV, JSON, and XML imports"
+                    );
+                }
+                Ok(())
+            };
+
+            let started_at = chrono::Local::now();
+            let started = std::time::Instant::now();
+            let outcome = (|| -> Result<ImportReport> {
+                let report = if let Some(spec) = spec {
+                    require_default_inserts()?;
+                    let spec = if std::path::Path::new(spec).is_file() {
+                        std::fs::read_to_string(spec)
+                            .with_context(|| format!("Failed to read spec file '{}'", spec))?
+                    } else {
+                        spec.clone()
+                    };
+                    let imported = import_fixed_width(
+                        &mut conn,
+                        file,
+                        table,
+                        &parse_fixed_width_spec(&spec)?,
+                    )?;
+                    ImportReport {
+                        imported,
+                        ..Default::default()
+                    }
+                } else if let Some(pattern) = pattern {
+                    require_default_inserts()?;
+                    ImportReport {
+                        imported: import_log(&mut conn, file, table, pattern)?,
+                        ..Default::default()
+                    }
+                } else if matches!(format.as_deref(), Some("json" | "jsonl" | "ndjson"))
+                    || (format.is_none() && is_json_path(file))
+                {
+                    let options = JsonImportOptions {
+                        create_table: *create_table,
+                        batch_size: batch_size.map(|size| size as usize),
+                        resume: *resume,
+                        on_conflict: conflict_policy,
+                        set_columns: set_columns.clone(),
+                    };
+                    import_json_to_table(&mut conn, file, table, &options)?
+                } else if format.as_deref() == Some("xml")
+                    || (format.is_none() && is_xml_path(file))
+                {
+                    let options = XmlImportOptions {
+                        record_path: record_path.clone(),
+                        create_table: *create_table,
+                        on_conflict: conflict_policy,
+                        set_columns: set_columns.clone(),
+                    };
+                    import_xml_to_table(&mut conn, file, table, &options)?
+                } else if format.as_deref().is_some_and(|f| f != "csv")
+                    || registry().for_path(file).is_some_and(|f| f.name() != "csv")
+                {
+                    require_default_inserts()?;
+                    let options = FileImportOptions {
+                        create_table: *create_table,
+                    };
+                    ImportReport {
+                        imported: import_file_with_options(
+                            &mut conn,
+                            file,
+                            table,
+                            format.as_deref(),
+                            &options,
+                        )?,
+                        ..Default::default()
+                    }
+                } else {
+                    let mut options = CsvImportOptions {
+                        null_string: null.clone(),
+                        batch_size: batch_size.map(|size| size as usize),
+                        resume: *resume,
+                        on_conflict: conflict_policy,
+                        set_columns: set_columns.clone(),
+                        continue_on_error: *continue_on_error || reject.is_some(),
+                        reject_file: reject.clone(),
+                        ..Default::default()
+                    };
+                    if *sniff {
+                        let dialect = sniff_csv_file(file)?;
+                        println!("Detected CSV dialect: {}", dialect);
+                        dialect.apply_to(&mut options);
+                    }
+                    if let Some(delimiter) = delimiter {
+                        options.delimiter = parse_csv_char(delimiter)?;
+                    }
+                    if let Some(quote) = quote {
+                        options.quote = parse_csv_char(quote)?;
+                    }
+                    if let Some(escape) = escape {
+                        options.escape = Some(parse_csv_char(escape)?);
+                    }
+                    if let Some(line_ending) = line_ending {
+                        options.line_ending = LineEnding::parse(line_ending)?;
+                    }
+                    if *no_header {
+                        options.has_header = false;
+                    }
+                    if let Some(skip) = skip {
+                        options.skip_lines = *skip;
+                    }
+                    if let Some(encoding) = encoding {
+                        options.encoding = TextEncoding::parse(encoding)?;
+                    }
+                    let report = import_csv_to_table_with_options(&mut conn, file, table, &options)
+                        .with_context(|| format!("Failed to import '{}' into '{}'", file, table))?;
+                    println!("Successfully imported '{}' into '{}'", file, table);
+                    report
+                };
+                Ok(report)
+            })();
+
+            if let Some(format) = report_format {
+                let (report, error) = match &outcome {
+                    Ok(report) => (report.clone(), None),
+                    Err(e) => (ImportReport::default(), Some(format!("{:#}", e))),
+                };
+                let audit = ImportAudit {
+                    source: file.clone(),
+                    table: table.clone(),
+                    database: db_path.clone(),
+                    started_at: started_at.to_rfc3339(),
+                    duration_secs: started.elapsed().as_secs_f64(),
+                    error,
+                    report,
+                    command: std::env::args().collect(),
+                };
+                let path = audit.write(format)?;
+                println!("Wrote import report to '{}'", path);
+            }
+            outcome?;
+        }
+        Commands::Foreach {
+            glob,
+            sql,
+            file,
+            parallel,
+        } => {
+            let sql = match (sql, file) {
+                (Some(sql), _) => sql.clone(),
+                (None, Some(file)) => std::fs::read_to_string(file)
+                    .with_context(|| format!("Failed to read SQL script '{}'", file))?,
+                (None, None) => unreachable!("clap requires --sql or --file"),
+            };
+            let paths = expand_globs(glob)?;
+            let options = ForeachOptions {
+                parallel: *parallel as usize,
+            };
+            let results = run_foreach(&paths, &sql, &options);
+            print_foreach_summary(&results);
+            let failed = results.iter().filter(|r| r.outcome.is_err()).count();
+            if failed > 0 {
+                anyhow::bail!("{} of {} databases failed", failed, results.len());
+            }
+        }
+        Commands::Federate {
+            glob,
+            sql,
+            format,
+            source_column,
+        } => {
+            let format = OutputFormat::parse(format)?;
+            let paths = expand_globs(glob)?;
+            let result = federate_query(&paths, sql, source_column)?;
+            print_records(&format, &result.columns, &result.rows)?;
+            for (path, error) in &result.errors {
+                eprintln!("Warning: Query failed on '{}': {}", path, error);
+            }
+            if !result.errors.is_empty() {
+                anyhow::bail!(
+                    "The query failed on {} of {} databases",
+                    result.errors.len(),
+                    paths.len()
+                );
+            }
+        }
         Commands::Shell { db_path } => {
             validate_database_path(db_path)?;
             let shell_action = shell_mode(db_path)
@@ -181,6 +1229,14 @@ fn validate_database_name(name: &str) -> Result<()> {
     Ok(())
 }
 
+#[cfg(feature = "duckdb")]
+fn print_copied_tables(copied: &[(String, usize)]) {
+    for (table, rows) in copied {
+        println!("Copied table '{}' ({} rows)", table, rows);
+    }
+    println!("Copied {} tables", copied.len());
+}
+
 fn validate_database_path(path: &str) -> Result<()> {
     if path.trim().is_empty() {
         anyhow::bail!("Database path cannot be empty");
@@ -198,7 +1254,7 @@ fn validate_table_name(name: &str) -> Result<()> {
         anyhow::bail!("Table name cannot be empty");
     }
 
-    if !name.chars().next().unwrap_or('0').is_alphabetic() && name.chars().next() != Some('_') {
+    if !name.chars().next().unwrap_or('0').is_alphabetic() && !name.starts_with('_') {
         anyhow::bail!("Table name must start with a letter or underscore");
     }
 
@@ -284,3 +1340,14 @@ fn print_error_with_context(error: &anyhow::Error) {
 
     eprintln!("\nFor more help, use: vapor-cli --help");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn verify_cli() {
+        Cli::command().debug_assert();
+    }
+}
@@ -0,0 +1,261 @@
+//! # Row Notes
+//!
+//! This module lets notes be attached to individual rows while investigating or cleaning
+//! data, without changing the tables themselves. Notes are kept in a sidecar table in the
+//! same database, keyed by table name and rowid.
+//!
+//! ## Features:
+//! - **Sidecar storage**: Notes live in `_vapor_notes`, which is created the first time a
+//!   note is added.
+//! - **Add, list, and delete**: Notes can be listed for a whole table or a single row.
+//! - **Notes column**: Table queries can include a `_notes` column with each row's notes,
+//!   for `.peek --notes` and `export --with-notes`.
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+
+use crate::backend::Backend;
+use crate::formats::quote_identifier;
+
+/// The sidecar table that stores notes.
+pub const NOTES_TABLE: &str = "_vapor_notes";
+
+/// The name of the extra column added by [`notes_select_query`].
+pub const NOTES_COLUMN: &str = "_notes";
+
+/// A note attached to a row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Note {
+    /// The note's ID, used to delete it.
+    pub id: i64,
+    /// The table the row belongs to.
+    pub table: String,
+    /// The rowid of the row.
+    pub row_id: i64,
+    /// The text of the note.
+    pub text: String,
+    /// When the note was added (UTC, `YYYY-MM-DD HH:MM:SS`).
+    pub created_at: String,
+}
+
+/// Attaches a note to a row.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `table` - The table the row belongs to.
+/// * `row_id` - The rowid of the row. The row must exist.
+/// * `text` - The note.
+///
+/// # Returns
+///
+/// A `Result` containing the new note's ID.
+pub fn add_note(conn: &Connection, table: &str, row_id: i64, text: &str) -> Result<i64> {
+    let exists: bool = conn
+        .query_row(
+            &format!(
+                "SELECT EXISTS (SELECT 1 FROM {} WHERE rowid = ?)",
+                quote_identifier(table)
+            ),
+            params![row_id],
+            |row| row.get(0),
+        )
+        .with_context(|| format!("Failed to look up row {} in '{}'", row_id, table))?;
+    if !exists {
+        anyhow::bail!("Table '{}' has no row with rowid {}", table, row_id);
+    }
+
+    conn.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS {notes} (
+             id INTEGER PRIMARY KEY,
+             table_name TEXT NOT NULL,
+             row_id INTEGER NOT NULL,
+             note TEXT NOT NULL,
+             created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+         );
+         CREATE INDEX IF NOT EXISTS {index} ON {notes} (table_name, row_id);",
+        notes = quote_identifier(NOTES_TABLE),
+        index = quote_identifier(&format!("{}_row", NOTES_TABLE)),
+    ))
+    .context("Failed to create the notes table")?;
+    conn.execute(
+        &format!(
+            "INSERT INTO {} (table_name, row_id, note) VALUES (?, ?, ?)",
+            quote_identifier(NOTES_TABLE)
+        ),
+        params![table, row_id, text],
+    )
+    .context("Failed to save the note")?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Lists the notes on a table, or on one of its rows.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `table` - The table whose notes to list.
+/// * `row_id` - If set, only the notes on this row.
+///
+/// # Returns
+///
+/// A `Result` containing the notes ordered by row and then by when they were added.
+pub fn list_notes(conn: &Connection, table: &str, row_id: Option<i64>) -> Result<Vec<Note>> {
+    if !has_notes_table(conn)? {
+        return Ok(Vec::new());
+    }
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, table_name, row_id, note, created_at FROM {}
+         WHERE table_name = ?1 AND (?2 IS NULL OR row_id = ?2)
+         ORDER BY row_id, id",
+        quote_identifier(NOTES_TABLE)
+    ))?;
+    let notes = stmt
+        .query_map(params![table, row_id], |row| {
+            Ok(Note {
+                id: row.get(0)?,
+                table: row.get(1)?,
+                row_id: row.get(2)?,
+                text: row.get(3)?,
+                created_at: row.get(4)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .context("Failed to read notes")?;
+    Ok(notes)
+}
+
+/// Deletes a note.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `id` - The ID of the note.
+///
+/// # Returns
+///
+/// A `Result` containing `true` if the note existed.
+pub fn delete_note(conn: &Connection, id: i64) -> Result<bool> {
+    if !has_notes_table(conn)? {
+        return Ok(false);
+    }
+    let deleted = conn
+        .execute(
+            &format!("DELETE FROM {} WHERE id = ?", quote_identifier(NOTES_TABLE)),
+            params![id],
+        )
+        .context("Failed to delete the note")?;
+    Ok(deleted > 0)
+}
+
+/// Builds a table query like `formats::table_select_query`, with an extra `_notes` column.
+///
+/// The column holds each row's notes joined with `"; "`, or `NULL` for rows without
+/// notes.
+///
+/// # Arguments
+///
+/// * `backend` - The database the query will run against, used to check whether it has
+///   any notes.
+/// * `table` - The table to select from.
+/// * `columns` - The columns to select. If empty, all columns are selected.
+/// * `filter` - An optional SQL condition for a `WHERE` clause.
+///
+/// # Returns
+///
+/// A `Result` containing the SQL query.
+pub fn notes_select_query(
+    backend: &dyn Backend,
+    table: &str,
+    columns: &[String],
+    filter: Option<&str>,
+) -> Result<String> {
+    let quoted = quote_identifier(table);
+    let notes = if backend.table_names()?.iter().any(|t| t == NOTES_TABLE) {
+        format!(
+            "(SELECT group_concat(note, '; ') FROM {} WHERE table_name = '{}' AND row_id = {}.rowid)",
+            quote_identifier(NOTES_TABLE),
+            table.replace('\'', "''"),
+            quoted
+        )
+    } else {
+        "NULL".to_string()
+    };
+    let selected = if columns.is_empty() {
+        format!("{}.*", quoted)
+    } else {
+        columns
+            .iter()
+            .map(|c| quote_identifier(c.trim()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let mut query = format!(
+        "SELECT {}, {} AS {} FROM {}",
+        selected,
+        notes,
+        quote_identifier(NOTES_COLUMN),
+        quoted
+    );
+    if let Some(filter) = filter.filter(|f| !f.trim().is_empty()) {
+        query.push_str(&format!(" WHERE {}", filter));
+    }
+    Ok(query)
+}
+
+fn has_notes_table(conn: &Connection) -> Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?)",
+        params![NOTES_TABLE],
+        |row| row.get(0),
+    )
+    .context("Failed to look up the notes table")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notes_overlay() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE people (id INTEGER PRIMARY KEY, name TEXT);
+             INSERT INTO people (name) VALUES ('Ann'), ('Bob');",
+        )
+        .unwrap();
+
+        let query = notes_select_query(&conn, "people", &[], None).unwrap();
+        assert!(query.contains("NULL AS \"_notes\""));
+        assert!(add_note(&conn, "people", 9, "missing").is_err());
+
+        let first = add_note(&conn, "people", 1, "duplicate of 7?").unwrap();
+        add_note(&conn, "people", 1, "checked").unwrap();
+        assert_eq!(list_notes(&conn, "people", Some(1)).unwrap().len(), 2);
+        assert!(list_notes(&conn, "people", Some(2)).unwrap().is_empty());
+
+        let query =
+            notes_select_query(&conn, "people", &["name".to_string()], Some("id < 3")).unwrap();
+        let rows: Vec<(String, Option<String>)> = conn
+            .prepare(&format!("{} ORDER BY id", query))
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                (
+                    "Ann".to_string(),
+                    Some("duplicate of 7?; checked".to_string())
+                ),
+                ("Bob".to_string(), None)
+            ]
+        );
+
+        assert!(delete_note(&conn, first).unwrap());
+        assert!(!delete_note(&conn, first).unwrap());
+        assert_eq!(list_notes(&conn, "people", None).unwrap().len(), 1);
+    }
+}
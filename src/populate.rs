@@ -22,6 +22,10 @@ use std::path::Path;
 use std::time::{Duration, Instant};
 use uuid::Uuid;
 
+use crate::cancel::interrupt_on_cancel;
+use crate::progress::ProgressTracker;
+use crate::tuning::Tuning;
+
 /// Defines the complete configuration for a database population task.
 ///
 /// This struct specifies the target table, the number of rows to generate, performance settings,
@@ -116,18 +120,39 @@ impl Default for PopulationConfig {
 ///
 /// * `db_path` - The file path to the SQLite database.
 /// * `config` - An `Option<PopulationConfig>` that defines the population parameters.
-///              If `None`, a default configuration is used.
+///   If `None`, a default configuration is used.
 ///
 /// # Returns
 ///
 /// A `Result` which is `Ok(())` on success, or an `Err` if any part of the process fails.
 pub fn populate_database(db_path: &str, config: Option<PopulationConfig>) -> Result<()> {
+    populate_database_with_tuning(db_path, config, &Tuning::default())
+}
+
+/// Populates a database like `populate_database`, with the connection's cache, mmap, and
+/// temp store settings taken from `tuning`.
+///
+/// # Arguments
+///
+/// * `db_path` - The file path to the SQLite database.
+/// * `config` - The population parameters, or `None` for the default configuration.
+/// * `tuning` - Connection settings; a cache size left unset defaults to 10000 pages.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` on success, or an `Err` if any part of the process fails.
+pub fn populate_database_with_tuning(
+    db_path: &str,
+    config: Option<PopulationConfig>,
+    tuning: &Tuning,
+) -> Result<()> {
     println!("Connecting to database: {}", db_path);
 
     // Validate database exists and is accessible
     validate_database_for_population(db_path)?;
 
-    let mut conn = create_connection_with_settings(db_path)?;
+    let mut conn = create_connection_with_settings(db_path, tuning)?;
+    let _interrupt = interrupt_on_cancel(&conn);
 
     // Check available disk space before starting
     check_disk_space_requirements(db_path, &config)?;
@@ -200,7 +225,7 @@ fn validate_database_for_population(db_path: &str) -> Result<()> {
     Ok(())
 }
 
-fn create_connection_with_settings(db_path: &str) -> Result<Connection> {
+fn create_connection_with_settings(db_path: &str, tuning: &Tuning) -> Result<Connection> {
     let conn = Connection::open(db_path)
         .with_context(|| format!("Failed to connect to database: {}", db_path))?;
 
@@ -211,8 +236,11 @@ fn create_connection_with_settings(db_path: &str) -> Result<Connection> {
     conn.pragma_update(None, "journal_mode", "MEMORY")
         .context("Failed to set journal mode to memory")?;
 
-    conn.pragma_update(None, "cache_size", "10000")
-        .context("Failed to increase cache size")?;
+    if tuning.cache_size.is_none() {
+        conn.pragma_update(None, "cache_size", "10000")
+            .context("Failed to increase cache size")?;
+    }
+    tuning.apply(&conn)?;
 
     println!("Database configured for bulk insert performance");
 
@@ -330,6 +358,7 @@ fn populate_with_transaction(conn: &mut Connection, config: &PopulationConfig) -
     };
 
     let mut rows_inserted = 0;
+    let mut progress = ProgressTracker::new("populate", Some(config.row_count as u64));
     let start_time = Instant::now();
     let mut last_checkpoint = Instant::now();
     let checkpoint_interval = Duration::from_secs(30);
@@ -343,6 +372,8 @@ fn populate_with_transaction(conn: &mut Connection, config: &PopulationConfig) -
             match stmt.execute(rusqlite::params_from_iter(values)) {
                 Ok(_) => {
                     rows_inserted += 1;
+                    progress.update(rows_inserted as u64);
+                    progress.check_cancelled()?;
 
                     // Show progress
                     if rows_inserted % config.batch_size == 0 {
@@ -396,6 +427,7 @@ fn populate_with_transaction(conn: &mut Connection, config: &PopulationConfig) -
     drop(stmt); // Release the prepared statement before committing
     tx.commit()
         .context("Failed to commit transaction. All changes have been rolled back.")?;
+    progress.finish();
 
     Ok(rows_inserted)
 }
@@ -0,0 +1,118 @@
+//! # Database Profiles
+//!
+//! This module keeps short names for database files, so `.use NAME` in the REPL can
+//! switch to a database without typing its path. Profiles are kept in
+//! `~/.vapor/profiles.json`.
+//!
+//! ## Features:
+//! - **Named databases**: Each profile maps a name, such as a tenant, to a database path.
+//! - **Resolution**: `resolve` turns what the user typed into a path, preferring an
+//!   existing file over a profile of the same name.
+//! - **Best effort loading**: A missing or unreadable file counts as no profiles.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::config;
+
+/// Named database paths.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Profiles {
+    /// Database paths, keyed by profile name.
+    #[serde(default)]
+    pub databases: BTreeMap<String, String>,
+}
+
+impl Profiles {
+    /// Loads the profiles, or returns none if there are none yet.
+    pub fn load() -> Self {
+        config::get_profiles_path()
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    /// Saves the profiles.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` which is `Ok(())` once the file has been written.
+    pub fn save(&self) -> Result<()> {
+        let path = config::get_profiles_path()?;
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(&path, json)
+            .with_context(|| format!("Failed to write profiles to {}", path.display()))
+    }
+
+    /// Adds or replaces a profile.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The profile name. It can't contain whitespace or path separators.
+    /// * `path` - The database path.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` which is `Ok(())` on success, or an `Err` if the name is invalid.
+    pub fn set(&mut self, name: &str, path: &str) -> Result<()> {
+        if name.is_empty() || name.contains(|c: char| c.is_whitespace() || c == '/' || c == '\\') {
+            anyhow::bail!(
+                "Invalid profile name '{}'. Names can't contain spaces or slashes",
+                name
+            );
+        }
+        self.databases.insert(name.to_string(), path.to_string());
+        Ok(())
+    }
+
+    /// Removes a profile, returning whether it existed.
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.databases.remove(name).is_some()
+    }
+
+    /// Resolves a database path or profile name to a path.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - A path to a database file, or a profile name.
+    ///
+    /// # Returns
+    ///
+    /// The path of an existing file, or of the profile with that name, or `None` if it is
+    /// neither.
+    pub fn resolve(&self, target: &str) -> Option<String> {
+        if Path::new(target).is_file() {
+            return Some(target.to_string());
+        }
+        self.databases.get(target).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_profiles() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("acme.db");
+        fs::write(&db, "").unwrap();
+        let db = db.to_str().unwrap();
+
+        let mut profiles = Profiles::default();
+        profiles.set("acme", db).unwrap();
+        assert!(profiles.set("two words", db).is_err());
+        assert!(profiles.set("a/b", db).is_err());
+
+        assert_eq!(profiles.resolve("acme").as_deref(), Some(db));
+        assert_eq!(profiles.resolve(db).as_deref(), Some(db));
+        assert_eq!(profiles.resolve("missing"), None);
+
+        assert!(profiles.remove("acme"));
+        assert_eq!(profiles.resolve("acme"), None);
+    }
+}
@@ -0,0 +1,331 @@
+//! # Machine-Readable Progress Events
+//!
+//! This module provides a side channel for emitting structured progress events from
+//! long-running operations such as `populate`, CSV import, and CSV export. Events are
+//! written as one JSON object per line (JSON Lines) so that wrapper UIs can render
+//! progress bars without scraping the human-oriented console output.
+//!
+//! ## Usage:
+//! - Call `set_progress_sink` once (e.g. from the `--progress-json` CLI flag) to choose
+//!   where events go: a file descriptor number or a file path.
+//! - Long-running operations create a `ProgressTracker` and call `update` as work
+//!   completes. When no sink is configured, tracking is a cheap no-op.
+//!
+//! ## Event Shape:
+//! ```json
+//! {"event":"progress","operation":"export","completed":20000,"total":null,"rate":51234.7,"elapsed_ms":390}
+//! ```
+//! The `event` field is one of `start`, `progress`, or `finish`.
+//!
+//! Background jobs can also follow the trackers created on their thread with
+//! `observe_progress`, whether or not a sink is configured. Trackers created during a
+//! cancellable operation record their progress in its `CancellationToken`, and
+//! `check_cancelled` lets row loops stop when it is cancelled.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::cell::RefCell;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::cancel::{current_token, CancellationToken, Cancelled};
+
+type Sink = Box<dyn Write + Send>;
+
+/// Minimum interval between two `progress` events for the same tracker.
+const EMIT_INTERVAL: Duration = Duration::from_millis(200);
+
+fn sink() -> &'static Mutex<Option<Sink>> {
+    static SINK: OnceLock<Mutex<Option<Sink>>> = OnceLock::new();
+    SINK.get_or_init(|| Mutex::new(None))
+}
+
+thread_local! {
+    static OBSERVER: RefCell<Option<Arc<AtomicU64>>> = const { RefCell::new(None) };
+}
+
+/// Mirrors the completed count of trackers created on this thread into a counter, so
+/// another thread can follow a background job's progress.
+///
+/// # Arguments
+///
+/// * `counter` - The counter to update, or `None` to stop observing.
+pub fn observe_progress(counter: Option<Arc<AtomicU64>>) {
+    OBSERVER.with(|observer| *observer.borrow_mut() = counter);
+}
+
+/// A single progress event as written to the progress sink.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgressEvent {
+    pub event: &'static str,
+    pub operation: String,
+    pub completed: u64,
+    pub total: Option<u64>,
+    pub rate: f64,
+    pub elapsed_ms: u64,
+}
+
+/// Configures the destination for progress events.
+///
+/// The target is interpreted as follows:
+/// - `1` or `stdout` and `2` or `stderr` select the standard streams.
+/// - Any other number is treated as an already-open file descriptor (Unix only). The
+///   sink writes to a duplicate, so the caller keeps ownership of the descriptor. `0`
+///   (stdin) and descriptors that are not open are rejected.
+/// - Anything else is treated as a file path, which is created or truncated.
+///
+/// # Arguments
+///
+/// * `target` - The file descriptor number or file path to write events to.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` once the sink is installed, or an `Err` if the target
+/// cannot be opened.
+pub fn set_progress_sink(target: &str) -> Result<()> {
+    let target = target.trim();
+    if target.is_empty() {
+        anyhow::bail!("Progress target cannot be empty");
+    }
+
+    let writer: Sink = match target {
+        "1" | "stdout" => Box::new(std::io::stdout()),
+        "2" | "stderr" => Box::new(std::io::stderr()),
+        _ => match target.parse::<i32>() {
+            Ok(fd) => open_fd(fd)?,
+            Err(_) => Box::new(
+                OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .truncate(true)
+                    .open(target)
+                    .with_context(|| format!("Failed to open progress file '{}'", target))?,
+            ),
+        },
+    };
+
+    *sink().lock().unwrap() = Some(writer);
+    Ok(())
+}
+
+/// Removes any configured progress sink, disabling event emission.
+pub fn clear_progress_sink() {
+    *sink().lock().unwrap() = None;
+}
+
+/// Returns `true` if a progress sink has been configured.
+pub fn is_enabled() -> bool {
+    sink().lock().unwrap().is_some()
+}
+
+#[cfg(unix)]
+fn open_fd(fd: i32) -> Result<Sink> {
+    use std::os::unix::io::BorrowedFd;
+
+    if fd <= 0 {
+        anyhow::bail!("Invalid file descriptor: {}", fd);
+    }
+    // Safety: `fcntl` with `F_GETFD` only reads the descriptor flags.
+    if unsafe { libc::fcntl(fd, libc::F_GETFD) } < 0 {
+        anyhow::bail!(
+            "File descriptor {} is not open: {}",
+            fd,
+            std::io::Error::last_os_error()
+        );
+    }
+    // Safety: the descriptor was checked to be open above and is only borrowed long
+    // enough to duplicate it. The sink owns the duplicate, so clearing the sink never
+    // closes the caller's descriptor.
+    let borrowed = unsafe { BorrowedFd::borrow_raw(fd) };
+    let owned = borrowed
+        .try_clone_to_owned()
+        .with_context(|| format!("Failed to duplicate file descriptor {}", fd))?;
+    Ok(Box::new(std::fs::File::from(owned)))
+}
+
+#[cfg(not(unix))]
+fn open_fd(fd: i32) -> Result<Sink> {
+    anyhow::bail!(
+        "Writing progress to file descriptor {} is only supported on Unix; use a file path instead",
+        fd
+    )
+}
+
+fn emit(event: &ProgressEvent) {
+    let mut guard = sink().lock().unwrap();
+    if let Some(writer) = guard.as_mut() {
+        // Progress reporting must never break the operation itself, so write errors are ignored.
+        if let Ok(line) = serde_json::to_string(event) {
+            let _ = writeln!(writer, "{}", line);
+            let _ = writer.flush();
+        }
+    }
+}
+
+/// Tracks the progress of a single long-running operation and emits throttled events.
+pub struct ProgressTracker {
+    operation: String,
+    total: Option<u64>,
+    completed: u64,
+    started: Instant,
+    last_emit: Option<Instant>,
+    enabled: bool,
+    observer: Option<Arc<AtomicU64>>,
+    cancel: Option<CancellationToken>,
+}
+
+impl ProgressTracker {
+    /// Creates a tracker and emits a `start` event.
+    ///
+    /// # Arguments
+    ///
+    /// * `operation` - A short name for the operation (e.g. `populate`, `import`, `export`).
+    /// * `total` - The total number of units of work, if known up front.
+    pub fn new(operation: &str, total: Option<u64>) -> Self {
+        let tracker = Self {
+            operation: operation.to_string(),
+            total,
+            completed: 0,
+            started: Instant::now(),
+            last_emit: None,
+            enabled: is_enabled(),
+            observer: OBSERVER.with(|observer| observer.borrow().clone()),
+            cancel: current_token(),
+        };
+        tracker.send("start");
+        tracker
+    }
+
+    /// Records that `completed` units of work are now done.
+    ///
+    /// A `progress` event is emitted at most once per 200ms, plus once when
+    /// the known total is reached.
+    pub fn update(&mut self, completed: u64) {
+        self.completed = completed;
+        if let Some(observer) = &self.observer {
+            observer.store(completed, Ordering::Relaxed);
+        }
+        if let Some(token) = &self.cancel {
+            token.record_progress(completed);
+        }
+        if !self.enabled {
+            return;
+        }
+
+        let reached_total = self.total.is_some_and(|total| completed >= total);
+        let due = match self.last_emit {
+            Some(last) => last.elapsed() >= EMIT_INTERVAL,
+            None => true,
+        };
+        if due || reached_total {
+            self.last_emit = Some(Instant::now());
+            self.send("progress");
+        }
+    }
+
+    /// Emits a final `finish` event with the last recorded count.
+    pub fn finish(self) {
+        self.send("finish");
+    }
+
+    /// Returns a `Cancelled` error if the operation this tracker belongs to was cancelled.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` which is `Ok(())` unless the operation's `CancellationToken` was
+    /// cancelled.
+    pub fn check_cancelled(&self) -> Result<()> {
+        match &self.cancel {
+            Some(token) if token.is_cancelled() => Err(Cancelled {
+                completed: self.completed,
+            }
+            .into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the number of completed units recorded so far.
+    pub fn completed(&self) -> u64 {
+        self.completed
+    }
+
+    fn send(&self, event: &'static str) {
+        if !self.enabled {
+            return;
+        }
+        let elapsed = self.started.elapsed();
+        let secs = elapsed.as_secs_f64();
+        emit(&ProgressEvent {
+            event,
+            operation: self.operation.clone(),
+            completed: self.completed,
+            total: self.total,
+            rate: if secs > 0.0 {
+                self.completed as f64 / secs
+            } else {
+                0.0
+            },
+            elapsed_ms: elapsed.as_millis() as u64,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_progress_events_written_as_json_lines() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("progress.jsonl");
+        set_progress_sink(path.to_str().unwrap())?;
+
+        let mut tracker = ProgressTracker::new("progress-test", Some(2));
+        tracker.update(1);
+        tracker.update(2);
+        tracker.finish();
+        clear_progress_sink();
+
+        let contents = std::fs::read_to_string(&path)?;
+        // Other tests may emit events concurrently, so only look at ours.
+        let events: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .filter(|e| e["operation"] == "progress-test")
+            .collect();
+
+        assert_eq!(events.first().unwrap()["event"], "start");
+        assert_eq!(events.last().unwrap()["event"], "finish");
+        assert_eq!(events.last().unwrap()["completed"], 2);
+        assert_eq!(events.last().unwrap()["total"], 2);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_progress_sink_rejects_stdin_and_closed_fds() {
+        assert!(set_progress_sink("0").is_err());
+        assert!(set_progress_sink("987654").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_progress_sink_leaves_caller_fd_open() -> Result<()> {
+        use std::os::unix::io::AsRawFd;
+
+        let dir = tempdir()?;
+        let mut file = std::fs::File::create(dir.path().join("fd.jsonl"))?;
+        let fd = file.as_raw_fd();
+        set_progress_sink(&fd.to_string())?;
+        clear_progress_sink();
+
+        // Safety: `fcntl` with `F_GETFD` only reads the descriptor flags.
+        assert!(unsafe { libc::fcntl(fd, libc::F_GETFD) } >= 0);
+        file.write_all(b"still open\n")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,350 @@
+//! # Background Query Queue
+//!
+//! This module runs a batch of SQL statements one after another on a background thread,
+//! so slow maintenance work (rebuilding indexes, backfilling columns, `VACUUM`) doesn't
+//! block the REPL. Statements are collected with `.queue add` and started with
+//! `.queue run`; the REPL reports when the batch is done.
+//!
+//! ## Features:
+//! - **Separate connection**: The queue opens its own connection to the database file and
+//!   waits for locks held by the REPL instead of failing.
+//! - **Per-statement status**: Each statement is pending, running, done (with its row
+//!   count and time), failed (with the error), or skipped.
+//! - **Keeps going**: A failing statement is recorded and the rest of the batch still runs.
+//! - **Cancel**: A running batch can be stopped; the current statement is interrupted and
+//!   the rest are skipped.
+
+use anyhow::{Context, Result};
+use rusqlite::{Connection, InterruptHandle};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::statement::split_statements;
+
+/// How long a queued statement waits for a lock held by another connection.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The state of a statement in a queue run.
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueuedState {
+    Pending,
+    Running,
+    Done {
+        rows_affected: u64,
+        elapsed: Duration,
+    },
+    Failed(String),
+    /// Not run because the batch was cancelled.
+    Skipped,
+}
+
+impl fmt::Display for QueuedState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueuedState::Pending => write!(f, "pending"),
+            QueuedState::Running => write!(f, "running"),
+            QueuedState::Done {
+                rows_affected,
+                elapsed,
+            } => write!(
+                f,
+                "done ({} row(s), {:.3}s)",
+                rows_affected,
+                elapsed.as_secs_f64()
+            ),
+            QueuedState::Failed(message) => write!(f, "failed: {}", message),
+            QueuedState::Skipped => write!(f, "skipped"),
+        }
+    }
+}
+
+/// A statement in a queue run and its state.
+#[derive(Debug, Clone)]
+pub struct QueuedQuery {
+    pub sql: String,
+    pub state: QueuedState,
+}
+
+/// The outcome of a finished queue run.
+#[derive(Debug, Clone, Default)]
+pub struct QueueSummary {
+    pub succeeded: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub elapsed: Duration,
+}
+
+impl fmt::Display for QueueSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} succeeded, {} failed, {} skipped in {:.3}s",
+            self.succeeded,
+            self.failed,
+            self.skipped,
+            self.elapsed.as_secs_f64()
+        )
+    }
+}
+
+/// A batch running on the background thread.
+struct QueueRun {
+    queries: Arc<Mutex<Vec<QueuedQuery>>>,
+    started: Instant,
+    finished: Arc<Mutex<Option<Instant>>>,
+    cancelled: Arc<AtomicBool>,
+    interrupt: InterruptHandle,
+    handle: Option<JoinHandle<()>>,
+}
+
+/// Statements waiting to run, and the batch that is running or last ran.
+#[derive(Default)]
+pub struct QueryQueue {
+    pending: Vec<String>,
+    run: Option<QueueRun>,
+    /// Whether the last run's summary has been handed out.
+    reported: bool,
+}
+
+/// The REPL's query queue.
+pub fn query_queue() -> &'static Mutex<QueryQueue> {
+    static QUEUE: OnceLock<Mutex<QueryQueue>> = OnceLock::new();
+    QUEUE.get_or_init(|| Mutex::new(QueryQueue::default()))
+}
+
+impl QueryQueue {
+    /// Adds statements to the queue.
+    ///
+    /// # Arguments
+    ///
+    /// * `sql` - One or more SQL statements.
+    ///
+    /// # Returns
+    ///
+    /// The number of statements added.
+    pub fn add(&mut self, sql: &str) -> usize {
+        let before = self.pending.len();
+        self.pending.extend(
+            split_statements(sql)
+                .into_iter()
+                .map(|(_, statement)| statement.to_string()),
+        );
+        self.pending.len() - before
+    }
+
+    /// The statements waiting for the next run.
+    pub fn pending(&self) -> &[String] {
+        &self.pending
+    }
+
+    /// Removes every waiting statement. A running batch is not affected.
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Whether a batch is running.
+    pub fn is_running(&self) -> bool {
+        self.run
+            .as_ref()
+            .is_some_and(|run| run.finished.lock().unwrap().is_none())
+    }
+
+    /// Starts running the waiting statements on a background thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `db_path` - The database file. The batch opens its own connection to it.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the number of statements started, or an `Err` if the queue
+    /// is empty, a batch is already running, or the database can't be opened.
+    pub fn run(&mut self, db_path: &str) -> Result<usize> {
+        if self.is_running() {
+            anyhow::bail!("The queue is already running; wait for it or use '.queue cancel'");
+        }
+        if self.pending.is_empty() {
+            anyhow::bail!("The queue is empty; add statements with '.queue add QUERY'");
+        }
+        let conn = Connection::open(db_path)
+            .with_context(|| format!("Failed to open database '{}' for the queue", db_path))?;
+        conn.busy_timeout(BUSY_TIMEOUT)
+            .context("Failed to set the queue's busy timeout")?;
+
+        let queries: Vec<QueuedQuery> = self
+            .pending
+            .drain(..)
+            .map(|sql| QueuedQuery {
+                sql,
+                state: QueuedState::Pending,
+            })
+            .collect();
+        let count = queries.len();
+        let queries = Arc::new(Mutex::new(queries));
+        let finished = Arc::new(Mutex::new(None));
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let interrupt = conn.get_interrupt_handle();
+
+        let handle = {
+            let queries = Arc::clone(&queries);
+            let finished = Arc::clone(&finished);
+            let cancelled = Arc::clone(&cancelled);
+            std::thread::spawn(move || {
+                run_queries(&conn, &queries, &cancelled);
+                *finished.lock().unwrap() = Some(Instant::now());
+            })
+        };
+
+        self.run = Some(QueueRun {
+            queries,
+            started: Instant::now(),
+            finished,
+            cancelled,
+            interrupt,
+            handle: Some(handle),
+        });
+        self.reported = false;
+        Ok(count)
+    }
+
+    /// Stops a running batch: the current statement is interrupted and the rest skipped.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a batch was running.
+    pub fn cancel(&self) -> bool {
+        match &self.run {
+            Some(run) if self.is_running() => {
+                run.cancelled.store(true, Ordering::SeqCst);
+                run.interrupt.interrupt();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The statements of the running or last batch and their states.
+    pub fn statuses(&self) -> Vec<QueuedQuery> {
+        self.run
+            .as_ref()
+            .map(|run| run.queries.lock().unwrap().clone())
+            .unwrap_or_default()
+    }
+
+    /// Returns the summary of a batch that has finished since the last call, so the
+    /// REPL reports each batch once.
+    pub fn take_finished(&mut self) -> Option<QueueSummary> {
+        if self.reported || self.is_running() {
+            return None;
+        }
+        let summary = self.summary()?;
+        self.reported = true;
+        Some(summary)
+    }
+
+    /// Waits for a running batch to finish.
+    ///
+    /// # Returns
+    ///
+    /// The summary of the last batch, or `None` if nothing has run.
+    pub fn wait(&mut self) -> Option<QueueSummary> {
+        if let Some(handle) = self.run.as_mut().and_then(|run| run.handle.take()) {
+            let _ = handle.join();
+        }
+        self.summary()
+    }
+
+    fn summary(&self) -> Option<QueueSummary> {
+        let run = self.run.as_ref()?;
+        let finished = (*run.finished.lock().unwrap())?;
+        let mut summary = QueueSummary {
+            elapsed: finished - run.started,
+            ..Default::default()
+        };
+        for query in run.queries.lock().unwrap().iter() {
+            match query.state {
+                QueuedState::Done { .. } => summary.succeeded += 1,
+                QueuedState::Failed(_) => summary.failed += 1,
+                _ => summary.skipped += 1,
+            }
+        }
+        Some(summary)
+    }
+}
+
+/// Runs queued statements in order, recording each one's state as it goes.
+fn run_queries(conn: &Connection, queries: &Mutex<Vec<QueuedQuery>>, cancelled: &AtomicBool) {
+    let count = queries.lock().unwrap().len();
+    for i in 0..count {
+        if cancelled.load(Ordering::SeqCst) {
+            queries.lock().unwrap()[i].state = QueuedState::Skipped;
+            continue;
+        }
+        let sql = {
+            let mut queries = queries.lock().unwrap();
+            queries[i].state = QueuedState::Running;
+            queries[i].sql.clone()
+        };
+        let start = Instant::now();
+        let state = match conn.execute_batch(&sql) {
+            Ok(()) => QueuedState::Done {
+                rows_affected: conn.changes(),
+                elapsed: start.elapsed(),
+            },
+            Err(_) if cancelled.load(Ordering::SeqCst) => QueuedState::Skipped,
+            Err(e) => QueuedState::Failed(e.to_string()),
+        };
+        queries.lock().unwrap()[i].state = state;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_queue() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("queue.db");
+        let db_path = db_path.to_str().unwrap();
+        let conn = Connection::open(db_path).unwrap();
+        conn.execute_batch("CREATE TABLE t (x INTEGER)").unwrap();
+
+        let mut queue = QueryQueue::default();
+        assert!(queue.run(db_path).is_err());
+        assert_eq!(
+            queue.add("INSERT INTO t VALUES (1), (2); INSERT INTO missing VALUES (1)"),
+            2
+        );
+        queue.add("UPDATE t SET x = x * 10");
+        assert_eq!(queue.run(db_path).unwrap(), 3);
+        assert!(queue.pending().is_empty());
+
+        let summary = queue.wait().unwrap();
+        assert_eq!(
+            (summary.succeeded, summary.failed, summary.skipped),
+            (2, 1, 0)
+        );
+        let statuses = queue.statuses();
+        assert!(matches!(
+            statuses[0].state,
+            QueuedState::Done {
+                rows_affected: 2,
+                ..
+            }
+        ));
+        assert!(matches!(statuses[1].state, QueuedState::Failed(_)));
+        let total: i64 = conn
+            .query_row("SELECT SUM(x) FROM t", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(total, 30);
+
+        // Each finished batch is reported once
+        assert!(queue.take_finished().is_some());
+        assert!(queue.take_finished().is_none());
+        assert!(!queue.cancel());
+    }
+}
@@ -0,0 +1,233 @@
+//! # Remote libsql/Turso Databases
+//!
+//! This module implements the `Backend` trait for hosted SQLite databases that speak the
+//! libsql HTTP protocol (Hrana over HTTP), such as Turso or a self-hosted `sqld`.
+//!
+//! ## Features:
+//! - **URL targets**: `libsql://` URLs are reached over HTTPS; `http://` and `https://`
+//!   URLs are used as given.
+//! - **Authentication**: A bearer token from the `authToken` URL parameter or the
+//!   `LIBSQL_AUTH_TOKEN` / `TURSO_AUTH_TOKEN` environment variables.
+//! - **Sessions**: The server's stream baton is carried between requests, so
+//!   `BEGIN`/`COMMIT` work across statements just as they do locally.
+//!
+//! This module requires the `remote` feature.
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use rusqlite::types::Value;
+use serde_json::{json, Value as JsonValue};
+use std::sync::Mutex;
+
+use crate::backend::{Backend, QueryResult};
+
+/// The position of a session on the server, carried between pipeline requests.
+#[derive(Default)]
+struct Stream {
+    baton: Option<String>,
+    base_url: Option<String>,
+}
+
+/// A remote database reached over the libsql HTTP protocol.
+pub struct RemoteBackend {
+    url: String,
+    auth_token: Option<String>,
+    stream: Mutex<Stream>,
+}
+
+impl RemoteBackend {
+    /// Connects to a remote database and checks that it answers queries.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - A `libsql://`, `https://`, or `http://` database URL.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the connected backend, or an `Err` if the server cannot be
+    /// reached or rejects the credentials.
+    pub fn connect(target: &str) -> Result<Self> {
+        let (url, auth_token) = parse_target(target);
+        let backend = Self {
+            url,
+            auth_token: auth_token
+                .or_else(|| std::env::var("LIBSQL_AUTH_TOKEN").ok())
+                .or_else(|| std::env::var("TURSO_AUTH_TOKEN").ok()),
+            stream: Mutex::new(Stream::default()),
+        };
+        backend
+            .run("SELECT 1", None)
+            .with_context(|| format!("Failed to connect to remote database '{}'", target))?;
+        Ok(backend)
+    }
+
+    /// Sends one pipeline request on the current stream and returns its single result.
+    fn execute(&self, sql: &str) -> Result<JsonValue> {
+        let mut stream = self.stream.lock().unwrap();
+        let base = stream.base_url.clone().unwrap_or_else(|| self.url.clone());
+        let body = json!({
+            "baton": stream.baton,
+            "requests": [{ "type": "execute", "stmt": { "sql": sql } }],
+        });
+
+        let mut request = ureq::post(&format!("{}/v2/pipeline", base.trim_end_matches('/')));
+        if let Some(token) = &self.auth_token {
+            request = request.set("Authorization", &format!("Bearer {}", token));
+        }
+        let response: JsonValue = match request.send_json(body) {
+            Ok(response) => response.into_json()?,
+            Err(ureq::Error::Status(code, response)) => {
+                let message = response.into_string().unwrap_or_default();
+                // The stream is gone after an HTTP error; start a new one next time
+                *stream = Stream::default();
+                anyhow::bail!("Remote database returned HTTP {}: {}", code, message.trim());
+            }
+            Err(e) => {
+                *stream = Stream::default();
+                return Err(e).context("Failed to reach remote database");
+            }
+        };
+
+        stream.baton = response["baton"].as_str().map(str::to_string);
+        if let Some(base_url) = response["base_url"].as_str() {
+            stream.base_url = Some(base_url.to_string());
+        }
+
+        let result = &response["results"][0];
+        match result["type"].as_str() {
+            Some("ok") => Ok(result["response"]["result"].clone()),
+            Some("error") => anyhow::bail!(
+                "{}",
+                result["error"]["message"]
+                    .as_str()
+                    .unwrap_or("Unknown remote error")
+            ),
+            _ => anyhow::bail!("Unexpected response from remote database: {}", response),
+        }
+    }
+}
+
+impl Backend for RemoteBackend {
+    fn describe(&self) -> String {
+        self.url.clone()
+    }
+
+    fn run(&self, sql: &str, max_rows: Option<usize>) -> Result<QueryResult> {
+        let result = self.execute(sql)?;
+        let columns = result["cols"]
+            .as_array()
+            .map(|cols| {
+                cols.iter()
+                    .map(|col| col["name"].as_str().unwrap_or("").to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let rows = result["rows"].as_array().cloned().unwrap_or_default();
+        let limit = max_rows.unwrap_or(usize::MAX);
+        let rows = rows
+            .iter()
+            .take(limit)
+            .map(|row| {
+                row.as_array()
+                    .map(|values| values.iter().map(decode_value).collect())
+                    .unwrap_or_else(|| Ok(Vec::new()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(QueryResult {
+            columns,
+            rows,
+            rows_affected: result["affected_row_count"].as_u64().unwrap_or(0) as usize,
+        })
+    }
+}
+
+/// Splits a target into the HTTP base URL and an optional `authToken` parameter.
+fn parse_target(target: &str) -> (String, Option<String>) {
+    let (base, query) = match target.split_once('?') {
+        Some((base, query)) => (base, Some(query)),
+        None => (target, None),
+    };
+    let url = match base.strip_prefix("libsql://") {
+        Some(host) => format!("https://{}", host),
+        None => base.to_string(),
+    };
+    let token = query.and_then(|query| {
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("authToken="))
+            .map(str::to_string)
+    });
+    (url, token)
+}
+
+/// Decodes a protocol value (`{"type": "integer", "value": "1"}` and so on).
+fn decode_value(value: &JsonValue) -> Result<Value> {
+    Ok(match value["type"].as_str() {
+        Some("null") | None => Value::Null,
+        Some("integer") => Value::Integer(
+            value["value"]
+                .as_str()
+                .and_then(|v| v.parse().ok())
+                .context("Invalid integer value from remote database")?,
+        ),
+        Some("float") => Value::Real(
+            value["value"]
+                .as_f64()
+                .context("Invalid float value from remote database")?,
+        ),
+        Some("text") => Value::Text(value["value"].as_str().unwrap_or("").to_string()),
+        Some("blob") => Value::Blob(
+            base64::engine::general_purpose::STANDARD_NO_PAD
+                .decode(value["base64"].as_str().unwrap_or("").trim_end_matches('='))
+                .context("Invalid blob value from remote database")?,
+        ),
+        Some(other) => anyhow::bail!("Unsupported value type '{}' from remote database", other),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_target_and_decode() {
+        assert_eq!(
+            parse_target("libsql://db-org.turso.io?authToken=abc"),
+            (
+                "https://db-org.turso.io".to_string(),
+                Some("abc".to_string())
+            )
+        );
+        assert_eq!(
+            parse_target("http://127.0.0.1:8080"),
+            ("http://127.0.0.1:8080".to_string(), None)
+        );
+
+        let row = json!([
+            { "type": "integer", "value": "42" },
+            { "type": "float", "value": 1.5 },
+            { "type": "text", "value": "hi" },
+            { "type": "blob", "base64": "AP8=" },
+            { "type": "null" },
+        ]);
+        let values: Vec<Value> = row
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(decode_value)
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(
+            values,
+            vec![
+                Value::Integer(42),
+                Value::Real(1.5),
+                Value::Text("hi".to_string()),
+                Value::Blob(vec![0, 255]),
+                Value::Null,
+            ]
+        );
+    }
+}
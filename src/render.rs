@@ -0,0 +1,312 @@
+//! # Result Rendering
+//!
+//! This module turns query results and table schemas into text in vapor-cli's output
+//! formats. It is part of the core that builds without the `native` feature, so the same
+//! rendering can run where there is no terminal or filesystem, such as a browser-based
+//! viewer compiled to `wasm32-unknown-unknown`.
+//!
+//! ## Features:
+//! - **Output formats**: `OutputFormat` names the formats (`table`, `json`, `csv`,
+//!   `plain`), and `render_result` renders a `QueryResult` in any of them as a `String`.
+//! - **Schemas**: `render_columns` renders a table's columns the way `.schema` shows them.
+//! - **Shared with the CLI**: The REPL prints through the same functions, so a viewer
+//!   built on them shows exactly what the command line does.
+
+use anyhow::Result;
+use prettytable::{row, Table};
+use serde_json::json;
+
+use crate::backend::{ColumnDescription, QueryResult, Value};
+
+/// Specifies the output format for query results.
+#[derive(Debug, Clone)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+    /// Space-aligned columns without borders, for piping into tools like `awk` or `grep`.
+    Plain,
+}
+
+impl OutputFormat {
+    /// Parses a format name: `table`, `json`, `csv`, or `plain`.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "table" => Ok(Self::Table),
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            "plain" => Ok(Self::Plain),
+            _ => anyhow::bail!(
+                "Invalid format '{}'. Available: table, json, csv, plain",
+                name
+            ),
+        }
+    }
+}
+
+/// Renders the rows of a query result in an output format.
+///
+/// # Arguments
+///
+/// * `format` - The output format.
+/// * `result` - The columns and rows returned by a `Backend`.
+///
+/// # Returns
+///
+/// A `Result` containing the rendered text, ending in a newline, or an `Err` if the rows
+/// can't be formatted.
+pub fn render_result(format: &OutputFormat, result: &QueryResult) -> Result<String> {
+    let rows: Vec<Vec<String>> = result
+        .rows
+        .iter()
+        .map(|row| row.iter().map(display_value).collect())
+        .collect();
+    render_rows(format, &result.columns, &rows)
+}
+
+/// Renders rows of display values in an output format.
+///
+/// # Arguments
+///
+/// * `format` - The output format.
+/// * `column_names` - The column names.
+/// * `rows` - The rows, each value already rendered with `display_value`.
+///
+/// # Returns
+///
+/// A `Result` containing the rendered text, ending in a newline, or an `Err` if the rows
+/// can't be formatted.
+pub fn render_rows(
+    format: &OutputFormat,
+    column_names: &[String],
+    rows: &[Vec<String>],
+) -> Result<String> {
+    Ok(match format {
+        OutputFormat::Table => build_table(column_names, rows).to_string(),
+        OutputFormat::Json => format!("{}\n", format_json(column_names, rows)?),
+        OutputFormat::Csv => format_csv(column_names, rows),
+        OutputFormat::Plain => format_plain(column_names, rows),
+    })
+}
+
+/// Renders a table's columns as the bordered table `.schema` shows.
+///
+/// # Arguments
+///
+/// * `columns` - The columns, in table order, as returned by `Backend::columns`.
+///
+/// # Returns
+///
+/// The rendered table, ending in a newline.
+pub fn render_columns(columns: &[ColumnDescription]) -> String {
+    schema_table(columns).to_string()
+}
+
+/// Renders a single value for display.
+pub fn display_value(value: &Value) -> String {
+    match value {
+        Value::Null => "NULL".to_string(),
+        Value::Integer(val) => val.to_string(),
+        Value::Real(val) => val.to_string(),
+        Value::Text(val) => val.clone(),
+        Value::Blob(val) => format!("<binary data: {} bytes>", val.len()),
+    }
+}
+
+/// Builds a bordered table of query results.
+pub(crate) fn build_table(column_names: &[String], rows: &[Vec<String>]) -> Table {
+    let mut table = Table::new();
+    table.set_format(*prettytable::format::consts::FORMAT_BOX_CHARS);
+
+    // Add header row
+    let mut header_row = prettytable::Row::empty();
+    for col_name in column_names {
+        header_row.add_cell(prettytable::Cell::new(col_name).style_spec("b"));
+    }
+    table.add_row(header_row);
+
+    // Add data rows
+    for row_values in rows {
+        let mut data_row = prettytable::Row::empty();
+        for value in row_values {
+            data_row.add_cell(prettytable::Cell::new(value));
+        }
+        table.add_row(data_row);
+    }
+    table
+}
+
+/// Builds the bordered table of a table's columns.
+pub(crate) fn schema_table(columns: &[ColumnDescription]) -> Table {
+    let mut table = Table::new();
+    table.set_format(*prettytable::format::consts::FORMAT_BOX_CHARS);
+    table.add_row(row![
+        "ID",
+        "Name",
+        "Type",
+        "Not Null",
+        "Default Value",
+        "Primary Key"
+    ]);
+    for (cid, column) in columns.iter().enumerate() {
+        let not_null_str = if column.not_null { "YES" } else { "NO" };
+        let pk_str = if column.primary_key { "YES" } else { "NO" };
+        let default_str = column.default_value.as_deref().unwrap_or("NULL");
+        table.add_row(row![
+            cid,
+            column.name,
+            column.declared_type,
+            not_null_str,
+            default_str,
+            pk_str
+        ]);
+    }
+    table
+}
+
+/// Formats query results as a JSON object.
+///
+/// The JSON output includes the column names, the number of rows, and the data itself.
+/// It attempts to infer numeric types from the string values.
+pub(crate) fn format_json(column_names: &[String], rows: &[Vec<String>]) -> Result<String> {
+    let mut json_rows = Vec::new();
+
+    for row_values in rows {
+        let mut json_row = serde_json::Map::new();
+        for (i, value) in row_values.iter().enumerate() {
+            let json_value = if value == "NULL" {
+                serde_json::Value::Null
+            } else if let Ok(int_val) = value.parse::<i64>() {
+                serde_json::Value::Number(serde_json::Number::from(int_val))
+            } else if let Ok(float_val) = value.parse::<f64>() {
+                serde_json::Value::Number(
+                    serde_json::Number::from_f64(float_val)
+                        .unwrap_or_else(|| serde_json::Number::from(0)),
+                )
+            } else {
+                serde_json::Value::String(value.clone())
+            };
+            json_row.insert(column_names[i].clone(), json_value);
+        }
+        json_rows.push(serde_json::Value::Object(json_row));
+    }
+
+    let output = json!({
+        "data": json_rows,
+        "columns": column_names,
+        "row_count": rows.len()
+    });
+
+    Ok(serde_json::to_string_pretty(&output)?)
+}
+
+/// Formats query results as CSV data.
+///
+/// This function handles basic CSV escaping for values containing commas or quotes.
+pub(crate) fn format_csv(column_names: &[String], rows: &[Vec<String>]) -> String {
+    let mut output = format!("{}\n", column_names.join(","));
+    for row_values in rows {
+        let escaped_values: Vec<String> = row_values
+            .iter()
+            .map(|v| {
+                if v.contains(',') || v.contains('"') || v.contains('\n') {
+                    format!("\"{}\"", v.replace('"', "\"\""))
+                } else {
+                    v.clone()
+                }
+            })
+            .collect();
+        output.push_str(&escaped_values.join(","));
+        output.push('\n');
+    }
+    output
+}
+
+/// Formats query results as space-aligned columns with no borders.
+///
+/// Columns are separated by two spaces and padded to their widest value, trailing
+/// padding is trimmed, and line breaks inside values are escaped so every row stays on
+/// one line.
+pub(crate) fn format_plain(column_names: &[String], rows: &[Vec<String>]) -> String {
+    let escape = |value: &str| value.replace('\r', "\\r").replace('\n', "\\n");
+    let lines: Vec<Vec<String>> = std::iter::once(column_names.to_vec())
+        .chain(rows.iter().cloned())
+        .map(|line| line.iter().map(|value| escape(value)).collect())
+        .collect();
+
+    let mut widths = vec![0; column_names.len()];
+    for line in &lines {
+        for (width, value) in widths.iter_mut().zip(line) {
+            *width = (*width).max(value.chars().count());
+        }
+    }
+
+    let mut output = String::new();
+    for line in &lines {
+        let mut text = String::new();
+        for (i, value) in line.iter().enumerate() {
+            if i > 0 {
+                text.push_str("  ");
+            }
+            // `{:<width$}` pads by characters, so non-ASCII values stay aligned
+            let width = widths.get(i).copied().unwrap_or(0);
+            text.push_str(&format!("{:<width$}", value, width = width));
+        }
+        output.push_str(text.trim_end());
+        output.push('\n');
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_plain() {
+        let columns = vec!["id".to_string(), "name".to_string(), "note".to_string()];
+        let rows = vec![
+            vec!["1".to_string(), "Ada".to_string(), "NULL".to_string()],
+            vec![
+                "10".to_string(),
+                "Grace Hopper".to_string(),
+                "two\nlines".to_string(),
+            ],
+        ];
+        assert_eq!(
+            format_plain(&columns, &rows),
+            "id  name          note\n1   Ada           NULL\n10  Grace Hopper  two\\nlines\n"
+        );
+    }
+
+    #[test]
+    fn test_render_result() {
+        let result = QueryResult {
+            columns: vec!["id".to_string(), "name".to_string()],
+            rows: vec![
+                vec![Value::Integer(1), Value::Text("Ada".to_string())],
+                vec![Value::Integer(2), Value::Null],
+            ],
+            rows_affected: 0,
+        };
+        assert_eq!(
+            render_result(&OutputFormat::Csv, &result).unwrap(),
+            "id,name\n1,Ada\n2,NULL\n"
+        );
+        let json = render_result(&OutputFormat::Json, &result).unwrap();
+        assert!(json.contains("\"row_count\": 2"));
+        let table = render_result(&OutputFormat::Table, &result).unwrap();
+        assert!(table.starts_with('┌'));
+        assert!(table.contains("│ 1  │ Ada  │"));
+
+        let columns = [ColumnDescription {
+            name: "id".to_string(),
+            declared_type: "INTEGER".to_string(),
+            not_null: false,
+            default_value: None,
+            primary_key: true,
+        }];
+        assert!(render_columns(&columns)
+            .contains("│ 0  │ id   │ INTEGER │ NO       │ NULL          │ YES         │"));
+    }
+}
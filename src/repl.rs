@@ -13,24 +13,100 @@
 //! - **Query Bookmarking**: Save, list, and run frequently used queries.
 //! - **Non-Interactive Mode**: Can execute SQL from piped input (e.g., `cat query.sql | vapor-cli repl ...`).
 //! - **Robust Error Handling**: Provides informative error messages and offers to reconnect on critical failures.
+//! - **SQL Scripts**: `.read FILE` (or `.source FILE`) runs a script statement by statement,
+//!   and `repl --init FILE` runs one before the session starts.
+//! - **Automation Scripts**: With the `scripting` feature, `.script run FILE` runs a Rhai
+//!   script that can loop over query results, branch, and export or import files.
+//! - **Startup File**: Interactive sessions run the dot-commands and SQL in `~/.vapor/vaporrc`
+//!   first, unless started with `--no-rc`.
+//! - **Hooks**: SQL from `~/.vapor/hooks.json` runs when a local session starts, before
+//!   each statement, and at exit; `.show settings` lists it with the other settings.
+//! - **Exit Tidy-up**: Leaving a local session runs `PRAGMA optimize` and a passive WAL
+//!   checkpoint, unless `VAPOR_OPTIMIZE_ON_EXIT=off`.
+//! - **Remote Databases**: With the `remote` feature, a `libsql://` or `https://` URL opens a
+//!   REPL against a hosted database with the same output commands and formats.
+//! - **Safe Mode**: `.safe on` asks before running a `DELETE` or `UPDATE` without a `WHERE`
+//!   clause, a `DROP`, or an `ALTER` outside a transaction.
+//! - **Write Batching**: `.autocommit off --batch N` commits consecutive `INSERT`, `UPDATE`,
+//!   and `DELETE` statements in transactions of N, which speeds up pasted scripts.
+//! - **Error Policy**: `.onerror rollback` rolls back a transaction when one of its statements
+//!   fails, and `.onerror abort` refuses further statements until `ROLLBACK` (shown as `!>`).
 
 use anyhow::{Context, Result};
 use atty::Stream;
 use rusqlite::Connection;
-use rustyline::DefaultEditor;
+use rustyline::history::DefaultHistory;
+use rustyline::Editor;
 use std::io::{Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
+use crate::backend::{is_remote_target, open_backend, Backend};
 use crate::bookmarks::BookmarkManager;
+use crate::browse::browse_tables;
+use crate::compression::Compression;
 use crate::config;
-use crate::db::list_tables;
+use crate::copy::{copy_table, CopyOptions};
+use crate::db::{
+    attached_databases, backup_connection, checkpoint, create_table_sql, get_all_table_names,
+    get_table_names, optimize_on_exit, pragma_statement, print_backup_progress,
+    print_pragma_settings, print_table_list, run_maintenance, schema_version, set_foreign_keys,
+    set_journal_mode, set_schema_version, wal_size, CheckpointMode, ConnectionOptions,
+    CreateTableOptions, GeneratedColumn, Maintenance,
+};
+use crate::diff::{
+    diff_schemas, diff_table_data, open_read_only, DataDiffOptions, DEFAULT_DIFF_ROWS,
+};
 use crate::display::{
-    execute_sql, show_all_schemas, show_database_info, show_table_schema, OutputFormat,
-    QueryOptions,
+    database_info, execute_sql, execute_sql_with_last, output_path, print_records, reset_output,
+    set_output_file, set_output_pipe, show_all_schemas, show_cell, show_table_constraints,
+    show_table_counts, show_table_schema, LastQuery, OutputFormat, QueryOptions,
+};
+use crate::export::{
+    export_database_to_csv_dir, import_csv_to_table_with_options, parse_csv_char, CsvImportOptions,
+    ExportOptions, LineEnding, TextEncoding,
+};
+use crate::formats::{
+    export_backend_query, export_query_with_options, export_result, import_file_with_options,
+    quote_identifier, registry, FileImportOptions, Record,
+};
+use crate::highlight::SqlHelper;
+use crate::hooks::{run_hooks, session_hooks, Hooks};
+use crate::import::{
+    import_json_to_table, import_xml_to_table, is_json_path, is_xml_path, ColumnSetting,
+    ConflictPolicy, JsonImportOptions, XmlImportOptions,
+};
+use crate::integrity::{
+    check_integrity, foreign_key_violations, print_foreign_key_violations, DEFAULT_MAX_ERRORS,
+};
+use crate::jobs::job_manager;
+use crate::notes::{add_note, delete_note, list_notes, notes_select_query};
+use crate::profiles::Profiles;
+use crate::queue::query_queue;
+use crate::render::render_columns;
+use crate::script::{run_sql_file, ScriptOptions};
+use crate::search::find_text;
+use crate::shell::{catch_ctrl_c, ctrl_c_pressed};
+use crate::sniff::sniff_csv_file;
+use crate::softdelete::{disable_soft_delete, enable_soft_delete, soft_delete_counts};
+use crate::statement::{
+    classify_with_verb, destructive_reason, is_complete_input, is_write_statement, parse_drop,
+    split_statements, DropStatement, StatementKind,
+};
+use crate::strict::{apply_strictify_statements, strictify_statements, type_mismatches};
+use crate::timestamps::{apply_timestamp_statements, timestamp_statements};
+use crate::tracking::{
+    install_change_triggers, is_polling, poll_changes, print_table_changes, remove_change_triggers,
+    start_polling, stop_polling, trackable_tables, tracked_tables, CHANGES_TABLE,
 };
-use crate::export::{export_to_csv, import_csv_to_table};
-use crate::transactions::TransactionManager;
+use crate::transactions::{ErrorPolicy, TransactionManager};
+use crate::transcript::{
+    add_session_note, note_command, read_session_notes, record_command, session_id,
+    start_transcript, stop_transcript, transcript_path,
+};
+use crate::usage::UsageStats;
+use crate::variables::session_variables;
 
 /// Starts the interactive SQL REPL session.
 ///
@@ -48,6 +124,136 @@ use crate::transactions::TransactionManager;
 /// A `Result` which is `Ok(())` when the REPL exits gracefully, or an `Err` with
 /// context if a critical error occurs that cannot be handled.
 pub fn repl_mode(db_path: &str) -> Result<()> {
+    repl_mode_with_options(db_path, &ReplOptions::default())
+}
+
+/// Options for starting an interactive REPL session.
+#[derive(Debug, Clone)]
+pub struct ReplOptions {
+    /// A SQL script to run before the session starts.
+    pub init: Option<PathBuf>,
+    /// Whether to run the startup file, `~/.vapor/vaporrc`, when the session starts.
+    pub rc_file: bool,
+}
+
+impl Default for ReplOptions {
+    fn default() -> Self {
+        Self {
+            init: None,
+            rc_file: true,
+        }
+    }
+}
+
+/// Runs SQL against a database and exits, for `repl --command`.
+///
+/// Statements run in order and stop at the first error. Only the results are printed,
+/// without row counts, timing, or a row limit, so the output can be consumed by scripts.
+///
+/// # Arguments
+///
+/// * `db_path` - The database file, or a remote database URL.
+/// * `sql` - One or more SQL statements.
+/// * `init` - An optional SQL script to run first.
+/// * `format` - The output format for query results.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` if every statement succeeded, or an `Err` for the first
+/// one that failed.
+pub fn command_mode(
+    db_path: &str,
+    sql: &str,
+    init: Option<&Path>,
+    format: OutputFormat,
+) -> Result<()> {
+    if !is_remote_target(db_path) && !Path::new(db_path).exists() {
+        anyhow::bail!(
+            "Database '{}' does not exist. Use 'vapor-cli init --name {}' to create it.",
+            db_path,
+            db_path.trim_end_matches(".db")
+        );
+    }
+    let backend = open_backend(db_path)?;
+    // Hooks set up local connections; a hosted database manages its own settings
+    let hooks = if is_remote_target(db_path) {
+        Hooks::default()
+    } else {
+        Hooks::load()
+    };
+    run_hooks(backend.as_ref(), "on_start", &hooks.on_start);
+    if let Some(init) = init {
+        run_init_script(backend.as_ref(), init)?;
+    }
+    let query_options = QueryOptions {
+        format,
+        max_rows: None,
+        show_timing: false,
+        quiet: true,
+    };
+    let last_select_query = Arc::new(Mutex::new(LastQuery::default()));
+    let result = split_statements(sql)
+        .into_iter()
+        .try_for_each(|(_, statement)| {
+            run_hooks(backend.as_ref(), "before_query", &hooks.before_query);
+            execute_sql_with_last(
+                backend.as_ref(),
+                statement,
+                &query_options,
+                &last_select_query,
+            )
+        });
+    run_hooks(backend.as_ref(), "on_exit", &hooks.on_exit);
+    if !is_remote_target(db_path) && config::get_optimize_on_exit() {
+        if let Err(e) = optimize_on_exit(backend.as_ref()) {
+            eprintln!("Warning: {:#}", e);
+        }
+    }
+    result
+}
+
+/// Starts the interactive SQL REPL session after running a SQL script.
+///
+/// The script runs before any input is read, for both interactive and piped sessions.
+/// Statements that fail are reported and skipped, as with `.read`.
+///
+/// # Arguments
+///
+/// * `db_path` - The file path to the SQLite database.
+/// * `init` - An optional SQL script to run first.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` when the REPL exits gracefully, or an `Err` if the
+/// script can't be read or a critical error occurs.
+pub fn repl_mode_with_init(db_path: &str, init: Option<&Path>) -> Result<()> {
+    let options = ReplOptions {
+        init: init.map(Path::to_path_buf),
+        ..Default::default()
+    };
+    repl_mode_with_options(db_path, &options)
+}
+
+/// Starts the interactive SQL REPL session with the given options.
+///
+/// The `--init` script runs first, for both interactive and piped sessions. Interactive
+/// sessions then run the startup file, so preferences like `.format` and `.timing`
+/// apply from the first prompt.
+///
+/// # Arguments
+///
+/// * `db_path` - The file path to the SQLite database, or a remote database URL.
+/// * `options` - The init script and whether to run the startup file.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` when the REPL exits gracefully, or an `Err` if the
+/// script can't be read or a critical error occurs.
+pub fn repl_mode_with_options(db_path: &str, options: &ReplOptions) -> Result<()> {
+    if is_remote_target(db_path) {
+        return remote_repl_mode(db_path, options);
+    }
+
     // Convert to absolute path
     let db_path = std::fs::canonicalize(db_path)
         .with_context(|| format!("Failed to resolve absolute path for database '{}'", db_path))?
@@ -68,11 +274,19 @@ pub fn repl_mode(db_path: &str) -> Result<()> {
     verify_database_file(&db_path)?;
 
     // Connect to the database with retry logic
-    let mut conn = create_robust_connection(&db_path)?;
+    let conn = create_robust_connection(&db_path)?;
+    *session_hooks().lock().unwrap() = Hooks::load();
+    run_session_hooks(&conn, "on_start");
+
+    if let Some(init) = &options.init {
+        run_init_script(&conn, init)?;
+    }
 
     // Handle non-interactive mode (piped input)
     if !atty::is(Stream::Stdin) {
-        return handle_non_interactive_mode(&conn);
+        let result = handle_non_interactive_mode(&conn);
+        end_session(&conn);
+        return result;
     }
 
     println!("Connected to database: {}", db_path);
@@ -80,98 +294,315 @@ pub fn repl_mode(db_path: &str) -> Result<()> {
     print_help_summary();
 
     // Initialize REPL components with error handling
-    let mut rl = match DefaultEditor::new() {
+    let mut rl = match new_editor() {
         Ok(editor) => editor,
         Err(e) => {
             eprintln!("Warning: Could not initialize readline editor: {}", e);
             eprintln!("   Falling back to basic input mode.");
-            return handle_basic_repl_mode(&conn);
+            let result = handle_basic_repl_mode(&conn);
+            end_session(&conn);
+            return result;
         }
     };
 
     // Load command history if available
-    let history_path = config::get_repl_history_path()?;
-    if rl.load_history(&history_path).is_err() {
-        // No history file yet is fine
+    let history_path = load_database_history(&mut rl, &db_path)?;
+    // The init script may have attached databases
+    refresh_attached_databases(&mut rl, &conn);
+
+    let mut target = LocalTarget {
+        conn,
+        db_path,
+        history_path,
+        bookmarks: Arc::new(Mutex::new(
+            BookmarkManager::new().with_context(|| "Failed to initialize bookmarks")?,
+        )),
+        last_select_query: Arc::new(Mutex::new(LastQuery::default())),
+        transaction_manager: TransactionManager::new(),
+        query_options: QueryOptions::for_stdout(),
+    };
+    run_interactive(&mut target, &mut rl, options.rc_file)
+}
+
+/// Runs the REPL against a remote database URL.
+///
+/// SQL and the dot-commands that don't depend on a local file (`.tables`, `.schema`,
+/// `.format`, `.limit`, `.timing`, and `.export`) behave as they do locally. Transactions
+/// are sent to the server as plain `BEGIN`/`COMMIT`/`ROLLBACK` statements.
+fn remote_repl_mode(url: &str, options: &ReplOptions) -> Result<()> {
+    let backend = open_backend(url)?;
+    if let Some(init) = &options.init {
+        run_init_script(backend.as_ref(), init)?;
+    }
+    let last_select_query = Arc::new(Mutex::new(LastQuery::default()));
+    let query_options = QueryOptions::for_stdout();
+
+    if !atty::is(Stream::Stdin) {
+        let mut input = String::new();
+        std::io::stdin().read_to_string(&mut input)?;
+        return execute_sql_with_last(backend.as_ref(), &input, &query_options, &last_select_query);
+    }
+
+    println!("Connected to remote database: {}", backend.describe());
+    print_help_summary();
+
+    let mut rl = new_editor().context("Failed to initialize readline editor")?;
+    let history_path = config::get_remote_history_path(url)?;
+    load_history_file(&mut rl, &history_path, &config::get_repl_history_path()?);
+    let mut target = RemoteTarget {
+        backend,
+        url: url.to_string(),
+        history_path,
+        last_select_query,
+        query_options,
+    };
+    run_interactive(&mut target, &mut rl, options.rc_file)
+}
+
+/// A database the interactive REPL runs commands against.
+///
+/// [`run_interactive`] owns the prompt loop, history, `.edit`, `.last`, `.history`,
+/// variables, and `.log`; a target decides what a complete command does and what
+/// happens between commands.
+trait ReplTarget {
+    /// The prompt for the next line of input.
+    fn prompt(&self, multi_line_input: &str) -> &'static str;
+
+    /// Runs one complete command. Returns `Ok(false)` to end the session.
+    fn run_command(&mut self, command: &str, rl: &mut ReplEditor) -> Result<bool>;
+
+    /// Called before each prompt.
+    fn before_prompt(&mut self) {}
+
+    /// Called after each command has run, whether it succeeded or not.
+    fn after_command(&mut self, _command: &str, _rl: &mut ReplEditor) {}
+
+    /// Whether the session can go on after a command failed with `error`.
+    fn recover(&mut self, _error: &anyhow::Error) -> bool {
+        true
+    }
+
+    /// Ends the session and saves the history.
+    fn finish(&mut self, rl: &mut ReplEditor) -> Result<()>;
+}
+
+/// A local database file, with transactions, bookmarks, and every dot-command.
+struct LocalTarget {
+    conn: Connection,
+    db_path: String,
+    history_path: PathBuf,
+    bookmarks: Arc<Mutex<BookmarkManager>>,
+    last_select_query: Arc<Mutex<LastQuery>>,
+    transaction_manager: TransactionManager,
+    query_options: QueryOptions,
+}
+
+impl ReplTarget for LocalTarget {
+    fn prompt(&self, multi_line_input: &str) -> &'static str {
+        get_prompt(multi_line_input, &self.transaction_manager)
+    }
+
+    fn run_command(&mut self, command: &str, rl: &mut ReplEditor) -> Result<bool> {
+        if command == ".use" || command.starts_with(".use ") {
+            return handle_use_command(
+                command,
+                rl,
+                &mut self.conn,
+                &mut self.db_path,
+                &mut self.history_path,
+                &self.transaction_manager,
+            )
+            .map(|()| true);
+        }
+        run_local_command(
+            command,
+            &mut self.conn,
+            &self.db_path,
+            &self.bookmarks,
+            &self.last_select_query,
+            &self.transaction_manager,
+            &mut self.query_options,
+        )
+    }
+
+    fn before_prompt(&mut self) {
+        report_finished_queue();
+        report_finished_jobs();
+        // A table that can't be read now (e.g. locked) is compared again at the next prompt
+        let _ = poll_changes(&self.conn);
+    }
+
+    fn after_command(&mut self, command: &str, rl: &mut ReplEditor) {
+        // Statements and scripts can attach and detach databases too
+        let lower = command.to_lowercase();
+        if lower.contains("attach") || lower.starts_with(".read") || lower.starts_with(".source") {
+            refresh_attached_databases(rl, &self.conn);
+        }
+    }
+
+    fn recover(&mut self, error: &anyhow::Error) -> bool {
+        !is_critical_error(error) || offer_reconnection(&self.db_path)
+    }
+
+    fn finish(&mut self, rl: &mut ReplEditor) -> Result<()> {
+        // Queued statements would be cut off if the process exited now
+        let mut queue = query_queue().lock().unwrap();
+        if queue.is_running() {
+            println!("Waiting for the queue to finish (Ctrl+C to abort)...");
+            if let Some(summary) = queue.wait() {
+                println!("Queue finished: {}", summary);
+            }
+        }
+        drop(queue);
+        let mut jobs = job_manager().lock().unwrap();
+        if jobs.running() > 0 {
+            println!(
+                "Waiting for {} background job(s) to finish (Ctrl+C to abort)...",
+                jobs.running()
+            );
+            jobs.wait_all();
+        }
+        drop(jobs);
+        report_finished_jobs();
+
+        cleanup_repl_session(
+            &self.conn,
+            &self.transaction_manager,
+            rl,
+            &self.history_path,
+        )
+    }
+}
+
+/// A remote database URL, with the dot-commands that don't depend on a local file.
+struct RemoteTarget {
+    backend: Box<dyn Backend>,
+    url: String,
+    history_path: PathBuf,
+    last_select_query: Arc<Mutex<LastQuery>>,
+    query_options: QueryOptions,
+}
+
+impl ReplTarget for RemoteTarget {
+    fn prompt(&self, multi_line_input: &str) -> &'static str {
+        if multi_line_input.is_empty() {
+            "> "
+        } else {
+            "... "
+        }
+    }
+
+    fn run_command(&mut self, command: &str, _rl: &mut ReplEditor) -> Result<bool> {
+        if command.starts_with('.') {
+            return handle_remote_command(
+                command,
+                self.backend.as_ref(),
+                &self.url,
+                &self.last_select_query,
+                &mut self.query_options,
+            );
+        }
+        execute_sql_with_last(
+            self.backend.as_ref(),
+            command,
+            &self.query_options,
+            &self.last_select_query,
+        )
+        .map(|()| true)
+    }
+
+    fn finish(&mut self, rl: &mut ReplEditor) -> Result<()> {
+        if let Err(e) = rl.save_history(&self.history_path) {
+            eprintln!("Warning: Could not save command history: {}", e);
+        }
+        Ok(())
+    }
+}
+
+/// Runs the startup file and then the interactive prompt loop against a target, until
+/// `.exit` or end of input.
+fn run_interactive(target: &mut dyn ReplTarget, rl: &mut ReplEditor, rc_file: bool) -> Result<()> {
+    if rc_file {
+        run_rc_file(|command| {
+            let result = run_target_command(target, rl, command);
+            target.after_command(command, rl);
+            result
+        });
     }
 
     let mut multi_line_input = String::new();
-    let last_select_query = Arc::new(Mutex::new(String::new()));
-    let bookmarks = Arc::new(Mutex::new(
-        BookmarkManager::new().with_context(|| "Failed to initialize bookmarks")?,
-    ));
-    let transaction_manager = TransactionManager::new();
-    let mut query_options = QueryOptions::default();
+    // Text to pre-fill the next prompt with, set by `.last`
+    let mut pending_edit: Option<String> = None;
 
     loop {
-        let prompt = get_prompt(&multi_line_input, &transaction_manager);
+        target.before_prompt();
+        let prompt = target.prompt(&multi_line_input);
 
-        let readline = rl.readline(prompt);
+        let readline = match pending_edit.take() {
+            Some(text) => rl.readline_with_initial(prompt, (&text, "")),
+            None => rl.readline(prompt),
+        };
         match readline {
             Ok(line) => {
                 // Add to history before extensive trimming or further processing
-                // to save exactly what the user typed, if not empty.
-                // We use line.as_ref() as add_history_entry expects a &str.
-                if !line.trim().is_empty() { // Check if line is not just whitespace before adding
-                    if let Err(err) = rl.add_history_entry(line.as_str()) {
-                        eprintln!("Warning: Could not add to history: {}", err);
-                    }
-                }
+                // to save exactly what the user typed.
+                record_history(rl, &line);
 
                 let line = line.trim(); // Now trim for command processing
                 if line.is_empty() && multi_line_input.is_empty() {
                     continue;
                 }
+                if line == ".edit" {
+                    UsageStats::record(line);
+                    pending_edit = edit_statement(rl, &mut multi_line_input);
+                    continue;
+                }
 
                 // Handle multi-line input
-                let command_to_execute = handle_multi_line_input(&mut multi_line_input, line);
-
-                if let Some(command) = command_to_execute {
-                    let command_trimmed = command.trim();
-                    let result = if command_trimmed.starts_with('.') {
-                        match handle_special_commands(
-                            command_trimmed,
-                            &mut conn,
-                            &db_path,
-                            &bookmarks,
-                            &last_select_query,
-                            &transaction_manager,
-                            &mut query_options,
-                        ) {
-                            Ok(should_continue) => {
-                                if !should_continue {
-                                    break; // Exit the REPL loop
-                                }
-                                Ok(())
-                            }
-                            Err(e) => Err(e), // Propagate other errors
-                        }
-                    } else {
-                        match transaction_manager.handle_sql_command(&conn, command_trimmed) {
-                            Ok(true) => Ok(()), // Command was handled, do nothing more.
-                            Ok(false) => {
-                                // Not a transaction command, execute normally.
-                                handle_single_line_command(
-                                    command_trimmed,
-                                    &mut conn,
-                                    &transaction_manager,
-                                    &mut query_options,
-                                    &last_select_query,
-                                )
-                            }
-                            Err(e) => Err(e), // Propagate error.
-                        }
-                    };
+                let Some(command) = handle_multi_line_input(&mut multi_line_input, line) else {
+                    continue;
+                };
+                if command.starts_with('.') {
+                    UsageStats::record(&command);
+                }
+                if command == ".last" {
+                    pending_edit = last_statement(rl);
+                    continue;
+                }
+                let command = if command.starts_with(".history") {
+                    match history_command(rl, &command) {
+                        Some(entry) => entry,
+                        None => continue,
+                    }
+                } else {
+                    command
+                };
+                let Some(command) = expand_variables(&command) else {
+                    continue;
+                };
+                let command = command.trim();
+                let result = log_command(command, || {
+                    let result = run_target_command(target, rl, command);
+                    if let Err(e) = &result {
+                        print_command_error(command, e);
+                    }
+                    result
+                });
 
-                    if let Err(e) = result {
-                        print_command_error(&command, &e);
-                        if is_critical_error(&e) {
-                            if !offer_reconnection(&db_path) {
-                                break; // Exit REPL
-                            }
+                match result {
+                    Ok(false) => break, // Exit the REPL loop
+                    Ok(true) => {
+                        if let Some(tip) = UsageStats::tip_after(command) {
+                            println!("Tip: {} (.tips off to stop these)", tip);
+                        }
+                    }
+                    Err(e) => {
+                        if !target.recover(&e) {
+                            break; // Exit REPL
                         }
                     }
                 }
+                target.after_command(command, rl);
             }
             Err(rustyline::error::ReadlineError::Interrupted) => {
                 println!("^C");
@@ -189,12 +620,301 @@ pub fn repl_mode(db_path: &str) -> Result<()> {
         }
     }
 
-    // Cleanup on exit
-    cleanup_repl_session(&conn, &transaction_manager, &mut rl, &history_path)?;
+    target.finish(rl)?;
+    stop_transcript();
     println!("Goodbye!");
     Ok(())
 }
 
+/// Runs one command against a target. `.highlight` changes the line editor, so it works
+/// the same for every target.
+fn run_target_command(
+    target: &mut dyn ReplTarget,
+    rl: &mut ReplEditor,
+    command: &str,
+) -> Result<bool> {
+    if command.starts_with(".highlight") {
+        handle_highlight_command(rl, command);
+        return Ok(true);
+    }
+    target.run_command(command, rl)
+}
+
+/// The line editor used by the REPL, with SQL highlighting.
+type ReplEditor = Editor<SqlHelper, DefaultHistory>;
+
+fn new_editor() -> rustyline::Result<ReplEditor> {
+    // Ctrl+R reverse search comes with rustyline's default (emacs) key bindings
+    let config = rustyline::Config::builder()
+        .max_history_size(config::get_repl_history_size())?
+        .history_ignore_dups(true)?
+        .build();
+    let mut rl = ReplEditor::with_config(config)?;
+    rl.set_helper(Some(SqlHelper::default()));
+    Ok(rl)
+}
+
+/// Loads the history of one database into the editor. Returns the path to save the
+/// history to.
+fn load_database_history(rl: &mut ReplEditor, db_path: &str) -> Result<PathBuf> {
+    let history_path = config::get_database_history_path(db_path)?;
+    load_history_file(rl, &history_path, &config::get_repl_history_path()?);
+    Ok(history_path)
+}
+
+/// Loads a history file into the editor. A database without a history file of its own
+/// starts from `global_path`, the single REPL history kept before each database got its
+/// own, so upgrading doesn't lose it.
+fn load_history_file(rl: &mut ReplEditor, history_path: &Path, global_path: &Path) {
+    if !history_path.exists() && global_path.exists() {
+        if let Err(e) = std::fs::copy(global_path, history_path) {
+            eprintln!(
+                "Warning: Failed to copy the REPL history from {}: {}",
+                global_path.display(),
+                e
+            );
+        }
+    }
+    if rl.load_history(history_path).is_err() {
+        // No history file yet is fine
+    }
+}
+
+/// Returns the most recent SQL statement in the history, for `.last` to put back in the
+/// prompt.
+fn last_statement(rl: &ReplEditor) -> Option<String> {
+    let statement = rl
+        .history()
+        .iter()
+        .rev()
+        .find(|entry| !entry.trim().is_empty() && !entry.trim_start().starts_with('.'))
+        .cloned();
+    if statement.is_none() {
+        println!("No previous statement in this database's history.");
+    }
+    statement
+}
+
+/// Handles `.edit`: opens the statement being typed, or else the previous one, in the
+/// user's editor. Returns the edited text to pre-fill the next prompt with.
+fn edit_statement(rl: &ReplEditor, multi_line_input: &mut String) -> Option<String> {
+    let current = if multi_line_input.is_empty() {
+        last_statement(rl).unwrap_or_default()
+    } else {
+        std::mem::take(multi_line_input)
+    };
+    match edit_in_editor(&current) {
+        Ok(edited) if edited.trim().is_empty() => {
+            println!("Editor returned an empty buffer; nothing to run.");
+            None
+        }
+        Ok(edited) => Some(edited.trim_end().to_string()),
+        Err(e) => {
+            print_command_error(".edit", &e);
+            None
+        }
+    }
+}
+
+/// Opens `text` in `$VISUAL` or `$EDITOR` (falling back to `vi`) and returns the saved
+/// buffer.
+fn edit_in_editor(text: &str) -> Result<String> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    // Allow editors that need flags, e.g. EDITOR="code --wait"
+    let mut words = editor.split_whitespace();
+    let program = words.next().context("$EDITOR is empty")?;
+
+    let mut file = tempfile::Builder::new()
+        .prefix("vapor-edit-")
+        .suffix(".sql")
+        .tempfile()
+        .context("Failed to create a temporary file to edit")?;
+    writeln!(file, "{}", text).context("Failed to write the temporary file")?;
+    file.flush()?;
+
+    let status = std::process::Command::new(program)
+        .args(words)
+        .arg(file.path())
+        .status()
+        .with_context(|| format!("Failed to start editor '{}'", editor))?;
+    if !status.success() {
+        anyhow::bail!("Editor '{}' exited with {}", editor, status);
+    }
+    std::fs::read_to_string(file.path()).context("Failed to read the edited file")
+}
+
+/// Adds a line to the history unless it is empty or a `.history` command. Those are
+/// left out so that adding one to a full history, which drops the oldest entry, can't
+/// shift the numbers `.history run N` refers to.
+fn record_history(rl: &mut ReplEditor, line: &str) {
+    let trimmed = line.trim();
+    if trimmed.is_empty() || trimmed.split_whitespace().next() == Some(".history") {
+        return;
+    }
+    if let Err(err) = rl.add_history_entry(line) {
+        eprintln!("Warning: Could not add to history: {}", err);
+    }
+}
+
+/// Handles `.history [N]` and `.history run N`.
+///
+/// Lists the last N history entries (default: 20) with their numbers, or returns entry N
+/// so the caller can run it again.
+fn history_command(rl: &mut ReplEditor, command: &str) -> Option<String> {
+    let args: Vec<&str> = command.split_whitespace().skip(1).collect();
+    let entries: Vec<&String> = rl.history().iter().collect();
+    match args.as_slice() {
+        ["run", number] => match number.parse::<usize>() {
+            Ok(n) if n >= 1 && n <= entries.len() => {
+                let entry = entries[n - 1].clone();
+                println!("{}", entry);
+                if let Err(err) = rl.add_history_entry(entry.as_str()) {
+                    eprintln!("Warning: Could not add to history: {}", err);
+                }
+                return Some(entry);
+            }
+            _ => println!("No history entry {}. Use .history to list entries.", number),
+        },
+        [] | [_] => {
+            let count = match args.first().map(|n| n.parse::<usize>()) {
+                None => 20,
+                Some(Ok(n)) => n,
+                Some(Err(_)) => {
+                    println!("Usage: .history [N] | .history run N");
+                    return None;
+                }
+            };
+            let start = entries.len().saturating_sub(count);
+            for (i, entry) in entries.iter().enumerate().skip(start) {
+                println!("{:>5}  {}", i + 1, entry.replace('\n', "\n       "));
+            }
+        }
+        _ => println!("Usage: .history [N] | .history run N"),
+    }
+    None
+}
+
+/// Handles `.highlight [on|off]`, which toggles syntax highlighting in the prompt.
+fn handle_highlight_command(rl: &mut ReplEditor, command: &str) {
+    let Some(helper) = rl.helper_mut() else {
+        return;
+    };
+    match command.split_whitespace().nth(1) {
+        Some("on") => {
+            helper.enabled = true;
+            println!("Syntax highlighting enabled");
+        }
+        Some("off") => {
+            helper.enabled = false;
+            println!("Syntax highlighting disabled");
+        }
+        _ => {
+            let state = if helper.enabled { "on" } else { "off" };
+            println!("Syntax highlighting is {}", state);
+            println!("Usage: .highlight [on|off]");
+        }
+    }
+}
+
+/// Handles a dot-command in the remote REPL. Returns `Ok(false)` to exit.
+fn handle_remote_command(
+    command: &str,
+    backend: &dyn Backend,
+    url: &str,
+    last_select_query: &Arc<Mutex<LastQuery>>,
+    query_options: &mut QueryOptions,
+) -> Result<bool> {
+    let parts: Vec<&str> = command.split_whitespace().collect();
+    match parts.first().copied().unwrap_or("") {
+        ".help" => match parts[1..] {
+            [name] => print_command_help(name),
+            _ => show_help(),
+        },
+        name if parts[1..] == ["?"] => print_command_help(name),
+        ".exit" | ".quit" => return Ok(false),
+        ".tables" => {
+            for table in backend.table_names()? {
+                println!("{}", table);
+            }
+        }
+        ".schema" => match parts.get(1) {
+            // The same column listing as a local `.schema TABLE`
+            Some(table) => {
+                let columns = backend.columns(table)?;
+                if columns.is_empty() {
+                    println!("Table '{}' does not exist.", table);
+                } else {
+                    println!("Schema for table '{}':", table);
+                    print!("{}", render_columns(&columns));
+                }
+            }
+            None => {
+                let sql = "SELECT sql FROM sqlite_master WHERE sql IS NOT NULL AND name NOT LIKE 'sqlite_%' ORDER BY name";
+                for row in backend.run(sql, None)?.rows {
+                    if let Some(rusqlite::types::Value::Text(sql)) = row.first() {
+                        println!("{};", sql);
+                    }
+                }
+            }
+        },
+        ".format" | ".limit" | ".timing" | ".notiming" => {
+            handle_output_command(&parts, query_options);
+        }
+        ".output" | ".once" | ".pipe" => handle_redirect_command(command)?,
+        ".cell" | ".more" => handle_cell_command(&parts)?,
+        ".clear" => {
+            print!("\x1B[2J\x1B[1;1H");
+            std::io::stdout().flush()?;
+        }
+        ".read" | ".source" => {
+            handle_read_command(backend, &parts, query_options, last_select_query)?;
+        }
+        ".watch" => handle_watch_command(backend, command, query_options, last_select_query)?,
+        ".set" | ".unset" | ".vars" => handle_variable_command(command)?,
+        ".log" => handle_log_command(&parts, url)?,
+        ".use" => println!("'.use' switches between local database files; start a new session for another remote database."),
+        ".export" => {
+            let (args, bom) = take_flag(&parts[1..], "--bom");
+            let (args, background) = take_flag(&args, "--background");
+            if background {
+                println!("Background exports are only available for local databases.");
+                return Ok(true);
+            }
+            let (args, compression) = take_flag_value(&args, "--compress")?;
+            let Some(&filename) = args.first() else {
+                println!("Usage: .export FILENAME [FORMAT] [--compress gzip|zstd] [--bom]");
+                return Ok(true);
+            };
+            let LastQuery {
+                sql: query,
+                returned,
+            } = last_select_query.lock().unwrap().clone();
+            if query.is_empty() && returned.is_none() {
+                println!("No SELECT query has been executed yet.");
+                return Ok(true);
+            }
+            let format = match args.get(1) {
+                Some(name) => Some(*name),
+                None if registry().for_path(filename).is_none() => Some("csv"),
+                None => None,
+            };
+            let options = ExportOptions {
+                compression: compression.map(Compression::parse).transpose()?,
+                bom,
+                ..Default::default()
+            };
+            match returned {
+                Some(result) => export_result(&result, filename, format, &options)?,
+                None => export_backend_query(backend, &query, filename, format, &options)?,
+            };
+        }
+        other => println!("'{}' is not available for remote databases.", other),
+    }
+    Ok(true)
+}
+
 fn verify_database_file(db_path: &str) -> Result<()> {
     let metadata = std::fs::metadata(db_path)
         .with_context(|| format!("Cannot read database file '{}'", db_path))?;
@@ -215,11 +935,13 @@ fn create_robust_connection(db_path: &str) -> Result<Connection> {
     let max_retries = 3;
 
     for attempt in 1..=max_retries {
-        match Connection::open(db_path) {
+        match ConnectionOptions::current().open_connection(db_path) {
             Ok(conn) => {
                 if attempt > 1 {
                     println!("Connection succeeded on attempt {}", attempt);
                 }
+                // Set either way, since SQLite's default depends on how it was built
+                set_foreign_keys(&conn, config::get_foreign_keys())?;
                 return Ok(conn);
             }
             Err(e) => {
@@ -239,10 +961,35 @@ fn create_robust_connection(db_path: &str) -> Result<Connection> {
         ))
 }
 
+/// Runs the session's hooks for a stage (`on_start`, `before_query`, or `on_exit`).
+fn run_session_hooks(conn: &Connection, stage: &str) {
+    let hooks = session_hooks().lock().unwrap();
+    let statements = match stage {
+        "on_start" => &hooks.on_start,
+        "before_query" => &hooks.before_query,
+        _ => &hooks.on_exit,
+    };
+    run_hooks(conn, stage, statements);
+}
+
+/// Ends a session on a database: runs the exit hooks, then `PRAGMA optimize` and a WAL
+/// checkpoint unless `VAPOR_OPTIMIZE_ON_EXIT` turns them off.
+fn end_session(conn: &Connection) {
+    if let Err(e) = flush_write_batch(conn) {
+        eprintln!("Warning: {:#}", e);
+    }
+    run_session_hooks(conn, "on_exit");
+    if config::get_optimize_on_exit() {
+        if let Err(e) = optimize_on_exit(conn) {
+            eprintln!("Warning: {:#}", e);
+        }
+    }
+}
+
 fn handle_non_interactive_mode(conn: &Connection) -> Result<()> {
     let mut input = String::new();
     std::io::stdin().read_to_string(&mut input)?;
-    let options = QueryOptions::default(); // Use default options for non-interactive mode
+    let options = QueryOptions::for_stdout();
     let dummy_last_query = Arc::new(Mutex::new(String::new()));
     execute_sql(conn, &input, &options, &dummy_last_query)
 }
@@ -250,7 +997,7 @@ fn handle_non_interactive_mode(conn: &Connection) -> Result<()> {
 fn handle_basic_repl_mode(conn: &Connection) -> Result<()> {
     println!("Basic input mode (no history or advanced features).");
     let mut stdout = std::io::stdout();
-    let options = QueryOptions::default(); // Use default options for basic mode
+    let options = QueryOptions::for_stdout();
     let dummy_last_query = Arc::new(Mutex::new(String::new()));
 
     loop {
@@ -266,6 +1013,7 @@ fn handle_basic_repl_mode(conn: &Connection) -> Result<()> {
             break;
         }
         if !line.is_empty() {
+            run_session_hooks(conn, "before_query");
             if let Err(e) = execute_sql(conn, line, &options, &dummy_last_query) {
                 eprintln!("Error: {}", e);
             }
@@ -274,64 +1022,527 @@ fn handle_basic_repl_mode(conn: &Connection) -> Result<()> {
     Ok(())
 }
 
-fn get_prompt(multi_line_input: &str, transaction_manager: &TransactionManager) -> &'static str {
-    if multi_line_input.is_empty() {
-        if transaction_manager.is_active() {
-            "*> "
-        } else {
-            "> "
-        }
-    } else {
-        "... "
-    }
+/// Whether `.safe on` is in effect: destructive statements outside a transaction are
+/// confirmed before they run.
+static SAFE_MODE: AtomicBool = AtomicBool::new(false);
+
+/// The number of statements per transaction when `.autocommit off` doesn't give one.
+const DEFAULT_WRITE_BATCH: usize = 1000;
+
+/// Consecutive write statements committed together while `.autocommit off` is in effect.
+#[derive(Debug)]
+struct WriteBatch {
+    /// Statements per transaction, or `None` while autocommit is on.
+    size: Option<usize>,
+    /// Statements run in the open batch transaction.
+    pending: usize,
 }
 
-fn handle_multi_line_input(multi_line_input: &mut String, line: &str) -> Option<String> {
-    if !multi_line_input.is_empty() {
-        multi_line_input.push_str(" ");
-        multi_line_input.push_str(line);
-        if line.ends_with(';') {
-            let command = multi_line_input.trim().to_string();
-            multi_line_input.clear();
-            Some(command)
+static WRITE_BATCH: Mutex<WriteBatch> = Mutex::new(WriteBatch {
+    size: None,
+    pending: 0,
+});
+
+/// Commits the open batch of write statements, if there is one.
+fn flush_write_batch(conn: &Connection) -> Result<()> {
+    let mut batch = WRITE_BATCH.lock().unwrap();
+    let pending = std::mem::take(&mut batch.pending);
+    // A failed statement may have made SQLite roll the batch back already
+    if pending > 0 && !conn.is_autocommit() {
+        conn.execute_batch("COMMIT")
+            .with_context(|| format!("Failed to commit the batch of {} writes", pending))?;
+    }
+    Ok(())
+}
+
+/// Counts a statement run in the batch transaction, committing the batch once it is full.
+fn count_batched_write(conn: &Connection) -> Result<()> {
+    let full = {
+        let mut batch = WRITE_BATCH.lock().unwrap();
+        batch.pending += 1;
+        batch.pending >= batch.size.unwrap_or(1)
+    };
+    if full || conn.is_autocommit() {
+        flush_write_batch(conn)?;
+    }
+    Ok(())
+}
+
+fn get_prompt(multi_line_input: &str, transaction_manager: &TransactionManager) -> &'static str {
+    if multi_line_input.is_empty() {
+        if transaction_manager.is_aborted() {
+            "!> "
+        } else if transaction_manager.is_active() {
+            "*> "
         } else {
-            None
+            "> "
         }
-    } else if line.ends_with(';') || is_complete_command(line) {
-        Some(line.to_string())
     } else {
-        multi_line_input.push_str(line);
-        None
+        "... "
+    }
+}
+
+/// Runs a complete dot-command or SQL statement in the local REPL. Returns `Ok(false)`
+/// to exit.
+fn run_local_command(
+    command: &str,
+    conn: &mut Connection,
+    db_path: &str,
+    bookmarks: &Arc<Mutex<BookmarkManager>>,
+    last_select_query: &Arc<Mutex<LastQuery>>,
+    transaction_manager: &TransactionManager,
+    query_options: &mut QueryOptions,
+) -> Result<bool> {
+    if command.starts_with('.') {
+        flush_write_batch(conn)?;
+        return handle_special_commands(
+            command,
+            conn,
+            db_path,
+            bookmarks,
+            last_select_query,
+            transaction_manager,
+            query_options,
+        );
+    }
+    // With `.autocommit off`, writes outside an explicit transaction are batched
+    let batched = WRITE_BATCH.lock().unwrap().size.is_some()
+        && !transaction_manager.is_active()
+        && is_write_statement(command);
+    if !batched {
+        flush_write_batch(conn)?;
+    }
+    // DROP statements confirm themselves once they know what they drop exists
+    let drop = parse_drop(command);
+    if SAFE_MODE.load(Ordering::Relaxed) && (conn.is_autocommit() || batched) && drop.is_none() {
+        if let Some(reason) = destructive_reason(command) {
+            let question = format!(
+                "This runs {} outside a transaction and can't be undone. Run it?",
+                reason
+            );
+            if !confirm(&question) {
+                println!("Statement not run.");
+                return Ok(true);
+            }
+        }
+    }
+    if transaction_manager.is_aborted() && !ends_transaction(command) {
+        println!("The current transaction is aborted; statements are ignored until ROLLBACK.");
+        return Ok(true);
+    }
+    if SAFE_MODE.load(Ordering::Relaxed) && commits_transaction(command) {
+        if let Some(stats) = transaction_manager
+            .stats(conn)
+            .filter(|stats| stats.rows_changed > 0 && !transaction_manager.is_aborted())
+        {
+            let question = format!(
+                "COMMIT will persist {} statement(s) changing {} row(s). Proceed?",
+                stats.statements, stats.rows_changed
+            );
+            if !confirm(&question) {
+                println!("Not committed; the transaction is still open.");
+                return Ok(true);
+            }
+        }
+    }
+    run_session_hooks(conn, "before_query");
+    // Transaction commands are handled by the transaction manager
+    if !transaction_manager.handle_sql_command(conn, command)? {
+        if batched && conn.is_autocommit() {
+            conn.execute_batch("BEGIN")
+                .context("Failed to begin a batch of writes")?;
+        }
+        let result = match &drop {
+            Some(drop) => {
+                let confirm_first = SAFE_MODE.load(Ordering::Relaxed) && conn.is_autocommit();
+                run_drop(conn, drop, command, confirm_first, query_options)
+            }
+            None => handle_single_line_command(
+                command,
+                conn,
+                transaction_manager,
+                query_options,
+                last_select_query,
+            ),
+        };
+        if batched {
+            count_batched_write(conn)?;
+        } else {
+            transaction_manager.record_statement();
+            if result.is_err() {
+                transaction_manager.statement_failed(conn)?;
+            }
+        }
+        result?;
+    }
+    Ok(true)
+}
+
+/// Runs a `DROP` statement as it was typed, after checking that what it drops exists
+/// (unless it says `IF EXISTS`) and, with `confirm_first`, asking before dropping it.
+/// Like other statements, it is timed when `.timing` is on.
+fn run_drop(
+    conn: &Connection,
+    drop: &DropStatement,
+    sql: &str,
+    confirm_first: bool,
+    options: &QueryOptions,
+) -> Result<()> {
+    let noun = match drop.kind {
+        "TABLE" => "Table",
+        "INDEX" => "Index",
+        "VIEW" => "View",
+        _ => "Trigger",
+    };
+    if !schema_object_exists(conn, drop)? {
+        if drop.if_exists {
+            println!(
+                "{} '{}' does not exist; nothing was dropped",
+                noun, drop.name
+            );
+            return Ok(());
+        }
+        anyhow::bail!("{} '{}' does not exist", noun, drop.name);
+    }
+    if confirm_first {
+        let question = format!(
+            "This runs DROP {} outside a transaction and can't be undone. Run it?",
+            drop.kind
+        );
+        if !confirm(&question) {
+            println!("Statement not run.");
+            return Ok(());
+        }
+    }
+    let start_time = std::time::Instant::now();
+    conn.execute_batch(sql).with_context(|| {
+        format!(
+            "Failed to drop {} '{}'",
+            drop.kind.to_lowercase(),
+            drop.name
+        )
+    })?;
+    if !options.quiet {
+        println!("{} '{}' dropped successfully", noun, drop.name);
+        if options.show_timing {
+            println!(
+                "Query executed in {:.3}ms",
+                start_time.elapsed().as_secs_f64() * 1000.0
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Whether the table, index, view, or trigger a `DROP` names exists, in its schema or, like
+/// SQLite, in any attached database when it names none.
+fn schema_object_exists(conn: &Connection, drop: &DropStatement) -> Result<bool> {
+    let schemas = match &drop.schema {
+        Some(schema) => vec![schema.clone()],
+        None => conn
+            .prepare("SELECT name FROM pragma_database_list")?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?,
+    };
+    for schema in schemas {
+        let count: i64 = conn
+            .query_row(
+                &format!(
+                    "SELECT COUNT(*) FROM {}.sqlite_master WHERE type = ?1 AND name = ?2 COLLATE NOCASE",
+                    quote_identifier(&schema)
+                ),
+                rusqlite::params![drop.kind.to_lowercase(), drop.name],
+                |row| row.get(0),
+            )
+            .with_context(|| format!("Failed to check whether '{}' exists", drop.name))?;
+        if count > 0 {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Whether a statement is a `COMMIT` or `ROLLBACK`, which an aborted transaction accepts.
+fn ends_transaction(command: &str) -> bool {
+    match classify_with_verb(command) {
+        (StatementKind::Transaction, Some(verb)) => {
+            matches!(verb.as_str(), "COMMIT" | "END" | "ROLLBACK")
+        }
+        _ => false,
+    }
+}
+
+/// Returns `true` if `command` is a `COMMIT` (or `END`) statement.
+fn commits_transaction(command: &str) -> bool {
+    match classify_with_verb(command) {
+        (StatementKind::Transaction, Some(verb)) => matches!(verb.as_str(), "COMMIT" | "END"),
+        _ => false,
+    }
+}
+
+/// Runs the startup file (`~/.vapor/vaporrc`) of dot-commands and SQL, if there is one.
+///
+/// Lines are collected into commands the same way as typed input. A command that fails
+/// is reported with its line number and the rest of the file still runs.
+fn run_rc_file(mut run: impl FnMut(&str) -> Result<bool>) {
+    let Ok(path) = config::get_rc_path() else {
+        return;
+    };
+    let Ok(contents) = std::fs::read_to_string(&path) else {
+        return;
+    };
+    let mut multi_line_input = String::new();
+    let mut start_line = 0;
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if multi_line_input.is_empty() {
+            if line.is_empty() || line.starts_with("--") {
+                continue;
+            }
+            start_line = i + 1;
+        }
+        let Some(command) = handle_multi_line_input(&mut multi_line_input, line) else {
+            continue;
+        };
+        let expanded = session_variables().lock().unwrap().expand_command(&command);
+        let result = expanded.and_then(|command| run(&command));
+        if let Err(e) = result {
+            eprintln!("Warning: {} line {}: {:#}", path.display(), start_line, e);
+        }
+    }
+    if !multi_line_input.trim().is_empty() {
+        eprintln!(
+            "Warning: {} line {}: statement is missing its closing semicolon",
+            path.display(),
+            start_line
+        );
     }
 }
 
-fn is_complete_command(line: &str) -> bool {
-    let line_lower = line.to_lowercase();
-    // These commands don't need semicolons
-    matches!(
-        line_lower.as_str(),
-        "exit" | "quit" | "help" | "tables" | "clear" | "info"
-    ) || line_lower.starts_with("schema")
-        || line_lower.starts_with(".")
-        || line_lower.starts_with("begin")
-        || line_lower.starts_with("commit")
-        || line_lower.starts_with("rollback")
-        || line_lower.starts_with("drop")
+fn handle_multi_line_input(multi_line_input: &mut String, line: &str) -> Option<String> {
+    if !multi_line_input.is_empty() {
+        // Keep line breaks so a `--` comment doesn't swallow the lines after it
+        multi_line_input.push('\n');
+    }
+    multi_line_input.push_str(line);
+    if is_complete_input(multi_line_input) {
+        let command = multi_line_input.trim().to_string();
+        multi_line_input.clear();
+        Some(command)
+    } else {
+        None
+    }
 }
 
+/// Every dot-command, for tab completion.
+pub(crate) const DOT_COMMANDS: &[&str] = &[
+    ".help",
+    ".tables",
+    ".track",
+    ".tips",
+    ".diff",
+    ".schema",
+    ".info",
+    ".counts",
+    ".attach",
+    ".detach",
+    ".copy",
+    ".backup",
+    ".check",
+    ".pragma",
+    ".vacuum",
+    ".wal",
+    ".fk",
+    ".fkcheck",
+    ".checkpoint",
+    ".analyze",
+    ".reindex",
+    ".version",
+    ".describe",
+    ".peek",
+    ".note",
+    ".notes",
+    ".find",
+    ".browse",
+    ".softdelete",
+    ".timestamps",
+    ".create-table",
+    ".strictify",
+    ".typecheck",
+    ".format",
+    ".limit",
+    ".timing",
+    ".notiming",
+    ".output",
+    ".once",
+    ".pipe",
+    ".cell",
+    ".more",
+    ".highlight",
+    ".safe",
+    ".onerror",
+    ".autocommit",
+    ".history",
+    ".last",
+    ".edit",
+    ".export",
+    ".exportall",
+    ".import",
+    ".read",
+    ".source",
+    ".script",
+    ".queue",
+    ".jobs",
+    ".job",
+    ".watch",
+    ".set",
+    ".unset",
+    ".vars",
+    ".use",
+    ".log",
+    ".bookmark",
+    ".status",
+    ".show",
+    ".shell",
+    ".clear",
+    ".exit",
+    ".quit",
+];
+
+/// The commands in the short help shown at startup, with their descriptions.
+const HELP_SUMMARY: &[(&str, &str)] = &[
+    (
+        ".help [COMMAND]",
+        "Show this help message, or one command's (also .COMMAND ?)",
+    ),
+    (".tables", "List all tables"),
+    (
+        ".schema [table]",
+        "Show schema for all tables or specific table",
+    ),
+    (".info", "Show database information"),
+    (".counts", "Show row counts, sizes, and indexes per table"),
+    (".track", "Track when each table last changed"),
+    (
+        ".diff schema OTHER_DB",
+        "Compare this database's schema with another's",
+    ),
+    (
+        ".diff data LEFT RIGHT",
+        "Compare two tables' rows by primary key",
+    ),
+    (
+        ".attach PATH AS alias",
+        "Attach another database (.detach alias)",
+    ),
+    (
+        ".copy SOURCE TO TARGET",
+        "Copy rows between attached databases",
+    ),
+    (
+        ".queue add QUERY|run",
+        "Run queued statements in the background",
+    ),
+    (".jobs", "List background jobs (.job status N)"),
+    (
+        ".watch SECONDS QUERY",
+        "Re-run a query every few seconds until Ctrl+C",
+    ),
+    (
+        ".set NAME VALUE",
+        "Set a variable to use as ${NAME} (.vars)",
+    ),
+    (".use PATH|PROFILE", "Switch to another database file"),
+    (".log FILE|off", "Record commands and output to a file"),
+    (
+        ".version [set N]",
+        "Show or set the schema version (user_version)",
+    ),
+    (
+        ".describe TABLE",
+        "Show a table's columns, constraints, and row counts",
+    ),
+    (
+        ".peek TABLE [N]",
+        "Show the first N rows of a table (default: 10)",
+    ),
+    (".find PATTERN [TABLE]", "Find a value in any text column"),
+    (".browse", "Pick a table to explore, filtering as you type"),
+    (
+        ".note add|show|delete",
+        "Attach notes to rows (.help for details)",
+    ),
+    (
+        ".note session 'TEXT'",
+        "Note an observation in this session; .notes lists them",
+    ),
+    (
+        ".softdelete enable|disable TABLE",
+        "Mark deleted rows instead of removing them",
+    ),
+    (
+        ".timestamps TABLE",
+        "Add created_at/updated_at columns and triggers",
+    ),
+    (".create-table", "Create a table step by step"),
+    (
+        ".typecheck [TABLE]",
+        "Find values that don't match their column's type",
+    ),
+    (".strictify TABLE", "Rebuild a table as a STRICT table"),
+    (
+        ".format [type]",
+        "Set output format (table, json, csv, plain)",
+    ),
+    (".limit [n]", "Set row limit (0 for no limit)"),
+    (".timing", "Enable query timing"),
+    (".notiming", "Disable query timing"),
+    (
+        ".output FILE|stdout",
+        "Send query results to a file (.once FILE for one query)",
+    ),
+    (
+        ".pipe 'COMMAND'",
+        "Stream the next query's results into a shell command",
+    ),
+    (
+        ".cell [ROW] [COLUMN]",
+        "Show a value from the last result in full",
+    ),
+    (".highlight on|off", "Toggle SQL syntax highlighting"),
+    (
+        ".tips on|off",
+        "Show occasional tips about features you haven't used",
+    ),
+    (
+        ".safe on|off",
+        "Confirm destructive statements outside a transaction",
+    ),
+    (".last", "Edit the previous statement"),
+    (".edit", "Open the current or previous statement in $EDITOR"),
+    (
+        ".history [n]",
+        "Show recent history (.history run N re-runs entry N; Ctrl+R searches)",
+    ),
+    (
+        ".read FILE",
+        "Run a SQL script (--echo, --transaction, --bail)",
+    ),
+    (".clear", "Clear screen"),
+    (".exit/.quit", "Exit REPL"),
+];
+
 fn print_help_summary() {
     println!("Vapor CLI - SQLite Database Management");
     println!("\nSpecial Commands:");
-    println!("  .help              Show this help message");
-    println!("  .tables            List all tables");
-    println!("  .schema [table]    Show schema for all tables or specific table");
-    println!("  .info             Show database information");
-    println!("  .format [type]    Set output format (table, json, csv)");
-    println!("  .limit [n]        Set row limit (0 for no limit)");
-    println!("  .timing           Enable query timing");
-    println!("  .notiming         Disable query timing");
-    println!("  .clear            Clear screen");
-    println!("  .exit/.quit       Exit REPL");
+    // The commands you use most come first
+    let mut commands = HELP_SUMMARY.to_vec();
+    UsageStats::load().rank_commands(&mut commands, |(usage, _)| {
+        usage.split(['/', ' ']).next().unwrap_or(usage)
+    });
+    for (usage, description) in commands {
+        println!("  {:<18} {}", usage, description);
+    }
     println!("\nSQL Commands:");
     println!("  Enter any valid SQL command ending with semicolon");
     println!("  Example: SELECT * FROM users;");
@@ -351,181 +1562,1887 @@ fn is_critical_error(error: &anyhow::Error) -> bool {
 }
 
 fn offer_reconnection(db_path: &str) -> bool {
-    print!(
-        "Would you like to try reconnecting to '{}'? (y/N): ",
+    confirm(&format!(
+        "Would you like to try reconnecting to '{}'?",
         db_path
-    );
+    ))
+}
+
+/// Asks for a line of input on the terminal, or returns `None` at end of input.
+fn prompt(question: &str) -> Option<String> {
+    print!("{}", question);
+    std::io::stdout().flush().unwrap_or(());
+
+    let mut input = String::new();
+    match std::io::stdin().read_line(&mut input) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(input.trim().to_string()),
+    }
+}
+
+/// Asks for a table's name, columns, and options, then creates it once confirmed.
+fn create_table_wizard(conn: &Connection) -> Result<()> {
+    let Some(name) = prompt("Table name: ").filter(|name| !name.is_empty()) else {
+        println!("No table created.");
+        return Ok(());
+    };
+    println!("Columns, one per line as NAME TYPE [CONSTRAINTS]; an empty line finishes:");
+    let mut columns = Vec::new();
+    while let Some(column) = prompt("  column: ").filter(|column| !column.is_empty()) {
+        columns.push(column);
+    }
+    if columns.is_empty() {
+        println!("No columns given; no table created.");
+        return Ok(());
+    }
+    println!("Generated columns, one per line as NAME [TYPE] = EXPR [STORED|VIRTUAL]; an empty line finishes:");
+    let mut generated = Vec::new();
+    while let Some(spec) = prompt("  generated: ").filter(|spec| !spec.is_empty()) {
+        match GeneratedColumn::parse(&spec) {
+            Ok(column) => generated.push(column),
+            Err(e) => println!("  {:#}", e),
+        }
+    }
+    println!("CHECK constraints, one expression per line; an empty line finishes:");
+    let mut checks = Vec::new();
+    while let Some(check) = prompt("  check: ").filter(|check| !check.is_empty()) {
+        checks.push(check);
+    }
+    let options = CreateTableOptions {
+        strict: confirm(
+            "Make it a STRICT table (types INT, INTEGER, REAL, TEXT, BLOB, or ANY; values of the wrong type are rejected)?",
+        ),
+        generated,
+        checks,
+    };
+
+    let sql = create_table_sql(&quote_identifier(&name), &columns.join(", "), &options)?;
+    println!("\n{};\n", sql);
+    if confirm("Create this table?") {
+        conn.execute_batch(&sql)?;
+        println!("Created table '{}'.", name);
+    } else {
+        println!("No table created.");
+    }
+    Ok(())
+}
+
+/// Asks a yes/no question on the terminal. Anything but an answer starting with 'y' is no.
+fn confirm(question: &str) -> bool {
+    print!("{} (y/N): ", question);
     std::io::stdout().flush().unwrap_or(());
 
-    let mut input = String::new();
-    if std::io::stdin().read_line(&mut input).is_ok() {
-        input.trim().to_lowercase().starts_with('y')
-    } else {
-        false
+    let mut input = String::new();
+    if std::io::stdin().read_line(&mut input).is_ok() {
+        input.trim().to_lowercase().starts_with('y')
+    } else {
+        false
+    }
+}
+
+fn cleanup_repl_session(
+    conn: &Connection,
+    transaction_manager: &TransactionManager,
+    rl: &mut ReplEditor,
+    history_path: &Path,
+) -> Result<()> {
+    // Rollback any active transaction
+    if transaction_manager.is_active() {
+        println!("Rolling back active transaction...");
+        transaction_manager.rollback_transaction(conn)?;
+    }
+    end_session(conn);
+
+    // Save command history
+    if let Err(e) = rl.save_history(history_path) {
+        eprintln!("Warning: Could not save command history: {}", e);
+    }
+
+    Ok(())
+}
+
+fn handle_special_commands(
+    command: &str,
+    conn: &mut Connection,
+    db_path: &str,
+    bookmarks: &Arc<Mutex<BookmarkManager>>,
+    last_select_query: &Arc<Mutex<LastQuery>>,
+    transaction_manager: &TransactionManager,
+    query_options: &mut QueryOptions,
+) -> Result<bool> {
+    let command = command.trim();
+    let parts: Vec<&str> = command.split_whitespace().collect();
+    let base_command = parts.first().cloned().unwrap_or("");
+
+    match base_command {
+        ".help" => {
+            match parts[1..] {
+                [name] => print_command_help(name),
+                _ => show_help(),
+            }
+            Ok(true)
+        }
+        name if parts[1..] == ["?"] => {
+            print_command_help(name);
+            Ok(true)
+        }
+        ".shell" => {
+            println!("Switching to shell mode...");
+            crate::shell::shell_mode(db_path)?;
+            println!("\nReturning to REPL mode.");
+            print_help_summary();
+            Ok(true)
+        }
+        ".exit" | ".quit" => Ok(false), // Signal to exit REPL
+        ".tables" => {
+            match parts[1..] {
+                [] => print_table_list(db_path, &get_all_table_names(conn)?),
+                ["--verbose"] => print_table_changes(conn)?,
+                _ => println!("Usage: .tables [--verbose]"),
+            }
+            Ok(true)
+        }
+        ".track" => {
+            handle_track_command(conn, &parts[1..])?;
+            Ok(true)
+        }
+        ".diff" => {
+            let (args, sql) = take_flag(&parts[1..], "--sql");
+            match args[..] {
+                ["schema", other] => {
+                    let other_conn = open_read_only(other)?;
+                    let diff = diff_schemas(conn, &other_conn)?;
+                    if sql {
+                        diff.print_reconcile_statements();
+                    } else {
+                        diff.print(db_path, other);
+                    }
+                }
+                ["data", ..] => {
+                    let (args, limit) = take_flag_value(&args[1..], "--limit")?;
+                    let limit = match limit {
+                        Some(limit) => limit
+                            .parse::<usize>()
+                            .map_err(|_| anyhow::anyhow!("Invalid --limit '{}'", limit))?,
+                        None => DEFAULT_DIFF_ROWS,
+                    };
+                    match args[..] {
+                        [left, right] => {
+                            let options = DataDiffOptions {
+                                limit: (limit > 0 && !sql).then_some(limit),
+                            };
+                            let diff = diff_table_data(conn, left, right, &options)?;
+                            if sql {
+                                diff.print_sync_statements();
+                            } else {
+                                diff.print(left, right);
+                            }
+                        }
+                        _ => println!("Usage: .diff data LEFT RIGHT [--limit N] [--sql]"),
+                    }
+                }
+                _ => println!(
+                    "Usage: .diff schema OTHER_DB [--sql] | .diff data LEFT RIGHT [--limit N] [--sql]"
+                ),
+            }
+            Ok(true)
+        }
+        ".tips" => {
+            let mut stats = UsageStats::load();
+            match parts[1..] {
+                [] => println!("Tips: {}", if stats.tips { "on" } else { "off" }),
+                [setting @ ("on" | "off")] => {
+                    stats.tips = setting == "on";
+                    stats.save()?;
+                    println!("Tips {}.", if stats.tips { "on" } else { "off" });
+                }
+                _ => println!("Usage: .tips [on|off]"),
+            }
+            Ok(true)
+        }
+        ".attach" | ".detach" => {
+            handle_attach_command(conn, command)?;
+            Ok(true)
+        }
+        ".copy" => {
+            handle_copy_command(conn, &parts[1..])?;
+            Ok(true)
+        }
+        ".backup" => {
+            match parts.get(1) {
+                Some(file) => {
+                    backup_connection(conn, file, print_backup_progress)?;
+                    println!("Backed up to '{}'", file);
+                }
+                None => println!("Usage: .backup FILE"),
+            }
+            Ok(true)
+        }
+        ".pragma" => {
+            match &parts[1..] {
+                ["list"] => print_pragma_settings(conn)?,
+                [name] => execute_sql_with_last(
+                    conn,
+                    &pragma_statement(name, None)?,
+                    query_options,
+                    last_select_query,
+                )?,
+                [name, value] => {
+                    let sql = pragma_statement(name, Some(value))?;
+                    // Pragmas that take an argument, like table_info, return rows; a
+                    // setting that returns nothing is read back to show its new value
+                    if conn.prepare(&sql)?.column_count() > 0 {
+                        execute_sql_with_last(conn, &sql, query_options, last_select_query)?;
+                    } else {
+                        conn.execute_batch(&sql)?;
+                        execute_sql_with_last(
+                            conn,
+                            &pragma_statement(name, None)?,
+                            query_options,
+                            last_select_query,
+                        )?;
+                    }
+                }
+                _ => println!("Usage: .pragma list | .pragma NAME [VALUE]"),
+            }
+            Ok(true)
+        }
+        ".check" => {
+            let quick = match parts.get(1) {
+                None => false,
+                Some(&"quick") => true,
+                Some(_) => {
+                    println!("Usage: .check [quick]");
+                    return Ok(true);
+                }
+            };
+            let report = check_integrity(conn, quick, DEFAULT_MAX_ERRORS)?;
+            report.print();
+            if !report.is_ok() {
+                println!(
+                    "Run 'vapor-cli repair --db-path {} --out FILE' to salvage what can still be read.",
+                    db_path
+                );
+            }
+            Ok(true)
+        }
+        ".vacuum" | ".analyze" | ".reindex" => {
+            let operation = match (parts[0], &parts[1..]) {
+                (".vacuum", []) => Some(Maintenance::Vacuum { into: None }),
+                (".vacuum", [into, file]) if into.eq_ignore_ascii_case("into") => {
+                    Some(Maintenance::Vacuum {
+                        into: Some(file.to_string()),
+                    })
+                }
+                (".analyze", []) => Some(Maintenance::Analyze),
+                (".reindex", []) => Some(Maintenance::Reindex),
+                _ => None,
+            };
+            match operation {
+                Some(operation) => run_maintenance(conn, &operation)?.print(),
+                None => println!("Usage: .vacuum [INTO FILE] | .analyze | .reindex"),
+            }
+            Ok(true)
+        }
+        ".wal" => {
+            let mode = match parts[1..] {
+                [] => None,
+                ["on"] => Some("wal"),
+                ["off"] => Some("delete"),
+                _ => {
+                    println!("Usage: .wal [on|off]");
+                    return Ok(true);
+                }
+            };
+            let mode = match mode {
+                Some(mode) => set_journal_mode(conn, mode)?,
+                None => conn.query_row("PRAGMA journal_mode", [], |row| row.get(0))?,
+            };
+            println!("Journal mode: {}", mode);
+            if let Some(size) = wal_size(conn) {
+                println!("WAL file: {}", crate::display::format_size(size as i64));
+            }
+            Ok(true)
+        }
+        ".fk" => {
+            match parts[1..] {
+                [] => {}
+                ["on"] => set_foreign_keys(conn, true)?,
+                ["off"] => set_foreign_keys(conn, false)?,
+                _ => {
+                    println!("Usage: .fk [on|off]");
+                    return Ok(true);
+                }
+            }
+            let on: bool = conn.query_row("PRAGMA foreign_keys", [], |row| row.get(0))?;
+            println!("Foreign key enforcement: {}", if on { "on" } else { "off" });
+            Ok(true)
+        }
+        ".fkcheck" => {
+            let table = match parts[1..] {
+                [] => None,
+                [table] => Some(table),
+                _ => {
+                    println!("Usage: .fkcheck [TABLE]");
+                    return Ok(true);
+                }
+            };
+            let violations = foreign_key_violations(conn, table)?;
+            if violations.is_empty() {
+                println!("No foreign key violations found");
+            } else {
+                print_foreign_key_violations(conn, &violations, &query_options.format, 20)?;
+            }
+            Ok(true)
+        }
+        ".checkpoint" => {
+            let mode = match parts[1..] {
+                [] => CheckpointMode::default(),
+                [mode] => CheckpointMode::parse(mode)?,
+                _ => {
+                    println!("Usage: .checkpoint [PASSIVE|FULL|TRUNCATE]");
+                    return Ok(true);
+                }
+            };
+            checkpoint(conn, mode)?.print();
+            Ok(true)
+        }
+        ".queue" => {
+            handle_queue_command(command, db_path)?;
+            Ok(true)
+        }
+        ".jobs" | ".job" => {
+            handle_jobs_command(&parts);
+            Ok(true)
+        }
+        ".autocommit" => {
+            let mut batch = WRITE_BATCH.lock().unwrap();
+            match parts[1..] {
+                ["on"] => {
+                    batch.size = None;
+                    println!("Autocommit on: each statement commits on its own");
+                }
+                ["off"] | ["off", "--batch", _] => {
+                    let size = match parts.get(3) {
+                        Some(n) => match n.parse::<usize>() {
+                            Ok(n) if n > 0 => n,
+                            _ => {
+                                println!("Invalid batch size '{}'. Use a positive number.", n);
+                                return Ok(true);
+                            }
+                        },
+                        None => DEFAULT_WRITE_BATCH,
+                    };
+                    batch.size = Some(size);
+                    println!(
+                        "Autocommit off: writes outside a transaction commit in batches of {}",
+                        size
+                    );
+                }
+                _ => {
+                    match batch.size {
+                        Some(size) => println!("Autocommit is off (batches of {})", size),
+                        None => println!("Autocommit is on"),
+                    }
+                    println!("Usage: .autocommit on | .autocommit off [--batch N]");
+                }
+            }
+            Ok(true)
+        }
+        ".onerror" => {
+            match parts.get(1) {
+                Some(name) => match ErrorPolicy::parse(name) {
+                    Ok(policy) => {
+                        transaction_manager.set_error_policy(policy);
+                        println!("Error policy set to {}", policy.name());
+                    }
+                    Err(e) => println!("{}", e),
+                },
+                None => {
+                    println!(
+                        "Error policy is {}",
+                        transaction_manager.error_policy().name()
+                    );
+                    println!("Usage: .onerror [continue|rollback|abort]");
+                }
+            }
+            Ok(true)
+        }
+        ".safe" => {
+            match parts.get(1).copied() {
+                Some("on") => {
+                    SAFE_MODE.store(true, Ordering::Relaxed);
+                    println!("Safe mode on: destructive statements outside a transaction will ask for confirmation");
+                }
+                Some("off") => {
+                    SAFE_MODE.store(false, Ordering::Relaxed);
+                    println!("Safe mode off");
+                }
+                _ => {
+                    let state = if SAFE_MODE.load(Ordering::Relaxed) {
+                        "on"
+                    } else {
+                        "off"
+                    };
+                    println!("Safe mode is {}", state);
+                    println!("Usage: .safe [on|off]");
+                }
+            }
+            Ok(true)
+        }
+        ".clear" => {
+            print!("\x1B[2J\x1B[1;1H");
+            std::io::stdout()
+                .flush()
+                .context("Failed to flush stdout")?;
+            Ok(true)
+        }
+        ".info" => {
+            let (args, exact) = take_flag(&parts[1..], "--exact");
+            match args[..] {
+                [] if !matches!(query_options.format, OutputFormat::Json) => {
+                    database_info(conn, db_path, exact)?.print()
+                }
+                [] | ["json"] => println!("{}", database_info(conn, db_path, exact)?.to_json()?),
+                _ => println!("Usage: .info [json] [--exact]"),
+            }
+            Ok(true)
+        }
+        ".counts" => {
+            show_table_counts(conn)?;
+            Ok(true)
+        }
+        ".version" => {
+            match parts.get(1..) {
+                Some([]) => println!("Schema version: {}", schema_version(conn)?),
+                Some(["set", version]) => match version.parse::<i32>() {
+                    Ok(version) => {
+                        set_schema_version(conn, version)?;
+                        println!("Schema version set to {}.", version);
+                    }
+                    Err(_) => println!("Invalid version: '{}'", version),
+                },
+                _ => println!("Usage: .version [set N]"),
+            }
+            Ok(true)
+        }
+        ".format" | ".limit" | ".timing" | ".notiming" => {
+            handle_output_command(&parts, query_options);
+            Ok(true)
+        }
+        ".output" | ".once" | ".pipe" => {
+            handle_redirect_command(command)?;
+            Ok(true)
+        }
+        ".cell" | ".more" => {
+            handle_cell_command(&parts)?;
+            Ok(true)
+        }
+        ".export" => {
+            let (args, resume) = take_flag(&parts[1..], "--resume");
+            let (args, bom) = take_flag(&args, "--bom");
+            let (args, background) = take_flag(&args, "--background");
+            let (args, compression) = match take_flag_value(&args, "--compress") {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    println!("{}", e);
+                    return Ok(true);
+                }
+            };
+            if let Some(&filename) = args.first() {
+                // After a write with RETURNING, its rows are exported as they were returned
+                let LastQuery {
+                    sql: query,
+                    returned,
+                } = last_select_query.lock().unwrap().clone();
+                if query.is_empty() && returned.is_none() {
+                    println!("No SELECT query has been executed yet.");
+                } else {
+                    let format = match args.get(1) {
+                        Some(name) => Some(*name),
+                        // Unknown extensions fall back to CSV, matching the historic behavior
+                        None if registry().for_path(filename).is_none() => Some("csv"),
+                        None => None,
+                    };
+                    let options = ExportOptions {
+                        compression: compression.map(Compression::parse).transpose()?,
+                        resume,
+                        bom,
+                        ..Default::default()
+                    };
+                    if let Some(result) = returned {
+                        if background {
+                            anyhow::bail!(
+                                "Rows returned by a write can't be exported in the background"
+                            );
+                        }
+                        export_result(&result, filename, format, &options)?;
+                    } else if background {
+                        let id = job_manager()
+                            .lock()
+                            .unwrap()
+                            .spawn_export(db_path, &query, filename, format, &options)?;
+                        println!(
+                            "Started job {}: export to '{}'. Use '.jobs' or '.job status {}' to follow it.",
+                            id, filename, id
+                        );
+                    } else {
+                        export_query_with_options(conn, &query, filename, format, &options)?;
+                    }
+                }
+            } else {
+                println!(
+                    "Usage: .export FILENAME [FORMAT] [--compress gzip|zstd] [--resume] [--bom] [--background]  (formats: {})",
+                    registry().names().join(", ")
+                );
+            }
+            Ok(true)
+        }
+        ".exportall" => {
+            if let Some(&dir) = parts.get(1) {
+                export_database_to_csv_dir(conn, dir)?;
+            } else {
+                println!("Usage: .exportall DIRECTORY");
+            }
+            Ok(true)
+        }
+        ".import" => {
+            handle_import_command(conn, &parts[1..])?;
+            Ok(true)
+        }
+        ".read" | ".source" => {
+            handle_read_command(&*conn, &parts, query_options, last_select_query)?;
+            Ok(true)
+        }
+        ".script" => {
+            handle_script_command(conn, &parts, query_options)?;
+            Ok(true)
+        }
+        ".watch" => {
+            handle_watch_command(&*conn, command, query_options, last_select_query)?;
+            Ok(true)
+        }
+        ".log" => {
+            handle_log_command(&parts, db_path)?;
+            Ok(true)
+        }
+        ".set" | ".unset" | ".vars" => {
+            handle_variable_command(command)?;
+            Ok(true)
+        }
+        ".bookmark" => {
+            handle_bookmark_command(command, bookmarks, last_select_query, conn, query_options)?;
+            Ok(true)
+        }
+        ".schema" => {
+            if parts.len() > 1 {
+                show_table_schema(conn, parts[1])?;
+            } else {
+                show_all_schemas(conn)?;
+            }
+            Ok(true)
+        }
+        ".describe" => {
+            match parts.get(1) {
+                Some(table) => {
+                    show_table_schema(conn, table)?;
+                    show_table_constraints(conn, table)?;
+                    print_row_counts(conn, table)?;
+                }
+                None => println!("Usage: .describe TABLE"),
+            }
+            Ok(true)
+        }
+        ".peek" => {
+            let (args, with_notes) = take_flag(&parts[1..], "--notes");
+            let limit = match args.get(1).map(|n| n.parse::<usize>()) {
+                None => Some(10),
+                Some(Ok(n)) => Some(n),
+                Some(Err(_)) => None,
+            };
+            match (args.first(), limit) {
+                (Some(table), Some(limit)) => {
+                    let query = if with_notes {
+                        notes_select_query(&*conn, table, &[], None)?
+                    } else {
+                        format!("SELECT * FROM {}", quote_identifier(table))
+                    };
+                    let sql = format!("{} LIMIT {}", query, limit);
+                    execute_sql_with_last(conn, &sql, query_options, last_select_query)?;
+                    print_row_counts(conn, table)?;
+                }
+                _ => println!("Usage: .peek TABLE [N] [--notes]"),
+            }
+            Ok(true)
+        }
+        ".note" => {
+            handle_note_command(conn, db_path, &parts[1..])?;
+            Ok(true)
+        }
+        ".notes" => {
+            let all = match parts[1..] {
+                [] => false,
+                ["--all"] => true,
+                _ => {
+                    println!("Usage: .notes [--all]");
+                    return Ok(true);
+                }
+            };
+            print_session_notes(db_path, all)?;
+            Ok(true)
+        }
+        ".find" => {
+            handle_find_command(conn, command, query_options)?;
+            Ok(true)
+        }
+        ".browse" => {
+            browse_tables(conn, query_options, last_select_query)?;
+            Ok(true)
+        }
+        ".softdelete" => {
+            match parts.get(1..) {
+                Some(["enable", table]) => {
+                    enable_soft_delete(conn, table)?;
+                    println!(
+                        "Soft delete enabled on '{0}': deletes now set deleted_at. Query {0}_live or {0}_deleted.",
+                        table
+                    );
+                }
+                Some(["disable", table]) => {
+                    disable_soft_delete(conn, table)?;
+                    println!(
+                        "Soft delete disabled on '{}'. The deleted_at column was kept.",
+                        table
+                    );
+                }
+                _ => println!("Usage: .softdelete enable|disable TABLE"),
+            }
+            Ok(true)
+        }
+        ".timestamps" => {
+            let (args, yes) = take_flag(&parts[1..], "--yes");
+            match args.as_slice() {
+                [table] => {
+                    let statements = timestamp_statements(conn, table)?;
+                    println!("The following statements will be run in one transaction:\n");
+                    for statement in &statements {
+                        println!("{};", statement);
+                    }
+                    println!();
+                    if yes || confirm("Apply these changes?") {
+                        apply_timestamp_statements(conn, &statements)?;
+                        println!("Added created_at/updated_at to '{}'.", table);
+                    } else {
+                        println!("No changes made.");
+                    }
+                }
+                _ => println!("Usage: .timestamps TABLE [--yes]"),
+            }
+            Ok(true)
+        }
+        ".create-table" => {
+            create_table_wizard(conn)?;
+            Ok(true)
+        }
+        ".typecheck" => {
+            let tables = match parts.get(1) {
+                Some(table) => vec![table.to_string()],
+                None => get_table_names(conn)?,
+            };
+            let mut found = 0;
+            for table in &tables {
+                for mismatch in type_mismatches(conn, table)? {
+                    println!(
+                        "{}.{} ({}): {} {} value(s), e.g. {}",
+                        mismatch.table,
+                        mismatch.column,
+                        mismatch.declared,
+                        mismatch.rows,
+                        mismatch.stored,
+                        mismatch.example
+                    );
+                    found += 1;
+                }
+            }
+            if found == 0 {
+                println!(
+                    "Every value matches its column's declared type ({} table(s) checked).",
+                    tables.len()
+                );
+            }
+            Ok(true)
+        }
+        ".strictify" => {
+            let (args, yes) = take_flag(&parts[1..], "--yes");
+            match args.as_slice() {
+                [table] => {
+                    let statements = strictify_statements(conn, table)?;
+                    println!("The following statements will be run in one transaction:\n");
+                    for statement in &statements {
+                        println!("{};", statement);
+                    }
+                    println!();
+                    if yes || confirm("Apply these changes?") {
+                        apply_strictify_statements(conn, &statements)?;
+                        println!("'{}' is now a STRICT table.", table);
+                    } else {
+                        println!("No changes made.");
+                    }
+                }
+                _ => println!("Usage: .strictify TABLE [--yes]"),
+            }
+            Ok(true)
+        }
+        ".status" => {
+            transaction_manager.show_status_with_stats(conn);
+            Ok(true)
+        }
+        ".show" => {
+            match parts.get(1) {
+                Some(&"settings") => show_settings(query_options, transaction_manager),
+                _ => println!("Usage: .show settings"),
+            }
+            Ok(true)
+        }
+        _ => {
+            println!(
+                "Unknown command: '{}'. Type '.help' for a list of commands.",
+                command
+            );
+            Ok(true)
+        }
+    }
+}
+
+/// Prints the session's settings for `.show settings`.
+fn show_settings(query_options: &QueryOptions, transaction_manager: &TransactionManager) {
+    let on_off = |on: bool| if on { "on" } else { "off" };
+    println!("Output format:  {:?}", query_options.format);
+    match query_options.max_rows {
+        Some(n) => println!("Row limit:      {}", n),
+        None => println!("Row limit:      none"),
+    }
+    println!("Timing:         {}", on_off(query_options.show_timing));
+    println!(
+        "Safe mode:      {}",
+        on_off(SAFE_MODE.load(Ordering::Relaxed))
+    );
+    match WRITE_BATCH.lock().unwrap().size {
+        Some(size) => println!("Autocommit:     off (batches of {})", size),
+        None => println!("Autocommit:     on"),
+    }
+    println!(
+        "On error:       {}",
+        transaction_manager.error_policy().name()
+    );
+    println!(
+        "Output:         {}",
+        output_path().unwrap_or_else(|| "stdout".to_string())
+    );
+    println!(
+        "Session log:    {}",
+        transcript_path().unwrap_or_else(|| "off".to_string())
+    );
+    println!("History size:   {}", config::get_repl_history_size());
+    if config::get_optimize_on_exit() {
+        println!("On exit:        PRAGMA optimize, WAL checkpoint");
+    } else {
+        println!("On exit:        nothing (VAPOR_OPTIMIZE_ON_EXIT is off)");
+    }
+    if let Ok(path) = config::get_rc_path() {
+        let state = if path.exists() { "" } else { " (not found)" };
+        println!("Startup file:   {}{}", path.display(), state);
+    }
+
+    let hooks = session_hooks().lock().unwrap();
+    match config::get_hooks_path() {
+        Ok(path) if hooks.is_empty() => {
+            println!("Hooks:          none (add them to {})", path.display())
+        }
+        Ok(path) => println!("Hooks ({}):", path.display()),
+        Err(_) => println!("Hooks:"),
+    }
+    if !hooks.is_empty() {
+        for (stage, statements) in hooks.stages() {
+            if statements.is_empty() {
+                println!("  {:<13} none", stage);
+            }
+            for statement in statements {
+                println!("  {:<13} {}", stage, statement);
+            }
+        }
+    }
+}
+
+/// Handles `.note add TABLE ROWID TEXT`, `.note show TABLE [ROWID]`, and `.note delete ID`
+/// for row notes, and `.note session TEXT` for session notes.
+fn handle_note_command(conn: &Connection, db_path: &str, args: &[&str]) -> Result<()> {
+    match args {
+        ["session", text @ ..] if !text.is_empty() => {
+            let text = text.join(" ");
+            let text = text
+                .strip_prefix('\'')
+                .and_then(|text| text.strip_suffix('\''))
+                .or_else(|| {
+                    text.strip_prefix('"')
+                        .and_then(|text| text.strip_suffix('"'))
+                })
+                .unwrap_or(&text);
+            let note = add_session_note(&config::get_session_notes_path(db_path)?, text)?;
+            println!("Noted at {}.", note.time);
+        }
+        ["add", table, row_id, text @ ..] if !text.is_empty() => {
+            let Ok(row_id) = row_id.parse::<i64>() else {
+                println!("Invalid rowid: '{}'", row_id);
+                return Ok(());
+            };
+            let text = text.join(" ");
+            let id = add_note(conn, table, row_id, text.trim_matches('"'))?;
+            println!("Added note {} to {} row {}.", id, table, row_id);
+        }
+        ["show", table] | ["show", table, _] => {
+            let row_id = match args.get(2).map(|id| id.parse::<i64>()) {
+                None => None,
+                Some(Ok(id)) => Some(id),
+                Some(Err(_)) => {
+                    println!("Invalid rowid: '{}'", args[2]);
+                    return Ok(());
+                }
+            };
+            let notes = list_notes(conn, table, row_id)?;
+            if notes.is_empty() {
+                println!("No notes found.");
+            }
+            for note in notes {
+                println!(
+                    "#{:<4} row {:<6} {}  {}",
+                    note.id, note.row_id, note.created_at, note.text
+                );
+            }
+        }
+        ["delete", id] => match id.parse::<i64>() {
+            Ok(id) if delete_note(conn, id)? => println!("Deleted note {}.", id),
+            _ => println!("No note with ID {}.", id),
+        },
+        _ => {
+            println!("Usage: .note session 'TEXT'");
+            println!("       .note add TABLE ROWID TEXT");
+            println!("       .note show TABLE [ROWID]");
+            println!("       .note delete ID");
+        }
+    }
+    Ok(())
+}
+
+/// Handles `.notes [--all]`: prints the session notes made in this session, or in every
+/// session on this database.
+fn print_session_notes(db_path: &str, all: bool) -> Result<()> {
+    let notes = read_session_notes(&config::get_session_notes_path(db_path)?)?;
+    let notes: Vec<_> = notes
+        .into_iter()
+        .filter(|note| all || note.session == session_id())
+        .collect();
+    if notes.is_empty() {
+        if all {
+            println!("No session notes for this database. Add one with .note session 'TEXT'.");
+        } else {
+            println!("No notes in this session. Add one with .note session 'TEXT'; .notes --all shows earlier sessions.");
+        }
+        return Ok(());
+    }
+    let mut session = None;
+    for note in &notes {
+        if all && session != Some(&note.session) {
+            println!("Session {}:", note.session);
+            session = Some(&note.session);
+        }
+        println!("  [{}] {}", note.time, note.text);
+    }
+    Ok(())
+}
+
+/// Handles `.copy SOURCE TO TARGET [--create]`.
+fn handle_copy_command(conn: &mut Connection, args: &[&str]) -> Result<()> {
+    let (args, create) = take_flag(args, "--create");
+    let (source, target) = match args.as_slice() {
+        [source, to, target] if to.eq_ignore_ascii_case("to") => (*source, *target),
+        _ => {
+            println!("Usage: .copy SOURCE TO TARGET [--create]  (e.g. .copy main.users TO archive.users)");
+            return Ok(());
+        }
+    };
+    let options = CopyOptions {
+        create,
+        ..Default::default()
+    };
+    let report = copy_table(conn, source, target, &options)?;
+    if report.created {
+        println!("Created table '{}'.", target);
+    }
+    println!(
+        "Copied {} row(s) from '{}' to '{}' ({} column(s)).",
+        report.rows,
+        source,
+        target,
+        report.columns.len()
+    );
+    if !report.skipped_columns.is_empty() {
+        println!(
+            "Not copied (missing from '{}'): {}",
+            target,
+            report.skipped_columns.join(", ")
+        );
+    }
+    if !report.defaulted_columns.is_empty() {
+        println!(
+            "Left to their defaults (missing from '{}'): {}",
+            source,
+            report.defaulted_columns.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Handles `.find PATTERN [TABLE]`. The pattern can be quoted to include spaces.
+fn handle_find_command(
+    conn: &Connection,
+    command: &str,
+    query_options: &QueryOptions,
+) -> Result<()> {
+    let rest = command.strip_prefix(".find").unwrap_or("").trim();
+    let quoted = ['\'', '"'].iter().find_map(|&q| {
+        let (pattern, table) = rest.strip_prefix(q)?.split_once(q)?;
+        Some((pattern, table.trim()))
+    });
+    let (pattern, table) = quoted.unwrap_or_else(|| {
+        let (pattern, table) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        (pattern, table.trim())
+    });
+    if pattern.is_empty() || table.contains(char::is_whitespace) {
+        println!("Usage: .find PATTERN [TABLE]  (quote PATTERN to include spaces)");
+        return Ok(());
+    }
+
+    let table = Some(table).filter(|table| !table.is_empty());
+    let matches = find_text(conn, pattern, table, query_options.max_rows)?;
+    if matches.is_empty() {
+        println!("No text values match '{}'.", pattern);
+        return Ok(());
+    }
+    let columns = ["table", "column", "rowid", "value"].map(String::from);
+    let rows: Vec<Record> = matches
+        .iter()
+        .map(|m| {
+            vec![
+                rusqlite::types::Value::Text(m.table.clone()),
+                rusqlite::types::Value::Text(m.column.clone()),
+                rusqlite::types::Value::Integer(m.rowid),
+                rusqlite::types::Value::Text(m.value.clone()),
+            ]
+        })
+        .collect();
+    print_records(&query_options.format, &columns, &rows)?;
+    if query_options.max_rows == Some(matches.len()) {
+        println!(
+            "Showing the first {} matches. Use '.limit' to see more.",
+            matches.len()
+        );
+    } else {
+        println!("{} match(es)", matches.len());
+    }
+    Ok(())
+}
+
+/// Prints a table's row count, split into live and deleted rows if it uses soft delete.
+fn print_row_counts(conn: &Connection, table: &str) -> Result<()> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?)",
+        [table],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        return Ok(());
+    }
+    match soft_delete_counts(conn, table)? {
+        Some(counts) => println!(
+            "Rows: {} live, {} deleted ({} total)",
+            counts.live,
+            counts.deleted,
+            counts.live + counts.deleted
+        ),
+        None => {
+            let count: i64 = conn.query_row(
+                &format!("SELECT COUNT(*) FROM {}", quote_identifier(table)),
+                [],
+                |row| row.get(0),
+            )?;
+            println!("Rows: {}", count);
+        }
+    }
+    Ok(())
+}
+
+/// Handles the dot-commands that only change output settings (`.format`, `.limit`,
+/// `.timing`, `.notiming`). These work the same for local and remote databases.
+fn handle_output_command(parts: &[&str], query_options: &mut QueryOptions) {
+    match parts.first().copied().unwrap_or("") {
+        ".format" => {
+            if parts.len() > 1 {
+                match OutputFormat::parse(parts[1]) {
+                    Ok(format) => query_options.format = format,
+                    Err(e) => println!("{}", e),
+                }
+            } else {
+                println!("Current format: {:?}", query_options.format);
+                println!("Usage: .format [table|json|csv|plain]");
+            }
+        }
+        ".limit" => {
+            if parts.len() > 1 {
+                if let Ok(n) = parts[1].parse::<usize>() {
+                    if n == 0 {
+                        query_options.max_rows = None;
+                        println!("Row limit removed");
+                    } else {
+                        query_options.max_rows = Some(n);
+                        println!("Row limit set to {}", n);
+                    }
+                } else {
+                    println!("Invalid limit value. Use a positive number or 0 for no limit.");
+                }
+            } else {
+                match query_options.max_rows {
+                    None => println!("No row limit set"),
+                    Some(n) => println!("Current row limit: {}", n),
+                }
+            }
+        }
+        ".timing" => {
+            query_options.show_timing = true;
+            println!("Query timing enabled");
+        }
+        ".notiming" => {
+            query_options.show_timing = false;
+            println!("Query timing disabled");
+        }
+        _ => {}
+    }
+}
+
+/// Handles `.output [FILE|stdout]`, `.once FILE`, and `.pipe 'COMMAND'`.
+fn handle_redirect_command(command: &str) -> Result<()> {
+    let command = command.trim();
+    if let Some(rest) = command.strip_prefix(".pipe") {
+        let rest = rest.trim();
+        // The command is the rest of the line, optionally wrapped in one pair of quotes
+        let shell_command = ['\'', '"']
+            .iter()
+            .find_map(|&q| rest.strip_prefix(q).and_then(|r| r.strip_suffix(q)))
+            .unwrap_or(rest);
+        if shell_command.trim().is_empty() {
+            println!("Usage: .pipe 'COMMAND'");
+        } else {
+            set_output_pipe(shell_command)?;
+            println!("The next query's output goes to '{}'.", shell_command);
+        }
+        return Ok(());
+    }
+    let parts: Vec<&str> = command.split_whitespace().collect();
+    match parts.as_slice() {
+        [".output"] => match output_path() {
+            Some(path) => println!("Query output goes to '{}'.", path),
+            None => println!("Query output goes to stdout."),
+        },
+        [".output", "stdout"] => {
+            reset_output();
+            println!("Query output goes to stdout.");
+        }
+        [".output", path] => {
+            set_output_file(path, false)?;
+            println!(
+                "Query output now goes to '{}'. Use '.output stdout' to switch back.",
+                path
+            );
+        }
+        [".once", path] => {
+            set_output_file(path, true)?;
+            println!("The next query's output goes to '{}'.", path);
+        }
+        [".once", ..] => println!("Usage: .once FILE"),
+        _ => println!("Usage: .output [FILE|stdout]"),
+    }
+    Ok(())
+}
+
+/// Handles `.queue add QUERY`, `.queue run`, `.queue [list]`, `.queue cancel`, and
+/// `.queue clear`.
+fn handle_queue_command(command: &str, db_path: &str) -> Result<()> {
+    let mut queue = query_queue().lock().unwrap();
+    let rest = command[".queue".len()..].trim();
+    let (subcommand, arg) = match rest.split_once(char::is_whitespace) {
+        Some((subcommand, arg)) => (subcommand, arg.trim()),
+        None => (rest, ""),
+    };
+    match subcommand {
+        "add" if !arg.is_empty() => {
+            let added = queue.add(arg);
+            println!(
+                "Queued {} statement(s), {} waiting. Use '.queue run' to start.",
+                added,
+                queue.pending().len()
+            );
+        }
+        "run" => {
+            let count = queue.run(db_path)?;
+            println!(
+                "Running {} statement(s) in the background. Use '.queue' to check on them.",
+                count
+            );
+        }
+        "" | "list" => {
+            // Listing a finished run counts as reporting it
+            queue.take_finished();
+            let statuses = queue.statuses();
+            if statuses.is_empty() && queue.pending().is_empty() {
+                println!("The queue is empty. Use '.queue add QUERY' to add statements.");
+            }
+            if !statuses.is_empty() {
+                let label = if queue.is_running() {
+                    "Running"
+                } else {
+                    "Last run"
+                };
+                println!("{}:", label);
+                for (i, query) in statuses.iter().enumerate() {
+                    println!("  {}. {} [{}]", i + 1, one_line(&query.sql), query.state);
+                }
+            }
+            if !queue.pending().is_empty() {
+                println!("Waiting:");
+                for (i, sql) in queue.pending().iter().enumerate() {
+                    println!("  {}. {}", i + 1, one_line(sql));
+                }
+            }
+        }
+        "cancel" => {
+            if queue.cancel() {
+                println!("Cancelling the queue; the remaining statements will be skipped.");
+            } else {
+                println!("The queue isn't running.");
+            }
+        }
+        "clear" => {
+            queue.clear();
+            println!("Removed the waiting statements.");
+        }
+        _ => println!("Usage: .queue add QUERY | run | list | cancel | clear"),
+    }
+    Ok(())
+}
+
+/// Handles `.jobs` and `.job status N`.
+fn handle_jobs_command(parts: &[&str]) {
+    let jobs = job_manager().lock().unwrap();
+    match parts {
+        [".jobs"] => {
+            let list = jobs.list();
+            if list.is_empty() {
+                println!("No background jobs. Use '.export FILE --background' to start one.");
+            }
+            for job in list {
+                println!(
+                    "  [{}] {} - {} ({} rows, {:.1}s)",
+                    job.id,
+                    job.description,
+                    job.state,
+                    job.progress,
+                    job.elapsed.as_secs_f64()
+                );
+            }
+        }
+        [".job", "status", id] => match id.parse().ok().and_then(|id| jobs.get(id)) {
+            Some(job) => {
+                println!("Job {}: {}", job.id, job.description);
+                println!("  State: {}", job.state);
+                println!("  Rows: {}", job.progress);
+                println!("  Elapsed: {:.1}s", job.elapsed.as_secs_f64());
+                println!("  Rate: {:.0} rows/s", job.rate());
+            }
+            None => println!("No job '{}'. Use '.jobs' to list them.", id),
+        },
+        _ => println!("Usage: .jobs | .job status N"),
+    }
+}
+
+/// Prints the background jobs that finished since the last prompt.
+fn report_finished_jobs() {
+    for job in job_manager().lock().unwrap().take_finished() {
+        println!(
+            "Job {} ({}) {} after {:.1}s",
+            job.id,
+            job.description,
+            job.state,
+            job.elapsed.as_secs_f64()
+        );
+    }
+}
+
+/// Prints the summary of a queue run that finished since the last prompt.
+fn report_finished_queue() {
+    if let Some(summary) = query_queue().lock().unwrap().take_finished() {
+        println!("Queue finished: {}. Use '.queue' for details.", summary);
+    }
+}
+
+/// Shortens a statement to one line of at most 60 characters, for listings.
+fn one_line(sql: &str) -> String {
+    let line = sql.split_whitespace().collect::<Vec<_>>().join(" ");
+    if line.chars().count() > 60 {
+        format!("{}...", line.chars().take(57).collect::<String>())
+    } else {
+        line
+    }
+}
+
+/// Handles `.attach PATH [AS] ALIAS` and `.detach ALIAS`.
+fn handle_attach_command(conn: &Connection, command: &str) -> Result<()> {
+    let mut words: Vec<&str> = command.split_whitespace().collect();
+    if words[0] == ".detach" {
+        match words.as_slice() {
+            [_, alias] => {
+                conn.execute_batch(&format!("DETACH DATABASE {}", quote_identifier(alias)))
+                    .with_context(|| format!("Failed to detach '{}'", alias))?;
+                println!("Detached '{}'.", alias);
+            }
+            _ => println!("Usage: .detach ALIAS"),
+        }
+        return Ok(());
+    }
+
+    // The alias is the last word, so the path may contain spaces
+    let alias = match words.len() {
+        0..=2 => None,
+        _ => words.pop(),
+    };
+    if words.len() > 2 && words[words.len() - 1].eq_ignore_ascii_case("as") {
+        words.pop();
+    }
+    let path = words[1..].join(" ");
+    let path = ['\'', '"']
+        .iter()
+        .find_map(|&q| path.strip_prefix(q).and_then(|p| p.strip_suffix(q)))
+        .unwrap_or(&path);
+    let Some(alias) = alias.filter(|_| !path.is_empty()) else {
+        println!("Usage: .attach PATH AS ALIAS");
+        return Ok(());
+    };
+    if path != ":memory:" && !Path::new(path).exists() {
+        println!("'{}' does not exist yet; it will be created.", path);
+    }
+    conn.execute(
+        &format!("ATTACH DATABASE ?1 AS {}", quote_identifier(alias)),
+        [path],
+    )
+    .with_context(|| format!("Failed to attach '{}' as '{}'", path, alias))?;
+    println!(
+        "Attached '{}' as '{}'. Its tables are available as {}.TABLE.",
+        path, alias, alias
+    );
+    Ok(())
+}
+
+/// Tells the editor which databases are attached, for completion. This is only done after
+/// commands that may attach or detach one, since reading the list waits for locks held by
+/// other connections, such as a running `.queue`.
+fn refresh_attached_databases(rl: &mut ReplEditor, conn: &Connection) {
+    if let Some(helper) = rl.helper_mut() {
+        helper.databases = attached_databases(conn)
+            .map(|databases| databases.into_iter().map(|(alias, _)| alias).collect())
+            .unwrap_or_default();
+    }
+}
+
+/// Handles `.cell [ROW] [COLUMN]` and `.more`.
+fn handle_cell_command(parts: &[&str]) -> Result<()> {
+    let row = match parts.get(1) {
+        Some(row) => Some(
+            row.parse::<usize>()
+                .with_context(|| format!("Invalid row number '{}'", row))?,
+        ),
+        None => None,
+    };
+    if parts.len() > 3 {
+        println!("Usage: .cell [ROW] [COLUMN]");
+        return Ok(());
+    }
+    show_cell(row, parts.get(2).copied())
+}
+
+/// Handles `.import FILENAME TABLE_NAME [FORMAT] [flags...]`.
+fn handle_import_command(conn: &mut Connection, args: &[&str]) -> Result<()> {
+    let (args, create_table) = take_flag(args, "--create-table");
+    let (args, no_header) = take_flag(&args, "--no-header");
+    let (args, sniff) = take_flag(&args, "--sniff");
+    let (args, record_path) = take_flag_value(&args, "--record")?;
+    let (args, delimiter) = take_flag_value(&args, "--delimiter")?;
+    let (args, quote) = take_flag_value(&args, "--quote")?;
+    let (args, escape) = take_flag_value(&args, "--escape")?;
+    let (args, line_ending) = take_flag_value(&args, "--line-ending")?;
+    let (args, null_string) = take_flag_value(&args, "--null")?;
+    let (args, skip) = take_flag_value(&args, "--skip")?;
+    let (args, encoding) = take_flag_value(&args, "--encoding")?;
+    let (args, batch_size) = take_flag_value(&args, "--batch-size")?;
+    let (args, continue_on_error) = take_flag(&args, "--continue-on-error");
+    let (args, reject_file) = take_flag_value(&args, "--reject")?;
+    let (args, on_conflict) = take_flag_value(&args, "--on-conflict")?;
+    let (args, settings) = take_flag_values(&args, "--set")?;
+
+    if args.len() < 2 {
+        println!("Usage: .import FILENAME|- TABLE_NAME [FORMAT] [--create-table] [--record PATH]");
+        println!("  CSV, JSON, and XML flags: [--on-conflict fail|ignore|replace] [--set COLUMN=VALUE]...");
+        println!("  CSV flags: [--sniff] [--delimiter C] [--quote C] [--escape C] [--line-ending crlf|lf|cr] [--no-header]");
+        println!("             [--null STR] [--skip N] [--encoding utf-8|latin-1|windows-1252|shift-jis]");
+        println!("             [--batch-size N] [--continue-on-error] [--reject FILE]");
+        return Ok(());
+    }
+    let (file, table) = (args[0], args[1]);
+    let on_conflict = match on_conflict {
+        Some(policy) => ConflictPolicy::parse(policy)?,
+        None => ConflictPolicy::Fail,
+    };
+    let set_columns = settings
+        .into_iter()
+        .map(ColumnSetting::parse)
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut csv_options = CsvImportOptions {
+        null_string: null_string.map(str::to_string),
+        on_conflict,
+        set_columns: set_columns.clone(),
+        continue_on_error: continue_on_error || reject_file.is_some(),
+        reject_file: reject_file.map(str::to_string),
+        ..Default::default()
+    };
+    if sniff {
+        let dialect = sniff_csv_file(file)?;
+        println!("Detected CSV dialect: {}", dialect);
+        dialect.apply_to(&mut csv_options);
+    }
+    if no_header {
+        csv_options.has_header = false;
+    }
+    if let Some(batch_size) = batch_size {
+        csv_options.batch_size = Some(
+            batch_size
+                .parse()
+                .ok()
+                .filter(|&size| size > 0)
+                .with_context(|| format!("Invalid --batch-size value '{}'", batch_size))?,
+        );
+    }
+    if let Some(delimiter) = delimiter {
+        csv_options.delimiter = parse_csv_char(delimiter)?;
+    }
+    if let Some(quote) = quote {
+        csv_options.quote = parse_csv_char(quote)?;
+    }
+    if let Some(escape) = escape {
+        csv_options.escape = Some(parse_csv_char(escape)?);
+    }
+    if let Some(line_ending) = line_ending {
+        csv_options.line_ending = LineEnding::parse(line_ending)?;
+    }
+    if let Some(skip) = skip {
+        csv_options.skip_lines = skip
+            .parse()
+            .with_context(|| format!("Invalid --skip value '{}'", skip))?;
+    }
+    if let Some(encoding) = encoding {
+        csv_options.encoding = TextEncoding::parse(encoding)?;
+    }
+    let json_options = JsonImportOptions {
+        create_table,
+        on_conflict,
+        set_columns: set_columns.clone(),
+        ..Default::default()
+    };
+    let xml_options = XmlImportOptions {
+        record_path: record_path.unwrap_or("/*/*").to_string(),
+        create_table,
+        on_conflict,
+        set_columns,
+    };
+    let file_options = FileImportOptions { create_table };
+
+    match args.get(2).copied() {
+        Some("csv") => {
+            import_csv_to_table_with_options(conn, file, table, &csv_options)?;
+        }
+        Some("json" | "jsonl" | "ndjson") => {
+            import_json_to_table(conn, file, table, &json_options)?;
+        }
+        Some("xml") => {
+            import_xml_to_table(conn, file, table, &xml_options)?;
+        }
+        Some(format) => {
+            import_file_with_options(conn, file, table, Some(format), &file_options)?;
+        }
+        None if is_json_path(file) => {
+            import_json_to_table(conn, file, table, &json_options)?;
+        }
+        None if is_xml_path(file) => {
+            import_xml_to_table(conn, file, table, &xml_options)?;
+        }
+        None if registry().for_path(file).is_some_and(|f| f.name() != "csv") => {
+            import_file_with_options(conn, file, table, None, &file_options)?;
+        }
+        // CSV keeps its dedicated importer
+        None => {
+            import_csv_to_table_with_options(conn, file, table, &csv_options)?;
+        }
+    }
+    Ok(())
+}
+
+/// Handles `.use [PATH|PROFILE]`, `.use --save NAME`, and `.use --forget NAME`.
+///
+/// Switching opens the new database before touching the current one, so a bad path
+/// leaves the session as it was. An open transaction on the current database is rolled
+/// back, and the command history follows the database.
+fn handle_use_command(
+    command: &str,
+    rl: &mut ReplEditor,
+    conn: &mut Connection,
+    db_path: &mut String,
+    history_path: &mut PathBuf,
+    transaction_manager: &TransactionManager,
+) -> Result<()> {
+    let mut profiles = Profiles::load();
+    let parts: Vec<&str> = command.split_whitespace().collect();
+    let target = match parts.as_slice() {
+        [_] => {
+            println!("Current database: {}", db_path);
+            for (name, path) in &profiles.databases {
+                println!("  {} -> {}", name, path);
+            }
+            if profiles.databases.is_empty() {
+                println!("No profiles saved. Use '.use --save NAME' to name this database.");
+            }
+            return Ok(());
+        }
+        [_, "--save", name] => {
+            profiles.set(name, db_path)?;
+            profiles.save()?;
+            println!("Saved profile '{}' for {}", name, db_path);
+            return Ok(());
+        }
+        [_, "--forget", name] => {
+            if profiles.remove(name) {
+                profiles.save()?;
+                println!("Removed profile '{}'", name);
+            } else {
+                println!("No profile named '{}'", name);
+            }
+            return Ok(());
+        }
+        [_, target] => *target,
+        _ => {
+            println!("Usage: .use PATH|PROFILE | .use --save NAME | .use --forget NAME");
+            return Ok(());
+        }
+    };
+
+    let Some(path) = profiles.resolve(target) else {
+        anyhow::bail!(
+            "No database file or profile named '{}'. Use 'vapor-cli init --name {}' to create a database.",
+            target,
+            target.trim_end_matches(".db")
+        );
+    };
+    if is_remote_target(&path) {
+        anyhow::bail!(
+            "'.use' switches between local database files; start a new session for '{}'",
+            path
+        );
     }
-}
+    let path = std::fs::canonicalize(&path)
+        .with_context(|| format!("Failed to resolve absolute path for database '{}'", path))?
+        .to_str()
+        .ok_or_else(|| anyhow::anyhow!("Database path contains invalid UTF-8 characters"))?
+        .to_string();
+    if path == *db_path {
+        println!("Already using {}", db_path);
+        return Ok(());
+    }
+    verify_database_file(&path)?;
+    let new_conn = create_robust_connection(&path)?;
 
-fn cleanup_repl_session(
-    conn: &Connection,
-    transaction_manager: &TransactionManager,
-    rl: &mut DefaultEditor,
-    history_path: &Path,
-) -> Result<()> {
-    // Rollback any active transaction
+    flush_write_batch(conn)?;
     if transaction_manager.is_active() {
-        println!("Rolling back active transaction...");
+        println!("Rolling back the open transaction on {}...", db_path);
         transaction_manager.rollback_transaction(conn)?;
+    } else if !conn.is_autocommit() {
+        // Opened with a plain `BEGIN;` statement
+        println!("Rolling back the open transaction on {}...", db_path);
+        conn.execute_batch("ROLLBACK")
+            .context("Failed to roll back the open transaction")?;
     }
-
-    // Save command history
-    if let Err(e) = rl.save_history(history_path) {
+    if let Err(e) = rl.save_history(&*history_path) {
         eprintln!("Warning: Could not save command history: {}", e);
     }
+    end_session(conn);
+    run_session_hooks(&new_conn, "on_start");
+    let old_conn = std::mem::replace(conn, new_conn);
+    if let Err((_, e)) = old_conn.close() {
+        eprintln!("Warning: Could not close {} cleanly: {}", db_path, e);
+    }
 
+    rl.clear_history()?;
+    *history_path = load_database_history(rl, &path)?;
+    *db_path = path;
+    refresh_attached_databases(rl, conn);
+    println!("Switched to database: {}", db_path);
+    if is_polling() {
+        start_polling(conn)?;
+        println!("Change polling restarted for the new database.");
+    }
     Ok(())
 }
 
-fn handle_special_commands(
-    command: &str,
-    conn: &mut Connection,
-    db_path: &str,
-    bookmarks: &Arc<Mutex<BookmarkManager>>,
-    last_select_query: &Arc<Mutex<String>>,
-    transaction_manager: &TransactionManager,
-    query_options: &mut QueryOptions,
-) -> Result<bool> {
-    let command = command.trim();
-    let parts: Vec<&str> = command.split_whitespace().collect();
-    let base_command = parts.get(0).cloned().unwrap_or("");
-
-    match base_command {
-        ".help" => {
-            show_help();
-            Ok(true)
-        }
-        ".shell" => {
-            println!("Switching to shell mode...");
-            crate::shell::shell_mode(db_path)?;
-            println!("\nReturning to REPL mode.");
-            print_help_summary();
-            Ok(true)
-        }
-        ".exit" | ".quit" => Ok(false), // Signal to exit REPL
-        ".tables" => {
-            let tables = list_tables(db_path)?;
-            for table in tables {
-                println!("{}", table);
+/// Handles `.track`: turns change polling on or off, and installs or removes the
+/// change triggers.
+fn handle_track_command(conn: &Connection, args: &[&str]) -> Result<()> {
+    let (args, yes) = take_flag(args, "--yes");
+    match args[..] {
+        [] => {
+            println!("Polling: {}", if is_polling() { "on" } else { "off" });
+            let tracked = tracked_tables(conn)?;
+            if tracked.is_empty() {
+                println!("Change triggers: none");
+            } else {
+                println!("Change triggers: {}", tracked.join(", "));
             }
-            Ok(true)
         }
-        ".clear" => {
-            print!("\x1B[2J\x1B[1;1H");
-            std::io::stdout()
-                .flush()
-                .context("Failed to flush stdout")?;
-            Ok(true)
+        ["on"] => {
+            start_polling(conn)?;
+            println!("Polling for table changes; '.tables --verbose' shows what changed and when.");
         }
-        ".info" => {
-            show_database_info(conn, db_path)?;
-            Ok(true)
+        ["off"] => {
+            stop_polling();
+            println!("Stopped polling for table changes.");
         }
-        ".format" => {
-            if parts.len() > 1 {
-                match parts[1] {
-                    "table" => query_options.format = OutputFormat::Table,
-                    "json" => query_options.format = OutputFormat::Json,
-                    "csv" => query_options.format = OutputFormat::Csv,
-                    _ => println!("Invalid format. Available: table, json, csv"),
-                }
+        ["triggers", "off"] => {
+            let removed = remove_change_triggers(conn)?;
+            println!(
+                "Removed {} change trigger(s) and the {} table.",
+                removed, CHANGES_TABLE
+            );
+        }
+        ["triggers", ref tables @ ..] => {
+            let tables: Vec<String> = if tables.is_empty() {
+                trackable_tables(conn)?
             } else {
-                println!("Current format: {:?}", query_options.format);
-                println!("Usage: .format [table|json|csv]");
+                tables.iter().map(|table| table.to_string()).collect()
+            };
+            if tables.is_empty() {
+                println!("No tables to track.");
+                return Ok(());
             }
-            Ok(true)
-        }
-        ".limit" => {
-            if parts.len() > 1 {
-                if let Ok(n) = parts[1].parse::<usize>() {
-                    if n == 0 {
-                        query_options.max_rows = None;
-                        println!("Row limit removed");
-                    } else {
-                        query_options.max_rows = Some(n);
-                        println!("Row limit set to {}", n);
-                    }
-                } else {
-                    println!("Invalid limit value. Use a positive number or 0 for no limit.");
-                }
+            println!(
+                "This adds insert, update, and delete triggers to {} table(s) that record each write in {}: {}",
+                tables.len(),
+                CHANGES_TABLE,
+                tables.join(", ")
+            );
+            if yes || confirm("Install the change triggers?") {
+                install_change_triggers(conn, &tables)?;
+                println!("Recording changes; '.tables --verbose' shows them and '.track triggers off' removes the triggers.");
             } else {
-                match query_options.max_rows {
-                    None => println!("No row limit set"),
-                    Some(n) => println!("Current row limit: {}", n),
-                }
+                println!("No changes made.");
             }
-            Ok(true)
         }
-        ".timing" => {
-            query_options.show_timing = true;
-            println!("Query timing enabled");
-            Ok(true)
+        _ => println!(
+            "Usage: .track [on|off] | .track triggers [TABLE...] [--yes] | .track triggers off"
+        ),
+    }
+    Ok(())
+}
+
+/// Substitutes session variables into a command, printing the error and returning
+/// `None` if it refers to one that isn't set.
+fn expand_variables(command: &str) -> Option<String> {
+    match session_variables().lock().unwrap().expand_command(command) {
+        Ok(command) => Some(command),
+        Err(e) => {
+            println!("{}", e);
+            None
         }
-        ".notiming" => {
-            query_options.show_timing = false;
-            println!("Query timing disabled");
-            Ok(true)
+    }
+}
+
+/// Runs a command, recording it and its output in the session log if `.log` is on.
+/// Commands that hand the terminal to another program, and `.log` itself, only have the
+/// command recorded.
+fn log_command<T>(command: &str, run: impl FnOnce() -> T) -> T {
+    let base_command = command.split_whitespace().next().unwrap_or("");
+    if matches!(
+        base_command,
+        ".log" | ".pipe" | ".shell" | ".cell" | ".more" | ".browse"
+    ) {
+        note_command(command);
+        run()
+    } else {
+        record_command(command, run)
+    }
+}
+
+/// Handles `.log FILE`, `.log off`, and `.log`.
+fn handle_log_command(parts: &[&str], database: &str) -> Result<()> {
+    match parts {
+        [_] => match transcript_path() {
+            Some(path) => println!("Logging commands and output to '{}'.", path),
+            None => println!("Logging is off. Use '.log FILE' to start."),
+        },
+        [_, "off"] => match stop_transcript() {
+            Some(path) => println!("Stopped logging to '{}'.", path),
+            None => println!("Logging is off."),
+        },
+        [_, path] => {
+            start_transcript(path, database)?;
+            println!(
+                "Logging commands and output to '{}'. Use '.log off' to stop.",
+                path
+            );
         }
-        ".export" => {
-            if parts.len() > 1 {
-                let filename = parts[1];
-                let query = last_select_query.lock().unwrap().clone();
-                if query.is_empty() {
-                    println!("No SELECT query has been executed yet.");
-                } else {
-                    export_to_csv(conn, &query, filename)?;
-                }
-            } else {
-                println!("Usage: .export FILENAME");
+        _ => println!("Usage: .log FILE | .log off"),
+    }
+    Ok(())
+}
+
+/// Handles `.set NAME VALUE`, `.unset NAME`, and `.vars`.
+fn handle_variable_command(command: &str) -> Result<()> {
+    let mut variables = session_variables().lock().unwrap();
+    let (name, rest) = command
+        .split_once(char::is_whitespace)
+        .unwrap_or((command, ""));
+    let rest = rest.trim();
+    match name {
+        ".set" => match rest.split_once(char::is_whitespace) {
+            Some((variable, value)) => variables.set(variable, value.trim())?,
+            None => println!("Usage: .set NAME VALUE  (then use ${{NAME}} in queries)"),
+        },
+        ".unset" if !rest.is_empty() => {
+            if !variables.unset(rest) {
+                println!("Variable '{}' is not set.", rest);
             }
-            Ok(true)
         }
-        ".import" => {
-            if parts.len() >= 3 {
-                import_csv_to_table(conn, parts[1], parts[2])?;
-            } else {
-                println!("Usage: .import CSV_FILENAME TABLE_NAME");
+        ".unset" => println!("Usage: .unset NAME"),
+        _ => {
+            let mut any = false;
+            for (variable, value) in variables.iter() {
+                println!("  {} = {}", variable, value);
+                any = true;
+            }
+            if !any {
+                println!("No variables set. Use '.set NAME VALUE' to set one.");
             }
-            Ok(true)
         }
-        ".bookmark" => {
-            handle_bookmark_command(
-                command,
-                bookmarks,
-                last_select_query,
-                conn,
-                query_options,
-            )?;
-            Ok(true)
+    }
+    Ok(())
+}
+
+/// Handles `.watch SECONDS QUERY`: re-runs the query every few seconds, redrawing the
+/// screen each time, until Ctrl+C is pressed.
+fn handle_watch_command(
+    backend: &dyn Backend,
+    command: &str,
+    query_options: &QueryOptions,
+    last_select_query: &Arc<Mutex<LastQuery>>,
+) -> Result<()> {
+    let args = command[".watch".len()..].trim();
+    let (seconds, query) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+    let interval = match seconds.parse::<f64>() {
+        Ok(seconds) if seconds > 0.0 && seconds.is_finite() => {
+            std::time::Duration::from_secs_f64(seconds)
         }
-        ".schema" => {
-            if parts.len() > 1 {
-                show_table_schema(conn, parts[1])?;
+        _ => {
+            println!("Usage: .watch SECONDS QUERY");
+            return Ok(());
+        }
+    };
+    let query = query.trim();
+    if query.is_empty() {
+        println!("Usage: .watch SECONDS QUERY");
+        return Ok(());
+    }
+
+    catch_ctrl_c(|| loop {
+        print!("\x1B[2J\x1B[1;1H");
+        println!(
+            "Every {}s: {}    {}  (Ctrl+C to stop)\n",
+            seconds,
+            one_line(query),
+            chrono::Local::now().format("%H:%M:%S")
+        );
+        if let Err(e) = execute_sql_with_last(backend, query, query_options, last_select_query) {
+            println!("Error: {:#}", e);
+        }
+        std::io::stdout().flush()?;
+
+        let deadline = std::time::Instant::now() + interval;
+        while std::time::Instant::now() < deadline {
+            if ctrl_c_pressed() {
+                return Ok(());
+            }
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+    })
+}
+
+/// Handles `.read FILE [--echo] [--transaction] [--bail]` and its alias `.source`.
+fn handle_read_command(
+    backend: &dyn Backend,
+    parts: &[&str],
+    query_options: &QueryOptions,
+    last_select_query: &Arc<Mutex<LastQuery>>,
+) -> Result<()> {
+    let (args, echo) = take_flag(&parts[1..], "--echo");
+    let (args, transaction) = take_flag(&args, "--transaction");
+    let (args, bail) = take_flag(&args, "--bail");
+    let [path] = args.as_slice() else {
+        println!("Usage: {} FILE [--echo] [--transaction] [--bail]", parts[0]);
+        return Ok(());
+    };
+    let options = ScriptOptions {
+        echo,
+        transaction,
+        bail,
+    };
+    let report = run_sql_file(
+        backend,
+        Path::new(path),
+        &options,
+        query_options,
+        last_select_query,
+    )?;
+    if report.aborted {
+        let error = &report.errors[report.errors.len() - 1];
+        println!(
+            "Script '{}' stopped by '-- vapor:abort-if' at line {}{}.",
+            path,
+            error.line,
+            if report.rolled_back {
+                "; all of its changes were rolled back"
             } else {
-                show_all_schemas(conn)?;
+                ""
+            }
+        );
+    } else if report.rolled_back {
+        println!(
+            "Script '{}' failed at line {}; all of its changes were rolled back.",
+            path, report.errors[0].line
+        );
+    } else if report.errors.is_empty() {
+        println!("Ran {} statement(s) from '{}'.", report.executed, path);
+    } else {
+        println!(
+            "Ran {} statement(s) from '{}'; {} failed.",
+            report.executed,
+            path,
+            report.errors.len()
+        );
+    }
+    Ok(())
+}
+
+/// Handles `.script run FILE [ARGS...]` and `.script check FILE`.
+#[cfg(feature = "scripting")]
+fn handle_script_command(
+    conn: &mut Connection,
+    parts: &[&str],
+    query_options: &QueryOptions,
+) -> Result<()> {
+    match parts.get(1..) {
+        Some(["run", path, args @ ..]) => {
+            let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            let output = catch_ctrl_c(|| {
+                crate::scripting::run_script_file(conn, Path::new(path), &args, query_options)
+            })?;
+            if !output.is_empty() {
+                println!("{}", output);
             }
-            Ok(true)
         }
-        ".status" => {
-            transaction_manager.show_status();
-            Ok(true)
+        Some(["check", path]) => {
+            crate::scripting::check_script_file(Path::new(path))?;
+            println!("Script '{}' has no syntax errors.", path);
         }
-        _ => {
-            println!(
-                "Unknown command: '{}'. Type '.help' for a list of commands.",
-                command
-            );
-            Ok(true)
+        _ => println!("Usage: .script run FILE [ARGS...] | .script check FILE"),
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "scripting"))]
+fn handle_script_command(
+    _conn: &mut Connection,
+    _parts: &[&str],
+    _query_options: &QueryOptions,
+) -> Result<()> {
+    println!("Scripts need the `scripting` feature: cargo install vapor-cli --features scripting");
+    Ok(())
+}
+
+/// Runs the `--init` script, with the CLI's default output options.
+fn run_init_script(backend: &dyn Backend, path: &Path) -> Result<()> {
+    let last_select_query = Arc::new(Mutex::new(LastQuery::default()));
+    let report = run_sql_file(
+        backend,
+        path,
+        &ScriptOptions::default(),
+        &QueryOptions::for_stdout(),
+        &last_select_query,
+    )?;
+    if !report.errors.is_empty() {
+        eprintln!(
+            "Warning: {} statement(s) in '{}' failed.",
+            report.errors.len(),
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Removes a boolean `--flag` from command arguments, returning the remaining
+/// arguments and whether the flag was present.
+fn take_flag<'a>(args: &[&'a str], flag: &str) -> (Vec<&'a str>, bool) {
+    let rest: Vec<&str> = args.iter().copied().filter(|&arg| arg != flag).collect();
+    let present = rest.len() != args.len();
+    (rest, present)
+}
+
+/// Removes a `--flag VALUE` pair from command arguments, returning the remaining
+/// arguments and the flag's value if present.
+fn take_flag_value<'a>(args: &[&'a str], flag: &str) -> Result<(Vec<&'a str>, Option<&'a str>)> {
+    let (rest, values) = take_flag_values(args, flag)?;
+    Ok((rest, values.last().copied()))
+}
+
+/// Removes every `--flag VALUE` pair from command arguments, returning the remaining
+/// arguments and the flag's values in order.
+fn take_flag_values<'a>(args: &[&'a str], flag: &str) -> Result<(Vec<&'a str>, Vec<&'a str>)> {
+    let mut rest = Vec::with_capacity(args.len());
+    let mut values = Vec::new();
+    let mut iter = args.iter();
+    while let Some(&arg) = iter.next() {
+        if arg == flag {
+            match iter.next() {
+                Some(&v) => values.push(v),
+                None => anyhow::bail!("Missing value for {}", flag),
+            }
+        } else {
+            rest.push(arg);
         }
     }
+    Ok((rest, values))
 }
 
 fn handle_single_line_command(
@@ -533,7 +3450,7 @@ fn handle_single_line_command(
     conn: &mut Connection,
     transaction_manager: &TransactionManager,
     query_options: &mut QueryOptions,
-    last_select_query: &Arc<Mutex<String>>,
+    last_select_query: &Arc<Mutex<LastQuery>>,
 ) -> Result<()> {
     let line = line.trim();
     match line.to_lowercase().as_str() {
@@ -542,7 +3459,7 @@ fn handle_single_line_command(
         "rollback" | "rollback transaction" => transaction_manager.rollback_transaction(conn),
         _ => {
             // Regular SQL query
-            execute_sql(conn, line, query_options, last_select_query)
+            execute_sql_with_last(conn, line, query_options, last_select_query)
         }
     }
 }
@@ -550,7 +3467,7 @@ fn handle_single_line_command(
 fn handle_bookmark_command(
     line: &str,
     bookmarks: &Arc<Mutex<BookmarkManager>>,
-    last_select_query: &Arc<Mutex<String>>,
+    last_select_query: &Arc<Mutex<LastQuery>>,
     conn: &mut Connection,
     query_options: &QueryOptions,
 ) -> Result<()> {
@@ -574,7 +3491,14 @@ fn handle_bookmark_command(
             } else {
                 None
             };
-            let query = last_select_query.lock().unwrap().clone();
+            let query = last_select_query.lock().unwrap().sql.clone();
+            // Keep the ${...} references so the bookmark follows the variables
+            let query = session_variables()
+                .lock()
+                .unwrap()
+                .template_for(&query)
+                .map(str::to_string)
+                .unwrap_or(query);
             if query.is_empty() {
                 println!("No query to save. Execute a query first.");
             } else {
@@ -593,7 +3517,11 @@ fn handle_bookmark_command(
             let name = parts[2];
             if let Some(bookmark) = bookmarks.get_bookmark(name) {
                 println!("Executing bookmark '{}': {}", name, bookmark.query);
-                execute_sql(conn, &bookmark.query, query_options, last_select_query)?;
+                let query = session_variables()
+                    .lock()
+                    .unwrap()
+                    .expand_command(&bookmark.query)?;
+                execute_sql_with_last(conn, &query, query_options, last_select_query)?;
             } else {
                 println!("Bookmark '{}' not found.", name);
             }
@@ -627,6 +3555,151 @@ fn handle_bookmark_command(
     Ok(())
 }
 
+/// The detailed help shown by `.help`, by section. Each command's entry starts with two
+/// spaces and the command; lines indented further continue the entry above them, so
+/// `.help COMMAND` can show one command's entries on their own.
+const HELP_SECTIONS: &[(&str, &[&str])] = &[
+    (
+        "SQL Operations",
+        &[
+            "  SQL statements - Any valid SQL statement ending with semicolon",
+            "  begin/commit/rollback - Transaction control",
+            "  .safe [on|off] - Ask y/N before running DELETE or UPDATE without WHERE, DROP, or ALTER outside a transaction (default: off)",
+            "  .onerror [continue|rollback|abort] - What a failed statement does to an open transaction (default: continue)",
+            "    continue keeps the transaction, rollback rolls it back, abort refuses statements until ROLLBACK (prompt !>)",
+            "  .autocommit on|off [--batch N] - With off, INSERT/UPDATE/DELETE outside a transaction commit in batches of N (default: 1000)",
+            "    The batch is committed early by any other statement or dot-command, and at exit",
+            "  .read FILE [--echo] [--transaction] [--bail] - Run a SQL script statement by statement (alias: .source)",
+            "    --echo prints each statement, --transaction undoes the whole script if one fails, --bail stops at the first error",
+            "    Directive lines between statements: -- vapor:if COND / elif COND / else / endif, -- vapor:abort-if COND, -- vapor:set NAME VALUE|(SQL)",
+            "    COND is [not] exists table|view|index|trigger NAME, rowcount OP N (rows of the last statement), or a SQL expression",
+            "  .script run FILE [ARGS...] - Run a Rhai automation script (needs the `scripting` feature); .script check FILE checks its syntax",
+            "    Scripts call query(sql), query_value(sql), execute(sql), show(sql), export_query(sql, path), import_file(path, table), and tables(); ARGS holds the extra words",
+            "  .queue add QUERY - Queue statements to run in the background on a separate connection",
+            "  .queue run - Run the queued statements one after another; the REPL reports when they finish",
+            "  .queue [list] | cancel | clear - Show queue status, stop a run, or drop waiting statements",
+        ],
+    ),
+    (
+        "Database Information",
+        &[
+            "  tables - List all tables in the database",
+            "  .tables --verbose - List tables with when each last changed (see .track)",
+            "  .note session 'TEXT' - Note an observation in the session (kept with the database's history and in the .log)",
+            "  .notes [--all] - Show this session's notes, or the notes from every session on this database",
+            "  .track on|off - Watch for table changes while the REPL is open (PRAGMA data_version polling)",
+            "  .track triggers [TABLE...] [--yes] - Record every write to the tables, from any program, with triggers; .track triggers off removes them",
+            "  schema [table_name] - Show schema for a table or all tables",
+            "  info [json] [--exact] - Show database information and statistics; as JSON with `json` or in .format json",
+            "    Row counts are estimated from ANALYZE statistics or the largest rowid; --exact counts every row",
+            "  .counts - Show each table's row count, size on disk (table and indexes), and number of indexes",
+            "  .attach PATH AS ALIAS - Attach another database; its tables are ALIAS.table in SQL, .tables, and .schema",
+            "  .detach ALIAS - Detach a database attached with .attach or ATTACH",
+            "  .copy SOURCE TO TARGET [--create] - Copy rows between tables (e.g. main.users TO archive.users), matching columns by name; --create makes TARGET from SOURCE's columns",
+            "  .diff schema OTHER_DB [--sql] - Compare tables, columns, indexes, triggers, and views with OTHER_DB (+ only there, - only here, ~ different)",
+            "    --sql prints the CREATE, DROP, and ALTER TABLE statements that make this database's schema match OTHER_DB's",
+            "  .diff data LEFT RIGHT [--limit N] [--sql] - Compare two tables' rows by LEFT's primary key (tables can be in attached databases, e.g. archive.users)",
+            "    Lists up to N rows of each kind (default 10, 0 for all); --sql prints the DELETE, UPDATE, and INSERT statements that make LEFT's rows match RIGHT's",
+            "  .backup FILE - Copy the database to FILE with SQLite's online backup API, showing progress",
+            "  .pragma NAME [VALUE] - Show a pragma, or set it and show its new value; .pragma list shows journal_mode, synchronous, foreign_keys, cache_size, and busy_timeout",
+            "  .check [quick] - Check the database for corruption with PRAGMA integrity_check (or quick_check)",
+            "  .wal [on|off] - Show the journal mode and WAL file size, or switch WAL mode on (concurrent readers) or off",
+            "  .fk [on|off] - Show whether foreign keys are enforced, or turn enforcement on or off (on by default; set VAPOR_FOREIGN_KEYS=off to change that)",
+            "  .fkcheck [TABLE] - List rows whose foreign keys refer to missing rows, grouped by table and foreign key",
+            "  .checkpoint [PASSIVE|FULL|TRUNCATE] - Copy the write-ahead log into the database (default PASSIVE; TRUNCATE also empties the WAL file)",
+            "  .vacuum [INTO FILE] - Rebuild the database without its free pages, or write a compacted copy to FILE",
+            "  .analyze - Gather the statistics the query planner uses to choose indexes",
+            "  .reindex - Rebuild every index",
+            "  .version [set N] - Show or stamp the schema version (PRAGMA user_version)",
+            "  .describe TABLE - Show a table's columns, indexes, foreign keys, triggers, CHECK constraints, and row counts (live/deleted with soft delete)",
+            "  .peek TABLE [N] - Show the first N rows of a table and its row counts (default: 10)",
+            "  .find PATTERN [TABLE] - Search every text column of every table (or TABLE) for PATTERN, ignoring case; % and _ are wildcards, quote PATTERN to include spaces",
+            "  .browse - Pick a table from a list that narrows as you type, then see its first rows and per-column stats (nulls, distinct values, min, max)",
+            "  .timestamps TABLE [--yes] - Preview, then add created_at/updated_at columns and the triggers that maintain them",
+            "  .softdelete enable|disable TABLE - Add a deleted_at column, TABLE_live/TABLE_deleted views, and a trigger that turns DELETE into a soft delete",
+            "  .create-table - Create a table by answering prompts for its name, columns, generated columns, CHECK constraints, and whether it is STRICT",
+            "  .typecheck [TABLE] - List values whose stored type doesn't match their column's declared type, in every table or TABLE",
+            "  .strictify TABLE [--yes] - Preview, then rebuild TABLE as a STRICT table with its rows, indexes, and triggers",
+        ],
+    ),
+    (
+        "Output Control",
+        &[
+            "  .format [table|json|csv|plain] - Set output format (default: table, or plain when piped)",
+            "  .limit [N] - Set row limit, 0 for no limit (default: 1000)",
+            "  .timing [on|off] - Toggle query timing (default: on)",
+            "  .output FILE - Write the results of following queries to FILE in the current format",
+            "  .output stdout - Write query results to the console again",
+            "  .once FILE - Write only the next query's results to FILE",
+            "  .pipe 'COMMAND' - Stream the next query's results into a shell command",
+            "  .cell [ROW] [COLUMN] - Page a value from the last result in full (default: the first value cut short in the table); .more is an alias",
+            "  .highlight [on|off] - Toggle SQL syntax highlighting in the prompt (default: on unless NO_COLOR is set)",
+            "  .export FILENAME [FORMAT] [--compress gzip|zstd] [--resume] [--bom] [--background] - Export last SELECT query (csv, json, xlsx, xml; default from extension; --bom marks CSV as UTF-8 for Excel; --background runs it as a job)",
+            "  .jobs                   - List background jobs and their progress",
+            "  .job status N           - Show rows written, elapsed time, and rate for job N",
+            "  .watch SECONDS QUERY    - Re-run QUERY every SECONDS, redrawing the screen, until Ctrl+C",
+            "  .set NAME VALUE         - Set a session variable; ${NAME} in SQL and commands is replaced by VALUE",
+            "  .unset NAME             - Remove a session variable",
+            "  .vars                   - List session variables",
+            "  .use PATH|PROFILE       - Switch to another database, rolling back an open transaction",
+            "  .use --save NAME        - Save the current database as profile NAME (.use --forget NAME removes it)",
+            "  .log FILE               - Append every command and its output to FILE, with timestamps",
+            "  .log off                - Stop logging (.log alone shows where the log goes)",
+            "  .exportall DIRECTORY - Export every table to DIRECTORY as CSV files with a manifest.json",
+            "  .import FILENAME|- TABLE_NAME [FORMAT] [--create-table] [--record PATH] - Import CSV, JSON/NDJSON, XML, or another format (.gz/.zst/.zip accepted; - reads stdin)",
+            "    CSV: --sniff (detect the dialect; other flags override it), --escape C, --line-ending crlf|lf|cr",
+            "         --delimiter C, --quote C, --no-header, --null STR, --skip N, --encoding utf-8|latin-1|windows-1252|shift-jis",
+            "         --batch-size N, --continue-on-error, --reject FILE",
+            "    CSV, JSON, XML: --on-conflict fail|ignore|replace (skip or replace rows that collide with existing ones)",
+            "                    --set COLUMN=VALUE (repeatable; fill a column for every row, e.g. source={{filename}} or at=now())",
+        ],
+    ),
+    (
+        "History",
+        &[
+            "  .history [N] - Show the last N history entries with their numbers (default: 20)",
+            "  .history run N - Run history entry N again",
+            "  .last - Put the previous SQL statement back in the prompt to edit and re-run",
+            "  .edit - Open the statement being typed, or the previous one, in $VISUAL/$EDITOR; the saved text is loaded back into the prompt",
+            "  Ctrl+R - Search history backwards as you type (history size: VAPOR_HISTORY_SIZE, default 1000)",
+        ],
+    ),
+    (
+        "Bookmarks",
+        &[
+            "  .bookmark save NAME [DESC] - Save current query as bookmark",
+            "  .bookmark list - List all saved bookmarks",
+            "  .bookmark run NAME - Execute a saved bookmark",
+            "  .bookmark show NAME - Show bookmark details",
+            "  .bookmark delete NAME - Delete a bookmark",
+        ],
+    ),
+    (
+        "Session Management",
+        &[
+            "  .status - Show transaction status, with how long an open transaction has run, its statements, and rows changed",
+            "  .show settings - Show the output settings, safe mode, startup file, and hooks in effect",
+            "    Hooks are SQL in ~/.vapor/hooks.json run at session start (on_start), before each statement (before_query), and at exit (on_exit)",
+            "  clear - Clear the screen",
+            "  .tips [on|off] - Show a tip now and then about features you haven't used, such as bookmarking a query you keep retyping (default: off)",
+            "    Tips come from the usage counts in ~/.vapor/usage.json and never leave your machine; each is shown once",
+            "  help [COMMAND] - Show this help message, or one command's usage, options, and examples (also .COMMAND ?)",
+            "  exit/quit - Exit the REPL",
+        ],
+    ),
+    (
+        "Features",
+        &[
+            "  • Multi-line input support (continue until semicolon)",
+            "  • Command history with arrow keys",
+            "  • Query timing and result pagination",
+            "  • Transaction status in prompt (* indicates active transaction, ! an aborted one)",
+            "  • Multiple output formats (table, JSON, CSV)",
+            "  • Query bookmarking system",
+        ],
+    ),
+];
+
 /// Displays detailed help information for all REPL commands.
 ///
 /// This function prints a comprehensive list of available special commands (`.commands`),
@@ -635,40 +3708,258 @@ fn handle_bookmark_command(
 pub fn show_help() {
     println!("Enhanced REPL Commands:");
     println!();
-    println!("SQL Operations:");
-    println!("  SQL statements - Any valid SQL statement ending with semicolon");
-    println!("  begin/commit/rollback - Transaction control");
-    println!();
-    println!("Database Information:");
-    println!("  tables - List all tables in the database");
-    println!("  schema [table_name] - Show schema for a table or all tables");
-    println!("  info - Show database information and statistics");
-    println!();
-    println!("Output Control:");
-    println!("  .format [table|json|csv] - Set output format (default: table)");
-    println!("  .limit [N] - Set row limit, 0 for no limit (default: 1000)");
-    println!("  .timing [on|off] - Toggle query timing (default: on)");
-    println!("  .export FILENAME - Export last SELECT query to CSV file");
-    println!("  .import CSV_FILENAME TABLE_NAME - Import CSV file into table");
-    println!();
-    println!("Bookmarks:");
-    println!("  .bookmark save NAME [DESC] - Save current query as bookmark");
-    println!("  .bookmark list - List all saved bookmarks");
-    println!("  .bookmark run NAME - Execute a saved bookmark");
-    println!("  .bookmark show NAME - Show bookmark details");
-    println!("  .bookmark delete NAME - Delete a bookmark");
-    println!();
-    println!("Session Management:");
-    println!("  .status - Show transaction status");
-    println!("  clear - Clear the screen");
-    println!("  help - Show this help message");
-    println!("  exit/quit - Exit the REPL");
-    println!();
-    println!("Features:");
-    println!("  • Multi-line input support (continue until semicolon)");
-    println!("  • Command history with arrow keys");
-    println!("  • Query timing and result pagination");
-    println!("  • Transaction status in prompt (* indicates active transaction)");
-    println!("  • Multiple output formats (table, JSON, CSV)");
-    println!("  • Query bookmarking system");
+    let usage = UsageStats::load();
+    let top = usage.top_commands(5);
+    if !top.is_empty() {
+        let top: Vec<String> = top
+            .iter()
+            .map(|(command, count)| format!("{} ({})", command, count))
+            .collect();
+        println!("Your most used: {}", top.join(", "));
+        println!();
+    }
+    for (i, (section, lines)) in HELP_SECTIONS.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("{}:", section);
+        for line in *lines {
+            println!("{}", line);
+        }
+    }
+}
+
+/// Other names commands go by, for `.help NAME`.
+const HELP_ALIASES: &[(&str, &str)] = &[("source", "read"), ("more", "cell"), ("quit", "exit")];
+
+/// Examples shown by `.help COMMAND` for the commands with the most options.
+const HELP_EXAMPLES: &[(&str, &[&str])] = &[
+    (
+        "export",
+        &[
+            ".export users.csv",
+            ".export report.xlsx",
+            ".export events.json.gz --compress gzip --background",
+        ],
+    ),
+    (
+        "import",
+        &[
+            ".import customers.csv customers --create-table",
+            ".import data.csv staging --sniff --on-conflict ignore",
+            ".import - events ndjson",
+        ],
+    ),
+    (
+        "read",
+        &[
+            ".read migrations/004.sql --transaction",
+            ".read seed.sql --echo --bail",
+        ],
+    ),
+    (
+        "find",
+        &[".find alice@example.com", ".find 'new york' customers"],
+    ),
+    (
+        "watch",
+        &[".watch 5 SELECT COUNT(*) FROM jobs WHERE status = 'running';"],
+    ),
+    (
+        "set",
+        &[
+            ".set since 2024-01-01",
+            "SELECT * FROM orders WHERE created_at >= '${since}';",
+        ],
+    ),
+    ("info", &[".info", ".info json --exact"]),
+    (
+        "track",
+        &[
+            ".track on",
+            ".track triggers orders customers --yes",
+            ".tables --verbose",
+        ],
+    ),
+    (
+        "note",
+        &[
+            ".note session 'order 123 already refunded'",
+            ".note add customers 42 possible duplicate of 17",
+        ],
+    ),
+    (
+        "pragma",
+        &[
+            ".pragma list",
+            ".pragma cache_size -64000",
+            ".pragma table_info users",
+        ],
+    ),
+    ("copy", &[".copy main.users TO archive.users --create"]),
+    (
+        "diff",
+        &[
+            ".diff schema production.db",
+            ".diff schema production.db --sql",
+            ".diff data users archive.users --limit 5",
+        ],
+    ),
+    ("use", &[".use other.db", ".use --save prod", ".use prod"]),
+    (
+        "bookmark",
+        &[
+            ".bookmark save active_users Users seen this week",
+            ".bookmark run active_users",
+        ],
+    ),
+];
+
+/// Whether a help entry or summary line is for `command` (without its leading `.`). The
+/// entry's first word names the command, with alternatives separated by `/`.
+fn help_entry_matches(entry: &str, command: &str) -> bool {
+    entry
+        .split_whitespace()
+        .next()
+        .unwrap_or("")
+        .split('/')
+        .any(|name| name.trim_start_matches('.').eq_ignore_ascii_case(command))
+}
+
+/// Prints the help for one command, for `.help COMMAND` and `.COMMAND ?`: its summary,
+/// its entries in the detailed help, and examples.
+fn print_command_help(name: &str) {
+    let command = name.trim_start_matches('.').to_lowercase();
+    let command = HELP_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == command)
+        .map_or(command.as_str(), |(_, target)| target);
+
+    let summary: Vec<_> = HELP_SUMMARY
+        .iter()
+        .filter(|(usage, _)| help_entry_matches(usage, command))
+        .collect();
+    let mut entries: Vec<(&str, Vec<&str>)> = Vec::new();
+    for (section, lines) in HELP_SECTIONS {
+        let mut matched = Vec::new();
+        let mut in_entry = false;
+        for line in *lines {
+            if line.starts_with("    ") {
+                if in_entry {
+                    matched.push(*line);
+                }
+            } else {
+                in_entry = help_entry_matches(line, command);
+                if in_entry {
+                    matched.push(*line);
+                }
+            }
+        }
+        if !matched.is_empty() {
+            entries.push((section, matched));
+        }
+    }
+    let examples = HELP_EXAMPLES
+        .iter()
+        .find(|(example_command, _)| *example_command == command)
+        .map(|(_, examples)| *examples);
+
+    if summary.is_empty() && entries.is_empty() {
+        println!("No help for '{}'. Type .help to see every command.", name);
+        return;
+    }
+    let mut blocks: Vec<String> = Vec::new();
+    if !summary.is_empty() {
+        let lines: Vec<String> = summary
+            .iter()
+            .map(|(usage, description)| format!("{} - {}", usage, description))
+            .collect();
+        blocks.push(lines.join("\n"));
+    }
+    for (section, lines) in entries {
+        blocks.push(format!("{}:\n{}", section, lines.join("\n")));
+    }
+    if let Some(examples) = examples {
+        blocks.push(format!("Examples:\n  {}", examples.join("\n  ")));
+    }
+    println!("{}", blocks.join("\n\n"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_run_with_full_history() {
+        let max = 5;
+        let config = rustyline::Config::builder()
+            .max_history_size(max)
+            .unwrap()
+            .build();
+        let mut rl = ReplEditor::with_config(config).unwrap();
+        for n in 1..=max {
+            record_history(&mut rl, &format!("SELECT {}", n));
+        }
+        assert_eq!(rl.history().iter().count(), max);
+
+        // The command itself isn't recorded, so entry 2 is still the one listed as 2
+        let command = ".history run 2";
+        record_history(&mut rl, command);
+        assert_eq!(
+            history_command(&mut rl, command).as_deref(),
+            Some("SELECT 2")
+        );
+        assert_eq!(rl.history().iter().count(), max);
+        assert_eq!(
+            rl.history().iter().last().map(String::as_str),
+            Some("SELECT 2")
+        );
+    }
+
+    #[test]
+    fn test_database_history_seeded_from_global_history() {
+        let max = 5;
+        let editor = || {
+            let config = rustyline::Config::builder()
+                .max_history_size(max)
+                .unwrap()
+                .build();
+            ReplEditor::with_config(config).unwrap()
+        };
+        let entries = |rl: &ReplEditor| -> Vec<String> { rl.history().iter().cloned().collect() };
+        let dir = tempfile::tempdir().unwrap();
+        let global = dir.path().join("repl_history");
+        let history = dir.path().join("history");
+
+        // Without either file, the history starts empty and nothing is written
+        let mut rl = editor();
+        load_history_file(&mut rl, &history, &global);
+        assert!(entries(&rl).is_empty());
+        assert!(!history.exists());
+
+        // A global history from before the upgrade seeds the database's own file, and
+        // loading it keeps only the newest entries up to the history size
+        let mut old = rustyline::DefaultEditor::new().unwrap();
+        for n in 1..=8 {
+            old.add_history_entry(format!("SELECT {}", n)).unwrap();
+        }
+        old.save_history(&global).unwrap();
+        let mut rl = editor();
+        load_history_file(&mut rl, &history, &global);
+        assert!(history.exists());
+        let seeded: Vec<String> = (4..=8).map(|n| format!("SELECT {}", n)).collect();
+        assert_eq!(entries(&rl), seeded);
+
+        // Once the database has its own history, the global one is left alone, and a
+        // saved history stays within the size limit
+        record_history(&mut rl, "SELECT 9");
+        rl.save_history(&history).unwrap();
+        let mut saved = rustyline::DefaultEditor::new().unwrap();
+        saved.load_history(&history).unwrap();
+        let kept: Vec<String> = (5..=9).map(|n| format!("SELECT {}", n)).collect();
+        assert_eq!(saved.history().iter().cloned().collect::<Vec<_>>(), kept);
+        let mut rl = editor();
+        load_history_file(&mut rl, &history, &global);
+        assert_eq!(entries(&rl), kept);
+    }
 }
@@ -0,0 +1,394 @@
+//! # Replica Verification
+//!
+//! This module compares a primary SQLite database with a copy of it (for example, a file
+//! synced to another device) and reports where the two have diverged. It is a check
+//! rather than a full diff: row contents are compared through checksums of a sample of
+//! rows, not row by row.
+//!
+//! ## Features:
+//! - **Schema versions**: Compares `PRAGMA schema_version` and `PRAGMA user_version`.
+//! - **Table definitions**: Reports tables that are missing on either side or whose
+//!   `CREATE` statements differ.
+//! - **Row counts**: Compares the number of rows in every table.
+//! - **Sampled checksums**: Hashes an evenly spaced sample of each table's rows, in a
+//!   deterministic order. The order comes from sorting every row by all its columns, so
+//!   each table is still read and sorted in full; sampling only limits what is hashed.
+
+use anyhow::{Context, Result};
+use rusqlite::types::ValueRef;
+use rusqlite::{Connection, OpenFlags};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::Hasher;
+
+use crate::formats::quote_identifier;
+
+/// The default number of rows sampled per table for checksums.
+pub const DEFAULT_SAMPLE_ROWS: usize = 1000;
+
+/// Options for `verify_replica`.
+#[derive(Debug, Clone)]
+pub struct VerifyOptions {
+    /// The number of rows per table to include in the checksum. `None` checksums every row.
+    pub sample_rows: Option<usize>,
+}
+
+impl Default for VerifyOptions {
+    fn default() -> Self {
+        Self {
+            sample_rows: Some(DEFAULT_SAMPLE_ROWS),
+        }
+    }
+}
+
+/// The comparison of one table between the primary and the replica.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableComparison {
+    /// The table name.
+    pub name: String,
+    /// Whether the table exists in the primary database.
+    pub in_primary: bool,
+    /// Whether the table exists in the replica database.
+    pub in_replica: bool,
+    /// Whether the `CREATE TABLE` statements are identical.
+    pub definition_matches: bool,
+    /// The row count in the primary, if the table exists there.
+    pub primary_rows: Option<usize>,
+    /// The row count in the replica, if the table exists there.
+    pub replica_rows: Option<usize>,
+    /// The sampled checksum in the primary, if the table exists there.
+    pub primary_checksum: Option<u64>,
+    /// The sampled checksum in the replica, if the table exists there.
+    pub replica_checksum: Option<u64>,
+}
+
+impl TableComparison {
+    /// Returns true if the table is identical as far as the checks can tell.
+    pub fn matches(&self) -> bool {
+        self.in_primary
+            && self.in_replica
+            && self.definition_matches
+            && self.primary_rows == self.replica_rows
+            && self.primary_checksum == self.replica_checksum
+    }
+}
+
+/// The result of comparing a primary database with a replica.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplicaReport {
+    /// `PRAGMA schema_version` of the primary and the replica.
+    pub schema_version: (i64, i64),
+    /// `PRAGMA user_version` of the primary and the replica.
+    pub user_version: (i64, i64),
+    /// Every table found in either database, sorted by name.
+    pub tables: Vec<TableComparison>,
+}
+
+impl ReplicaReport {
+    /// Returns true if no divergence was found.
+    pub fn is_consistent(&self) -> bool {
+        self.schema_version.0 == self.schema_version.1
+            && self.user_version.0 == self.user_version.1
+            && self.tables.iter().all(TableComparison::matches)
+    }
+
+    /// Returns a human-readable description of every divergence found.
+    pub fn divergences(&self) -> Vec<String> {
+        let mut found = Vec::new();
+        if self.schema_version.0 != self.schema_version.1 {
+            found.push(format!(
+                "schema_version differs: primary {}, replica {}",
+                self.schema_version.0, self.schema_version.1
+            ));
+        }
+        if self.user_version.0 != self.user_version.1 {
+            found.push(format!(
+                "user_version differs: primary {}, replica {}",
+                self.user_version.0, self.user_version.1
+            ));
+        }
+
+        for table in &self.tables {
+            if !table.in_replica {
+                found.push(format!(
+                    "table '{}' is missing from the replica",
+                    table.name
+                ));
+                continue;
+            }
+            if !table.in_primary {
+                found.push(format!("table '{}' only exists in the replica", table.name));
+                continue;
+            }
+            if !table.definition_matches {
+                found.push(format!("table '{}' has a different definition", table.name));
+            }
+            if table.primary_rows != table.replica_rows {
+                found.push(format!(
+                    "table '{}' row count differs: primary {}, replica {}",
+                    table.name,
+                    table.primary_rows.unwrap_or(0),
+                    table.replica_rows.unwrap_or(0)
+                ));
+            } else if table.primary_checksum != table.replica_checksum {
+                found.push(format!(
+                    "table '{}' sampled checksum differs: primary {:016x}, replica {:016x}",
+                    table.name,
+                    table.primary_checksum.unwrap_or(0),
+                    table.replica_checksum.unwrap_or(0)
+                ));
+            }
+        }
+        found
+    }
+
+    /// Prints a per-table summary followed by any divergences.
+    pub fn print(&self) {
+        println!(
+            "schema_version: {} / {}    user_version: {} / {}",
+            self.schema_version.0, self.schema_version.1, self.user_version.0, self.user_version.1
+        );
+        for table in &self.tables {
+            let status = if table.matches() { "ok" } else { "DIVERGED" };
+            println!(
+                "  {:<30} {:>10} / {:<10} {}",
+                table.name,
+                count_label(table.primary_rows),
+                count_label(table.replica_rows),
+                status
+            );
+        }
+
+        let divergences = self.divergences();
+        if divergences.is_empty() {
+            println!("Replica is consistent with the primary.");
+        } else {
+            println!("Found {} divergence(s):", divergences.len());
+            for divergence in divergences {
+                println!("  - {}", divergence);
+            }
+        }
+    }
+}
+
+fn count_label(count: Option<usize>) -> String {
+    count.map_or_else(|| "-".to_string(), |c| c.to_string())
+}
+
+/// The facts gathered about one database before comparison.
+struct Snapshot {
+    schema_version: i64,
+    user_version: i64,
+    /// Table name to (definition, row count, sampled checksum).
+    tables: BTreeMap<String, (String, usize, u64)>,
+}
+
+/// Opens two database files read-only and compares them.
+///
+/// # Arguments
+///
+/// * `primary` - The path to the primary database file.
+/// * `replica` - The path to the replica database file.
+/// * `options` - Controls how many rows are sampled for checksums.
+///
+/// # Returns
+///
+/// A `Result` containing the `ReplicaReport`, or an `Err` if either file cannot be opened
+/// or read.
+pub fn verify_replica_files(
+    primary: &str,
+    replica: &str,
+    options: &VerifyOptions,
+) -> Result<ReplicaReport> {
+    let open = |path: &str| {
+        if !std::path::Path::new(path).exists() {
+            anyhow::bail!("Database '{}' not found", path);
+        }
+        Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .with_context(|| format!("Failed to open database '{}'", path))
+    };
+    let primary_conn = open(primary)?;
+    let replica_conn = open(replica)?;
+    verify_replica(&primary_conn, &replica_conn, options)
+}
+
+/// Compares a primary database with a replica.
+///
+/// # Arguments
+///
+/// * `primary` - A connection to the primary database.
+/// * `replica` - A connection to the replica database.
+/// * `options` - Controls how many rows are sampled for checksums.
+///
+/// # Returns
+///
+/// A `Result` containing the `ReplicaReport`, or an `Err` if either database cannot be
+/// read.
+pub fn verify_replica(
+    primary: &Connection,
+    replica: &Connection,
+    options: &VerifyOptions,
+) -> Result<ReplicaReport> {
+    let primary = snapshot(primary, options).context("Failed to read the primary database")?;
+    let replica = snapshot(replica, options).context("Failed to read the replica database")?;
+
+    let mut names: Vec<&String> = primary.tables.keys().chain(replica.tables.keys()).collect();
+    names.sort();
+    names.dedup();
+
+    let tables = names
+        .into_iter()
+        .map(|name| {
+            let p = primary.tables.get(name);
+            let r = replica.tables.get(name);
+            TableComparison {
+                name: name.clone(),
+                in_primary: p.is_some(),
+                in_replica: r.is_some(),
+                definition_matches: matches!((p, r), (Some(p), Some(r)) if p.0 == r.0),
+                primary_rows: p.map(|t| t.1),
+                replica_rows: r.map(|t| t.1),
+                primary_checksum: p.map(|t| t.2),
+                replica_checksum: r.map(|t| t.2),
+            }
+        })
+        .collect();
+
+    Ok(ReplicaReport {
+        schema_version: (primary.schema_version, replica.schema_version),
+        user_version: (primary.user_version, replica.user_version),
+        tables,
+    })
+}
+
+fn snapshot(conn: &Connection, options: &VerifyOptions) -> Result<Snapshot> {
+    let schema_version = conn.query_row("PRAGMA schema_version", [], |row| row.get(0))?;
+    let user_version = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+    let mut stmt = conn.prepare(
+        "SELECT name, sql FROM sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%'",
+    )?;
+    let definitions = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut tables = BTreeMap::new();
+    for (name, sql) in definitions {
+        let (rows, checksum) = table_checksum(conn, &name, options.sample_rows)
+            .with_context(|| format!("Failed to checksum table '{}'", name))?;
+        tables.insert(name, (sql.unwrap_or_default(), rows, checksum));
+    }
+
+    Ok(Snapshot {
+        schema_version,
+        user_version,
+        tables,
+    })
+}
+
+/// Counts a table's rows and hashes an evenly spaced sample of them.
+///
+/// Rows are read ordered by every column, so two tables with the same contents produce
+/// the same checksum regardless of their physical layout (rowids can differ after a
+/// `VACUUM`). That sort reads the whole table, whatever the sample size.
+fn table_checksum(
+    conn: &Connection,
+    table: &str,
+    sample_rows: Option<usize>,
+) -> Result<(usize, u64)> {
+    let quoted = quote_identifier(table);
+    let count: usize = conn.query_row(&format!("SELECT COUNT(*) FROM {}", quoted), [], |row| {
+        row.get::<_, i64>(0).map(|c| c as usize)
+    })?;
+
+    let column_count = conn
+        .prepare(&format!("SELECT * FROM {} LIMIT 0", quoted))?
+        .column_count();
+    let order_by = (1..=column_count)
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let step = match sample_rows {
+        Some(sample) if sample > 0 && count > sample => count / sample,
+        _ => 1,
+    };
+
+    let mut hasher = DefaultHasher::new();
+    let mut stmt = conn.prepare(&format!("SELECT * FROM {} ORDER BY {}", quoted, order_by))?;
+    let mut rows = stmt.query([])?;
+    let mut until_next_sample = 0usize;
+    while let Some(row) = rows.next()? {
+        if until_next_sample == 0 {
+            for i in 0..column_count {
+                hash_value(&mut hasher, row.get_ref(i)?);
+            }
+            until_next_sample = step;
+        }
+        until_next_sample -= 1;
+    }
+    Ok((count, hasher.finish()))
+}
+
+fn hash_value(hasher: &mut DefaultHasher, value: ValueRef) {
+    match value {
+        ValueRef::Null => hasher.write_u8(0),
+        ValueRef::Integer(i) => {
+            hasher.write_u8(1);
+            hasher.write_i64(i);
+        }
+        ValueRef::Real(f) => {
+            hasher.write_u8(2);
+            hasher.write_u64(f.to_bits());
+        }
+        ValueRef::Text(bytes) | ValueRef::Blob(bytes) => {
+            hasher.write_u8(3);
+            hasher.write_usize(bytes.len());
+            hasher.write(bytes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn database(rows: &[(i64, &str)]) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);")
+            .unwrap();
+        for (id, name) in rows {
+            conn.execute(
+                "INSERT INTO users VALUES (?1, ?2)",
+                rusqlite::params![id, name],
+            )
+            .unwrap();
+        }
+        conn
+    }
+
+    #[test]
+    fn test_verify_replica_detects_divergence() {
+        let rows = [(1, "ada"), (2, "grace"), (3, "linus")];
+        let primary = database(&rows);
+        let options = VerifyOptions::default();
+
+        let report = verify_replica(&primary, &database(&rows), &options).unwrap();
+        assert!(report.is_consistent(), "{:?}", report.divergences());
+
+        let changed = database(&[(1, "ada"), (2, "grace"), (3, "linux")]);
+        let report = verify_replica(&primary, &changed, &options).unwrap();
+        assert!(!report.is_consistent());
+        assert!(report.divergences()[0].contains("checksum"));
+
+        let missing = database(&rows[..2]);
+        missing.execute_batch("CREATE TABLE extra (x);").unwrap();
+        let report = verify_replica(&primary, &missing, &options).unwrap();
+        let divergences = report.divergences();
+        assert!(divergences.iter().any(|d| d.contains("row count differs")));
+        assert!(divergences
+            .iter()
+            .any(|d| d.contains("only exists in the replica")));
+    }
+}
@@ -0,0 +1,719 @@
+//! # WAL Replication
+//!
+//! This module continuously copies a SQLite database in WAL mode to a backup directory,
+//! in the style of Litestream, so it can later be restored to a point in time. It is
+//! experimental and deliberately small: it ships to local (or mounted) directories only.
+//!
+//! ## Features:
+//! - **Generations**: Each time the database's write-ahead log restarts, a new
+//!   generation begins with a raw snapshot of the database file.
+//! - **WAL segments**: Committed WAL frames are copied into timestamped segment files as
+//!   they appear. Frames are checked against their salts and checksums, and only whole
+//!   transactions are shipped.
+//! - **Point-in-time restore**: `restore_database` picks the latest generation started
+//!   before the requested time, replays its segments up to that time, and checkpoints
+//!   the result into a standalone database file.
+//! - **Backup directories**: Plain directories of timestamped database backups can be
+//!   restored from too, picking the newest backup taken before the requested time.
+//!
+//! ## Layout
+//!
+//! ```text
+//! <target>/generations/<started-at>/snapshot.db
+//! <target>/generations/<started-at>/wal-header
+//! <target>/generations/<started-at>/wal/<first-frame>-<captured-at>.wal
+//! ```
+//!
+//! Points in time between a checkpoint that restarts the log and the next poll are not
+//! recoverable individually; the next generation's snapshot contains their changes.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use rusqlite::Connection;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const WAL_HEADER_SIZE: usize = 32;
+const FRAME_HEADER_SIZE: usize = 24;
+const SNAPSHOT_FILE: &str = "snapshot.db";
+const WAL_HEADER_FILE: &str = "wal-header";
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%S%.3fZ";
+
+/// Options for `replicate`.
+#[derive(Debug, Clone)]
+pub struct ReplicateOptions {
+    /// How often to check the write-ahead log for new frames.
+    pub interval: Duration,
+    /// Copy what is there once and return instead of running until interrupted.
+    pub once: bool,
+}
+
+impl Default for ReplicateOptions {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            once: false,
+        }
+    }
+}
+
+/// The parsed header of a write-ahead log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct WalHeader {
+    bytes: [u8; WAL_HEADER_SIZE],
+    page_size: usize,
+    big_endian: bool,
+}
+
+impl WalHeader {
+    /// Parses and validates a WAL header, returning `None` if it is missing or incomplete.
+    fn parse(bytes: &[u8]) -> Option<Self> {
+        let bytes: [u8; WAL_HEADER_SIZE] = bytes.get(..WAL_HEADER_SIZE)?.try_into().ok()?;
+        let big_endian = match read_u32_be(&bytes[0..4]) {
+            0x377f_0682 => false,
+            0x377f_0683 => true,
+            _ => return None,
+        };
+        let page_size = match read_u32_be(&bytes[8..12]) {
+            1 => 65536,
+            size => size as usize,
+        };
+        let checksum = wal_checksum(big_endian, &bytes[..24], (0, 0));
+        if checksum != stored_checksum(&bytes[24..32]) || page_size < 512 {
+            return None;
+        }
+        Some(Self {
+            bytes,
+            page_size,
+            big_endian,
+        })
+    }
+
+    /// The salts that identify this incarnation of the log.
+    fn salts(&self) -> &[u8] {
+        &self.bytes[16..24]
+    }
+
+    fn checksum(&self) -> (u32, u32) {
+        stored_checksum(&self.bytes[24..32])
+    }
+
+    fn frame_size(&self) -> usize {
+        FRAME_HEADER_SIZE + self.page_size
+    }
+}
+
+/// The generation currently being shipped.
+struct Generation {
+    dir: PathBuf,
+    header: Option<WalHeader>,
+    /// The number of frames shipped so far.
+    frames: u64,
+    /// The running checksum after the last shipped frame.
+    checksum: (u32, u32),
+}
+
+/// Continuously replicates a database to a backup directory.
+///
+/// The database is switched to WAL mode if it isn't already. On each poll the
+/// replicator takes a read transaction (which stops the log from restarting underneath
+/// it), starts a new generation if the log has restarted, and copies any newly
+/// committed frames into a segment file.
+///
+/// # Arguments
+///
+/// * `db_path` - The path to the database file to replicate.
+/// * `target` - The backup directory. `s3://` targets are not supported yet.
+/// * `options` - The poll interval and whether to stop after one pass.
+///
+/// # Returns
+///
+/// A `Result` that is `Ok` once a single pass completes (with `once`), or an `Err` if
+/// the database or target cannot be read or written.
+pub fn replicate(db_path: &str, target: &str, options: &ReplicateOptions) -> Result<()> {
+    if target.contains("://") {
+        anyhow::bail!(
+            "Replication target '{}' is not supported yet; replicate to a directory instead",
+            target
+        );
+    }
+    if !Path::new(db_path).exists() {
+        anyhow::bail!("Database '{}' not found", db_path);
+    }
+
+    let conn = Connection::open(db_path)
+        .with_context(|| format!("Failed to open database '{}'", db_path))?;
+    let mode: String = conn.query_row("PRAGMA journal_mode", [], |row| row.get(0))?;
+    if !mode.eq_ignore_ascii_case("wal") {
+        conn.query_row("PRAGMA journal_mode=WAL", [], |row| row.get::<_, String>(0))
+            .context("Failed to switch the database to WAL mode")?;
+        println!("Switched '{}' to WAL mode for replication.", db_path);
+    }
+    conn.execute_batch("PRAGMA wal_autocheckpoint=0;")?;
+
+    let target = PathBuf::from(target);
+    fs::create_dir_all(target.join("generations"))
+        .with_context(|| format!("Failed to create replication target '{}'", target.display()))?;
+
+    let wal_path = PathBuf::from(format!("{}-wal", db_path));
+    let mut generation: Option<Generation> = None;
+    if !options.once {
+        println!(
+            "Replicating '{}' to '{}' (press Ctrl+C to stop)...",
+            db_path,
+            target.display()
+        );
+    }
+
+    loop {
+        conn.execute_batch("BEGIN")?;
+        // Reading the schema starts the read transaction that pins the log
+        conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| {
+            row.get::<_, i64>(0)
+        })?;
+        let result = poll(Path::new(db_path), &wal_path, &target, &mut generation);
+        conn.execute_batch("COMMIT")?;
+        result?;
+
+        if options.once {
+            return Ok(());
+        }
+        std::thread::sleep(options.interval);
+    }
+}
+
+/// Runs one replication pass while the caller holds a read transaction.
+fn poll(
+    db_path: &Path,
+    wal_path: &Path,
+    target: &Path,
+    generation: &mut Option<Generation>,
+) -> Result<()> {
+    let mut wal = Vec::new();
+    let mut wal_file = match fs::File::open(wal_path) {
+        Ok(file) => Some(file),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to open '{}'", wal_path.display()))
+        }
+    };
+    if let Some(file) = wal_file.as_mut() {
+        file.by_ref()
+            .take(WAL_HEADER_SIZE as u64)
+            .read_to_end(&mut wal)?;
+    }
+    let header = WalHeader::parse(&wal);
+
+    let restarted = match generation {
+        Some(current) => {
+            current.header.as_ref().map(WalHeader::salts) != header.as_ref().map(WalHeader::salts)
+        }
+        None => true,
+    };
+    if restarted {
+        *generation = Some(start_generation(db_path, target, header.clone())?);
+    }
+
+    let (Some(current), Some(header), Some(file)) = (generation.as_mut(), header, wal_file) else {
+        return Ok(());
+    };
+    ship_frames(current, &header, file)
+}
+
+/// Starts a new generation with a raw copy of the database file.
+fn start_generation(
+    db_path: &Path,
+    target: &Path,
+    header: Option<WalHeader>,
+) -> Result<Generation> {
+    let dir = target
+        .join("generations")
+        .join(Utc::now().format(TIMESTAMP_FORMAT).to_string());
+    fs::create_dir_all(dir.join("wal"))
+        .with_context(|| format!("Failed to create generation '{}'", dir.display()))?;
+
+    write_atomically(&dir.join(SNAPSHOT_FILE), &fs::read(db_path)?)
+        .context("Failed to write generation snapshot")?;
+    if let Some(header) = &header {
+        write_atomically(&dir.join(WAL_HEADER_FILE), &header.bytes)?;
+    }
+    println!("Started generation {}", dir.display());
+
+    Ok(Generation {
+        checksum: header.as_ref().map_or((0, 0), WalHeader::checksum),
+        dir,
+        header,
+        frames: 0,
+    })
+}
+
+/// Copies newly committed frames of the current generation into a segment file.
+fn ship_frames(generation: &mut Generation, header: &WalHeader, mut wal: fs::File) -> Result<()> {
+    let frame_size = header.frame_size();
+    let start = WAL_HEADER_SIZE as u64 + generation.frames * frame_size as u64;
+    wal.seek(SeekFrom::Start(start))?;
+    let mut data = Vec::new();
+    wal.read_to_end(&mut data)?;
+
+    let mut checksum = generation.checksum;
+    let mut committed = None;
+    for (index, frame) in data.chunks_exact(frame_size).enumerate() {
+        if &frame[8..16] != header.salts() {
+            break;
+        }
+        checksum = wal_checksum(header.big_endian, &frame[..8], checksum);
+        checksum = wal_checksum(header.big_endian, &frame[FRAME_HEADER_SIZE..], checksum);
+        if checksum != stored_checksum(&frame[16..24]) {
+            break;
+        }
+        // A non-zero database size marks the last frame of a transaction
+        if read_u32_be(&frame[4..8]) != 0 {
+            committed = Some((index + 1, checksum));
+        }
+    }
+
+    let Some((count, checksum)) = committed else {
+        return Ok(());
+    };
+    let name = format!(
+        "{:010}-{}.wal",
+        generation.frames,
+        Utc::now().format(TIMESTAMP_FORMAT)
+    );
+    write_atomically(
+        &generation.dir.join("wal").join(name),
+        &data[..count * frame_size],
+    )
+    .context("Failed to write WAL segment")?;
+    generation.frames += count as u64;
+    generation.checksum = checksum;
+    Ok(())
+}
+
+/// What `restore_database` will restore.
+#[derive(Debug, Clone)]
+pub struct RestorePlan {
+    /// The database snapshot or backup file the restore starts from.
+    pub snapshot: PathBuf,
+    /// When the snapshot was taken.
+    pub snapshot_time: DateTime<Utc>,
+    /// Files that are concatenated, in order, into the write-ahead log replayed on top of
+    /// the snapshot. Empty when there is nothing to replay.
+    pub wal_files: Vec<PathBuf>,
+    /// When the last replayed WAL segment was captured, if any.
+    pub restored_to: Option<DateTime<Utc>>,
+}
+
+impl RestorePlan {
+    /// Prints the files a restore from this plan would use, for dry runs.
+    pub fn print(&self) {
+        println!(
+            "Snapshot: {} (taken {})",
+            self.snapshot.display(),
+            self.snapshot_time.to_rfc3339()
+        );
+        for file in &self.wal_files {
+            println!("WAL:      {}", file.display());
+        }
+        match self.restored_to {
+            Some(time) => println!("Restores to: {}", time.to_rfc3339()),
+            None => println!("Restores to: {}", self.snapshot_time.to_rfc3339()),
+        }
+    }
+}
+
+/// Works out which snapshot and WAL files restore a database to a point in time.
+///
+/// `source` may be a replication target written by `replicate`, or a plain directory of
+/// database backups. Backups are dated by a `YYYYMMDDTHHMMSS[Z]` timestamp in the file
+/// name, or by their modification time, and a `<backup>-wal` file next to a backup is
+/// replayed with it.
+///
+/// # Arguments
+///
+/// * `source` - The replication target or backup directory.
+/// * `at` - The point in time to restore to, or `None` for the latest state.
+///
+/// # Returns
+///
+/// A `Result` containing the `RestorePlan`, or an `Err` if nothing in `source` was
+/// taken at or before `at`.
+pub fn plan_restore(source: &str, at: Option<DateTime<Utc>>) -> Result<RestorePlan> {
+    if Path::new(source).join("generations").is_dir() {
+        plan_replica_restore(source, at)
+    } else {
+        plan_backup_restore(source, at)
+    }
+}
+
+fn plan_replica_restore(source: &str, at: Option<DateTime<Utc>>) -> Result<RestorePlan> {
+    let entries = fs::read_dir(Path::new(source).join("generations"))
+        .with_context(|| format!("Failed to read replication target '{}'", source))?;
+
+    let mut generations = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let started = path
+            .file_name()
+            .and_then(|name| parse_timestamp_name(&name.to_string_lossy()));
+        if let Some(started) = started {
+            if path.join(SNAPSHOT_FILE).exists() && not_after(started, at) {
+                generations.push((started, path));
+            }
+        }
+    }
+    generations.sort();
+    let Some((snapshot_time, generation)) = generations.pop() else {
+        anyhow::bail!(
+            "No generation in '{}' was started at or before the requested time",
+            source
+        );
+    };
+
+    let mut segments = Vec::new();
+    if let Ok(entries) = fs::read_dir(generation.join("wal")) {
+        for entry in entries {
+            let path = entry?.path();
+            let captured = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.split_once('-'))
+                .and_then(|(first, time)| {
+                    Some((first.parse::<u64>().ok()?, parse_timestamp_name(time)?))
+                });
+            if let Some((first, captured)) = captured {
+                if not_after(captured, at) {
+                    segments.push((first, captured, path));
+                }
+            }
+        }
+    }
+    segments.sort();
+
+    let mut wal_files = Vec::new();
+    if !segments.is_empty() {
+        wal_files.push(generation.join(WAL_HEADER_FILE));
+    }
+    Ok(RestorePlan {
+        snapshot: generation.join(SNAPSHOT_FILE),
+        snapshot_time,
+        restored_to: segments.last().map(|(_, captured, _)| *captured),
+        wal_files: wal_files
+            .into_iter()
+            .chain(segments.into_iter().map(|(_, _, path)| path))
+            .collect(),
+    })
+}
+
+fn plan_backup_restore(source: &str, at: Option<DateTime<Utc>>) -> Result<RestorePlan> {
+    let entries = fs::read_dir(source)
+        .with_context(|| format!("Failed to read backup directory '{}'", source))?;
+
+    let mut backups = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        if !path.is_file() || !is_sqlite_file(&path) {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().to_string();
+        let taken = match timestamp_in_name(&name) {
+            Some(taken) => taken,
+            None => entry.metadata()?.modified()?.into(),
+        };
+        backups.push((taken, path));
+    }
+    if backups.is_empty() {
+        anyhow::bail!("No database backups found in '{}'", source);
+    }
+
+    backups.sort();
+    let earliest = backups[0].0;
+    backups.retain(|(taken, _)| not_after(*taken, at));
+    let Some((snapshot_time, snapshot)) = backups.pop() else {
+        anyhow::bail!(
+            "No backup in '{}' was taken at or before the requested time (the earliest is from {})",
+            source,
+            earliest.to_rfc3339()
+        );
+    };
+
+    let wal = PathBuf::from(format!("{}-wal", snapshot.display()));
+    Ok(RestorePlan {
+        snapshot,
+        snapshot_time,
+        wal_files: if wal.is_file() { vec![wal] } else { Vec::new() },
+        restored_to: None,
+    })
+}
+
+/// Returns true if a file starts with the SQLite database header.
+pub(crate) fn is_sqlite_file(path: &Path) -> bool {
+    let mut header = [0u8; 16];
+    fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .map(|_| &header == b"SQLite format 3\0")
+        .unwrap_or(false)
+}
+
+/// Finds a `YYYYMMDDTHHMMSS` timestamp in a file name. A trailing `Z` marks UTC;
+/// otherwise the time is read as local time.
+fn timestamp_in_name(name: &str) -> Option<DateTime<Utc>> {
+    let pattern = regex::Regex::new(r"(\d{8}T\d{6})(?:\.\d+)?(Z)?").ok()?;
+    let captures = pattern.captures(name)?;
+    let naive = NaiveDateTime::parse_from_str(&captures[1], "%Y%m%dT%H%M%S").ok()?;
+    if captures.get(2).is_some() {
+        Some(Utc.from_utc_datetime(&naive))
+    } else {
+        Local
+            .from_local_datetime(&naive)
+            .earliest()
+            .map(|time| time.with_timezone(&Utc))
+    }
+}
+
+/// Restores a database from a snapshot and its write-ahead log into a new file.
+///
+/// # Arguments
+///
+/// * `plan` - The plan returned by `plan_restore`.
+/// * `output` - The database file to create. It must not already exist.
+///
+/// # Returns
+///
+/// A `Result` that is `Ok` once the restored database has been written and passes an
+/// integrity check, or an `Err` otherwise.
+pub fn restore_database(plan: &RestorePlan, output: &str) -> Result<()> {
+    let output_path = Path::new(output);
+    if output_path.exists() {
+        anyhow::bail!("'{}' already exists; restore into a new file", output);
+    }
+
+    fs::copy(&plan.snapshot, output_path)
+        .with_context(|| format!("Failed to write '{}'", output))?;
+
+    if !plan.wal_files.is_empty() {
+        let mut wal = Vec::new();
+        for file in &plan.wal_files {
+            wal.extend(
+                fs::read(file).with_context(|| format!("Failed to read '{}'", file.display()))?,
+            );
+        }
+        fs::write(format!("{}-wal", output), wal)?;
+    }
+
+    // Opening the file replays the log; checkpointing folds it into the database
+    let result = (|| -> Result<()> {
+        let conn = Connection::open(output_path)?;
+        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+        conn.query_row("PRAGMA journal_mode=DELETE", [], |_| Ok(()))?;
+        let integrity: String = conn.query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+        if integrity != "ok" {
+            anyhow::bail!(
+                "Restored database failed its integrity check: {}",
+                integrity
+            );
+        }
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(output_path);
+        let _ = fs::remove_file(format!("{}-wal", output));
+        let _ = fs::remove_file(format!("{}-shm", output));
+    }
+    result.with_context(|| format!("Failed to restore into '{}'", output))
+}
+
+/// Parses a point in time given on the command line.
+///
+/// RFC 3339 timestamps are used as given; `YYYY-MM-DD[ T]HH:MM[:SS]` and `YYYY-MM-DD`
+/// are read as local time.
+///
+/// # Arguments
+///
+/// * `input` - The timestamp text.
+///
+/// # Returns
+///
+/// A `Result` containing the time in UTC, or an `Err` if the format is not recognized.
+pub fn parse_timestamp(input: &str) -> Result<DateTime<Utc>> {
+    let input = input.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(input) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    let naive = [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(input, format).ok())
+    .or_else(|| {
+        NaiveDate::parse_from_str(input, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+    })
+    .with_context(|| {
+        format!(
+            "Unrecognized timestamp '{}' (expected e.g. 2024-05-01T12:00)",
+            input
+        )
+    })?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+        .with_context(|| format!("'{}' does not exist in the local time zone", input))
+}
+
+/// Returns true if `time` is at or before `at`, or if there is no limit.
+fn not_after(time: DateTime<Utc>, at: Option<DateTime<Utc>>) -> bool {
+    match at {
+        Some(at) => time <= at,
+        None => true,
+    }
+}
+
+fn parse_timestamp_name(name: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(name, TIMESTAMP_FORMAT)
+        .ok()
+        .map(|naive| Utc.from_utc_datetime(&naive))
+}
+
+fn write_atomically(path: &Path, data: &[u8]) -> Result<()> {
+    let temp = path.with_extension("tmp");
+    fs::write(&temp, data).with_context(|| format!("Failed to write '{}'", temp.display()))?;
+    fs::rename(&temp, path).with_context(|| format!("Failed to write '{}'", path.display()))?;
+    Ok(())
+}
+
+fn read_u32_be(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// Reads a stored checksum pair, which is always big-endian.
+fn stored_checksum(bytes: &[u8]) -> (u32, u32) {
+    (read_u32_be(&bytes[0..4]), read_u32_be(&bytes[4..8]))
+}
+
+/// The SQLite WAL checksum, continuing from a previous value.
+fn wal_checksum(big_endian: bool, data: &[u8], (mut s0, mut s1): (u32, u32)) -> (u32, u32) {
+    let word = |bytes: &[u8]| {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        if big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    };
+    for pair in data.chunks_exact(8) {
+        s0 = s0.wrapping_add(word(&pair[0..4])).wrapping_add(s1);
+        s1 = s1.wrapping_add(word(&pair[4..8])).wrapping_add(s0);
+    }
+    (s0, s1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replicate_and_restore_to_point_in_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("app.db");
+        let db = db_path.to_str().unwrap();
+        let target = dir.path().join("replica");
+        let target = target.to_str().unwrap();
+        let once = ReplicateOptions {
+            once: true,
+            ..Default::default()
+        };
+
+        // The application keeps its connection open, as it would in production
+        let app = Connection::open(db).unwrap();
+        app.query_row("PRAGMA journal_mode=WAL", [], |_| Ok(()))
+            .unwrap();
+        app.execute_batch("CREATE TABLE t (x); INSERT INTO t VALUES (1);")
+            .unwrap();
+        replicate(db, target, &once).unwrap();
+
+        std::thread::sleep(Duration::from_millis(20));
+        let between = Utc::now();
+        std::thread::sleep(Duration::from_millis(20));
+
+        app.execute_batch("INSERT INTO t VALUES (2); INSERT INTO t VALUES (3);")
+            .unwrap();
+        replicate(db, target, &once).unwrap();
+
+        let count = |path: &str| -> i64 {
+            Connection::open(path)
+                .unwrap()
+                .query_row("SELECT COUNT(*) FROM t", [], |row| row.get(0))
+                .unwrap()
+        };
+
+        let latest = dir.path().join("latest.db");
+        restore_database(
+            &plan_restore(target, None).unwrap(),
+            latest.to_str().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(count(latest.to_str().unwrap()), 3);
+
+        let earlier = dir.path().join("earlier.db");
+        restore_database(
+            &plan_restore(target, Some(between)).unwrap(),
+            earlier.to_str().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(count(earlier.to_str().unwrap()), 1);
+    }
+
+    #[test]
+    fn test_plan_restore_from_backup_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, value) in [
+            ("app-20240501T100000Z.db", 1),
+            ("app-20240501T120000Z.db", 2),
+            ("app-20240502T120000Z.db", 3),
+        ] {
+            let conn = Connection::open(dir.path().join(name)).unwrap();
+            conn.execute_batch(&format!(
+                "CREATE TABLE t (x); INSERT INTO t VALUES ({});",
+                value
+            ))
+            .unwrap();
+        }
+        fs::write(dir.path().join("notes.txt"), "not a database").unwrap();
+        let source = dir.path().to_str().unwrap();
+
+        let at = parse_timestamp("2024-05-01T13:00:00Z").unwrap();
+        let plan = plan_restore(source, Some(at)).unwrap();
+        assert!(plan.snapshot.ends_with("app-20240501T120000Z.db"));
+        assert!(plan.wal_files.is_empty());
+
+        let output = dir.path().join("restored.sqlite");
+        restore_database(&plan, output.to_str().unwrap()).unwrap();
+        let value: i64 = Connection::open(&output)
+            .unwrap()
+            .query_row("SELECT x FROM t", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(value, 2);
+
+        let too_early = parse_timestamp("2024-04-30T00:00:00Z").unwrap();
+        assert!(plan_restore(source, Some(too_early)).is_err());
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(
+            parse_timestamp("2024-05-01T12:00:00Z").unwrap(),
+            Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap()
+        );
+        assert!(parse_timestamp("2024-05-01T12:00").is_ok());
+        assert!(parse_timestamp("yesterday").is_err());
+    }
+}
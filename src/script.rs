@@ -0,0 +1,528 @@
+//! # SQL Scripts
+//!
+//! This module runs SQL script files, for `.read` (or `.source`) in the REPL and for
+//! `repl --init`. Scripts are split into statements with the same rules the REPL uses
+//! for multi-line input, so semicolons in literals, comments, and trigger bodies are
+//! handled, and each statement is run and reported on its own.
+//!
+//! ## Features:
+//! - **Per-statement errors**: A failing statement is reported with the line it starts
+//!   on, and the rest of the script still runs unless asked to stop.
+//! - **Echo**: Each statement can be printed before it runs, to follow along with output.
+//! - **Transactions**: A script can be run as a single unit, so that one failure undoes
+//!   every statement before it.
+//! - **Directives**: Comment lines starting with `-- vapor:` between statements add simple
+//!   control flow: `if`, `elif`, `else`, and `endif` run statements only when a condition
+//!   holds, `abort-if` stops the script, and `set NAME VALUE` sets a session variable,
+//!   which statements and conditions refer to as `${NAME}`. Conditions are
+//!   `[not] exists table|view|index|trigger NAME`, `rowcount OP N` for the rows the last
+//!   statement returned or changed, or any SQL expression, such as
+//!   `(SELECT COUNT(*) FROM users) > 0`.
+
+use anyhow::{Context, Result};
+use rusqlite::types::Value;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::backend::Backend;
+use crate::display::{execute_sql_counted, LastQuery, QueryOptions};
+use crate::render::display_value;
+use crate::statement::split_statements;
+use crate::variables::session_variables;
+
+/// How a script is run.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptOptions {
+    /// Print each statement before running it.
+    pub echo: bool,
+    /// Run the whole script in one transaction, and undo it if any statement fails.
+    pub transaction: bool,
+    /// Stop at the first failing statement. Implied by `transaction`.
+    pub bail: bool,
+}
+
+/// A statement in a script that failed.
+#[derive(Debug, Clone)]
+pub struct ScriptError {
+    /// The line the statement starts on, counting from 1.
+    pub line: usize,
+    /// The statement.
+    pub statement: String,
+    /// Why it failed.
+    pub message: String,
+}
+
+/// What happened when a script ran.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptReport {
+    /// The number of statements that ran successfully.
+    pub executed: usize,
+    /// The statements that failed, in order.
+    pub errors: Vec<ScriptError>,
+    /// Whether the script's changes were rolled back.
+    pub rolled_back: bool,
+    /// Whether an `abort-if` directive stopped the script. The directive is the last
+    /// entry in `errors`.
+    pub aborted: bool,
+}
+
+/// A piece of a script: SQL statements, or a `-- vapor:` directive.
+enum Chunk<'a> {
+    Sql(&'a str),
+    Directive(&'a str),
+}
+
+/// An `if` block being run, innermost last.
+struct Branch {
+    /// Whether the current branch of the block runs, given that its parent runs.
+    running: bool,
+    /// Whether an earlier branch of the block already ran.
+    taken: bool,
+}
+
+/// Runs a SQL script file statement by statement.
+///
+/// # Arguments
+///
+/// * `backend` - The database to run the script against.
+/// * `path` - The script file.
+/// * `options` - How to run the script.
+/// * `query_options` - How to display the results of each statement.
+/// * `last_select_query` - Receives each `SELECT` statement, for `.export`.
+///
+/// # Returns
+///
+/// A `Result` containing a `ScriptReport`. Failing statements are reported in it rather
+/// than as an `Err`, which is only returned if the file can't be read or the transaction
+/// can't be started or committed.
+pub fn run_sql_file(
+    backend: &dyn Backend,
+    path: &Path,
+    options: &ScriptOptions,
+    query_options: &QueryOptions,
+    last_select_query: &Arc<Mutex<LastQuery>>,
+) -> Result<ScriptReport> {
+    let script = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read SQL script '{}'", path.display()))?;
+    run_sql_script(backend, &script, options, query_options, last_select_query)
+}
+
+/// Runs the statements in a SQL script.
+///
+/// # Arguments
+///
+/// * `backend` - The database to run the script against.
+/// * `script` - The script text.
+/// * `options` - How to run the script.
+/// * `query_options` - How to display the results of each statement.
+/// * `last_select_query` - Receives each `SELECT` statement, for `.export`.
+///
+/// # Returns
+///
+/// A `Result` containing a `ScriptReport`, as for [`run_sql_file`].
+pub fn run_sql_script(
+    backend: &dyn Backend,
+    script: &str,
+    options: &ScriptOptions,
+    query_options: &QueryOptions,
+    last_select_query: &Arc<Mutex<LastQuery>>,
+) -> Result<ScriptReport> {
+    let mut report = ScriptReport::default();
+    if options.transaction {
+        // A savepoint nests inside a transaction the REPL user may already have open
+        backend
+            .run("SAVEPOINT vapor_script", None)
+            .context("Failed to start the script transaction")?;
+    }
+
+    let line_of = |offset: usize| script[..offset].matches('\n').count() + 1;
+    let mut branches: Vec<Branch> = Vec::new();
+    let mut rowcount = 0;
+    'chunks: for (chunk_offset, chunk) in script_chunks(script) {
+        let sql = match chunk {
+            Chunk::Sql(sql) => sql,
+            Chunk::Directive(directive) => {
+                let result = run_directive(backend, directive, &mut branches, rowcount);
+                if let Err(e) = result {
+                    let error = ScriptError {
+                        line: line_of(chunk_offset),
+                        statement: format!("-- vapor:{}", directive),
+                        message: format!("{:#}", e),
+                    };
+                    report.aborted = directive.trim_start().starts_with("abort-if");
+                    if report.aborted {
+                        eprintln!("Stopped at line {}: {}", error.line, error.message);
+                    } else {
+                        eprintln!("Error at line {}: {}", error.line, error.message);
+                    }
+                    report.errors.push(error);
+                    // A broken directive leaves no sensible way to go on
+                    break;
+                }
+                continue;
+            }
+        };
+        if !branches.iter().all(|branch| branch.running) {
+            continue;
+        }
+        for (offset, statement) in split_statements(sql) {
+            if options.echo {
+                println!("{}", statement);
+            }
+            let result = expand_variables(statement).and_then(|statement| {
+                execute_sql_counted(backend, &statement, query_options, last_select_query)
+            });
+            match result {
+                Ok(count) => {
+                    report.executed += 1;
+                    rowcount = count;
+                }
+                Err(e) => {
+                    let error = ScriptError {
+                        line: line_of(chunk_offset + offset),
+                        statement: statement.to_string(),
+                        message: format!("{:#}", e),
+                    };
+                    eprintln!("Error at line {}: {}", error.line, error.message);
+                    report.errors.push(error);
+                    if options.transaction || options.bail {
+                        break 'chunks;
+                    }
+                }
+            }
+        }
+    }
+    if !branches.is_empty() && report.errors.is_empty() {
+        let error = ScriptError {
+            line: line_of(script.len()),
+            statement: String::new(),
+            message: "Missing '-- vapor:endif' at the end of the script".to_string(),
+        };
+        eprintln!("Error at line {}: {}", error.line, error.message);
+        report.errors.push(error);
+    }
+
+    if options.transaction {
+        if !report.errors.is_empty() {
+            backend
+                .run("ROLLBACK TO vapor_script", None)
+                .context("Failed to roll back the script")?;
+            report.rolled_back = true;
+        }
+        backend
+            .run("RELEASE vapor_script", None)
+            .context("Failed to commit the script")?;
+    }
+    Ok(report)
+}
+
+/// Splits a script into SQL and the `-- vapor:` directive lines between statements,
+/// each with the byte offset where it starts.
+fn script_chunks(script: &str) -> Vec<(usize, Chunk<'_>)> {
+    let mut chunks = Vec::new();
+    let mut sql_start = 0;
+    let mut line_start = 0;
+    for line in script.split_inclusive('\n') {
+        let directive = line
+            .trim_start()
+            .strip_prefix("--")
+            .and_then(|rest| rest.trim_start().strip_prefix("vapor:"));
+        if let Some(directive) = directive {
+            if sql_start < line_start {
+                chunks.push((sql_start, Chunk::Sql(&script[sql_start..line_start])));
+            }
+            chunks.push((line_start, Chunk::Directive(directive.trim())));
+            sql_start = line_start + line.len();
+        }
+        line_start += line.len();
+    }
+    if sql_start < script.len() {
+        chunks.push((sql_start, Chunk::Sql(&script[sql_start..])));
+    }
+    chunks
+}
+
+/// Substitutes session variables into a statement that refers to any.
+fn expand_variables(statement: &str) -> Result<String> {
+    if statement.contains("${") {
+        session_variables().lock().unwrap().interpolate(statement)
+    } else {
+        Ok(statement.to_string())
+    }
+}
+
+/// Runs a directive, updating the stack of `if` blocks.
+fn run_directive(
+    backend: &dyn Backend,
+    directive: &str,
+    branches: &mut Vec<Branch>,
+    rowcount: usize,
+) -> Result<()> {
+    let (name, argument) = directive
+        .split_once(char::is_whitespace)
+        .map_or((directive, ""), |(name, rest)| (name, rest.trim()));
+    let running = branches.iter().all(|branch| branch.running);
+    let parent_running =
+        branches.len() < 2 || branches[..branches.len() - 1].iter().all(|b| b.running);
+    match name.to_lowercase().as_str() {
+        "if" => {
+            // Conditions inside a skipped block aren't evaluated
+            let holds = running && evaluate_condition(backend, argument, rowcount)?;
+            branches.push(Branch {
+                running: holds,
+                taken: holds || !running,
+            });
+        }
+        "elif" => {
+            let Some(branch) = branches.last_mut() else {
+                anyhow::bail!("'-- vapor:elif' without an 'if'");
+            };
+            let holds =
+                !branch.taken && parent_running && evaluate_condition(backend, argument, rowcount)?;
+            branch.running = holds;
+            branch.taken |= holds;
+        }
+        "else" => {
+            let Some(branch) = branches.last_mut() else {
+                anyhow::bail!("'-- vapor:else' without an 'if'");
+            };
+            branch.running = !branch.taken;
+            branch.taken = true;
+        }
+        "endif" => {
+            if branches.pop().is_none() {
+                anyhow::bail!("'-- vapor:endif' without an 'if'");
+            }
+        }
+        "abort-if" => {
+            if running && evaluate_condition(backend, argument, rowcount)? {
+                anyhow::bail!("Aborted because {} (rowcount was {})", argument, rowcount);
+            }
+        }
+        "set" => {
+            if running {
+                let Some((variable, value)) = argument.split_once(char::is_whitespace) else {
+                    anyhow::bail!("Usage: -- vapor:set NAME VALUE (or NAME (SELECT ...))");
+                };
+                let value = expand_variables(value.trim())?;
+                // A parenthesized value is a SQL expression, evaluated now
+                let value = if value.starts_with('(') && value.ends_with(')') {
+                    let result = backend.run(&format!("SELECT {}", value), Some(1))?;
+                    result
+                        .rows
+                        .first()
+                        .and_then(|row| row.first())
+                        .map(display_value)
+                        .unwrap_or_else(|| "NULL".to_string())
+                } else {
+                    value
+                };
+                session_variables().lock().unwrap().set(variable, &value)?;
+            }
+        }
+        _ => anyhow::bail!(
+            "Unknown directive '{}'. Use if, elif, else, endif, abort-if, or set",
+            name
+        ),
+    }
+    Ok(())
+}
+
+/// Evaluates the condition of an `if`, `elif`, or `abort-if` directive.
+fn evaluate_condition(backend: &dyn Backend, condition: &str, rowcount: usize) -> Result<bool> {
+    let condition = expand_variables(condition)?;
+    let (negate, condition) = match condition.trim().split_once(char::is_whitespace) {
+        Some((first, rest)) if first.eq_ignore_ascii_case("not") => (true, rest.trim()),
+        _ => (false, condition.trim()),
+    };
+    let words: Vec<&str> = condition.split_whitespace().collect();
+    let is_schema_kind = |kind: &str| {
+        ["table", "view", "index", "trigger"]
+            .iter()
+            .any(|k| k.eq_ignore_ascii_case(kind))
+    };
+    let holds = match words.as_slice() {
+        [] => anyhow::bail!("Missing condition"),
+        [exists, kind, object] if exists.eq_ignore_ascii_case("exists") && is_schema_kind(kind) => {
+            let kind = kind.to_lowercase();
+            let name = object.trim_matches(|c| c == '"' || c == '`' || c == '[' || c == ']');
+            let literal = format!("'{}'", name.replace('\'', "''"));
+            let sql = format!(
+                "SELECT COUNT(*) FROM (SELECT type, name FROM sqlite_master UNION ALL SELECT type, name FROM sqlite_temp_master) WHERE type = '{}' AND name = {} COLLATE NOCASE",
+                kind, literal
+            );
+            let result = backend.run(&sql, Some(1))?;
+            !matches!(
+                result.rows.first().and_then(|row| row.first()),
+                Some(Value::Integer(0)) | None
+            )
+        }
+        [first, op, number] if first.eq_ignore_ascii_case("rowcount") => {
+            let number: usize = number
+                .parse()
+                .with_context(|| format!("'{}' is not a row count", number))?;
+            match *op {
+                "=" | "==" => rowcount == number,
+                "!=" | "<>" => rowcount != number,
+                "<" => rowcount < number,
+                "<=" => rowcount <= number,
+                ">" => rowcount > number,
+                ">=" => rowcount >= number,
+                _ => anyhow::bail!("Unknown comparison '{}'. Use =, !=, <, <=, >, or >=", op),
+            }
+        }
+        _ => {
+            let sql = format!("SELECT CASE WHEN ({}) THEN 1 ELSE 0 END", condition);
+            let result = backend
+                .run(&sql, Some(1))
+                .with_context(|| format!("Failed to evaluate '{}'", condition))?;
+            matches!(
+                result.rows.first().and_then(|row| row.first()),
+                Some(Value::Integer(1))
+            )
+        }
+    };
+    Ok(holds != negate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+
+    fn count(conn: &Connection) -> i64 {
+        conn.query_row("SELECT COUNT(*) FROM t", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_run_sql_script() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE t (x INTEGER UNIQUE)")
+            .unwrap();
+        let last = Arc::new(Mutex::new(LastQuery::default()));
+        let query_options = QueryOptions::default();
+        let script =
+            "INSERT INTO t VALUES (1);\nINSERT INTO t VALUES (1);\n\nINSERT INTO t VALUES (2);";
+
+        let report = run_sql_script(
+            &conn,
+            script,
+            &ScriptOptions::default(),
+            &query_options,
+            &last,
+        )
+        .unwrap();
+        assert_eq!(report.executed, 2);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].line, 2);
+        assert!(!report.rolled_back);
+        assert_eq!(count(&conn), 2);
+
+        let options = ScriptOptions {
+            transaction: true,
+            ..Default::default()
+        };
+        let script =
+            "INSERT INTO t VALUES (3);\nINSERT INTO t VALUES (1);\nINSERT INTO t VALUES (4);";
+        let report = run_sql_script(&conn, script, &options, &query_options, &last).unwrap();
+        assert_eq!(report.executed, 1);
+        assert!(report.rolled_back);
+        assert_eq!(count(&conn), 2);
+
+        let report = run_sql_script(
+            &conn,
+            "INSERT INTO t VALUES (5); INSERT INTO t VALUES (6);",
+            &options,
+            &query_options,
+            &last,
+        )
+        .unwrap();
+        assert!(report.errors.is_empty() && !report.rolled_back);
+        assert_eq!(count(&conn), 4);
+    }
+
+    #[test]
+    fn test_script_directives() {
+        let conn = Connection::open_in_memory().unwrap();
+        let last = Arc::new(Mutex::new(LastQuery::default()));
+        let query_options = QueryOptions::default();
+        let script = "\
+-- vapor:if not exists table users
+CREATE TABLE users (id INTEGER, name TEXT);
+INSERT INTO users VALUES (1, 'Ada'), (2, 'Grace');
+-- vapor:else
+CREATE TABLE never (x);
+-- vapor:endif
+-- vapor:set script_test_max (SELECT MAX(id) FROM users)
+-- vapor:if ${script_test_max} > 5
+INSERT INTO users VALUES (100, 'big');
+-- vapor:elif exists index missing_index
+INSERT INTO users VALUES (101, 'indexed');
+-- vapor:else
+INSERT INTO users VALUES (${script_test_max} + 1, 'next');
+-- vapor:endif
+SELECT * FROM users WHERE id > 10;
+-- vapor:abort-if rowcount > 0
+DELETE FROM users WHERE id < 3;
+";
+        let report = run_sql_script(
+            &conn,
+            script,
+            &ScriptOptions::default(),
+            &query_options,
+            &last,
+        )
+        .unwrap();
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+        assert_eq!(report.executed, 5);
+        let names: Vec<String> = conn
+            .prepare("SELECT name FROM users ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(names, ["next"]);
+
+        // The same script again: the table exists, and the abort keeps the rows
+        conn.execute_batch("INSERT INTO users VALUES (1, 'Ada'), (20, 'Linus')")
+            .unwrap();
+        let options = ScriptOptions {
+            transaction: true,
+            ..Default::default()
+        };
+        let report = run_sql_script(&conn, script, &options, &query_options, &last).unwrap();
+        assert!(report.aborted && report.rolled_back);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].line, 16);
+        assert!(report.errors[0].message.contains("rowcount was 2"));
+        let rows: i64 = conn
+            .query_row("SELECT COUNT(*) FROM users", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(rows, 3);
+
+        let report = run_sql_script(
+            &conn,
+            "-- vapor:if 1\nSELECT 1;\n-- vapor:endif\n-- vapor:endif\n",
+            &ScriptOptions::default(),
+            &query_options,
+            &last,
+        )
+        .unwrap();
+        assert_eq!(report.errors[0].line, 4);
+        assert!(report.errors[0].message.contains("without an 'if'"));
+        let report = run_sql_script(
+            &conn,
+            "-- vapor:if 0\nSELECT 1;\n",
+            &ScriptOptions::default(),
+            &query_options,
+            &last,
+        )
+        .unwrap();
+        assert!(report.errors[0]
+            .message
+            .contains("Missing '-- vapor:endif'"));
+    }
+}
@@ -0,0 +1,353 @@
+//! # Automation Scripts
+//!
+//! This module runs [Rhai](https://rhai.rs) scripts against the open database, for
+//! `.script run FILE` in the REPL. Scripts fill the gap between single SQL statements
+//! and a whole Rust program: they can loop over query results, branch on them, and call
+//! vapor-cli's export and import functions. It is built with the `scripting` feature.
+//!
+//! ## Features:
+//! - **Queries**: `query(sql)` returns the rows as an array of maps from column name to
+//!   value, `query_value(sql)` returns the first column of the first row, and
+//!   `execute(sql)` runs a statement and returns the number of rows it changed. Each
+//!   takes an optional array of `?` parameters, such as `query(sql, [id])`.
+//! - **Output**: `show(sql)` prints a query the way the REPL does, in the current format.
+//! - **Files**: `export_query(sql, path)` and `import_file(path, table)` use the same
+//!   formats as `.export` and `.import`, each with an optional format name as the last
+//!   argument.
+//! - **Schema**: `tables()` returns the table names.
+//! - **Arguments**: Extra words after the file name are passed in as the `ARGS` array.
+//! - **Same connection**: Scripts run on the REPL's connection, so they see its open
+//!   transaction, temporary tables, and attached databases. Ctrl+C stops a script.
+
+use anyhow::{Context, Result};
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, Scope};
+use rusqlite::types::Value;
+use rusqlite::Connection;
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+use crate::db::get_all_table_names;
+use crate::display::{execute_sql, QueryOptions};
+use crate::export::ExportOptions;
+use crate::formats::{export_query_with_options, import_file};
+use crate::shell::ctrl_c_pressed;
+
+type ScriptResult<T> = std::result::Result<T, Box<EvalAltResult>>;
+
+/// Runs a Rhai automation script against a database connection.
+///
+/// # Arguments
+///
+/// * `conn` - The connection the script runs on. It is lent to the script and handed
+///   back when the script finishes, even if it fails.
+/// * `path` - The script file.
+/// * `args` - Values for the script's `ARGS` array.
+/// * `query_options` - The output format and row limit used by `show`.
+///
+/// # Returns
+///
+/// A `Result` containing the value of the script's last expression, rendered as text
+/// (empty if it has none), or an `Err` with the script error and its line.
+pub fn run_script_file(
+    conn: &mut Connection,
+    path: &Path,
+    args: &[String],
+    query_options: &QueryOptions,
+) -> Result<String> {
+    let script = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read script '{}'", path.display()))?;
+
+    // The engine's functions must own what they use, so the connection is moved into a
+    // shared cell for the run and moved back afterwards
+    let placeholder = Connection::open_in_memory()?;
+    let shared = Rc::new(RefCell::new(std::mem::replace(conn, placeholder)));
+    let result = {
+        let engine = script_engine(&shared, query_options);
+        let mut scope = Scope::new();
+        let args: Array = args.iter().cloned().map(Dynamic::from).collect();
+        scope.push_constant("ARGS", args);
+        engine.eval_with_scope::<Dynamic>(&mut scope, &script)
+    };
+    match Rc::try_unwrap(shared) {
+        Ok(cell) => *conn = cell.into_inner(),
+        Err(_) => unreachable!("the script engine outlived the script"),
+    }
+
+    let value = result.map_err(|e| anyhow::anyhow!("Script '{}' failed: {}", path.display(), e))?;
+    Ok(if value.is_unit() {
+        String::new()
+    } else {
+        value.to_string()
+    })
+}
+
+/// Checks a script for syntax errors without running it.
+///
+/// # Arguments
+///
+/// * `path` - The script file.
+///
+/// # Returns
+///
+/// A `Result` that is `Ok` if the script compiles, or an `Err` with the first error and
+/// its line.
+pub fn check_script_file(path: &Path) -> Result<()> {
+    let script = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read script '{}'", path.display()))?;
+    Engine::new()
+        .compile(&script)
+        .map_err(|e| anyhow::anyhow!("Script '{}' has an error: {}", path.display(), e))?;
+    Ok(())
+}
+
+/// Builds an engine with the database functions registered.
+fn script_engine(conn: &Rc<RefCell<Connection>>, query_options: &QueryOptions) -> Engine {
+    let mut engine = Engine::new();
+    engine.on_progress(|_| {
+        if ctrl_c_pressed() {
+            Some("Interrupted".into())
+        } else {
+            None
+        }
+    });
+
+    let db = conn.clone();
+    engine.register_fn("query", move |sql: &str| {
+        query(&db.borrow(), sql, Array::new())
+    });
+    let db = conn.clone();
+    engine.register_fn("query", move |sql: &str, params: Array| {
+        query(&db.borrow(), sql, params)
+    });
+    let db = conn.clone();
+    engine.register_fn("query_value", move |sql: &str| {
+        query_value(&db.borrow(), sql, Array::new())
+    });
+    let db = conn.clone();
+    engine.register_fn("query_value", move |sql: &str, params: Array| {
+        query_value(&db.borrow(), sql, params)
+    });
+    let db = conn.clone();
+    engine.register_fn("execute", move |sql: &str| {
+        execute(&db.borrow(), sql, Array::new())
+    });
+    let db = conn.clone();
+    engine.register_fn("execute", move |sql: &str, params: Array| {
+        execute(&db.borrow(), sql, params)
+    });
+
+    let db = conn.clone();
+    let options = QueryOptions {
+        format: query_options.format.clone(),
+        max_rows: query_options.max_rows,
+        show_timing: false,
+        quiet: query_options.quiet,
+    };
+    engine.register_fn("show", move |sql: &str| -> ScriptResult<()> {
+        let last_select_query = Arc::new(Mutex::new(String::new()));
+        execute_sql(&db.borrow(), sql, &options, &last_select_query).map_err(script_error)
+    });
+
+    let db = conn.clone();
+    engine.register_fn("export_query", move |sql: &str, path: &str| {
+        export(&db.borrow(), sql, path, None)
+    });
+    let db = conn.clone();
+    engine.register_fn(
+        "export_query",
+        move |sql: &str, path: &str, format: &str| export(&db.borrow(), sql, path, Some(format)),
+    );
+    let db = conn.clone();
+    engine.register_fn("import_file", move |path: &str, table: &str| {
+        import(&mut db.borrow_mut(), path, table, None)
+    });
+    let db = conn.clone();
+    engine.register_fn(
+        "import_file",
+        move |path: &str, table: &str, format: &str| {
+            import(&mut db.borrow_mut(), path, table, Some(format))
+        },
+    );
+
+    let db = conn.clone();
+    engine.register_fn("tables", move || -> ScriptResult<Array> {
+        let names = get_all_table_names(&db.borrow()).map_err(script_error)?;
+        Ok(names.into_iter().map(Dynamic::from).collect())
+    });
+    engine
+}
+
+fn query(conn: &Connection, sql: &str, params: Array) -> ScriptResult<Array> {
+    let mut stmt = conn.prepare(sql).map_err(sql_error)?;
+    let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+    let params = to_sql_params(params)?;
+    let mut rows = stmt
+        .query(rusqlite::params_from_iter(params))
+        .map_err(sql_error)?;
+    let mut result = Array::new();
+    while let Some(row) = rows.next().map_err(sql_error)? {
+        let mut map = Map::new();
+        for (i, column) in columns.iter().enumerate() {
+            let value: Value = row.get(i).map_err(sql_error)?;
+            map.insert(column.as_str().into(), to_dynamic(value));
+        }
+        result.push(map.into());
+    }
+    Ok(result)
+}
+
+fn query_value(conn: &Connection, sql: &str, params: Array) -> ScriptResult<Dynamic> {
+    let mut stmt = conn.prepare(sql).map_err(sql_error)?;
+    let params = to_sql_params(params)?;
+    let mut rows = stmt
+        .query(rusqlite::params_from_iter(params))
+        .map_err(sql_error)?;
+    match rows.next().map_err(sql_error)? {
+        Some(row) => Ok(to_dynamic(row.get(0).map_err(sql_error)?)),
+        None => Ok(Dynamic::UNIT),
+    }
+}
+
+fn execute(conn: &Connection, sql: &str, params: Array) -> ScriptResult<i64> {
+    let params = to_sql_params(params)?;
+    let changed = conn
+        .execute(sql, rusqlite::params_from_iter(params))
+        .map_err(sql_error)?;
+    Ok(changed as i64)
+}
+
+fn export(conn: &Connection, sql: &str, path: &str, format: Option<&str>) -> ScriptResult<i64> {
+    let rows = export_query_with_options(conn, sql, path, format, &ExportOptions::default())
+        .map_err(script_error)?;
+    Ok(rows as i64)
+}
+
+fn import(
+    conn: &mut Connection,
+    path: &str,
+    table: &str,
+    format: Option<&str>,
+) -> ScriptResult<i64> {
+    let rows = import_file(conn, path, table, format).map_err(script_error)?;
+    Ok(rows as i64)
+}
+
+/// Converts a SQLite value to a script value. `NULL` becomes `()`.
+fn to_dynamic(value: Value) -> Dynamic {
+    match value {
+        Value::Null => Dynamic::UNIT,
+        Value::Integer(i) => i.into(),
+        Value::Real(f) => f.into(),
+        Value::Text(s) => s.into(),
+        Value::Blob(b) => Dynamic::from_blob(b),
+    }
+}
+
+/// Converts script values to statement parameters.
+fn to_sql_params(params: Array) -> ScriptResult<Vec<Value>> {
+    params
+        .into_iter()
+        .map(|param| {
+            if param.is_unit() {
+                Ok(Value::Null)
+            } else if let Ok(i) = param.as_int() {
+                Ok(Value::Integer(i))
+            } else if let Ok(f) = param.as_float() {
+                Ok(Value::Real(f))
+            } else if let Ok(b) = param.as_bool() {
+                Ok(Value::Integer(b as i64))
+            } else if param.is_string() {
+                Ok(Value::Text(param.into_string()?))
+            } else if param.is_blob() {
+                Ok(Value::Blob(param.cast()))
+            } else {
+                Err(format!("Can't use a {} as a SQL parameter", param.type_name()).into())
+            }
+        })
+        .collect()
+}
+
+fn sql_error(e: rusqlite::Error) -> Box<EvalAltResult> {
+    e.to_string().into()
+}
+
+fn script_error(e: anyhow::Error) -> Box<EvalAltResult> {
+    format!("{:#}", e).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_script_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE users (id INTEGER, name TEXT, active INTEGER);
+             INSERT INTO users VALUES (1, 'Ada', 1), (2, 'Grace', 0), (3, 'Linus', 1);
+             CREATE TEMP TABLE session_only (x);",
+        )
+        .unwrap();
+
+        let csv_path = dir.path().join("active.csv");
+        let script = dir.path().join("nightly.rhai");
+        std::fs::write(
+            &script,
+            format!(
+                r#"
+                let exported = export_query("SELECT * FROM users WHERE active = 1", "{}");
+                let renamed = 0;
+                for user in query("SELECT id, name FROM users WHERE active = ?", [ARGS[0].parse_int()]) {{
+                    renamed += execute("UPDATE users SET name = ? WHERE id = ?", [user.name + "!", user.id]);
+                }}
+                if query_value("SELECT COUNT(*) FROM session_only") == 0 {{
+                    execute("INSERT INTO session_only VALUES (1)");
+                }}
+                `${{exported}} exported, ${{renamed}} renamed, ${{tables().len()}} tables`
+                "#,
+                csv_path.display()
+            ),
+        )
+        .unwrap();
+
+        let output = run_script_file(
+            &mut conn,
+            &script,
+            &["1".to_string()],
+            &QueryOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(output, "2 exported, 2 renamed, 1 tables");
+        assert!(std::fs::read_to_string(&csv_path)
+            .unwrap()
+            .contains("Linus"));
+
+        // The connection comes back, with the script's changes and the session's state
+        let names: Vec<String> = conn
+            .prepare("SELECT name FROM users ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(names, ["Ada!", "Grace", "Linus!"]);
+        let temp_rows: i64 = conn
+            .query_row("SELECT COUNT(*) FROM session_only", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(temp_rows, 1);
+
+        std::fs::write(&script, "let x = 1;\nquery(\"SELECT * FROM missing\");").unwrap();
+        let error = run_script_file(&mut conn, &script, &[], &QueryOptions::default())
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("no such table: missing"), "{}", error);
+        assert!(error.contains("line 2"), "{}", error);
+        assert!(conn.execute_batch("SELECT 1 FROM users").is_ok());
+
+        std::fs::write(&script, "let x = ;").unwrap();
+        assert!(check_script_file(&script).is_err());
+    }
+}
@@ -0,0 +1,176 @@
+//! # Text Search
+//!
+//! This module answers "where is this value?" by searching the text columns of every
+//! table for a pattern, instead of writing a query per table and column by hand. It
+//! backs the REPL's `.find` command.
+//!
+//! ## Features:
+//! - **Substring matching**: The pattern matches anywhere in a value, ignoring ASCII case,
+//!   and `%` and `_` work as `LIKE` wildcards.
+//! - **Text columns only**: Columns declared with a text type (`TEXT`, `VARCHAR`, `CLOB`,
+//!   ...) or with no type are searched; numeric and blob columns are skipped.
+//! - **Attached databases**: Tables in attached databases are searched too, and can be
+//!   named as `alias.table`.
+//! - **Row locations**: Each match names its table, column, and rowid, so the row can be
+//!   looked up directly.
+
+use anyhow::{Context, Result};
+use rusqlite::Connection;
+
+use crate::db::{get_all_table_names, split_table_name, visible_columns};
+use crate::formats::quote_identifier;
+
+/// A value that matched a search.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextMatch {
+    /// The table, qualified with its database's alias if it is attached.
+    pub table: String,
+    pub column: String,
+    pub rowid: i64,
+    pub value: String,
+}
+
+/// Searches the text columns of one or all tables for a pattern.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `pattern` - The text to look for, with optional `%` and `_` wildcards.
+/// * `table` - The table to search, or `None` for every table.
+/// * `limit` - The most matches to return, or `None` for all of them.
+///
+/// # Returns
+///
+/// A `Result` containing the matches in table, column, and rowid order, or an `Err` if
+/// the named table doesn't exist or can't be searched.
+pub fn find_text(
+    conn: &Connection,
+    pattern: &str,
+    table: Option<&str>,
+    limit: Option<usize>,
+) -> Result<Vec<TextMatch>> {
+    let tables = match table {
+        Some(table) => vec![table.to_string()],
+        None => get_all_table_names(conn)?,
+    };
+    let mut matches = Vec::new();
+    for name in &tables {
+        let (schema, table_name) = split_table_name(conn, name)?;
+        let Some(without_rowid) = is_without_rowid(conn, &schema, &table_name)? else {
+            anyhow::bail!("Table '{}' does not exist", name);
+        };
+        if without_rowid {
+            if table.is_some() {
+                anyhow::bail!("Table '{}' has no rowid, so matches can't be located", name);
+            }
+            continue;
+        }
+        for column in text_columns(conn, &schema, &table_name)? {
+            let remaining = limit.map(|limit| limit.saturating_sub(matches.len()));
+            if remaining == Some(0) {
+                return Ok(matches);
+            }
+            let sql = format!(
+                "SELECT rowid, CAST({col} AS TEXT) FROM {}.{} WHERE {col} LIKE '%' || ?1 || '%' ORDER BY rowid LIMIT ?2",
+                quote_identifier(&schema),
+                quote_identifier(&table_name),
+                col = quote_identifier(&column)
+            );
+            let mut stmt = conn
+                .prepare(&sql)
+                .with_context(|| format!("Failed to search '{}.{}'", name, column))?;
+            let rows = stmt.query_map(
+                rusqlite::params![pattern, remaining.map_or(-1, |n| n as i64)],
+                |row| {
+                    Ok(TextMatch {
+                        table: name.clone(),
+                        column: column.clone(),
+                        rowid: row.get(0)?,
+                        value: row.get(1)?,
+                    })
+                },
+            )?;
+            for row in rows {
+                matches.push(row.with_context(|| format!("Failed to search '{}'", name))?);
+            }
+        }
+    }
+    Ok(matches)
+}
+
+/// Whether a table is declared `WITHOUT ROWID`, or `None` if there is no such table.
+fn is_without_rowid(conn: &Connection, schema: &str, table: &str) -> Result<Option<bool>> {
+    let mut stmt = conn.prepare(
+        "SELECT wr FROM pragma_table_list WHERE schema = ?1 AND name = ?2 AND type = 'table'",
+    )?;
+    let mut rows = stmt.query([schema, table])?;
+    Ok(match rows.next()? {
+        Some(row) => Some(row.get(0)?),
+        None => None,
+    })
+}
+
+/// The columns of a table with text affinity or no declared type.
+fn text_columns(conn: &Connection, schema: &str, table: &str) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    for column in visible_columns(conn, Some(schema), table)? {
+        let declared_type = column.decl_type.to_ascii_uppercase();
+        // SQLite's affinity rules: INT wins, then CHAR/CLOB/TEXT mean text
+        let is_text = !declared_type.contains("INT")
+            && (declared_type.is_empty()
+                || ["CHAR", "CLOB", "TEXT"]
+                    .iter()
+                    .any(|t| declared_type.contains(t)));
+        if is_text {
+            names.push(column.name);
+        }
+    }
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_text() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, email VARCHAR(80), age INTEGER);
+             INSERT INTO users (name, email, age) VALUES
+                 ('Alice', 'alice@example.com', 30),
+                 ('Bob', 'bob@acme.test', 41);
+             CREATE TABLE notes (body);
+             INSERT INTO notes VALUES ('Call ALICE back'), ('nothing here');
+             CREATE TABLE codes (code TEXT PRIMARY KEY) WITHOUT ROWID;
+             INSERT INTO codes VALUES ('alice');",
+        )
+        .unwrap();
+
+        let matches = find_text(&conn, "alice", None, None).unwrap();
+        let found: Vec<(&str, &str, i64)> = matches
+            .iter()
+            .map(|m| (m.table.as_str(), m.column.as_str(), m.rowid))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("notes", "body", 1),
+                ("users", "name", 1),
+                ("users", "email", 1)
+            ]
+        );
+        assert_eq!(matches[0].value, "Call ALICE back");
+
+        assert_eq!(find_text(&conn, "4", Some("users"), None).unwrap().len(), 0);
+        assert_eq!(
+            find_text(&conn, "%.test", Some("users"), None)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(find_text(&conn, "e", None, Some(2)).unwrap().len(), 2);
+        assert!(find_text(&conn, "x", Some("missing"), None).is_err());
+        assert!(find_text(&conn, "x", Some("codes"), None).is_err());
+    }
+}
@@ -0,0 +1,358 @@
+//! # Library Sessions
+//!
+//! This module offers what the REPL's dot-commands do as methods on a `Session`, so an
+//! application embedding vapor-cli, such as a GUI, can offer the same features without
+//! driving the interactive REPL through stdin.
+//!
+//! ## Features:
+//! - **Output settings**: `set_format`, `set_limit`, and `set_timing` match `.format`,
+//!   `.limit`, and `.timing`, and apply to `execute` and `query`.
+//! - **Last result**: Like the REPL, a session remembers its last `SELECT`, or the rows
+//!   returned by its last write with `RETURNING`, and `export_last_result` writes them to
+//!   a file as `.export` does.
+//! - **Data, not text**: `query` and `describe_table` return values for the caller to
+//!   display, while `execute` prints like the REPL.
+//! - **Bookmarks**: `save_bookmark` and `run_bookmark` use the same bookmarks as
+//!   `.bookmark save` and `.bookmark run`.
+
+use anyhow::{Context, Result};
+use rusqlite::Connection;
+use std::sync::{Arc, Mutex};
+
+use crate::backend::{Backend, ColumnDescription, QueryResult};
+use crate::bookmarks::BookmarkManager;
+use crate::db::{get_all_table_names, set_foreign_keys, split_table_name, visible_columns};
+use crate::display::{
+    execute_sql_with_last, remember_query, LastQuery, OutputFormat, QueryOptions,
+};
+use crate::export::ExportOptions;
+use crate::formats::{export_query_with_options, export_result, quote_identifier};
+use crate::functions::register_functions;
+use crate::softdelete::soft_delete_counts;
+use crate::transactions::TransactionManager;
+use crate::variables::session_variables;
+
+/// A table's columns and row counts, as shown by `.describe`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableDescription {
+    pub name: String,
+    pub columns: Vec<ColumnDescription>,
+    /// The number of rows, not counting soft-deleted rows.
+    pub row_count: u64,
+    /// The number of soft-deleted rows, if the table uses soft delete.
+    pub deleted_rows: Option<u64>,
+}
+
+/// A connection to a database with the state a REPL session keeps.
+pub struct Session {
+    conn: Connection,
+    db_path: String,
+    query_options: QueryOptions,
+    last_select_query: Arc<Mutex<LastQuery>>,
+    bookmarks: Option<BookmarkManager>,
+    transaction_manager: TransactionManager,
+}
+
+impl Session {
+    /// Opens a session on a database file, creating the file if it doesn't exist.
+    /// Like the REPL, it enforces foreign keys unless `VAPOR_FOREIGN_KEYS` is `off`.
+    ///
+    /// # Arguments
+    ///
+    /// * `db_path` - The path to the SQLite database file.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the session, or an `Err` if the database can't be opened.
+    pub fn open<P: AsRef<std::path::Path>>(db_path: P) -> Result<Self> {
+        let db_path = db_path.as_ref().to_string_lossy().to_string();
+        let conn = Connection::open(&db_path)
+            .with_context(|| format!("Failed to open database '{}'", db_path))?;
+        set_foreign_keys(&conn, crate::config::get_foreign_keys())?;
+        register_functions(&conn)?;
+        Ok(Self {
+            conn,
+            db_path,
+            query_options: QueryOptions::default(),
+            last_select_query: Arc::new(Mutex::new(LastQuery::default())),
+            bookmarks: BookmarkManager::new().ok(),
+            transaction_manager: TransactionManager::new(),
+        })
+    }
+
+    /// The session's connection, for anything the session doesn't wrap.
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// The path of the database file.
+    pub fn db_path(&self) -> &str {
+        &self.db_path
+    }
+
+    /// The session's transaction manager.
+    pub fn transaction_manager(&self) -> &TransactionManager {
+        &self.transaction_manager
+    }
+
+    /// The output settings used by `execute` and `query`.
+    pub fn query_options(&self) -> &QueryOptions {
+        &self.query_options
+    }
+
+    /// Sets the output format for `execute`, like `.format`.
+    pub fn set_format(&mut self, format: OutputFormat) {
+        self.query_options.format = format;
+    }
+
+    /// Sets the most rows `execute` and `query` return, like `.limit`. `None` removes the
+    /// limit.
+    pub fn set_limit(&mut self, max_rows: Option<usize>) {
+        self.query_options.max_rows = max_rows;
+    }
+
+    /// Turns the timing `execute` prints after each statement on or off, like `.timing`
+    /// and `.notiming`.
+    pub fn set_timing(&mut self, show_timing: bool) {
+        self.query_options.show_timing = show_timing;
+    }
+
+    /// Runs SQL and prints its results in the session's format, as the REPL does.
+    ///
+    /// # Arguments
+    ///
+    /// * `sql` - The SQL statement to run.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` which is `Ok(())` on success, or an `Err` if the statement fails.
+    pub fn execute(&self, sql: &str) -> Result<()> {
+        execute_sql_with_last(
+            &self.conn,
+            sql,
+            &self.query_options,
+            &self.last_select_query,
+        )
+    }
+
+    /// Runs a single SQL statement and returns its results instead of printing them.
+    ///
+    /// # Arguments
+    ///
+    /// * `sql` - The SQL statement to run.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the columns and rows, cut off at the session's row limit.
+    pub fn query(&self, sql: &str) -> Result<QueryResult> {
+        let result = self.conn.run(sql, self.query_options.max_rows)?;
+        remember_query(&self.last_select_query, sql, &result);
+        Ok(result)
+    }
+
+    /// The last `SELECT` run with `execute`, `query`, or `run_bookmark`.
+    pub fn last_query(&self) -> Option<String> {
+        let last = self.last_select_query.lock().unwrap();
+        Some(last.sql.clone()).filter(|query| !query.is_empty())
+    }
+
+    /// Exports all rows of the last `SELECT` to a file, like `.export`. After an `INSERT`,
+    /// `UPDATE`, or `DELETE ... RETURNING`, the rows it returned are exported instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The output file. It is overwritten if it exists.
+    /// * `format` - A format name such as `csv` or `json`, or `None` to pick one from the
+    ///   file extension.
+    /// * `options` - Compression and other export settings.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the number of rows written, or an `Err` if no `SELECT` has
+    /// been run yet or the export fails.
+    pub fn export_last_result(
+        &self,
+        path: &str,
+        format: Option<&str>,
+        options: &ExportOptions,
+    ) -> Result<usize> {
+        let LastQuery { sql, returned } = self.last_select_query.lock().unwrap().clone();
+        match returned {
+            Some(result) => export_result(&result, path, format, options),
+            None if !sql.is_empty() => {
+                export_query_with_options(&self.conn, &sql, path, format, options)
+            }
+            None => anyhow::bail!("No SELECT query has been executed yet"),
+        }
+    }
+
+    /// Lists the tables of the database and any attached databases, like `.tables`.
+    pub fn tables(&self) -> Result<Vec<String>> {
+        get_all_table_names(&self.conn)
+    }
+
+    /// Describes a table's columns and row counts, like `.describe`.
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - The table, qualified with an attached database's alias if needed.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the description, or an `Err` if the table doesn't exist.
+    pub fn describe_table(&self, table: &str) -> Result<TableDescription> {
+        let (schema, table_name) = split_table_name(&self.conn, table)?;
+        let columns: Vec<ColumnDescription> =
+            visible_columns(&self.conn, Some(&schema), &table_name)?
+                .into_iter()
+                .map(ColumnDescription::from)
+                .collect();
+        if columns.is_empty() {
+            anyhow::bail!("Table '{}' does not exist", table);
+        }
+
+        let counts = if schema == "main" {
+            soft_delete_counts(&self.conn, &table_name)?
+        } else {
+            None
+        };
+        let (row_count, deleted_rows) = match counts {
+            Some(counts) => (counts.live as u64, Some(counts.deleted as u64)),
+            None => {
+                let count: i64 = self.conn.query_row(
+                    &format!(
+                        "SELECT COUNT(*) FROM {}.{}",
+                        quote_identifier(&schema),
+                        quote_identifier(&table_name)
+                    ),
+                    [],
+                    |row| row.get(0),
+                )?;
+                (count as u64, None)
+            }
+        };
+        Ok(TableDescription {
+            name: table.to_string(),
+            columns,
+            row_count,
+            deleted_rows,
+        })
+    }
+
+    /// The saved bookmarks, or `None` if the bookmarks file couldn't be loaded.
+    pub fn bookmarks(&self) -> Option<&BookmarkManager> {
+        self.bookmarks.as_ref()
+    }
+
+    /// Saves the last `SELECT` as a bookmark, like `.bookmark save`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The bookmark name. An existing bookmark with this name is replaced.
+    /// * `description` - An optional description.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` which is `Ok(())` once the bookmark is saved, or an `Err` if no `SELECT`
+    /// has been run yet or the bookmark can't be saved.
+    pub fn save_bookmark(&mut self, name: &str, description: Option<&str>) -> Result<()> {
+        let query = self
+            .last_query()
+            .context("No SELECT query has been executed yet")?;
+        self.bookmarks
+            .as_mut()
+            .context("Bookmarks are not available")?
+            .save_bookmark(name.to_string(), query, description.map(str::to_string))
+    }
+
+    /// Runs a bookmarked query and returns its results, like `.bookmark run`. Session
+    /// variables in the query are substituted first.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The bookmark to run.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the query's results, or an `Err` if there is no such bookmark
+    /// or the query fails.
+    pub fn run_bookmark(&self, name: &str) -> Result<QueryResult> {
+        let bookmark = self
+            .bookmarks
+            .as_ref()
+            .and_then(|bookmarks| bookmarks.get_bookmark(name))
+            .with_context(|| format!("Bookmark '{}' not found", name))?;
+        let query = session_variables()
+            .lock()
+            .unwrap()
+            .interpolate(&bookmark.query)?;
+        self.query(&query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut session = Session::open(dir.path().join("session.db")).unwrap();
+        session
+            .connection()
+            .execute_batch(
+                "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT NOT NULL, role TEXT DEFAULT 'member');
+                 INSERT INTO users (name) VALUES ('alice'), ('bob'), ('carol');",
+            )
+            .unwrap();
+
+        assert_eq!(session.tables().unwrap(), ["users"]);
+        let description = session.describe_table("users").unwrap();
+        assert_eq!(description.row_count, 3);
+        assert_eq!(description.deleted_rows, None);
+        assert_eq!(description.columns.len(), 3);
+        assert!(description.columns[0].primary_key);
+        assert!(description.columns[1].not_null);
+        assert_eq!(
+            description.columns[2].default_value.as_deref(),
+            Some("'member'")
+        );
+        assert!(session.describe_table("missing").is_err());
+        // The core's introspection over `Backend` sees the same columns
+        assert_eq!(
+            session.connection().columns("users").unwrap(),
+            description.columns
+        );
+
+        let export = dir.path().join("out.csv");
+        let export = export.to_str().unwrap();
+        assert!(session
+            .export_last_result(export, None, &ExportOptions::default())
+            .is_err());
+
+        session.set_limit(Some(2));
+        let result = session.query("SELECT name FROM users ORDER BY id").unwrap();
+        assert_eq!(result.columns, ["name"]);
+        assert_eq!(result.rows.len(), 2);
+        assert_eq!(
+            session.last_query().as_deref(),
+            Some("SELECT name FROM users ORDER BY id")
+        );
+
+        // Writes that return rows aren't kept, since exporting would repeat them
+        session
+            .query("INSERT INTO users (name) VALUES ('dave') RETURNING id")
+            .unwrap();
+        session.query("VALUES (1)").unwrap();
+        assert_eq!(session.last_query().as_deref(), Some("VALUES (1)"));
+        session.query("SELECT name FROM users ORDER BY id").unwrap();
+
+        // Exports are not cut off at the row limit
+        let written = session
+            .export_last_result(export, None, &ExportOptions::default())
+            .unwrap();
+        assert_eq!(written, 4);
+        let csv = std::fs::read_to_string(export).unwrap();
+        assert!(csv.starts_with("name\n"));
+    }
+}
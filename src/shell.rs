@@ -12,6 +12,9 @@
 //! - **REPL Integration**: Seamlessly switch back to the SQL REPL using the `.vrepl` command.
 //! - **Command Completion**: Provides basic completion for built-in commands and file paths.
 //! - **Persistent History**: Saves shell command history across sessions.
+//! - **Query Pipes**: `spawn_piped` runs a command that the REPL streams query results into.
+//! - **Ctrl+C Handling**: `catch_ctrl_c` keeps Ctrl+C from ending the process while the
+//!   shell or a long-running REPL command such as `.watch` is active.
 
 use crate::config;
 use anyhow::{Context, Result};
@@ -25,7 +28,14 @@ use rustyline::{Editor, Helper};
 use std::env;
 use std::io::{self};
 use std::path::Path;
-use std::process::Command;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+/// Whether Ctrl+C is being caught instead of ending the process.
+static CATCHING_CTRL_C: AtomicBool = AtomicBool::new(false);
+/// Whether Ctrl+C was pressed while it was being caught.
+static CTRL_C_PRESSED: AtomicBool = AtomicBool::new(false);
 
 /// Defines the possible actions that can be returned from the shell session.
 /// This is used to signal whether the user wants to exit the application entirely
@@ -139,13 +149,6 @@ impl Shell {
             }
         }
 
-        // Set up Ctrl+C handler
-        if let Err(e) = ctrlc::set_handler(move || {
-            println!("\nUse 'exit' to return to the REPL");
-        }) {
-            eprintln!("Warning: Could not set up Ctrl+C handler: {}", e);
-        }
-
         Ok(Shell {
             editor,
             original_dir,
@@ -182,6 +185,10 @@ impl Shell {
     /// A `ShellAction` indicating the user's intent to either exit the application
     /// or switch back to the REPL.
     pub fn run(&mut self) -> ShellAction {
+        catch_ctrl_c(|| self.run_loop())
+    }
+
+    fn run_loop(&mut self) -> ShellAction {
         println!("Welcome to Vapor Shell! Type 'exit' to return to the REPL.");
         println!("Type 'help' for available commands.");
 
@@ -218,6 +225,9 @@ impl Shell {
                     }
 
                     self.execute_command(line);
+                    if ctrl_c_pressed() {
+                        println!("\nUse 'exit' to return to the REPL");
+                    }
                 }
                 Err(ReadlineError::Interrupted) => {
                     println!("^C");
@@ -344,4 +354,64 @@ pub fn shell_mode(db_path: &str) -> Result<ShellAction> {
     }
 
     Ok(action)
-}
\ No newline at end of file
+}
+/// Runs a function with Ctrl+C caught instead of ending the process. Presses in the
+/// meantime are reported by `ctrl_c_pressed`.
+///
+/// # Arguments
+///
+/// * `f` - The function to run.
+///
+/// # Returns
+///
+/// What `f` returns.
+pub fn catch_ctrl_c<T>(f: impl FnOnce() -> T) -> T {
+    static HANDLER: OnceLock<bool> = OnceLock::new();
+    let installed = *HANDLER.get_or_init(|| {
+        let result = ctrlc::set_handler(|| {
+            if CATCHING_CTRL_C.load(Ordering::SeqCst) {
+                CTRL_C_PRESSED.store(true, Ordering::SeqCst);
+            } else {
+                std::process::exit(130);
+            }
+        });
+        if let Err(e) = &result {
+            eprintln!("Warning: Could not set up Ctrl+C handler: {}", e);
+        }
+        result.is_ok()
+    });
+
+    let was_catching = CATCHING_CTRL_C.swap(installed, Ordering::SeqCst);
+    CTRL_C_PRESSED.store(false, Ordering::SeqCst);
+    let result = f();
+    CATCHING_CTRL_C.store(was_catching, Ordering::SeqCst);
+    result
+}
+
+/// Returns whether Ctrl+C was pressed since the last call, inside `catch_ctrl_c`.
+pub fn ctrl_c_pressed() -> bool {
+    CTRL_C_PRESSED.swap(false, Ordering::SeqCst)
+}
+
+/// Starts a command through the system shell with its standard input piped, so the
+/// REPL can stream query results into it (`.pipe`).
+///
+/// # Arguments
+///
+/// * `command` - The command line. Pipes, quoting, and redirections work as they would
+///   in `sh` (or `cmd` on Windows).
+///
+/// # Returns
+///
+/// A `Result` containing the running `Child`, whose output goes to the console.
+pub fn spawn_piped(command: &str) -> Result<Child> {
+    #[cfg(windows)]
+    let (shell, flag) = ("cmd", "/C");
+    #[cfg(not(windows))]
+    let (shell, flag) = ("sh", "-c");
+    Command::new(shell)
+        .args([flag, command])
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run '{}'", command))
+}
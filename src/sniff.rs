@@ -0,0 +1,319 @@
+//! # CSV Dialect Sniffing
+//!
+//! This module guesses the dialect of a CSV file from a sample of its first few
+//! kilobytes, so files that don't match the usual comma-and-double-quote defaults can be
+//! imported without working out the right flags by hand.
+//!
+//! ## Features:
+//! - **Delimiter**: Picks the one of `,`, `;`, tab, and `|` that splits the most records
+//!   into the same number of fields.
+//! - **Quotes and escapes**: Detects single-quoted fields and backslash-escaped quotes.
+//! - **Line endings**: Detects CRLF, LF, and CR-only files.
+//! - **Preamble and header**: Counts leading lines (such as a report title) that don't
+//!   have the same shape as the data, and guesses whether the first row is a header.
+//! - **Encoding**: Files that aren't valid UTF-8 are read as Windows-1252.
+
+use anyhow::{Context, Result};
+use std::fmt;
+use std::io::Read;
+
+use crate::compression::{open_input, STDIN_PATH};
+use crate::export::{CsvImportOptions, LineEnding, TextEncoding};
+
+/// How many bytes of a file are read to sniff its dialect.
+pub const SNIFF_SAMPLE_SIZE: usize = 64 * 1024;
+
+const DELIMITERS: [u8; 4] = [b',', b';', b'\t', b'|'];
+
+/// The dialect of a CSV file, as detected by [`sniff_csv_dialect`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvDialect {
+    /// The field delimiter.
+    pub delimiter: u8,
+    /// The quote character.
+    pub quote: u8,
+    /// The character that escapes quotes, or `None` if quotes are doubled.
+    pub escape: Option<u8>,
+    /// The line endings.
+    pub line_ending: LineEnding,
+    /// Whether the first row after the skipped lines is a header.
+    pub has_header: bool,
+    /// The number of leading lines that aren't part of the table.
+    pub skip_lines: usize,
+    /// The text encoding.
+    pub encoding: TextEncoding,
+}
+
+impl CsvDialect {
+    /// Copies the detected dialect into import options, keeping their other settings.
+    pub fn apply_to(&self, options: &mut CsvImportOptions) {
+        options.delimiter = self.delimiter;
+        options.quote = self.quote;
+        options.escape = self.escape;
+        options.line_ending = self.line_ending;
+        options.has_header = self.has_header;
+        options.skip_lines = self.skip_lines;
+        options.encoding = self.encoding;
+    }
+}
+
+impl fmt::Display for CsvDialect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let delimiter = match self.delimiter {
+            b'\t' => "tab".to_string(),
+            other => format!("'{}'", other as char),
+        };
+        let escape = match self.escape {
+            Some(escape) => format!("'{}'", escape as char),
+            None => "doubled quotes".to_string(),
+        };
+        write!(
+            f,
+            "delimiter {}, quote '{}', escape {}, line ending {:?}, {}, {} skipped line(s), encoding {:?}",
+            delimiter,
+            self.quote as char,
+            escape,
+            self.line_ending,
+            if self.has_header { "header" } else { "no header" },
+            self.skip_lines,
+            self.encoding
+        )
+    }
+}
+
+/// Detects the dialect of a CSV file from its first [`SNIFF_SAMPLE_SIZE`] bytes.
+///
+/// # Arguments
+///
+/// * `file_path` - The file to sniff. Compressed files are decompressed first. Standard
+///   input can't be sniffed, because it can only be read once.
+///
+/// # Returns
+///
+/// A `Result` containing the detected `CsvDialect`.
+pub fn sniff_csv_file(file_path: &str) -> Result<CsvDialect> {
+    if file_path == STDIN_PATH {
+        anyhow::bail!(
+            "Can't detect the CSV dialect of standard input; pass the dialect flags instead"
+        );
+    }
+    let mut sample = Vec::with_capacity(SNIFF_SAMPLE_SIZE);
+    open_input(file_path)?
+        .take(SNIFF_SAMPLE_SIZE as u64)
+        .read_to_end(&mut sample)
+        .with_context(|| format!("Failed to read '{}'", file_path))?;
+    Ok(sniff_csv_dialect(&sample))
+}
+
+/// Detects the dialect of a CSV sample.
+///
+/// # Arguments
+///
+/// * `sample` - The start of the file. A partial last line is ignored.
+///
+/// # Returns
+///
+/// The detected `CsvDialect`. Anything that can't be detected keeps its default.
+pub fn sniff_csv_dialect(sample: &[u8]) -> CsvDialect {
+    let sample = sample
+        .strip_prefix(crate::export::UTF8_BOM)
+        .unwrap_or(sample);
+    let encoding = match std::str::from_utf8(sample) {
+        // A sample cut off in the middle of a character is still UTF-8
+        Err(e) if e.error_len().is_some() => TextEncoding::Windows1252,
+        _ => TextEncoding::Utf8,
+    };
+    let line_ending = if sample.windows(2).any(|w| w == b"\r\n") {
+        LineEnding::Crlf
+    } else if !sample.contains(&b'\n') && sample.contains(&b'\r') {
+        LineEnding::Cr
+    } else {
+        LineEnding::Lf
+    };
+
+    let quote = sniff_quote(sample);
+    let escape = sniff_escape(sample, quote);
+
+    // The delimiter that gives the most records the same number of fields
+    let mut best: Option<(u8, usize, usize)> = None;
+    for delimiter in DELIMITERS {
+        let counts: Vec<usize> = records(sample, delimiter, quote, escape)
+            .iter()
+            .map(Vec::len)
+            .collect();
+        let Some((fields, frequency)) = mode(&counts) else {
+            continue;
+        };
+        let better = best
+            .filter(|&(_, f, n)| (n, f) >= (frequency, fields))
+            .is_none();
+        if fields > 1 && better {
+            best = Some((delimiter, fields, frequency));
+        }
+    }
+    let (delimiter, fields) = best.map_or((b',', 1), |(d, f, _)| (d, f));
+
+    let records = records(sample, delimiter, quote, escape);
+    let skip_lines = if fields > 1 {
+        records.iter().take_while(|r| r.len() != fields).count()
+    } else {
+        0
+    };
+    let has_header = match records.get(skip_lines) {
+        Some(first) => looks_like_header(first, &records[skip_lines + 1..]),
+        None => true,
+    };
+
+    CsvDialect {
+        delimiter,
+        quote,
+        escape,
+        line_ending,
+        has_header,
+        skip_lines,
+        encoding,
+    }
+}
+
+/// Uses `'` as the quote if it, and not `"`, starts fields.
+fn sniff_quote(sample: &[u8]) -> u8 {
+    let starts_field = |quote: u8| {
+        sample
+            .iter()
+            .enumerate()
+            .filter(|&(i, &b)| {
+                b == quote
+                    && (i == 0
+                        || matches!(sample[i - 1], b'\n' | b'\r')
+                        || DELIMITERS.contains(&sample[i - 1]))
+            })
+            .count()
+    };
+    if !sample.contains(&b'"') && starts_field(b'\'') > 0 {
+        b'\''
+    } else {
+        b'"'
+    }
+}
+
+/// Uses `\` as the escape if `\"` appears inside fields more often than `""` does.
+fn sniff_escape(sample: &[u8], quote: u8) -> Option<u8> {
+    let ends_field = |b: Option<&u8>| match b {
+        Some(b) => matches!(b, b'\n' | b'\r') || DELIMITERS.contains(b),
+        None => true,
+    };
+    let mut backslashed = 0;
+    let mut doubled = 0;
+    for (i, pair) in sample.windows(2).enumerate() {
+        if pair[1] != quote || ends_field(sample.get(i + 2)) {
+            continue;
+        }
+        if pair[0] == b'\\' {
+            backslashed += 1;
+        } else if pair[0] == quote && i > 0 && !ends_field(sample.get(i - 1)) {
+            doubled += 1;
+        }
+    }
+    (backslashed > doubled).then_some(b'\\')
+}
+
+/// Splits a sample into records of fields, honoring quotes. The last record is left out
+/// if it isn't terminated, since the sample may have cut it short.
+fn records(sample: &[u8], delimiter: u8, quote: u8, escape: Option<u8>) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = Vec::new();
+    let mut in_quotes = false;
+    let mut bytes = sample.iter().copied().peekable();
+    while let Some(b) = bytes.next() {
+        if in_quotes {
+            if Some(b) == escape {
+                field.extend(bytes.next());
+            } else if b == quote {
+                if escape.is_none() && bytes.peek() == Some(&quote) {
+                    field.push(quote);
+                    bytes.next();
+                } else {
+                    in_quotes = false;
+                }
+            } else {
+                field.push(b);
+            }
+        } else if b == quote && field.is_empty() {
+            in_quotes = true;
+        } else if b == delimiter {
+            record.push(String::from_utf8_lossy(&field).into_owned());
+            field.clear();
+        } else if b == b'\n' || b == b'\r' {
+            if b == b'\r' && bytes.peek() == Some(&b'\n') {
+                bytes.next();
+            }
+            record.push(String::from_utf8_lossy(&field).into_owned());
+            field.clear();
+            records.push(std::mem::take(&mut record));
+        } else {
+            field.push(b);
+        }
+    }
+    records
+}
+
+/// Returns the most common value and how often it occurs, preferring the larger value
+/// on ties.
+fn mode(values: &[usize]) -> Option<(usize, usize)> {
+    let mut counts = std::collections::BTreeMap::new();
+    for &value in values {
+        *counts.entry(value).or_insert(0) += 1;
+    }
+    counts
+        .into_iter()
+        .max_by_key(|&(value, count)| (count, value))
+}
+
+/// A first row is a header unless it holds numbers: names are almost never numeric, and
+/// a numeric column whose first value is text is a strong sign of one.
+fn looks_like_header(first: &[String], rest: &[Vec<String>]) -> bool {
+    let is_number = |value: &str| value.trim().parse::<f64>().is_ok();
+    if first.iter().any(|value| is_number(value)) {
+        return false;
+    }
+    let rest = &rest[..rest.len().min(20)];
+    let numeric_column = (0..first.len()).any(|column| {
+        !rest.is_empty()
+            && rest
+                .iter()
+                .all(|record| record.get(column).is_some_and(|value| is_number(value)))
+    });
+    // All-text tables can't be told apart, and a header is the more common case
+    numeric_column || !first.iter().any(String::is_empty)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_csv_dialect() {
+        let dialect = sniff_csv_dialect(b"id,name\n1,Ann\n2,\"Bob, Jr.\"\n");
+        assert_eq!(dialect.delimiter, b',');
+        assert_eq!(dialect.line_ending, LineEnding::Lf);
+        assert!(dialect.has_header);
+        assert_eq!(dialect.skip_lines, 0);
+
+        let mut sample = b"Report generated 2024-01-01\r\n1;M".to_vec();
+        sample.push(0xFC);
+        sample.extend_from_slice(b"nchen;NA\r\n2;'Oslo; Norway';ok\r\n3;Bergen;ok\r\n");
+        let dialect = sniff_csv_dialect(&sample);
+        assert_eq!(dialect.delimiter, b';');
+        assert_eq!(dialect.quote, b'\'');
+        assert_eq!(dialect.line_ending, LineEnding::Crlf);
+        assert_eq!(dialect.skip_lines, 1);
+        assert!(!dialect.has_header);
+        assert_eq!(dialect.encoding, TextEncoding::Windows1252);
+
+        let dialect = sniff_csv_dialect(b"a\tb\r\"say \\\"hi\\\"\"\tx\ry\tz\r");
+        assert_eq!(dialect.delimiter, b'\t');
+        assert_eq!(dialect.escape, Some(b'\\'));
+        assert_eq!(dialect.line_ending, LineEnding::Cr);
+    }
+}
@@ -0,0 +1,249 @@
+//! # Soft Delete
+//!
+//! This module packages the soft-delete pattern: rows are marked with a `deleted_at`
+//! timestamp instead of being removed, and views give the live and deleted rows their own
+//! names. It saves writing the same column, views, and trigger by hand for every table.
+//!
+//! ## Features:
+//! - **`deleted_at` column**: Added to the table if it doesn't already have one.
+//! - **Filtered views**: `<table>_live` shows rows that haven't been deleted, and
+//!   `<table>_deleted` shows the ones that have.
+//! - **Delete trigger**: A `DELETE` on the table stamps `deleted_at` instead of removing
+//!   the row. Deleting a row that is already soft-deleted removes it for good.
+//! - **Row counts**: Reports live and deleted row counts for `.peek` and `.describe`.
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+
+use crate::db::table_columns;
+use crate::formats::quote_identifier;
+
+/// The column that marks a row as deleted.
+pub const DELETED_AT_COLUMN: &str = "deleted_at";
+
+/// Live and deleted row counts for a table with soft delete enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SoftDeleteCounts {
+    /// Rows whose `deleted_at` is `NULL`.
+    pub live: i64,
+    /// Rows with a `deleted_at` timestamp.
+    pub deleted: i64,
+}
+
+fn live_view_name(table: &str) -> String {
+    format!("{}_live", table)
+}
+
+fn deleted_view_name(table: &str) -> String {
+    format!("{}_deleted", table)
+}
+
+fn trigger_name(table: &str) -> String {
+    format!("{}_soft_delete", table)
+}
+
+/// Enables soft delete on a table.
+///
+/// Adds the `deleted_at` column if needed, then creates the `<table>_live` and
+/// `<table>_deleted` views and the trigger that turns deletes into updates. Running it
+/// again on the same table recreates the views and trigger.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `table` - The table to enable soft delete on. It must be a rowid table.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` once the table has been set up.
+pub fn enable_soft_delete(conn: &Connection, table: &str) -> Result<()> {
+    let columns = rowid_table_columns(conn, table, "Soft delete")?;
+    let quoted = quote_identifier(table);
+    let column = quote_identifier(DELETED_AT_COLUMN);
+    let mut sql = String::from("SAVEPOINT soft_delete;\n");
+    if !columns.iter().any(|c| c == DELETED_AT_COLUMN) {
+        sql.push_str(&format!(
+            "ALTER TABLE {} ADD COLUMN {} TEXT;\n",
+            quoted, column
+        ));
+    }
+    sql.push_str(&format!(
+        "DROP VIEW IF EXISTS {live};
+         CREATE VIEW {live} AS SELECT * FROM {table} WHERE {column} IS NULL;
+         DROP VIEW IF EXISTS {deleted};
+         CREATE VIEW {deleted} AS SELECT * FROM {table} WHERE {column} IS NOT NULL;
+         DROP TRIGGER IF EXISTS {trigger};
+         CREATE TRIGGER {trigger} BEFORE DELETE ON {table}
+         WHEN OLD.{column} IS NULL
+         BEGIN
+             UPDATE {table} SET {column} = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+             WHERE rowid = OLD.rowid;
+             SELECT RAISE(IGNORE);
+         END;
+         RELEASE soft_delete;",
+        live = quote_identifier(&live_view_name(table)),
+        deleted = quote_identifier(&deleted_view_name(table)),
+        trigger = quote_identifier(&trigger_name(table)),
+        table = quoted,
+        column = column,
+    ));
+
+    if let Err(e) = conn.execute_batch(&sql) {
+        let _ = conn.execute_batch("ROLLBACK TO soft_delete; RELEASE soft_delete;");
+        return Err(e).with_context(|| format!("Failed to enable soft delete on '{}'", table));
+    }
+    Ok(())
+}
+
+/// Disables soft delete on a table.
+///
+/// Drops the views and trigger created by [`enable_soft_delete`]. The `deleted_at`
+/// column and the rows marked as deleted are left in place.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `table` - The table to disable soft delete on.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` once the views and trigger are gone.
+pub fn disable_soft_delete(conn: &Connection, table: &str) -> Result<()> {
+    conn.execute_batch(&format!(
+        "DROP TRIGGER IF EXISTS {};
+         DROP VIEW IF EXISTS {};
+         DROP VIEW IF EXISTS {};",
+        quote_identifier(&trigger_name(table)),
+        quote_identifier(&live_view_name(table)),
+        quote_identifier(&deleted_view_name(table)),
+    ))
+    .with_context(|| format!("Failed to disable soft delete on '{}'", table))
+}
+
+/// Returns the live and deleted row counts of a table, if soft delete is enabled on it.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `table` - The table to count.
+///
+/// # Returns
+///
+/// A `Result` containing the counts, or `None` if the table doesn't use soft delete.
+pub fn soft_delete_counts(conn: &Connection, table: &str) -> Result<Option<SoftDeleteCounts>> {
+    let enabled: bool = conn
+        .query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'trigger' AND name = ? AND tbl_name = ?)",
+            params![trigger_name(table), table],
+            |row| row.get(0),
+        )
+        .context("Failed to look up the soft delete trigger")?;
+    if !enabled {
+        return Ok(None);
+    }
+
+    let column = quote_identifier(DELETED_AT_COLUMN);
+    let counts = conn
+        .query_row(
+            &format!(
+                "SELECT COALESCE(SUM({column} IS NULL), 0), COALESCE(SUM({column} IS NOT NULL), 0) FROM {}",
+                quote_identifier(table),
+                column = column
+            ),
+            [],
+            |row| {
+                Ok(SoftDeleteCounts {
+                    live: row.get(0)?,
+                    deleted: row.get(1)?,
+                })
+            },
+        )
+        .with_context(|| format!("Failed to count rows in '{}'", table))?;
+    Ok(Some(counts))
+}
+
+/// Returns the columns of a table that triggers can address by rowid, or an error naming
+/// `feature` if the table is missing or is a `WITHOUT ROWID` table.
+pub(crate) fn rowid_table_columns(
+    conn: &Connection,
+    table: &str,
+    feature: &str,
+) -> Result<Vec<String>> {
+    let columns: Vec<String> = table_columns(conn, None, table)?
+        .into_iter()
+        .filter(|column| !column.generated)
+        .map(|column| column.name)
+        .collect();
+    if columns.is_empty() {
+        anyhow::bail!("Table '{}' does not exist", table);
+    }
+    let without_rowid: bool = conn
+        .query_row(
+            "SELECT sql LIKE '%WITHOUT ROWID%' FROM sqlite_master WHERE type = 'table' AND name = ?",
+            params![table],
+            |row| row.get(0),
+        )
+        .with_context(|| format!("Failed to read the definition of table '{}'", table))?;
+    if without_rowid {
+        anyhow::bail!(
+            "{} needs a rowid, and '{}' is a WITHOUT ROWID table",
+            feature,
+            table
+        );
+    }
+    Ok(columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_soft_delete_marks_then_purges() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT);
+             INSERT INTO items (name) VALUES ('a'), ('b'), ('c');",
+        )
+        .unwrap();
+        assert_eq!(soft_delete_counts(&conn, "items").unwrap(), None);
+
+        enable_soft_delete(&conn, "items").unwrap();
+        conn.execute("DELETE FROM items WHERE name = 'a'", [])
+            .unwrap();
+        let counts = soft_delete_counts(&conn, "items").unwrap().unwrap();
+        assert_eq!(
+            counts,
+            SoftDeleteCounts {
+                live: 2,
+                deleted: 1
+            }
+        );
+
+        let live: i64 = conn
+            .query_row("SELECT COUNT(*) FROM items_live", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(live, 2);
+
+        // A second delete of the same row removes it.
+        conn.execute("DELETE FROM items WHERE name = 'a'", [])
+            .unwrap();
+        let counts = soft_delete_counts(&conn, "items").unwrap().unwrap();
+        assert_eq!(
+            counts,
+            SoftDeleteCounts {
+                live: 2,
+                deleted: 0
+            }
+        );
+
+        // Enabling again is harmless, and disabling keeps the column.
+        enable_soft_delete(&conn, "items").unwrap();
+        disable_soft_delete(&conn, "items").unwrap();
+        assert_eq!(soft_delete_counts(&conn, "items").unwrap(), None);
+        assert!(table_columns(&conn, None, "items")
+            .unwrap()
+            .iter()
+            .any(|column| column.name == DELETED_AT_COLUMN));
+    }
+}
@@ -0,0 +1,643 @@
+//! # Statement Completeness
+//!
+//! This module decides whether REPL input is a complete SQL statement or needs more
+//! lines. A plain "ends with a semicolon" test gets this wrong for semicolons inside
+//! string literals, comments, and `CREATE TRIGGER ... BEGIN ... END` bodies, so the input
+//! is tokenized instead.
+//!
+//! ## Features:
+//! - **Literal and comment aware**: Semicolons inside quotes, quoted identifiers, and
+//!   `--` or `/* */` comments don't end a statement.
+//! - **Trigger bodies**: Inside `CREATE TRIGGER`, statements between `BEGIN` and `END`
+//!   don't end the outer statement. `CASE ... END` is tracked so its `END` isn't
+//!   mistaken for the end of the body.
+//! - **Terminator-free commands**: Dot-commands and a few keywords (`exit`, `tables`,
+//!   `begin`, ...) run without a semicolon, as they always have.
+//! - **Script splitting**: The same rules split a SQL file into statements for `.read`.
+//! - **Destructive statements**: `destructive_reason` spots `DELETE` and `UPDATE` without
+//!   a `WHERE` clause, `DROP`, and `ALTER`, for the REPL's `.safe` mode.
+//! - **Write statements**: `is_write_statement` spots `INSERT`, `UPDATE`, `DELETE`, and
+//!   `REPLACE`, for batching them with `.autocommit off`.
+//! - **Statement kinds**: `classify` tells queries, row changes, schema changes,
+//!   transaction control, pragmas, and dot-commands apart, looking past `WITH` clauses
+//!   and comments, so callers don't guess from the first word. `classify_with_verb`
+//!   also returns the verb, such as `COMMIT` or `ROLLBACK`.
+//! - **DROP statements**: `parse_drop` reads what a `DROP TABLE`, `INDEX`, `VIEW`, or
+//!   `TRIGGER` statement drops, keeping quoted and mixed-case names as written.
+
+/// A token, as far as completeness is concerned.
+#[derive(Debug, PartialEq, Eq)]
+enum Token<'a> {
+    Word(&'a str),
+    /// A semicolon, with its byte offset.
+    Semicolon(usize),
+    /// A string literal or quoted identifier, with its quotes.
+    Quoted(&'a str),
+    Open,
+    Close,
+    Dot,
+    Other,
+}
+
+/// Returns `true` if `sql` ends with a complete statement.
+///
+/// # Arguments
+///
+/// * `sql` - The input collected so far, possibly over several lines.
+///
+/// # Returns
+///
+/// `true` if the input ends in a semicolon that terminates a statement, ignoring
+/// trailing whitespace and comments. `false` if it is empty, ends inside a literal or
+/// comment, or ends inside a trigger body.
+pub fn is_complete_statement(sql: &str) -> bool {
+    let (tokens, closed) = tokenize(sql);
+    closed && statement_ends(&tokens).1
+}
+
+/// Splits a SQL script into statements.
+///
+/// # Arguments
+///
+/// * `sql` - The script text.
+///
+/// # Returns
+///
+/// Each statement with the byte offset where it starts, including its terminating
+/// semicolon. Text after the last semicolon is returned as a final statement unless it
+/// is only whitespace and comments. Empty statements (`;;`) are skipped.
+pub fn split_statements(sql: &str) -> Vec<(usize, &str)> {
+    let (tokens, _) = tokenize(sql);
+    let (mut ends, _) = statement_ends(&tokens);
+    ends.push(sql.len());
+
+    let mut statements = Vec::new();
+    let mut start = 0;
+    for end in ends {
+        let end = (end + 1).min(sql.len());
+        let piece = &sql[start..end];
+        let (tokens, _) = tokenize(piece);
+        if tokens.iter().any(|t| !matches!(t, Token::Semicolon(_))) {
+            let trimmed = piece.trim_start();
+            statements.push((start + piece.len() - trimmed.len(), trimmed.trim_end()));
+        }
+        start = end;
+    }
+    statements
+}
+
+/// Describes why SQL should be confirmed before it runs, if it should.
+///
+/// # Arguments
+///
+/// * `sql` - One or more SQL statements.
+///
+/// # Returns
+///
+/// A description such as `DELETE without a WHERE clause` or `DROP TABLE` for the first
+/// statement that deletes or updates every row of a table, drops something, or alters a
+/// table, or `None` if there is no such statement. Statements inside trigger bodies
+/// don't count, since they only run when the trigger fires.
+pub fn destructive_reason(sql: &str) -> Option<String> {
+    split_statements(sql)
+        .into_iter()
+        .find_map(|(_, statement)| {
+            let words = top_level_words(statement);
+            let mut words = words.iter().map(String::as_str);
+            match classify_words(&mut words) {
+                (StatementKind::Dml, Some(verb @ ("DELETE" | "UPDATE")))
+                    if !words.any(|word| word == "WHERE") =>
+                {
+                    Some(format!("{} without a WHERE clause", verb))
+                }
+                (StatementKind::Ddl, Some(verb @ ("DROP" | "ALTER"))) => Some(match words.next() {
+                    Some(object) => format!("{} {}", verb, object),
+                    None => verb.to_string(),
+                }),
+                _ => None,
+            }
+        })
+}
+
+/// Returns `true` if `sql` only inserts, updates, or deletes rows.
+///
+/// # Arguments
+///
+/// * `sql` - One or more SQL statements.
+///
+/// # Returns
+///
+/// `true` if there is at least one statement and each is an `INSERT`, `UPDATE`,
+/// `DELETE`, or `REPLACE`, including after a `WITH` clause.
+pub fn is_write_statement(sql: &str) -> bool {
+    let statements = split_statements(sql);
+    !statements.is_empty()
+        && statements
+            .into_iter()
+            .all(|(_, statement)| classify(statement) == StatementKind::Dml)
+}
+
+/// What kind of statement something is, as decided by `classify`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatementKind {
+    /// Returns rows: `SELECT`, `VALUES`, `EXPLAIN`, or a `WITH` clause ending in `SELECT`.
+    Query,
+    /// Changes rows: `INSERT`, `UPDATE`, `DELETE`, or `REPLACE`, with or without `WITH`.
+    Dml,
+    /// Changes the schema: `CREATE`, `DROP`, or `ALTER`.
+    Ddl,
+    /// Controls transactions: `BEGIN`, `COMMIT`, `END`, `ROLLBACK`, `SAVEPOINT`, or
+    /// `RELEASE`.
+    Transaction,
+    /// A `PRAGMA`.
+    Pragma,
+    /// A REPL dot-command such as `.tables`.
+    DotCommand,
+    /// Anything else, such as `ATTACH`, `VACUUM`, or `ANALYZE`, or empty input.
+    Other,
+}
+
+/// Classifies the first statement in `sql`.
+///
+/// # Arguments
+///
+/// * `sql` - A statement or dot-command. Leading whitespace and comments are skipped.
+///
+/// # Returns
+///
+/// The kind of the statement. `WITH` clauses are classified by the statement after
+/// them, so `WITH recent AS (...) SELECT ...` is a `Query`.
+pub fn classify(sql: &str) -> StatementKind {
+    classify_with_verb(sql).0
+}
+
+/// Classifies the first statement in `sql`, as `classify` does, and also returns its verb.
+///
+/// # Arguments
+///
+/// * `sql` - A statement or dot-command. Leading whitespace and comments are skipped.
+///
+/// # Returns
+///
+/// The kind of the statement and its verb, upper-cased, such as `COMMIT` or `DELETE`.
+/// After a `WITH` clause the verb is the statement's, not `WITH`. Dot-commands and
+/// empty input have no verb.
+pub fn classify_with_verb(sql: &str) -> (StatementKind, Option<String>) {
+    if sql.trim_start().starts_with('.') {
+        return (StatementKind::DotCommand, None);
+    }
+    let statement = split_statements(sql)
+        .into_iter()
+        .next()
+        .map_or("", |(_, statement)| statement);
+    let words = top_level_words(statement);
+    let (kind, verb) = classify_words(&mut words.iter().map(String::as_str));
+    (kind, verb.map(str::to_string))
+}
+
+/// Classifies a statement from its top-level words. Returns its kind and verb, and
+/// leaves the words after the verb.
+fn classify_words<'a>(
+    words: &mut impl Iterator<Item = &'a str>,
+) -> (StatementKind, Option<&'a str>) {
+    let verb = statement_verb(words);
+    let kind = match verb {
+        Some("SELECT" | "VALUES" | "EXPLAIN") => StatementKind::Query,
+        Some("INSERT" | "UPDATE" | "DELETE" | "REPLACE") => StatementKind::Dml,
+        Some("CREATE" | "DROP" | "ALTER") => StatementKind::Ddl,
+        Some("BEGIN" | "COMMIT" | "END" | "ROLLBACK" | "SAVEPOINT" | "RELEASE") => {
+            StatementKind::Transaction
+        }
+        Some("PRAGMA") => StatementKind::Pragma,
+        _ => StatementKind::Other,
+    };
+    (kind, verb)
+}
+
+/// A `DROP` statement, as recognized by `parse_drop`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DropStatement {
+    /// What is dropped: `TABLE`, `INDEX`, `VIEW`, or `TRIGGER`.
+    pub kind: &'static str,
+    /// Whether the statement has `IF EXISTS`.
+    pub if_exists: bool,
+    /// The schema given before the name, such as `main` or an attached database.
+    pub schema: Option<String>,
+    /// The name of what is dropped, unquoted and with its case kept.
+    pub name: String,
+}
+
+/// Recognizes a single `DROP TABLE`, `DROP INDEX`, `DROP VIEW`, or `DROP TRIGGER`
+/// statement.
+///
+/// # Arguments
+///
+/// * `sql` - The statement, with or without a terminating semicolon.
+///
+/// # Returns
+///
+/// The parts of the statement, or `None` if `sql` is anything else, including several
+/// statements.
+pub fn parse_drop(sql: &str) -> Option<DropStatement> {
+    let statements = split_statements(sql);
+    let [(_, statement)] = statements.as_slice() else {
+        return None;
+    };
+    let (tokens, closed) = tokenize(statement);
+    if !closed {
+        return None;
+    }
+    let mut tokens = tokens
+        .into_iter()
+        .filter(|token| !matches!(token, Token::Semicolon(_)));
+    let mut keyword = || match tokens.next() {
+        Some(Token::Word(word)) => Some(word.to_ascii_uppercase()),
+        _ => None,
+    };
+    if keyword()? != "DROP" {
+        return None;
+    }
+    let kind = match keyword()?.as_str() {
+        "TABLE" => "TABLE",
+        "INDEX" => "INDEX",
+        "VIEW" => "VIEW",
+        "TRIGGER" => "TRIGGER",
+        _ => return None,
+    };
+
+    let mut rest: Vec<Token> = tokens.collect();
+    let if_exists = matches!(
+        rest.as_slice(),
+        [Token::Word(a), Token::Word(b), ..]
+            if a.eq_ignore_ascii_case("IF") && b.eq_ignore_ascii_case("EXISTS")
+    );
+    if if_exists {
+        rest.drain(..2);
+    }
+    let (schema, name) = match rest.as_slice() {
+        [name] => (None, identifier(name)?),
+        [schema, Token::Dot, name] => (Some(identifier(schema)?), identifier(name)?),
+        _ => return None,
+    };
+    Some(DropStatement {
+        kind,
+        if_exists,
+        schema,
+        name,
+    })
+}
+
+/// The name in a bare or quoted identifier token.
+fn identifier(token: &Token<'_>) -> Option<String> {
+    match token {
+        Token::Word(word) => Some(word.to_string()),
+        Token::Quoted(quoted) => {
+            let close = quoted.chars().last()?;
+            let inner = &quoted[1..quoted.len() - close.len_utf8()];
+            Some(match close {
+                ']' => inner.to_string(),
+                _ => inner.replace(&close.to_string().repeat(2), &close.to_string()),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// The upper-cased words of a statement outside parentheses, so subqueries and CTE
+/// bodies are left out.
+fn top_level_words(statement: &str) -> Vec<String> {
+    let (tokens, _) = tokenize(statement);
+    let mut depth = 0usize;
+    let mut words = Vec::new();
+    for token in &tokens {
+        match token {
+            Token::Open => depth += 1,
+            Token::Close => depth = depth.saturating_sub(1),
+            Token::Word(word) if depth == 0 => words.push(word.to_ascii_uppercase()),
+            _ => {}
+        }
+    }
+    words
+}
+
+/// Takes the verb of a statement from its top-level words, leaving the words after it.
+fn statement_verb<'a>(words: &mut impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    match words.next()? {
+        // `WITH name AS (...) DELETE ...` runs the statement after the CTEs
+        "WITH" => words.find(|word| {
+            matches!(
+                *word,
+                "DELETE" | "UPDATE" | "INSERT" | "REPLACE" | "SELECT" | "VALUES"
+            )
+        }),
+        verb => Some(verb),
+    }
+}
+
+/// Finds the semicolons that end a statement.
+///
+/// Returns their offsets and whether the last token is one of them.
+fn statement_ends(tokens: &[Token<'_>]) -> (Vec<usize>, bool) {
+    let mut ends = Vec::new();
+    let mut complete = false;
+    // The words at the start of the current statement, to recognize CREATE TRIGGER
+    let mut leading: Vec<String> = Vec::new();
+    let mut in_trigger = false;
+    let mut depth = 0usize;
+    for token in tokens {
+        match token {
+            Token::Semicolon(offset) if depth == 0 => {
+                ends.push(*offset);
+                complete = true;
+                leading.clear();
+                in_trigger = false;
+            }
+            Token::Semicolon(_) => complete = false,
+            Token::Word(word) => {
+                complete = false;
+                let word = word.to_ascii_uppercase();
+                if leading.len() < 4 {
+                    leading.push(word.clone());
+                    in_trigger = in_trigger || starts_trigger(&leading);
+                }
+                if in_trigger {
+                    match word.as_str() {
+                        "BEGIN" | "CASE" => depth += 1,
+                        "END" => depth = depth.saturating_sub(1),
+                        _ => {}
+                    }
+                }
+            }
+            Token::Quoted(_) | Token::Open | Token::Close | Token::Dot | Token::Other => {
+                complete = false
+            }
+        }
+    }
+    (ends, complete)
+}
+
+/// Returns `true` if REPL input can run as it is: a dot-command, one of the commands that
+/// never needed a semicolon, or a complete statement.
+///
+/// # Arguments
+///
+/// * `input` - The input collected so far.
+///
+/// # Returns
+///
+/// `true` if the input should be run, `false` if the REPL should keep reading lines.
+pub fn is_complete_input(input: &str) -> bool {
+    let input = input.trim();
+    input.is_empty() || is_complete_command(input) || is_complete_statement(input)
+}
+
+fn is_complete_command(line: &str) -> bool {
+    // These commands don't need semicolons
+    matches!(
+        line.to_lowercase().as_str(),
+        "exit" | "quit" | "help" | "tables" | "clear" | "info"
+    ) || matches!(
+        classify(line),
+        StatementKind::DotCommand | StatementKind::Transaction
+    )
+}
+
+fn starts_trigger(words: &[String]) -> bool {
+    match words {
+        [create, trigger, ..] if create == "CREATE" && trigger == "TRIGGER" => true,
+        [create, temp, trigger, ..] => {
+            create == "CREATE" && (temp == "TEMP" || temp == "TEMPORARY") && trigger == "TRIGGER"
+        }
+        _ => false,
+    }
+}
+
+/// Splits `sql` into tokens, skipping whitespace and comments. Also returns `false` if the
+/// input ends inside a literal, quoted identifier, or block comment, in which case the
+/// unclosed part is left out.
+fn tokenize(sql: &str) -> (Vec<Token<'_>>, bool) {
+    let mut tokens = Vec::new();
+    let mut rest = sql;
+    while let Some(c) = rest.chars().next() {
+        let len = if c.is_whitespace() {
+            c.len_utf8()
+        } else if rest.starts_with("--") {
+            rest.find('\n').unwrap_or(rest.len())
+        } else if let Some(body) = rest.strip_prefix("/*") {
+            match body.find("*/") {
+                Some(end) => end + 4,
+                None => return (tokens, false),
+            }
+        } else if c == ';' {
+            tokens.push(Token::Semicolon(sql.len() - rest.len()));
+            1
+        } else if c == '\'' || c == '"' || c == '`' || c == '[' {
+            let close = if c == '[' { ']' } else { c };
+            let Some(len) = quoted_len(rest, close) else {
+                return (tokens, false);
+            };
+            tokens.push(Token::Quoted(&rest[..len]));
+            len
+        } else if c.is_alphanumeric() || c == '_' {
+            let len = rest
+                .find(|ch: char| !(ch.is_alphanumeric() || ch == '_' || ch == '$'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Word(&rest[..len]));
+            len
+        } else {
+            tokens.push(match c {
+                '(' => Token::Open,
+                ')' => Token::Close,
+                '.' => Token::Dot,
+                _ => Token::Other,
+            });
+            c.len_utf8()
+        };
+        rest = &rest[len..];
+    }
+    (tokens, true)
+}
+
+/// Returns the length of the quoted text at the start of `text`, where a doubled closing
+/// quote is an escaped one, or `None` if it isn't closed.
+fn quoted_len(text: &str, close: char) -> Option<usize> {
+    let mut chars = text.char_indices().skip(1).peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == close {
+            if close != ']' && chars.peek().map(|&(_, next)| next) == Some(close) {
+                chars.next();
+                continue;
+            }
+            return Some(i + c.len_utf8());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_complete_statement() {
+        assert!(is_complete_statement("SELECT 1;"));
+        assert!(is_complete_statement("SELECT 1; -- done"));
+        assert!(!is_complete_statement("SELECT 1"));
+        assert!(!is_complete_statement("SELECT 'a;"));
+        assert!(is_complete_statement("SELECT 'it''s;';"));
+        assert!(!is_complete_statement("SELECT 1 -- ;"));
+        assert!(!is_complete_statement("SELECT 1 /* ; */"));
+        assert!(!is_complete_statement("SELECT 1 /* ;"));
+        assert!(is_complete_statement("SELECT \"a;b\" FROM t;"));
+
+        let trigger = "CREATE TRIGGER t AFTER INSERT ON a BEGIN\n  UPDATE a SET x = 1;";
+        assert!(!is_complete_statement(trigger));
+        let with_case = format!("{}\n  SELECT CASE WHEN 1 THEN 2 END;", trigger);
+        assert!(!is_complete_statement(&with_case));
+        assert!(!is_complete_statement(&format!("{}\nEND", with_case)));
+        assert!(is_complete_statement(&format!("{}\nEND;", with_case)));
+        assert!(is_complete_statement(
+            "CREATE TEMP TRIGGER t AFTER DELETE ON a BEGIN DELETE FROM b; END; SELECT 1;"
+        ));
+
+        assert!(is_complete_input(".tables"));
+        assert!(is_complete_input("begin"));
+        assert!(!is_complete_input("SELECT *\nFROM t"));
+        // A DROP is SQL like any other and waits for its semicolon
+        assert!(!is_complete_input("DROP TABLE t"));
+        assert!(!is_complete_input("schema_version"));
+    }
+
+    #[test]
+    fn test_destructive_reason() {
+        let reason = destructive_reason;
+        assert_eq!(
+            reason("DELETE FROM users;").as_deref(),
+            Some("DELETE without a WHERE clause")
+        );
+        assert_eq!(reason("delete from users where id = 1;"), None);
+        assert_eq!(
+            reason("UPDATE users SET active = (SELECT 0 WHERE 1);").as_deref(),
+            Some("UPDATE without a WHERE clause")
+        );
+        assert_eq!(
+            reason("UPDATE users SET note = 'where' WHERE id = 2;"),
+            None
+        );
+        assert_eq!(
+            reason("WITH old AS (SELECT id FROM users WHERE age > 90) DELETE FROM logs;")
+                .as_deref(),
+            Some("DELETE without a WHERE clause")
+        );
+        assert_eq!(
+            reason("SELECT 1; drop table if exists users;").as_deref(),
+            Some("DROP TABLE")
+        );
+        assert_eq!(
+            reason("ALTER TABLE users ADD COLUMN age INTEGER;").as_deref(),
+            Some("ALTER TABLE")
+        );
+        assert_eq!(
+            reason("CREATE TRIGGER t AFTER INSERT ON a BEGIN DELETE FROM b; END;"),
+            None
+        );
+        assert_eq!(reason("SELECT * FROM users -- DELETE FROM users"), None);
+    }
+
+    #[test]
+    fn test_is_write_statement() {
+        assert!(is_write_statement("INSERT INTO a VALUES (1);"));
+        assert!(is_write_statement(
+            "insert into a values (1); update a set x = 2; replace into a values (3);"
+        ));
+        assert!(is_write_statement(
+            "WITH old AS (SELECT x FROM a) DELETE FROM b WHERE x IN old;"
+        ));
+        assert!(!is_write_statement("SELECT * FROM a;"));
+        assert!(!is_write_statement("INSERT INTO a VALUES (1); SELECT 1;"));
+        assert!(!is_write_statement("CREATE TABLE b AS SELECT * FROM a;"));
+        assert!(!is_write_statement("-- INSERT INTO a VALUES (1)"));
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify("select 1"), StatementKind::Query);
+        assert_eq!(
+            classify("WITH recent AS (DELETE FROM t) SELECT * FROM recent"),
+            StatementKind::Query
+        );
+        assert_eq!(
+            classify("-- totals\nWITH x AS (SELECT 1) INSERT INTO t SELECT * FROM x"),
+            StatementKind::Dml
+        );
+        assert_eq!(classify("VALUES (1), (2)"), StatementKind::Query);
+        assert_eq!(
+            classify("EXPLAIN QUERY PLAN SELECT 1"),
+            StatementKind::Query
+        );
+        assert_eq!(classify("create table t(a)"), StatementKind::Ddl);
+        assert_eq!(classify("SAVEPOINT a"), StatementKind::Transaction);
+        assert_eq!(
+            classify_with_verb("/* done */ commit;"),
+            (StatementKind::Transaction, Some("COMMIT".to_string()))
+        );
+        assert_eq!(
+            classify_with_verb("WITH x AS (SELECT 1) DELETE FROM t"),
+            (StatementKind::Dml, Some("DELETE".to_string()))
+        );
+        assert_eq!(
+            classify_with_verb(".tables"),
+            (StatementKind::DotCommand, None)
+        );
+        assert_eq!(classify("PRAGMA user_version"), StatementKind::Pragma);
+        assert_eq!(classify("  .tables"), StatementKind::DotCommand);
+        assert_eq!(classify("ATTACH 'x.db' AS x"), StatementKind::Other);
+        assert_eq!(classify(""), StatementKind::Other);
+    }
+
+    #[test]
+    fn test_parse_drop() {
+        let drop = parse_drop("drop table \"Order Items\";").unwrap();
+        assert_eq!(drop.kind, "TABLE");
+        assert!(!drop.if_exists);
+        assert_eq!(drop.name, "Order Items");
+
+        let drop = parse_drop("DROP INDEX IF EXISTS main.[Idx_Name]").unwrap();
+        assert_eq!(drop.kind, "INDEX");
+        assert!(drop.if_exists);
+        assert_eq!(drop.schema.as_deref(), Some("main"));
+        assert_eq!(drop.name, "Idx_Name");
+
+        assert_eq!(parse_drop("DROP VIEW `a``b`").unwrap().name, "a`b");
+        assert_eq!(
+            parse_drop("DROP TRIGGER CamelCase;").unwrap().name,
+            "CamelCase"
+        );
+        assert!(parse_drop("DROP users").is_none());
+        assert!(parse_drop("DROP TABLE a; DROP TABLE b;").is_none());
+        assert!(parse_drop("SELECT 1").is_none());
+    }
+
+    #[test]
+    fn test_split_statements() {
+        let script = "-- setup\nCREATE TABLE a (x);\n\nINSERT INTO a VALUES (';');;\n\
+                      CREATE TRIGGER t AFTER INSERT ON a BEGIN DELETE FROM a; END;\n\
+                      SELECT 1 -- no semicolon";
+        let statements: Vec<&str> = split_statements(script)
+            .into_iter()
+            .map(|(_, s)| s)
+            .collect();
+        assert_eq!(
+            statements,
+            vec![
+                "-- setup\nCREATE TABLE a (x);",
+                "INSERT INTO a VALUES (';');",
+                "CREATE TRIGGER t AFTER INSERT ON a BEGIN DELETE FROM a; END;",
+                "SELECT 1 -- no semicolon",
+            ]
+        );
+        let (offset, _) = split_statements(script)[1];
+        assert!(script[offset..].starts_with("INSERT"));
+        assert!(split_statements("  -- only a comment\n").is_empty());
+    }
+}
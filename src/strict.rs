@@ -0,0 +1,513 @@
+//! # Strict Tables
+//!
+//! This module helps move tables onto SQLite's `STRICT` typing, where a column only
+//! accepts values of its declared type instead of storing whatever it is given.
+//!
+//! ## Features:
+//! - **Type checking**: `type_mismatches` finds values whose storage class doesn't match
+//!   their column's declared type affinity, such as text in an `INTEGER` column.
+//! - **Migration**: SQLite can't make an existing table strict, so `strictify_statements`
+//!   generates the rebuild: a `STRICT` copy of the table with its declared types mapped
+//!   onto the strict ones, the rows, and the table's indexes and triggers.
+//! - **Preview**: The statements are generated first so they can be shown before being
+//!   applied, and are then applied in a single transaction.
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::ops::Range;
+
+use crate::db::table_columns;
+use crate::formats::quote_identifier;
+use crate::import::Affinity;
+
+/// The column types a `STRICT` table accepts.
+pub const STRICT_TYPES: &[&str] = &["INT", "INTEGER", "REAL", "TEXT", "BLOB", "ANY"];
+
+/// Words that start a table constraint rather than a column definition.
+const TABLE_CONSTRAINTS: &[&str] = &["CONSTRAINT", "PRIMARY", "UNIQUE", "CHECK", "FOREIGN"];
+
+/// Words that end a column's type name.
+const COLUMN_CONSTRAINTS: &[&str] = &[
+    "CONSTRAINT",
+    "PRIMARY",
+    "NOT",
+    "NULL",
+    "UNIQUE",
+    "CHECK",
+    "DEFAULT",
+    "COLLATE",
+    "REFERENCES",
+    "GENERATED",
+    "AS",
+];
+
+/// Values in a column whose storage class doesn't match the column's declared type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeMismatch {
+    pub table: String,
+    pub column: String,
+    /// The column's declared type.
+    pub declared: String,
+    /// The storage class of the values, as reported by `typeof()`.
+    pub stored: String,
+    /// How many rows hold a value of that storage class.
+    pub rows: i64,
+    /// One of the values, quoted as an SQL literal.
+    pub example: String,
+}
+
+/// Maps a declared column type onto one a `STRICT` table accepts.
+///
+/// Types that are already strict are kept, so `INTEGER PRIMARY KEY` stays a rowid alias.
+/// Other integer types become `INT` rather than `INTEGER` so that no column turns into a
+/// rowid alias, and types without a strict equivalent (such as `NUMERIC` or `DATE`, or
+/// no type at all) become `ANY`.
+///
+/// # Arguments
+///
+/// * `declared` - The declared type, e.g. `VARCHAR(255)`.
+///
+/// # Returns
+///
+/// One of [`STRICT_TYPES`].
+pub fn strict_column_type(declared: &str) -> &'static str {
+    let declared = declared.trim().to_uppercase();
+    if let Some(strict) = STRICT_TYPES.iter().find(|t| **t == declared) {
+        return strict;
+    }
+    match Affinity::from_declared_type(&declared) {
+        Affinity::Integer => "INT",
+        Affinity::Real => "REAL",
+        Affinity::Text => "TEXT",
+        Affinity::Blob if !declared.is_empty() => "BLOB",
+        _ => "ANY",
+    }
+}
+
+/// Finds values whose storage class doesn't match their column's declared type affinity.
+///
+/// SQLite converts values to a column's affinity when it can, and otherwise stores them
+/// as given, so an `INTEGER` column can quietly collect text. Columns without an
+/// affinity accept anything and are not checked; `NULL` is accepted everywhere.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `table` - The table to check.
+///
+/// # Returns
+///
+/// A `Result` containing one `TypeMismatch` per column and unexpected storage class, or
+/// an `Err` if the table does not exist.
+pub fn type_mismatches(conn: &Connection, table: &str) -> Result<Vec<TypeMismatch>> {
+    let columns = stored_columns(conn, table)?;
+    let quoted_table = quote_identifier(table);
+
+    let mut mismatches = Vec::new();
+    for (column, declared) in columns {
+        let accepted: &[&str] = match Affinity::from_declared_type(&declared) {
+            Affinity::Integer => &["integer"],
+            Affinity::Real | Affinity::Numeric => &["integer", "real"],
+            Affinity::Text => &["text"],
+            Affinity::Blob => continue,
+        };
+        let quoted = quote_identifier(&column);
+        let mut stmt = conn.prepare(&format!(
+            "SELECT typeof({col}) AS stored, COUNT(*), substr(quote(MIN({col})), 1, 40) \
+             FROM {table} WHERE {col} IS NOT NULL GROUP BY stored ORDER BY stored",
+            col = quoted,
+            table = quoted_table,
+        ))?;
+        let found = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<rusqlite::Result<Vec<(String, i64, String)>>>()
+            .with_context(|| format!("Failed to check the values of '{}.{}'", table, column))?;
+        for (stored, rows, example) in found {
+            if !accepted.contains(&stored.as_str()) {
+                mismatches.push(TypeMismatch {
+                    table: table.to_string(),
+                    column: column.clone(),
+                    declared: declared.clone(),
+                    stored,
+                    rows,
+                    example,
+                });
+            }
+        }
+    }
+    Ok(mismatches)
+}
+
+/// Generates the statements that rebuild a table as a `STRICT` table.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `table` - The table to rebuild.
+///
+/// # Returns
+///
+/// A `Result` containing the statements in the order they should run, or an `Err` if
+/// the table does not exist, is already strict, or is referenced by foreign keys while
+/// they are enforced (dropping it would fire their `ON DELETE` actions).
+pub fn strictify_statements(conn: &Connection, table: &str) -> Result<Vec<String>> {
+    let definition: Option<(String, String, bool)> = conn
+        .query_row(
+            "SELECT m.name, m.sql, l.strict FROM sqlite_master m \
+             JOIN pragma_table_list l ON l.schema = 'main' AND l.name = m.name \
+             WHERE m.type = 'table' AND l.type = 'table' AND m.name = ?1 COLLATE NOCASE",
+            params![table],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()
+        .with_context(|| format!("Failed to read the definition of table '{}'", table))?;
+    let Some((table, sql, strict)) = definition else {
+        anyhow::bail!("Table '{}' does not exist", table);
+    };
+    if strict {
+        anyhow::bail!("'{}' is already a STRICT table", table);
+    }
+
+    let foreign_keys: bool = conn.query_row("PRAGMA foreign_keys", [], |row| row.get(0))?;
+    if foreign_keys {
+        let referenced: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master m, pragma_foreign_key_list(m.name) f \
+             WHERE m.type = 'table' AND f.\"table\" = ?1 COLLATE NOCASE)",
+            params![table],
+            |row| row.get(0),
+        )?;
+        if referenced {
+            anyhow::bail!(
+                "Other tables have foreign keys to '{}'; run PRAGMA foreign_keys = OFF first so rebuilding it doesn't fire their ON DELETE actions",
+                table
+            );
+        }
+    }
+
+    let rebuilt = format!("{}_strictify", table);
+    let columns = stored_columns(conn, &table)?
+        .into_iter()
+        .map(|(name, _)| quote_identifier(&name))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut statements = vec![
+        strict_create_statement(&sql, &rebuilt)?,
+        format!(
+            "INSERT INTO {new} ({columns}) SELECT {columns} FROM {old}",
+            new = quote_identifier(&rebuilt),
+            columns = columns,
+            old = quote_identifier(&table),
+        ),
+        format!("DROP TABLE {}", quote_identifier(&table)),
+        format!(
+            "ALTER TABLE {} RENAME TO {}",
+            quote_identifier(&rebuilt),
+            quote_identifier(&table)
+        ),
+    ];
+
+    // Dropping the table drops its indexes and triggers, so they are created again
+    let mut stmt = conn.prepare(
+        "SELECT sql FROM sqlite_master WHERE tbl_name = ?1 AND type IN ('index', 'trigger') \
+         AND sql IS NOT NULL ORDER BY type = 'trigger', rowid",
+    )?;
+    let dependents = stmt
+        .query_map(params![table], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()
+        .with_context(|| format!("Failed to read the indexes and triggers of '{}'", table))?;
+    statements.extend(dependents);
+    Ok(statements)
+}
+
+/// Applies statements generated by [`strictify_statements`] in a single transaction.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `statements` - The statements to run.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` if every statement succeeded. If any fails, none of them
+/// take effect.
+pub fn apply_strictify_statements(conn: &Connection, statements: &[String]) -> Result<()> {
+    let legacy: bool = conn.query_row("PRAGMA legacy_alter_table", [], |row| row.get(0))?;
+    // Views and triggers naming the table would otherwise fail the rename, since the
+    // table they name is gone until the rebuilt one takes its place
+    conn.execute_batch("PRAGMA legacy_alter_table = ON")?;
+    let result = (|| -> Result<()> {
+        // A savepoint nests inside a transaction the REPL user may already have open
+        conn.execute_batch("SAVEPOINT strictify")?;
+        for statement in statements {
+            if let Err(e) = conn.execute_batch(statement) {
+                let _ = conn.execute_batch("ROLLBACK TO strictify; RELEASE strictify");
+                let hint = if statement.starts_with("INSERT") {
+                    "\nRun .typecheck to find the values that don't fit."
+                } else {
+                    ""
+                };
+                anyhow::bail!("Failed to run: {}\n{}{}", statement, e, hint);
+            }
+        }
+        conn.execute_batch("RELEASE strictify")
+            .context("Failed to commit the STRICT rebuild")
+    })();
+    if !legacy {
+        conn.execute_batch("PRAGMA legacy_alter_table = OFF")?;
+    }
+    result
+}
+
+/// Returns a table's stored (not generated) columns with their declared types.
+fn stored_columns(conn: &Connection, table: &str) -> Result<Vec<(String, String)>> {
+    let columns: Vec<(String, String)> = table_columns(conn, None, table)?
+        .into_iter()
+        .filter(|column| !column.generated)
+        .map(|column| (column.name, column.decl_type))
+        .collect();
+    if columns.is_empty() {
+        anyhow::bail!("Table '{}' does not exist", table);
+    }
+    Ok(columns)
+}
+
+/// Rewrites a `CREATE TABLE` statement to create a `STRICT` table named `name`, with each
+/// column's declared type replaced by its [`strict_column_type`].
+fn strict_create_statement(sql: &str, name: &str) -> Result<String> {
+    let tokens = lex(sql);
+    let word = |range: &Range<usize>| sql[range.clone()].to_uppercase();
+    let open = tokens
+        .iter()
+        .position(|(kind, _)| *kind == Kind::Open)
+        .context("Could not find the column definitions")?;
+
+    let mut depth = 0;
+    let mut definitions = Vec::new();
+    let mut definition_start = open + 1;
+    let mut close = None;
+    for (i, (kind, _)) in tokens.iter().enumerate().skip(open) {
+        match kind {
+            Kind::Open => depth += 1,
+            Kind::Close => {
+                depth -= 1;
+                if depth == 0 {
+                    definitions.push(definition_start..i);
+                    close = Some(i);
+                    break;
+                }
+            }
+            Kind::Comma if depth == 1 => {
+                definitions.push(definition_start..i);
+                definition_start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    let close = close.context("The column definitions are not closed")?;
+
+    let mut rewritten = format!("CREATE TABLE {} (", quote_identifier(name));
+    let mut copied = tokens[open].1.end;
+    for definition in definitions {
+        let tokens = &tokens[definition];
+        let Some((_, name)) = tokens.first() else {
+            continue;
+        };
+        if TABLE_CONSTRAINTS.contains(&word(name).as_str()) {
+            continue;
+        }
+        // The type is every word after the name up to the first constraint, plus a size
+        let mut span = name.end..name.end;
+        let mut i = 1;
+        while let Some((Kind::Word, range)) = tokens.get(i) {
+            if COLUMN_CONSTRAINTS.contains(&word(range).as_str()) {
+                break;
+            }
+            if span.is_empty() {
+                span.start = range.start;
+            }
+            span.end = range.end;
+            i += 1;
+        }
+        if !span.is_empty() && matches!(tokens.get(i), Some((Kind::Open, _))) {
+            let mut depth = 0;
+            for (kind, range) in &tokens[i..] {
+                match kind {
+                    Kind::Open => depth += 1,
+                    Kind::Close => depth -= 1,
+                    _ => {}
+                }
+                if depth == 0 {
+                    span.end = range.end;
+                    break;
+                }
+            }
+        }
+        rewritten.push_str(&sql[copied..span.start]);
+        if span.is_empty() {
+            rewritten.push(' ');
+        }
+        rewritten.push_str(strict_column_type(&sql[span.clone()]));
+        copied = span.end;
+    }
+    rewritten.push_str(&sql[copied..tokens[close].1.end]);
+
+    let options = sql[tokens[close].1.end..].trim();
+    if options.is_empty() {
+        rewritten.push_str(" STRICT");
+    } else {
+        rewritten.push_str(&format!(" {}, STRICT", options));
+    }
+    Ok(rewritten)
+}
+
+/// A token of a `CREATE TABLE` statement, as far as finding column types is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Word,
+    Quoted,
+    Open,
+    Close,
+    Comma,
+    Other,
+}
+
+/// Splits SQL into tokens with their byte ranges, skipping whitespace and comments.
+fn lex(sql: &str) -> Vec<(Kind, Range<usize>)> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    while let Some(c) = sql[start..].chars().next() {
+        let rest = &sql[start..];
+        let (kind, len) = if c.is_whitespace() {
+            (None, c.len_utf8())
+        } else if rest.starts_with("--") {
+            (None, rest.find('\n').unwrap_or(rest.len()))
+        } else if let Some(body) = rest.strip_prefix("/*") {
+            (None, body.find("*/").map_or(rest.len(), |end| end + 4))
+        } else if matches!(c, '\'' | '"' | '`' | '[') {
+            let close = if c == '[' { ']' } else { c };
+            let mut len = rest.len();
+            let mut chars = rest.char_indices().skip(1).peekable();
+            while let Some((i, ch)) = chars.next() {
+                if ch == close {
+                    if close != ']' && chars.peek().map(|&(_, next)| next) == Some(close) {
+                        chars.next();
+                        continue;
+                    }
+                    len = i + 1;
+                    break;
+                }
+            }
+            (Some(Kind::Quoted), len)
+        } else if c.is_alphanumeric() || c == '_' {
+            let len = rest
+                .find(|ch: char| !(ch.is_alphanumeric() || ch == '_' || ch == '$'))
+                .unwrap_or(rest.len());
+            (Some(Kind::Word), len)
+        } else {
+            let kind = match c {
+                '(' => Kind::Open,
+                ')' => Kind::Close,
+                ',' => Kind::Comma,
+                _ => Kind::Other,
+            };
+            (Some(kind), c.len_utf8())
+        };
+        if let Some(kind) = kind {
+            tokens.push((kind, start..start + len));
+        }
+        start += len;
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strict_column_type() {
+        assert_eq!(strict_column_type("INTEGER"), "INTEGER");
+        assert_eq!(strict_column_type("bigint"), "INT");
+        assert_eq!(strict_column_type("VARCHAR(255)"), "TEXT");
+        assert_eq!(strict_column_type("DOUBLE PRECISION"), "REAL");
+        assert_eq!(strict_column_type("blob"), "BLOB");
+        assert_eq!(strict_column_type("DECIMAL(10, 2)"), "ANY");
+        assert_eq!(strict_column_type(""), "ANY");
+    }
+
+    #[test]
+    fn test_strictify_table() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE people (
+                 id INTEGER PRIMARY KEY,
+                 name VARCHAR(50) NOT NULL, -- a comment, with a comma
+                 \"age in years\" bigint CHECK (\"age in years\" >= 0),
+                 balance DECIMAL(10, 2) DEFAULT 0,
+                 extra,
+                 UNIQUE (name)
+             );
+             CREATE INDEX people_age ON people (\"age in years\");
+             CREATE VIEW adults AS SELECT * FROM people WHERE \"age in years\" >= 18;
+             INSERT INTO people (name, \"age in years\", balance) VALUES ('Ada', 36, 1.5), ('Bob', 'old', 2);",
+        )
+        .unwrap();
+
+        let mismatches = type_mismatches(&conn, "people").unwrap();
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].column, "age in years");
+        assert_eq!(mismatches[0].stored, "text");
+        assert_eq!(mismatches[0].example, "'old'");
+
+        // The text age can't be stored strictly, so nothing changes
+        let statements = strictify_statements(&conn, "people").unwrap();
+        assert!(statements[0].contains("name TEXT NOT NULL"));
+        assert!(statements[0].contains("\"age in years\" INT CHECK"));
+        assert!(statements[0].contains("balance ANY DEFAULT 0"));
+        assert!(statements[0].contains("extra ANY"));
+        assert!(statements[0].ends_with(") STRICT"));
+        let err = apply_strictify_statements(&conn, &statements).unwrap_err();
+        assert!(format!("{:#}", err).contains(".typecheck"));
+        assert!(!conn
+            .query_row(
+                "SELECT strict FROM pragma_table_list WHERE name = 'people'",
+                [],
+                |row| row.get::<_, bool>(0)
+            )
+            .unwrap());
+
+        conn.execute(
+            "UPDATE people SET \"age in years\" = 70 WHERE name = 'Bob'",
+            [],
+        )
+        .unwrap();
+        assert!(type_mismatches(&conn, "people").unwrap().is_empty());
+        apply_strictify_statements(&conn, &statements).unwrap();
+        let (strict, adults): (bool, i64) = conn
+            .query_row(
+                "SELECT (SELECT strict FROM pragma_table_list WHERE name = 'people'), \
+                 (SELECT COUNT(*) FROM adults)",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert!(strict);
+        assert_eq!(adults, 2);
+        assert!(conn
+            .execute(
+                "INSERT INTO people (name, \"age in years\") VALUES ('Cy', 'young')",
+                []
+            )
+            .is_err());
+        let index: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE name = 'people_age'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(index, 1);
+
+        assert!(strictify_statements(&conn, "people").is_err());
+    }
+}
@@ -0,0 +1,406 @@
+//! # Database Templates
+//!
+//! This module creates new databases from a template instead of an empty file, so teams
+//! can start every project from the same schema rather than re-running DDL scripts.
+//!
+//! ## Features:
+//! - **Database templates**: Any existing SQLite file can be a template. Its tables,
+//!   indexes, views, and triggers are recreated in the new database, along with its
+//!   `user_version` and `application_id`.
+//! - **Saved templates**: Files in `~/.vapor/templates/` can be used by name, so
+//!   `init --template service` uses `~/.vapor/templates/service.db`.
+//! - **Built-in presets**: A few common schemas (`users`, `blog`, `inventory`) are
+//!   available without any files.
+//! - **Seed data**: Optionally copies the template's rows, or a preset's sample rows,
+//!   into the new database.
+
+use anyhow::{Context, Result};
+use rusqlite::{Connection, OpenFlags};
+use std::path::{Path, PathBuf};
+
+use crate::config::get_templates_dir;
+use crate::db::{database_file_name, verify_database_integrity};
+
+/// A schema that ships with vapor-cli.
+#[derive(Debug, Clone, Copy)]
+pub struct Preset {
+    /// The name used with `--template`.
+    pub name: &'static str,
+    /// A one-line description.
+    pub description: &'static str,
+    /// The DDL that creates the schema.
+    pub schema: &'static str,
+    /// Sample rows inserted when seed data is requested.
+    pub seed: &'static str,
+}
+
+/// The built-in presets.
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "users",
+        description: "User accounts with sessions",
+        schema: "
+            CREATE TABLE users (
+                id INTEGER PRIMARY KEY,
+                email TEXT NOT NULL UNIQUE,
+                name TEXT NOT NULL,
+                created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            );
+            CREATE TABLE sessions (
+                id TEXT PRIMARY KEY,
+                user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                expires_at TEXT NOT NULL
+            );
+            CREATE INDEX idx_sessions_user_id ON sessions(user_id);",
+        seed: "
+            INSERT INTO users (email, name) VALUES
+                ('ada@example.com', 'Ada Lovelace'),
+                ('grace@example.com', 'Grace Hopper');",
+    },
+    Preset {
+        name: "blog",
+        description: "Authors, posts, comments, and tags",
+        schema: "
+            CREATE TABLE authors (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                email TEXT UNIQUE
+            );
+            CREATE TABLE posts (
+                id INTEGER PRIMARY KEY,
+                author_id INTEGER NOT NULL REFERENCES authors(id),
+                title TEXT NOT NULL,
+                body TEXT NOT NULL DEFAULT '',
+                published_at TEXT
+            );
+            CREATE TABLE comments (
+                id INTEGER PRIMARY KEY,
+                post_id INTEGER NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
+                author_name TEXT NOT NULL,
+                body TEXT NOT NULL,
+                created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            );
+            CREATE TABLE tags (id INTEGER PRIMARY KEY, name TEXT NOT NULL UNIQUE);
+            CREATE TABLE post_tags (
+                post_id INTEGER NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
+                tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+                PRIMARY KEY (post_id, tag_id)
+            );
+            CREATE INDEX idx_posts_author_id ON posts(author_id);
+            CREATE INDEX idx_comments_post_id ON comments(post_id);",
+        seed: "
+            INSERT INTO authors (name, email) VALUES ('Ada Lovelace', 'ada@example.com');
+            INSERT INTO posts (author_id, title, body, published_at)
+                VALUES (1, 'Hello, world', 'The first post.', CURRENT_TIMESTAMP);
+            INSERT INTO tags (name) VALUES ('news');
+            INSERT INTO post_tags VALUES (1, 1);",
+    },
+    Preset {
+        name: "inventory",
+        description: "Products, customers, and orders",
+        schema: "
+            CREATE TABLE products (
+                id INTEGER PRIMARY KEY,
+                sku TEXT NOT NULL UNIQUE,
+                name TEXT NOT NULL,
+                price_cents INTEGER NOT NULL CHECK (price_cents >= 0),
+                stock INTEGER NOT NULL DEFAULT 0
+            );
+            CREATE TABLE customers (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                email TEXT UNIQUE
+            );
+            CREATE TABLE orders (
+                id INTEGER PRIMARY KEY,
+                customer_id INTEGER NOT NULL REFERENCES customers(id),
+                status TEXT NOT NULL DEFAULT 'pending',
+                created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            );
+            CREATE TABLE order_items (
+                order_id INTEGER NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+                product_id INTEGER NOT NULL REFERENCES products(id),
+                quantity INTEGER NOT NULL CHECK (quantity > 0),
+                price_cents INTEGER NOT NULL,
+                PRIMARY KEY (order_id, product_id)
+            );
+            CREATE INDEX idx_orders_customer_id ON orders(customer_id);",
+        seed: "
+            INSERT INTO products (sku, name, price_cents, stock) VALUES
+                ('WID-1', 'Widget', 499, 100),
+                ('GAD-1', 'Gadget', 1299, 25);
+            INSERT INTO customers (name, email) VALUES ('Ada Lovelace', 'ada@example.com');
+            INSERT INTO orders (customer_id, status) VALUES (1, 'shipped');
+            INSERT INTO order_items VALUES (1, 1, 2, 499);",
+    },
+];
+
+/// Where a template's schema comes from.
+enum TemplateSource {
+    Database(PathBuf),
+    Preset(&'static Preset),
+}
+
+/// Creates a new database from a template database file or a preset.
+///
+/// `template` is resolved in order as a path to a database file, the name of a file in
+/// `~/.vapor/templates/` (with or without `.db`), and finally a built-in preset name.
+///
+/// # Arguments
+///
+/// * `name` - The name of the database file to create (`.db` is appended if missing).
+/// * `template` - A template database path, saved template name, or preset name.
+/// * `with_data` - Also copy the template's rows (or the preset's sample rows).
+///
+/// # Returns
+///
+/// A `Result` containing the path of the created database, or an `Err` if the database
+/// already exists, the template cannot be found, or its schema cannot be recreated.
+pub fn init_database_from_template(name: &str, template: &str, with_data: bool) -> Result<String> {
+    let db_path = database_file_name(name);
+    if Path::new(&db_path).exists() {
+        anyhow::bail!(
+            "Database '{}' already exists; templates only create new databases",
+            db_path
+        );
+    }
+    let source = resolve_template(template)?;
+
+    if let Some(parent) = Path::new(&db_path).parent() {
+        if parent != Path::new("") && !parent.exists() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory {:?}", parent))?;
+        }
+    }
+
+    let result = (|| -> Result<()> {
+        let mut conn = Connection::open(&db_path)
+            .with_context(|| format!("Failed to create database '{}'", db_path))?;
+        match &source {
+            TemplateSource::Database(path) => copy_template(&mut conn, path, with_data)?,
+            TemplateSource::Preset(preset) => {
+                let tx = conn.transaction()?;
+                tx.execute_batch(preset.schema)?;
+                if with_data {
+                    tx.execute_batch(preset.seed)?;
+                }
+                tx.commit()?;
+            }
+        }
+        drop(conn);
+        verify_database_integrity(&db_path)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&db_path);
+    }
+    result.with_context(|| {
+        format!(
+            "Failed to create '{}' from template '{}'",
+            db_path, template
+        )
+    })?;
+
+    println!(
+        "Successfully created database: {} (template '{}')",
+        db_path, template
+    );
+    Ok(db_path)
+}
+
+/// Returns the names of the saved templates in `~/.vapor/templates/`, sorted.
+///
+/// # Returns
+///
+/// A `Result` containing the template names without their `.db` extension.
+pub fn saved_template_names() -> Result<Vec<String>> {
+    let dir = get_templates_dir()?;
+    let mut names = Vec::new();
+    if let Ok(entries) = std::fs::read_dir(&dir) {
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "db") {
+                if let Some(stem) = path.file_stem() {
+                    names.push(stem.to_string_lossy().to_string());
+                }
+            }
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+fn resolve_template(template: &str) -> Result<TemplateSource> {
+    if Path::new(template).is_file() {
+        return Ok(TemplateSource::Database(PathBuf::from(template)));
+    }
+
+    if let Ok(dir) = get_templates_dir() {
+        let saved = dir.join(database_file_name(template));
+        if saved.is_file() {
+            return Ok(TemplateSource::Database(saved));
+        }
+    }
+
+    if let Some(preset) = PRESETS.iter().find(|p| p.name == template) {
+        return Ok(TemplateSource::Preset(preset));
+    }
+
+    let mut available: Vec<String> = PRESETS.iter().map(|p| p.name.to_string()).collect();
+    available.extend(saved_template_names().unwrap_or_default());
+    anyhow::bail!(
+        "Template '{}' is not a database file, a saved template, or a preset. Available: {}",
+        template,
+        available.join(", ")
+    )
+}
+
+/// Recreates the schema of a template database, and optionally its rows.
+///
+/// Tables are created first, then rows are copied, and indexes, views, and triggers are
+/// created last so triggers don't fire while the seed data is copied.
+fn copy_template(conn: &mut Connection, template: &Path, with_data: bool) -> Result<()> {
+    let source = Connection::open_with_flags(template, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("Failed to open template '{}'", template.display()))?;
+
+    let mut stmt = source.prepare(
+        "SELECT type, name, sql FROM sqlite_master
+         WHERE sql IS NOT NULL AND name NOT LIKE 'sqlite_%'
+         ORDER BY CASE type WHEN 'table' THEN 0 WHEN 'index' THEN 1 WHEN 'view' THEN 2 ELSE 3 END, rowid",
+    )?;
+    let objects = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .with_context(|| format!("Failed to read the schema of '{}'", template.display()))?;
+    let user_version: i64 = source.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    let application_id: i64 = source.query_row("PRAGMA application_id", [], |row| row.get(0))?;
+    drop(stmt);
+    drop(source);
+
+    // Virtual tables create their own shadow tables, which must not be created twice
+    let virtual_tables: Vec<&str> = objects
+        .iter()
+        .filter(|(kind, _, sql)| {
+            kind == "table" && sql.to_uppercase().starts_with("CREATE VIRTUAL TABLE")
+        })
+        .map(|(_, name, _)| name.as_str())
+        .collect();
+    let is_shadow = |name: &str| {
+        virtual_tables.iter().any(|vt| {
+            name.len() > vt.len() && name.starts_with(vt) && name[vt.len()..].starts_with('_')
+        })
+    };
+    let tables: Vec<&str> = objects
+        .iter()
+        .filter(|(kind, name, _)| kind == "table" && !is_shadow(name))
+        .map(|(_, name, _)| name.as_str())
+        .collect();
+
+    // Databases can't be attached inside a transaction
+    if with_data {
+        conn.execute(
+            "ATTACH DATABASE ?1 AS template",
+            [template.to_string_lossy().as_ref()],
+        )?;
+    }
+    let tx = conn.transaction()?;
+    for (kind, name, sql) in &objects {
+        if kind == "table" && !is_shadow(name) {
+            tx.execute_batch(sql)
+                .with_context(|| format!("Failed to create table '{}'", name))?;
+        }
+    }
+
+    if with_data {
+        for table in &tables {
+            let quoted = format!("\"{}\"", table.replace('"', "\"\""));
+            let copied = tx
+                .execute(
+                    &format!("INSERT INTO main.{0} SELECT * FROM template.{0}", quoted),
+                    [],
+                )
+                .with_context(|| format!("Failed to copy rows of table '{}'", table))?;
+            println!("  {}: {} rows", table, copied);
+        }
+    }
+
+    for (kind, name, sql) in &objects {
+        if kind != "table" {
+            tx.execute_batch(sql)
+                .with_context(|| format!("Failed to create {} '{}'", kind, name))?;
+        }
+    }
+    tx.execute_batch(&format!(
+        "PRAGMA user_version = {}; PRAGMA application_id = {};",
+        user_version, application_id
+    ))?;
+    tx.commit()?;
+
+    if with_data {
+        conn.execute_batch("DETACH DATABASE template")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_init_from_template_database_and_preset() {
+        let dir = tempfile::tempdir().unwrap();
+        let template = dir.path().join("template.db");
+        let conn = Connection::open(&template).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE items (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+             CREATE TABLE audit (item_id INTEGER);
+             CREATE TRIGGER log_item AFTER INSERT ON items BEGIN INSERT INTO audit VALUES (new.id); END;
+             CREATE VIEW item_names AS SELECT name FROM items;
+             INSERT INTO items (name) VALUES ('seed');
+             PRAGMA user_version = 7;",
+        )
+        .unwrap();
+        drop(conn);
+
+        let count = |conn: &Connection, sql: &str| -> i64 {
+            conn.query_row(sql, [], |row| row.get(0)).unwrap()
+        };
+
+        let empty = dir.path().join("empty");
+        let path =
+            init_database_from_template(empty.to_str().unwrap(), template.to_str().unwrap(), false)
+                .unwrap();
+        let conn = Connection::open(&path).unwrap();
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM items"), 0);
+        assert_eq!(count(&conn, "PRAGMA user_version"), 7);
+        conn.execute("INSERT INTO items (name) VALUES ('x')", [])
+            .unwrap();
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM audit"), 1);
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM item_names"), 1);
+
+        let seeded = dir.path().join("seeded.db");
+        init_database_from_template(seeded.to_str().unwrap(), template.to_str().unwrap(), true)
+            .unwrap();
+        let conn = Connection::open(&seeded).unwrap();
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM items"), 1);
+        // Only the template's audit row: the trigger is created after the rows are copied
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM audit"), 1);
+
+        let blog = dir.path().join("blog.db");
+        init_database_from_template(blog.to_str().unwrap(), "blog", true).unwrap();
+        let conn = Connection::open(&blog).unwrap();
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM posts"), 1);
+
+        assert!(init_database_from_template(blog.to_str().unwrap(), "blog", false).is_err());
+        let missing = dir.path().join("missing.db");
+        assert!(
+            init_database_from_template(missing.to_str().unwrap(), "no-such-template", false)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,172 @@
+//! # Timestamp Columns
+//!
+//! This module scaffolds `created_at` and `updated_at` columns on an existing table, the
+//! DDL that is otherwise written by hand (and easily gotten wrong) for every new table.
+//!
+//! ## Features:
+//! - **Columns**: Adds whichever of `created_at` and `updated_at` the table is missing,
+//!   and fills them in for existing rows.
+//! - **Defaults**: SQLite can't add a column with a `CURRENT_TIMESTAMP` default, so an
+//!   insert trigger stamps both columns when a row is inserted without them.
+//! - **Update trigger**: Sets `updated_at` whenever a row changes, unless the statement
+//!   set it explicitly.
+//! - **Preview**: The statements are generated first so they can be shown before being
+//!   applied, and are then applied in a single transaction.
+
+use anyhow::{Context, Result};
+use rusqlite::Connection;
+
+use crate::formats::quote_identifier;
+use crate::softdelete::rowid_table_columns;
+
+/// The column stamped when a row is inserted.
+pub const CREATED_AT_COLUMN: &str = "created_at";
+
+/// The column stamped whenever a row changes.
+pub const UPDATED_AT_COLUMN: &str = "updated_at";
+
+/// Generates the statements that add timestamp columns and triggers to a table.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `table` - The table to add timestamps to. It must be a rowid table.
+///
+/// # Returns
+///
+/// A `Result` containing the statements in the order they should run. Running them on a
+/// table that already has timestamps recreates its triggers.
+pub fn timestamp_statements(conn: &Connection, table: &str) -> Result<Vec<String>> {
+    let columns = rowid_table_columns(conn, table, "Timestamp triggers")?;
+    let quoted = quote_identifier(table);
+    let created = quote_identifier(CREATED_AT_COLUMN);
+    let updated = quote_identifier(UPDATED_AT_COLUMN);
+    let insert_trigger = quote_identifier(&format!("{}_created_at", table));
+    let update_trigger = quote_identifier(&format!("{}_updated_at", table));
+
+    let mut statements = Vec::new();
+    for column in [CREATED_AT_COLUMN, UPDATED_AT_COLUMN] {
+        if !columns.iter().any(|c| c == column) {
+            statements.push(format!(
+                "ALTER TABLE {} ADD COLUMN {} TEXT",
+                quoted,
+                quote_identifier(column)
+            ));
+        }
+    }
+    statements.push(format!(
+        "UPDATE {table} SET {created} = COALESCE({created}, CURRENT_TIMESTAMP), \
+         {updated} = COALESCE({updated}, {created}, CURRENT_TIMESTAMP) \
+         WHERE {created} IS NULL OR {updated} IS NULL",
+        table = quoted,
+        created = created,
+        updated = updated,
+    ));
+    statements.push(format!("DROP TRIGGER IF EXISTS {}", insert_trigger));
+    statements.push(format!(
+        "CREATE TRIGGER {trigger} AFTER INSERT ON {table}\n\
+         WHEN NEW.{created} IS NULL OR NEW.{updated} IS NULL\n\
+         BEGIN\n    \
+             UPDATE {table} SET {created} = COALESCE(NEW.{created}, CURRENT_TIMESTAMP),\n        \
+             {updated} = COALESCE(NEW.{updated}, NEW.{created}, CURRENT_TIMESTAMP)\n    \
+             WHERE rowid = NEW.rowid;\n\
+         END",
+        trigger = insert_trigger,
+        table = quoted,
+        created = created,
+        updated = updated,
+    ));
+    statements.push(format!("DROP TRIGGER IF EXISTS {}", update_trigger));
+    statements.push(format!(
+        "CREATE TRIGGER {trigger} AFTER UPDATE ON {table}\n\
+         WHEN NEW.{updated} IS OLD.{updated}\n\
+         BEGIN\n    \
+             UPDATE {table} SET {updated} = CURRENT_TIMESTAMP WHERE rowid = NEW.rowid;\n\
+         END",
+        trigger = update_trigger,
+        table = quoted,
+        updated = updated,
+    ));
+    Ok(statements)
+}
+
+/// Applies statements generated by [`timestamp_statements`] in a single transaction.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `statements` - The statements to run.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` if every statement succeeded. If any fails, none of them
+/// take effect.
+pub fn apply_timestamp_statements(conn: &Connection, statements: &[String]) -> Result<()> {
+    // A savepoint nests inside a transaction the REPL user may already have open
+    conn.execute_batch("SAVEPOINT timestamps")?;
+    for statement in statements {
+        if let Err(e) = conn.execute_batch(statement) {
+            let _ = conn.execute_batch("ROLLBACK TO timestamps; RELEASE timestamps");
+            return Err(e).with_context(|| format!("Failed to run: {}", statement));
+        }
+    }
+    conn.execute_batch("RELEASE timestamps")
+        .context("Failed to commit the timestamp changes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamp_triggers() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE notes (id INTEGER PRIMARY KEY, body TEXT);
+             INSERT INTO notes (body) VALUES ('old');",
+        )
+        .unwrap();
+
+        let statements = timestamp_statements(&conn, "notes").unwrap();
+        assert!(statements[0].contains("ADD COLUMN \"created_at\""));
+        apply_timestamp_statements(&conn, &statements).unwrap();
+
+        conn.execute_batch(
+            "INSERT INTO notes (body) VALUES ('new');
+             INSERT INTO notes (body, created_at, updated_at) VALUES ('given', '2000-01-01', '2000-01-01');
+             UPDATE notes SET created_at = '1999-01-01', updated_at = '1999-01-01' WHERE id = 1;",
+        )
+        .unwrap();
+        let stamps: Vec<(Option<String>, Option<String>)> = conn
+            .prepare("SELECT created_at, updated_at FROM notes ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            stamps[0],
+            (Some("1999-01-01".into()), Some("1999-01-01".into()))
+        );
+        assert!(stamps[1].0.is_some() && stamps[1].1.is_some());
+        assert_eq!(
+            stamps[2],
+            (Some("2000-01-01".into()), Some("2000-01-01".into()))
+        );
+
+        // A plain update refreshes updated_at
+        conn.execute("UPDATE notes SET body = 'edited' WHERE id = 3", [])
+            .unwrap();
+        let updated: String = conn
+            .query_row("SELECT updated_at FROM notes WHERE id = 3", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_ne!(updated, "2000-01-01");
+
+        // Running it again only recreates the triggers
+        let again = timestamp_statements(&conn, "notes").unwrap();
+        assert!(!again.iter().any(|s| s.contains("ADD COLUMN")));
+        apply_timestamp_statements(&conn, &again).unwrap();
+    }
+}
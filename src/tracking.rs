@@ -0,0 +1,454 @@
+//! # Table Change Tracking
+//!
+//! This module finds out when each table last changed, which helps work out which tables
+//! an application actually writes to when its code isn't at hand. Tracking is opt-in and
+//! comes in two forms, which `last_changes` merges for `.tables --verbose`.
+//!
+//! ## Features:
+//! - **Polling**: While polling is on, the REPL reads `PRAGMA data_version` (which moves
+//!   when another connection commits) and the session's own change count before each
+//!   prompt. When either moved, every table's row count and largest rowid are compared
+//!   with the last snapshot, and the tables that differ are stamped with the current
+//!   time. Nothing is written to the database, but updates that change neither number
+//!   go unnoticed, and changes are only seen while the REPL is open.
+//! - **Triggers**: `install_change_triggers` adds insert, update, and delete triggers that
+//!   record every write in `_vapor_table_changes`, whichever program makes it and whether
+//!   or not vapor-cli is running. `remove_change_triggers` takes them out again.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use prettytable::{row, Table};
+use rusqlite::Connection;
+
+use crate::db::{get_all_table_names, get_table_names, split_table_name};
+use crate::formats::quote_identifier;
+
+/// The table the change triggers record writes in.
+pub const CHANGES_TABLE: &str = "_vapor_table_changes";
+
+/// The prefix of the change triggers' names, so they can be found and removed.
+const TRIGGER_PREFIX: &str = "_vapor_changed_";
+
+/// When a table last changed, and how that is known.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableChange {
+    /// The local time of the change, as `YYYY-MM-DD HH:MM:SS`.
+    pub changed_at: String,
+    /// The rows written since the triggers were installed, or `None` for a change seen by
+    /// polling.
+    pub writes: Option<i64>,
+}
+
+/// A table's row count and largest rowid, compared between polls.
+type TableSignature = (i64, Option<i64>);
+
+/// The state of polling: the counters last read and each table's last signature.
+struct ChangePoller {
+    data_version: i64,
+    total_changes: i64,
+    signatures: HashMap<String, TableSignature>,
+    last_changed: HashMap<String, DateTime<Local>>,
+}
+
+fn poller() -> &'static Mutex<Option<ChangePoller>> {
+    static POLLER: OnceLock<Mutex<Option<ChangePoller>>> = OnceLock::new();
+    POLLER.get_or_init(|| Mutex::new(None))
+}
+
+fn counters(conn: &Connection) -> Result<(i64, i64)> {
+    conn.query_row(
+        "SELECT (SELECT data_version FROM pragma_data_version), total_changes()",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .context("Failed to read the database's change counters")
+}
+
+fn signatures(conn: &Connection) -> Result<HashMap<String, TableSignature>> {
+    let mut signatures = HashMap::new();
+    for name in get_all_table_names(conn)? {
+        let (schema, table) = split_table_name(conn, &name)?;
+        let table = format!("{}.{}", quote_identifier(&schema), quote_identifier(&table));
+        // WITHOUT ROWID tables have no rowid, so only their row count is compared
+        let signature = conn
+            .query_row(
+                &format!("SELECT COUNT(*), MAX(rowid) FROM {}", table),
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .or_else(|_| {
+                conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                    Ok((row.get(0)?, None))
+                })
+            })?;
+        signatures.insert(name, signature);
+    }
+    Ok(signatures)
+}
+
+/// Starts polling for table changes, taking the current state of every table as the
+/// baseline. Starting again resets the baseline and forgets the changes seen so far.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` on success, or an `Err` if a table couldn't be read.
+pub fn start_polling(conn: &Connection) -> Result<()> {
+    let (data_version, total_changes) = counters(conn)?;
+    let state = ChangePoller {
+        data_version,
+        total_changes,
+        signatures: signatures(conn)?,
+        last_changed: HashMap::new(),
+    };
+    *poller().lock().unwrap() = Some(state);
+    Ok(())
+}
+
+/// Stops polling and forgets the changes it saw.
+pub fn stop_polling() {
+    *poller().lock().unwrap() = None;
+}
+
+/// Whether polling is on.
+pub fn is_polling() -> bool {
+    poller().lock().unwrap().is_some()
+}
+
+/// Checks for table changes since the last poll, if polling is on.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+///
+/// # Returns
+///
+/// A `Result` containing the tables that changed, which is empty when polling is off or
+/// nothing changed.
+pub fn poll_changes(conn: &Connection) -> Result<Vec<String>> {
+    let mut guard = poller().lock().unwrap();
+    let Some(state) = guard.as_mut() else {
+        return Ok(Vec::new());
+    };
+    let (data_version, total_changes) = counters(conn)?;
+    if (data_version, total_changes) == (state.data_version, state.total_changes) {
+        return Ok(Vec::new());
+    }
+    let now = Local::now();
+    let current = signatures(conn)?;
+    state.data_version = data_version;
+    state.total_changes = total_changes;
+    let mut changed: Vec<String> = current
+        .iter()
+        .filter(|(name, signature)| state.signatures.get(*name) != Some(*signature))
+        .map(|(name, _)| name.clone())
+        .collect();
+    changed.sort();
+    for name in &changed {
+        state.last_changed.insert(name.clone(), now);
+    }
+    state.signatures = current;
+    Ok(changed)
+}
+
+/// Generates the statements that create the changes table and the change triggers on
+/// the given tables of the main database.
+///
+/// # Arguments
+///
+/// * `tables` - The tables to track.
+///
+/// # Returns
+///
+/// The statements in the order they should run. Running them again recreates the
+/// triggers and keeps the changes recorded so far.
+pub fn change_trigger_statements(tables: &[String]) -> Vec<String> {
+    let changes = quote_identifier(CHANGES_TABLE);
+    let mut statements = vec![format!(
+        "CREATE TABLE IF NOT EXISTS {} (\n    \
+             table_name TEXT PRIMARY KEY,\n    \
+             changed_at TEXT NOT NULL,\n    \
+             writes INTEGER NOT NULL DEFAULT 0\n\
+         )",
+        changes
+    )];
+    for table in tables {
+        for event in ["insert", "update", "delete"] {
+            let trigger = quote_identifier(&format!("{}{}_{}", TRIGGER_PREFIX, table, event));
+            statements.push(format!("DROP TRIGGER IF EXISTS {}", trigger));
+            statements.push(format!(
+                "CREATE TRIGGER {trigger} AFTER {event} ON {table}\n\
+                 BEGIN\n    \
+                     INSERT INTO {changes} (table_name, changed_at, writes)\n    \
+                     VALUES ({name}, datetime('now', 'localtime'), 1)\n    \
+                     ON CONFLICT (table_name) DO UPDATE\n    \
+                     SET changed_at = excluded.changed_at, writes = writes + 1;\n\
+                 END",
+                trigger = trigger,
+                event = event.to_uppercase(),
+                table = quote_identifier(table),
+                changes = changes,
+                name = quote_literal(table),
+            ));
+        }
+    }
+    statements
+}
+
+fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// The tables of the main database that change triggers can be installed on, which is all
+/// of them except the changes table.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+///
+/// # Returns
+///
+/// A `Result` containing the table names sorted alphabetically.
+pub fn trackable_tables(conn: &Connection) -> Result<Vec<String>> {
+    Ok(get_table_names(conn)?
+        .into_iter()
+        .filter(|name| name != CHANGES_TABLE)
+        .collect())
+}
+
+/// Installs change triggers on tables of the main database, in one transaction.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+/// * `tables` - The tables to track.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` on success, or an `Err` if a table doesn't exist.
+pub fn install_change_triggers(conn: &Connection, tables: &[String]) -> Result<()> {
+    let existing = trackable_tables(conn)?;
+    if let Some(missing) = tables.iter().find(|table| !existing.contains(table)) {
+        anyhow::bail!("Table '{}' does not exist in the main database", missing);
+    }
+    let sql = change_trigger_statements(tables).join(";\n");
+    conn.execute_batch(&format!(
+        "SAVEPOINT vapor_track;\n{};\nRELEASE vapor_track;",
+        sql
+    ))
+    .map_err(|e| {
+        let _ = conn.execute_batch("ROLLBACK TO vapor_track; RELEASE vapor_track;");
+        anyhow::anyhow!("Failed to install the change triggers: {}", e)
+    })
+}
+
+/// Removes every change trigger and the changes table.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+///
+/// # Returns
+///
+/// A `Result` containing the number of triggers removed.
+pub fn remove_change_triggers(conn: &Connection) -> Result<usize> {
+    let triggers = change_triggers(conn)?;
+    let mut sql: Vec<String> = triggers
+        .iter()
+        .map(|trigger| format!("DROP TRIGGER {}", quote_identifier(trigger)))
+        .collect();
+    sql.push(format!(
+        "DROP TABLE IF EXISTS {}",
+        quote_identifier(CHANGES_TABLE)
+    ));
+    conn.execute_batch(&format!(
+        "SAVEPOINT vapor_track;\n{};\nRELEASE vapor_track;",
+        sql.join(";\n")
+    ))
+    .context("Failed to remove the change triggers")?;
+    Ok(triggers.len())
+}
+
+fn change_triggers(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT name FROM sqlite_master WHERE type = 'trigger' AND substr(name, 1, ?1) = ?2 ORDER BY name",
+    )?;
+    let triggers = stmt
+        .query_map(
+            rusqlite::params![TRIGGER_PREFIX.len() as i64, TRIGGER_PREFIX],
+            |row| row.get(0),
+        )?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    Ok(triggers)
+}
+
+/// The tables of the main database that have change triggers.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+///
+/// # Returns
+///
+/// A `Result` containing the table names sorted alphabetically.
+pub fn tracked_tables(conn: &Connection) -> Result<Vec<String>> {
+    let mut tables: Vec<String> = change_triggers(conn)?
+        .iter()
+        .filter_map(|trigger| {
+            let name = trigger.strip_prefix(TRIGGER_PREFIX)?;
+            Some(name[..name.rfind('_')?].to_string())
+        })
+        .collect();
+    tables.sort();
+    tables.dedup();
+    Ok(tables)
+}
+
+/// Collects when each table last changed, from the change triggers and from polling.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+///
+/// # Returns
+///
+/// A `Result` containing the tables with a known change. Triggers win over polling, since
+/// they see every write.
+pub fn last_changes(conn: &Connection) -> Result<HashMap<String, TableChange>> {
+    let mut changes: HashMap<String, TableChange> = poller()
+        .lock()
+        .unwrap()
+        .iter()
+        .flat_map(|state| state.last_changed.iter())
+        .map(|(name, at)| {
+            let change = TableChange {
+                changed_at: at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                writes: None,
+            };
+            (name.clone(), change)
+        })
+        .collect();
+
+    let recorded = conn.prepare(&format!(
+        "SELECT table_name, changed_at, writes FROM {}",
+        quote_identifier(CHANGES_TABLE)
+    ));
+    // The changes table only exists once triggers have been installed
+    if let Ok(mut stmt) = recorded {
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                TableChange {
+                    changed_at: row.get(1)?,
+                    writes: Some(row.get(2)?),
+                },
+            ))
+        })?;
+        for row in rows {
+            let (name, change) = row?;
+            changes.insert(name, change);
+        }
+    }
+    Ok(changes)
+}
+
+/// Prints every table with when it last changed, for `.tables --verbose`.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the active `rusqlite::Connection`.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` on success, or an `Err` on failure.
+pub fn print_table_changes(conn: &Connection) -> Result<()> {
+    let tables = get_all_table_names(conn)?;
+    let changes = last_changes(conn)?;
+    let mut table = Table::new();
+    table.add_row(row!["Table Name", "Last Changed", "Writes"]);
+    for name in tables.iter().filter(|name| *name != CHANGES_TABLE) {
+        let change = changes.get(name);
+        table.add_row(row![
+            name,
+            change.map_or("-", |change| change.changed_at.as_str()),
+            r -> change
+                .and_then(|change| change.writes)
+                .map_or("-".to_string(), |writes| writes.to_string())
+        ]);
+    }
+    table.printstd();
+
+    let tracked = tracked_tables(conn)?;
+    match (is_polling(), tracked.len()) {
+        (false, 0) => println!(
+            "Change tracking is off. '.track on' watches for changes while the REPL is open; '.track triggers' records every write."
+        ),
+        (polling, triggers) => println!(
+            "Polling: {}; change triggers on {} table(s).",
+            if polling { "on" } else { "off" },
+            triggers
+        ),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_change_tracking() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.db");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE orders (id INTEGER PRIMARY KEY, total REAL);
+             CREATE TABLE customers (id INTEGER PRIMARY KEY, name TEXT);
+             CREATE TABLE config (key TEXT PRIMARY KEY, value TEXT) WITHOUT ROWID;",
+        )
+        .unwrap();
+
+        // Polling sees the session's own writes and other connections' commits
+        start_polling(&conn).unwrap();
+        assert!(poll_changes(&conn).unwrap().is_empty());
+        conn.execute("INSERT INTO orders (total) VALUES (9.5)", [])
+            .unwrap();
+        assert_eq!(poll_changes(&conn).unwrap(), vec!["orders"]);
+        let other = Connection::open(&path).unwrap();
+        other
+            .execute("INSERT INTO config VALUES ('theme', 'dark')", [])
+            .unwrap();
+        assert_eq!(poll_changes(&conn).unwrap(), vec!["config"]);
+        let changes = last_changes(&conn).unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes["orders"].writes, None);
+        stop_polling();
+        assert!(last_changes(&conn).unwrap().is_empty());
+
+        // Triggers record every write, including updates
+        let tables = trackable_tables(&conn).unwrap();
+        install_change_triggers(&conn, &tables).unwrap();
+        assert_eq!(tracked_tables(&conn).unwrap(), tables);
+        assert!(!trackable_tables(&conn)
+            .unwrap()
+            .contains(&CHANGES_TABLE.to_string()));
+        other.execute("UPDATE orders SET total = 10", []).unwrap();
+        other
+            .execute("INSERT INTO orders (total) VALUES (1), (2)", [])
+            .unwrap();
+        let changes = last_changes(&conn).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes["orders"].writes, Some(3));
+
+        assert!(install_change_triggers(&conn, &["missing".to_string()]).is_err());
+        assert_eq!(remove_change_triggers(&conn).unwrap(), 9);
+        assert!(tracked_tables(&conn).unwrap().is_empty());
+        assert!(last_changes(&conn).unwrap().is_empty());
+    }
+}
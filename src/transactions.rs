@@ -6,15 +6,31 @@
 //!
 //! ## Core Components:
 //! - `TransactionManager`: A thread-safe struct that tracks the current transaction state.
-//! - `TransactionState`: An enum representing whether a transaction is `Active` or `None`.
+//! - `TransactionState`: An enum representing whether a transaction is `Active`, `Aborted`,
+//!   or `None`.
+//! - `ErrorPolicy`: What happens to an open transaction when one of its statements fails.
+//! - `TransactionStats`: How long the open transaction has run, how many statements it
+//!   has run, and how many rows they changed, reported on `COMMIT` and by `.status`.
 //!
 //! The manager ensures that users cannot start a new transaction while one is already
 //! active and provides clear feedback about the transaction status. It also intercepts
 //! transaction-related SQL keywords (`BEGIN`, `COMMIT`, `ROLLBACK`) to manage state correctly.
+//!
+//! For library code, `begin_nested`, `commit_nested`, and `rollback_nested` let
+//! transactions nest: beginning one while another is active creates a savepoint, and
+//! committing or rolling back the inner one releases or undoes only that savepoint.
+//!
+//! When a statement fails inside a transaction, SQLite undoes only that statement and
+//! keeps the transaction open (psql's `ON_ERROR_ROLLBACK on`). The `rollback` error policy
+//! rolls the whole transaction back instead, and `abort` marks it aborted so that further
+//! statements are refused until `ROLLBACK`, as PostgreSQL does by default.
 
 use anyhow::{Context, Result};
 use rusqlite::Connection;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 /// Represents the current state of a database transaction.
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -23,6 +39,86 @@ pub enum TransactionState {
     None,
     /// A transaction is active and awaiting a `COMMIT` or `ROLLBACK`.
     Active,
+    /// A statement failed under `ErrorPolicy::Abort`; the transaction can only be rolled
+    /// back.
+    Aborted,
+}
+
+/// What happens to an open transaction when one of its statements fails.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ErrorPolicy {
+    /// Only the failed statement is undone and the transaction stays open (SQLite's
+    /// behavior).
+    #[default]
+    Continue,
+    /// The whole transaction is rolled back.
+    Rollback,
+    /// The transaction is marked aborted and further statements are refused until
+    /// `ROLLBACK`.
+    Abort,
+}
+
+impl ErrorPolicy {
+    /// Parses a policy name: `continue`, `rollback`, or `abort`.
+    ///
+    /// # Arguments
+    /// * `name` - The policy name, in any case.
+    ///
+    /// # Returns
+    /// A `Result` containing the policy, or an `Err` naming the valid policies.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "continue" => Ok(Self::Continue),
+            "rollback" => Ok(Self::Rollback),
+            "abort" => Ok(Self::Abort),
+            _ => anyhow::bail!(
+                "Invalid error policy '{}'. Use continue, rollback, or abort.",
+                name
+            ),
+        }
+    }
+
+    /// The policy's name, as accepted by `parse`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Continue => "continue",
+            Self::Rollback => "rollback",
+            Self::Abort => "abort",
+        }
+    }
+}
+
+/// What the open transaction has done so far.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransactionStats {
+    /// How long the transaction has been open.
+    pub elapsed: Duration,
+    /// The statements run in it, as recorded with `TransactionManager::record_statement`.
+    pub statements: usize,
+    /// The rows inserted, updated, or deleted since it began.
+    pub rows_changed: u64,
+}
+
+impl fmt::Display for TransactionStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "open {:.2}s, {} statement{}, {} row{} changed",
+            self.elapsed.as_secs_f64(),
+            self.statements,
+            if self.statements == 1 { "" } else { "s" },
+            self.rows_changed,
+            if self.rows_changed == 1 { "" } else { "s" }
+        )
+    }
+}
+
+/// Where the open transaction's statistics are counted from.
+#[derive(Debug, Clone, Copy)]
+struct StatsStart {
+    started: Instant,
+    statements: usize,
+    total_changes: u64,
 }
 
 /// Manages the state of database transactions in a thread-safe manner.
@@ -32,6 +128,18 @@ pub enum TransactionState {
 /// other command handlers, while preventing race conditions.
 pub struct TransactionManager {
     state: Arc<Mutex<TransactionState>>,
+    /// The number of savepoints open inside the active transaction.
+    savepoints: Arc<AtomicUsize>,
+    /// What happens to the transaction when one of its statements fails.
+    error_policy: Arc<Mutex<ErrorPolicy>>,
+    /// When the active transaction began, for `stats`.
+    stats_start: Arc<Mutex<Option<StatsStart>>>,
+}
+
+impl Default for TransactionManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TransactionManager {
@@ -39,6 +147,9 @@ impl TransactionManager {
     pub fn new() -> Self {
         Self {
             state: Arc::new(Mutex::new(TransactionState::None)),
+            savepoints: Arc::new(AtomicUsize::new(0)),
+            error_policy: Arc::new(Mutex::new(ErrorPolicy::default())),
+            stats_start: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -57,9 +168,18 @@ impl TransactionManager {
                 println!("Warning: Transaction already active. Use COMMIT or ROLLBACK first.");
                 return Ok(());
             }
+            TransactionState::Aborted => {
+                println!("Warning: The current transaction is aborted. Use ROLLBACK first.");
+                return Ok(());
+            }
             TransactionState::None => {
                 conn.execute("BEGIN", [])?;
                 *state = TransactionState::Active;
+                *self.stats_start.lock().unwrap() = Some(StatsStart {
+                    started: Instant::now(),
+                    statements: 0,
+                    total_changes: total_changes(conn)?,
+                });
                 println!("Transaction started.");
             }
         }
@@ -70,7 +190,8 @@ impl TransactionManager {
     /// Commits the active database transaction.
     ///
     /// If no transaction is active, it prints a message and does nothing.
-    /// Otherwise, it executes a `COMMIT` statement and resets the state to `None`.
+    /// Otherwise, it executes a `COMMIT` statement and resets the state to `None`. An
+    /// aborted transaction can't be committed, so it is rolled back instead.
     ///
     /// # Arguments
     /// * `conn` - A reference to the `rusqlite::Connection`.
@@ -83,9 +204,22 @@ impl TransactionManager {
                 return Ok(());
             }
             TransactionState::Active => {
+                let stats = self.stats_since_start(conn);
                 conn.execute("COMMIT", [])?;
                 *state = TransactionState::None;
-                println!("Transaction committed.");
+                *self.stats_start.lock().unwrap() = None;
+                self.savepoints.store(0, Ordering::SeqCst);
+                match stats {
+                    Some(stats) => println!("Transaction committed ({}).", stats),
+                    None => println!("Transaction committed."),
+                }
+            }
+            TransactionState::Aborted => {
+                conn.execute("ROLLBACK", [])?;
+                *state = TransactionState::None;
+                *self.stats_start.lock().unwrap() = None;
+                self.savepoints.store(0, Ordering::SeqCst);
+                println!("The transaction was aborted, so it was rolled back instead.");
             }
         }
 
@@ -107,9 +241,11 @@ impl TransactionManager {
                 println!("No active transaction to rollback.");
                 return Ok(());
             }
-            TransactionState::Active => {
+            TransactionState::Active | TransactionState::Aborted => {
                 conn.execute("ROLLBACK", [])?;
                 *state = TransactionState::None;
+                *self.stats_start.lock().unwrap() = None;
+                self.savepoints.store(0, Ordering::SeqCst);
                 println!("Transaction rolled back.");
             }
         }
@@ -117,12 +253,190 @@ impl TransactionManager {
         Ok(())
     }
 
+    /// Begins a transaction, or a savepoint if one is already active.
+    ///
+    /// Code that manages its own transaction can then be called from inside another
+    /// transaction: its commit releases the savepoint, and its rollback undoes only its
+    /// own changes.
+    ///
+    /// # Arguments
+    /// * `conn` - A reference to the `rusqlite::Connection`.
+    pub fn begin_nested(&self, conn: &Connection) -> Result<()> {
+        if !self.is_active() {
+            return self.begin_transaction(conn);
+        }
+        let depth = self.savepoints.load(Ordering::SeqCst) + 1;
+        conn.execute_batch(&format!("SAVEPOINT vapor_nested_{}", depth))
+            .context("Failed to create a savepoint for the nested transaction")?;
+        self.savepoints.store(depth, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Commits the innermost transaction begun with `begin_nested`.
+    ///
+    /// Inside another transaction this releases the savepoint, keeping its changes as
+    /// part of the outer transaction; otherwise it commits.
+    ///
+    /// # Arguments
+    /// * `conn` - A reference to the `rusqlite::Connection`.
+    pub fn commit_nested(&self, conn: &Connection) -> Result<()> {
+        let depth = self.savepoints.load(Ordering::SeqCst);
+        if depth == 0 {
+            return self.commit_transaction(conn);
+        }
+        conn.execute_batch(&format!("RELEASE vapor_nested_{}", depth))
+            .context("Failed to release the savepoint of the nested transaction")?;
+        self.savepoints.store(depth - 1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Rolls back the innermost transaction begun with `begin_nested`.
+    ///
+    /// Inside another transaction this undoes the changes made since the savepoint and
+    /// leaves the outer transaction open; otherwise it rolls back.
+    ///
+    /// # Arguments
+    /// * `conn` - A reference to the `rusqlite::Connection`.
+    pub fn rollback_nested(&self, conn: &Connection) -> Result<()> {
+        let depth = self.savepoints.load(Ordering::SeqCst);
+        if depth == 0 {
+            return self.rollback_transaction(conn);
+        }
+        conn.execute_batch(&format!(
+            "ROLLBACK TO vapor_nested_{0}; RELEASE vapor_nested_{0}",
+            depth
+        ))
+        .context("Failed to roll back the savepoint of the nested transaction")?;
+        self.savepoints.store(depth - 1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// The number of nested transactions open: 0 with none, 1 inside a transaction, and
+    /// one more for each savepoint created by `begin_nested`.
+    pub fn depth(&self) -> usize {
+        if self.is_active() {
+            1 + self.savepoints.load(Ordering::SeqCst)
+        } else {
+            0
+        }
+    }
+
     /// Checks if a transaction is currently active.
     ///
     /// # Returns
-    /// `true` if the transaction state is `Active`, `false` otherwise.
+    /// `true` if the transaction state is `Active` or `Aborted`, `false` otherwise.
     pub fn is_active(&self) -> bool {
-        matches!(*self.state.lock().unwrap(), TransactionState::Active)
+        matches!(
+            *self.state.lock().unwrap(),
+            TransactionState::Active | TransactionState::Aborted
+        )
+    }
+
+    /// Counts a statement run in the active transaction, for `stats`. Does nothing outside
+    /// a transaction.
+    pub fn record_statement(&self) {
+        if let Some(start) = self.stats_start.lock().unwrap().as_mut() {
+            start.statements += 1;
+        }
+    }
+
+    /// What the active transaction has done so far.
+    ///
+    /// # Arguments
+    /// * `conn` - The connection the transaction is on, to count the rows changed.
+    ///
+    /// # Returns
+    /// The statistics, or `None` outside a transaction begun with `begin_transaction`.
+    pub fn stats(&self, conn: &Connection) -> Option<TransactionStats> {
+        if !self.is_active() {
+            return None;
+        }
+        self.stats_since_start(conn)
+    }
+
+    fn stats_since_start(&self, conn: &Connection) -> Option<TransactionStats> {
+        let start = (*self.stats_start.lock().unwrap())?;
+        let rows_changed = total_changes(conn)
+            .ok()?
+            .saturating_sub(start.total_changes);
+        Some(TransactionStats {
+            elapsed: start.started.elapsed(),
+            statements: start.statements,
+            rows_changed,
+        })
+    }
+
+    /// Checks if the transaction was aborted by a failed statement.
+    pub fn is_aborted(&self) -> bool {
+        matches!(*self.state.lock().unwrap(), TransactionState::Aborted)
+    }
+
+    /// Sets what happens to the transaction when one of its statements fails.
+    pub fn set_error_policy(&self, policy: ErrorPolicy) {
+        *self.error_policy.lock().unwrap() = policy;
+    }
+
+    /// The current error policy.
+    pub fn error_policy(&self) -> ErrorPolicy {
+        *self.error_policy.lock().unwrap()
+    }
+
+    /// Applies the error policy after a statement failed.
+    ///
+    /// If SQLite already ended the transaction (some errors roll it back on their own), the
+    /// state is reset to match. Otherwise the policy decides: `Continue` leaves the
+    /// transaction open, `Rollback` rolls it back, and `Abort` marks it aborted.
+    ///
+    /// # Arguments
+    /// * `conn` - A reference to the `rusqlite::Connection`.
+    pub fn statement_failed(&self, conn: &Connection) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if *state == TransactionState::None {
+            return Ok(());
+        }
+        if conn.is_autocommit() {
+            *state = TransactionState::None;
+            *self.stats_start.lock().unwrap() = None;
+            self.savepoints.store(0, Ordering::SeqCst);
+            println!("The failed statement ended the transaction; its changes were rolled back.");
+            return Ok(());
+        }
+        match self.error_policy() {
+            ErrorPolicy::Continue => {}
+            ErrorPolicy::Rollback => {
+                conn.execute("ROLLBACK", [])?;
+                *state = TransactionState::None;
+                *self.stats_start.lock().unwrap() = None;
+                self.savepoints.store(0, Ordering::SeqCst);
+                println!("Transaction rolled back because a statement failed.");
+            }
+            ErrorPolicy::Abort => {
+                if *state == TransactionState::Active {
+                    *state = TransactionState::Aborted;
+                    println!("Transaction aborted: statements are ignored until ROLLBACK.");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Prints the current transaction status with the statistics of an open transaction,
+    /// for `.status`.
+    ///
+    /// # Arguments
+    /// * `conn` - The connection the transaction is on.
+    pub fn show_status_with_stats(&self, conn: &Connection) {
+        self.show_status();
+        if let Some(stats) = self.stats(conn) {
+            println!("  Open for:     {:.2}s", stats.elapsed.as_secs_f64());
+            println!("  Statements:   {}", stats.statements);
+            println!("  Rows changed: {}", stats.rows_changed);
+            // `sqlite3_changes`: rows changed by the most recent INSERT, UPDATE, or DELETE
+            println!("  Last write:   {} row(s)", conn.changes());
+            if self.depth() > 1 {
+                println!("  Savepoints:   {}", self.depth() - 1);
+            }
+        }
     }
 
     /// Prints the current transaction status to the console.
@@ -131,15 +445,17 @@ impl TransactionManager {
         match *state {
             TransactionState::None => println!("No active transaction."),
             TransactionState::Active => println!("Transaction is active."),
+            TransactionState::Aborted => {
+                println!("Transaction is aborted. Use ROLLBACK to end it.")
+            }
         }
     }
 
     /// Intercepts and handles transaction-related SQL commands.
     ///
     /// This method checks if the input SQL string matches known transaction control
-    /// statements (`BEGIN`, `COMMIT`, `ROLLBACK`) or a `DROP` command. If a match is found,
-    /// it calls the appropriate `TransactionManager` method and returns `Ok(true)`.
-    /// For `DROP`, it adds extra validation.
+    /// statements (`BEGIN`, `COMMIT`, `ROLLBACK`). If a match is found, it calls the
+    /// appropriate `TransactionManager` method and returns `Ok(true)`.
     ///
     /// If the command is not a recognized transaction command, it returns `Ok(false)`,
     /// indicating that the command should be executed as a standard SQL query.
@@ -151,7 +467,8 @@ impl TransactionManager {
     /// # Returns
     /// A `Result<bool>` which is `Ok(true)` if the command was handled, or `Ok(false)` if not.
     pub fn handle_sql_command(&self, conn: &Connection, sql: &str) -> Result<bool> {
-        let sql_lower = sql.to_lowercase().trim().to_string();
+        // `BEGIN;` as typed in the REPL is the same command as `BEGIN`
+        let sql_lower = sql.trim().trim_end_matches(';').trim_end().to_lowercase();
 
         match sql_lower.as_str() {
             "begin" | "begin transaction" => {
@@ -166,52 +483,15 @@ impl TransactionManager {
                 self.rollback_transaction(conn)?;
                 Ok(true) // Command was handled
             }
-            _ => {
-                // Handle DROP commands
-                if sql_lower.starts_with("drop") {
-                    let parts: Vec<&str> = sql_lower.split_whitespace().collect();
-                    if parts.len() < 2 {
-                        println!("Usage: DROP TABLE table_name; or DROP table_name;");
-                        return Ok(true);
-                    }
-
-                    let table_name = if parts[1] == "table" {
-                        if parts.len() < 3 {
-                            println!("Usage: DROP TABLE table_name;");
-                            return Ok(true);
-                        }
-                        parts[2].trim_end_matches(';')
-                    } else {
-                        parts[1].trim_end_matches(';')
-                    };
-
-                    // Verify table exists before dropping
-                    let mut stmt = conn
-                        .prepare(
-                            "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name=?1",
-                        )
-                        .context("Failed to prepare table existence check")?;
-
-                    let count: i64 = stmt
-                        .query_row(rusqlite::params![table_name], |row| row.get(0))
-                        .with_context(||
-                            format!("Failed to check if table '{}' exists", table_name)
-                        )?;
-
-                    if count == 0 {
-                        println!("Table '{}' does not exist", table_name);
-                        return Ok(true);
-                    }
-
-                    // Execute the DROP command
-                    conn.execute(&format!("DROP TABLE {}", table_name), [])
-                        .with_context(|| format!("Failed to drop table '{}'", table_name))?;
-
-                    println!("Table '{}' dropped successfully", table_name);
-                    return Ok(true);
-                }
-                Ok(false) // Command was not handled
-            }
+            _ => Ok(false), // Command was not handled
         }
     }
 }
+
+/// The rows changed on a connection since it opened (SQLite's `total_changes()`).
+fn total_changes(conn: &Connection) -> Result<u64> {
+    let changes: i64 = conn
+        .query_row("SELECT total_changes()", [], |row| row.get(0))
+        .context("Failed to read the number of rows changed")?;
+    Ok(changes as u64)
+}
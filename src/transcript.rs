@@ -0,0 +1,359 @@
+//! # Session Transcripts
+//!
+//! This module keeps a log of a REPL session: every command entered and the output it
+//! printed, appended to a file. It backs `.log FILE` and `.log off`, for keeping an
+//! auditable record of an investigation or a data fix.
+//!
+//! ## Features:
+//! - **Append only**: Logs are opened for appending, so several sessions can share one
+//!   file, and each session starts with a dated header.
+//! - **Timestamps**: Each command is written with the time it was run.
+//! - **Output capture**: On Unix, everything a command writes to stdout and stderr is
+//!   copied to the log while still being shown on the terminal. Elsewhere only the
+//!   commands are logged.
+//! - **Session notes**: Observations made during a session (`.note session TEXT`) are kept in a
+//!   notes file next to the database's history, tagged with the session they were made
+//!   in, and show up in the log like any other command.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+/// The log file of the current session.
+struct Transcript {
+    path: String,
+    file: File,
+}
+
+fn transcript() -> &'static Mutex<Option<Transcript>> {
+    static TRANSCRIPT: OnceLock<Mutex<Option<Transcript>>> = OnceLock::new();
+    TRANSCRIPT.get_or_init(|| Mutex::new(None))
+}
+
+/// Starts logging commands and their output to a file, replacing any current log.
+///
+/// # Arguments
+///
+/// * `path` - The log file. It is created if needed and appended to otherwise.
+/// * `database` - The database the session is working on, for the log's header.
+///
+/// # Returns
+///
+/// A `Result` which is `Ok(())` once the file is open, or an `Err` if it can't be opened.
+pub fn start_transcript(path: &str, database: &str) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open log file '{}'", path))?;
+    writeln!(
+        file,
+        "-- Session log started {} on {}",
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S %z"),
+        database
+    )
+    .with_context(|| format!("Failed to write to log file '{}'", path))?;
+    *transcript().lock().unwrap() = Some(Transcript {
+        path: path.to_string(),
+        file,
+    });
+    Ok(())
+}
+
+/// Stops logging, returning the path of the log that was open.
+pub fn stop_transcript() -> Option<String> {
+    let mut transcript = transcript().lock().unwrap();
+    let mut stopped = transcript.take()?;
+    let _ = writeln!(
+        stopped.file,
+        "-- Session log stopped {}",
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S %z")
+    );
+    Some(stopped.path)
+}
+
+/// The path of the current log, if logging is on.
+pub fn transcript_path() -> Option<String> {
+    transcript()
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|transcript| transcript.path.clone())
+}
+
+/// Runs a command, logging it and the output it prints if logging is on.
+///
+/// # Arguments
+///
+/// * `command` - The command as it is run.
+/// * `run` - Runs the command.
+///
+/// # Returns
+///
+/// What `run` returns.
+pub fn record_command<T>(command: &str, run: impl FnOnce() -> T) -> T {
+    let Some(file) = write_command(command) else {
+        return run();
+    };
+
+    #[cfg(unix)]
+    {
+        match capture::Capture::start(&file) {
+            Ok(capture) => {
+                let result = run();
+                capture.finish();
+                result
+            }
+            Err(_) => run(),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        drop(file);
+        run()
+    }
+}
+
+/// Logs a command without its output, for commands that start processes which keep
+/// writing after the command returns, such as `.pipe`.
+///
+/// # Arguments
+///
+/// * `command` - The command as it is run.
+pub fn note_command(command: &str) {
+    write_command(command);
+}
+
+/// Writes a command to the log, returning a handle to the log for its output.
+fn write_command(command: &str) -> Option<File> {
+    // Clone the file so the command itself can stop or replace the log
+    let mut file = transcript()
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(|transcript| transcript.file.try_clone().ok())?;
+    let _ = writeln!(
+        file,
+        "\n-- {}\n{}",
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+        command
+    );
+    Some(file)
+}
+
+/// Copies what is written to stdout and stderr into the log by pointing them at pipes
+/// whose contents are forwarded to both the terminal and the log.
+#[cfg(unix)]
+mod capture {
+    use std::fs::File;
+    use std::io::{self, Read, Write};
+    use std::os::unix::io::{FromRawFd, RawFd};
+    use std::thread::JoinHandle;
+
+    pub struct Capture {
+        /// The original descriptors, to restore when the command is done.
+        saved: Vec<(RawFd, RawFd)>,
+        forwarders: Vec<JoinHandle<()>>,
+    }
+
+    impl Capture {
+        pub fn start(log: &File) -> io::Result<Self> {
+            flush();
+            let mut capture = Capture {
+                saved: Vec::new(),
+                forwarders: Vec::new(),
+            };
+            for fd in [libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+                if let Err(e) = capture.redirect(fd, log) {
+                    capture.finish();
+                    return Err(e);
+                }
+            }
+            Ok(capture)
+        }
+
+        fn redirect(&mut self, fd: RawFd, log: &File) -> io::Result<()> {
+            let mut log = log.try_clone()?;
+            let mut pipe = [0; 2];
+            // SAFETY: the descriptors come from `pipe` and `dup`, are checked for errors,
+            // and each is owned by exactly one `File` or restored in `finish`.
+            unsafe {
+                if libc::pipe(pipe.as_mut_ptr()) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                let saved = libc::dup(fd);
+                let terminal = libc::dup(fd);
+                if saved < 0 || terminal < 0 || libc::dup2(pipe[1], fd) < 0 {
+                    let error = io::Error::last_os_error();
+                    for fd in [pipe[0], pipe[1], saved, terminal] {
+                        if fd >= 0 {
+                            libc::close(fd);
+                        }
+                    }
+                    return Err(error);
+                }
+                libc::close(pipe[1]);
+                self.saved.push((fd, saved));
+
+                let mut reader = File::from_raw_fd(pipe[0]);
+                let mut terminal = File::from_raw_fd(terminal);
+                self.forwarders.push(std::thread::spawn(move || {
+                    let mut buf = [0; 8192];
+                    while let Ok(n) = reader.read(&mut buf) {
+                        if n == 0 {
+                            break;
+                        }
+                        let _ = terminal.write_all(&buf[..n]);
+                        let _ = log.write_all(&buf[..n]);
+                    }
+                }));
+            }
+            Ok(())
+        }
+
+        /// Restores stdout and stderr and waits for the captured output to be copied.
+        pub fn finish(self) {
+            flush();
+            for (fd, saved) in &self.saved {
+                // SAFETY: `saved` was duplicated from `fd` in `redirect` and is closed once.
+                unsafe {
+                    libc::dup2(*saved, *fd);
+                    libc::close(*saved);
+                }
+            }
+            // Restoring the descriptors closed the pipes' write ends
+            for forwarder in self.forwarders {
+                let _ = forwarder.join();
+            }
+        }
+    }
+
+    fn flush() {
+        let _ = io::stdout().flush();
+        let _ = io::stderr().flush();
+    }
+}
+
+/// A note made during a REPL session, such as "order 123 already refunded".
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionNote {
+    /// The session the note was made in; see `session_id`.
+    pub session: String,
+    /// When the note was made, as `YYYY-MM-DD HH:MM:SS` local time.
+    pub time: String,
+    pub text: String,
+}
+
+/// Identifies the current session: the time its first note was made. Notes made in the
+/// same run of vapor-cli share it.
+pub fn session_id() -> &'static str {
+    static SESSION: OnceLock<String> = OnceLock::new();
+    SESSION.get_or_init(|| chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+/// Adds a note to the current session.
+///
+/// # Arguments
+///
+/// * `notes_file` - The database's notes file (see `config::get_session_notes_path`). It
+///   is created if needed and appended to otherwise, one JSON note per line.
+/// * `text` - The note.
+///
+/// # Returns
+///
+/// A `Result` containing the note as it was saved.
+pub fn add_session_note(notes_file: &Path, text: &str) -> Result<SessionNote> {
+    let note = SessionNote {
+        session: session_id().to_string(),
+        time: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        text: text.to_string(),
+    };
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(notes_file)
+        .with_context(|| format!("Failed to open notes file '{}'", notes_file.display()))?;
+    writeln!(file, "{}", serde_json::to_string(&note)?)
+        .with_context(|| format!("Failed to write to notes file '{}'", notes_file.display()))?;
+    Ok(note)
+}
+
+/// Reads the notes in a database's notes file, oldest first.
+///
+/// # Arguments
+///
+/// * `notes_file` - The database's notes file. A missing file has no notes.
+///
+/// # Returns
+///
+/// A `Result` containing the notes. Lines that aren't notes are skipped.
+pub fn read_session_notes(notes_file: &Path) -> Result<Vec<SessionNote>> {
+    if !notes_file.exists() {
+        return Ok(Vec::new());
+    }
+    let content = std::fs::read_to_string(notes_file)
+        .with_context(|| format!("Failed to read notes file '{}'", notes_file.display()))?;
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transcript_records_commands_and_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.log");
+        let path = path.to_str().unwrap();
+
+        assert_eq!(record_command("SELECT 0;", || 0), 0);
+        start_transcript(path, "test.db").unwrap();
+        assert_eq!(transcript_path().as_deref(), Some(path));
+        // `println!` is captured by the test harness, so write to stdout directly
+        let value = record_command("SELECT 42;", || {
+            writeln!(std::io::stdout(), "answer: 42").unwrap();
+            42
+        });
+        assert_eq!(value, 42);
+        assert_eq!(stop_transcript().as_deref(), Some(path));
+        record_command("SELECT 1;", || ());
+
+        let log = std::fs::read_to_string(path).unwrap();
+        assert!(log.starts_with("-- Session log started"));
+        assert!(log.contains("\nSELECT 42;\n"));
+        #[cfg(unix)]
+        assert!(log.contains("answer: 42\n"));
+        assert!(log.contains("-- Session log stopped"));
+        assert!(!log.contains("SELECT 0;") && !log.contains("SELECT 1;"));
+    }
+
+    #[test]
+    fn test_session_notes() {
+        let dir = tempfile::tempdir().unwrap();
+        let notes_file = dir.path().join("app.notes");
+        assert!(read_session_notes(&notes_file).unwrap().is_empty());
+
+        // Notes from an earlier session are kept alongside this session's
+        let earlier = SessionNote {
+            session: "2024-01-01 09:00:00".to_string(),
+            time: "2024-01-01 09:05:00".to_string(),
+            text: "checked the refunds table".to_string(),
+        };
+        std::fs::write(
+            &notes_file,
+            format!("{}\nnot a note\n", serde_json::to_string(&earlier).unwrap()),
+        )
+        .unwrap();
+        let note = add_session_note(&notes_file, "order 123 already refunded").unwrap();
+        assert_eq!(note.session, session_id());
+
+        let notes = read_session_notes(&notes_file).unwrap();
+        assert_eq!(notes, vec![earlier, note]);
+    }
+}
@@ -0,0 +1,194 @@
+//! # Connection Tuning for Bulk Commands
+//!
+//! This module holds the SQLite settings that matter most for bulk work (the page cache,
+//! memory-mapped I/O, and where temporary tables and indexes are kept), so `populate`,
+//! `import`, `export`, and `export-all` can be tuned for the machine they run on instead
+//! of relying on fixed values.
+//!
+//! ## Features:
+//! - **Presets**: `bulk` trades memory for speed on large loads and exports, `low-memory`
+//!   keeps SQLite small on constrained machines, and `default` leaves SQLite's own settings.
+//! - **Overrides**: `--cache-size`, `--mmap-size`, and `--temp-store` replace single
+//!   settings of a preset.
+//! - **Readable sizes**: Sizes are given in bytes with an optional `KB`, `MB`, or `GB`
+//!   suffix, such as `64MB`.
+
+use anyhow::{Context, Result};
+use rusqlite::Connection;
+
+const MB: u64 = 1024 * 1024;
+
+/// Where SQLite keeps temporary tables and indexes, such as those built for sorting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TempStore {
+    /// SQLite's compile-time default (a file for the bundled SQLite).
+    Default,
+    /// Temporary files on disk.
+    File,
+    /// Memory, which is faster but needs room for large sorts.
+    Memory,
+}
+
+impl TempStore {
+    /// Parses `default`, `file`, or `memory`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The temp store name, in any case.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the temp store, or an `Err` naming the valid values.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "default" => Ok(Self::Default),
+            "file" => Ok(Self::File),
+            "memory" => Ok(Self::Memory),
+            _ => anyhow::bail!(
+                "Invalid temp store '{}'. Use default, file, or memory.",
+                name
+            ),
+        }
+    }
+
+    fn pragma_value(self) -> i64 {
+        match self {
+            Self::Default => 0,
+            Self::File => 1,
+            Self::Memory => 2,
+        }
+    }
+}
+
+/// SQLite settings applied to the connection of a bulk command. Settings left as `None`
+/// keep SQLite's (or the command's) default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tuning {
+    /// The page cache size in bytes.
+    pub cache_size: Option<u64>,
+    /// The most bytes of the database file to access through memory-mapped I/O; 0 turns
+    /// it off.
+    pub mmap_size: Option<u64>,
+    /// Where temporary tables and indexes are kept.
+    pub temp_store: Option<TempStore>,
+}
+
+impl Tuning {
+    /// Returns the settings of a named preset.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - `default`, `bulk`, or `low-memory`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the preset, or an `Err` naming the valid presets.
+    pub fn preset(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "default" => Ok(Self::default()),
+            "bulk" => Ok(Self {
+                cache_size: Some(256 * MB),
+                mmap_size: Some(1024 * MB),
+                temp_store: Some(TempStore::Memory),
+            }),
+            "low-memory" => Ok(Self {
+                cache_size: Some(2 * MB),
+                mmap_size: Some(0),
+                temp_store: Some(TempStore::File),
+            }),
+            _ => anyhow::bail!(
+                "Invalid tuning preset '{}'. Use default, bulk, or low-memory.",
+                name
+            ),
+        }
+    }
+
+    /// Whether every setting is left as it is.
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Applies the settings to a connection.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - The connection to tune.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` which is `Ok(())` on success, or an `Err` if a setting is rejected.
+    pub fn apply(&self, conn: &Connection) -> Result<()> {
+        if let Some(bytes) = self.cache_size {
+            // A negative cache size is in KiB rather than pages
+            let kib = (bytes / 1024).max(1) as i64;
+            conn.pragma_update(None, "cache_size", -kib)
+                .context("Failed to set the cache size")?;
+        }
+        if let Some(bytes) = self.mmap_size {
+            // The new size comes back as a row, except for in-memory databases
+            conn.execute_batch(&format!("PRAGMA mmap_size = {}", bytes))
+                .context("Failed to set the mmap size")?;
+        }
+        if let Some(temp_store) = self.temp_store {
+            conn.pragma_update(None, "temp_store", temp_store.pragma_value())
+                .context("Failed to set the temp store")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses a size in bytes with an optional `KB`, `MB`, or `GB` suffix (powers of 1024).
+///
+/// # Arguments
+///
+/// * `size` - A size such as `4096`, `512KB`, or `64MB`.
+///
+/// # Returns
+///
+/// A `Result` containing the size in bytes, or an `Err` if it can't be parsed.
+pub fn parse_size(size: &str) -> Result<u64> {
+    let upper = size.trim().to_uppercase();
+    let (digits, multiplier) = [("GB", 1024 * MB), ("MB", MB), ("KB", 1024), ("B", 1)]
+        .iter()
+        .find_map(|&(suffix, multiplier)| {
+            upper
+                .strip_suffix(suffix)
+                .map(|digits| (digits.to_string(), multiplier))
+        })
+        .unwrap_or((upper.clone(), 1));
+    let value: u64 = digits.trim().parse().with_context(|| {
+        format!(
+            "Invalid size '{}'. Use bytes or a number with KB, MB, or GB, such as 64MB.",
+            size
+        )
+    })?;
+    value
+        .checked_mul(multiplier)
+        .with_context(|| format!("Size '{}' is too large", size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tuning_presets_and_apply() {
+        assert_eq!(parse_size("4096").unwrap(), 4096);
+        assert_eq!(parse_size("64mb").unwrap(), 64 * MB);
+        assert_eq!(parse_size("1 GB").unwrap(), 1024 * MB);
+        assert!(parse_size("lots").is_err());
+        assert!(Tuning::preset("turbo").is_err());
+        assert!(Tuning::preset("default").unwrap().is_default());
+
+        let mut tuning = Tuning::preset("bulk").unwrap();
+        tuning.temp_store = Some(TempStore::parse("FILE").unwrap());
+        let conn = Connection::open_in_memory().unwrap();
+        tuning.apply(&conn).unwrap();
+        let pragma = |name: &str| -> i64 {
+            conn.query_row(&format!("PRAGMA {}", name), [], |row| row.get(0))
+                .unwrap()
+        };
+        assert_eq!(pragma("cache_size"), -256 * 1024);
+        assert_eq!(pragma("temp_store"), 1);
+    }
+}
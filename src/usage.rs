@@ -0,0 +1,285 @@
+//! # Command Usage
+//!
+//! This module counts how often each dot-command and bookmark is used, so the REPL can
+//! list the ones you use most first. The counts never leave your machine; they are kept
+//! in `~/.vapor/usage.json`.
+//!
+//! ## Features:
+//! - **Usage counts**: Dot-commands and bookmark runs are counted per name.
+//! - **Ranking**: Sorts a list of names by use, keeping the original order for ties, for
+//!   the help summary and tab completion.
+//! - **Best effort**: A missing or unreadable file counts as no usage, and failures to
+//!   save are ignored so they never get in the way of the REPL.
+//! - **Tips**: Opt-in hints about features you haven't used yet, such as bookmarking a
+//!   query you keep retyping. Each tip is shown once. To spot repeated queries, a hash of
+//!   each statement is counted; the SQL itself is never stored.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+
+use crate::config;
+
+/// How often each dot-command and bookmark has been used.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageStats {
+    /// Uses per dot-command, keyed by command name (e.g. `.tables`).
+    #[serde(default)]
+    pub commands: HashMap<String, u64>,
+    /// Runs per bookmark, keyed by bookmark name.
+    #[serde(default)]
+    pub bookmarks: HashMap<String, u64>,
+    /// Whether tips are shown (`.tips on`). Off by default.
+    #[serde(default)]
+    pub tips: bool,
+    /// Runs per SQL statement while tips are on, keyed by a hash of the statement.
+    #[serde(default)]
+    pub queries: HashMap<String, u64>,
+    /// The tips already shown, so each is shown only once.
+    #[serde(default)]
+    pub tips_shown: Vec<String>,
+    /// The number of dot-commands used when the last feature tip was shown.
+    #[serde(default)]
+    pub commands_at_last_tip: u64,
+}
+
+/// Runs of the same statement before suggesting a bookmark for it.
+const REPEATED_QUERY_TIP: u64 = 5;
+
+/// Dot-commands used between two feature tips, so they stay occasional.
+const TIP_INTERVAL: u64 = 25;
+
+/// The most statements counted for tips; beyond this, statements run only once are
+/// forgotten.
+const MAX_TRACKED_QUERIES: usize = 500;
+
+/// Tips about features, shown once each to users who haven't used the command.
+const FEATURE_TIPS: &[(&str, &str)] = &[
+    (
+        ".last",
+        "`.last` puts your previous statement back in the prompt to fix and re-run.",
+    ),
+    (
+        ".help",
+        "`.help COMMAND` (or `.COMMAND ?`) shows one command's options and examples.",
+    ),
+    (
+        ".find",
+        "`.find PATTERN` searches every text column of every table for a value.",
+    ),
+    (
+        ".describe",
+        "`.describe TABLE` shows a table's columns, indexes, foreign keys, and row counts in one place.",
+    ),
+    (
+        ".set",
+        "`.set NAME VALUE` defines a variable that queries can use as ${NAME}.",
+    ),
+    (
+        ".watch",
+        "`.watch SECONDS QUERY` re-runs a query every few seconds, e.g. to follow a running job.",
+    ),
+    (
+        ".browse",
+        "`.browse` picks a table as you type and shows its first rows and column stats.",
+    ),
+    (
+        ".log",
+        "`.log FILE` keeps a timestamped record of the session's commands and output.",
+    ),
+];
+
+impl UsageStats {
+    /// Loads the usage counts, or returns empty counts if there are none yet.
+    pub fn load() -> Self {
+        config::get_usage_path()
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    /// Saves the usage counts.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` which is `Ok(())` once the file has been written.
+    pub fn save(&self) -> Result<()> {
+        let path = config::get_usage_path()?;
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(&path, json)
+            .with_context(|| format!("Failed to write usage counts to {}", path.display()))
+    }
+
+    /// Counts one use of the dot-command in `input`, and of the bookmark if it runs one.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The command as typed, e.g. `.bookmark run daily_totals`.
+    pub fn record(input: &str) {
+        let mut parts = input.split_whitespace();
+        // Every session ends with one of these, so counting them would only add noise
+        let Some(command) = parts
+            .next()
+            .filter(|c| c.starts_with('.') && !matches!(*c, ".exit" | ".quit"))
+        else {
+            return;
+        };
+        let mut stats = Self::load();
+        *stats.commands.entry(command.to_string()).or_default() += 1;
+        if command == ".bookmark" && parts.next() == Some("run") {
+            if let Some(name) = parts.next() {
+                *stats.bookmarks.entry(name.to_string()).or_default() += 1;
+            }
+        }
+        let _ = stats.save();
+    }
+
+    /// Sorts dot-commands by use, most used first.
+    ///
+    /// # Arguments
+    ///
+    /// * `items` - The items to sort. Ties keep their order.
+    /// * `name` - Returns the command name of an item.
+    pub fn rank_commands<T>(&self, items: &mut [T], name: impl Fn(&T) -> &str) {
+        items.sort_by_key(|item| Reverse(self.commands.get(name(item)).copied().unwrap_or(0)));
+    }
+
+    /// Sorts bookmark names by use, most used first. Ties keep their order.
+    pub fn rank_bookmarks(&self, names: &mut [String]) {
+        names.sort_by_key(|name| Reverse(self.bookmarks.get(name).copied().unwrap_or(0)));
+    }
+
+    /// Returns a tip to show after a command has run, if tips are on and one is due. Tips
+    /// are off until `.tips on`, so usage is only analysed for users who ask for it.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The SQL statement or dot-command that just ran.
+    ///
+    /// # Returns
+    ///
+    /// The tip's text, or `None` if there is nothing to say.
+    pub fn tip_after(input: &str) -> Option<String> {
+        let mut stats = Self::load();
+        if !stats.tips {
+            return None;
+        }
+        let tip = stats.next_tip(input);
+        if tip.is_some() || !input.starts_with('.') {
+            let _ = stats.save();
+        }
+        tip
+    }
+
+    /// Works out the tip due after `input`, marking it as shown.
+    fn next_tip(&mut self, input: &str) -> Option<String> {
+        if !input.starts_with('.') {
+            let key = query_key(input);
+            let runs = self.queries.entry(key.clone()).or_default();
+            *runs += 1;
+            let runs = *runs;
+            if self.queries.len() > MAX_TRACKED_QUERIES {
+                self.queries.retain(|_, runs| *runs > 1);
+            }
+            let id = format!("repeat:{}", key);
+            if runs == REPEATED_QUERY_TIP && !self.tips_shown.contains(&id) {
+                self.tips_shown.push(id);
+                return Some(format!(
+                    "You've run this query {} times. Save it with `.bookmark save NAME` and run it again with `.bookmark run NAME`.",
+                    runs
+                ));
+            }
+            return None;
+        }
+
+        let used: u64 = self.commands.values().sum();
+        if used < self.commands_at_last_tip + TIP_INTERVAL {
+            return None;
+        }
+        let (command, tip) = FEATURE_TIPS.iter().find(|(command, _)| {
+            !self.commands.contains_key(*command) && !self.tips_shown.iter().any(|id| id == command)
+        })?;
+        self.tips_shown.push(command.to_string());
+        self.commands_at_last_tip = used;
+        Some(tip.to_string())
+    }
+
+    /// Returns up to `limit` of the most used dot-commands with their counts.
+    pub fn top_commands(&self, limit: usize) -> Vec<(&str, u64)> {
+        let mut top: Vec<(&str, u64)> = self
+            .commands
+            .iter()
+            .map(|(name, &count)| (name.as_str(), count))
+            .collect();
+        top.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        top.truncate(limit);
+        top
+    }
+}
+
+/// A hash of a statement that ignores case, spacing, and the trailing semicolon.
+fn query_key(sql: &str) -> String {
+    let normalized = sql
+        .trim()
+        .trim_end_matches(';')
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    // FNV-1a, so keys stay the same across Rust releases (unlike `DefaultHasher`)
+    let hash = normalized
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+        });
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rank_by_usage() {
+        let mut stats = UsageStats::default();
+        stats.commands.insert(".peek".to_string(), 5);
+        stats.commands.insert(".tables".to_string(), 2);
+        stats.bookmarks.insert("weekly".to_string(), 3);
+
+        let mut commands = vec![".help", ".tables", ".schema", ".peek"];
+        stats.rank_commands(&mut commands, |c| c);
+        assert_eq!(commands, vec![".peek", ".tables", ".help", ".schema"]);
+
+        let mut bookmarks = vec!["daily".to_string(), "weekly".to_string()];
+        stats.rank_bookmarks(&mut bookmarks);
+        assert_eq!(bookmarks, vec!["weekly", "daily"]);
+
+        assert_eq!(stats.top_commands(1), vec![(".peek", 5)]);
+    }
+
+    #[test]
+    fn test_tips() {
+        let mut stats = UsageStats::default();
+        for _ in 1..REPEATED_QUERY_TIP {
+            assert_eq!(stats.next_tip("SELECT * FROM orders;"), None);
+        }
+        let tip = stats.next_tip("select *\n  from orders").unwrap();
+        assert!(tip.contains(".bookmark save"));
+        // Each tip is shown once
+        assert_eq!(stats.next_tip("SELECT * FROM orders;"), None);
+
+        // Feature tips wait for enough use, then suggest commands not used yet
+        stats.commands.insert(".last".to_string(), 3);
+        stats
+            .commands
+            .insert(".tables".to_string(), TIP_INTERVAL - 4);
+        assert_eq!(stats.next_tip(".tables"), None);
+        *stats.commands.get_mut(".tables").unwrap() += 1;
+        let tip = stats.next_tip(".tables").unwrap();
+        assert!(tip.starts_with("`.help COMMAND`"));
+        assert_eq!(stats.next_tip(".tables"), None);
+    }
+}
@@ -0,0 +1,167 @@
+//! # Session Variables
+//!
+//! This module stores named values for a REPL session and substitutes them into
+//! commands, so a query can be written once and re-run with different inputs. Variables
+//! are set with `.set NAME VALUE`, listed with `.vars`, and referenced as `${NAME}` in
+//! SQL and dot-commands.
+//!
+//! ## Features:
+//! - **Plain substitution**: `${NAME}` is replaced with the value as written; quote it in
+//!   SQL (`'${NAME}'`) when it is a string.
+//! - **Unknown names are errors**: A command that refers to an unset variable is not run.
+//! - **Parameterized bookmarks**: The REPL remembers the `${...}` form of the last query,
+//!   so saving it as a bookmark keeps the variables for later runs.
+
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
+
+/// The variables set in a session.
+#[derive(Debug, Default)]
+pub struct Variables {
+    values: BTreeMap<String, String>,
+    /// The last command that used variables, as written and as run.
+    last_template: Option<(String, String)>,
+}
+
+/// The REPL's session variables.
+pub fn session_variables() -> &'static Mutex<Variables> {
+    static VARIABLES: OnceLock<Mutex<Variables>> = OnceLock::new();
+    VARIABLES.get_or_init(|| Mutex::new(Variables::default()))
+}
+
+impl Variables {
+    /// Sets a variable.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Letters, digits, and underscores, not starting with a digit.
+    /// * `value` - The text substituted for `${name}`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` which is `Ok(())` on success, or an `Err` if the name is invalid.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        let valid = name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            anyhow::bail!(
+                "Invalid variable name '{}'. Use letters, digits, and underscores, not starting with a digit",
+                name
+            );
+        }
+        self.values.insert(name.to_string(), value.to_string());
+        Ok(())
+    }
+
+    /// Removes a variable, returning whether it was set.
+    pub fn unset(&mut self, name: &str) -> bool {
+        self.values.remove(name).is_some()
+    }
+
+    /// The value of a variable.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    /// Every variable and its value, sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.values.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Replaces each `${name}` in the text with the variable's value.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A SQL statement or dot-command.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the text with variables substituted, or an `Err` naming the
+    /// first variable that isn't set.
+    pub fn interpolate(&self, text: &str) -> Result<String> {
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find("${") {
+            let Some(len) = rest[start + 2..].find('}') else {
+                break;
+            };
+            let name = &rest[start + 2..start + 2 + len];
+            let Some(value) = self.get(name) else {
+                anyhow::bail!(
+                    "Variable '{}' is not set. Use '.set {} VALUE' to set it",
+                    name,
+                    name
+                );
+            };
+            result.push_str(&rest[..start]);
+            result.push_str(value);
+            rest = &rest[start + 3 + len..];
+        }
+        result.push_str(rest);
+        Ok(result)
+    }
+
+    /// Interpolates a command and, if it used variables, remembers it for `template_for`.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command as the user wrote it.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the command to run.
+    pub fn expand_command(&mut self, command: &str) -> Result<String> {
+        let expanded = self.interpolate(command)?;
+        if expanded != command {
+            self.last_template = Some((command.to_string(), expanded.clone()));
+        }
+        Ok(expanded)
+    }
+
+    /// The command with `${...}` references that expanded to the given text, if it was
+    /// the last one that used variables.
+    pub fn template_for(&self, expanded: &str) -> Option<&str> {
+        match &self.last_template {
+            Some((template, last)) if last.trim() == expanded.trim() => Some(template),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interpolate_variables() {
+        let mut vars = Variables::default();
+        vars.set("tenant", "acme").unwrap();
+        vars.set("min_total", "100").unwrap();
+        assert!(vars.set("2x", "y").is_err());
+        assert!(vars.set("a-b", "y").is_err());
+
+        let sql = "SELECT * FROM orders WHERE tenant = '${tenant}' AND total > ${min_total}";
+        assert_eq!(
+            vars.interpolate(sql).unwrap(),
+            "SELECT * FROM orders WHERE tenant = 'acme' AND total > 100"
+        );
+        assert_eq!(
+            vars.interpolate("SELECT '$1', '${unclosed'").unwrap(),
+            "SELECT '$1', '${unclosed'"
+        );
+        let err = vars.interpolate("SELECT ${missing}").unwrap_err();
+        assert!(err.to_string().contains("'missing' is not set"));
+
+        let expanded = vars.expand_command(sql).unwrap();
+        assert_eq!(vars.template_for(&expanded), Some(sql));
+        assert_eq!(vars.template_for("SELECT 1"), None);
+
+        assert!(vars.unset("tenant"));
+        assert!(!vars.unset("tenant"));
+        assert!(vars.interpolate(sql).is_err());
+    }
+}
@@ -0,0 +1,58 @@
+/*
+ * C interface to vapor-cli.
+ *
+ * Link against libvapor_ffi (built with `cargo build --release -p vapor-ffi`).
+ *
+ * Calls that fail return NULL or -1; vapor_last_error() then describes the failure.
+ * Strings returned by the library are freed with vapor_string_free(). A handle must
+ * only be used by one thread at a time.
+ */
+#ifndef VAPOR_H
+#define VAPOR_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* An open database. */
+typedef struct VaporHandle VaporHandle;
+
+/* Opens a database file, creating it if needed. Returns NULL on error. */
+VaporHandle *vapor_open(const char *path);
+
+/* Closes a database and frees its handle. NULL is ignored. */
+void vapor_close(VaporHandle *handle);
+
+/* Runs SQL that returns no rows (one or more statements). Returns the rows changed by
+ * the last statement, or -1 on error. */
+int64_t vapor_execute(VaporHandle *handle, const char *sql);
+
+/* Runs a query and returns its results as "json", "csv", "table", or "plain" text
+ * (NULL format means "json"). Free the result with vapor_string_free(). Returns NULL
+ * on error. */
+char *vapor_query(VaporHandle *handle, const char *sql, const char *format);
+
+/* Exports a query's results to a file. format is an export format such as "csv",
+ * "json", or "xlsx", or NULL to pick one from the file extension. Returns the rows
+ * written, or -1 on error. */
+int64_t vapor_export(VaporHandle *handle, const char *sql, const char *path,
+                     const char *format);
+
+/* Fills a table with generated test data. config_json is a population config as JSON,
+ * or NULL for the default config. Returns 0 on success or -1 on error. */
+int32_t vapor_populate(VaporHandle *handle, const char *config_json);
+
+/* The error of the last failing call on this thread, or NULL if it succeeded. Valid
+ * until the next call on this thread. */
+const char *vapor_last_error(void);
+
+/* Frees a string returned by vapor_query(). NULL is ignored. */
+void vapor_string_free(char *value);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* VAPOR_H */
@@ -0,0 +1,350 @@
+//! # Vapor FFI
+//!
+//! This crate exposes vapor-cli's higher-level conveniences through a C ABI, so tools
+//! written in C, Python (`ctypes` or `cffi`), Go, and other languages can open a database,
+//! run queries with formatted output, export results, and generate test data without
+//! reimplementing them. The declarations are in `include/vapor.h`.
+//!
+//! ## Features:
+//! - **Opaque handles**: `vapor_open` returns a `VaporHandle` that every other call takes,
+//!   and `vapor_close` frees it.
+//! - **Formatted queries**: `vapor_query` returns results as JSON, CSV, a table, or plain
+//!   columns, rendered the same way as the CLI.
+//! - **Exports and test data**: `vapor_export` writes a query's results to a file in any
+//!   export format, and `vapor_populate` takes a JSON population config.
+//! - **Errors**: Failing calls return `NULL` or `-1`, and `vapor_last_error` describes the
+//!   failure. Panics are caught at the boundary instead of unwinding into the caller.
+//! - **Ownership**: Strings returned by the library are freed with `vapor_string_free`.
+
+use anyhow::{Context, Result};
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+use vapor_cli::{
+    export_query_with_options, populate_database, render_result, Backend, ExportOptions,
+    OutputFormat, PopulationConfig, VaporDB,
+};
+
+/// An open database, created by `vapor_open` and freed by `vapor_close`.
+pub struct VaporHandle {
+    db: VaporDB,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Runs a call, recording its error for `vapor_last_error` and returning `failed` if it
+/// fails or panics.
+fn ffi_call<T>(failed: T, call: impl FnOnce() -> Result<T>) -> T {
+    let result = catch_unwind(AssertUnwindSafe(call)).unwrap_or_else(|panic| {
+        let message = panic
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        Err(anyhow::anyhow!("vapor-ffi panicked: {}", message))
+    });
+    match result {
+        Ok(value) => {
+            set_last_error(None);
+            value
+        }
+        Err(e) => {
+            set_last_error(Some(format!("{:#}", e)));
+            failed
+        }
+    }
+}
+
+fn set_last_error(message: Option<String>) {
+    // Interior NULs can't be represented in a C string
+    let message = message.map(|m| CString::new(m.replace('\0', " ")).unwrap_or_default());
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+}
+
+/// Reads a required string argument.
+///
+/// # Safety
+///
+/// `ptr` must be `NULL` or point to a NUL-terminated string.
+unsafe fn required_str<'a>(ptr: *const c_char, name: &str) -> Result<&'a str> {
+    optional_str(ptr)?.with_context(|| format!("`{}` must not be NULL", name))
+}
+
+/// Reads an optional string argument, where `NULL` means none.
+///
+/// # Safety
+///
+/// `ptr` must be `NULL` or point to a NUL-terminated string.
+unsafe fn optional_str<'a>(ptr: *const c_char) -> Result<Option<&'a str>> {
+    if ptr.is_null() {
+        return Ok(None);
+    }
+    let value = CStr::from_ptr(ptr)
+        .to_str()
+        .context("String arguments must be UTF-8")?;
+    Ok(Some(value))
+}
+
+/// Reads a handle argument.
+///
+/// # Safety
+///
+/// `handle` must be `NULL` or a handle from `vapor_open` that hasn't been closed.
+unsafe fn handle_ref<'a>(handle: *mut VaporHandle) -> Result<&'a mut VaporHandle> {
+    handle.as_mut().context("`handle` must not be NULL")
+}
+
+fn into_c_string(value: String) -> Result<*mut c_char> {
+    Ok(CString::new(value)
+        .context("The result contains a NUL byte")?
+        .into_raw())
+}
+
+/// Opens a database file, creating it if it doesn't exist.
+///
+/// # Safety
+///
+/// `path` must point to a NUL-terminated UTF-8 string.
+///
+/// # Returns
+///
+/// A handle to pass to the other functions, or `NULL` on error.
+#[no_mangle]
+pub unsafe extern "C" fn vapor_open(path: *const c_char) -> *mut VaporHandle {
+    ffi_call(ptr::null_mut(), || {
+        let path = required_str(path, "path")?;
+        let db = VaporDB::open(path)?;
+        Ok(Box::into_raw(Box::new(VaporHandle { db })))
+    })
+}
+
+/// Closes a database and frees its handle. `NULL` is ignored.
+///
+/// # Safety
+///
+/// `handle` must be `NULL` or a handle from `vapor_open` that hasn't been closed.
+#[no_mangle]
+pub unsafe extern "C" fn vapor_close(handle: *mut VaporHandle) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// Runs SQL that doesn't return rows, such as `INSERT` or `CREATE TABLE`. Several
+/// statements separated by semicolons can be run at once.
+///
+/// # Safety
+///
+/// `handle` must be an open handle and `sql` a NUL-terminated UTF-8 string.
+///
+/// # Returns
+///
+/// The number of rows changed by the last statement, or -1 on error.
+#[no_mangle]
+pub unsafe extern "C" fn vapor_execute(handle: *mut VaporHandle, sql: *const c_char) -> i64 {
+    ffi_call(-1, || {
+        let handle = handle_ref(handle)?;
+        let sql = required_str(sql, "sql")?;
+        handle.db.connection.execute_batch(sql)?;
+        Ok(handle.db.connection.changes() as i64)
+    })
+}
+
+/// Runs a query and returns its results as text.
+///
+/// # Safety
+///
+/// `handle` must be an open handle, `sql` a NUL-terminated UTF-8 string, and `format`
+/// `NULL` or a NUL-terminated string.
+///
+/// # Returns
+///
+/// The results in `format` (`json`, `csv`, `table`, or `plain`; `NULL` means `json`),
+/// to be freed with `vapor_string_free`, or `NULL` on error.
+#[no_mangle]
+pub unsafe extern "C" fn vapor_query(
+    handle: *mut VaporHandle,
+    sql: *const c_char,
+    format: *const c_char,
+) -> *mut c_char {
+    ffi_call(ptr::null_mut(), || {
+        let handle = handle_ref(handle)?;
+        let sql = required_str(sql, "sql")?;
+        let format = OutputFormat::parse(optional_str(format)?.unwrap_or("json"))?;
+        let result = handle.db.connection.run(sql, None)?;
+        into_c_string(render_result(&format, &result)?)
+    })
+}
+
+/// Exports the results of a query to a file.
+///
+/// # Safety
+///
+/// `handle` must be an open handle, `sql` and `path` NUL-terminated UTF-8 strings, and
+/// `format` `NULL` or a NUL-terminated string.
+///
+/// # Returns
+///
+/// The number of rows written, or -1 on error. `format` names an export format such as
+/// `csv`, `json`, or `xlsx`; `NULL` picks one from the file extension.
+#[no_mangle]
+pub unsafe extern "C" fn vapor_export(
+    handle: *mut VaporHandle,
+    sql: *const c_char,
+    path: *const c_char,
+    format: *const c_char,
+) -> i64 {
+    ffi_call(-1, || {
+        let handle = handle_ref(handle)?;
+        let sql = required_str(sql, "sql")?;
+        let path = required_str(path, "path")?;
+        let format = optional_str(format)?;
+        // The row count is returned, so nothing is printed
+        let options = ExportOptions {
+            quiet: true,
+            ..ExportOptions::default()
+        };
+        let rows = export_query_with_options(&handle.db.connection, sql, path, format, &options)?;
+        Ok(rows as i64)
+    })
+}
+
+/// Fills a table with generated test data, printing progress to stdout.
+///
+/// # Safety
+///
+/// `handle` must be an open handle and `config_json` `NULL` or a NUL-terminated UTF-8
+/// string.
+///
+/// # Returns
+///
+/// 0 on success or -1 on error. `config_json` is a population config as JSON, such as
+/// `{"table_name": "users", "row_count": 1000, "batch_size": 500, "seed": 42,
+/// "columns": [...]}`; `NULL` uses the default config.
+#[no_mangle]
+pub unsafe extern "C" fn vapor_populate(
+    handle: *mut VaporHandle,
+    config_json: *const c_char,
+) -> i32 {
+    ffi_call(-1, || {
+        let handle = handle_ref(handle)?;
+        let config = optional_str(config_json)?
+            .map(serde_json::from_str::<PopulationConfig>)
+            .transpose()
+            .context("Invalid population config")?;
+        populate_database(&handle.db.db_path, config)?;
+        Ok(0)
+    })
+}
+
+/// Describes the error of the last failing call on this thread.
+///
+/// # Returns
+///
+/// The message, or `NULL` if the last call succeeded. It belongs to the library and
+/// stays valid until the next call on this thread.
+#[no_mangle]
+pub extern "C" fn vapor_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Frees a string returned by the library. `NULL` is ignored.
+///
+/// # Safety
+///
+/// `value` must be `NULL` or a string returned by `vapor_query` that hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn vapor_string_free(value: *mut c_char) {
+    if !value.is_null() {
+        drop(CString::from_raw(value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn c(value: &str) -> CString {
+        CString::new(value).unwrap()
+    }
+
+    fn last_error() -> String {
+        let message = vapor_last_error();
+        assert!(!message.is_null());
+        unsafe { CStr::from_ptr(message) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn test_ffi_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = c(dir.path().join("ffi.db").to_str().unwrap());
+        let csv_path = dir.path().join("out.csv");
+        unsafe {
+            let handle = vapor_open(db_path.as_ptr());
+            assert!(!handle.is_null());
+
+            let sql = c(
+                "CREATE TABLE t (id INTEGER, name TEXT); INSERT INTO t VALUES (1, 'a'), (2, 'b');",
+            );
+            assert_eq!(vapor_execute(handle, sql.as_ptr()), 2);
+            assert!(vapor_last_error().is_null());
+
+            let select = c("SELECT id, name FROM t ORDER BY id");
+            let csv = vapor_query(handle, select.as_ptr(), c("csv").as_ptr());
+            assert_eq!(CStr::from_ptr(csv).to_str().unwrap(), "id,name\n1,a\n2,b\n");
+            vapor_string_free(csv);
+            let json = vapor_query(handle, select.as_ptr(), ptr::null());
+            assert!(CStr::from_ptr(json)
+                .to_str()
+                .unwrap()
+                .contains("\"row_count\": 2"));
+            vapor_string_free(json);
+
+            let path = c(csv_path.to_str().unwrap());
+            assert_eq!(
+                vapor_export(handle, select.as_ptr(), path.as_ptr(), ptr::null()),
+                2
+            );
+            assert!(std::fs::read_to_string(&csv_path)
+                .unwrap()
+                .starts_with("id,name"));
+
+            let config = c(
+                r#"{"table_name": "generated", "row_count": 50, "batch_size": 10, "seed": 7,
+                "columns": [{"name": "id", "data_type": "Integer", "distribution": "Sequential", "nullable": false}]}"#,
+            );
+            assert_eq!(vapor_populate(handle, config.as_ptr()), 0);
+            let count = vapor_query(
+                handle,
+                c("SELECT COUNT(*) AS n FROM generated").as_ptr(),
+                c("plain").as_ptr(),
+            );
+            assert_eq!(CStr::from_ptr(count).to_str().unwrap(), "n\n50\n");
+            vapor_string_free(count);
+
+            // Errors are reported instead of crashing the caller
+            assert!(
+                vapor_query(handle, c("SELECT * FROM missing").as_ptr(), ptr::null()).is_null()
+            );
+            assert!(last_error().contains("Failed to prepare SQL statement"));
+            assert!(vapor_query(handle, select.as_ptr(), c("yaml").as_ptr()).is_null());
+            assert!(last_error().contains("Invalid format 'yaml'"));
+            assert_eq!(vapor_execute(ptr::null_mut(), select.as_ptr()), -1);
+            assert_eq!(last_error(), "`handle` must not be NULL");
+            assert_eq!(vapor_populate(handle, c("{").as_ptr()), -1);
+            assert!(last_error().starts_with("Invalid population config"));
+
+            vapor_close(handle);
+        }
+    }
+}